@@ -137,6 +137,7 @@ TOC = [
                     Page("Key Binding", "config/keys.md"),
                     Page("Mouse Binding", "config/mouse.md"),
                     Page("Colors & Appearance", "config/appearance.md"),
+                    Page("Accessibility", "config/accessibility.md"),
                 ],
             ),
             Page("Scrollback", "scrollback.md"),
@@ -145,6 +146,7 @@ TOC = [
             Page("Hyperlinks", "hyperlinks.md"),
             Page("Shell Integration", "shell-integration.md"),
             Page("iTerm Image Protocol", "imgcat.md"),
+            Page("Recording Sessions", "recording.md"),
             Page("SSH", "ssh.md"),
             Page("Serial Ports & Arduino", "serial.md"),
             Page("Multiplexing", "multiplexing.md"),
@@ -216,6 +218,7 @@ clear and convenient.
 
 """,
                     ),
+                    Page("object: ExecDomain", "config/lua/ExecDomain.md"),
                     Page("object: PaneInformation", "config/lua/PaneInformation.md"),
                     Page("object: TabInformation", "config/lua/TabInformation.md"),
                     Page("object: SshDomain", "config/lua/SshDomain.md"),
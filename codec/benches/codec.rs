@@ -0,0 +1,97 @@
+//! Micro-benchmarks for encoding and decoding mux PDUs.
+//! Run them with `cargo bench -p codec`.
+use codec::{GetLinesResponse, Pdu, WriteToPane};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use termwiz::cell::CellAttributes;
+use termwiz::surface::Line;
+
+/// Output from a busy pane, which is poorly compressible
+fn write_to_pane(size: usize) -> Pdu {
+    let data = (0..size)
+        .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+        .collect();
+    Pdu::WriteToPane(WriteToPane { pane_id: 1, data })
+}
+
+/// A screenful of lines, which is what the client fetches as a
+/// remote pane scrolls
+fn get_lines_response(num_lines: usize) -> Pdu {
+    let attrs = CellAttributes::default();
+    let lines = (0..num_lines)
+        .map(|i| {
+            let text = format!("{:>6}: the quick brown fox jumps over the lazy dog", i);
+            (i as isize, Line::from_text(&text, &attrs))
+        })
+        .collect::<Vec<_>>();
+    Pdu::GetLinesResponse(GetLinesResponse {
+        pane_id: 1,
+        lines: lines.into(),
+    })
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for &size in &[64, 4096, 256 * 1024] {
+        let pdu = write_to_pane(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("write_to_pane", size), &pdu, |b, pdu| {
+            let mut buffer = Vec::with_capacity(2 * size);
+            b.iter(|| {
+                buffer.clear();
+                pdu.encode(&mut buffer, 1).unwrap();
+                black_box(&buffer);
+            })
+        });
+    }
+
+    let pdu = get_lines_response(100);
+    group.bench_function("get_lines_response", |b| {
+        let mut buffer = vec![];
+        b.iter(|| {
+            buffer.clear();
+            pdu.encode(&mut buffer, 1).unwrap();
+            black_box(&buffer);
+        })
+    });
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for &size in &[64, 4096, 256 * 1024] {
+        let mut encoded = vec![];
+        write_to_pane(size).encode(&mut encoded, 1).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(
+            BenchmarkId::new("write_to_pane", size),
+            &encoded,
+            |b, encoded| b.iter(|| black_box(Pdu::decode(encoded.as_slice()).unwrap())),
+        );
+    }
+
+    let mut encoded = vec![];
+    get_lines_response(100).encode(&mut encoded, 1).unwrap();
+    group.bench_function("get_lines_response", |b| {
+        b.iter(|| black_box(Pdu::decode(encoded.as_slice()).unwrap()))
+    });
+
+    // A stream of small PDUs, as sent while typing into a remote pane
+    let mut stream = vec![];
+    for serial in 0..100 {
+        write_to_pane(16).encode(&mut stream, serial).unwrap();
+    }
+    group.throughput(Throughput::Elements(100));
+    group.bench_function("stream_decode", |b| {
+        let mut buffer = Vec::with_capacity(stream.len());
+        b.iter(|| {
+            buffer.extend_from_slice(&stream);
+            while let Some(decoded) = Pdu::stream_decode(&mut buffer).unwrap() {
+                black_box(decoded);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
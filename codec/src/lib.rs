@@ -13,7 +13,9 @@
 
 use anyhow::{bail, Context as _, Error};
 use leb128;
+use mux::client::ClientId;
 use mux::domain::DomainId;
+use mux::memory::MemoryStat;
 use mux::pane::PaneId;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::{PaneNode, SerdeUrl, SplitDirection, TabId};
@@ -24,14 +26,14 @@ use serde::{Deserialize, Serialize};
 use smol::io::AsyncWriteExt;
 use smol::prelude::*;
 use std::convert::TryInto;
-use std::io::Cursor;
+use std::io::IoSlice;
 use std::ops::Range;
 use std::sync::Arc;
 use termwiz::hyperlink::Hyperlink;
 use termwiz::surface::{Line, SequenceNo};
 use varbincode;
 use wezterm_term::color::ColorPalette;
-use wezterm_term::{Alert, ClipboardSelection, StableRowIndex};
+use wezterm_term::{Alert, ClipboardSelection, CommandResult, StableRowIndex};
 
 /// Returns the encoded length of the leb128 representation of value
 fn encoded_length(value: u64) -> usize {
@@ -50,36 +52,161 @@ fn encoded_length(value: u64) -> usize {
 
 const COMPRESSED_MASK: u64 = 1 << 63;
 
-fn encode_raw_as_vec(
+/// The largest encoded frame header: three leb128 encoded u64 values
+const MAX_HEADER_LEN: usize = 30;
+
+/// Payloads up to this size are copied in after the header so that the
+/// frame goes out in a single write; since we run with nodelay enabled,
+/// it is desirable for a small frame to be a single packet.  Larger
+/// payloads are written along with the header using vectored writes,
+/// which avoids copying them.
+const COALESCE_WRITE_THRESH: usize = 16 * 1024;
+
+/// Decode buffers that grew beyond this size are released after use
+/// rather than being kept around for the next frame
+const MAX_RETAINED_DECODE_BUFFER: usize = 1024 * 1024;
+
+/// The header of a frame.  See encode_raw() for the format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FrameHeader {
     ident: u64,
     serial: u64,
-    data: &[u8],
+    data_len: usize,
     is_compressed: bool,
-) -> anyhow::Result<Vec<u8>> {
-    let len = data.len() + encoded_length(ident) + encoded_length(serial);
-    let masked_len = if is_compressed {
-        (len as u64) | COMPRESSED_MASK
-    } else {
-        len as u64
-    };
+}
+
+impl FrameHeader {
+    /// Validates the raw values read from a frame header
+    fn from_raw(len: u64, serial: u64, ident: u64) -> anyhow::Result<Self> {
+        let (len, is_compressed) = if (len & COMPRESSED_MASK) != 0 {
+            (len & !COMPRESSED_MASK, true)
+        } else {
+            (len, false)
+        };
+        let data_len =
+            match (len as usize).overflowing_sub(encoded_length(ident) + encoded_length(serial)) {
+                (_, true) => {
+                    anyhow::bail!(
+                        "sizes don't make sense: len:{} serial:{} (enc={}) ident:{} (enc={})",
+                        len,
+                        serial,
+                        encoded_length(serial),
+                        ident,
+                        encoded_length(ident)
+                    );
+                }
+                (data_len, false) => data_len,
+            };
 
-    // Double-buffer the data; since we run with nodelay enabled, it is
-    // desirable for the write to be a single packet (or at least, for
-    // the header portion to go out in a single packet)
-    let mut buffer = Vec::with_capacity(len + encoded_length(masked_len));
+        if is_compressed {
+            metrics::histogram!("pdu.decode.compressed.size", data_len as f64);
+        } else {
+            metrics::histogram!("pdu.decode.size", data_len as f64);
+        }
 
-    leb128::write::unsigned(&mut buffer, masked_len).context("writing pdu len")?;
-    leb128::write::unsigned(&mut buffer, serial).context("writing pdu serial")?;
-    leb128::write::unsigned(&mut buffer, ident).context("writing pdu ident")?;
-    buffer.extend_from_slice(data);
+        Ok(Self {
+            ident,
+            serial,
+            data_len,
+            is_compressed,
+        })
+    }
 
+    /// Encodes the header into a fixed size buffer, returning the
+    /// buffer and the number of bytes of it that were used
+    fn encode(&self) -> anyhow::Result<([u8; MAX_HEADER_LEN], usize)> {
+        let len = self.data_len + encoded_length(self.ident) + encoded_length(self.serial);
+        let masked_len = if self.is_compressed {
+            (len as u64) | COMPRESSED_MASK
+        } else {
+            len as u64
+        };
+
+        let mut buffer = [0u8; MAX_HEADER_LEN];
+        let mut w = &mut buffer[..];
+        leb128::write::unsigned(&mut w, masked_len).context("writing pdu len")?;
+        leb128::write::unsigned(&mut w, self.serial).context("writing pdu serial")?;
+        leb128::write::unsigned(&mut w, self.ident).context("writing pdu ident")?;
+        let used = MAX_HEADER_LEN - w.len();
+        Ok((buffer, used))
+    }
+
+    /// Decodes a header from the start of `buf`, returning it and the
+    /// number of bytes that it occupied, or None if `buf` doesn't yet
+    /// hold a complete header
+    fn decode_slice(buf: &[u8]) -> anyhow::Result<Option<(Self, usize)>> {
+        let mut r = buf;
+        let mut values = [0u64; 3];
+        for value in &mut values {
+            *value = match leb128::read::unsigned(&mut r) {
+                Ok(value) => value,
+                Err(leb128::read::Error::IoError(_)) => return Ok(None),
+                Err(err) => return Err(anyhow::Error::new(err).context("reading leb128")),
+            };
+        }
+        let header = Self::from_raw(values[0], values[1], values[2])?;
+        Ok(Some((header, buf.len() - r.len())))
+    }
+}
+
+fn record_encoded_size(size: usize, is_compressed: bool) {
     if is_compressed {
-        metrics::histogram!("pdu.encode.compressed.size", buffer.len() as f64);
+        metrics::histogram!("pdu.encode.compressed.size", size as f64);
     } else {
-        metrics::histogram!("pdu.encode.size", buffer.len() as f64);
+        metrics::histogram!("pdu.encode.size", size as f64);
     }
+}
 
-    Ok(buffer)
+/// Writes `header` followed by `data`, using vectored writes so that
+/// `data` doesn't need to be copied in after the header
+fn write_all_vectored<W: std::io::Write>(
+    w: &mut W,
+    mut header: &[u8],
+    mut data: &[u8],
+) -> std::io::Result<()> {
+    while !header.is_empty() || !data.is_empty() {
+        let result = if header.is_empty() {
+            w.write(data)
+        } else {
+            w.write_vectored(&[IoSlice::new(header), IoSlice::new(data)])
+        };
+        let n = match result {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(n) => n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        let from_header = n.min(header.len());
+        header = &header[from_header..];
+        data = &data[n - from_header..];
+    }
+    Ok(())
+}
+
+/// The async equivalent of write_all_vectored()
+async fn write_all_vectored_async<W: Unpin + AsyncWriteExt>(
+    w: &mut W,
+    mut header: &[u8],
+    mut data: &[u8],
+) -> std::io::Result<()> {
+    while !header.is_empty() || !data.is_empty() {
+        let result = if header.is_empty() {
+            w.write(data).await
+        } else {
+            w.write_vectored(&[IoSlice::new(header), IoSlice::new(data)])
+                .await
+        };
+        let n = match result {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(n) => n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        let from_header = n.min(header.len());
+        header = &header[from_header..];
+        data = &data[n - from_header..];
+    }
+    Ok(())
 }
 
 /// Encode a frame.  If the data is compressed, the high bit of the length
@@ -95,9 +222,26 @@ fn encode_raw<W: std::io::Write>(
     is_compressed: bool,
     mut w: W,
 ) -> anyhow::Result<usize> {
-    let buffer = encode_raw_as_vec(ident, serial, data, is_compressed)?;
-    w.write_all(&buffer).context("writing pdu data buffer")?;
-    Ok(buffer.len())
+    let (header, header_len) = FrameHeader {
+        ident,
+        serial,
+        data_len: data.len(),
+        is_compressed,
+    }
+    .encode()?;
+    let header = &header[..header_len];
+    let size = header.len() + data.len();
+    record_encoded_size(size, is_compressed);
+
+    if data.len() <= COALESCE_WRITE_THRESH {
+        let mut buffer = Vec::with_capacity(size);
+        buffer.extend_from_slice(header);
+        buffer.extend_from_slice(data);
+        w.write_all(&buffer).context("writing pdu data buffer")?;
+    } else {
+        write_all_vectored(&mut w, header, data).context("writing pdu data buffer")?;
+    }
+    Ok(size)
 }
 
 async fn encode_raw_async<W: Unpin + AsyncWriteExt>(
@@ -107,11 +251,30 @@ async fn encode_raw_async<W: Unpin + AsyncWriteExt>(
     is_compressed: bool,
     w: &mut W,
 ) -> anyhow::Result<usize> {
-    let buffer = encode_raw_as_vec(ident, serial, data, is_compressed)?;
-    w.write_all(&buffer)
-        .await
-        .context("writing pdu data buffer")?;
-    Ok(buffer.len())
+    let (header, header_len) = FrameHeader {
+        ident,
+        serial,
+        data_len: data.len(),
+        is_compressed,
+    }
+    .encode()?;
+    let header = &header[..header_len];
+    let size = header.len() + data.len();
+    record_encoded_size(size, is_compressed);
+
+    if data.len() <= COALESCE_WRITE_THRESH {
+        let mut buffer = Vec::with_capacity(size);
+        buffer.extend_from_slice(header);
+        buffer.extend_from_slice(data);
+        w.write_all(&buffer)
+            .await
+            .context("writing pdu data buffer")?;
+    } else {
+        write_all_vectored_async(w, header, data)
+            .await
+            .context("writing pdu data buffer")?;
+    }
+    Ok(size)
 }
 
 /// Read a single leb128 encoded value from the stream
@@ -119,10 +282,9 @@ async fn read_u64_async<R>(r: &mut R) -> anyhow::Result<u64>
 where
     R: Unpin + AsyncRead + std::fmt::Debug,
 {
-    let mut buf = vec![];
-    loop {
-        let mut byte = [0u8];
-        let nread = r.read(&mut byte).await?;
+    let mut buf = [0u8; 10];
+    for len in 1..=buf.len() {
+        let nread = r.read(&mut buf[len - 1..len]).await?;
         if nread == 0 {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::UnexpectedEof,
@@ -130,9 +292,8 @@ where
             )
             .into());
         }
-        buf.push(byte[0]);
 
-        match leb128::read::unsigned(&mut buf.as_slice()) {
+        match leb128::read::unsigned(&mut &buf[..len]) {
             Ok(n) => {
                 return Ok(n);
             }
@@ -140,6 +301,7 @@ where
             Err(leb128::read::Error::Overflow) => anyhow::bail!("leb128 is too large"),
         }
     }
+    anyhow::bail!("leb128 is too large")
 }
 
 /// Read a single leb128 encoded value from the stream
@@ -154,106 +316,56 @@ fn read_u64<R: std::io::Read>(mut r: R) -> anyhow::Result<u64> {
 
 #[derive(Debug)]
 struct Decoded {
-    ident: u64,
-    serial: u64,
+    header: FrameHeader,
     data: Vec<u8>,
-    is_compressed: bool,
 }
 
-/// Decode a frame.
+/// Decode a frame header from the stream.
 /// See encode_raw() for the frame format.
-async fn decode_raw_async<R: Unpin + AsyncRead + std::fmt::Debug>(
+async fn decode_header_async<R: Unpin + AsyncRead + std::fmt::Debug>(
     r: &mut R,
-) -> anyhow::Result<Decoded> {
+) -> anyhow::Result<FrameHeader> {
     let len = read_u64_async(r).await.context("reading PDU length")?;
-    let (len, is_compressed) = if (len & COMPRESSED_MASK) != 0 {
-        (len & !COMPRESSED_MASK, true)
-    } else {
-        (len, false)
-    };
     let serial = read_u64_async(r).await.context("reading PDU serial")?;
     let ident = read_u64_async(r).await.context("reading PDU ident")?;
-    let data_len =
-        match (len as usize).overflowing_sub(encoded_length(ident) + encoded_length(serial)) {
-            (_, true) => {
-                anyhow::bail!(
-                    "sizes don't make sense: len:{} serial:{} (enc={}) ident:{} (enc={})",
-                    len,
-                    serial,
-                    encoded_length(serial),
-                    ident,
-                    encoded_length(ident)
-                );
-            }
-            (data_len, false) => data_len,
-        };
-
-    if is_compressed {
-        metrics::histogram!("pdu.decode.compressed.size", data_len as f64);
-    } else {
-        metrics::histogram!("pdu.decode.size", data_len as f64);
-    }
+    FrameHeader::from_raw(len, serial, ident)
+}
 
-    let mut data = vec![0u8; data_len];
-    r.read_exact(&mut data).await.with_context(|| {
+/// Decode a frame, reading its data into `data`, whose allocation
+/// is reused from one frame to the next.
+/// See encode_raw() for the frame format.
+async fn decode_raw_async<R: Unpin + AsyncRead + std::fmt::Debug>(
+    r: &mut R,
+    data: &mut Vec<u8>,
+) -> anyhow::Result<FrameHeader> {
+    let header = decode_header_async(r).await?;
+    data.clear();
+    data.resize(header.data_len, 0);
+    r.read_exact(data).await.with_context(|| {
         format!(
-            "reading {} bytes of data for PDU of length {} with serial={} ident={}",
-            data_len, len, serial, ident
+            "reading {} bytes of data for PDU with serial={} ident={}",
+            header.data_len, header.serial, header.ident
         )
     })?;
-    Ok(Decoded {
-        ident,
-        serial,
-        data,
-        is_compressed,
-    })
+    Ok(header)
 }
 
 /// Decode a frame.
 /// See encode_raw() for the frame format.
 fn decode_raw<R: std::io::Read>(mut r: R) -> anyhow::Result<Decoded> {
     let len = read_u64(r.by_ref()).context("reading PDU length")?;
-    let (len, is_compressed) = if (len & COMPRESSED_MASK) != 0 {
-        (len & !COMPRESSED_MASK, true)
-    } else {
-        (len, false)
-    };
     let serial = read_u64(r.by_ref()).context("reading PDU serial")?;
     let ident = read_u64(r.by_ref()).context("reading PDU ident")?;
-    let data_len =
-        match (len as usize).overflowing_sub(encoded_length(ident) + encoded_length(serial)) {
-            (_, true) => {
-                anyhow::bail!(
-                    "sizes don't make sense: len:{} serial:{} (enc={}) ident:{} (enc={})",
-                    len,
-                    serial,
-                    encoded_length(serial),
-                    ident,
-                    encoded_length(ident)
-                );
-            }
-            (data_len, false) => data_len,
-        };
+    let header = FrameHeader::from_raw(len, serial, ident)?;
 
-    if is_compressed {
-        metrics::histogram!("pdu.decode.compressed.size", data_len as f64);
-    } else {
-        metrics::histogram!("pdu.decode.size", data_len as f64);
-    }
-
-    let mut data = vec![0u8; data_len];
+    let mut data = vec![0u8; header.data_len];
     r.read_exact(&mut data).with_context(|| {
         format!(
-            "reading {} bytes of data for PDU of length {} with serial={} ident={}",
-            data_len, len, serial, ident
+            "reading {} bytes of data for PDU with serial={} ident={}",
+            header.data_len, header.serial, header.ident
         )
     })?;
-    Ok(Decoded {
-        ident,
-        serial,
-        data,
-        is_compressed,
-    })
+    Ok(Decoded { header, data })
 }
 
 #[derive(Debug, PartialEq)]
@@ -273,13 +385,9 @@ fn serialize<T: serde::Serialize>(t: &T) -> Result<(Vec<u8>, bool), Error> {
     if uncompressed.len() <= COMPRESS_THRESH {
         return Ok((uncompressed, false));
     }
-    // It's a little heavy; let's try compressing it
-    let mut compressed = Vec::new();
-    let mut compress = zstd::Encoder::new(&mut compressed, zstd::DEFAULT_COMPRESSION_LEVEL)?;
-    let mut encode = varbincode::Serializer::new(&mut compress);
-    t.serialize(&mut encode)?;
-    drop(encode);
-    compress.finish()?;
+    // It's a little heavy; let's try compressing it.  Compress the
+    // bytes that we already have rather than serializing again.
+    let compressed = zstd::encode_all(uncompressed.as_slice(), zstd::DEFAULT_COMPRESSION_LEVEL)?;
 
     log::debug!(
         "serialized+compress len {} vs {}",
@@ -319,6 +427,15 @@ macro_rules! pdu {
         }
 
         impl Pdu {
+            pub fn pdu_name(&self) -> &'static str {
+                match self {
+                    Pdu::Invalid{..} => "Invalid",
+                    $(
+                        Pdu::$name(_) => stringify!($name),
+                    )*
+                }
+            }
+
             pub fn encode<W: std::io::Write>(&self, w: W, serial: u64) -> Result<(), Error> {
                 match self {
                     Pdu::Invalid{..} => bail!("attempted to serialize Pdu::Invalid"),
@@ -347,52 +464,55 @@ macro_rules! pdu {
                 }
             }
 
-            pub fn decode<R: std::io::Read>(r: R) -> Result<DecodedPdu, Error> {
-                let decoded = decode_raw(r).context("decoding a PDU")?;
-                match decoded.ident {
+            /// Deserializes the data of a frame with the given header
+            fn decode_data(header: &FrameHeader, data: &[u8]) -> Result<DecodedPdu, Error> {
+                match header.ident {
                     $(
                         $vers => {
-                            metrics::histogram!("pdu.size", decoded.data.len() as f64, "pdu" => stringify!($name));
+                            metrics::histogram!("pdu.size", data.len() as f64, "pdu" => stringify!($name));
                             Ok(DecodedPdu {
-                                serial: decoded.serial,
-                                pdu: Pdu::$name(deserialize(decoded.data.as_slice(), decoded.is_compressed)?)
+                                serial: header.serial,
+                                pdu: Pdu::$name(deserialize(data, header.is_compressed)?)
                             })
                         }
                     ,)*
                     _ => {
-                        metrics::histogram!("pdu.size", decoded.data.len() as f64, "pdu" => "??");
+                        metrics::histogram!("pdu.size", data.len() as f64, "pdu" => "??");
                         Ok(DecodedPdu {
-                            serial: decoded.serial,
-                            pdu: Pdu::Invalid{ident:decoded.ident}
+                            serial: header.serial,
+                            pdu: Pdu::Invalid{ident:header.ident}
                         })
                     }
                 }
             }
 
+            pub fn decode<R: std::io::Read>(r: R) -> Result<DecodedPdu, Error> {
+                let decoded = decode_raw(r).context("decoding a PDU")?;
+                Self::decode_data(&decoded.header, &decoded.data)
+            }
+
             pub async fn decode_async<R>(r: &mut R) -> Result<DecodedPdu, Error>
                 where R: std::marker::Unpin,
                       R: AsyncRead,
                       R: std::fmt::Debug
             {
-                let decoded = decode_raw_async(r).await.context("decoding a PDU")?;
-                match decoded.ident {
-                    $(
-                        $vers => {
-                            metrics::histogram!("pdu.size", decoded.data.len() as f64, "pdu" => stringify!($name));
-                            Ok(DecodedPdu {
-                                serial: decoded.serial,
-                                pdu: Pdu::$name(deserialize(decoded.data.as_slice(), decoded.is_compressed)?)
-                            })
-                        }
-                    ,)*
-                    _ => {
-                        metrics::histogram!("pdu.size", decoded.data.len() as f64, "pdu" => "??");
-                        Ok(DecodedPdu {
-                            serial: decoded.serial,
-                            pdu: Pdu::Invalid{ident:decoded.ident}
-                        })
-                    }
+                Self::decode_async_with_buffer(r, &mut Vec::new()).await
+            }
+
+            /// Like decode_async(), but reads the data of the frame into
+            /// `buffer`, so that a connection can reuse one allocation
+            /// for all of the frames that it receives
+            pub async fn decode_async_with_buffer<R>(r: &mut R, buffer: &mut Vec<u8>) -> Result<DecodedPdu, Error>
+                where R: std::marker::Unpin,
+                      R: AsyncRead,
+                      R: std::fmt::Debug
+            {
+                let header = decode_raw_async(r, buffer).await.context("decoding a PDU")?;
+                let result = Self::decode_data(&header, buffer);
+                if buffer.capacity() > MAX_RETAINED_DECODE_BUFFER {
+                    *buffer = Vec::new();
                 }
+                result
             }
         }
     }
@@ -401,7 +521,7 @@ macro_rules! pdu {
 /// The overall version of the codec.
 /// This must be bumped when backwards incompatible changes
 /// are made to the types and protocol.
-pub const CODEC_VERSION: usize = 9;
+pub const CODEC_VERSION: usize = 24;
 
 // Defines the Pdu enum.
 // Each struct has an explicit identifying number.
@@ -440,42 +560,49 @@ pdu! {
     PaneRemoved: 37,
     SetPalette: 38,
     NotifyAlert: 39,
+    SetClientId: 40,
+    SetFocusedPane: 41,
+    SetActiveWorkspace: 42,
+    GetFileInfo: 43,
+    FileInfoResponse: 44,
+    ReadFileChunk: 45,
+    FileChunkResponse: 46,
+    WriteFileChunk: 47,
+    SetLogLevel: 48,
+    MovePane: 49,
+    MovePaneResponse: 50,
+    ListWorkspaces: 51,
+    ListWorkspacesResponse: 52,
+    RenameWorkspace: 53,
+    KillWorkspace: 54,
+    SetWorkspaceOrder: 55,
+    ShowWindows: 56,
+    GetFocusedWindow: 57,
+    GetFocusedWindowResponse: 58,
+    GetMemoryStats: 59,
+    GetMemoryStatsResponse: 60,
+    SetPaneAmbiguousWidth: 61,
 }
 
 impl Pdu {
+    /// Decodes a PDU from the start of `buffer` and removes it from
+    /// the buffer.  Returns None if the buffer doesn't yet hold a
+    /// complete frame.  The data is deserialized in place rather than
+    /// being copied out of the buffer first.
     pub fn stream_decode(buffer: &mut Vec<u8>) -> anyhow::Result<Option<DecodedPdu>> {
-        let mut cursor = Cursor::new(buffer.as_slice());
-        match Self::decode(&mut cursor) {
-            Ok(decoded) => {
-                let consumed = cursor.position() as usize;
-                let remain = buffer.len() - consumed;
-                // Remove `consumed` bytes from the start of the vec.
-                // This is safe because the vec is just bytes and we are
-                // constrained the offsets accordingly.
-                unsafe {
-                    std::ptr::copy_nonoverlapping(
-                        buffer.as_ptr().add(consumed),
-                        buffer.as_mut_ptr(),
-                        remain,
-                    );
-                }
-                buffer.truncate(remain);
-                Ok(Some(decoded))
-            }
-            Err(err) => {
-                if let Some(ioerr) = err.root_cause().downcast_ref::<std::io::Error>() {
-                    match ioerr.kind() {
-                        std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::WouldBlock => {
-                            return Ok(None);
-                        }
-                        _ => {}
-                    }
-                } else {
-                    log::error!("not an ioerror in stream_decode: {:?}", err);
-                }
-                Err(err)
-            }
+        let (header, header_len) =
+            match FrameHeader::decode_slice(buffer).context("decoding a PDU")? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+        let end = header_len + header.data_len;
+        if buffer.len() < end {
+            return Ok(None);
         }
+        let decoded =
+            Self::decode_data(&header, &buffer[header_len..end]).context("decoding a PDU")?;
+        buffer.drain(..end);
+        Ok(Some(decoded))
     }
 
     pub fn try_read_and_decode<R: std::io::Read>(
@@ -573,6 +700,8 @@ pub struct Spawn {
     pub command: Option<CommandBuilder>,
     pub command_dir: Option<String>,
     pub size: PtySize,
+    /// The character encoding used by the command
+    pub encoding: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -582,6 +711,8 @@ pub struct SplitPane {
     pub command: Option<CommandBuilder>,
     pub command_dir: Option<String>,
     pub domain: config::keyassignment::SpawnTabDomain,
+    /// The character encoding used by the command
+    pub encoding: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -592,6 +723,10 @@ pub struct SpawnV2 {
     pub command: Option<CommandBuilder>,
     pub command_dir: Option<String>,
     pub size: PtySize,
+    /// The character encoding used by the command
+    pub encoding: Option<String>,
+    /// The name of the profile that the gui should apply to the new tab
+    pub profile: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -618,6 +753,13 @@ pub struct WriteToPane {
     pub data: Vec<u8>,
 }
 
+/// Adjusts the log level filters of the server.
+/// `filters` uses the same `module=level` syntax as `WEZTERM_LOG`.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetLogLevel {
+    pub filters: String,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SendPaste {
     pub pane_id: PaneId,
@@ -690,6 +832,132 @@ pub struct NotifyAlert {
     pub alert: Alert,
 }
 
+/// Sent by a client immediately after connecting to identify
+/// itself and to select the workspace that it wants to view.
+/// Subsequent ListPanes requests on that connection are limited
+/// to the windows in that workspace.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetClientId {
+    pub client_id: ClientId,
+    pub workspace: String,
+    /// If true, the server will refuse requests from this
+    /// client that would send input to or otherwise change
+    /// the state of panes
+    pub read_only: bool,
+}
+
+/// Records which pane the client considers to be focused.
+/// This is tracked per-client so that multiple clients can
+/// focus different panes without fighting each other.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetFocusedPane {
+    pub pane_id: PaneId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetActiveWorkspace {
+    pub workspace: String,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListWorkspaces {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListWorkspacesResponse {
+    /// The workspaces that the client may view, in their preferred order
+    pub workspaces: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct RenameWorkspace {
+    pub old: String,
+    pub new: String,
+}
+
+/// Closes all of the windows in a workspace.  Unless `force` is
+/// true, the request fails if any of its panes are running
+/// programs that would ordinarily prompt before being closed.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct KillWorkspace {
+    pub workspace: String,
+    pub force: bool,
+}
+
+/// Sets the preferred order in which workspaces are listed
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetWorkspaceOrder {
+    pub workspaces: Vec<String>,
+}
+
+/// Asks the gui that hosts the mux to show its windows, including
+/// those whose panes were kept running in the background after
+/// their window was closed
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ShowWindows {}
+
+/// Requests the window that the gui that hosts the mux most
+/// recently focused
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetFocusedWindow {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetFocusedWindowResponse {
+    pub window_id: Option<WindowId>,
+}
+
+/// Requests the approximate memory used by the subsystems of the
+/// process that hosts the mux
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetMemoryStats {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetMemoryStatsResponse {
+    pub stats: Vec<MemoryStat>,
+}
+
+/// Requests information about a file on the host where `pane_id`
+/// is running.  Relative paths are resolved against the current
+/// working directory of that pane.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetFileInfo {
+    pub pane_id: PaneId,
+    pub path: String,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct FileInfoResponse {
+    /// The size of the file, or None if it doesn't exist
+    pub size: Option<u64>,
+}
+
+/// Reads up to `len` bytes starting at `offset` from a file on the
+/// host where `pane_id` is running
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ReadFileChunk {
+    pub pane_id: PaneId,
+    pub path: String,
+    pub offset: u64,
+    pub len: u32,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct FileChunkResponse {
+    pub data: Vec<u8>,
+    pub total_size: u64,
+}
+
+/// Writes `data` at `offset` into a file on the host where `pane_id`
+/// is running.  An `offset` of 0 creates or truncates the file;
+/// any other offset must not be beyond the current end of the file,
+/// which allows an interrupted transfer to be resumed.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct WriteFileChunk {
+    pub pane_id: PaneId,
+    pub path: String,
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct Resize {
     pub containing_tab_id: TabId,
@@ -704,6 +972,37 @@ pub struct SetPaneZoomed {
     pub zoomed: bool,
 }
 
+/// Overrides `treat_east_asian_ambiguous_width_as_wide` for a pane,
+/// as configured for the domain through which a client sees it.
+/// None restores the setting from the configuration of the server.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetPaneAmbiguousWidth {
+    pub pane_id: PaneId,
+    pub ambiguous_are_wide: Option<bool>,
+}
+
+/// Where a pane should be moved to
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub enum MovePaneTarget {
+    /// A new window in the named workspace, or in the workspace that
+    /// the client is viewing if None
+    NewWindow { workspace: Option<String> },
+    /// A new tab in an existing window
+    Window(WindowId),
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MovePane {
+    pub pane_id: PaneId,
+    pub target: MovePaneTarget,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MovePaneResponse {
+    pub tab_id: TabId,
+    pub window_id: WindowId,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct GetPaneRenderChanges {
     pub pane_id: PaneId,
@@ -724,6 +1023,8 @@ pub struct GetPaneRenderChangesResponse {
     pub dirty_lines: Vec<Range<StableRowIndex>>,
     pub title: String,
     pub working_dir: Option<SerdeUrl>,
+    /// The outcomes of the commands whose prompts are in the scrollback
+    pub command_results: Vec<CommandResult>,
     /// Lines that the server thought we'd almost certainly
     /// want to fetch as soon as we received this response
     pub bonus_lines: SerializedLines,
@@ -890,6 +1191,7 @@ pub struct SearchScrollbackResponse {
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::io::Cursor;
 
     #[test]
     fn test_frame() {
@@ -897,8 +1199,8 @@ mod test {
         encode_raw(0x81, 0x42, b"hello", false, &mut encoded).unwrap();
         assert_eq!(&encoded, b"\x08\x42\x81\x01hello");
         let decoded = decode_raw(encoded.as_slice()).unwrap();
-        assert_eq!(decoded.ident, 0x81);
-        assert_eq!(decoded.serial, 0x42);
+        assert_eq!(decoded.header.ident, 0x81);
+        assert_eq!(decoded.header.serial, 0x42);
         assert_eq!(decoded.data, b"hello");
     }
 
@@ -911,13 +1213,43 @@ mod test {
             let mut encoded = Vec::new();
             encode_raw(0x42, serial, payload.as_slice(), false, &mut encoded).unwrap();
             let decoded = decode_raw(encoded.as_slice()).unwrap();
-            assert_eq!(decoded.ident, 0x42);
-            assert_eq!(decoded.serial, serial);
+            assert_eq!(decoded.header.ident, 0x42);
+            assert_eq!(decoded.header.serial, serial);
             assert_eq!(decoded.data, payload);
             serial += 1;
         }
     }
 
+    /// A writer that accepts only a few bytes at a time
+    struct TrickleWriter(Vec<u8>);
+
+    impl std::io::Write for TrickleWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(7);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_vectored_frame() {
+        let payload = vec![b'a'; COALESCE_WRITE_THRESH * 2];
+        let mut expect = Vec::new();
+        encode_raw(0x42, 1, &payload, false, &mut expect).unwrap();
+
+        let mut trickle = TrickleWriter(vec![]);
+        encode_raw(0x42, 1, &payload, false, &mut trickle).unwrap();
+        assert_eq!(trickle.0, expect);
+
+        let decoded = decode_raw(trickle.0.as_slice()).unwrap();
+        assert_eq!(decoded.header.ident, 0x42);
+        assert_eq!(decoded.data, payload);
+    }
+
     #[test]
     fn test_pdu_ping() {
         let mut encoded = Vec::new();
@@ -963,6 +1295,40 @@ mod test {
         );
     }
 
+    #[test]
+    fn stream_decode_partial() {
+        let pdu = Pdu::WriteToPane(WriteToPane {
+            pane_id: 1,
+            data: vec![b'x'; 100],
+        });
+        let mut encoded = Vec::new();
+        pdu.encode(&mut encoded, 3).unwrap();
+        Pdu::Ping(Ping {}).encode(&mut encoded, 4).unwrap();
+
+        // Nothing is decoded until the whole frame has arrived
+        let (first, second) = encoded.split_at(encoded.len() - 3);
+        let mut buffer = Vec::new();
+        for (i, b) in first.iter().enumerate() {
+            assert_eq!(Pdu::stream_decode(&mut buffer).unwrap(), None, "at {}", i);
+            buffer.push(*b);
+        }
+        assert_eq!(
+            Pdu::stream_decode(&mut buffer).unwrap(),
+            Some(DecodedPdu { serial: 3, pdu })
+        );
+        assert!(buffer.is_empty());
+
+        buffer.extend_from_slice(second);
+        assert_eq!(
+            Pdu::stream_decode(&mut buffer).unwrap(),
+            Some(DecodedPdu {
+                serial: 4,
+                pdu: Pdu::Ping(Ping {})
+            })
+        );
+        assert!(buffer.is_empty());
+    }
+
     #[test]
     fn test_pdu_ping_base91() {
         let mut encoded = Vec::new();
@@ -995,6 +1361,68 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_pdu_set_active_workspace() {
+        let mut encoded = Vec::new();
+        let pdu = Pdu::SetActiveWorkspace(SetActiveWorkspace {
+            workspace: "coding".to_string(),
+        });
+        pdu.encode(&mut encoded, 0x43).unwrap();
+        assert_eq!(
+            DecodedPdu {
+                serial: 0x43,
+                pdu: Pdu::SetActiveWorkspace(SetActiveWorkspace {
+                    workspace: "coding".to_string(),
+                })
+            },
+            Pdu::decode(encoded.as_slice()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pdu_rename_workspace() {
+        let mut encoded = Vec::new();
+        let pdu = Pdu::RenameWorkspace(RenameWorkspace {
+            old: "default".to_string(),
+            new: "coding".to_string(),
+        });
+        pdu.encode(&mut encoded, 0x45).unwrap();
+        assert_eq!(
+            DecodedPdu {
+                serial: 0x45,
+                pdu: Pdu::RenameWorkspace(RenameWorkspace {
+                    old: "default".to_string(),
+                    new: "coding".to_string(),
+                })
+            },
+            Pdu::decode(encoded.as_slice()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pdu_write_file_chunk() {
+        let mut encoded = Vec::new();
+        let pdu = Pdu::WriteFileChunk(WriteFileChunk {
+            pane_id: 3,
+            path: "/tmp/foo".to_string(),
+            offset: 1024,
+            data: b"hello".to_vec(),
+        });
+        pdu.encode(&mut encoded, 0x44).unwrap();
+        assert_eq!(
+            DecodedPdu {
+                serial: 0x44,
+                pdu: Pdu::WriteFileChunk(WriteFileChunk {
+                    pane_id: 3,
+                    path: "/tmp/foo".to_string(),
+                    offset: 1024,
+                    data: b"hello".to_vec(),
+                })
+            },
+            Pdu::decode(encoded.as_slice()).unwrap()
+        );
+    }
+
     #[test]
     fn test_bogus_pdu() {
         let mut encoded = Vec::new();
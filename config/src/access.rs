@@ -0,0 +1,104 @@
+use crate::*;
+
+/// The kinds of operation that a mux client may be granted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum MuxPermission {
+    /// Observe the panes: list them, retrieve their content and search
+    /// their scrollback
+    View,
+    /// Send keyboard, mouse and paste input; resize and zoom panes
+    Input,
+    /// Spawn new windows, tabs and split panes
+    Spawn,
+    /// Kill panes
+    Kill,
+}
+impl_lua_conversion!(MuxPermission);
+
+/// Grants a set of permissions to a named user.
+/// The user is identified by their unix user name when connecting
+/// via a unix domain socket, or by the common name in their client
+/// certificate when connecting via TLS.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct MuxAccessRule {
+    /// The user to which this rule applies, or `*` to match
+    /// any authenticated user
+    pub user: String,
+
+    /// If non-empty, the rule only applies to windows that belong
+    /// to one of these workspaces
+    #[serde(default)]
+    pub workspaces: Vec<String>,
+
+    /// If non-empty, the rule only applies to panes that belong
+    /// to one of these domains (as named on the server)
+    #[serde(default)]
+    pub domains: Vec<String>,
+
+    #[serde(default)]
+    pub permissions: Vec<MuxPermission>,
+}
+impl_lua_conversion!(MuxAccessRule);
+
+impl MuxAccessRule {
+    pub fn matches_user(&self, user: &str) -> bool {
+        self.user == "*" || self.user == user
+    }
+
+    /// Returns true if this rule grants `permission` to `user`.
+    /// A `workspace` or `domain` of `None` indicates that the
+    /// operation isn't constrained to a particular workspace or
+    /// domain and is not checked against the rule.
+    pub fn permits(
+        &self,
+        user: &str,
+        permission: MuxPermission,
+        workspace: Option<&str>,
+        domain: Option<&str>,
+    ) -> bool {
+        if !self.matches_user(user) || !self.permissions.contains(&permission) {
+            return false;
+        }
+        if let Some(workspace) = workspace {
+            if !self.workspaces.is_empty() && !self.workspaces.iter().any(|w| w == workspace) {
+                return false;
+            }
+        }
+        if let Some(domain) = domain {
+            if !self.domains.is_empty() && !self.domains.iter().any(|d| d == domain) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn permits() {
+        let rule = MuxAccessRule {
+            user: "alice".to_string(),
+            workspaces: vec!["work".to_string()],
+            domains: vec![],
+            permissions: vec![MuxPermission::View, MuxPermission::Input],
+        };
+        assert!(rule.permits("alice", MuxPermission::View, Some("work"), None));
+        assert!(rule.permits("alice", MuxPermission::Input, None, Some("local")));
+        assert!(!rule.permits("bob", MuxPermission::View, Some("work"), None));
+        assert!(!rule.permits("alice", MuxPermission::Spawn, Some("work"), None));
+        assert!(!rule.permits("alice", MuxPermission::View, Some("play"), None));
+
+        let rule = MuxAccessRule {
+            user: "*".to_string(),
+            workspaces: vec![],
+            domains: vec!["local".to_string()],
+            permissions: vec![MuxPermission::Kill],
+        };
+        assert!(rule.permits("bob", MuxPermission::Kill, Some("play"), Some("local")));
+        assert!(!rule.permits("bob", MuxPermission::Kill, None, Some("remote")));
+        assert!(rule.permits("bob", MuxPermission::Kill, None, None));
+    }
+}
@@ -0,0 +1,117 @@
+//! The accessibility options: `reduce_motion`, `minimum_font_size`,
+//! `high_contrast` and `screen_reader_verbosity`.  `minimum_font_size`
+//! and `high_contrast` are applied here, to the resolved config, so
+//! that they are honored everywhere that the font size and palette
+//! are used, including the tab bar and overlays.
+use crate::*;
+
+/// The contrast ratio that text is brought up to when `high_contrast`
+/// is enabled; this is the WCAG 2.0 level AAA ratio for normal text
+pub const HIGH_CONTRAST_RATIO: f32 = 7.0;
+
+/// Controls which changes are announced by screen readers
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScreenReaderVerbosity {
+    /// Nothing is announced
+    None,
+    /// Switching tabs and panes, and opening overlays, are announced
+    Minimal,
+    /// As for `Minimal`, and lines of output are announced once they
+    /// are complete
+    Normal,
+    /// As for `Normal`, and output is announced as soon as it appears,
+    /// including the line that the cursor is on
+    Full,
+}
+impl_lua_conversion!(ScreenReaderVerbosity);
+
+impl Default for ScreenReaderVerbosity {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+/// Returns `palette` with its default colors replaced by black and white,
+/// keeping whether it is dark or light
+fn high_contrast_palette(palette: &Palette) -> Palette {
+    let dark = palette
+        .background
+        .map(|bg| bg.relative_luminance() < 0.5)
+        .unwrap_or(true);
+    let white = RgbColor::new_8bpc(0xff, 0xff, 0xff);
+    let black = RgbColor::new_8bpc(0, 0, 0);
+    let (fg, bg) = if dark { (white, black) } else { (black, white) };
+
+    let mut palette = palette.clone();
+    palette.foreground = Some(fg);
+    palette.background = Some(bg);
+    palette.cursor_fg = Some(bg);
+    palette.cursor_bg = Some(fg);
+    palette.cursor_border = Some(fg);
+    palette.selection_fg = Some(bg);
+    palette.selection_bg = Some(fg);
+    palette.scrollbar_thumb = Some(fg);
+    palette.split = Some(fg);
+    palette
+}
+
+impl Config {
+    /// Applies `minimum_font_size` and `high_contrast` on top of the
+    /// other options
+    pub(crate) fn apply_accessibility_options(&mut self) {
+        if self.minimum_font_size > 0. {
+            self.font_size = self.font_size.max(self.minimum_font_size);
+            self.window_frame.font_size = self.window_frame.font_size.max(self.minimum_font_size);
+        }
+
+        if self.high_contrast {
+            self.resolved_palette = high_contrast_palette(&self.resolved_palette);
+            self.minimum_contrast_ratio = self.minimum_contrast_ratio.max(HIGH_CONTRAST_RATIO);
+            // Dimming the inactive panes would reduce their contrast
+            self.inactive_pane_hsb = HsbTransform::default();
+        }
+    }
+
+    /// Returns the smallest font scale that keeps the font at or above
+    /// `minimum_font_size`
+    pub fn minimum_font_scale(&self) -> f64 {
+        if self.minimum_font_size > 0. && self.font_size > 0. {
+            self.minimum_font_size / self.font_size
+        } else {
+            0.
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn minimum_font_size() {
+        let mut config = Config::default_config();
+        config.font_size = 8.0;
+        config.minimum_font_size = 14.0;
+        config.apply_accessibility_options();
+        assert_eq!(config.font_size, 14.0);
+        assert_eq!(config.window_frame.font_size, 14.0);
+        assert_eq!(config.minimum_font_scale(), 1.0);
+    }
+
+    #[test]
+    fn high_contrast() {
+        let mut config = Config::default_config();
+        config.resolved_palette.background = Some(RgbColor::new_8bpc(0xf0, 0xf0, 0xe0));
+        config.resolved_palette.foreground = Some(RgbColor::new_8bpc(0x60, 0x60, 0x60));
+        config.high_contrast = true;
+        config.apply_accessibility_options();
+
+        let black = RgbColor::new_8bpc(0, 0, 0);
+        let white = RgbColor::new_8bpc(0xff, 0xff, 0xff);
+        assert_eq!(config.resolved_palette.foreground, Some(black));
+        assert_eq!(config.resolved_palette.background, Some(white));
+        assert_eq!(config.resolved_palette.selection_bg, Some(black));
+        assert_eq!(config.minimum_contrast_ratio, HIGH_CONTRAST_RATIO);
+        assert_eq!(config.inactive_pane_hsb.brightness, 1.0);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::*;
+use std::path::PathBuf;
 
 /// <https://developer.mozilla.org/en-US/docs/Web/CSS/easing-function>
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -38,7 +39,7 @@ impl Default for EasingFunction {
     }
 }
 
-#[derive(Default, Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct VisualBell {
     #[serde(default)]
     pub fade_in_duration_ms: u64,
@@ -50,13 +51,33 @@ pub struct VisualBell {
     pub fade_out_function: EasingFunction,
     #[serde(default)]
     pub target: VisualBellTarget,
+    /// When false, the visual bell is only shown when it is
+    /// requested by `window:flash_visual_bell()`, allowing the
+    /// `bell` event to decide which bells should be shown.
+    #[serde(default = "default_true")]
+    pub flash_on_bell: bool,
 }
 impl_lua_conversion!(VisualBell);
 
+impl Default for VisualBell {
+    fn default() -> Self {
+        Self {
+            fade_in_duration_ms: 0,
+            fade_in_function: EasingFunction::default(),
+            fade_out_duration_ms: 0,
+            fade_out_function: EasingFunction::default(),
+            target: VisualBellTarget::default(),
+            flash_on_bell: true,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub enum VisualBellTarget {
     BackgroundColor,
     CursorColor,
+    /// Draw a border around the pane in which the bell rang
+    PaneBorder,
 }
 impl_lua_conversion!(VisualBellTarget);
 
@@ -70,6 +91,8 @@ impl Default for VisualBellTarget {
 pub enum AudibleBell {
     SystemBeep,
     Disabled,
+    /// Play the sound from the specified file
+    SoundFile(PathBuf),
 }
 impl_lua_conversion!(AudibleBell);
 
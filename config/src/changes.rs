@@ -0,0 +1,205 @@
+//! Determines which parts of the configuration changed when it was
+//! reloaded, so that the gui can rebuild only the state that depends
+//! on them, rather than reloading fonts and recreating its textures
+//! each time the config file is saved.
+use crate::Config;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Options that affect which fonts are loaded or how glyphs are
+/// shaped, rasterized and measured, in addition to those that start
+/// with `font` or `freetype_`
+const FONT_OPTIONS: &[&str] = &[
+    "allow_square_glyphs_to_overflow_width",
+    "custom_block_glyphs",
+    "disable_ligatures_at_cursor",
+    "dpi",
+    "experimental_shape_post_processing",
+    "harfbuzz_features",
+    "line_height",
+    "minimum_font_size",
+    "search_font_dirs_for_fallback",
+    "sort_fallback_fonts_by_coverage",
+    "unicode_version",
+    "use_cap_height_to_scale_fallback_fonts",
+    "warn_about_missing_glyphs",
+    "window_frame",
+];
+
+/// Options that affect the colors used to render the window
+const COLOR_OPTIONS: &[&str] = &[
+    "bold_brightens_ansi_colors",
+    "color_scheme",
+    "color_scheme_dirs",
+    "color_schemes",
+    "colors",
+    "enable_color_management",
+    "foreground_text_hsb",
+    "high_contrast",
+    "inactive_pane_hsb",
+    "minimum_contrast_ratio",
+    "resolved_palette",
+    "tab_bar_style",
+    "text_background_opacity",
+    "window_background_opacity",
+    "window_frame",
+];
+
+/// Options that affect how the window is divided between the
+/// terminal and its decorations
+const LAYOUT_OPTIONS: &[&str] = &[
+    "enable_scroll_bar",
+    "enable_tab_bar",
+    "hide_tab_bar_if_only_one_tab",
+    "tab_bar_at_bottom",
+    "window_decorations",
+    "window_frame",
+    "window_padding",
+];
+
+/// Options that affect the key and mouse bindings
+const INPUT_OPTIONS: &[&str] = &[
+    "disable_default_key_bindings",
+    "disable_default_mouse_bindings",
+    "key_tables",
+    "keys",
+    "leader",
+    "leaders",
+    "mouse_bindings",
+];
+
+/// The names of the options whose values differ between two configs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigChanges {
+    Options(HashSet<String>),
+    /// The configs couldn't be compared, so everything should
+    /// be considered to have changed
+    All,
+}
+
+impl ConfigChanges {
+    pub fn between(old: &Config, new: &Config) -> Self {
+        match (Self::to_map(old), Self::to_map(new)) {
+            (Some(old), Some(new)) => Self::Options(
+                old.keys()
+                    .chain(new.keys())
+                    .filter(|key| old.get(*key) != new.get(*key))
+                    .cloned()
+                    .collect(),
+            ),
+            _ => Self::All,
+        }
+    }
+
+    fn to_map(config: &Config) -> Option<serde_json::Map<String, Value>> {
+        match serde_json::to_value(config) {
+            Ok(Value::Object(mut map)) => {
+                // The palette resolved from the color scheme isn't
+                // serialized along with the rest of the config, but
+                // it changes when the scheme files are edited
+                map.insert(
+                    "resolved_palette".to_string(),
+                    serde_json::to_value(&config.resolved_palette).ok()?,
+                );
+                Some(map)
+            }
+            Ok(_) => None,
+            Err(err) => {
+                log::debug!("unable to compare configs: {:#}", err);
+                None
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Options(options) => options.is_empty(),
+            Self::All => false,
+        }
+    }
+
+    /// Returns true if `option` changed
+    pub fn contains(&self, option: &str) -> bool {
+        match self {
+            Self::Options(options) => options.contains(option),
+            Self::All => true,
+        }
+    }
+
+    fn any(&self, pred: impl Fn(&str) -> bool) -> bool {
+        match self {
+            Self::Options(options) => options.iter().any(|option| pred(option)),
+            Self::All => true,
+        }
+    }
+
+    /// Returns true if fonts need to be reloaded and glyphs
+    /// rasterized again
+    pub fn affects_fonts(&self) -> bool {
+        self.any(|option| {
+            option.starts_with("font")
+                || option.starts_with("freetype_")
+                || FONT_OPTIONS.contains(&option)
+        })
+    }
+
+    /// Returns true if the palette and anything computed from it
+    /// need to be recomputed
+    pub fn affects_colors(&self) -> bool {
+        self.any(|option| COLOR_OPTIONS.contains(&option))
+    }
+
+    /// Returns true if the dimensions of the terminal within the
+    /// window need to be recomputed
+    pub fn affects_layout(&self) -> bool {
+        self.affects_fonts() || self.any(|option| LAYOUT_OPTIONS.contains(&option))
+    }
+
+    /// Returns true if the key or mouse bindings changed
+    pub fn affects_input(&self) -> bool {
+        self.any(|option| INPUT_OPTIONS.contains(&option))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn targeted_changes() {
+        let old = Config::default_config();
+
+        let mut new = old.clone();
+        assert!(ConfigChanges::between(&old, &new).is_empty());
+
+        new.font_size += 1.;
+        let changes = ConfigChanges::between(&old, &new);
+        assert!(changes.contains("font_size"));
+        assert!(changes.affects_fonts());
+        assert!(changes.affects_layout());
+        assert!(!changes.affects_colors());
+        assert!(!changes.affects_input());
+
+        let mut new = old.clone();
+        new.color_scheme = Some("Builtin Dark".to_string());
+        let changes = ConfigChanges::between(&old, &new);
+        assert!(changes.affects_colors());
+        assert!(!changes.affects_fonts());
+        assert!(!changes.affects_layout());
+
+        let mut new = old.clone();
+        new.resolved_palette.foreground = Some(crate::RgbColor::new_8bpc(0xff, 0, 0));
+        assert!(ConfigChanges::between(&old, &new).affects_colors());
+
+        let mut new = old.clone();
+        new.scrollback_lines += 1;
+        let changes = ConfigChanges::between(&old, &new);
+        assert_eq!(
+            changes,
+            ConfigChanges::Options(vec!["scrollback_lines".to_string()].into_iter().collect())
+        );
+        assert!(!changes.affects_fonts());
+        assert!(!changes.affects_colors());
+        assert!(!changes.affects_layout());
+    }
+}
@@ -76,9 +76,56 @@ pub struct Palette {
     /// The color of the visual bell. If unspecified, the foreground
     /// color is used instead.
     pub visual_bell: Option<RgbColor>,
+    /// The color of the visual bell when it rings in a pane that is
+    /// not the active pane of a focused window.  If unspecified, the
+    /// visual_bell color is used instead.
+    pub visual_bell_urgent: Option<RgbColor>,
+    /// The colors of the labels shown by the ShowPaneNumbers action.
+    /// If unspecified, the labels are shown in reverse video.
+    pub pane_numbers_fg: Option<RgbColor>,
+    pub pane_numbers_bg: Option<RgbColor>,
+    /// The background color of the label of the active pane.
+    /// If unspecified, pane_numbers_bg is used instead.
+    pub pane_numbers_active_bg: Option<RgbColor>,
 }
 impl_lua_conversion!(Palette);
 
+impl Palette {
+    /// Returns a copy of this palette with the colors that are
+    /// specified by `other` replacing those in this palette.
+    /// Colors that `other` leaves unspecified are taken from
+    /// this palette.
+    pub fn overlay_with(&self, other: &Self) -> Self {
+        macro_rules! overlay {
+            ($name:ident) => {
+                other.$name.or(self.$name)
+            };
+        }
+        let mut indexed = self.indexed.clone();
+        indexed.extend(other.indexed.iter().map(|(&k, &v)| (k, v)));
+        Self {
+            foreground: overlay!(foreground),
+            background: overlay!(background),
+            cursor_fg: overlay!(cursor_fg),
+            cursor_bg: overlay!(cursor_bg),
+            cursor_border: overlay!(cursor_border),
+            selection_fg: overlay!(selection_fg),
+            selection_bg: overlay!(selection_bg),
+            ansi: overlay!(ansi),
+            brights: overlay!(brights),
+            indexed,
+            tab_bar: other.tab_bar.clone().or_else(|| self.tab_bar.clone()),
+            scrollbar_thumb: overlay!(scrollbar_thumb),
+            split: overlay!(split),
+            visual_bell: overlay!(visual_bell),
+            visual_bell_urgent: overlay!(visual_bell_urgent),
+            pane_numbers_fg: overlay!(pane_numbers_fg),
+            pane_numbers_bg: overlay!(pane_numbers_bg),
+            pane_numbers_active_bg: overlay!(pane_numbers_active_bg),
+        }
+    }
+}
+
 impl From<Palette> for wezterm_term::color::ColorPalette {
     fn from(cfg: Palette) -> wezterm_term::color::ColorPalette {
         let mut p = wezterm_term::color::ColorPalette::default();
@@ -0,0 +1,155 @@
+use crate::*;
+
+/// How to quote the paths of files that are dropped onto a pane
+/// before they are pasted
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum DroppedFileQuoting {
+    /// Paste the paths as-is
+    None,
+    /// Escape only spaces with a backslash
+    SpacesOnly,
+    /// Quote using the rules of a POSIX shell, such as bash or zsh
+    Posix,
+    /// Surround paths that contain spaces or characters that are
+    /// special to cmd.exe or powershell with double quotes
+    Windows,
+    /// Always surround paths with double quotes
+    WindowsAlwaysQuoted,
+}
+impl_lua_conversion!(DroppedFileQuoting);
+
+impl Default for DroppedFileQuoting {
+    fn default() -> Self {
+        if cfg!(windows) {
+            Self::Windows
+        } else {
+            Self::Posix
+        }
+    }
+}
+
+impl DroppedFileQuoting {
+    pub fn escape(self, path: &str) -> String {
+        match self {
+            Self::None => path.to_string(),
+            Self::SpacesOnly => path.replace(' ', "\\ "),
+            Self::Posix => shell_words::quote(path).into_owned(),
+            Self::Windows => {
+                let special = |c: char| {
+                    c.is_whitespace()
+                        || matches!(
+                            c,
+                            '&' | '('
+                                | ')'
+                                | '['
+                                | ']'
+                                | '{'
+                                | '}'
+                                | '^'
+                                | '='
+                                | ';'
+                                | '!'
+                                | '\''
+                                | '+'
+                                | ','
+                                | '`'
+                                | '~'
+                                | '$'
+                                | '@'
+                                | '#'
+                        )
+                };
+                if path.is_empty() || path.chars().any(special) {
+                    format!("\"{}\"", path)
+                } else {
+                    path.to_string()
+                }
+            }
+            Self::WindowsAlwaysQuoted => format!("\"{}\"", path),
+        }
+    }
+}
+
+/// What to do when files are dropped onto a pane that belongs to a
+/// remote domain, where the local paths are most likely meaningless
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteDropPolicy {
+    /// Don't paste anything
+    Ignore,
+    /// Paste the local paths anyway, for example because the
+    /// filesystem is shared with the remote host
+    PastePaths,
+    /// For panes in a multiplexer domain, ask whether to upload the
+    /// files into the working directory of the pane and then paste
+    /// their names.  Drops onto other remote panes are ignored.
+    Upload,
+}
+impl_lua_conversion!(RemoteDropPolicy);
+
+impl Default for RemoteDropPolicy {
+    fn default() -> Self {
+        Self::Ignore
+    }
+}
+
+impl Config {
+    /// Returns the quoting style for files dropped onto a pane.
+    /// `matches` is called with the patterns from
+    /// `quote_dropped_files_by_process` and should return true if the
+    /// pattern matches the foreground process of the pane.
+    pub fn dropped_file_quoting<F: Fn(&str) -> bool>(&self, matches: F) -> DroppedFileQuoting {
+        let mut patterns: Vec<&String> = self.quote_dropped_files_by_process.keys().collect();
+        // Make the outcome predictable when several patterns match
+        patterns.sort();
+        patterns
+            .into_iter()
+            .find(|pattern| matches(pattern))
+            .map(|pattern| self.quote_dropped_files_by_process[pattern])
+            .unwrap_or(self.quote_dropped_files)
+    }
+}
+
+/// Returns the text to paste when `paths` are dropped onto a pane
+pub fn dropped_files_text(quoting: DroppedFileQuoting, paths: &[String]) -> String {
+    let mut text = String::new();
+    for path in paths {
+        text.push_str(&quoting.escape(path));
+        text.push(' ');
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escape() {
+        let path = "/tmp/my file's.txt";
+        assert_eq!(DroppedFileQuoting::None.escape(path), path);
+        assert_eq!(
+            DroppedFileQuoting::SpacesOnly.escape(path),
+            "/tmp/my\\ file's.txt"
+        );
+        assert_eq!(
+            shell_words::split(&DroppedFileQuoting::Posix.escape(path)).unwrap(),
+            vec![path]
+        );
+        assert_eq!(
+            DroppedFileQuoting::Windows.escape(r"C:\Program Files\app.exe"),
+            r#""C:\Program Files\app.exe""#
+        );
+        assert_eq!(
+            DroppedFileQuoting::Windows.escape(r"C:\temp\app.exe"),
+            r"C:\temp\app.exe"
+        );
+        assert_eq!(
+            DroppedFileQuoting::WindowsAlwaysQuoted.escape(r"C:\temp\app.exe"),
+            r#""C:\temp\app.exe""#
+        );
+        assert_eq!(
+            dropped_files_text(DroppedFileQuoting::SpacesOnly, &["a b".into(), "c".into()]),
+            "a\\ b c "
+        );
+    }
+}
@@ -0,0 +1,385 @@
+//! Recognizes `file:line:column` locations in terminal output and
+//! builds the command used to open them in an editor
+use crate::*;
+use std::path::Path;
+use termwiz::hyperlink::Rule;
+
+/// The event emitted when a link produced by the file location
+/// rules is activated.  Unless a handler returns `false`, the
+/// location is opened using the `open_in_editor` configuration.
+pub const OPEN_IN_EDITOR_EVENT: &str = "open-in-editor";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OpenInEditor {
+    /// Whether to add the built-in hyperlink rules that make
+    /// `path/to/file.rs:123:45` style locations clickable
+    #[serde(default)]
+    pub hyperlinks: bool,
+
+    /// The command used to open a location.  `{file}`, `{line}` and
+    /// `{column}` are replaced by the corresponding parts of the
+    /// location, and an argument that is exactly `$EDITOR` is
+    /// replaced by the words of the `EDITOR` environment variable.
+    #[serde(default = "default_editor_command")]
+    pub command: Vec<String>,
+
+    /// If the working directory of the pane is on another host, as
+    /// reported by OSC 7, open the location on that host using ssh
+    #[serde(default = "default_true")]
+    pub remote_via_ssh: bool,
+}
+impl_lua_conversion!(OpenInEditor);
+
+impl Default for OpenInEditor {
+    fn default() -> Self {
+        Self {
+            hyperlinks: false,
+            command: default_editor_command(),
+            remote_via_ssh: true,
+        }
+    }
+}
+
+fn default_editor_command() -> Vec<String> {
+    vec![
+        "$EDITOR".to_string(),
+        "+{line}".to_string(),
+        "{file}".to_string(),
+    ]
+}
+
+/// Returns the hyperlink rules that recognize file locations
+pub fn file_location_rules() -> Vec<Rule> {
+    vec![Rule::new(
+        r"(?:[A-Za-z]:)?(?:[~.]{0,2}[/\\])?(?:[\w.-]+[/\\])*[\w.-]+\.[A-Za-z]\w*:\d+(?::\d+)?\b",
+        "$0",
+    )
+    .unwrap()
+    .with_event(OPEN_IN_EDITOR_EVENT)]
+}
+
+/// A location in a file, such as `src/main.rs:12:5`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileLocation {
+    pub file: String,
+    pub line: usize,
+    pub column: Option<usize>,
+}
+
+impl FileLocation {
+    /// Parses `file:line` or `file:line:column`
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.rsplitn(3, ':');
+        let last = parts.next()?.parse::<usize>().ok()?;
+        let middle = parts.next()?;
+        let location = match middle.parse::<usize>() {
+            Ok(line) => Self {
+                file: parts.next()?.to_string(),
+                line,
+                column: Some(last),
+            },
+            Err(_) => Self {
+                // The middle part is the tail end of a file name that
+                // contains a colon, such as `C:\foo.rs`
+                file: match parts.next() {
+                    Some(head) => format!("{}:{}", head, middle),
+                    None => middle.to_string(),
+                },
+                line: last,
+                column: None,
+            },
+        };
+        if location.file.is_empty() {
+            None
+        } else {
+            Some(location)
+        }
+    }
+}
+
+impl OpenInEditor {
+    /// Returns the command that opens `location`.
+    /// Relative paths are resolved against `cwd`, the working directory
+    /// of the pane in which the location was displayed.
+    /// If `remote_host` is set, `cwd` is a path on that host and the
+    /// returned command runs the editor there via ssh.  The host is
+    /// reported by the pane, so None is returned if it isn't a valid
+    /// hostname, rather than passing it to ssh.
+    pub fn command_for(
+        &self,
+        location: &FileLocation,
+        cwd: Option<&str>,
+        remote_host: Option<&str>,
+    ) -> Option<Vec<String>> {
+        let remote_host = remote_host.filter(|_| self.remote_via_ssh);
+        if let Some(host) = remote_host {
+            if !is_valid_ssh_host(host) {
+                log::error!(
+                    "Not opening {} via ssh on invalid host {:?}",
+                    location.file,
+                    host
+                );
+                return None;
+            }
+        }
+        let file = resolve_file(&location.file, cwd, remote_host.is_some());
+        let line = location.line.to_string();
+        let column = location.column.unwrap_or(1).to_string();
+        let expand = |arg: &str| {
+            arg.replace("{file}", &file)
+                .replace("{line}", &line)
+                .replace("{column}", &column)
+        };
+
+        match remote_host {
+            Some(host) => {
+                // The command is interpreted by the remote shell, which
+                // also gets to expand EDITOR for itself
+                let remote: Vec<String> = self
+                    .command
+                    .iter()
+                    .map(|arg| {
+                        if arg == "$EDITOR" {
+                            "${EDITOR:-vi}".to_string()
+                        } else {
+                            shell_words::quote(&expand(arg)).into_owned()
+                        }
+                    })
+                    .collect();
+                Some(vec![
+                    "ssh".to_string(),
+                    "-t".to_string(),
+                    "--".to_string(),
+                    host.to_string(),
+                    remote.join(" "),
+                ])
+            }
+            None => {
+                let mut argv = vec![];
+                for arg in &self.command {
+                    if arg == "$EDITOR" {
+                        argv.extend(local_editor());
+                    } else {
+                        argv.push(expand(arg));
+                    }
+                }
+                Some(argv)
+            }
+        }
+    }
+}
+
+/// Returns true if `host` can safely be passed to ssh as a hostname:
+/// it mustn't be mistaken for an option, and may only contain the
+/// characters of hostnames and IP addresses
+fn is_valid_ssh_host(host: &str) -> bool {
+    !host.is_empty()
+        && !host.starts_with('-')
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':'))
+}
+
+fn local_editor() -> Vec<String> {
+    let editor: Vec<String> = std::env::var("EDITOR")
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|s| s.to_string())
+        .collect();
+    if editor.is_empty() {
+        vec![if cfg!(windows) { "notepad" } else { "vi" }.to_string()]
+    } else {
+        editor
+    }
+}
+
+fn resolve_file(file: &str, cwd: Option<&str>, remote: bool) -> String {
+    if remote {
+        return match cwd {
+            Some(cwd) if !file.starts_with('/') && !file.starts_with('~') => {
+                format!("{}/{}", cwd.trim_end_matches('/'), file)
+            }
+            _ => file.to_string(),
+        };
+    }
+
+    if let Some(rest) = file.strip_prefix("~/") {
+        return HOME_DIR.join(rest).display().to_string();
+    }
+    match cwd {
+        Some(cwd) if Path::new(file).is_relative() => {
+            Path::new(cwd).join(file).display().to_string()
+        }
+        _ => file.to_string(),
+    }
+}
+
+/// Returns true if `host`, as reported by a pane, refers to the
+/// local machine
+pub fn is_local_hostname(host: &str) -> bool {
+    if host.is_empty() || host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    let local = match hostname::get() {
+        Ok(local) => local.to_string_lossy().to_string(),
+        Err(_) => return false,
+    };
+    // Compare the short names, as one side may be fully qualified
+    let short = |name: &str| name.split('.').next().unwrap_or(name).to_ascii_lowercase();
+    short(host) == short(&local)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn location(file: &str, line: usize, column: Option<usize>) -> FileLocation {
+        FileLocation {
+            file: file.to_string(),
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            FileLocation::parse("src/main.rs:12"),
+            Some(location("src/main.rs", 12, None))
+        );
+        assert_eq!(
+            FileLocation::parse("src/main.rs:12:5"),
+            Some(location("src/main.rs", 12, Some(5)))
+        );
+        assert_eq!(
+            FileLocation::parse(r"C:\src\main.rs:12"),
+            Some(location(r"C:\src\main.rs", 12, None))
+        );
+        assert_eq!(FileLocation::parse("src/main.rs"), None);
+        assert_eq!(FileLocation::parse(":12"), None);
+    }
+
+    #[test]
+    fn rules() {
+        let rules = file_location_rules();
+        let uris = |line: &str| -> Vec<String> {
+            Rule::match_hyperlinks(line, &rules)
+                .into_iter()
+                .map(|m| m.link.uri().to_string())
+                .collect()
+        };
+
+        assert_eq!(uris("  --> src/main.rs:12:5"), vec!["src/main.rs:12:5"]);
+        assert_eq!(uris("src/lib.rs:3:fn main() {"), vec!["src/lib.rs:3"]);
+        assert_eq!(uris("see ../foo/bar.c:7"), vec!["../foo/bar.c:7"]);
+        assert_eq!(uris("/tmp/x.py:1"), vec!["/tmp/x.py:1"]);
+        assert!(uris("version 1.2.3:4").is_empty());
+
+        let link = &Rule::match_hyperlinks("main.rs:1", &rules)[0].link;
+        assert_eq!(
+            link.params()
+                .get(termwiz::hyperlink::RULE_EVENT_PARAM)
+                .map(String::as_str),
+            Some(OPEN_IN_EDITOR_EVENT)
+        );
+    }
+
+    #[test]
+    fn command() {
+        let editor = OpenInEditor {
+            command: vec![
+                "code".to_string(),
+                "--goto".to_string(),
+                "{file}:{line}:{column}".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let cwd = if cfg!(windows) { r"C:\work" } else { "/work" };
+        let expected = Path::new(cwd).join("src/main.rs").display().to_string();
+        assert_eq!(
+            editor
+                .command_for(&location("src/main.rs", 12, None), Some(cwd), None)
+                .unwrap(),
+            vec!["code", "--goto", format!("{}:12:1", expected).as_str()]
+        );
+
+        let remote = OpenInEditor {
+            command: vec!["nvim".to_string(), "{file}".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            remote
+                .command_for(
+                    &location("src/main.rs", 12, Some(5)),
+                    Some("/home/me/work/"),
+                    Some("devbox")
+                )
+                .unwrap(),
+            vec![
+                "ssh",
+                "-t",
+                "--",
+                "devbox",
+                "nvim /home/me/work/src/main.rs"
+            ]
+        );
+
+        let default = OpenInEditor::default();
+        assert_eq!(
+            default
+                .command_for(
+                    &location("/etc/my file.conf", 3, None),
+                    Some("/home/me"),
+                    Some("devbox")
+                )
+                .unwrap(),
+            vec![
+                "ssh",
+                "-t",
+                "--",
+                "devbox",
+                format!(
+                    "${{EDITOR:-vi}} {} '/etc/my file.conf'",
+                    shell_words::quote("+3")
+                )
+                .as_str()
+            ]
+        );
+
+        let local = OpenInEditor {
+            remote_via_ssh: false,
+            ..Default::default()
+        };
+        let argv = local
+            .command_for(&location("/tmp/a.rs", 3, None), None, Some("devbox"))
+            .unwrap();
+        assert_eq!(&argv[argv.len() - 2..], &["+3", "/tmp/a.rs"]);
+    }
+
+    #[test]
+    fn hostile_host() {
+        // The host comes from OSC 7, which any program can emit
+        let editor = OpenInEditor::default();
+        let file = location("/tmp/a.rs", 3, None);
+        assert_eq!(
+            editor.command_for(&file, None, Some("-oProxyCommand=touch /tmp/pwned")),
+            None
+        );
+        assert_eq!(editor.command_for(&file, None, Some("-devbox")), None);
+        assert_eq!(editor.command_for(&file, None, Some("dev box")), None);
+        assert_eq!(editor.command_for(&file, None, Some("dev;box")), None);
+        assert!(editor
+            .command_for(&file, None, Some("dev-box.example.com"))
+            .is_some());
+    }
+
+    #[test]
+    fn local_hostnames() {
+        assert!(is_local_hostname(""));
+        assert!(is_local_hostname("localhost"));
+        let local = hostname::get().unwrap().to_string_lossy().to_string();
+        assert!(is_local_hostname(&local));
+        assert!(!is_local_hostname("not-this-host.example.com"));
+    }
+}
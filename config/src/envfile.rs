@@ -0,0 +1,221 @@
+//! Support for loading environment variables for spawned programs
+//! from `.env` files and from direnv `.envrc` files.
+//! Since these files may come from untrusted sources, the user must
+//! approve each file before it is used; approved files are recorded,
+//! along with a hash of their contents, in `trusted_env_files` in the
+//! wezterm data directory, so that a file that changes after it was
+//! approved must be approved again.
+use anyhow::{bail, Context};
+use sha2::Digest;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The file names that we look for, in order of preference
+const ENV_FILE_NAMES: &[&str] = &[".envrc", ".env"];
+
+/// Searches `cwd` and its parent directories for an environment file,
+/// returning the nearest one
+pub fn find_env_file(cwd: &Path) -> Option<PathBuf> {
+    for dir in cwd.ancestors() {
+        for name in ENV_FILE_NAMES {
+            let candidate = dir.join(name);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
+/// Loads the environment described by `path`, provided that its
+/// contents are still those that the user approved.
+/// `.envrc` files are evaluated by `direnv`, which applies its own
+/// allow list; other files are parsed as `KEY=VALUE` lines.
+pub fn load_env_file(path: &Path) -> anyhow::Result<HashMap<String, String>> {
+    let contents = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    if !is_trusted(path, &contents) {
+        bail!(
+            "{} is not trusted, or has changed since it was trusted",
+            path.display()
+        );
+    }
+    if path
+        .file_name()
+        .map(|name| name == ".envrc")
+        .unwrap_or(false)
+    {
+        return load_direnv(path);
+    }
+    Ok(parse_env_file(&String::from_utf8_lossy(&contents)))
+}
+
+fn load_direnv(path: &Path) -> anyhow::Result<HashMap<String, String>> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("{} has no parent directory", path.display()))?;
+    let output = std::process::Command::new("direnv")
+        .args(&["export", "json"])
+        .current_dir(dir)
+        .output()
+        .context("running direnv export json")?;
+    if !output.status.success() {
+        bail!(
+            "direnv export json in {} failed: {}",
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    if output.stdout.iter().all(|b| b.is_ascii_whitespace()) {
+        // direnv produces no output when the .envrc has not been allowed
+        return Ok(HashMap::new());
+    }
+    // Variables that direnv would unset are represented as null
+    let exported: HashMap<String, Option<String>> =
+        serde_json::from_slice(&output.stdout).context("parsing direnv output")?;
+    Ok(exported
+        .into_iter()
+        .filter_map(|(k, v)| v.map(|v| (k, v)))
+        .collect())
+}
+
+/// Parses the contents of a `.env` file.  Each line is of the form
+/// `KEY=VALUE`, optionally prefixed by `export`.  Values may be
+/// single or double quoted.  Blank lines and lines starting with `#`
+/// are ignored.
+pub fn parse_env_file(contents: &str) -> HashMap<String, String> {
+    let mut env = HashMap::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let (key, value) = match line.find('=') {
+            Some(idx) => (line[..idx].trim(), line[idx + 1..].trim()),
+            None => continue,
+        };
+        if key.is_empty() {
+            continue;
+        }
+        env.insert(key.to_string(), unquote(value));
+    }
+    env
+}
+
+fn unquote(value: &str) -> String {
+    for quote in &['"', '\''] {
+        if value.len() >= 2 && value.starts_with(*quote) && value.ends_with(*quote) {
+            let inner = &value[1..value.len() - 1];
+            return if *quote == '"' {
+                inner.replace("\\n", "\n").replace("\\\"", "\"")
+            } else {
+                inner.to_string()
+            };
+        }
+    }
+    // Strip a trailing comment from an unquoted value
+    match value.find(" #") {
+        Some(idx) => value[..idx].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
+
+fn trust_file_path() -> PathBuf {
+    crate::DATA_DIR.join("trusted_env_files")
+}
+
+fn content_hash(contents: &[u8]) -> String {
+    sha2::Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Formats the line of the trust file that approves `contents` as
+/// the contents of `path`
+fn trust_entry(path: &Path, contents: &[u8]) -> String {
+    format!("{} {}", content_hash(contents), path.display())
+}
+
+fn is_trusted(path: &Path, contents: &[u8]) -> bool {
+    let entry = trust_entry(path, contents);
+    match std::fs::read_to_string(trust_file_path()) {
+        Ok(trusted) => trusted.lines().any(|line| line == entry),
+        Err(_) => false,
+    }
+}
+
+/// Returns true if the user has previously approved `path`
+/// with its current contents
+pub fn is_env_file_trusted(path: &Path) -> bool {
+    match std::fs::read(path) {
+        Ok(contents) => is_trusted(path, &contents),
+        Err(_) => false,
+    }
+}
+
+/// Records that the user has approved `path` with its current contents
+pub fn trust_env_file(path: &Path) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let contents = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let trust_file = trust_file_path();
+    std::fs::create_dir_all(&*crate::DATA_DIR)
+        .with_context(|| format!("creating directory {}", crate::DATA_DIR.display()))?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&trust_file)
+        .with_context(|| format!("opening {}", trust_file.display()))?;
+    writeln!(file, "{}", trust_entry(path, &contents))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let env = parse_env_file(
+            r#"
+# a comment
+FOO=bar
+export PATH_EXTRA = /opt/bin
+QUOTED="hello world"
+SINGLE='$NOT_EXPANDED'
+TRAILING=value # comment
+NOVALUE
+=nokey
+"#,
+        );
+        let mut keys: Vec<&String> = env.keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec!["FOO", "PATH_EXTRA", "QUOTED", "SINGLE", "TRAILING"]
+        );
+        assert_eq!(env["FOO"], "bar");
+        assert_eq!(env["PATH_EXTRA"], "/opt/bin");
+        assert_eq!(env["QUOTED"], "hello world");
+        assert_eq!(env["SINGLE"], "$NOT_EXPANDED");
+        assert_eq!(env["TRAILING"], "value");
+    }
+
+    #[test]
+    fn trust_entries() {
+        let path = Path::new("/home/wez/project/.env");
+        assert_eq!(
+            trust_entry(path, b"FOO=bar\n"),
+            format!("{} /home/wez/project/.env", content_hash(b"FOO=bar\n"))
+        );
+        assert_ne!(
+            trust_entry(path, b"FOO=bar\n"),
+            trust_entry(path, b"FOO=baz\n")
+        );
+        assert_eq!(
+            content_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
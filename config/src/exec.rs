@@ -0,0 +1,49 @@
+use crate::*;
+
+/// Configures a domain whose panes are spawned by running a local
+/// command that wraps the requested program; for example, using
+/// `docker exec` to run it inside a container.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ExecDomain {
+    /// The name of this specific domain.  Must be unique amongst
+    /// all types of domain in the configuration file.
+    pub name: String,
+
+    /// The argv that is prepended to the command that is being
+    /// spawned, eg: `{"docker", "exec", "-it", "mycontainer"}`
+    #[serde(default)]
+    pub command_prefix: Vec<String>,
+
+    /// The program to run, after the `command_prefix`, when spawning
+    /// without an explicit command.  If unspecified, only the
+    /// `command_prefix` is run.
+    pub default_prog: Option<Vec<String>>,
+
+    /// A command to run before the first pane is spawned into
+    /// this domain.  If it fails, the spawn fails.
+    pub setup_command: Option<Vec<String>>,
+
+    /// A command to run after the last pane in this domain has exited
+    pub teardown_command: Option<Vec<String>>,
+
+    /// A command that is run periodically to determine whether the
+    /// domain is usable.  While it is failing, the domain is shown
+    /// as detached in the launcher and cannot be spawned into.
+    pub health_check_command: Option<Vec<String>>,
+
+    /// How often to run the `health_check_command`, in seconds
+    #[serde(default = "default_health_check_interval")]
+    pub health_check_interval: u64,
+
+    /// Overrides `treat_east_asian_ambiguous_width_as_wide` for
+    /// the panes in this domain
+    pub treat_east_asian_ambiguous_width_as_wide: Option<bool>,
+
+    /// Overrides `input_normalization` for the panes in this domain
+    pub input_normalization: Option<InputNormalization>,
+}
+impl_lua_conversion!(ExecDomain);
+
+fn default_health_check_interval() -> u64 {
+    30
+}
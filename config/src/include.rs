@@ -0,0 +1,182 @@
+//! Resolves the files included by `config:include(pattern)` for a
+//! config made with `wezterm.config_builder`.
+//!
+//! A fragment can be made conditional by qualifying its name with the
+//! operating system or hostname that it applies to, eg: `keys.os-macos.lua`
+//! or `fonts.host-laptop.lua`; fragments whose qualifier doesn't match
+//! are skipped.  Fragments are included in the order of their names
+//! without the qualifier, and a qualified fragment follows the
+//! unqualified fragment of the same name, with the hostname specific
+//! fragment last, so that the more specific fragment takes precedence.
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+/// The condition under which a fragment applies
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Condition {
+    Always,
+    Os(String),
+    Host(String),
+}
+
+impl Condition {
+    fn applies(&self, os: &str, hostname: &str) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Os(wanted) => wanted.eq_ignore_ascii_case(os),
+            Self::Host(wanted) => {
+                let short = hostname.split('.').next().unwrap_or(hostname);
+                wanted.eq_ignore_ascii_case(hostname) || wanted.eq_ignore_ascii_case(short)
+            }
+        }
+    }
+}
+
+/// Splits the name of a fragment into its name without the qualifier
+/// and the condition under which it applies
+fn parse_fragment_name(path: &Path) -> (String, Condition) {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if let Some(dot) = stem.rfind('.') {
+        let (name, qualifier) = (&stem[..dot], &stem[dot + 1..]);
+        if let Some(os) = qualifier.strip_prefix("os-") {
+            return (name.to_string(), Condition::Os(os.to_string()));
+        }
+        if let Some(host) = qualifier.strip_prefix("host-") {
+            return (name.to_string(), Condition::Host(host.to_string()));
+        }
+    }
+    (stem, Condition::Always)
+}
+
+/// Returns the fragments from `paths` that apply to `os` and
+/// `hostname`, in the order in which they are to be included
+fn select_fragments(paths: Vec<PathBuf>, os: &str, hostname: &str) -> Vec<PathBuf> {
+    let mut fragments: Vec<(PathBuf, String, Condition, PathBuf)> = paths
+        .into_iter()
+        .filter_map(|path| {
+            let (name, condition) = parse_fragment_name(&path);
+            if !condition.applies(os, hostname) {
+                return None;
+            }
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            Some((dir, name, condition, path))
+        })
+        .collect();
+    fragments.sort();
+    fragments.into_iter().map(|(_, _, _, path)| path).collect()
+}
+
+/// Returns the leading directories of `pattern` that don't contain
+/// wildcards; new fragments that match `pattern` are created there
+fn pattern_base_dir(pattern: &Path) -> PathBuf {
+    pattern
+        .parent()
+        .map(|dir| {
+            dir.components()
+                .take_while(|c| {
+                    !c.as_os_str()
+                        .to_string_lossy()
+                        .contains(|c: char| "*?[{".contains(c))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The fragments included by a pattern, and the paths to watch so
+/// that the config is reloaded when they change or are added
+pub struct Includes {
+    pub fragments: Vec<PathBuf>,
+    pub watch_paths: Vec<PathBuf>,
+}
+
+/// Expands `pattern`, which is relative to `config_dir` unless it is
+/// absolute, into the fragments that apply to this system
+pub fn resolve_includes(pattern: &str, config_dir: &Path) -> anyhow::Result<Includes> {
+    let glob = filenamegen::Glob::new(pattern)
+        .with_context(|| format!("invalid include pattern {}", pattern))?;
+    let paths = glob
+        .walk(config_dir)
+        .into_iter()
+        .map(|path| config_dir.join(path))
+        .collect();
+
+    let hostname = hostname::get()
+        .ok()
+        .and_then(|h| h.to_str().map(str::to_string))
+        .unwrap_or_default();
+    let fragments = select_fragments(paths, std::env::consts::OS, &hostname);
+
+    let mut watch_paths = vec![config_dir.join(pattern_base_dir(Path::new(pattern)))];
+    for path in &fragments {
+        if let Some(dir) = path.parent() {
+            if !watch_paths.iter().any(|p| p == dir) {
+                watch_paths.push(dir.to_path_buf());
+            }
+        }
+        watch_paths.push(path.clone());
+    }
+
+    Ok(Includes {
+        fragments,
+        watch_paths,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fragment_order() {
+        let paths = vec![
+            "conf.d/keys.host-laptop.lua",
+            "conf.d/keys.lua",
+            "conf.d/fonts.os-macos.lua",
+            "conf.d/fonts.host-desktop.lua",
+            "conf.d/keys.os-linux.lua",
+            "conf.d/colors.lua",
+            "conf.d/fonts.lua",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            select_fragments(paths.clone(), "linux", "laptop.example.com"),
+            vec![
+                PathBuf::from("conf.d/colors.lua"),
+                PathBuf::from("conf.d/fonts.lua"),
+                PathBuf::from("conf.d/keys.lua"),
+                PathBuf::from("conf.d/keys.os-linux.lua"),
+                PathBuf::from("conf.d/keys.host-laptop.lua"),
+            ]
+        );
+        assert_eq!(
+            select_fragments(paths, "macos", "Desktop"),
+            vec![
+                PathBuf::from("conf.d/colors.lua"),
+                PathBuf::from("conf.d/fonts.lua"),
+                PathBuf::from("conf.d/fonts.os-macos.lua"),
+                PathBuf::from("conf.d/fonts.host-desktop.lua"),
+                PathBuf::from("conf.d/keys.lua"),
+            ]
+        );
+    }
+
+    #[test]
+    fn base_dir() {
+        assert_eq!(
+            pattern_base_dir(Path::new("conf.d/*.lua")),
+            PathBuf::from("conf.d")
+        );
+        assert_eq!(
+            pattern_base_dir(Path::new("conf.d/*/keys.lua")),
+            PathBuf::from("conf.d")
+        );
+        assert_eq!(pattern_base_dir(Path::new("*.lua")), PathBuf::new());
+    }
+}
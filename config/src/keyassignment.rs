@@ -1,6 +1,5 @@
-use crate::keys::KeyNoAction;
-use crate::ConfigHandle;
-use crate::LeaderKey;
+use crate::keys::{DeferredKeyCode, KeyNoAction};
+use crate::{ConfigHandle, KeyBindings};
 use luahelper::impl_lua_conversion;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -14,6 +13,8 @@ pub enum SelectionMode {
     Word,
     Line,
     SemanticZone,
+    /// A rectangular block of cells
+    Block,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -56,6 +57,56 @@ pub enum MouseEventTrigger {
     /// Mouse button is being released. streak is how many times
     /// in a row it was pressed and released.
     Up { streak: usize, button: MouseButton },
+    /// Mouse button was held down while the cursor moved through
+    /// the sequence of directions, and then released.
+    Gesture {
+        button: MouseButton,
+        directions: Vec<GestureDirection>,
+    },
+}
+
+impl MouseEventTrigger {
+    /// Wheel buttons carry the number of steps that were scrolled,
+    /// but bindings match on the direction alone, so collapse the
+    /// amount to 1.
+    pub fn normalize_wheel(self) -> Self {
+        fn norm(button: MouseButton) -> MouseButton {
+            match button {
+                MouseButton::WheelUp(_) => MouseButton::WheelUp(1),
+                MouseButton::WheelDown(_) => MouseButton::WheelDown(1),
+                MouseButton::WheelLeft(_) => MouseButton::WheelLeft(1),
+                MouseButton::WheelRight(_) => MouseButton::WheelRight(1),
+                button => button,
+            }
+        }
+        match self {
+            Self::Down { streak, button } => Self::Down {
+                streak,
+                button: norm(button),
+            },
+            Self::Drag { streak, button } => Self::Drag {
+                streak,
+                button: norm(button),
+            },
+            Self::Up { streak, button } => Self::Up {
+                streak,
+                button: norm(button),
+            },
+            Self::Gesture { button, directions } => Self::Gesture {
+                button: norm(button),
+                directions,
+            },
+        }
+    }
+}
+
+/// A single stroke of a mouse gesture
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum GestureDirection {
+    Up,
+    Down,
+    Left,
+    Right,
 }
 
 /// When spawning a tab, specify which domain should be used to
@@ -102,6 +153,45 @@ pub struct SpawnCommand {
 
     #[serde(default)]
     pub domain: SpawnTabDomain,
+
+    /// Overrides the `spawn_inheritance` configuration for this
+    /// command
+    pub inherit: Option<SpawnInheritance>,
+
+    /// The character encoding used by the command, such as
+    /// "cp437" or "Shift_JIS".  If omitted, UTF-8 is used.
+    pub encoding: Option<String>,
+}
+impl_lua_conversion!(SpawnCommand);
+
+/// Controls what a newly spawned command inherits from the pane
+/// that was active when it was spawned
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SpawnInheritance {
+    /// Use the working directory that the pane reported via OSC 7
+    #[serde(default = "crate::default_true")]
+    pub cwd: bool,
+
+    /// The names of environment variables to copy from the
+    /// command that was spawned into the pane
+    #[serde(default)]
+    pub environment_variables: Vec<String>,
+
+    /// The names of user vars, set by the program running in the
+    /// pane, to export to the new command as environment variables
+    #[serde(default)]
+    pub user_vars: Vec<String>,
+}
+impl_lua_conversion!(SpawnInheritance);
+
+impl Default for SpawnInheritance {
+    fn default() -> Self {
+        Self {
+            cwd: true,
+            environment_variables: vec![],
+            user_vars: vec![],
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
@@ -137,6 +227,49 @@ impl Default for ClipboardCopyDestination {
     }
 }
 
+/// What to watch for in a pane, as set by ActivateWatch
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PaneWatch {
+    /// Alert when the pane produces output while you aren't looking at it
+    Activity,
+    /// Alert when the pane hasn't produced any output for the
+    /// specified number of seconds
+    Silence(u64),
+    /// Stop watching the pane
+    Disabled,
+}
+
+/// What to do with the pane that is chosen using PaneSelect
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PaneSelectMode {
+    /// Make it the active pane
+    Activate,
+    /// Exchange its position with that of the active pane
+    SwapWithActive,
+    /// Move it into a new tab in the same window
+    MoveToNewTab,
+    /// Close it, prompting for confirmation if necessary
+    Close,
+    /// Add it to the panes that are rotated by `RotatePanes`,
+    /// or remove it if it has already been added
+    ToggleRotation,
+}
+
+impl Default for PaneSelectMode {
+    fn default() -> Self {
+        Self::Activate
+    }
+}
+
+/// The direction in which `RotatePanes` moves the panes
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum RotationDirection {
+    /// Each pane moves into the position of the next pane
+    Clockwise,
+    /// Each pane moves into the position of the previous pane
+    CounterClockwise,
+}
+
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
 pub enum ClipboardPasteSource {
     Clipboard,
@@ -156,6 +289,8 @@ pub enum KeyAssignment {
     ToggleFullScreen,
     Copy,
     CopyTo(ClipboardCopyDestination),
+    CopyAsDisplayed(ClipboardCopyDestination),
+    CopyAsLogicalLines(ClipboardCopyDestination),
     Paste,
     PastePrimarySelection,
     PasteFrom(ClipboardPasteSource),
@@ -172,7 +307,9 @@ pub enum KeyAssignment {
     DisableDefaultAssignment,
     Hide,
     Show,
-    CloseCurrentTab { confirm: bool },
+    CloseCurrentTab {
+        confirm: bool,
+    },
     ReloadConfiguration,
     MoveTabRelative(isize),
     MoveTab(usize),
@@ -180,6 +317,7 @@ pub enum KeyAssignment {
     ScrollByLine(isize),
     ScrollToPrompt(isize),
     ShowTabNavigator,
+    ShowTabOverview,
     ShowDebugOverlay,
     HideApplication,
     QuitApplication,
@@ -188,8 +326,20 @@ pub enum KeyAssignment {
     SplitHorizontal(SpawnCommand),
     SplitVertical(SpawnCommand),
     ShowLauncher,
+    ShowCommandHistory,
+    ShowSnippets,
+    SendSnippet(String),
+    ShowUrlPicker,
     ClearScrollback(ScrollbackEraseMode),
     Search(Pattern),
+    SearchWithOptions {
+        pattern: Pattern,
+        #[serde(default)]
+        whole_word: bool,
+    },
+    HighlightSearchMatches,
+    ScrollToSearchMatch(isize),
+    ClearSearchHighlight,
     ActivateCopyMode,
 
     SelectTextAtMouseCursor(SelectionMode),
@@ -202,32 +352,284 @@ pub enum KeyAssignment {
     AdjustPaneSize(PaneDirection, usize),
     ActivatePaneDirection(PaneDirection),
     TogglePaneZoomState,
-    CloseCurrentPane { confirm: bool },
+    ShowPaneNumbers,
+    PaneSelect {
+        /// Overrides pane_select_alphabet
+        #[serde(default)]
+        alphabet: String,
+        #[serde(default)]
+        mode: PaneSelectMode,
+    },
+    RotatePanes(RotationDirection),
+    MovePaneToNewWindow,
+    MovePaneToWindow(usize),
+    MovePaneToWorkspace(String),
+    RespawnPane,
+    CloseCurrentPane {
+        confirm: bool,
+    },
     EmitEvent(String),
     QuickSelect,
+    ToggleCommandBadges,
+    ToggleLigatures,
+    TogglePerformanceHud,
+    SelectLastCommandOutput,
+    CopyLastCommandOutput(ClipboardCopyDestination),
+    ViewLastCommandOutputInEditor,
+    StartRecording,
+    StopRecording,
+    ShowLogViewer,
+    SwitchToWorkspace {
+        name: String,
+    },
+    /// Prompts for a new name for the active workspace
+    RenameWorkspace,
+    /// Closes all of the windows in the active workspace
+    CloseWorkspace {
+        #[serde(default = "crate::default_true")]
+        confirm: bool,
+    },
+    ActivateWatch {
+        watch: PaneWatch,
+        /// Show a toast notification when the watch alerts
+        #[serde(default)]
+        toast: bool,
+    },
+    ActivateKeyTable {
+        name: String,
+        timeout_milliseconds: Option<u64>,
+        #[serde(default)]
+        replace_current: bool,
+    },
+    /// Looks up the keys that follow in compose_sequences and the
+    /// RFC 1345 digraphs, and sends the resulting text to the pane
+    ComposeCharacter,
+    /// Reads the hexadecimal digits of a unicode codepoint and sends
+    /// the corresponding character to the pane
+    InputCodepoint,
+    /// Records the keys that are sent to panes, until StopKeyMacro,
+    /// so that they can be replayed by PlayKeyMacro
+    StartKeyMacro {
+        name: String,
+        /// Whether to also replay the time between the keys
+        #[serde(default)]
+        record_timing: bool,
+    },
+    StopKeyMacro,
+    PlayKeyMacro(String),
+    /// Sends the suggestion that is shown after the cursor
+    AcceptSuggestion,
+    /// Sends the next word of the suggestion that is shown after the cursor
+    AcceptSuggestionWord,
+    /// Switches the window to the named profile, or to the default
+    /// profile if no name is given.  When `tab` is true, only the
+    /// active tab uses the profile, and the window switches to and from
+    /// it as that tab is activated and deactivated.
+    SwitchProfile {
+        name: Option<String>,
+        #[serde(default)]
+        tab: bool,
+    },
 
     Multiple(Vec<KeyAssignment>),
 }
 impl_lua_conversion!(KeyAssignment);
 
+/// Converts a CamelCase variant name into a sentence,
+/// eg: `ShowTabNavigator` becomes `Show tab navigator`
+fn variant_words(name: &str) -> String {
+    let mut words = String::new();
+    for (idx, c) in name.chars().enumerate() {
+        if c.is_uppercase() && idx > 0 {
+            words.push(' ');
+            words.extend(c.to_lowercase());
+        } else {
+            words.push(c);
+        }
+    }
+    words
+}
+
+fn describe_spawn(verb: &str, spawn: &SpawnCommand) -> String {
+    let command = match (&spawn.label, &spawn.args) {
+        (Some(label), _) => label.clone(),
+        (None, Some(args)) => args.join(" "),
+        (None, None) => return verb.to_string(),
+    };
+    format!("{}: {}", verb, command)
+}
+
+fn describe_copy_destination(dest: &ClipboardCopyDestination) -> &'static str {
+    match dest {
+        ClipboardCopyDestination::Clipboard => "the clipboard",
+        ClipboardCopyDestination::PrimarySelection => "the primary selection",
+        ClipboardCopyDestination::ClipboardAndPrimarySelection => {
+            "the clipboard and primary selection"
+        }
+    }
+}
+
+fn describe_count(count: isize, unit: &str, backwards: &str, forwards: &str) -> String {
+    let direction = if count < 0 { backwards } else { forwards };
+    let count = count.abs();
+    format!(
+        "{} {} {}{}",
+        direction,
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+impl KeyAssignment {
+    /// Returns a short, human readable description of the action,
+    /// for showing alongside the keys that are assigned to it
+    pub fn description(&self) -> String {
+        use KeyAssignment::*;
+        match self {
+            SpawnTab(SpawnTabDomain::DomainName(name)) => format!("New tab in {}", name),
+            SpawnTab(SpawnTabDomain::DefaultDomain) => "New tab in the default domain".to_string(),
+            SpawnTab(SpawnTabDomain::CurrentPaneDomain) => "New tab".to_string(),
+            SpawnWindow => "New window".to_string(),
+            SpawnCommandInNewTab(spawn) => describe_spawn("New tab", spawn),
+            SpawnCommandInNewWindow(spawn) => describe_spawn("New window", spawn),
+            SplitHorizontal(spawn) => describe_spawn("Split horizontally", spawn),
+            SplitVertical(spawn) => describe_spawn("Split vertically", spawn),
+            CopyTo(dest) => format!("Copy to {}", describe_copy_destination(dest)),
+            CopyAsDisplayed(dest) => {
+                format!("Copy as displayed to {}", describe_copy_destination(dest))
+            }
+            CopyAsLogicalLines(dest) => format!(
+                "Copy as logical lines to {}",
+                describe_copy_destination(dest)
+            ),
+            CopyLastCommandOutput(dest) => format!(
+                "Copy last command output to {}",
+                describe_copy_destination(dest)
+            ),
+            PasteFrom(ClipboardPasteSource::Clipboard) => "Paste".to_string(),
+            PasteFrom(ClipboardPasteSource::PrimarySelection) => {
+                "Paste primary selection".to_string()
+            }
+            ActivateTabRelative(1) => "Next tab".to_string(),
+            ActivateTabRelative(-1) => "Previous tab".to_string(),
+            ActivateTabRelative(n) => describe_count(*n, "tab", "Back", "Forward"),
+            ActivateTab(-1) => "Activate last tab".to_string(),
+            ActivateTab(n) if *n < 0 => format!("Activate tab {} from the end", -n),
+            ActivateTab(n) => format!("Activate tab {}", n + 1),
+            ActivateLastTab => "Activate previous active tab".to_string(),
+            MoveTabRelative(n) => describe_count(*n, "place", "Move tab left", "Move tab right"),
+            MoveTab(n) => format!("Move tab to position {}", n + 1),
+            ScrollByPage(n) => describe_count(*n, "page", "Scroll up", "Scroll down"),
+            ScrollByLine(n) => describe_count(*n, "line", "Scroll up", "Scroll down"),
+            ScrollToPrompt(n) => describe_count(*n, "prompt", "Scroll up", "Scroll down"),
+            ScrollToSearchMatch(n) if *n < 0 => "Previous search match".to_string(),
+            ScrollToSearchMatch(_) => "Next search match".to_string(),
+            SendString(s) => format!("Send {:?}", s),
+            SendKey(key) => format!("Send {}", crate::keys::describe_key(&key.key, key.mods)),
+            SendSnippet(name) => format!("Send snippet {}", name),
+            CloseCurrentTab { .. } => "Close tab".to_string(),
+            CloseCurrentPane { .. } => "Close pane".to_string(),
+            CloseWorkspace { .. } => "Close workspace".to_string(),
+            ClearScrollback(_) => "Clear scrollback".to_string(),
+            Search(pattern) | SearchWithOptions { pattern, .. } => {
+                format!("Search for {:?}", &**pattern)
+            }
+            AdjustPaneSize(dir, n) => format!(
+                "Resize pane {} by {}",
+                variant_words(&format!("{:?}", dir)).to_lowercase(),
+                n
+            ),
+            ActivatePaneDirection(dir) => format!(
+                "Activate pane {}",
+                variant_words(&format!("{:?}", dir)).to_lowercase()
+            ),
+            RotatePanes(dir) => format!(
+                "Rotate panes {}",
+                variant_words(&format!("{:?}", dir)).to_lowercase()
+            ),
+            MovePaneToWindow(n) => format!("Move pane to window {}", n + 1),
+            MovePaneToWorkspace(name) => format!("Move pane to workspace {}", name),
+            SwitchToWorkspace { name } => format!("Switch to workspace {}", name),
+            EmitEvent(name) => format!("Emit {}", name),
+            ActivateKeyTable { name, .. } => format!("Activate key table {}", name),
+            StartKeyMacro { name, .. } => format!("Record key macro {}", name),
+            PlayKeyMacro(name) => format!("Play key macro {}", name),
+            SwitchProfile {
+                name: Some(name), ..
+            } => format!("Switch to profile {}", name),
+            SwitchProfile { name: None, .. } => "Switch to default profile".to_string(),
+            Multiple(actions) => actions
+                .iter()
+                .map(|action| action.description())
+                .collect::<Vec<_>>()
+                .join(", then "),
+            action => {
+                let debug = format!("{:?}", action);
+                let name = debug
+                    .split(|c: char| !c.is_alphanumeric())
+                    .next()
+                    .unwrap_or("");
+                variant_words(name)
+            }
+        }
+    }
+}
+
+/// The key table that applies while a suggestion from
+/// `enable_scrollback_suggestions` is shown
+pub const SUGGESTION_KEY_TABLE: &str = "suggestion";
+
 pub struct InputMap {
     keys: HashMap<(KeyCode, Modifiers), KeyAssignment>,
     mouse: HashMap<(MouseEventTrigger, Modifiers), KeyAssignment>,
-    leader: Option<LeaderKey>,
+    leaders: Vec<Leader>,
+    key_tables: HashMap<String, HashMap<(KeyCode, Modifiers), KeyAssignment>>,
+}
+
+/// A resolved leader key
+struct Leader {
+    key: KeyCode,
+    mods: Modifiers,
+    timeout: std::time::Duration,
+    key_table: Option<String>,
 }
 
 impl InputMap {
     pub fn new(config: &ConfigHandle) -> Self {
         let mut mouse = config.mouse_bindings();
 
-        let mut keys = config.key_bindings();
+        let KeyBindings {
+            mut keys,
+            mut key_tables,
+            ..
+        } = config.key_bindings();
+
+        let preference = config.key_map_preference;
 
-        let leader = config.leader.clone();
+        let leaders = config
+            .leader
+            .iter()
+            .chain(config.leaders.iter())
+            .map(|leader| {
+                let (key, mods) = leader.key.resolve(leader.mods, preference);
+                Leader {
+                    key,
+                    mods,
+                    timeout: std::time::Duration::from_millis(leader.timeout_milliseconds),
+                    key_table: leader.key_table.clone(),
+                }
+            })
+            .collect();
 
+        // When physical keys are preferred, the defaults are expressed
+        // in terms of physical keys too, so that they can be overridden
+        // and disabled in the same way as before
         macro_rules! k {
             ($([$mod:expr, $code:expr, $action:expr]),* $(,)?) => {
                 $(
-                keys.entry(($code, $mod)).or_insert($action);
+                keys.entry(DeferredKeyCode::from($code).resolve($mod, preference))
+                    .or_insert($action);
                 )*
             };
         }
@@ -306,6 +708,7 @@ impl InputMap {
                     Search(Pattern::CaseSensitiveString("".into()))
                 ],
                 [Modifiers::CTRL, KeyCode::Char('L'), ShowDebugOverlay],
+                [Modifiers::CTRL, KeyCode::Char('U'), InputCodepoint],
                 [ctrl_shift, KeyCode::Char(' '), QuickSelect],
                 // Font size manipulation
                 [Modifiers::CTRL, KeyCode::Char('-'), DecreaseFontSize],
@@ -544,6 +947,30 @@ impl InputMap {
                     },
                     ExtendSelectionToMouseCursor(Some(SelectionMode::Line))
                 ],
+                [
+                    Modifiers::ALT,
+                    MouseEventTrigger::Down {
+                        streak: 1,
+                        button: MouseButton::Left
+                    },
+                    SelectTextAtMouseCursor(SelectionMode::Block)
+                ],
+                [
+                    Modifiers::ALT,
+                    MouseEventTrigger::Drag {
+                        streak: 1,
+                        button: MouseButton::Left
+                    },
+                    ExtendSelectionToMouseCursor(Some(SelectionMode::Block))
+                ],
+                [
+                    Modifiers::ALT,
+                    MouseEventTrigger::Up {
+                        streak: 1,
+                        button: MouseButton::Left
+                    },
+                    CompleteSelection(ClipboardCopyDestination::PrimarySelection)
+                ],
                 [
                     Modifiers::NONE,
                     MouseEventTrigger::Down {
@@ -569,27 +996,92 @@ impl InputMap {
                     StartWindowDrag
                 ],
             );
+
+            // The keys that accept a suggestion from
+            // enable_scrollback_suggestions
+            let suggestion = key_tables
+                .entry(SUGGESTION_KEY_TABLE.to_string())
+                .or_default();
+            for (key, mods, action) in vec![
+                (KeyCode::RightArrow, Modifiers::NONE, AcceptSuggestion),
+                (KeyCode::Char('\t'), Modifiers::NONE, AcceptSuggestion),
+                (KeyCode::RightArrow, Modifiers::ALT, AcceptSuggestionWord),
+            ] {
+                suggestion.entry((key, mods)).or_insert(action);
+            }
         }
 
         keys.retain(|_, v| *v != KeyAssignment::DisableDefaultAssignment);
         mouse.retain(|_, v| *v != KeyAssignment::DisableDefaultAssignment);
+        for table in key_tables.values_mut() {
+            table.retain(|_, v| *v != KeyAssignment::DisableDefaultAssignment);
+        }
 
         Self {
             keys,
-            leader,
+            leaders,
             mouse,
+            key_tables,
         }
     }
 
-    pub fn is_leader(&self, key: &KeyCode, mods: Modifiers) -> Option<std::time::Duration> {
-        if let Some(leader) = self.leader.as_ref() {
-            if leader.key == *key && leader.mods == mods {
-                return Some(std::time::Duration::from_millis(
-                    leader.timeout_milliseconds,
-                ));
-            }
+    /// If `key` and `mods` match a leader key, returns the key table
+    /// that it activates and how long it remains active.
+    /// A key table of None activates the LEADER modifier.
+    pub fn is_leader(
+        &self,
+        key: &KeyCode,
+        mods: Modifiers,
+    ) -> Option<(Option<String>, std::time::Duration)> {
+        self.leaders
+            .iter()
+            .find(|leader| leader.key == *key && leader.mods == mods)
+            .map(|leader| (leader.key_table.clone(), leader.timeout))
+    }
+
+    /// Returns the bindings that apply while the key table `name` is
+    /// active or, if `name` is None, those that require the LEADER modifier,
+    /// with the LEADER modifier removed
+    pub fn key_table_entries(
+        &self,
+        name: Option<&str>,
+    ) -> Vec<(KeyCode, Modifiers, KeyAssignment)> {
+        match name {
+            Some(name) => self
+                .key_tables
+                .get(name)
+                .map(|table| {
+                    table
+                        .iter()
+                        .map(|((key, mods), action)| (key.clone(), *mods, action.clone()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            None => self
+                .keys
+                .iter()
+                .filter(|((_, mods), _)| mods.contains(Modifiers::LEADER))
+                .map(|((key, mods), action)| {
+                    (key.clone(), *mods - Modifiers::LEADER, action.clone())
+                })
+                .collect(),
         }
-        None
+    }
+
+    pub fn has_key_table(&self, name: &str) -> bool {
+        self.key_tables.contains_key(name)
+    }
+
+    pub fn lookup_key_table(
+        &self,
+        name: &str,
+        key: &KeyCode,
+        mods: Modifiers,
+    ) -> Option<KeyAssignment> {
+        self.key_tables
+            .get(name)?
+            .get(&key.normalize_shift(Self::remove_positional_alt(mods)))
+            .cloned()
     }
 
     fn remove_positional_alt(mods: Modifiers) -> Modifiers {
@@ -604,7 +1096,57 @@ impl InputMap {
 
     pub fn lookup_mouse(&self, event: MouseEventTrigger, mods: Modifiers) -> Option<KeyAssignment> {
         self.mouse
-            .get(&(event, Self::remove_positional_alt(mods)))
+            .get(&(event.normalize_wheel(), Self::remove_positional_alt(mods)))
             .cloned()
     }
+
+    /// Returns true if any gesture is bound to `button`, in which
+    /// case its movement needs to be tracked while it is held.
+    pub fn has_gestures(&self, button: MouseButton) -> bool {
+        self.mouse.keys().any(|(event, _)| match event {
+            MouseEventTrigger::Gesture { button: b, .. } => *b == button,
+            _ => false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn description() {
+        assert_eq!(
+            KeyAssignment::ShowTabNavigator.description(),
+            "Show tab navigator"
+        );
+        assert_eq!(
+            KeyAssignment::ActivateTab(1).description(),
+            "Activate tab 2"
+        );
+        assert_eq!(
+            KeyAssignment::ActivateTabRelative(-1).description(),
+            "Previous tab"
+        );
+        assert_eq!(
+            KeyAssignment::ScrollByPage(-2).description(),
+            "Scroll up 2 pages"
+        );
+        assert_eq!(
+            KeyAssignment::CloseCurrentPane { confirm: true }.description(),
+            "Close pane"
+        );
+        assert_eq!(
+            KeyAssignment::SplitVertical(SpawnCommand {
+                args: Some(vec!["htop".to_string()]),
+                ..Default::default()
+            })
+            .description(),
+            "Split vertically: htop"
+        );
+        assert_eq!(
+            KeyAssignment::AdjustPaneSize(PaneDirection::Left, 5).description(),
+            "Resize pane left by 5"
+        );
+    }
 }
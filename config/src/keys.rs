@@ -2,7 +2,7 @@ use crate::{KeyAssignment, MouseEventTrigger};
 use luahelper::impl_lua_conversion;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
-use wezterm_input_types::{KeyCode, Modifiers};
+use wezterm_input_types::{KeyCode, Modifiers, PhysKeyCode};
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct KeyNoAction {
@@ -13,10 +13,83 @@ pub struct KeyNoAction {
 }
 impl_lua_conversion!(KeyNoAction);
 
+/// Whether keys that could be either a physical key position or the
+/// character produced by the current keyboard layout are treated as
+/// the former or the latter
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMapPreference {
+    Physical,
+    Mapped,
+}
+impl_lua_conversion!(KeyMapPreference);
+
+impl Default for KeyMapPreference {
+    fn default() -> Self {
+        Self::Mapped
+    }
+}
+
+/// A key from the configuration whose interpretation may depend
+/// upon the `key_map_preference` option.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub enum DeferredKeyCode {
+    /// Explicitly `phys:` or `mapped:`, or a key that has no
+    /// alternative interpretation
+    KeyCode(KeyCode),
+    Either {
+        physical: PhysKeyCode,
+        mapped: KeyCode,
+    },
+}
+
+impl DeferredKeyCode {
+    /// Resolves the key and its modifiers according to `preference`.
+    /// An upper case letter implies SHIFT, which has to be made
+    /// explicit when it is resolved to its physical key.
+    pub fn resolve(&self, mods: Modifiers, preference: KeyMapPreference) -> (KeyCode, Modifiers) {
+        match (self, preference) {
+            (Self::KeyCode(key), _) => key.normalize_shift(mods),
+            (Self::Either { physical, mapped }, KeyMapPreference::Physical) => {
+                let mods = match mapped {
+                    KeyCode::Char(c) if c.is_ascii_uppercase() => mods | Modifiers::SHIFT,
+                    _ => mods,
+                };
+                (KeyCode::Physical(*physical), mods)
+            }
+            (Self::Either { mapped, .. }, KeyMapPreference::Mapped) => mapped.normalize_shift(mods),
+        }
+    }
+}
+
+impl From<KeyCode> for DeferredKeyCode {
+    fn from(key: KeyCode) -> Self {
+        if let KeyCode::Char(c) = &key {
+            // Whitespace and control characters are the same
+            // in all layouts
+            if c.is_ascii_graphic() {
+                if let Some(physical) = PhysKeyCode::from_us_char(*c) {
+                    // Only unshifted characters have a physical equivalent;
+                    // upper case letters are the exception as SHIFT is
+                    // normalized away for those
+                    if c.is_ascii_uppercase() || physical.to_us_key_code() == key {
+                        return Self::Either {
+                            physical,
+                            mapped: key,
+                        };
+                    }
+                }
+            }
+        }
+        Self::KeyCode(key)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Key {
-    #[serde(deserialize_with = "de_keycode")]
-    pub key: KeyCode,
+    /// Either a single key, or a sequence of keys that must be
+    /// pressed one after the other.  `mods` applies to the first key.
+    #[serde(deserialize_with = "de_key_sequence")]
+    pub key: Vec<DeferredKeyCode>,
     #[serde(deserialize_with = "de_modifiers", default)]
     pub mods: Modifiers,
     pub action: KeyAssignment,
@@ -25,16 +98,20 @@ impl_lua_conversion!(Key);
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LeaderKey {
-    #[serde(deserialize_with = "de_keycode")]
-    pub key: KeyCode,
+    #[serde(deserialize_with = "de_deferred_keycode")]
+    pub key: DeferredKeyCode,
     #[serde(deserialize_with = "de_modifiers", default)]
     pub mods: Modifiers,
     #[serde(default = "default_leader_timeout")]
     pub timeout_milliseconds: u64,
+    /// If set, the keys in this table are consulted for the next key
+    /// press, rather than those bound with the LEADER modifier
+    #[serde(default)]
+    pub key_table: Option<String>,
 }
 impl_lua_conversion!(LeaderKey);
 
-fn default_leader_timeout() -> u64 {
+pub fn default_leader_timeout() -> u64 {
     1000
 }
 
@@ -148,18 +225,83 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
+    parse_keycode(&s).map_err(serde::de::Error::custom)
+}
+
+fn de_deferred_keycode<'de, D>(deserializer: D) -> Result<DeferredKeyCode, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_deferred_keycode(&s).map_err(serde::de::Error::custom)
+}
+
+fn parse_deferred_keycode(s: &str) -> Result<DeferredKeyCode, String> {
+    if s.starts_with("phys:") || s.starts_with("mapped:") {
+        return parse_keycode(s).map(DeferredKeyCode::KeyCode);
+    }
+
+    parse_keycode(s).map(DeferredKeyCode::from)
+}
+
+/// Accepts either a single key, or a non-empty sequence of keys
+fn de_key_sequence<'de, D>(deserializer: D) -> Result<Vec<DeferredKeyCode>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct KeySequenceVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for KeySequenceVisitor {
+        type Value = Vec<DeferredKeyCode>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a key, or a sequence of keys")
+        }
+
+        fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(vec![parse_deferred_keycode(s).map_err(E::custom)?])
+        }
 
-    if let Some(c) = KEYCODE_MAP.get(&s) {
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            let mut keys = vec![];
+            while let Some(s) = seq.next_element::<String>()? {
+                keys.push(parse_deferred_keycode(&s).map_err(serde::de::Error::custom)?);
+            }
+            if keys.is_empty() {
+                return Err(serde::de::Error::custom(
+                    "a sequence of keys must contain at least one key",
+                ));
+            }
+            Ok(keys)
+        }
+    }
+
+    deserializer.deserialize_any(KeySequenceVisitor)
+}
+
+fn parse_keycode(s: &str) -> Result<KeyCode, String> {
+    if let Some(name) = s.strip_prefix("phys:") {
+        return PhysKeyCode::from_name(name)
+            .map(KeyCode::Physical)
+            .ok_or_else(|| format!("invalid physical key name in {}", s));
+    }
+
+    let s = s.strip_prefix("mapped:").unwrap_or(s);
+
+    if let Some(c) = KEYCODE_MAP.get(s) {
         return Ok(c.clone());
     }
 
     if s.len() > 4 && s.starts_with("raw:") {
-        let num: u32 = s[4..].parse().map_err(|_| {
-            serde::de::Error::custom(format!(
-                "expected raw:<NUMBER> raw keycode string, got: {}",
-                s
-            ))
-        })?;
+        let num: u32 = s[4..]
+            .parse()
+            .map_err(|_| format!("expected raw:<NUMBER> raw keycode string, got: {}", s))?;
         return Ok(KeyCode::RawCode(num));
     }
 
@@ -167,10 +309,7 @@ where
     if chars.len() == 1 {
         Ok(KeyCode::Char(chars[0]))
     } else {
-        Err(serde::de::Error::custom(format!(
-            "invalid KeyCode string {}",
-            s
-        )))
+        Err(format!("invalid KeyCode string {}", s))
     }
 }
 
@@ -179,6 +318,12 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
+    parse_modifiers(&s).map_err(serde::de::Error::custom)
+}
+
+/// Parses a modifier string such as `CTRL|SHIFT`, using the same
+/// syntax as the `mods` field of a key binding
+pub fn parse_modifiers(s: &str) -> Result<Modifiers, String> {
     let mut mods = Modifiers::NONE;
     for ele in s.split('|') {
         // Allow for whitespace; debug printing Modifiers includes spaces
@@ -198,11 +343,218 @@ where
         } else if ele == "NONE" || ele == "" {
             mods |= Modifiers::NONE;
         } else {
-            return Err(serde::de::Error::custom(format!(
-                "invalid modifier name {} in {}",
-                ele, s
-            )));
+            return Err(format!("invalid modifier name {} in {}", ele, s));
         }
     }
     Ok(mods)
 }
+
+/// Produces a short, human readable description of a key combination,
+/// such as `CTRL+SHIFT+a`, for display in the UI
+pub fn describe_key(key: &KeyCode, mods: Modifiers) -> String {
+    let mut desc = String::new();
+    for (flag, name) in &[
+        (Modifiers::LEADER, "LEADER"),
+        (Modifiers::SUPER, "SUPER"),
+        (Modifiers::CTRL, "CTRL"),
+        (Modifiers::ALT, "ALT"),
+        (Modifiers::LEFT_ALT, "LEFT_ALT"),
+        (Modifiers::RIGHT_ALT, "RIGHT_ALT"),
+        (Modifiers::SHIFT, "SHIFT"),
+    ] {
+        if mods.contains(*flag) {
+            desc.push_str(name);
+            desc.push('+');
+        }
+    }
+
+    match key {
+        KeyCode::Char(' ') => desc.push_str("Space"),
+        KeyCode::Char('\r') => desc.push_str("Enter"),
+        KeyCode::Char('\t') => desc.push_str("Tab"),
+        KeyCode::Char('\u{1b}') => desc.push_str("Escape"),
+        KeyCode::Char('\u{8}') => desc.push_str("Backspace"),
+        KeyCode::Char('\u{7f}') => desc.push_str("Delete"),
+        KeyCode::Char(c) => desc.push(*c),
+        KeyCode::Physical(phys) => return describe_key(&phys.to_us_key_code(), mods),
+        KeyCode::RawCode(code) => desc.push_str(&format!("raw:{}", code)),
+        KeyCode::Function(n) => desc.push_str(&format!("F{}", n)),
+        KeyCode::Numpad(n) => desc.push_str(&format!("Numpad{}", n)),
+        key => desc.push_str(&format!("{:?}", key)),
+    }
+    desc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde::de::value::{Error, StrDeserializer};
+    use serde::de::IntoDeserializer;
+
+    fn parse(s: &str) -> Result<DeferredKeyCode, String> {
+        let deserializer: StrDeserializer<Error> = s.into_deserializer();
+        de_deferred_keycode(deserializer).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn deferred() {
+        let ctrl = Modifiers::CTRL;
+        let a = parse("a").unwrap();
+        assert_eq!(
+            a.resolve(ctrl, KeyMapPreference::Mapped),
+            (KeyCode::Char('a'), ctrl)
+        );
+        assert_eq!(
+            a.resolve(ctrl, KeyMapPreference::Physical),
+            (KeyCode::Physical(PhysKeyCode::A), ctrl)
+        );
+
+        let upper = parse("C").unwrap();
+        assert_eq!(
+            upper.resolve(ctrl, KeyMapPreference::Physical),
+            (KeyCode::Physical(PhysKeyCode::C), ctrl | Modifiers::SHIFT)
+        );
+
+        // Shifted punctuation has no physical equivalent
+        assert_eq!(
+            parse("%").unwrap(),
+            DeferredKeyCode::KeyCode(KeyCode::Char('%'))
+        );
+
+        assert_eq!(
+            parse("phys:Semicolon")
+                .unwrap()
+                .resolve(ctrl, KeyMapPreference::Mapped),
+            (KeyCode::Physical(PhysKeyCode::Semicolon), ctrl)
+        );
+        assert_eq!(
+            parse("phys:1")
+                .unwrap()
+                .resolve(ctrl, KeyMapPreference::Mapped),
+            (KeyCode::Physical(PhysKeyCode::K1), ctrl)
+        );
+        assert_eq!(
+            parse("mapped:a")
+                .unwrap()
+                .resolve(ctrl, KeyMapPreference::Physical),
+            (KeyCode::Char('a'), ctrl)
+        );
+        assert_eq!(
+            parse("Enter").unwrap(),
+            DeferredKeyCode::KeyCode(KeyCode::Char('\r'))
+        );
+        assert!(parse("phys:Nope").is_err());
+    }
+
+    #[test]
+    fn sequence() {
+        fn parse_seq<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<DeferredKeyCode>, String> {
+            de_key_sequence(d).map_err(|e| e.to_string())
+        }
+
+        let deserializer: StrDeserializer<Error> = "g".into_deserializer();
+        assert_eq!(
+            parse_seq(deserializer).unwrap(),
+            vec![DeferredKeyCode::from(KeyCode::Char('g'))]
+        );
+
+        let deserializer: serde::de::value::SeqDeserializer<_, Error> =
+            vec!["g", "Enter"].into_deserializer();
+        assert_eq!(
+            parse_seq(deserializer).unwrap(),
+            vec![
+                DeferredKeyCode::from(KeyCode::Char('g')),
+                DeferredKeyCode::KeyCode(KeyCode::Char('\r'))
+            ]
+        );
+
+        let deserializer: serde::de::value::SeqDeserializer<_, Error> =
+            Vec::<String>::new().into_deserializer();
+        assert!(parse_seq(deserializer).is_err());
+    }
+
+    #[test]
+    fn sequence_bindings() {
+        fn key(keys: &[char], mods: Modifiers, action: KeyAssignment) -> Key {
+            Key {
+                key: keys
+                    .iter()
+                    .map(|c| DeferredKeyCode::from(KeyCode::Char(*c)))
+                    .collect(),
+                mods,
+                action,
+            }
+        }
+
+        let config = crate::Config {
+            keys: vec![
+                key(&['g', 't'], Modifiers::NONE, KeyAssignment::ActivateTab(0)),
+                key(&['g', 'T'], Modifiers::NONE, KeyAssignment::ActivateTab(1)),
+            ],
+            ..Default::default()
+        };
+        let bindings = config.key_bindings();
+        assert!(bindings.conflicts.is_empty());
+        assert_eq!(
+            bindings.keys.get(&(KeyCode::Char('g'), Modifiers::NONE)),
+            Some(&KeyAssignment::ActivateKeyTable {
+                name: "g".to_string(),
+                timeout_milliseconds: None,
+                replace_current: true,
+            })
+        );
+        let table = &bindings.key_tables["g"];
+        assert_eq!(
+            table.get(&(KeyCode::Char('t'), Modifiers::NONE)),
+            Some(&KeyAssignment::ActivateTab(0))
+        );
+        assert_eq!(
+            table.get(&(KeyCode::Char('T'), Modifiers::NONE)),
+            Some(&KeyAssignment::ActivateTab(1))
+        );
+
+        let config = crate::Config {
+            keys: vec![
+                key(
+                    &['g', 't', 'x'],
+                    Modifiers::CTRL,
+                    KeyAssignment::ActivateTab(0),
+                ),
+                key(&['g', 't'], Modifiers::CTRL, KeyAssignment::ActivateTab(1)),
+                key(&['g'], Modifiers::CTRL, KeyAssignment::ActivateTab(2)),
+            ],
+            ..Default::default()
+        };
+        let bindings = config.key_bindings();
+        assert_eq!(
+            bindings.conflicts,
+            vec![
+                "the key sequence `CTRL+g t x` conflicts with another assignment for `CTRL+g`"
+                    .to_string(),
+                "the key sequence `CTRL+g t` conflicts with another assignment for `CTRL+g`"
+                    .to_string(),
+            ]
+        );
+        assert!(bindings.check_conflicts().is_err());
+    }
+
+    #[test]
+    fn describe() {
+        assert_eq!(describe_key(&KeyCode::Char('a'), Modifiers::NONE), "a");
+        assert_eq!(
+            describe_key(&KeyCode::Char('a'), Modifiers::CTRL | Modifiers::SHIFT),
+            "CTRL+SHIFT+a"
+        );
+        assert_eq!(
+            describe_key(&KeyCode::Char('\r'), Modifiers::LEADER),
+            "LEADER+Enter"
+        );
+        assert_eq!(
+            describe_key(&KeyCode::Physical(PhysKeyCode::K1), Modifiers::ALT),
+            "ALT+1"
+        );
+        assert_eq!(describe_key(&KeyCode::Function(5), Modifiers::NONE), "F5");
+        assert_eq!(describe_key(&KeyCode::PageUp, Modifiers::NONE), "PageUp");
+    }
+}
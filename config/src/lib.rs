@@ -26,34 +26,65 @@ use termwiz::hyperlink;
 use termwiz::surface::CursorShape;
 use toml;
 use wezterm_input_types::{KeyCode, Modifiers, WindowDecorations};
+use wezterm_term::{TerminalCompatibility, TerminalQuery};
 
+mod access;
+mod accessibility;
 mod background;
 mod bell;
+mod changes;
 mod color;
 mod daemon;
+mod droppedfiles;
+mod editor;
+mod envfile;
+mod exec;
 mod font;
 mod frontend;
+mod include;
 pub mod keyassignment;
 mod keys;
 pub mod lua;
+mod normalize;
+mod profile;
+mod project;
+mod remotecontrol;
+mod snippet;
 mod ssh;
 mod terminal;
 mod tls;
 mod unix;
 mod version;
+mod workspace;
+mod wsl;
 
+pub use access::*;
+pub use accessibility::*;
 pub use background::*;
 pub use bell::*;
+pub use changes::*;
 pub use color::*;
 pub use daemon::*;
+pub use droppedfiles::*;
+pub use editor::*;
+pub use envfile::*;
+pub use exec::*;
 pub use font::*;
 pub use frontend::*;
+pub use include::*;
 pub use keys::*;
+pub use normalize::*;
+pub use profile::*;
+pub use project::*;
+pub use remotecontrol::*;
+pub use snippet::*;
 pub use ssh::*;
 pub use terminal::*;
 pub use tls::*;
 pub use unix::*;
 pub use version::*;
+pub use workspace::*;
+pub use wsl::*;
 
 type LuaFactory = fn(&Path) -> anyhow::Result<Lua>;
 type ErrorCallback = fn(&str);
@@ -62,6 +93,7 @@ lazy_static! {
     pub static ref HOME_DIR: PathBuf = dirs_next::home_dir().expect("can't find HOME dir");
     pub static ref CONFIG_DIR: PathBuf = xdg_config_home();
     pub static ref RUNTIME_DIR: PathBuf = compute_runtime_dir().unwrap();
+    pub static ref DATA_DIR: PathBuf = compute_data_dir();
     static ref CONFIG: Configuration = Configuration::new();
     static ref CONFIG_FILE_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
     static ref CONFIG_OVERRIDES: Mutex<Vec<(String, String)>> = Mutex::new(vec![]);
@@ -208,6 +240,15 @@ where
     func(lua)
 }
 
+/// Returns the number of bytes used by the lua state of the most
+/// recently loaded configuration, if any.
+/// This function MUST only be called from the main thread.
+pub fn lua_memory_usage() -> Option<usize> {
+    run_immediate_with_lua_config(|lua| Ok(lua.map(|lua| lua.used_memory())))
+        .ok()
+        .flatten()
+}
+
 fn schedule_with_lua<F, RETF, RET>(func: F) -> promise::spawn::Task<anyhow::Result<RET>>
 where
     F: 'static,
@@ -254,7 +295,7 @@ fn default_config_with_overrides_applied() -> anyhow::Result<Config> {
         .context("Error converting lua value from overrides to Config struct")?;
     // Compute but discard the key bindings here so that we raise any
     // problems earlier than we use them.
-    let _ = cfg.key_bindings();
+    cfg.key_bindings().check_conflicts()?;
 
     Ok(cfg)
 }
@@ -716,6 +757,25 @@ impl Default for ExitBehavior {
     }
 }
 
+/// How the current working directory of a local pane is determined
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum CwdTracking {
+    /// Use the directory reported by the shell via OSC 7, falling
+    /// back to asking the operating system about the foreground process
+    PreferOsc7,
+    /// Ask the operating system about the foreground process, falling
+    /// back to the directory reported via OSC 7
+    PreferProcess,
+    /// Only use the directory reported via OSC 7
+    Osc7Only,
+}
+
+impl Default for CwdTracking {
+    fn default() -> Self {
+        CwdTracking::PreferOsc7
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     /// The font size, measured in points
@@ -783,6 +843,18 @@ pub struct Config {
     #[serde(default = "default_scrollback_lines")]
     pub scrollback_lines: usize,
 
+    /// If true, the scrollback of each pane is indexed as lines are
+    /// added to it, so that searches only need to scan the parts of
+    /// it that might match
+    #[serde(default)]
+    pub enable_scrollback_search_index: bool,
+
+    /// The maximum amount of memory, in bytes, that the search index
+    /// of a pane may use.  When it is exceeded, the oldest part of the
+    /// scrollback is dropped from the index and is scanned instead.
+    #[serde(default = "default_scrollback_search_index_max_bytes")]
+    pub scrollback_search_index_max_bytes: usize,
+
     /// If no `prog` is specified on the command line, use this
     /// instead of running the user's shell.
     /// For example, to have `wezterm` always run `top` by default,
@@ -797,14 +869,104 @@ pub struct Config {
     /// as the positional arguments to that command.
     pub default_prog: Option<Vec<String>>,
 
+    /// Rules that select the program, environment and title to
+    /// use when spawning into a directory that belongs to a
+    /// recognized type of project
+    #[serde(default)]
+    pub project_rules: Vec<ProjectRule>,
+
+    /// If true, when spawning into a local directory that contains
+    /// (or has a parent that contains) a `.env` or direnv `.envrc`
+    /// file, load environment variables from it.  Each file must be
+    /// approved by the user the first time that it is used.
+    #[serde(default)]
+    pub load_env_files: bool,
+
+    /// If true, commands that are reported via OSC 133 shell
+    /// integration are recorded so that they can be recalled
+    /// using the ShowCommandHistory key assignment
+    #[serde(default)]
+    pub enable_command_history: bool,
+
+    /// If true, suggest a completion for the text at the prompt, based
+    /// on the command history and the words in the scrollback.  The
+    /// suggestion is shown in dim text after the cursor, and can be
+    /// accepted using the keys in the `suggestion` key table.
+    #[serde(default)]
+    pub enable_scrollback_suggestions: bool,
+
+    /// When a command that is reported via OSC 133 shell integration
+    /// takes at least this many seconds to complete, and the pane that
+    /// ran it isn't being looked at, show a notification and highlight
+    /// its tab.  0 disables these notifications.
+    #[serde(default)]
+    pub command_completion_notification_threshold: u64,
+
+    /// The directory in which the StartRecording key assignment
+    /// saves recordings.  Defaults to the home directory.
+    #[serde(default)]
+    pub recording_directory: Option<PathBuf>,
+
+    /// If true, a report is written to the data directory when
+    /// wezterm panics, and the GUI offers to show it on the next
+    /// launch
+    #[serde(default)]
+    pub enable_crash_reports: bool,
+
+    /// The editor used by ViewLastCommandOutputInEditor, expressed
+    /// as a program and its arguments.  The path to the file that
+    /// should be opened is appended to the arguments.
+    /// If not specified, the VISUAL or EDITOR environment variables
+    /// are consulted.
+    pub editor: Option<Vec<String>>,
+
     /// Specifies the default current working directory if none is specified
     /// through configuration or OSC 7 (see docs for `default_cwd` for more
     /// info!)
     pub default_cwd: Option<PathBuf>,
 
+    /// Controls what is carried over from the current pane when
+    /// spawning a new split, tab or window.  May be overridden by
+    /// the `inherit` field of a SpawnCommand.
+    #[serde(default)]
+    pub spawn_inheritance: SpawnInheritance,
+
+    /// The name of the workspace that windows will be placed into
+    /// when no other workspace has been selected.  Each client that
+    /// attaches to a mux starts out viewing this workspace.
+    #[serde(default = "default_workspace")]
+    pub default_workspace: String,
+
+    /// Named workspaces, along with the programs that are started
+    /// in them when they are opened for the first time
+    #[serde(default)]
+    pub workspaces: Vec<WorkspaceDefinition>,
+
+    /// When true, the windows of every workspace remain visible, rather
+    /// than only those of the active workspace.  Switching workspaces
+    /// raises a window of that workspace, and focusing a window makes
+    /// its workspace the active workspace.
+    #[serde(default)]
+    pub window_per_workspace: bool,
+
+    /// Named bundles of font, color, padding and domain settings that
+    /// can be switched to at runtime via `SwitchProfile`
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+
+    /// The profile used by windows that haven't switched to another
+    /// one.  `wezterm start --profile NAME` sets this.
+    #[serde(default)]
+    pub default_profile: Option<String>,
+
     #[serde(default)]
     pub exit_behavior: ExitBehavior,
 
+    /// Controls how the current working directory of local panes
+    /// is determined
+    #[serde(default)]
+    pub cwd_tracking: CwdTracking,
+
     /// Specifies a map of environment variables that should be set
     /// when spawning commands in the local domain.
     /// This is not used when working with remote domains.
@@ -819,6 +981,19 @@ pub struct Config {
     #[serde(default)]
     pub enable_kitty_graphics: bool,
 
+    /// The version of unicode used to compute the width of text.
+    /// Characters with emoji presentation are two cells wide from
+    /// version 9 onwards.  Applications can change the version in
+    /// use by a pane with the iTerm2 `UnicodeVersion` escape sequence.
+    #[serde(default = "default_unicode_version")]
+    pub unicode_version: u8,
+
+    /// When true, characters whose East Asian Width property is
+    /// Ambiguous are treated as two cells wide.  This can be
+    /// overridden for an individual ssh, unix, tls or exec domain.
+    #[serde(default)]
+    pub treat_east_asian_ambiguous_width_as_wide: bool,
+
     /// Specifies the width of a new window, expressed in character cells
     #[serde(default = "default_initial_cols")]
     pub initial_cols: u16,
@@ -826,6 +1001,30 @@ pub struct Config {
     #[serde(default = "default_hyperlink_rules")]
     pub hyperlink_rules: Vec<hyperlink::Rule>,
 
+    /// How to quote the paths of files that are dropped onto a pane
+    #[serde(default)]
+    pub quote_dropped_files: DroppedFileQuoting,
+
+    /// Overrides `quote_dropped_files` when the foreground process
+    /// of the pane matches one of the keys, which use the same patterns
+    /// as `skip_close_confirmation_for_processes_named`
+    #[serde(default)]
+    pub quote_dropped_files_by_process: HashMap<String, DroppedFileQuoting>,
+
+    /// What to do when files are dropped onto a pane in a remote domain
+    #[serde(default)]
+    pub dropped_files_remote_policy: RemoteDropPolicy,
+
+    /// The unicode normalization to apply to text that is typed or
+    /// pasted into a pane
+    #[serde(default)]
+    pub input_normalization: InputNormalization,
+
+    /// Configures how `file:line` locations are recognized in the
+    /// output and opened in an editor
+    #[serde(default)]
+    pub open_in_editor: OpenInEditor,
+
     /// What to set the TERM variable to
     #[serde(default = "default_term")]
     pub term: String,
@@ -886,6 +1085,12 @@ pub struct Config {
     #[serde(default = "default_harfbuzz_features")]
     pub harfbuzz_features: Vec<String>,
 
+    /// When true, the text under the cursor is shaped separately from
+    /// the text either side of it, so that ligatures are broken up into
+    /// their constituent characters where the cursor is positioned
+    #[serde(default)]
+    pub disable_ligatures_at_cursor: bool,
+
     #[serde(default)]
     pub front_end: FrontEndSelection,
 
@@ -896,6 +1101,28 @@ pub struct Config {
     #[serde(default)]
     pub ssh_domains: Vec<SshDomain>,
 
+    /// Controls what happens when the `term` terminfo entry is
+    /// missing on a host that is connected to via `wezterm ssh`
+    /// or an ssh multiplexing domain.
+    /// The first rule whose host pattern matches is used; if none
+    /// match, no check is made.
+    #[serde(default)]
+    pub ssh_terminfo: Vec<SshTerminfoRule>,
+
+    /// The set of domains whose panes are spawned via a local
+    /// wrapper command
+    #[serde(default)]
+    pub exec_domains: Vec<ExecDomain>,
+
+    /// Container runtimes, such as "docker" or "podman", whose
+    /// running containers are made available as domains
+    #[serde(default)]
+    pub container_runtimes: Vec<String>,
+
+    /// How often, in seconds, to refresh the list of containers
+    #[serde(default = "default_container_refresh_interval")]
+    pub container_refresh_interval: u64,
+
     /// When running in server mode, defines configuration for
     /// each of the endpoints that we'll listen for connections
     #[serde(default)]
@@ -920,6 +1147,27 @@ pub struct Config {
     #[serde(default = "default_mux_output_parser_buffer_size")]
     pub mux_output_parser_buffer_size: usize,
 
+    /// How long to wait for more output before applying a partial
+    /// batch of output to the terminal, so that a program that writes
+    /// its screen in several small pieces is rendered in one go.
+    /// 0 applies output as soon as it is read.
+    #[serde(default = "default_mux_output_parser_coalesce_delay_ms")]
+    pub mux_output_parser_coalesce_delay_ms: u64,
+
+    /// When true, the size of a batch of output is reduced from
+    /// mux_output_parser_buffer_size while applying a batch takes
+    /// longer than half a frame, so that a flood of output doesn't
+    /// make the window unresponsive.
+    #[serde(default = "default_true")]
+    pub mux_output_adaptive_batching: bool,
+
+    /// How many bytes of output may be buffered ahead of the parser.
+    /// Once this much is pending, output is no longer read from the
+    /// pty, and a program that writes more blocks until the terminal
+    /// catches up.
+    #[serde(default = "default_mux_output_backpressure_bytes")]
+    pub mux_output_backpressure_bytes: usize,
+
     #[serde(default)]
     pub keys: Vec<Key>,
     #[serde(
@@ -935,6 +1183,66 @@ pub struct Config {
     pub disable_default_key_bindings: bool,
     pub leader: Option<LeaderKey>,
 
+    /// Additional leader keys; each of these activates the key table
+    /// named by its `key_table` field
+    #[serde(default)]
+    pub leaders: Vec<LeaderKey>,
+
+    /// Named sets of key bindings that can be activated by a leader
+    /// key or the ActivateKeyTable action
+    #[serde(default)]
+    pub key_tables: HashMap<String, Vec<Key>>,
+
+    /// Whether to show the active leader and key tables, along with
+    /// the time remaining before they are cancelled
+    #[serde(default = "default_true")]
+    pub show_leader_indicator: bool,
+
+    /// Whether to list the available key bindings when a leader
+    /// or key table has been active for which_key_delay_milliseconds
+    #[serde(default = "default_true")]
+    pub show_which_key: bool,
+    #[serde(default = "default_which_key_delay")]
+    pub which_key_delay_milliseconds: u64,
+
+    /// How long the labels shown by the ShowPaneNumbers action
+    /// remain visible
+    #[serde(default = "default_pane_numbers_timeout")]
+    pub pane_numbers_timeout_milliseconds: u64,
+
+    /// Sequences, typed after the ComposeCharacter action, that
+    /// produce the associated text.  These take precedence over
+    /// the built-in digraphs.
+    #[serde(default)]
+    pub compose_sequences: HashMap<String, String>,
+
+    /// Whether ComposeCharacter recognizes the RFC 1345 digraphs
+    #[serde(default = "default_true")]
+    pub enable_rfc1345_digraphs: bool,
+
+    /// Text that can be inserted into panes via the SendSnippet
+    /// action or the snippet picker
+    #[serde(default)]
+    pub snippets: Vec<Snippet>,
+
+    /// The characters used to label the panes for PaneSelect
+    #[serde(default = "default_alphabet")]
+    pub pane_select_alphabet: String,
+    /// The minimum size of the PaneSelect labels, as a fraction
+    /// of the width and height of the pane
+    #[serde(default = "default_pane_select_label_scale")]
+    pub pane_select_label_scale: f64,
+    /// Whether to dim the panes while PaneSelect is active,
+    /// using inactive_pane_hsb
+    #[serde(default = "default_true")]
+    pub pane_select_dim_background: bool,
+
+    /// Whether single character keys in `keys` and `leader` refer to
+    /// the physical key in that position on a US keyboard, or to the
+    /// character produced by the current keyboard layout
+    #[serde(default)]
+    pub key_map_preference: KeyMapPreference,
+
     #[serde(default)]
     pub disable_default_quick_select_patterns: bool,
     #[serde(default)]
@@ -950,6 +1258,11 @@ pub struct Config {
     #[serde(default)]
     pub daemon_options: DaemonOptions,
 
+    /// If set, wezterm-mux-server will exit once it has had no panes
+    /// and no connected clients for this many seconds
+    #[serde(default)]
+    pub exit_when_idle_for: Option<u64>,
+
     /// If set to true, send the system specific composed key when
     /// the ALT key is held down.  If set to false
     /// then send the key with the ALT modifier (this is typically
@@ -1003,6 +1316,13 @@ pub struct Config {
     #[serde(default)]
     pub enable_scroll_bar: bool,
 
+    /// If true, the prompt line of each command that completed is
+    /// decorated with its duration and exit status, as reported via
+    /// OSC 133 shell integration.
+    /// This can be toggled per-pane using ToggleCommandBadges.
+    #[serde(default)]
+    pub show_command_badges: bool,
+
     /// If false, do not try to use a Wayland protocol connection
     /// when starting the gui frontend, and instead use X11.
     /// This option is only considered on X11/Wayland systems and
@@ -1041,6 +1361,21 @@ pub struct Config {
     #[serde(default)]
     pub foreground_text_hsb: HsbTransform,
 
+    /// When the contrast ratio between the foreground and background
+    /// colors of text is lower than this value, the foreground color
+    /// is lightened or darkened to bring it up to this ratio.
+    /// The ratio is computed as described by WCAG 2.0, and ranges
+    /// from 1.0 (which disables the adjustment) to 21.0.
+    #[serde(default = "default_one_point_oh")]
+    pub minimum_contrast_ratio: f32,
+
+    /// When true, colors are treated as sRGB and are converted to
+    /// the color profile of the display by the system, which prevents
+    /// them from appearing over-saturated on wide gamut displays.
+    /// This is currently only implemented on macOS.
+    #[serde(default = "default_true")]
+    pub enable_color_management: bool,
+
     /// Specifies the alpha value to use when rendering the background
     /// of the window.  The background is taken either from the
     /// window_background_image, or if there is none, the background
@@ -1098,6 +1433,15 @@ pub struct Config {
     #[serde(default = "default_cursor_blink_rate")]
     pub cursor_blink_rate: u64,
 
+    /// Override `cursor_blink_rate` for blinking block, underline
+    /// and bar cursors respectively
+    #[serde(default)]
+    pub block_cursor_blink_rate: Option<u64>,
+    #[serde(default)]
+    pub underline_cursor_blink_rate: Option<u64>,
+    #[serde(default)]
+    pub bar_cursor_blink_rate: Option<u64>,
+
     #[serde(default)]
     pub force_reverse_video_cursor: bool,
 
@@ -1112,6 +1456,17 @@ pub struct Config {
     #[serde(default)]
     pub default_cursor_style: DefaultCursorStyle,
 
+    /// When set, the cursor is always drawn in this style, ignoring
+    /// the style requested by applications
+    #[serde(default)]
+    pub force_cursor_style: Option<DefaultCursorStyle>,
+
+    /// When true, the cursor style that was in effect before an
+    /// application activated the alternate screen is restored when
+    /// it returns to the primary screen
+    #[serde(default = "default_true")]
+    pub restore_cursor_style_after_alt_screen: bool,
+
     /// Specifies how often blinking text (normal speed) transitions
     /// between visible and invisible, expressed in milliseconds.
     /// Setting this to 0 disables slow text blinking.  Note that this
@@ -1162,6 +1517,9 @@ pub struct Config {
     #[serde(default = "default_true")]
     pub add_wsl_distributions_to_launch_menu: bool,
 
+    #[serde(default)]
+    pub add_serial_ports_to_launch_menu: bool,
+
     #[serde(default = "default_true")]
     pub check_for_updates: bool,
     #[serde(default)]
@@ -1180,15 +1538,76 @@ pub struct Config {
     #[serde(default)]
     pub window_close_confirmation: WindowCloseConfirmation,
 
+    /// When false, closing the last window leaves its panes running
+    /// in the background, and the next `wezterm start` shows them
+    /// again rather than starting afresh
+    #[serde(default = "default_true")]
+    pub quit_when_all_windows_are_closed: bool,
+
+    /// Shows an icon in the system tray (or the menu bar on macOS)
+    /// with a menu of quick actions
+    #[serde(default)]
+    pub enable_tray_icon: bool,
+
+    /// When true, `wezterm start` opens its program in a new tab of
+    /// the wezterm gui that is already running, rather than starting
+    /// another process.  Can be overridden by `--always-new-process`.
+    #[serde(default)]
+    pub single_instance: bool,
+
+    /// Grants programs running in panes the ability to request
+    /// actions via the remote control escape sequence.  Empty,
+    /// and thus disabled, by default.
+    #[serde(default)]
+    pub remote_control: Vec<RemoteControlRule>,
+
     #[serde(default)]
     pub native_macos_fullscreen_mode: bool,
 
     #[serde(default = "default_word_boundary")]
     pub selection_word_boundary: String,
 
+    /// How double clicking selects a word
+    #[serde(default)]
+    pub selection_word_mode: SelectionWordMode,
+
+    /// Whether copying a selection that spans wrapped lines joins
+    /// them back into their logical lines, or keeps the line breaks
+    /// as they are displayed
+    #[serde(default)]
+    pub copy_line_wrap: CopyLineWrap,
+
+    /// Whether trailing whitespace is removed from each line of
+    /// copied text
+    #[serde(default = "default_true")]
+    pub copy_trim_trailing_whitespace: bool,
+
+    /// The string sent in response to an ENQ control code
     #[serde(default = "default_enq_answerback")]
     pub enq_answerback: String,
 
+    /// The terminal type that wezterm presents itself as when
+    /// answering device attribute queries
+    #[serde(default)]
+    pub terminal_compatibility: TerminalCompatibility,
+
+    /// Overrides the parameters reported in response to DA1
+    #[serde(default)]
+    pub primary_device_attributes: Option<String>,
+
+    /// Overrides the parameters reported in response to DA2
+    #[serde(default)]
+    pub secondary_device_attributes: Option<String>,
+
+    /// Overrides the unit id reported in response to DA3
+    #[serde(default)]
+    pub terminal_unit_id: Option<String>,
+
+    /// The kinds of query from applications running in the terminal
+    /// that will be answered; other queries are ignored.
+    #[serde(default = "default_terminal_query_allowlist")]
+    pub terminal_query_allowlist: Vec<TerminalQuery>,
+
     #[serde(default = "default_true")]
     pub adjust_window_size_when_changing_font_size: bool,
 
@@ -1204,6 +1623,16 @@ pub struct Config {
     #[serde(default = "default_stateless_process_list")]
     pub skip_close_confirmation_for_processes_named: Vec<String>,
 
+    /// Processes that always require confirmation before closing,
+    /// even if they also match skip_close_confirmation_for_processes_named
+    #[serde(default)]
+    pub always_confirm_close_for_processes_named: Vec<String>,
+
+    /// Overrides the close confirmation process lists for panes
+    /// in the named domains
+    #[serde(default)]
+    pub close_confirmation_by_domain: HashMap<String, CloseConfirmationPolicy>,
+
     #[serde(default = "default_true")]
     pub warn_about_missing_glyphs: bool,
 
@@ -1222,14 +1651,99 @@ pub struct Config {
     #[serde(default)]
     pub pane_focus_follows_mouse: bool,
 
+    /// When pane_focus_follows_mouse is enabled, how many milliseconds
+    /// the mouse must rest over an inactive pane before it is activated
+    #[serde(default)]
+    pub pane_focus_follows_mouse_delay: u64,
+
+    /// Whether clicking in an inactive window brings it to the front
+    /// as well as focusing it.  Only has an effect on macOS.
+    #[serde(default = "default_true")]
+    pub raise_window_on_click: bool,
+
+    /// The minimum number of milliseconds between mouse motion
+    /// reports sent to an application that has enabled mouse
+    /// tracking.  Motion in between is coalesced into the most
+    /// recent position.  0 sends every motion event.
+    #[serde(default)]
+    pub mouse_motion_report_interval: u64,
+
     #[serde(default = "default_max_fps")]
     pub max_fps: u8,
 
+    /// The number of recently used tabs, other than the active one,
+    /// whose text is shaped in advance so that switching to them is
+    /// fast.  0 disables this.
+    #[serde(default)]
+    pub prerender_recent_tabs: usize,
+
+    /// The approximate amount of memory, in kilobytes, that may be
+    /// used by the text of the tabs that are shaped in advance.
+    /// When it is exceeded, the least recently used tabs are evicted.
+    #[serde(default = "default_prerender_memory_budget_kb")]
+    pub prerender_memory_budget_kb: usize,
+
+    /// The maximum number of runs of shaped text that are cached
+    /// by each window
+    #[serde(default = "default_shape_cache_size")]
+    pub shape_cache_size: usize,
+
+    /// When enabled, output that a local pane produces in response
+    /// to a key press is painted immediately, rather than waiting
+    /// for the next frame to be scheduled.
+    #[serde(default)]
+    pub low_latency_input: bool,
+
     #[serde(default)]
     pub visual_bell: VisualBell,
 
+    /// How long it takes for the colors to fade to a new color scheme,
+    /// and for the font to scale to a new size.  0 makes such changes
+    /// take effect immediately.
+    #[serde(default = "default_appearance_transition_duration_ms")]
+    pub appearance_transition_duration_ms: u64,
+    #[serde(default)]
+    pub appearance_transition_function: EasingFunction,
+
+    /// When true, animations are disabled: changes in appearance take
+    /// effect immediately, the cursor and text don't blink, and the
+    /// visual bell is shown without fading
+    #[serde(default)]
+    pub reduce_motion: bool,
+
+    /// The font size, and the size of the font in the tab bar, are
+    /// never smaller than this, including after decreasing the font
+    /// size.  0 disables this.
+    #[serde(default, deserialize_with = "de_number")]
+    pub minimum_font_size: f64,
+
+    /// When true, the default colors are replaced by black and white,
+    /// and text is kept at a contrast ratio of at least 7:1
+    #[serde(default)]
+    pub high_contrast: bool,
+
+    /// How much is spoken by screen readers without being asked,
+    /// such as new output and the activation of tabs
+    #[serde(default)]
+    pub screen_reader_verbosity: ScreenReaderVerbosity,
+
     #[serde(default)]
     pub audible_bell: AudibleBell,
+
+    /// The volume at which the audible bell sound file is played,
+    /// in the range 0.0 (silent) to 1.0 (the volume of the file)
+    #[serde(default = "default_one_point_oh")]
+    pub audible_bell_volume: f32,
+
+    /// The maximum number of times per second that the audible
+    /// bell is sounded; bells in excess of this are silent
+    #[serde(default = "default_ratelimit_audible_bell_per_second")]
+    pub ratelimit_audible_bell_per_second: u32,
+
+    /// The names of domains whose panes don't make a sound
+    /// when the bell rings in them
+    #[serde(default)]
+    pub audible_bell_muted_domains: Vec<String>,
 }
 impl_lua_conversion!(Config);
 
@@ -1237,6 +1751,18 @@ fn default_max_fps() -> u8 {
     60
 }
 
+fn default_appearance_transition_duration_ms() -> u64 {
+    150
+}
+
+fn default_prerender_memory_budget_kb() -> usize {
+    8192
+}
+
+fn default_shape_cache_size() -> usize {
+    65536
+}
+
 fn default_stateless_process_list() -> Vec<String> {
     [
         "bash",
@@ -1266,6 +1792,40 @@ fn default_alphabet() -> String {
     "asdfqwerzxcvjklmiuopghtybn".to_string()
 }
 
+/// How double clicking selects a word
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionWordMode {
+    /// Select the run of characters that aren't in `selection_word_boundary`
+    Boundary,
+    /// Select the URL, path or quoted string around the click if there is
+    /// one, falling back to the `Boundary` behavior otherwise
+    Semantic,
+}
+impl_lua_conversion!(SelectionWordMode);
+
+impl Default for SelectionWordMode {
+    fn default() -> Self {
+        Self::Boundary
+    }
+}
+
+/// How copying text treats lines that were wrapped because they
+/// were too long for the width of the pane
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum CopyLineWrap {
+    /// Join the wrapped rows into the logical line that was output
+    Logical,
+    /// Copy each row as it is displayed, ending every row with a newline
+    Physical,
+}
+impl_lua_conversion!(CopyLineWrap);
+
+impl Default for CopyLineWrap {
+    fn default() -> Self {
+        Self::Logical
+    }
+}
+
 fn default_word_boundary() -> String {
     " \t\n{[}]()\"'`".to_string()
 }
@@ -1274,6 +1834,10 @@ fn default_enq_answerback() -> String {
     "".to_string()
 }
 
+fn default_terminal_query_allowlist() -> Vec<TerminalQuery> {
+    TerminalQuery::ALL.to_vec()
+}
+
 fn default_one_point_oh_f64() -> f64 {
     1.0
 }
@@ -1320,6 +1884,8 @@ impl Default for DefaultCursorStyle {
 }
 
 impl DefaultCursorStyle {
+    /// Returns the shape that the cursor is drawn with when the
+    /// terminal has requested `shape`
     pub fn effective_shape(self, shape: CursorShape) -> CursorShape {
         match shape {
             CursorShape::Default => match self {
@@ -1363,6 +1929,18 @@ impl Default for WindowCloseConfirmation {
     }
 }
 
+/// Process lists that replace the global close confirmation
+/// lists for the panes in a particular domain.
+/// Lists that are not specified fall back to the global lists.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct CloseConfirmationPolicy {
+    #[serde(default)]
+    pub skip_close_confirmation_for_processes_named: Option<Vec<String>>,
+    #[serde(default)]
+    pub always_confirm_close_for_processes_named: Option<Vec<String>>,
+}
+impl_lua_conversion!(CloseConfirmationPolicy);
+
 impl Default for Config {
     fn default() -> Self {
         // Ask serde to provide the defaults based on the attributes
@@ -1397,6 +1975,27 @@ impl PathPossibility {
     }
 }
 
+/// The key assignments from the configuration, resolved according
+/// to the `key_map_preference`
+#[derive(Default)]
+pub struct KeyBindings {
+    pub keys: HashMap<(KeyCode, Modifiers), KeyAssignment>,
+    pub key_tables: HashMap<String, HashMap<(KeyCode, Modifiers), KeyAssignment>>,
+    /// Descriptions of key sequences that could not be assigned
+    /// because they overlap with another assignment
+    pub conflicts: Vec<String>,
+}
+
+impl KeyBindings {
+    pub fn check_conflicts(&self) -> anyhow::Result<()> {
+        if self.conflicts.is_empty() {
+            Ok(())
+        } else {
+            bail!("{}", self.conflicts.join("\n"))
+        }
+    }
+}
+
 impl Config {
     pub fn load() -> Result<LoadedConfig, Error> {
         Self::load_with_overrides(&serde_json::Value::default())
@@ -1470,7 +2069,9 @@ impl Config {
 
             // Compute but discard the key bindings here so that we raise any
             // problems earlier than we use them.
-            let _ = cfg.key_bindings();
+            cfg.key_bindings()
+                .check_conflicts()
+                .with_context(|| format!("Error in the key assignments in {}", p.display()))?;
 
             std::env::set_var("WEZTERM_CONFIG_FILE", p);
             if let Some(dir) = p.parent() {
@@ -1535,22 +2136,134 @@ impl Config {
         Self::default().compute_extra_defaults(None)
     }
 
-    pub fn key_bindings(&self) -> HashMap<(KeyCode, Modifiers), KeyAssignment> {
+    /// Resolves `keys` and `key_tables` according to `key_map_preference`.
+    /// Sequences of keys are expanded into a chain of generated key
+    /// tables, each activated by the previous key in the sequence.
+    pub fn key_bindings(&self) -> KeyBindings {
+        let mut bindings = KeyBindings::default();
+
+        let keys = self.expand_key_bindings(None, &self.keys, &mut bindings);
+        bindings.keys = keys;
+
+        let mut names: Vec<&String> = self.key_tables.keys().collect();
+        names.sort();
+        for name in names {
+            let table = self.expand_key_bindings(Some(name), &self.key_tables[name], &mut bindings);
+            bindings.key_tables.insert(name.clone(), table);
+        }
+
+        bindings
+    }
+
+    fn expand_key_bindings(
+        &self,
+        table_name: Option<&str>,
+        keys: &[Key],
+        bindings: &mut KeyBindings,
+    ) -> HashMap<(KeyCode, Modifiers), KeyAssignment> {
         let mut map = HashMap::new();
 
-        for k in &self.keys {
-            let (key, mods) = k.key.normalize_shift(k.mods);
+        // Single keys first, so that a sequence that starts with a
+        // key that is also bound on its own is reported as a conflict,
+        // regardless of the order in which they were defined
+        for k in keys.iter().filter(|k| k.key.len() == 1) {
+            let (key, mods) = k.key[0].resolve(k.mods, self.key_map_preference);
             map.insert((key, mods), k.action.clone());
         }
 
+        for k in keys.iter().filter(|k| k.key.len() > 1) {
+            let resolved: Vec<(KeyCode, Modifiers)> = k
+                .key
+                .iter()
+                .enumerate()
+                .map(|(idx, key)| {
+                    let mods = if idx == 0 { k.mods } else { Modifiers::NONE };
+                    key.resolve(mods, self.key_map_preference)
+                })
+                .collect();
+            let describe = |len: usize| {
+                let mut desc: Vec<String> = table_name.map(String::from).into_iter().collect();
+                desc.extend(
+                    resolved[..len]
+                        .iter()
+                        .map(|(key, mods)| describe_key(key, *mods)),
+                );
+                desc.join(" ")
+            };
+
+            let mut current: Option<String> = None;
+            for (idx, (key, mods)) in resolved.iter().enumerate() {
+                let is_last = idx == resolved.len() - 1;
+                let action = if is_last {
+                    k.action.clone()
+                } else {
+                    let name = describe(idx + 1);
+                    if self.key_tables.contains_key(&name) {
+                        bindings.conflicts.push(format!(
+                            "the key sequence `{}` conflicts with the key table named `{}`",
+                            describe(resolved.len()),
+                            name
+                        ));
+                        break;
+                    }
+                    KeyAssignment::ActivateKeyTable {
+                        name,
+                        timeout_milliseconds: None,
+                        replace_current: true,
+                    }
+                };
+
+                let target = match &current {
+                    Some(name) => bindings.key_tables.entry(name.clone()).or_default(),
+                    None => &mut map,
+                };
+
+                match target.get(&(key.clone(), *mods)) {
+                    None => {
+                        target.insert((key.clone(), *mods), action.clone());
+                    }
+                    Some(existing) if !is_last && *existing == action => {}
+                    Some(_) => {
+                        bindings.conflicts.push(format!(
+                            "the key sequence `{}` conflicts with another \
+                             assignment for `{}`",
+                            describe(resolved.len()),
+                            describe(idx + 1)
+                        ));
+                        break;
+                    }
+                }
+
+                if let KeyAssignment::ActivateKeyTable { name, .. } = action {
+                    current.replace(name);
+                }
+            }
+        }
+
         map
     }
 
+    /// Returns the lists of process name patterns that skip, and that
+    /// always require, close confirmation for panes in `domain_name`
+    pub fn close_confirmation_lists(&self, domain_name: &str) -> (&[String], &[String]) {
+        let policy = self.close_confirmation_by_domain.get(domain_name);
+        let skip = policy
+            .and_then(|p| p.skip_close_confirmation_for_processes_named.as_ref())
+            .unwrap_or(&self.skip_close_confirmation_for_processes_named);
+        let confirm = policy
+            .and_then(|p| p.always_confirm_close_for_processes_named.as_ref())
+            .unwrap_or(&self.always_confirm_close_for_processes_named);
+        (skip, confirm)
+    }
+
     pub fn mouse_bindings(&self) -> HashMap<(MouseEventTrigger, Modifiers), KeyAssignment> {
         let mut map = HashMap::new();
 
         for m in &self.mouse_bindings {
-            map.insert((m.event.clone(), m.mods), m.action.clone());
+            map.insert(
+                (m.event.clone().normalize_wheel(), m.mods),
+                m.action.clone(),
+            );
         }
 
         map
@@ -1577,6 +2290,10 @@ impl Config {
             }
         }
 
+        if cfg.open_in_editor.hyperlinks {
+            cfg.hyperlink_rules.extend(file_location_rules());
+        }
+
         // Add some reasonable default font rules
         let reduced = self.font.reduce_first_font_to_family();
 
@@ -1628,7 +2345,8 @@ impl Config {
             .ok();
 
         cfg.resolved_palette = cfg.colors.as_ref().cloned().unwrap_or(Default::default());
-        // Color scheme overrides any manually specified palette
+        // The color scheme provides the base palette; any colors that
+        // were explicitly specified in `colors` take precedence over it
         if let Some(scheme) = cfg.color_scheme.as_ref() {
             match cfg.resolve_color_scheme() {
                 None => {
@@ -1639,11 +2357,13 @@ impl Config {
                     );
                 }
                 Some(p) => {
-                    cfg.resolved_palette = p.clone();
+                    cfg.resolved_palette = p.overlay_with(&cfg.resolved_palette);
                 }
             }
         }
 
+        cfg.apply_accessibility_options();
+
         cfg
     }
 
@@ -1727,6 +2447,30 @@ impl Config {
         }
     }
 
+    /// Returns the shape to draw the cursor with when the terminal
+    /// has requested `shape`, taking into account the default and
+    /// forced cursor styles
+    pub fn cursor_shape(&self, shape: CursorShape) -> CursorShape {
+        match self.force_cursor_style {
+            Some(style) => style.effective_shape(CursorShape::Default),
+            None => self.default_cursor_style.effective_shape(shape),
+        }
+    }
+
+    /// Returns the interval, in milliseconds, at which a cursor
+    /// drawn with `shape` blinks
+    pub fn cursor_blink_rate_for(&self, shape: CursorShape) -> u64 {
+        let rate = match shape {
+            CursorShape::BlinkingBlock | CursorShape::SteadyBlock => self.block_cursor_blink_rate,
+            CursorShape::BlinkingUnderline | CursorShape::SteadyUnderline => {
+                self.underline_cursor_blink_rate
+            }
+            CursorShape::BlinkingBar | CursorShape::SteadyBar => self.bar_cursor_blink_rate,
+            CursorShape::Default => None,
+        };
+        rate.unwrap_or(self.cursor_blink_rate)
+    }
+
     pub fn initial_size(&self) -> PtySize {
         PtySize {
             rows: self.initial_rows,
@@ -1745,6 +2489,61 @@ impl Config {
         }
     }
 
+    /// Returns the `treat_east_asian_ambiguous_width_as_wide` override
+    /// of the domain named `domain_name`, if it has one
+    pub fn ambiguous_width_override_for_domain(&self, domain_name: &str) -> Option<bool> {
+        let ssh = || {
+            self.ssh_domains
+                .iter()
+                .find(|dom| dom.name == domain_name)
+                .and_then(|dom| dom.treat_east_asian_ambiguous_width_as_wide)
+        };
+        let unix = || {
+            self.unix_domains
+                .iter()
+                .find(|dom| dom.name == domain_name)
+                .and_then(|dom| dom.treat_east_asian_ambiguous_width_as_wide)
+        };
+        let tls = || {
+            self.tls_clients
+                .iter()
+                .find(|dom| dom.name == domain_name)
+                .and_then(|dom| dom.treat_east_asian_ambiguous_width_as_wide)
+        };
+        let exec = || {
+            self.exec_domains
+                .iter()
+                .find(|dom| dom.name == domain_name)
+                .and_then(|dom| dom.treat_east_asian_ambiguous_width_as_wide)
+        };
+        ssh().or_else(unix).or_else(tls).or_else(exec)
+    }
+
+    /// Returns the ssh_terminfo rule that applies to `host`
+    pub fn ssh_terminfo_rule(&self, host: &str) -> Option<&SshTerminfoRule> {
+        self.ssh_terminfo.iter().find(|rule| rule.matches(host))
+    }
+
+    /// Returns the program and arguments to use to edit a file,
+    /// taken from the `editor` option or from the VISUAL or EDITOR
+    /// environment variables
+    pub fn resolve_editor(&self) -> Option<Vec<String>> {
+        if let Some(editor) = &self.editor {
+            if !editor.is_empty() {
+                return Some(editor.clone());
+            }
+        }
+        for var in &["VISUAL", "EDITOR"] {
+            if let Ok(value) = std::env::var(var) {
+                let args: Vec<String> = value.split_whitespace().map(|s| s.to_string()).collect();
+                if !args.is_empty() {
+                    return Some(args);
+                }
+            }
+        }
+        None
+    }
+
     pub fn build_prog(&self, prog: Option<Vec<&OsStr>>) -> Result<CommandBuilder, Error> {
         let mut cmd = match prog {
             Some(args) => {
@@ -1813,10 +2612,38 @@ fn default_mux_output_parser_buffer_size() -> usize {
     128 * 1024
 }
 
+fn default_mux_output_parser_coalesce_delay_ms() -> u64 {
+    3
+}
+
+fn default_mux_output_backpressure_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_ratelimit_audible_bell_per_second() -> u32 {
+    4
+}
+
 fn default_ratelimit_line_prefetches_per_second() -> u32 {
     10
 }
 
+fn default_which_key_delay() -> u64 {
+    500
+}
+
+fn default_pane_numbers_timeout() -> u64 {
+    1000
+}
+
+fn default_pane_select_label_scale() -> f64 {
+    0.2
+}
+
+fn default_unicode_version() -> u8 {
+    9
+}
+
 fn default_true() -> bool {
     true
 }
@@ -1839,10 +2666,18 @@ fn default_swap_backspace_and_delete() -> bool {
     false
 }
 
+pub fn default_workspace() -> String {
+    "default".to_string()
+}
+
 fn default_scrollback_lines() -> usize {
     3500
 }
 
+fn default_scrollback_search_index_max_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
 fn default_initial_rows() -> u16 {
     24
 }
@@ -1885,10 +2720,21 @@ fn compute_runtime_dir() -> Result<PathBuf, Error> {
     Ok(HOME_DIR.join(".local/share/wezterm"))
 }
 
+/// Where state that should persist across sessions is stored
+fn compute_data_dir() -> PathBuf {
+    dirs_next::data_dir()
+        .unwrap_or_else(|| HOME_DIR.join(".local/share"))
+        .join("wezterm")
+}
+
 pub fn pki_dir() -> anyhow::Result<PathBuf> {
     compute_runtime_dir().map(|d| d.join("pki"))
 }
 
+fn default_container_refresh_interval() -> u64 {
+    10
+}
+
 fn default_read_timeout() -> Duration {
     Duration::from_secs(60)
 }
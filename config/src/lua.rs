@@ -14,6 +14,7 @@ use termwiz::surface::change::Change;
 use unicode_segmentation::UnicodeSegmentation;
 
 static LUA_REGISTRY_USER_CALLBACK_COUNT: &str = "wezterm-user-callback-count";
+static LUA_REGISTRY_CONFIG_DIR: &str = "wezterm-config-dir";
 
 /// Set up a lua context for executing some code.
 /// The path to the directory containing the configuration is
@@ -37,6 +38,8 @@ static LUA_REGISTRY_USER_CALLBACK_COUNT: &str = "wezterm-user-callback-count";
 /// * `target_triple` - the rust compilation target triple.
 /// * `version` - the version of the running wezterm instance.
 /// * `home_dir` - the path to the user's home directory
+/// * `config_builder` - makes a config table that can `include`
+///   config fragments from other files.
 ///
 /// In addition to this, the lua standard library, except for
 /// the `debug` module, is also available to the script.
@@ -87,6 +90,11 @@ pub fn make_lua_context(config_file: &Path) -> anyhow::Result<Lua> {
         )?;
 
         lua.set_named_registry_value("wezterm-watch-paths", Vec::<String>::new())?;
+        lua.set_named_registry_value(
+            LUA_REGISTRY_CONFIG_DIR,
+            config_dir.to_string_lossy().to_string(),
+        )?;
+        wezterm_mod.set("config_builder", lua.create_function(config_builder)?)?;
         wezterm_mod.set(
             "add_to_config_reload_watch_list",
             lua.create_function(|lua, args: Variadic<String>| {
@@ -262,6 +270,11 @@ pub fn make_lua_context(config_file: &Path) -> anyhow::Result<Lua> {
         wezterm_mod.set("battery_info", lua.create_function(battery_info)?)?;
         wezterm_mod.set("gradient_colors", lua.create_function(gradient_colors)?)?;
 
+        let wsl_mod = lua.create_table()?;
+        wsl_mod.set("to_wsl_path", lua.create_function(to_wsl_path)?)?;
+        wsl_mod.set("to_windows_path", lua.create_function(to_windows_path)?)?;
+        wezterm_mod.set("wsl", wsl_mod)?;
+
         package.set("path", path_array.join(";"))?;
 
         let loaded: Table = package.get("loaded")?;
@@ -648,6 +661,72 @@ async fn glob<'lua>(
     Ok(entries)
 }
 
+/// This implements `wezterm.config_builder`, which returns an empty
+/// config table whose methods allow composing the config from
+/// several files.
+fn config_builder<'lua>(lua: &'lua Lua, _: ()) -> mlua::Result<Table<'lua>> {
+    let methods = lua.create_table()?;
+    methods.set(
+        "include",
+        lua.create_async_function(include_config_fragments)?,
+    )?;
+    let meta = lua.create_table()?;
+    meta.set("__index", methods)?;
+
+    let builder = lua.create_table()?;
+    builder.set_metatable(Some(meta));
+    Ok(builder)
+}
+
+/// This implements `config:include(pattern)`.
+/// Each fragment that matches `pattern` and applies to this system
+/// is evaluated with the builder as its argument, and the options
+/// in the table that it returns, if any, are assigned to the builder.
+/// The fragments are added to the config reload watch list.
+async fn include_config_fragments<'lua>(
+    lua: &'lua Lua,
+    (builder, pattern): (Table<'lua>, String),
+) -> mlua::Result<Table<'lua>> {
+    let config_dir: String = lua.named_registry_value(LUA_REGISTRY_CONFIG_DIR)?;
+    let includes = crate::resolve_includes(&pattern, Path::new(&config_dir))
+        .map_err(|e| mlua::Error::external(e))?;
+
+    let mut watch_paths: Vec<String> = lua.named_registry_value("wezterm-watch-paths")?;
+    for path in &includes.watch_paths {
+        watch_paths.push(path.to_string_lossy().to_string());
+    }
+    lua.set_named_registry_value("wezterm-watch-paths", watch_paths)?;
+
+    for path in includes.fragments {
+        log::trace!("including config fragment {}", path.display());
+        let code = std::fs::read_to_string(&path).map_err(|e| {
+            mlua::Error::external(anyhow!("unable to read {}: {:#}", path.display(), e))
+        })?;
+        let fragment = lua
+            .load(&code)
+            .set_name(path.to_string_lossy().as_bytes())?
+            .into_function()?;
+        match fragment.call_async(builder.clone()).await? {
+            Value::Table(options) => {
+                for pair in options.pairs::<Value, Value>() {
+                    let (key, value) = pair?;
+                    builder.set(key, value)?;
+                }
+            }
+            Value::Nil => {}
+            other => {
+                return Err(mlua::Error::external(anyhow!(
+                    "config fragment {} returned a {} rather than a table",
+                    path.display(),
+                    other.type_name()
+                )))
+            }
+        }
+    }
+
+    Ok(builder)
+}
+
 fn split_by_newlines<'lua>(_: &'lua Lua, text: String) -> mlua::Result<Vec<String>> {
     Ok(text
         .lines()
@@ -744,6 +823,16 @@ pub async fn emit_event<'lua>(
     }
 }
 
+/// Returns true if a handler has been registered for the
+/// event `name` using `wezterm.on`
+pub fn has_event_handler(lua: &Lua, name: &str) -> bool {
+    let decorated_name = format!("wezterm-event-{}", name);
+    matches!(
+        lua.named_registry_value::<_, mlua::Value>(&decorated_name),
+        Ok(mlua::Value::Table(_))
+    )
+}
+
 pub fn emit_sync_callback<'lua, A>(
     lua: &'lua Lua,
     (name, args): (String, A),
@@ -765,6 +854,17 @@ where
     }
 }
 
+fn to_wsl_path<'lua>(_: &'lua Lua, path: String) -> mlua::Result<Option<String>> {
+    Ok(crate::windows_path_to_wsl(&path))
+}
+
+fn to_windows_path<'lua>(
+    _: &'lua Lua,
+    (path, distro): (String, Option<String>),
+) -> mlua::Result<Option<String>> {
+    Ok(crate::wsl_path_to_windows(&path, distro.as_deref()))
+}
+
 /// Ungh: https://github.com/microsoft/WSL/issues/4456
 fn utf16_to_utf8<'lua>(_: &'lua Lua, text: mlua::String) -> mlua::Result<String> {
     let bytes = text.as_bytes();
@@ -0,0 +1,110 @@
+use crate::*;
+use std::borrow::Cow;
+use unicode_normalization::UnicodeNormalization;
+
+/// The unicode normalization form to apply to text that is typed or
+/// pasted into a pane.  Text from macOS, such as file names, is often
+/// decomposed, which can confuse programs on other systems that expect
+/// composed characters.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum InputNormalization {
+    /// Send the text unchanged
+    None,
+    /// Canonical composition, eg: `e` followed by a combining acute
+    /// accent is sent as `é`
+    NFC,
+    /// Canonical decomposition, eg: `é` is sent as `e` followed by
+    /// a combining acute accent
+    NFD,
+}
+impl_lua_conversion!(InputNormalization);
+
+impl Default for InputNormalization {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl InputNormalization {
+    pub fn apply<'a>(self, text: &'a str) -> Cow<'a, str> {
+        // ASCII is the same in every form
+        if text.is_ascii() {
+            return Cow::Borrowed(text);
+        }
+        match self {
+            Self::None => Cow::Borrowed(text),
+            Self::NFC => Cow::Owned(text.nfc().collect()),
+            Self::NFD => Cow::Owned(text.nfd().collect()),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the normalization to apply to input for the panes
+    /// in the domain named `domain_name`
+    pub fn input_normalization_for_domain(&self, domain_name: &str) -> InputNormalization {
+        let ssh = || {
+            self.ssh_domains
+                .iter()
+                .find(|dom| dom.name == domain_name)
+                .and_then(|dom| dom.input_normalization)
+        };
+        let unix = || {
+            self.unix_domains
+                .iter()
+                .find(|dom| dom.name == domain_name)
+                .and_then(|dom| dom.input_normalization)
+        };
+        let tls = || {
+            self.tls_clients
+                .iter()
+                .find(|dom| dom.name == domain_name)
+                .and_then(|dom| dom.input_normalization)
+        };
+        let exec = || {
+            self.exec_domains
+                .iter()
+                .find(|dom| dom.name == domain_name)
+                .and_then(|dom| dom.input_normalization)
+        };
+        ssh()
+            .or_else(unix)
+            .or_else(tls)
+            .or_else(exec)
+            .unwrap_or(self.input_normalization)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalization() {
+        let decomposed = "cafe\u{301}";
+        let composed = "caf\u{e9}";
+        assert_eq!(InputNormalization::NFC.apply(decomposed), composed);
+        assert_eq!(InputNormalization::NFD.apply(composed), decomposed);
+        assert_eq!(InputNormalization::None.apply(decomposed), decomposed);
+        assert_eq!(InputNormalization::NFD.apply("plain"), "plain");
+    }
+
+    #[test]
+    fn domain_override() {
+        let mut config = Config::default_config();
+        config.input_normalization = InputNormalization::NFC;
+        config.ssh_domains.push(SshDomain {
+            name: "linux".to_string(),
+            input_normalization: Some(InputNormalization::None),
+            ..Default::default()
+        });
+        assert_eq!(
+            config.input_normalization_for_domain("linux"),
+            InputNormalization::None
+        );
+        assert_eq!(
+            config.input_normalization_for_domain("local"),
+            InputNormalization::NFC
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use crate::*;
+
+/// A named bundle of appearance and domain settings that can be
+/// switched to at runtime via `SwitchProfile`, or selected at startup
+/// via `default_profile` or `wezterm start --profile`.
+/// The options that are set replace the corresponding options of the
+/// config in the windows that use the profile.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct Profile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font: Option<TextStyle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_scheme: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colors: Option<Palette>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_padding: Option<WindowPadding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_background_opacity: Option<f32>,
+
+    /// The domain in which new tabs and windows are spawned, in
+    /// place of the default domain
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+impl_lua_conversion!(Profile);
+
+impl Profile {
+    /// Returns the config overrides that apply this profile
+    pub fn to_overrides(&self) -> serde_json::Map<String, serde_json::Value> {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(mut obj)) => {
+                // The domain isn't a config option
+                obj.remove("domain");
+                obj
+            }
+            _ => serde_json::Map::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the config overrides that apply the profile named
+    /// `profile`, with `overrides` taking precedence over it
+    pub fn profile_overrides(
+        &self,
+        profile: Option<&str>,
+        overrides: &serde_json::Value,
+    ) -> serde_json::Value {
+        let mut result = match profile {
+            Some(name) => match self.profiles.get(name) {
+                Some(profile) => profile.to_overrides(),
+                None => {
+                    log::error!("There is no profile named {}", name);
+                    serde_json::Map::new()
+                }
+            },
+            None => serde_json::Map::new(),
+        };
+        if let serde_json::Value::Object(obj) = overrides {
+            for (key, value) in obj {
+                result.insert(key.clone(), value.clone());
+            }
+        }
+        serde_json::Value::Object(result)
+    }
+
+    /// Returns the profile named `profile`, if any
+    pub fn profile(&self, profile: Option<&str>) -> Option<&Profile> {
+        profile.and_then(|name| self.profiles.get(name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn overrides() {
+        let mut config = Config::default_config();
+        config.profiles.insert(
+            "work".to_string(),
+            Profile {
+                font_size: Some(14.0),
+                color_scheme: Some("Builtin Light".to_string()),
+                domain: Some("devbox".to_string()),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            config.profile_overrides(Some("work"), &json!({"font_size": 16.0})),
+            json!({
+                "font_size": 16.0,
+                "color_scheme": "Builtin Light",
+            })
+        );
+        assert_eq!(
+            config.profile_overrides(Some("missing"), &serde_json::Value::Null),
+            json!({})
+        );
+        assert_eq!(
+            config
+                .profile(Some("work"))
+                .and_then(|p| p.domain.as_deref()),
+            Some("devbox")
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Describes a type of project, identified by the presence of
+/// marker files, and how to spawn programs in it
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ProjectRule {
+    /// The names of files or directories whose presence in a
+    /// directory identifies it as the root of this type of project,
+    /// eg: `{".git"}` or `{"Cargo.toml"}`
+    pub markers: Vec<String>,
+
+    /// The command to run when spawning without an explicit command
+    pub args: Option<Vec<String>>,
+
+    /// Additional environment variables to set when spawning
+    #[serde(default)]
+    pub set_environment_variables: HashMap<String, String>,
+
+    /// The title to assign to the newly spawned pane.
+    /// `{project}` is replaced by the name of the project directory.
+    pub tab_title: Option<String>,
+}
+impl_lua_conversion!(ProjectRule);
+
+impl ProjectRule {
+    /// Returns the expanded tab_title for a project rooted at `root`
+    pub fn expand_tab_title(&self, root: &Path) -> Option<String> {
+        let title = self.tab_title.as_ref()?;
+        let project = root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| root.display().to_string());
+        Some(title.replace("{project}", &project))
+    }
+}
+
+/// Searches `cwd` and its parent directories for a project marker.
+/// The nearest directory with a marker wins; if several rules match
+/// in that directory, the first of them is used.
+/// Returns the rule and the root directory of the project.
+pub fn find_project_rule<'a>(
+    rules: &'a [ProjectRule],
+    cwd: &Path,
+) -> Option<(&'a ProjectRule, PathBuf)> {
+    if rules.is_empty() {
+        return None;
+    }
+    for dir in cwd.ancestors() {
+        for rule in rules {
+            if rule.markers.iter().any(|marker| dir.join(marker).exists()) {
+                return Some((rule, dir.to_path_buf()));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(marker: &str, title: &str) -> ProjectRule {
+        ProjectRule {
+            markers: vec![marker.to_string()],
+            tab_title: Some(title.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn nearest_marker_wins() {
+        let root = std::env::temp_dir().join(format!("wezterm-project-{}", std::process::id()));
+        let nested = root.join("crate").join("src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join("crate").join("Cargo.toml"), "").unwrap();
+
+        let rules = vec![rule(".git", "git {project}"), rule("Cargo.toml", "rust")];
+
+        let (found, dir) = find_project_rule(&rules, &nested).unwrap();
+        assert_eq!(dir, root.join("crate"));
+        assert_eq!(found.tab_title.as_deref(), Some("rust"));
+
+        let (found, dir) = find_project_rule(&rules, &root).unwrap();
+        assert_eq!(dir, root);
+        let expected = format!("git {}", root.file_name().unwrap().to_string_lossy());
+        assert_eq!(found.expand_tab_title(&dir), Some(expected));
+
+        assert!(find_project_rule(&[], &nested).is_none());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+use crate::*;
+
+/// The actions that a program running in a pane can request via
+/// the remote control escape sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum RemoteControlAction {
+    /// Open a new tab in the window that contains the pane
+    NewTab,
+    /// Set or clear the color of the tab that contains the pane
+    SetTabColor,
+    /// Report the tabs and panes of the window that contains the pane
+    GetLayout,
+}
+impl_lua_conversion!(RemoteControlAction);
+
+/// Grants a set of remote control actions to programs running
+/// in panes, optionally only when they present a password
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct RemoteControlRule {
+    /// If set, the rule only applies to requests that include
+    /// this password
+    pub password: Option<String>,
+
+    #[serde(default)]
+    pub actions: Vec<RemoteControlAction>,
+}
+impl_lua_conversion!(RemoteControlRule);
+
+impl RemoteControlRule {
+    /// Returns true if this rule grants `action` to a request
+    /// that presented `password`
+    pub fn permits(&self, action: RemoteControlAction, password: Option<&str>) -> bool {
+        if let Some(required) = &self.password {
+            match password {
+                Some(password) if constant_time_eq(password, required) => {}
+                _ => return false,
+            }
+        }
+        self.actions.contains(&action)
+    }
+}
+
+/// Compares two passwords in an amount of time that doesn't depend
+/// on where they differ, so that a program can't guess a password
+/// one character at a time by timing its requests
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let diff = a
+        .iter()
+        .zip(b.iter())
+        .fold(a.len() ^ b.len(), |diff, (x, y)| diff | (x ^ y) as usize);
+    diff == 0
+}
+
+/// Returns true if any of `rules` grants `action` to a request
+/// that presented `password`
+pub fn remote_control_permits(
+    rules: &[RemoteControlRule],
+    action: RemoteControlAction,
+    password: Option<&str>,
+) -> bool {
+    rules.iter().any(|rule| rule.permits(action, password))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn permits() {
+        let rules = vec![
+            RemoteControlRule {
+                password: None,
+                actions: vec![RemoteControlAction::GetLayout],
+            },
+            RemoteControlRule {
+                password: Some("secret".to_string()),
+                actions: vec![RemoteControlAction::NewTab],
+            },
+        ];
+
+        assert!(remote_control_permits(
+            &rules,
+            RemoteControlAction::GetLayout,
+            None
+        ));
+        assert!(!remote_control_permits(
+            &rules,
+            RemoteControlAction::NewTab,
+            None
+        ));
+        assert!(!remote_control_permits(
+            &rules,
+            RemoteControlAction::NewTab,
+            Some("wrong")
+        ));
+        assert!(!remote_control_permits(
+            &rules,
+            RemoteControlAction::NewTab,
+            Some("secre")
+        ));
+        assert!(!remote_control_permits(
+            &rules,
+            RemoteControlAction::NewTab,
+            Some("secrets")
+        ));
+        assert!(remote_control_permits(
+            &rules,
+            RemoteControlAction::NewTab,
+            Some("secret")
+        ));
+        assert!(!remote_control_permits(
+            &rules,
+            RemoteControlAction::SetTabColor,
+            Some("secret")
+        ));
+        assert!(!remote_control_permits(
+            &[],
+            RemoteControlAction::GetLayout,
+            None
+        ));
+    }
+}
@@ -0,0 +1,194 @@
+use crate::*;
+use std::collections::HashMap;
+
+/// Text, such as a command, that can be inserted into a pane via
+/// the SendSnippet action or the snippet picker.
+/// The text may contain placeholders: `{cwd}` and `{clipboard}` are
+/// replaced by the shell-quoted working directory of the pane and
+/// contents of the clipboard, `{raw_cwd}` and `{raw_clipboard}` by the
+/// same values without quoting, and any other `{label}` is prompted
+/// for.  `{{` and `}}` produce literal braces.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct Snippet {
+    pub name: String,
+    pub text: String,
+    /// Shown alongside the name in the snippet picker
+    #[serde(default)]
+    pub description: Option<String>,
+}
+impl_lua_conversion!(Snippet);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnippetPart {
+    Text(String),
+    /// The working directory of the pane.  It is reported by the
+    /// program running in the pane, so it is quoted unless `raw`.
+    Cwd {
+        raw: bool,
+    },
+    /// The contents of the clipboard, quoted unless `raw`
+    Clipboard {
+        raw: bool,
+    },
+    /// A value that is prompted for, identified by its label
+    Field(String),
+}
+
+/// Splits `text` into literal text and placeholders
+pub fn parse_snippet(text: &str) -> Vec<SnippetPart> {
+    let mut parts = vec![];
+    let mut literal = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut label = String::new();
+                let mut closed = false;
+                for c in &mut chars {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    label.push(c);
+                }
+                if !closed {
+                    // Not a placeholder after all
+                    literal.push('{');
+                    literal.push_str(&label);
+                    continue;
+                }
+                if !literal.is_empty() {
+                    parts.push(SnippetPart::Text(std::mem::take(&mut literal)));
+                }
+                parts.push(match label.as_str() {
+                    "cwd" => SnippetPart::Cwd { raw: false },
+                    "raw_cwd" => SnippetPart::Cwd { raw: true },
+                    "clipboard" => SnippetPart::Clipboard { raw: false },
+                    "raw_clipboard" => SnippetPart::Clipboard { raw: true },
+                    _ => SnippetPart::Field(label),
+                });
+            }
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(SnippetPart::Text(literal));
+    }
+    parts
+}
+
+impl Snippet {
+    /// Returns the labels of the values that need to be prompted for,
+    /// in the order in which they first appear
+    pub fn fields(&self) -> Vec<String> {
+        let mut fields = vec![];
+        for part in parse_snippet(&self.text) {
+            if let SnippetPart::Field(label) = part {
+                if !fields.contains(&label) {
+                    fields.push(label);
+                }
+            }
+        }
+        fields
+    }
+
+    pub fn uses_clipboard(&self) -> bool {
+        parse_snippet(&self.text)
+            .iter()
+            .any(|part| matches!(part, SnippetPart::Clipboard { .. }))
+    }
+
+    /// Returns the text with its placeholders replaced.  Fields that
+    /// are missing from `values` are replaced with nothing.  `cwd` and
+    /// `clipboard` are shell-quoted unless the raw placeholders are used.
+    pub fn expand(&self, cwd: &str, clipboard: &str, values: &HashMap<String, String>) -> String {
+        parse_snippet(&self.text)
+            .into_iter()
+            .map(|part| match part {
+                SnippetPart::Text(text) => text,
+                SnippetPart::Cwd { raw } => quote_unless_raw(cwd, raw),
+                SnippetPart::Clipboard { raw } => quote_unless_raw(clipboard, raw),
+                SnippetPart::Field(label) => values.get(&label).cloned().unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+fn quote_unless_raw(value: &str, raw: bool) -> String {
+    if raw {
+        value.to_string()
+    } else {
+        shell_words::quote(value).into_owned()
+    }
+}
+
+impl Config {
+    pub fn snippet_by_name(&self, name: &str) -> Option<&Snippet> {
+        self.snippets.iter().find(|snippet| snippet.name == name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_snippet("cd {cwd} && git commit -m '{message}' {{}}"),
+            vec![
+                SnippetPart::Text("cd ".to_string()),
+                SnippetPart::Cwd { raw: false },
+                SnippetPart::Text(" && git commit -m '".to_string()),
+                SnippetPart::Field("message".to_string()),
+                SnippetPart::Text("' {}".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_snippet("awk '{print $1"),
+            vec![SnippetPart::Text("awk '{print $1".to_string())]
+        );
+    }
+
+    #[test]
+    fn expand() {
+        let snippet = Snippet {
+            name: "scp".to_string(),
+            text: "scp {file} {host}:{clipboard} && ssh {host}".to_string(),
+            description: None,
+        };
+        assert_eq!(snippet.fields(), vec!["file", "host"]);
+        assert!(snippet.uses_clipboard());
+
+        let mut values = HashMap::new();
+        values.insert("file".to_string(), "notes.txt".to_string());
+        values.insert("host".to_string(), "example".to_string());
+        assert_eq!(
+            snippet.expand("/home/me", "/tmp", &values),
+            "scp notes.txt example:/tmp && ssh example"
+        );
+    }
+
+    #[test]
+    fn quoting() {
+        let snippet = Snippet {
+            name: "untar".to_string(),
+            text: "tar xzf {clipboard} -C {cwd}; echo {raw_clipboard}".to_string(),
+            description: None,
+        };
+        assert!(snippet.uses_clipboard());
+        assert_eq!(
+            snippet.expand("/tmp/$(rm -rf ~)", "a b.tgz", &HashMap::new()),
+            "tar xzf 'a b.tgz' -C '/tmp/$(rm -rf ~)'; echo a b.tgz"
+        );
+    }
+}
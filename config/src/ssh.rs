@@ -27,6 +27,13 @@ pub struct SshDomain {
 
     /// The path to the wezterm binary on the remote host
     pub remote_wezterm_path: Option<String>,
+
+    /// Overrides `input_normalization` for the panes in this domain
+    pub input_normalization: Option<InputNormalization>,
+
+    /// Overrides `treat_east_asian_ambiguous_width_as_wide` for
+    /// the panes in this domain
+    pub treat_east_asian_ambiguous_width_as_wide: Option<bool>,
 }
 impl_lua_conversion!(SshDomain);
 
@@ -76,3 +83,97 @@ impl FromStr for SshParameters {
         }
     }
 }
+
+/// What to do when the terminfo entry named by the `term`
+/// configuration is not installed on the host that we are
+/// connecting to via `wezterm ssh`
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum SshTerminfoAction {
+    /// Ask whether to install it, falling back if declined
+    Ask,
+    /// Install it without asking
+    Install,
+    /// Use the fallback TERM value instead
+    Fallback,
+}
+impl_lua_conversion!(SshTerminfoAction);
+
+impl Default for SshTerminfoAction {
+    fn default() -> Self {
+        Self::Ask
+    }
+}
+
+fn default_fallback_term() -> String {
+    "xterm-256color".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SshTerminfoRule {
+    /// The host name that this rule applies to.  `*` and `?`
+    /// wildcards are supported.
+    pub host: String,
+
+    #[serde(default)]
+    pub action: SshTerminfoAction,
+
+    /// The TERM value to use if the terminfo entry is not
+    /// installed on the host and is not going to be installed
+    #[serde(default = "default_fallback_term")]
+    pub fallback_term: String,
+}
+impl_lua_conversion!(SshTerminfoRule);
+
+impl SshTerminfoRule {
+    pub fn matches(&self, host: &str) -> bool {
+        wildcard_match(&self.host, host)
+    }
+}
+
+/// Matches `text` against a glob style `pattern` in which `*` matches
+/// any sequence of characters and `?` matches any single character
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The position of the most recent `*` in the pattern, and the
+    // position in the text that it is currently matched up to
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the `*` consume one more character and retry
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, t));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("*.example.com", "build.example.com"));
+        assert!(!wildcard_match("*.example.com", "example.com"));
+        assert!(wildcard_match("host?", "host1"));
+        assert!(!wildcard_match("host?", "host12"));
+        assert!(wildcard_match("a*b*c", "aXXbYYbc"));
+        assert!(!wildcard_match("a*b*c", "aXXbYYb"));
+        assert!(wildcard_match("exact", "exact"));
+        assert!(!wildcard_match("exact", "exactly"));
+    }
+}
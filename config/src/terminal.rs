@@ -2,24 +2,58 @@
 
 use crate::{configuration, ConfigHandle};
 use std::sync::Mutex;
+use termwiz::cell::UnicodeVersion;
 use termwiz::hyperlink::Rule as HyperlinkRule;
+use termwiz::surface::CursorShape;
 use wezterm_term::color::ColorPalette;
+use wezterm_term::{TerminalCompatibility, TerminalQuery};
 
 #[derive(Debug)]
 pub struct TermConfig {
     config: Mutex<Option<ConfigHandle>>,
+    /// The name of the domain of the pane, used to apply
+    /// the overrides of that domain
+    domain_name: Option<String>,
+    /// Overrides `treat_east_asian_ambiguous_width_as_wide`
+    /// for the pane; takes precedence over the domain
+    ambiguous_are_wide: Option<bool>,
 }
 
 impl TermConfig {
     pub fn new() -> Self {
         Self {
             config: Mutex::new(None),
+            domain_name: None,
+            ambiguous_are_wide: None,
         }
     }
 
     pub fn with_config(config: ConfigHandle) -> Self {
         Self {
             config: Mutex::new(Some(config)),
+            domain_name: None,
+            ambiguous_are_wide: None,
+        }
+    }
+
+    /// Returns a copy of this configuration that applies the
+    /// overrides of the domain named `domain_name`
+    pub fn for_domain(&self, domain_name: &str) -> Self {
+        Self {
+            config: Mutex::new(self.config.lock().unwrap().clone()),
+            domain_name: Some(domain_name.to_string()),
+            ambiguous_are_wide: self.ambiguous_are_wide,
+        }
+    }
+
+    /// Returns this configuration with East Asian Ambiguous width
+    /// characters treated as wide if `ambiguous_are_wide` is true.
+    /// This is used by the mux server to apply the override that a
+    /// client has configured for its domain.
+    pub fn with_ambiguous_width(self, ambiguous_are_wide: bool) -> Self {
+        Self {
+            ambiguous_are_wide: Some(ambiguous_are_wide),
+            ..self
         }
     }
 
@@ -64,10 +98,96 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
     }
 
     fn enq_answerback(&self) -> String {
-        configuration().enq_answerback.clone()
+        self.configuration().enq_answerback.clone()
+    }
+
+    fn compatibility(&self) -> TerminalCompatibility {
+        self.configuration().terminal_compatibility
+    }
+
+    fn primary_device_attributes(&self) -> Option<String> {
+        self.configuration().primary_device_attributes.clone()
+    }
+
+    fn secondary_device_attributes(&self) -> Option<String> {
+        self.configuration().secondary_device_attributes.clone()
+    }
+
+    fn terminal_unit_id(&self) -> Option<String> {
+        self.configuration().terminal_unit_id.clone()
     }
 
     fn enable_kitty_graphics(&self) -> bool {
         self.configuration().enable_kitty_graphics
     }
+
+    fn default_cursor_style(&self) -> CursorShape {
+        self.configuration()
+            .default_cursor_style
+            .effective_shape(CursorShape::Default)
+    }
+
+    fn restore_cursor_style_after_alt_screen(&self) -> bool {
+        self.configuration().restore_cursor_style_after_alt_screen
+    }
+
+    fn unicode_version(&self) -> UnicodeVersion {
+        let config = self.configuration();
+        let ambiguous_are_wide = self
+            .ambiguous_are_wide
+            .or_else(|| {
+                self.domain_name
+                    .as_ref()
+                    .and_then(|name| config.ambiguous_width_override_for_domain(name))
+            })
+            .unwrap_or(config.treat_east_asian_ambiguous_width_as_wide);
+        UnicodeVersion {
+            version: config.unicode_version,
+            ambiguous_are_wide,
+        }
+    }
+
+    fn allow_query(&self, query: TerminalQuery) -> bool {
+        self.configuration()
+            .terminal_query_allowlist
+            .contains(&query)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Config, UnixDomain};
+    use std::sync::Arc;
+    use wezterm_term::TerminalConfiguration;
+
+    #[test]
+    fn ambiguous_width_override() {
+        let mut config = Config::default_config();
+        config.unix_domains.push(UnixDomain {
+            name: "cjk".to_string(),
+            treat_east_asian_ambiguous_width_as_wide: Some(true),
+            ..Default::default()
+        });
+        let config = ConfigHandle {
+            config: Arc::new(config),
+            generation: 0,
+        };
+
+        let term_config = TermConfig::with_config(config);
+        assert!(!term_config.unicode_version().ambiguous_are_wide);
+        assert!(
+            term_config
+                .for_domain("cjk")
+                .unicode_version()
+                .ambiguous_are_wide
+        );
+        assert!(
+            !term_config
+                .for_domain("cjk")
+                .with_ambiguous_width(false)
+                .unicode_version()
+                .ambiguous_are_wide
+        );
+    }
 }
@@ -22,6 +22,13 @@ pub struct TlsDomainServer {
     /// to the trust store.
     #[serde(default)]
     pub pem_root_certs: Vec<PathBuf>,
+
+    /// Restricts what connecting users are permitted to do.
+    /// Users are identified by the common name in their client
+    /// certificate.  If empty, only the user running the server
+    /// may connect, and has full access.
+    #[serde(default)]
+    pub access_rules: Vec<MuxAccessRule>,
 }
 impl_lua_conversion!(TlsDomainServer);
 
@@ -82,6 +89,13 @@ pub struct TlsDomainClient {
 
     /// The path to the wezterm binary on the remote host
     pub remote_wezterm_path: Option<String>,
+
+    /// Overrides `input_normalization` for the panes in this domain
+    pub input_normalization: Option<InputNormalization>,
+
+    /// Overrides `treat_east_asian_ambiguous_width_as_wide` for
+    /// the panes in this domain
+    pub treat_east_asian_ambiguous_width_as_wide: Option<bool>,
 }
 impl_lua_conversion!(TlsDomainClient);
 
@@ -43,6 +43,24 @@ pub struct UnixDomain {
 
     #[serde(default = "default_write_timeout")]
     pub write_timeout: Duration,
+
+    /// When serving this domain, restricts what connecting users
+    /// are permitted to do.  If empty, any user that is able to
+    /// connect to the socket has full access.
+    #[serde(default)]
+    pub access_rules: Vec<MuxAccessRule>,
+
+    /// If set, when serving this domain, also listen on this path
+    /// for JSON-RPC requests that can be used to automate the mux
+    /// from other programs
+    pub control_socket_path: Option<PathBuf>,
+
+    /// Overrides `input_normalization` for the panes in this domain
+    pub input_normalization: Option<InputNormalization>,
+
+    /// Overrides `treat_east_asian_ambiguous_width_as_wide` for
+    /// the panes in this domain
+    pub treat_east_asian_ambiguous_width_as_wide: Option<bool>,
 }
 impl_lua_conversion!(UnixDomain);
 
@@ -57,6 +75,10 @@ impl Default for UnixDomain {
             skip_permissions_check: false,
             read_timeout: default_read_timeout(),
             write_timeout: default_write_timeout(),
+            access_rules: vec![],
+            control_socket_path: None,
+            input_normalization: None,
+            treat_east_asian_ambiguous_width_as_wide: None,
         }
     }
 }
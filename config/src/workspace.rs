@@ -0,0 +1,128 @@
+use crate::keyassignment::SpawnCommand;
+use crate::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Describes a named workspace, along with the tabs and panes that
+/// are created when it is opened for the first time
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct WorkspaceDefinition {
+    /// The name of the workspace.  When returned from the
+    /// gui-startup event, defaults to the workspace being started.
+    #[serde(default)]
+    pub name: String,
+
+    /// The working directory for programs spawned by this definition
+    /// that don't specify their own
+    pub cwd: Option<PathBuf>,
+
+    /// Environment variables that are set for each of the programs
+    /// spawned by this definition.  Variables that are set by the
+    /// individual panes take precedence.
+    #[serde(default)]
+    pub set_environment_variables: HashMap<String, String>,
+
+    /// The tabs to create in the first window of the workspace.
+    /// If empty, a single tab running the default program is created.
+    #[serde(default)]
+    pub tabs: Vec<WorkspaceTab>,
+}
+impl_lua_conversion!(WorkspaceDefinition);
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct WorkspaceTab {
+    /// The panes of the tab.  The first pane fills the tab, and each
+    /// subsequent pane is created by splitting the pane before it.
+    /// If empty, the tab runs the default program.
+    #[serde(default)]
+    pub panes: Vec<WorkspacePane>,
+}
+impl_lua_conversion!(WorkspaceTab);
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct WorkspacePane {
+    /// How the previous pane is split to make room for this one.
+    /// Ignored for the first pane in a tab.
+    #[serde(default)]
+    pub split: WorkspaceSplit,
+
+    /// The program to run in the pane
+    #[serde(default)]
+    pub command: SpawnCommand,
+}
+impl_lua_conversion!(WorkspacePane);
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum WorkspaceSplit {
+    /// Place the new pane to the right, like SplitHorizontal
+    Horizontal,
+    /// Place the new pane below, like SplitVertical
+    Vertical,
+}
+impl_lua_conversion!(WorkspaceSplit);
+
+impl Default for WorkspaceSplit {
+    fn default() -> Self {
+        Self::Horizontal
+    }
+}
+
+impl WorkspaceDefinition {
+    /// Returns `spawn` with the working directory and environment of
+    /// this workspace applied to it
+    pub fn apply_to(&self, spawn: &SpawnCommand) -> SpawnCommand {
+        let mut spawn = spawn.clone();
+        if spawn.cwd.is_none() {
+            spawn.cwd = self.cwd.clone();
+        }
+        for (k, v) in &self.set_environment_variables {
+            spawn
+                .set_environment_variables
+                .entry(k.clone())
+                .or_insert_with(|| v.clone());
+        }
+        spawn
+    }
+}
+
+/// Returns the definition of the workspace named `name`, if there is one
+pub fn find_workspace_definition<'a>(
+    workspaces: &'a [WorkspaceDefinition],
+    name: &str,
+) -> Option<&'a WorkspaceDefinition> {
+    workspaces.iter().find(|w| w.name == name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn apply_to() {
+        let mut def = WorkspaceDefinition {
+            name: "dev".to_string(),
+            cwd: Some(PathBuf::from("/src")),
+            ..Default::default()
+        };
+        def.set_environment_variables
+            .insert("A".to_string(), "workspace".to_string());
+        def.set_environment_variables
+            .insert("B".to_string(), "workspace".to_string());
+
+        let mut spawn = SpawnCommand::default();
+        spawn
+            .set_environment_variables
+            .insert("A".to_string(), "pane".to_string());
+        let spawn = def.apply_to(&spawn);
+        assert_eq!(spawn.cwd, Some(PathBuf::from("/src")));
+        assert_eq!(spawn.set_environment_variables["A"], "pane");
+        assert_eq!(spawn.set_environment_variables["B"], "workspace");
+
+        let spawn = def.apply_to(&SpawnCommand {
+            cwd: Some(PathBuf::from("/tmp")),
+            ..Default::default()
+        });
+        assert_eq!(spawn.cwd, Some(PathBuf::from("/tmp")));
+        assert!(find_workspace_definition(&[def], "dev").is_some());
+    }
+}
@@ -0,0 +1,146 @@
+//! Helpers for translating paths between Windows and the
+//! Windows Subsystem for Linux (WSL)
+
+/// Translates a Windows path into the path by which it is known
+/// inside WSL.  Drive paths such as `C:\Users\wez` map to
+/// `/mnt/c/Users/wez`, and paths inside a distribution such as
+/// `\\wsl$\Ubuntu\home\wez` map to `/home/wez`.
+/// Returns None if the path has no WSL equivalent.
+pub fn windows_path_to_wsl(path: &str) -> Option<String> {
+    for prefix in &[r"\\wsl$\", r"\\wsl.localhost\"] {
+        if let Some(rest) = strip_prefix_ignore_case(path, prefix) {
+            // Skip over the distribution name
+            let rest = match rest.find('\\') {
+                Some(idx) => &rest[idx..],
+                None => "",
+            };
+            let rest = rest.replace('\\', "/");
+            return Some(if rest.is_empty() {
+                "/".to_string()
+            } else {
+                rest
+            });
+        }
+    }
+
+    let bytes = path.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        if bytes.len() > 2 && bytes[2] != b'\\' && bytes[2] != b'/' {
+            // A drive relative path such as `C:foo` can't be translated
+            return None;
+        }
+        let drive = (bytes[0] as char).to_ascii_lowercase();
+        let rest = path[2..].replace('\\', "/");
+        let rest = rest.trim_end_matches('/');
+        return Some(format!("/mnt/{}{}", drive, rest));
+    }
+
+    None
+}
+
+/// Translates a path from inside WSL into its Windows equivalent.
+/// Paths under `/mnt/X/` map to the `X:` drive.  Other absolute
+/// paths can only be translated if the name of the `distro` is known,
+/// in which case they map to `\\wsl$\DISTRO\...`.
+/// Returns None if the path cannot be translated.
+pub fn wsl_path_to_windows(path: &str, distro: Option<&str>) -> Option<String> {
+    if !path.starts_with('/') {
+        return None;
+    }
+
+    if let Some(rest) = path.strip_prefix("/mnt/") {
+        let mut chars = rest.chars();
+        if let Some(drive) = chars.next() {
+            let remainder = chars.as_str();
+            if drive.is_ascii_alphabetic() && (remainder.is_empty() || remainder.starts_with('/')) {
+                let remainder = remainder.replace('/', "\\");
+                return Some(format!(
+                    "{}:{}",
+                    drive.to_ascii_uppercase(),
+                    if remainder.is_empty() {
+                        "\\"
+                    } else {
+                        &remainder
+                    }
+                ));
+            }
+        }
+    }
+
+    distro.map(|distro| format!(r"\\wsl$\{}{}", distro, path.replace('/', "\\")))
+}
+
+/// Returns true if `executable`, the path of a process on Windows,
+/// is the `wsl.exe` launcher.  WSL distributions run as local programs
+/// via that launcher, so a pane whose foreground process is `wsl.exe`
+/// is running inside WSL and expects paths as they are known there.
+pub fn is_wsl_launcher(executable: &str) -> bool {
+    let name = executable
+        .rsplit(|c| c == '\\' || c == '/')
+        .next()
+        .unwrap_or(executable);
+    name.eq_ignore_ascii_case("wsl.exe") || name.eq_ignore_ascii_case("wsl")
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.len() >= prefix.len()
+        && s.is_char_boundary(prefix.len())
+        && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+    {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn to_wsl() {
+        assert_eq!(
+            windows_path_to_wsl(r"C:\Users\wez").as_deref(),
+            Some("/mnt/c/Users/wez")
+        );
+        assert_eq!(windows_path_to_wsl(r"d:\").as_deref(), Some("/mnt/d"));
+        assert_eq!(
+            windows_path_to_wsl(r"\\wsl$\Ubuntu\home\wez").as_deref(),
+            Some("/home/wez")
+        );
+        assert_eq!(
+            windows_path_to_wsl(r"\\wsl.localhost\Ubuntu").as_deref(),
+            Some("/")
+        );
+        assert_eq!(windows_path_to_wsl(r"C:foo"), None);
+        assert_eq!(windows_path_to_wsl("relative"), None);
+    }
+
+    #[test]
+    fn launcher() {
+        assert!(is_wsl_launcher(r"C:\Windows\System32\wsl.exe"));
+        assert!(is_wsl_launcher(r"C:\Windows\System32\WSL.EXE"));
+        assert!(is_wsl_launcher("wsl.exe"));
+        assert!(!is_wsl_launcher(r"C:\Windows\System32\cmd.exe"));
+        assert!(!is_wsl_launcher(r"C:\tools\notwsl.exe"));
+    }
+
+    #[test]
+    fn to_windows() {
+        assert_eq!(
+            wsl_path_to_windows("/mnt/c/Users/wez", None).as_deref(),
+            Some(r"C:\Users\wez")
+        );
+        assert_eq!(wsl_path_to_windows("/mnt/d", None).as_deref(), Some(r"D:\"));
+        assert_eq!(wsl_path_to_windows("/home/wez", None), None);
+        assert_eq!(
+            wsl_path_to_windows("/home/wez", Some("Ubuntu")).as_deref(),
+            Some(r"\\wsl$\Ubuntu\home\wez")
+        );
+        assert_eq!(
+            wsl_path_to_windows("/mnt/data/foo", Some("Ubuntu")).as_deref(),
+            Some(r"\\wsl$\Ubuntu\mnt\data\foo")
+        );
+        assert_eq!(wsl_path_to_windows("relative", Some("Ubuntu")), None);
+    }
+}
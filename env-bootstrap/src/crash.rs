@@ -0,0 +1,191 @@
+//! Captures a report when the process panics, so that it can be
+//! inspected after the fact and attached to an issue.
+//! Reports are written to `DATA_DIR/crash-reports`; once the user
+//! has been offered a report it is moved into the `seen` directory
+//! beneath it.
+use chrono::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::panic::PanicInfo;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The number of reports that are retained once seen
+const MAX_SEEN_REPORTS: usize = 20;
+
+lazy_static::lazy_static! {
+    static ref ANNOTATIONS: Mutex<BTreeMap<&'static str, String>> = Mutex::new(BTreeMap::new());
+    static ref CONFIG_SUB: Mutex<Option<config::ConfigSubscription>> = Mutex::new(None);
+}
+
+pub fn crash_report_dir() -> PathBuf {
+    config::DATA_DIR.join("crash-reports")
+}
+
+fn seen_dir() -> PathBuf {
+    crash_report_dir().join("seen")
+}
+
+/// Records some information about the state of the process,
+/// such as the GPU in use, that will be included in any subsequent
+/// crash report
+pub fn annotate(key: &'static str, value: String) {
+    ANNOTATIONS.lock().unwrap().insert(key, value);
+}
+
+/// Records a hash of the configuration, which allows reports to be
+/// correlated with configuration changes without revealing its content
+fn annotate_config(config: &config::Config) {
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", config).hash(&mut hasher);
+    annotate("config", format!("{:016x}", hasher.finish()));
+}
+
+/// If `enable_crash_reports` is set, arranges for a report to be
+/// written when the process panics.  The previously installed
+/// panic hook continues to be called after the report is written.
+pub fn install_crash_handler(process_name: &'static str) {
+    let config = config::configuration();
+    if !config.enable_crash_reports {
+        return;
+    }
+    annotate_config(&config);
+
+    // The subscriber is called with the configuration locked, so we
+    // need to look at the new configuration from another thread
+    CONFIG_SUB
+        .lock()
+        .unwrap()
+        .replace(config::subscribe_to_config_reload(|| {
+            std::thread::spawn(|| annotate_config(&config::configuration()));
+            true
+        }));
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        match write_report(process_name, info) {
+            Ok(path) => eprintln!("wezterm: crash report written to {}", path.display()),
+            Err(err) => eprintln!("wezterm: failed to write crash report: {:#}", err),
+        }
+        default_hook(info);
+    }));
+}
+
+fn panic_message(info: &PanicInfo) -> String {
+    if let Some(s) = info.payload().downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = info.payload().downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+fn format_report(process_name: &str, info: &PanicInfo) -> String {
+    let mut report = String::new();
+    let mut field = |name: &str, value: &str| {
+        report.push_str(&format!("{:<10} {}\n", format!("{}:", name), value));
+    };
+
+    field("version", config::wezterm_version());
+    field(
+        "process",
+        &format!("{} (pid {})", process_name, std::process::id()),
+    );
+    field("time", &Local::now().to_rfc3339());
+    field(
+        "os",
+        &format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+    );
+    field(
+        "thread",
+        std::thread::current().name().unwrap_or("<unnamed>"),
+    );
+    field("panic", &panic_message(info));
+    if let Some(location) = info.location() {
+        field(
+            "location",
+            &format!(
+                "{}:{}:{}",
+                location.file(),
+                location.line(),
+                location.column()
+            ),
+        );
+    }
+
+    // If we panicked while the annotations were locked, don't deadlock
+    match ANNOTATIONS.try_lock() {
+        Ok(annotations) => {
+            for (key, value) in annotations.iter() {
+                field(key, value);
+            }
+        }
+        Err(_) => field("annotations", "<unavailable>"),
+    }
+
+    report.push_str("\nbacktrace:\n");
+    report.push_str(&format!("{:?}", backtrace::Backtrace::new()));
+    report
+}
+
+fn write_report(process_name: &str, info: &PanicInfo) -> anyhow::Result<PathBuf> {
+    let dir = crash_report_dir();
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "{}-{}-{}.txt",
+        process_name,
+        Local::now().format("%Y%m%d-%H%M%S"),
+        std::process::id()
+    ));
+    let mut file = std::fs::File::create(&path)?;
+    file.write_all(format_report(process_name, info).as_bytes())?;
+    Ok(path)
+}
+
+fn list_reports(dir: &Path) -> Vec<PathBuf> {
+    let mut reports: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map(|ext| ext == "txt").unwrap_or(false))
+            .collect(),
+        Err(_) => vec![],
+    };
+    reports.sort_by_key(|path| {
+        path.metadata()
+            .and_then(|m| m.modified())
+            .unwrap_or(std::time::SystemTime::UNIX_EPOCH)
+    });
+    reports
+}
+
+/// Returns the reports that the user has not yet been offered,
+/// oldest first
+pub fn pending_crash_reports() -> Vec<PathBuf> {
+    list_reports(&crash_report_dir())
+}
+
+/// Moves `report` into the `seen` directory so that it isn't offered
+/// again, pruning the oldest seen reports.
+/// Returns the new location of the report.
+pub fn mark_crash_report_seen(report: &Path) -> anyhow::Result<PathBuf> {
+    let seen = seen_dir();
+    std::fs::create_dir_all(&seen)?;
+    let name = report
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("{} has no file name", report.display()))?;
+    let dest = seen.join(name);
+    std::fs::rename(report, &dest)?;
+
+    let reports = list_reports(&seen);
+    if reports.len() > MAX_SEEN_REPORTS {
+        for old in &reports[..reports.len() - MAX_SEEN_REPORTS] {
+            std::fs::remove_file(old).ok();
+        }
+    }
+
+    Ok(dest)
+}
@@ -1,3 +1,4 @@
+pub mod crash;
 pub mod ringlog;
 pub use ringlog::setup_logger;
 use std::path::{Path, PathBuf};
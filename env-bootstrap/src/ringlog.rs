@@ -3,13 +3,170 @@
 //! a pretty logger on stderr.
 //! This allows other code to collect the ring buffer and display it
 //! within the application.
+//! The level filters are managed here rather than by the pretty logger
+//! so that they can be adjusted at runtime via `set_log_filters`.
 use chrono::prelude::*;
-use log::{Level, Record};
+use log::{Level, LevelFilter, Record};
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The number of entries retained for each level
+const RING_SIZE: usize = 128;
 
 lazy_static::lazy_static! {
     static ref RINGS: Mutex<Rings> = Mutex::new(Rings::new());
+    static ref FILTERS: Mutex<Arc<Filters>> = Mutex::new(Arc::new(Filters::default()));
+}
+
+/// Incremented each time that FILTERS is replaced, so that threads
+/// can tell when their snapshot of it is stale
+static FILTERS_GENERATION: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    /// This thread's snapshot of FILTERS, along with its generation.
+    /// Checking whether a record is enabled happens for every log
+    /// call, so we avoid taking the FILTERS lock unless it has changed.
+    static FILTERS_SNAPSHOT: RefCell<(usize, Arc<Filters>)> =
+        RefCell::new((0, Arc::new(Filters::default())));
+}
+
+fn replace_filters(filters: Filters) {
+    *FILTERS.lock().unwrap() = Arc::new(filters);
+    FILTERS_GENERATION.fetch_add(1, Ordering::Release);
+}
+
+/// Returns the level that the current filters apply to `target`
+fn level_for(target: &str) -> LevelFilter {
+    let generation = FILTERS_GENERATION.load(Ordering::Acquire);
+    FILTERS_SNAPSHOT
+        .try_with(|snapshot| {
+            let mut snapshot = snapshot.borrow_mut();
+            if snapshot.0 != generation {
+                *snapshot = (generation, Arc::clone(&FILTERS.lock().unwrap()));
+            }
+            snapshot.1.level_for(target)
+        })
+        // The thread local has already been destroyed
+        // because this thread is exiting
+        .unwrap_or_else(|_| FILTERS.lock().unwrap().level_for(target))
+}
+
+/// A single `module=level` directive.
+/// A directive without a module applies to all modules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directive {
+    pub module: Option<String>,
+    pub level: LevelFilter,
+}
+
+/// Parses a filter specification in the same syntax as `RUST_LOG`
+/// and `WEZTERM_LOG`: a comma separated list of `module=level`,
+/// `module` or `level` elements.
+pub fn parse_filters(spec: &str) -> Result<Vec<Directive>, String> {
+    let mut directives = vec![];
+    for element in spec.split(',') {
+        let element = element.trim();
+        if element.is_empty() {
+            continue;
+        }
+        let mut parts = element.splitn(2, '=');
+        let first = parts.next().unwrap_or("").trim();
+        let directive = match parts.next() {
+            Some(level) => Directive {
+                module: Some(first.to_string()),
+                level: level
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid log level `{}` in `{}`", level, element))?,
+            },
+            None => match first.parse() {
+                Ok(level) => Directive {
+                    module: None,
+                    level,
+                },
+                // A bare module name enables all logging for that module
+                Err(_) => Directive {
+                    module: Some(first.to_string()),
+                    level: LevelFilter::Trace,
+                },
+            },
+        };
+        directives.push(directive);
+    }
+    Ok(directives)
+}
+
+#[derive(Default, Clone)]
+struct Filters {
+    directives: Vec<Directive>,
+}
+
+impl Filters {
+    /// Adds or replaces the directives; later directives for
+    /// the same module take precedence over earlier ones
+    fn merge(&mut self, directives: Vec<Directive>) {
+        for directive in directives {
+            match self
+                .directives
+                .iter_mut()
+                .find(|d| d.module == directive.module)
+            {
+                Some(existing) => existing.level = directive.level,
+                None => self.directives.push(directive),
+            }
+        }
+    }
+
+    /// Returns the level that applies to `target`, which is
+    /// the level of the directive with the longest matching
+    /// module path
+    fn level_for(&self, target: &str) -> LevelFilter {
+        let mut best: Option<&Directive> = None;
+        for directive in &self.directives {
+            let matched = match &directive.module {
+                None => true,
+                Some(module) => {
+                    target == module
+                        || (target.starts_with(module.as_str())
+                            && target[module.len()..].starts_with("::"))
+                }
+            };
+            if !matched {
+                continue;
+            }
+            let len = |d: &Directive| d.module.as_ref().map(|m| m.len()).unwrap_or(0);
+            if best.map(|b| len(directive) >= len(b)).unwrap_or(true) {
+                best.replace(directive);
+            }
+        }
+        best.map(|d| d.level).unwrap_or(LevelFilter::Error)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|d| d.level)
+            .max()
+            .unwrap_or(LevelFilter::Error)
+    }
+}
+
+/// Adjusts the level filters at runtime.
+/// `spec` uses the same syntax as `WEZTERM_LOG`; the directives
+/// it contains are merged into the current set, replacing any
+/// existing directive for the same module.
+pub fn set_log_filters(spec: &str) -> Result<(), String> {
+    let directives = parse_filters(spec)?;
+    let mut current = FILTERS.lock().unwrap();
+    let mut filters = Filters::clone(&current);
+    filters.merge(directives);
+    log::set_max_level(filters.max_level());
+    *current = Arc::new(filters);
+    drop(current);
+    FILTERS_GENERATION.fetch_add(1, Ordering::Release);
+    Ok(())
 }
 
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
@@ -30,7 +187,7 @@ impl LevelRing {
     fn new(level: Level) -> Self {
         let mut entries = vec![];
         let now = Local::now();
-        for _ in 0..16 {
+        for _ in 0..RING_SIZE {
             entries.push(Entry {
                 then: now,
                 level,
@@ -137,7 +294,7 @@ impl Logger {
 
 impl log::Log for Logger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        self.pretty.enabled(metadata)
+        metadata.level() <= level_for(metadata.target())
     }
 
     fn flush(&self) {
@@ -145,6 +302,9 @@ impl log::Log for Logger {
     }
 
     fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
         RINGS.lock().unwrap().log(record);
         self.pretty.log(record)
     }
@@ -159,16 +319,34 @@ pub fn get_entries() -> Vec<Entry> {
 
 pub fn setup_logger() {
     let mut builder = pretty_env_logger::formatted_timed_builder();
-    builder.filter(Some("wgpu_core"), log::LevelFilter::Error);
-    builder.filter(Some("gfx_backend_metal"), log::LevelFilter::Error);
+    // Filtering is performed by our Logger
+    builder.filter(None, LevelFilter::Trace);
+    let pretty = builder.build();
+
+    let mut filters = Filters::default();
+    filters.merge(vec![
+        Directive {
+            module: None,
+            level: LevelFilter::Info,
+        },
+        Directive {
+            module: Some("wgpu_core".to_string()),
+            level: LevelFilter::Error,
+        },
+        Directive {
+            module: Some("gfx_backend_metal".to_string()),
+            level: LevelFilter::Error,
+        },
+    ]);
+    let mut bad_spec = None;
     if let Ok(s) = std::env::var("WEZTERM_LOG") {
-        builder.parse_filters(&s);
-    } else {
-        builder.filter(None, log::LevelFilter::Info);
+        match parse_filters(&s) {
+            Ok(directives) => filters.merge(directives),
+            Err(err) => bad_spec = Some(err),
+        }
     }
-
-    let pretty = builder.build();
-    let max_level = pretty.filter();
+    let max_level = filters.max_level();
+    replace_filters(filters);
 
     let pretty = Box::new(pretty);
     let logger = Logger::new(pretty);
@@ -176,4 +354,73 @@ pub fn setup_logger() {
     if log::set_boxed_logger(Box::new(logger)).is_ok() {
         log::set_max_level(max_level);
     }
+
+    if let Some(err) = bad_spec {
+        log::error!("WEZTERM_LOG: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_filters("info, wezterm_font=debug,mux").unwrap(),
+            vec![
+                Directive {
+                    module: None,
+                    level: LevelFilter::Info
+                },
+                Directive {
+                    module: Some("wezterm_font".to_string()),
+                    level: LevelFilter::Debug
+                },
+                Directive {
+                    module: Some("mux".to_string()),
+                    level: LevelFilter::Trace
+                },
+            ]
+        );
+        assert!(parse_filters("mux=loud").is_err());
+    }
+
+    #[test]
+    fn longest_prefix() {
+        let mut filters = Filters::default();
+        filters.merge(parse_filters("warn,wezterm_font=info,wezterm_font::locator=trace").unwrap());
+        assert_eq!(filters.level_for("wezterm_gui"), LevelFilter::Warn);
+        assert_eq!(filters.level_for("wezterm_font"), LevelFilter::Info);
+        assert_eq!(filters.level_for("wezterm_font::shaper"), LevelFilter::Info);
+        assert_eq!(
+            filters.level_for("wezterm_font::locator::fontconfig"),
+            LevelFilter::Trace
+        );
+        // A module name that merely shares a prefix doesn't match
+        assert_eq!(filters.level_for("wezterm_fontx"), LevelFilter::Warn);
+        assert_eq!(filters.max_level(), LevelFilter::Trace);
+
+        filters.merge(parse_filters("wezterm_font::locator=off").unwrap());
+        assert_eq!(filters.level_for("wezterm_font::locator"), LevelFilter::Off);
+        assert_eq!(filters.max_level(), LevelFilter::Info);
+    }
+
+    #[test]
+    fn snapshot() {
+        replace_filters(Filters::default());
+        assert_eq!(level_for("mux"), LevelFilter::Error);
+
+        set_log_filters("mux=debug").unwrap();
+        assert_eq!(level_for("mux"), LevelFilter::Debug);
+        assert_eq!(level_for("config"), LevelFilter::Error);
+
+        // Other threads observe the change too
+        std::thread::spawn(|| assert_eq!(level_for("mux::tab"), LevelFilter::Debug))
+            .join()
+            .unwrap();
+
+        set_log_filters("mux=warn").unwrap();
+        assert_eq!(level_for("mux"), LevelFilter::Warn);
+    }
 }
@@ -0,0 +1,65 @@
+use crate::pane::PaneId;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Instant, SystemTime};
+
+static CLIENT_ID: AtomicUsize = AtomicUsize::new(0);
+lazy_static::lazy_static! {
+    static ref EPOCH: u64 = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+}
+
+/// Identifies a client (typically a GUI process) that is attached
+/// to the mux.  Each connection from a given process allocates a
+/// distinct `id` so that two windows attached from the same host
+/// can still be told apart.
+/// This type is used directly by the codec, take care to bump
+/// the codec version if you change this
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct ClientId {
+    pub hostname: String,
+    pub username: String,
+    pub pid: u32,
+    pub epoch: u64,
+    pub id: usize,
+}
+
+impl ClientId {
+    pub fn new() -> Self {
+        let id = CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+        Self {
+            hostname: hostname::get()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|_| "localhost".to_string()),
+            username: config::username_from_env().unwrap_or_else(|_| "somebody".to_string()),
+            pid: std::process::id(),
+            epoch: *EPOCH,
+            id,
+        }
+    }
+}
+
+/// Per-client state tracked by the mux.  This is what allows two
+/// clients that are attached to the same mux to look at different
+/// workspaces and to have their own notion of which pane is focused,
+/// rather than mirroring each other.
+#[derive(Clone, Debug)]
+pub struct ClientInfo {
+    pub client_id: ClientId,
+    pub connected_at: Instant,
+    pub active_workspace: String,
+    pub focused_pane_id: Option<PaneId>,
+}
+
+impl ClientInfo {
+    pub fn new(client_id: &ClientId, workspace: &str) -> Self {
+        Self {
+            client_id: client_id.clone(),
+            connected_at: Instant::now(),
+            active_workspace: workspace.to_string(),
+            focused_pane_id: None,
+        }
+    }
+}
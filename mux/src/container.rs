@@ -0,0 +1,329 @@
+use crate::domain::{Domain, DomainId, DomainState, LocalDomain};
+use crate::encoding::CharacterEncoding;
+use crate::exec::wrap_command;
+use crate::pane::{Pane, PaneId};
+use crate::tab::{SplitDirection, Tab, TabId};
+use crate::window::WindowId;
+use crate::Mux;
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use portable_pty::{CommandBuilder, PtySize};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Starts a login shell, preferring bash if the container has it
+const DEFAULT_SHELL: &str =
+    "if command -v bash >/dev/null 2>&1; then exec bash -l; else exec sh -l; fi";
+
+/// A container as reported by `docker ps` or `podman ps`
+#[derive(Debug, Clone, PartialEq)]
+struct ContainerInfo {
+    name: String,
+    image: String,
+}
+
+/// A domain that spawns panes inside a container via
+/// `docker exec` or `podman exec`.  These domains are created
+/// automatically for the running containers of each of the
+/// configured `container_runtimes`.  A container that is no longer
+/// running is shown as detached; attaching to it starts it again.
+pub struct ContainerDomain {
+    local: LocalDomain,
+    name: String,
+    label: String,
+    runtime: String,
+    container: String,
+    running: Arc<AtomicBool>,
+}
+
+impl ContainerDomain {
+    fn new(runtime: &str, info: &ContainerInfo) -> anyhow::Result<Self> {
+        let name = format!("{}:{}", runtime, info.name);
+        Ok(Self {
+            local: LocalDomain::new(&name)?,
+            label: format!("{} container {} ({})", runtime, info.name, info.image),
+            name,
+            runtime: runtime.to_string(),
+            container: info.name.clone(),
+            running: Arc::new(AtomicBool::new(true)),
+        })
+    }
+
+    pub fn runtime(&self) -> &str {
+        &self.runtime
+    }
+
+    pub fn container_name(&self) -> &str {
+        &self.container
+    }
+
+    fn set_running(&self, running: bool) {
+        self.running.store(running, Ordering::SeqCst);
+    }
+
+    /// Computes the command to spawn.  The working directory was most
+    /// likely reported by a program running inside the container, and
+    /// usually doesn't exist locally, so it is only passed to the
+    /// container.
+    fn build_command(
+        &self,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+    ) -> Option<CommandBuilder> {
+        let mut prefix = vec![self.runtime.clone(), "exec".to_string(), "-it".to_string()];
+        let command_cwd = command
+            .as_ref()
+            .and_then(|cmd| cmd.get_cwd())
+            .map(|cwd| cwd.to_string_lossy().to_string());
+        if let Some(dir) = command_cwd.or(command_dir) {
+            prefix.push("-w".to_string());
+            prefix.push(dir);
+        }
+        prefix.push(self.container.clone());
+
+        let default_prog = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            DEFAULT_SHELL.to_string(),
+        ];
+        let mut wrapped = wrap_command(&prefix, Some(&default_prog), command)?;
+        wrapped.clear_cwd();
+        Some(wrapped)
+    }
+}
+
+#[async_trait(?Send)]
+impl Domain for ContainerDomain {
+    async fn spawn(
+        &self,
+        size: PtySize,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
+        window: WindowId,
+    ) -> anyhow::Result<Rc<Tab>> {
+        let command = self.build_command(command, command_dir);
+        self.local
+            .spawn(size, command, None, encoding, window)
+            .await
+    }
+
+    async fn split_pane(
+        &self,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
+        tab: TabId,
+        pane_id: PaneId,
+        direction: SplitDirection,
+    ) -> anyhow::Result<Rc<dyn Pane>> {
+        let command = self.build_command(command, command_dir);
+        self.local
+            .split_pane(command, None, encoding, tab, pane_id, direction)
+            .await
+    }
+
+    fn domain_id(&self) -> DomainId {
+        self.local.domain_id()
+    }
+
+    fn domain_name(&self) -> &str {
+        &self.name
+    }
+
+    fn domain_label(&self) -> &str {
+        &self.label
+    }
+
+    /// Starts the container if it is not running
+    async fn attach(&self) -> anyhow::Result<()> {
+        if self.running.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let runtime = self.runtime.clone();
+        let container = self.container.clone();
+        smol::unblock(move || -> anyhow::Result<()> {
+            let status = std::process::Command::new(&runtime)
+                .args(&["start", &container])
+                .status()
+                .with_context(|| format!("running {} start {}", runtime, container))?;
+            if !status.success() {
+                bail!("{} start {} failed: {}", runtime, container, status);
+            }
+            Ok(())
+        })
+        .await?;
+        self.set_running(true);
+        Ok(())
+    }
+
+    fn detach(&self) -> anyhow::Result<()> {
+        bail!("detach not implemented");
+    }
+
+    fn state(&self) -> DomainState {
+        if self.running.load(Ordering::SeqCst) {
+            DomainState::Attached
+        } else {
+            DomainState::Detached
+        }
+    }
+}
+
+/// Parses the output of `ps --format '{{.Names}}\t{{.Image}}'`
+fn parse_container_list(output: &str) -> Vec<ContainerInfo> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().splitn(2, '\t');
+            let name = fields.next()?.trim();
+            if name.is_empty() {
+                return None;
+            }
+            let image = fields.next().unwrap_or("").trim();
+            Some(ContainerInfo {
+                name: name.to_string(),
+                image: image.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn list_running_containers(runtime: &str) -> anyhow::Result<Vec<ContainerInfo>> {
+    let output = std::process::Command::new(runtime)
+        .args(&["ps", "--format", "{{.Names}}\t{{.Image}}"])
+        .output()
+        .with_context(|| format!("running {} ps", runtime))?;
+    if !output.status.success() {
+        bail!(
+            "{} ps failed: {}: {}",
+            runtime,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(parse_container_list(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Synchronizes the set of container domains with the containers
+/// that are currently running under `runtime`
+fn update_container_domains(runtime: &str, containers: &[ContainerInfo]) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+
+    for domain in mux.iter_domains() {
+        if let Some(container_dom) = domain.downcast_ref::<ContainerDomain>() {
+            if container_dom.runtime() == runtime {
+                let running = containers
+                    .iter()
+                    .any(|info| info.name == container_dom.container_name());
+                container_dom.set_running(running);
+            }
+        }
+    }
+
+    for info in containers {
+        let name = format!("{}:{}", runtime, info.name);
+        if mux.get_domain_by_name(&name).is_none() {
+            log::trace!("adding domain for {} container {}", runtime, info.name);
+            let domain: Arc<dyn Domain> = Arc::new(ContainerDomain::new(runtime, info)?);
+            mux.add_domain(&domain);
+        }
+    }
+
+    Ok(())
+}
+
+/// Periodically enumerates the running containers of each of the
+/// configured `container_runtimes`, adding a domain for each one
+pub fn register_container_domains() {
+    let config = config::configuration();
+    if config.container_runtimes.is_empty() {
+        return;
+    }
+    let interval = Duration::from_secs(config.container_refresh_interval.max(1));
+
+    promise::spawn::spawn(async move {
+        loop {
+            for runtime in config::configuration().container_runtimes.iter() {
+                let rt = runtime.clone();
+                match smol::unblock(move || list_running_containers(&rt)).await {
+                    Ok(containers) => {
+                        if let Err(err) = update_container_domains(runtime, &containers) {
+                            log::error!("updating {} container domains: {:#}", runtime, err);
+                        }
+                    }
+                    Err(err) => log::debug!("{:#}", err),
+                }
+            }
+            smol::Timer::after(interval).await;
+        }
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_ps_output() {
+        assert_eq!(
+            parse_container_list("dev\tubuntu:20.04\n\ndb\tpostgres\nbare\n"),
+            vec![
+                ContainerInfo {
+                    name: "dev".to_string(),
+                    image: "ubuntu:20.04".to_string()
+                },
+                ContainerInfo {
+                    name: "db".to_string(),
+                    image: "postgres".to_string()
+                },
+                ContainerInfo {
+                    name: "bare".to_string(),
+                    image: "".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn working_directory() {
+        let domain = ContainerDomain::new(
+            "docker",
+            &ContainerInfo {
+                name: "dev".to_string(),
+                image: "ubuntu".to_string(),
+            },
+        )
+        .unwrap();
+
+        let argv = |cmd: &CommandBuilder| -> Vec<String> {
+            cmd.get_argv()
+                .iter()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect()
+        };
+
+        let mut cmd = CommandBuilder::new("top");
+        cmd.cwd("/srv/app");
+        let wrapped = domain.build_command(Some(cmd), None).unwrap();
+        assert_eq!(
+            argv(&wrapped),
+            vec!["docker", "exec", "-it", "-w", "/srv/app", "dev", "top"]
+        );
+        assert!(wrapped.get_cwd().is_none());
+
+        let wrapped = domain
+            .build_command(None, Some("/home/me".to_string()))
+            .unwrap();
+        assert_eq!(
+            argv(&wrapped)[..6],
+            ["docker", "exec", "-it", "-w", "/home/me", "dev"]
+        );
+        assert!(wrapped.get_cwd().is_none());
+    }
+}
@@ -5,12 +5,13 @@
 //! container or actually remote, running on the other end
 //! of an ssh session somewhere.
 
+use crate::encoding::{CharacterEncoding, EncodingWriter, PaneEncoding};
 use crate::localpane::LocalPane;
 use crate::pane::{alloc_pane_id, Pane, PaneId};
 use crate::tab::{SplitDirection, Tab, TabId};
 use crate::window::WindowId;
-use crate::Mux;
-use anyhow::{bail, Error};
+use crate::{Mux, MuxNotification};
+use anyhow::{anyhow, bail, Error};
 use async_trait::async_trait;
 use config::configuration;
 use downcast_rs::{impl_downcast, Downcast};
@@ -32,12 +33,16 @@ pub fn alloc_domain_id() -> DomainId {
 
 #[async_trait(?Send)]
 pub trait Domain: Downcast {
-    /// Spawn a new command within this domain
+    /// Spawn a new command within this domain.
+    /// If `encoding` is specified, the command exchanges data
+    /// using that character encoding rather than the default
+    /// encoding of the domain.
     async fn spawn(
         &self,
         size: PtySize,
         command: Option<CommandBuilder>,
         command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
         window: WindowId,
     ) -> Result<Rc<Tab>, Error>;
 
@@ -45,11 +50,23 @@ pub trait Domain: Downcast {
         &self,
         command: Option<CommandBuilder>,
         command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
         tab: TabId,
         pane_id: PaneId,
         split_direction: SplitDirection,
     ) -> anyhow::Result<Rc<dyn Pane>>;
 
+    /// Relaunches the command that was originally spawned into
+    /// `pane_id`, whose process must have exited, replacing the
+    /// pane with a new pane in the same position within its tab.
+    async fn respawn_pane(&self, pane_id: PaneId) -> anyhow::Result<Rc<dyn Pane>> {
+        bail!(
+            "domain {} doesn't support respawning pane {}",
+            self.domain_name(),
+            pane_id
+        );
+    }
+
     /// Returns false if the `spawn` method will never succeed.
     /// There are some internal placeholder domains that are
     /// pre-created with local UI that we do not want to allow
@@ -86,6 +103,7 @@ pub struct LocalDomain {
     pty_system: Box<dyn PtySystem>,
     id: DomainId,
     name: String,
+    encoding: CharacterEncoding,
 }
 
 impl LocalDomain {
@@ -99,8 +117,52 @@ impl LocalDomain {
             pty_system,
             id,
             name: name.to_string(),
+            encoding: CharacterEncoding::default(),
         }
     }
+
+    /// Sets the character encoding used by panes that are spawned
+    /// into this domain
+    pub fn with_encoding(mut self, encoding: CharacterEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Spawns `cmd` into a new pty and wraps it in a pane, remembering
+    /// the command so that the pane can be respawned later.
+    /// The pane exchanges data with `cmd` using `encoding`.
+    fn spawn_pane(
+        &self,
+        mut cmd: CommandBuilder,
+        size: PtySize,
+        encoding: CharacterEncoding,
+    ) -> anyhow::Result<Rc<dyn Pane>> {
+        let command = cmd.clone();
+        let pair = self.pty_system.openpty(size)?;
+        let pane_id = alloc_pane_id();
+        cmd.env("WEZTERM_PANE", pane_id.to_string());
+
+        let child = pair.slave.spawn_command(cmd)?;
+        log::trace!("spawned: {:?}", child);
+
+        let encoding = PaneEncoding::new(encoding);
+        let writer = pair.master.try_clone_writer()?;
+        let input_writer = pair.master.try_clone_writer()?;
+
+        let terminal = wezterm_term::Terminal::new(
+            crate::pty_size_to_terminal_size(size),
+            std::sync::Arc::new(config::TermConfig::new().for_domain(&self.name)),
+            "WezTerm",
+            config::wezterm_version(),
+            Box::new(EncodingWriter::new(writer, encoding.clone())),
+        );
+
+        Ok(Rc::new(
+            LocalPane::new(pane_id, terminal, child, pair.master, self.id)
+                .with_command(command)
+                .with_encoding(encoding, input_writer),
+        ))
+    }
 }
 
 #[async_trait(?Send)]
@@ -110,6 +172,7 @@ impl Domain for LocalDomain {
         size: PtySize,
         command: Option<CommandBuilder>,
         command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
         window: WindowId,
     ) -> Result<Rc<Tab>, Error> {
         let config = configuration();
@@ -129,32 +192,9 @@ impl Domain for LocalDomain {
                 cmd.cwd(dir);
             }
         }
-        let pair = self.pty_system.openpty(size)?;
-        let pane_id = alloc_pane_id();
-        cmd.env("WEZTERM_PANE", pane_id.to_string());
-
-        let child = pair.slave.spawn_command(cmd)?;
-        log::trace!("spawned: {:?}", child);
-
-        let writer = pair.master.try_clone_writer()?;
+        let pane = self.spawn_pane(cmd, size, encoding.unwrap_or(self.encoding))?;
         let mux = Mux::get().unwrap();
 
-        let terminal = wezterm_term::Terminal::new(
-            crate::pty_size_to_terminal_size(size),
-            std::sync::Arc::new(config::TermConfig::new()),
-            "WezTerm",
-            config::wezterm_version(),
-            Box::new(writer),
-        );
-
-        let pane: Rc<dyn Pane> = Rc::new(LocalPane::new(
-            pane_id,
-            terminal,
-            child,
-            pair.master,
-            self.id,
-        ));
-
         let tab = Rc::new(Tab::new(&size));
         tab.assign_pane(&pane);
 
@@ -168,6 +208,7 @@ impl Domain for LocalDomain {
         &self,
         command: Option<CommandBuilder>,
         command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
         tab: TabId,
         pane_id: PaneId,
         direction: SplitDirection,
@@ -209,33 +250,59 @@ impl Domain for LocalDomain {
                 cmd.cwd(dir);
             }
         }
-        let pair = self.pty_system.openpty(split_size.second)?;
-        let pane_id = alloc_pane_id();
-        cmd.env("WEZTERM_PANE", pane_id.to_string());
-        let child = pair.slave.spawn_command(cmd)?;
-        log::trace!("spawned: {:?}", child);
+        let pane = self.spawn_pane(cmd, split_size.second, encoding.unwrap_or(self.encoding))?;
 
-        let writer = pair.master.try_clone_writer()?;
+        tab.split_and_insert(pane_index, direction, Rc::clone(&pane))?;
 
-        let terminal = wezterm_term::Terminal::new(
-            crate::pty_size_to_terminal_size(split_size.second),
-            std::sync::Arc::new(config::TermConfig::new()),
-            "WezTerm",
-            config::wezterm_version(),
-            Box::new(writer),
-        );
+        mux.add_pane(&pane)?;
 
-        let pane: Rc<dyn Pane> = Rc::new(LocalPane::new(
-            pane_id,
-            terminal,
-            child,
-            pair.master,
-            self.id,
-        ));
+        Ok(pane)
+    }
 
-        tab.split_and_insert(pane_index, direction, Rc::clone(&pane))?;
+    async fn respawn_pane(&self, pane_id: PaneId) -> anyhow::Result<Rc<dyn Pane>> {
+        let mux = Mux::get().unwrap();
+        let (_domain_id, window_id, tab_id) = mux
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+        let tab = mux
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", tab_id))?;
+        let old_pane = mux
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+        let local_pane = old_pane
+            .downcast_ref::<LocalPane>()
+            .ok_or_else(|| anyhow!("pane {} is not a local pane", pane_id))?;
+        if local_pane.is_process_running() {
+            bail!("pane {} is still running", pane_id);
+        }
+        let cmd = local_pane
+            .spawn_command()
+            .cloned()
+            .ok_or_else(|| anyhow!("the command for pane {} is not known", pane_id))?;
+        let encoding = local_pane.character_encoding();
+
+        tab.set_zoomed(false);
+        let pos = tab
+            .iter_panes()
+            .into_iter()
+            .find(|p| p.pane.pane_id() == pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found in tab {}", pane_id, tab_id))?;
+        let size = PtySize {
+            rows: pos.height as u16,
+            cols: pos.width as u16,
+            pixel_width: pos.pixel_width as u16,
+            pixel_height: pos.pixel_height as u16,
+        };
 
+        let pane = self.spawn_pane(cmd, size, encoding)?;
+        tab.replace_pane(pos.index, &pane);
+        if let Some(window) = mux.get_window(window_id) {
+            window.assign_clipboard_to_pane(&pane);
+        }
         mux.add_pane(&pane)?;
+        mux.remove_pane(pane_id);
+        mux.notify(MuxNotification::WindowInvalidated(window_id));
 
         Ok(pane)
     }
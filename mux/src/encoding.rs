@@ -0,0 +1,361 @@
+//! Transcoding of the data that is exchanged with programs that
+//! don't use UTF-8, such as legacy hosts reached via a serial port.
+use anyhow::anyhow;
+use encoding_rs::{CoderResult, Decoder, EncoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use std::collections::HashMap;
+use std::io::{Read, Result as IoResult, Write};
+use std::sync::{Arc, Mutex};
+
+/// The characters represented by the bytes 0x80-0xff in code page 437
+const CP437_HIGH: &str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»\
+                          ░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀\
+                          αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}";
+
+lazy_static::lazy_static! {
+    static ref CP437_DECODE: Vec<char> = CP437_HIGH.chars().collect();
+    static ref CP437_ENCODE: HashMap<char, u8> = CP437_DECODE
+        .iter()
+        .enumerate()
+        .map(|(idx, &c)| (c, 0x80 + idx as u8))
+        .collect();
+}
+
+/// A character encoding that can be used to talk to the program
+/// running in a pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharacterEncoding {
+    /// ISO-8859-1; each byte is the codepoint with the same value.
+    /// This is distinct from windows-1252, which is what the WHATWG
+    /// encoding standard uses for the `iso-8859-1` label.
+    Latin1,
+    /// The character set of the original IBM PC
+    Cp437,
+    /// An encoding from the WHATWG encoding standard, such as
+    /// UTF-8, Shift_JIS or windows-1252
+    Whatwg(&'static Encoding),
+}
+
+impl Default for CharacterEncoding {
+    fn default() -> Self {
+        Self::Whatwg(UTF_8)
+    }
+}
+
+impl CharacterEncoding {
+    /// Resolves an encoding label such as `"cp437"`, `"latin1"`
+    /// or `"Shift_JIS"`
+    pub fn for_label(label: &str) -> anyhow::Result<Self> {
+        let normalized: String = label
+            .trim()
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_' | ' '))
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        match normalized.as_str() {
+            "latin1" | "iso88591" | "l1" => return Ok(Self::Latin1),
+            "cp437" | "ibm437" | "437" => return Ok(Self::Cp437),
+            _ => {}
+        }
+
+        match Encoding::for_label_no_replacement(label.trim().as_bytes()) {
+            // UTF-16 isn't compatible with the escape sequences that
+            // the terminal exchanges with the program
+            Some(enc) if enc == UTF_16BE || enc == UTF_16LE => {
+                Err(anyhow!("encoding {} is not supported", label))
+            }
+            Some(enc) => Ok(Self::Whatwg(enc)),
+            None => Err(anyhow!("unknown encoding {}", label)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Latin1 => "ISO-8859-1",
+            Self::Cp437 => "CP437",
+            Self::Whatwg(enc) => enc.name(),
+        }
+    }
+
+    pub fn is_utf8(&self) -> bool {
+        *self == Self::Whatwg(UTF_8)
+    }
+
+    /// Converts `text` into this encoding, replacing characters
+    /// that cannot be represented with `?`
+    pub fn encode(&self, text: &str, output: &mut Vec<u8>) {
+        match self {
+            Self::Latin1 => {
+                output.extend(
+                    text.chars()
+                        .map(|c| if (c as u32) <= 0xff { c as u8 } else { b'?' }),
+                )
+            }
+            Self::Cp437 => output.extend(text.chars().map(|c| {
+                if c.is_ascii() {
+                    c as u8
+                } else {
+                    CP437_ENCODE.get(&c).copied().unwrap_or(b'?')
+                }
+            })),
+            Self::Whatwg(enc) => {
+                let mut encoder = enc.new_encoder();
+                let mut input = text;
+                let mut buf = [0u8; 1024];
+                loop {
+                    let (result, read, written) =
+                        encoder.encode_from_utf8_without_replacement(input, &mut buf, true);
+                    output.extend_from_slice(&buf[..written]);
+                    input = &input[read..];
+                    match result {
+                        EncoderResult::InputEmpty => break,
+                        EncoderResult::OutputFull => {}
+                        EncoderResult::Unmappable(_) => output.push(b'?'),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The encoding used by the program running in a pane.
+/// This is shared between the pane, the thread that reads its
+/// output and the writer that sends it input, so that it can
+/// be changed while the program is running.
+#[derive(Clone, Default)]
+pub struct PaneEncoding {
+    inner: Arc<Mutex<CharacterEncoding>>,
+}
+
+impl PaneEncoding {
+    pub fn new(encoding: CharacterEncoding) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(encoding)),
+        }
+    }
+
+    pub fn get(&self) -> CharacterEncoding {
+        *self.inner.lock().unwrap()
+    }
+
+    pub fn set(&self, encoding: CharacterEncoding) {
+        *self.inner.lock().unwrap() = encoding;
+    }
+}
+
+/// Converts the output of a program from its encoding to UTF-8
+pub struct DecodingReader<R> {
+    inner: R,
+    encoding: PaneEncoding,
+    /// The stateful decoder for multi-byte encodings, which may
+    /// have a partial character buffered from a prior read
+    decoder: Option<(CharacterEncoding, Decoder)>,
+    raw: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl<R: Read> DecodingReader<R> {
+    pub fn new(inner: R, encoding: PaneEncoding) -> Self {
+        Self {
+            inner,
+            encoding,
+            decoder: None,
+            raw: vec![],
+            pending: vec![],
+        }
+    }
+
+    fn decode(&mut self, encoding: CharacterEncoding, len: usize) {
+        let raw = &self.raw[..len];
+        match encoding {
+            CharacterEncoding::Latin1 => {
+                for &b in raw {
+                    let mut buf = [0u8; 4];
+                    self.pending
+                        .extend_from_slice((b as char).encode_utf8(&mut buf).as_bytes());
+                }
+            }
+            CharacterEncoding::Cp437 => {
+                for &b in raw {
+                    let c = if b < 0x80 {
+                        b as char
+                    } else {
+                        CP437_DECODE[(b - 0x80) as usize]
+                    };
+                    let mut buf = [0u8; 4];
+                    self.pending
+                        .extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+            CharacterEncoding::Whatwg(enc) => {
+                let is_current =
+                    matches!(&self.decoder, Some((current, _)) if *current == encoding);
+                if !is_current {
+                    self.decoder = Some((encoding, enc.new_decoder()));
+                }
+                let decoder = match self.decoder.as_mut() {
+                    Some((_, decoder)) => decoder,
+                    None => return,
+                };
+                let mut text = String::with_capacity(
+                    decoder
+                        .max_utf8_buffer_length(raw.len())
+                        .unwrap_or(raw.len() * 3),
+                );
+                let (result, _read, _replaced) = decoder.decode_to_string(raw, &mut text, false);
+                debug_assert!(result == CoderResult::InputEmpty);
+                self.pending.extend_from_slice(text.as_bytes());
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for DecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        while self.pending.is_empty() {
+            let encoding = self.encoding.get();
+            if encoding.is_utf8() {
+                // The terminal parser deals with UTF-8 directly
+                self.decoder.take();
+                return self.inner.read(buf);
+            }
+
+            self.raw.resize(buf.len(), 0);
+            let len = self.inner.read(&mut self.raw)?;
+            if len == 0 {
+                return Ok(0);
+            }
+            self.decode(encoding, len);
+        }
+
+        let len = buf.len().min(self.pending.len());
+        buf[..len].copy_from_slice(&self.pending[..len]);
+        self.pending.drain(..len);
+        Ok(len)
+    }
+}
+
+/// Converts the UTF-8 input that is sent to a program into its encoding
+pub struct EncodingWriter<W> {
+    inner: W,
+    encoding: PaneEncoding,
+    /// The start of a UTF-8 sequence that was split across writes
+    partial: Vec<u8>,
+}
+
+impl<W: Write> EncodingWriter<W> {
+    pub fn new(inner: W, encoding: PaneEncoding) -> Self {
+        Self {
+            inner,
+            encoding,
+            partial: vec![],
+        }
+    }
+}
+
+impl<W: Write> Write for EncodingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let encoding = self.encoding.get();
+        if encoding.is_utf8() && self.partial.is_empty() {
+            return self.inner.write(buf);
+        }
+
+        self.partial.extend_from_slice(buf);
+        let mut output = vec![];
+        let mut data = &self.partial[..];
+        while !data.is_empty() {
+            match std::str::from_utf8(data) {
+                Ok(text) => {
+                    encoding.encode(text, &mut output);
+                    data = &[];
+                }
+                Err(err) => {
+                    let (valid, after_valid) = data.split_at(err.valid_up_to());
+                    if let Ok(valid) = std::str::from_utf8(valid) {
+                        encoding.encode(valid, &mut output);
+                    }
+                    match err.error_len() {
+                        // An incomplete sequence at the end of the buffer;
+                        // hang on to it until the rest of it is written
+                        None => {
+                            data = after_valid;
+                            break;
+                        }
+                        // Bytes that aren't UTF-8 are passed through as-is
+                        Some(len) => {
+                            output.extend_from_slice(&after_valid[..len]);
+                            data = &after_valid[len..];
+                        }
+                    }
+                }
+            }
+        }
+        self.partial = data.to_vec();
+
+        self.inner.write_all(&output)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn labels() {
+        assert_eq!(
+            CharacterEncoding::for_label("ISO-8859-1").unwrap(),
+            CharacterEncoding::Latin1
+        );
+        assert_eq!(
+            CharacterEncoding::for_label("cp437").unwrap(),
+            CharacterEncoding::Cp437
+        );
+        assert_eq!(
+            CharacterEncoding::for_label("sjis").unwrap().name(),
+            "Shift_JIS"
+        );
+        assert!(CharacterEncoding::for_label("utf-16le").is_err());
+        assert!(CharacterEncoding::for_label("bogus").is_err());
+
+        // The name is what gets sent to the mux server when spawning
+        for label in &["latin1", "cp437", "sjis", "utf-8"] {
+            let encoding = CharacterEncoding::for_label(label).unwrap();
+            assert_eq!(
+                CharacterEncoding::for_label(encoding.name()).unwrap(),
+                encoding
+            );
+        }
+    }
+
+    #[test]
+    fn decode() {
+        let encoding = PaneEncoding::new(CharacterEncoding::Cp437);
+        let mut reader = DecodingReader::new(&b"\xc9\xcd\xbb\x1b[m"[..], encoding.clone());
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "╔═╗\x1b[m");
+
+        // A character that is split across reads is decoded once
+        // the remainder arrives
+        encoding.set(CharacterEncoding::for_label("Shift_JIS").unwrap());
+        let mut reader = DecodingReader::new(&b"\x82"[..], encoding);
+        let mut buf = [0u8; 16];
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        reader.inner = &b"\xa0a"[..];
+        let len = reader.read(&mut buf).unwrap();
+        assert_eq!(std::str::from_utf8(&buf[..len]).unwrap(), "あa");
+    }
+
+    #[test]
+    fn encode() {
+        let encoding = PaneEncoding::new(CharacterEncoding::Latin1);
+        let mut writer = EncodingWriter::new(vec![], encoding);
+        writer.write_all(b"caf\xc3").unwrap();
+        writer.write_all(b"\xa9 \xe2\x9c\x93").unwrap();
+        assert_eq!(writer.inner, b"caf\xe9 ?");
+    }
+}
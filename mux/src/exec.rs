@@ -0,0 +1,380 @@
+use crate::domain::{Domain, DomainId, DomainState, LocalDomain};
+use crate::encoding::CharacterEncoding;
+use crate::pane::{Pane, PaneId};
+use crate::tab::{SplitDirection, Tab, TabId};
+use crate::window::WindowId;
+use crate::{Mux, MuxNotification};
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use config::ExecDomain as ExecDomainConfig;
+use portable_pty::{CommandBuilder, PtySize};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+/// A domain that spawns its panes by running a local command that
+/// wraps the requested program; for example `docker exec` or `ssh`.
+/// Optional hooks are run before the first pane is spawned and after
+/// the last pane has exited, and an optional health check determines
+/// whether the domain is considered to be attached.
+pub struct ExecDomain {
+    /// Used to spawn the wrapper processes.  The panes that it
+    /// creates are associated with its domain id, which we
+    /// also use as our own.
+    local: LocalDomain,
+    config: ExecDomainConfig,
+    /// true while setup_command has run and teardown_command has not
+    active: Arc<AtomicBool>,
+    /// Held while running the setup_command and spawning, so that
+    /// concurrent spawns don't each run it, and while running the
+    /// teardown_command, so that it can't undo a setup that a spawn
+    /// has just made
+    setup_lock: Arc<smol::lock::Mutex<()>>,
+    healthy: Arc<AtomicBool>,
+}
+
+impl ExecDomain {
+    pub fn new(config: ExecDomainConfig) -> anyhow::Result<Self> {
+        let local = LocalDomain::new(&config.name)?;
+        let healthy = Arc::new(AtomicBool::new(true));
+
+        if let Some(health_check) = config.health_check_command.clone() {
+            let interval = Duration::from_secs(config.health_check_interval.max(1));
+            let weak = Arc::downgrade(&healthy);
+            let name = config.name.clone();
+            std::thread::spawn(move || health_check_thread(name, health_check, interval, weak));
+        }
+
+        Ok(Self {
+            local,
+            config,
+            active: Arc::new(AtomicBool::new(false)),
+            setup_lock: Arc::new(smol::lock::Mutex::new(())),
+            healthy,
+        })
+    }
+
+    /// Refuses to spawn while the health check is failing
+    fn check_health(&self) -> anyhow::Result<()> {
+        if !self.healthy.load(Ordering::SeqCst) {
+            bail!(
+                "exec domain {}: cannot spawn while health_check_command is failing",
+                self.config.name
+            );
+        }
+        Ok(())
+    }
+
+    /// Runs the setup_command prior to spawning the first pane, and
+    /// arranges for the teardown_command to run once the last pane
+    /// in this domain has been removed.  The returned guard should be
+    /// held until the pane has been added to the mux.
+    async fn ensure_setup(&self) -> anyhow::Result<smol::lock::MutexGuard<'_, ()>> {
+        let guard = self.setup_lock.lock().await;
+        if self.active.load(Ordering::SeqCst) {
+            return Ok(guard);
+        }
+
+        if let Some(setup) = self.config.setup_command.clone() {
+            let name = self.config.name.clone();
+            smol::unblock(move || run_hook(&name, "setup_command", &setup)).await?;
+        }
+        self.active.store(true, Ordering::SeqCst);
+
+        let domain_id = self.domain_id();
+        let active = Arc::clone(&self.active);
+        let setup_lock = Arc::clone(&self.setup_lock);
+        let name = self.config.name.clone();
+        let teardown = self.config.teardown_command.clone();
+        let mux = Mux::get().unwrap();
+        mux.subscribe(move |n| {
+            if !active.load(Ordering::SeqCst) {
+                return false;
+            }
+            if let MuxNotification::PaneRemoved(_) = n {
+                if !domain_has_panes(domain_id) {
+                    promise::spawn::spawn(teardown_when_unused(
+                        domain_id,
+                        Arc::clone(&active),
+                        Arc::clone(&setup_lock),
+                        name.clone(),
+                        teardown.clone(),
+                    ))
+                    .detach();
+                    return false;
+                }
+            }
+            true
+        });
+
+        Ok(guard)
+    }
+}
+
+/// Wraps `command` by prepending `prefix`.  If no explicit command
+/// was requested, `default_prog` is used in its place.
+pub(crate) fn wrap_command(
+    prefix: &[String],
+    default_prog: Option<&Vec<String>>,
+    command: Option<CommandBuilder>,
+) -> Option<CommandBuilder> {
+    let mut argv: Vec<std::ffi::OsString> = prefix.iter().map(Into::into).collect();
+
+    let command = match command {
+        Some(cmd) if !cmd.is_default_prog() => cmd,
+        _ => match default_prog {
+            Some(prog) => CommandBuilder::from_argv(prog.iter().map(Into::into).collect()),
+            None if argv.is_empty() => return None,
+            None => CommandBuilder::new_default_prog(),
+        },
+    };
+
+    if argv.is_empty() {
+        return Some(command);
+    }
+
+    argv.extend(command.get_argv().iter().cloned());
+    let mut wrapped = CommandBuilder::from_argv(argv);
+    for (k, v) in command.iter_env_as_str() {
+        wrapped.env(k, v);
+    }
+    if let Some(cwd) = command.get_cwd() {
+        wrapped.cwd(cwd);
+    }
+    Some(wrapped)
+}
+
+/// Adds the `exec_domains` from the configuration to the mux
+pub fn register_exec_domains() -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    for exec_dom in &config::configuration().exec_domains {
+        let domain: Arc<dyn Domain> = Arc::new(ExecDomain::new(exec_dom.clone())?);
+        mux.add_domain(&domain);
+    }
+    Ok(())
+}
+
+fn domain_has_panes(domain_id: DomainId) -> bool {
+    let mux = Mux::get().unwrap();
+    mux.iter_panes()
+        .iter()
+        .any(|pane| pane.domain_id() == domain_id)
+}
+
+/// Runs the teardown_command, unless a pane was spawned into the
+/// domain while waiting for `setup_lock`
+async fn teardown_when_unused(
+    domain_id: DomainId,
+    active: Arc<AtomicBool>,
+    setup_lock: Arc<smol::lock::Mutex<()>>,
+    name: String,
+    teardown: Option<Vec<String>>,
+) {
+    let _guard = setup_lock.lock().await;
+    if !active.load(Ordering::SeqCst) || domain_has_panes(domain_id) {
+        return;
+    }
+    active.store(false, Ordering::SeqCst);
+    if let Some(teardown) = teardown {
+        let result = smol::unblock(move || run_hook(&name, "teardown_command", &teardown)).await;
+        if let Err(err) = result {
+            log::error!("{:#}", err);
+        }
+    }
+}
+
+fn run_hook(domain_name: &str, label: &str, argv: &[String]) -> anyhow::Result<()> {
+    if argv.is_empty() {
+        return Ok(());
+    }
+    log::info!("exec domain {}: running {} {:?}", domain_name, label, argv);
+    let status = std::process::Command::new(&argv[0])
+        .args(&argv[1..])
+        .status()
+        .with_context(|| format!("exec domain {}: running {} {:?}", domain_name, label, argv))?;
+    if !status.success() {
+        bail!(
+            "exec domain {}: {} {:?} failed: {}",
+            domain_name,
+            label,
+            argv,
+            status
+        );
+    }
+    Ok(())
+}
+
+/// Periodically runs the health check command, updating `healthy`.
+/// Terminates once the domain has been dropped.
+fn health_check_thread(
+    domain_name: String,
+    argv: Vec<String>,
+    interval: Duration,
+    healthy: Weak<AtomicBool>,
+) {
+    loop {
+        let ok = match run_hook(&domain_name, "health_check_command", &argv) {
+            Ok(()) => true,
+            Err(err) => {
+                log::warn!("{:#}", err);
+                false
+            }
+        };
+        match healthy.upgrade() {
+            Some(healthy) => healthy.store(ok, Ordering::SeqCst),
+            None => return,
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+#[async_trait(?Send)]
+impl Domain for ExecDomain {
+    async fn spawn(
+        &self,
+        size: PtySize,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
+        window: WindowId,
+    ) -> anyhow::Result<Rc<Tab>> {
+        self.check_health()?;
+        let _guard = self.ensure_setup().await?;
+        let command = wrap_command(
+            &self.config.command_prefix,
+            self.config.default_prog.as_ref(),
+            command,
+        );
+        self.local
+            .spawn(size, command, command_dir, encoding, window)
+            .await
+    }
+
+    async fn split_pane(
+        &self,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
+        tab: TabId,
+        pane_id: PaneId,
+        direction: SplitDirection,
+    ) -> anyhow::Result<Rc<dyn Pane>> {
+        self.check_health()?;
+        let _guard = self.ensure_setup().await?;
+        let command = wrap_command(
+            &self.config.command_prefix,
+            self.config.default_prog.as_ref(),
+            command,
+        );
+        self.local
+            .split_pane(command, command_dir, encoding, tab, pane_id, direction)
+            .await
+    }
+
+    fn domain_id(&self) -> DomainId {
+        self.local.domain_id()
+    }
+
+    fn domain_name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn attach(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn detach(&self) -> anyhow::Result<()> {
+        bail!("detach not implemented");
+    }
+
+    fn state(&self) -> DomainState {
+        if self.healthy.load(Ordering::SeqCst) {
+            DomainState::Attached
+        } else {
+            DomainState::Detached
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn argv(cmd: &CommandBuilder) -> Vec<String> {
+        cmd.get_argv()
+            .iter()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn wrap() {
+        let prefix = vec!["docker".to_string(), "exec".to_string()];
+        let prog = vec!["bash".to_string()];
+
+        let mut cmd = CommandBuilder::new("top");
+        cmd.env("FOO", "bar");
+        cmd.cwd("/tmp");
+        let wrapped = wrap_command(&prefix, Some(&prog), Some(cmd)).unwrap();
+        assert_eq!(argv(&wrapped), vec!["docker", "exec", "top"]);
+        assert!(wrapped
+            .iter_env_as_str()
+            .any(|(key, value)| key == "FOO" && value == "bar"));
+        assert_eq!(wrapped.get_cwd().unwrap(), "/tmp");
+
+        let wrapped = wrap_command(&prefix, Some(&prog), None).unwrap();
+        assert_eq!(argv(&wrapped), vec!["docker", "exec", "bash"]);
+
+        let wrapped = wrap_command(&prefix, None, None).unwrap();
+        assert_eq!(argv(&wrapped), vec!["docker", "exec"]);
+
+        assert!(wrap_command(&[], None, None).is_none());
+    }
+
+    #[test]
+    fn unhealthy_refuses_spawn() {
+        let domain = ExecDomain::new(ExecDomainConfig {
+            name: "unhealthy".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(domain.state(), DomainState::Attached);
+
+        domain.healthy.store(false, Ordering::SeqCst);
+        assert_eq!(domain.state(), DomainState::Detached);
+        let err = smol::block_on(domain.spawn(PtySize::default(), None, None, None, 0))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("health_check_command"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn setup_runs_once() {
+        let mux = Rc::new(Mux::new(None));
+        Mux::set_mux(&mux);
+
+        let log = std::env::temp_dir().join(format!("wezterm-exec-setup-{}", std::process::id()));
+        std::fs::remove_file(&log).ok();
+        let domain = ExecDomain::new(ExecDomainConfig {
+            name: "setup".to_string(),
+            setup_command: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!("echo setup >> {}", log.display()),
+            ]),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Each spawn releases the lock once it is done with it
+        let setup = || async { domain.ensure_setup().await.map(drop) };
+        let (a, b) = smol::block_on(smol::future::zip(setup(), setup()));
+        a.unwrap();
+        b.unwrap();
+        assert!(domain.active.load(Ordering::SeqCst));
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "setup\n");
+        std::fs::remove_file(&log).ok();
+    }
+}
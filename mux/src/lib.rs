@@ -1,10 +1,11 @@
+use crate::client::{ClientId, ClientInfo};
 use crate::pane::{Pane, PaneId};
 use crate::tab::{Tab, TabId};
 use crate::window::{Window, WindowId};
 use anyhow::{anyhow, Context, Error};
 use config::{configuration, ExitBehavior};
 use domain::{Domain, DomainId};
-use filedescriptor::{socketpair, AsRawSocketDescriptor, FileDescriptor};
+use filedescriptor::{poll, pollfd, socketpair, AsRawSocketDescriptor, FileDescriptor, POLLIN};
 #[cfg(unix)]
 use libc::{SOL_SOCKET, SO_RCVBUF, SO_SNDBUF};
 use log::error;
@@ -17,7 +18,7 @@ use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use termwiz::escape::csi::{DecPrivateMode, DecPrivateModeCode, Device, Mode};
 use termwiz::escape::{Action, CSI};
 use thiserror::*;
@@ -25,11 +26,21 @@ use thiserror::*;
 use winapi::um::winsock2::{SOL_SOCKET, SO_RCVBUF, SO_SNDBUF};
 
 pub mod activity;
+pub mod client;
 pub mod connui;
+pub mod container;
 pub mod domain;
+pub mod encoding;
+pub mod exec;
 pub mod localpane;
+pub mod memory;
 pub mod pane;
+mod paneoutput;
+pub mod procinfo;
+pub mod recording;
+mod remotecontrol;
 pub mod renderable;
+pub mod searchindex;
 pub mod ssh;
 pub mod tab;
 pub mod termwiztermtab;
@@ -37,6 +48,7 @@ pub mod tmux;
 pub mod window;
 
 use crate::activity::Activity;
+use crate::memory::MemoryStat;
 
 #[derive(Clone, Debug)]
 pub enum MuxNotification {
@@ -50,6 +62,18 @@ pub enum MuxNotification {
         pane_id: PaneId,
         alert: wezterm_term::Alert,
     },
+    ActiveWorkspaceChanged(String),
+    /// A window was created in a workspace that previously had none
+    WorkspaceCreated(String),
+    WorkspaceRenamed {
+        old: String,
+        new: String,
+    },
+    /// The windows of a workspace were closed
+    WorkspaceClosed(String),
+    /// Something asked the gui to show its windows, such as a
+    /// `wezterm start` that found this process running in the background
+    ShowWindowsRequested,
     Empty,
 }
 
@@ -63,49 +87,111 @@ pub struct Mux {
     domains: RefCell<HashMap<DomainId, Arc<dyn Domain>>>,
     domains_by_name: RefCell<HashMap<String, Arc<dyn Domain>>>,
     subscribers: RefCell<HashMap<usize, Box<dyn Fn(MuxNotification) -> bool>>>,
+    memory_reporters: RefCell<HashMap<usize, Box<dyn Fn(&mut Vec<MemoryStat>) -> bool>>>,
     banner: RefCell<Option<String>>,
+    clients: RefCell<HashMap<ClientId, ClientInfo>>,
+    active_workspace: RefCell<Option<String>>,
+    /// The preferred order of workspaces, as set by set_workspace_order
+    workspace_order: RefCell<Vec<String>>,
+    /// The window that the gui most recently focused
+    focused_window: RefCell<Option<WindowId>>,
 }
 
 const BUFSIZE: usize = 1024 * 1024;
 
+/// The smallest number of bytes that adaptive batching will
+/// reduce a batch of output to
+const MIN_BATCH_SIZE: usize = 4 * 1024;
+
 /// This function bounces parsed actions over to the main thread to feed to
-/// the pty in the mux.
+/// the pty in the mux; see `paneoutput` for how the output of the panes
+/// is scheduled there.
 /// It blocks until the mux has finished consuming the data, which provides
 /// some back-pressure so that eg: ctrl-c can remain responsive.
-fn send_actions_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, actions: Vec<Action>) {
+/// Returns how long it took to apply the actions to the pane.
+fn send_actions_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, actions: Vec<Action>) -> Duration {
     let start = Instant::now();
-    promise::spawn::block_on(promise::spawn::spawn_into_main_thread({
-        let dead = Arc::clone(&dead);
-        async move {
-            let mux = Mux::get().unwrap();
-            if let Some(pane) = mux.get_pane(pane_id) {
-                let start = Instant::now();
-                pane.perform_actions(actions);
-                histogram!(
-                    "send_actions_to_mux.perform_actions.latency",
-                    start.elapsed()
-                );
-                mux.notify(MuxNotification::PaneOutput(pane_id));
-            } else {
-                // Something else removed the pane from
-                // the mux, so signal that we should stop
-                // trying to process it in read_from_pane_pty.
-                dead.store(true, Ordering::Relaxed);
-            }
+    let elapsed = match paneoutput::apply_actions(pane_id, actions) {
+        Some(elapsed) => elapsed,
+        None => {
+            // Something else removed the pane from
+            // the mux, so signal that we should stop
+            // trying to process it in read_from_pane_pty.
+            dead.store(true, Ordering::Relaxed);
+            Duration::from_millis(0)
         }
-    }));
+    };
     histogram!("send_actions_to_mux.latency", start.elapsed());
     histogram!("send_actions_to_mux.rate", 1.);
+    elapsed
+}
+
+/// Decides how many bytes of output are parsed and applied to the
+/// terminal model in one batch.  While a program floods the pane with
+/// output, the batch shrinks when applying it takes more than half of
+/// a frame, so that the gui can still paint and respond to input, and
+/// grows back to `mux_output_parser_buffer_size` when it is cheap.
+struct BatchSize {
+    limit: usize,
+    max: usize,
+    budget: Duration,
+    adaptive: bool,
+}
+
+impl BatchSize {
+    fn new(max: usize, max_fps: u8, adaptive: bool) -> Self {
+        let mut size = Self {
+            limit: max,
+            max,
+            budget: Duration::from_millis(0),
+            adaptive,
+        };
+        size.configure(max, max_fps, adaptive);
+        size
+    }
+
+    fn configure(&mut self, max: usize, max_fps: u8, adaptive: bool) {
+        self.max = max.max(MIN_BATCH_SIZE);
+        self.budget = Duration::from_secs_f64(0.5 / max_fps.max(1) as f64);
+        self.adaptive = adaptive;
+        if adaptive {
+            self.limit = self.limit.min(self.max).max(MIN_BATCH_SIZE);
+        } else {
+            self.limit = self.max;
+        }
+    }
+
+    /// Adjusts the limit given how long it took to apply the
+    /// previous batch, which held `size` bytes
+    fn update(&mut self, size: usize, elapsed: Duration) {
+        if !self.adaptive {
+            return;
+        }
+        if elapsed > self.budget {
+            self.limit = (self.limit / 2).max(MIN_BATCH_SIZE);
+        } else if size >= self.limit && elapsed < self.budget / 4 {
+            self.limit = (self.limit * 2).min(self.max);
+        }
+    }
 }
 
 fn parse_buffered_data(pane_id: PaneId, dead: &Arc<AtomicBool>, mut rx: FileDescriptor) {
-    let mut buf = vec![0; configuration().mux_output_parser_buffer_size];
+    let config = configuration();
+    let mut batch = BatchSize::new(
+        config.mux_output_parser_buffer_size,
+        config.max_fps,
+        config.mux_output_adaptive_batching,
+    );
+    let mut delay = Duration::from_millis(config.mux_output_parser_coalesce_delay_ms);
+    let mut buf = vec![0; batch.max];
     let mut parser = termwiz::escape::parser::Parser::new();
     let mut actions = vec![];
     let mut hold = false;
+    let mut pending_size = 0;
+    let mut deadline = None;
 
     loop {
-        match rx.read(&mut buf) {
+        match rx.read(&mut buf[..batch.limit]) {
             Ok(size) if size == 0 => {
                 dead.store(true, Ordering::Relaxed);
                 break;
@@ -146,11 +232,45 @@ fn parse_buffered_data(pane_id: PaneId, dead: &Arc<AtomicBool>, mut rx: FileDesc
                         send_actions_to_mux(pane_id, dead, std::mem::take(&mut actions));
                     }
                 });
+                pending_size += size;
+
                 if !actions.is_empty() && !hold {
-                    send_actions_to_mux(pane_id, dead, std::mem::take(&mut actions));
+                    // Unless we already have a full batch, wait a little
+                    // for more output, so that a program that writes a
+                    // screenful in several small pieces is rendered in
+                    // one go rather than as a series of partial frames
+                    if pending_size < batch.limit && delay > Duration::from_millis(0) {
+                        let now = Instant::now();
+                        let target = *deadline.get_or_insert(now + delay);
+                        if target > now {
+                            let mut pfd = [pollfd {
+                                fd: rx.as_socket_descriptor(),
+                                events: POLLIN,
+                                revents: 0,
+                            }];
+                            if let Ok(1) = poll(&mut pfd, Some(target - now)) {
+                                continue;
+                            }
+                        }
+                    }
+
+                    let elapsed = send_actions_to_mux(pane_id, dead, std::mem::take(&mut actions));
+                    batch.update(pending_size, elapsed);
+                    pending_size = 0;
+                    deadline = None;
+                } else if actions.is_empty() {
+                    pending_size = 0;
+                    deadline = None;
                 }
 
-                buf.resize(configuration().mux_output_parser_buffer_size, 0);
+                let config = configuration();
+                batch.configure(
+                    config.mux_output_parser_buffer_size,
+                    config.max_fps,
+                    config.mux_output_adaptive_batching,
+                );
+                buf.resize(batch.max, 0);
+                delay = Duration::from_millis(config.mux_output_parser_coalesce_delay_ms);
             }
         }
     }
@@ -185,9 +305,13 @@ fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<d
     // or in the main mux thread.  If `true`, this thread will terminate.
     let dead = Arc::new(AtomicBool::new(false));
 
+    // Output that the parser hasn't caught up with is buffered in this
+    // socket pair.  Once it is full, we stop reading from the pty, and
+    // the program blocks when it writes more output, until we catch up.
     let (mut tx, mut rx) = socketpair().unwrap();
-    set_socket_buffer(&mut tx, SO_SNDBUF, BUFSIZE).unwrap();
-    set_socket_buffer(&mut rx, SO_RCVBUF, BUFSIZE).unwrap();
+    let backpressure = configuration().mux_output_backpressure_bytes;
+    set_socket_buffer(&mut tx, SO_SNDBUF, backpressure).unwrap();
+    set_socket_buffer(&mut rx, SO_RCVBUF, backpressure).unwrap();
 
     std::thread::spawn({
         let dead = Arc::clone(&dead);
@@ -210,6 +334,7 @@ fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<d
             }
             Ok(size) => {
                 histogram!("read_from_pane_pty.bytes.rate", size as f64);
+                recording::record_output(pane_id, &buf[..size]);
                 if let Err(err) = tx.write_all(&buf[..size]) {
                     error!(
                         "read_pty failed to write to parser: pane {} {:?}",
@@ -316,7 +441,12 @@ impl Mux {
             domains_by_name: RefCell::new(domains_by_name),
             domains: RefCell::new(domains),
             subscribers: RefCell::new(HashMap::new()),
+            memory_reporters: RefCell::new(HashMap::new()),
             banner: RefCell::new(None),
+            clients: RefCell::new(HashMap::new()),
+            active_workspace: RefCell::new(None),
+            workspace_order: RefCell::new(vec![]),
+            focused_window: RefCell::new(None),
         }
     }
 
@@ -335,6 +465,44 @@ impl Mux {
         subscribers.retain(|_, notify| notify(notification.clone()));
     }
 
+    /// Registers a function that adds the memory used by a subsystem
+    /// that lives outside of the mux, such as the gui, to the stats
+    /// returned by `memory_stats`.  The reporter is removed when it
+    /// returns false.
+    pub fn register_memory_reporter<F>(&self, reporter: F)
+    where
+        F: Fn(&mut Vec<MemoryStat>) -> bool + 'static,
+    {
+        let id = SUB_ID.fetch_add(1, Ordering::Relaxed);
+        self.memory_reporters
+            .borrow_mut()
+            .insert(id, Box::new(reporter));
+    }
+
+    /// Returns the approximate memory used by the scrollback of each
+    /// pane, the lua configuration and any registered reporters
+    pub fn memory_stats(&self) -> Vec<MemoryStat> {
+        let mut stats = vec![];
+        let mut panes: Vec<_> = self.panes.borrow().values().cloned().collect();
+        panes.sort_by_key(|pane| pane.pane_id());
+        for pane in panes {
+            if let Some(bytes) = pane.memory_usage() {
+                stats.push(MemoryStat::new(
+                    "scrollback",
+                    Some(format!("pane {}", pane.pane_id())),
+                    bytes,
+                ));
+            }
+        }
+        if let Some(bytes) = config::lua_memory_usage() {
+            stats.push(MemoryStat::new("lua heap", None, bytes));
+        }
+        self.memory_reporters
+            .borrow_mut()
+            .retain(|_, reporter| reporter(&mut stats));
+        stats
+    }
+
     pub fn default_domain(&self) -> Arc<dyn Domain> {
         self.default_domain
             .borrow()
@@ -424,6 +592,7 @@ impl Mux {
         if let Some(pane) = self.panes.borrow_mut().remove(&pane_id) {
             log::debug!("killing pane {}", pane_id);
             pane.kill();
+            recording::stop_recording(pane_id);
             self.notify(MuxNotification::PaneRemoved(pane_id));
         }
     }
@@ -546,10 +715,15 @@ impl Mux {
         window.get_active().map(Rc::clone)
     }
 
-    pub fn new_empty_window(&self) -> MuxWindowBuilder {
-        let window = Window::new();
+    pub fn new_empty_window(&self, workspace: Option<String>) -> MuxWindowBuilder {
+        let workspace = workspace.unwrap_or_else(|| self.active_workspace());
+        let is_new_workspace = self.iter_windows_in_workspace(&workspace).is_empty();
+        let window = Window::new(workspace.clone());
         let window_id = window.window_id();
         self.windows.borrow_mut().insert(window_id, window);
+        if is_new_workspace {
+            self.notify(MuxNotification::WorkspaceCreated(workspace));
+        }
         MuxWindowBuilder {
             window_id,
             activity: Some(Activity::new()),
@@ -565,6 +739,128 @@ impl Mux {
         Ok(())
     }
 
+    /// Removes `pane_id` from its tab, without killing it, and places
+    /// it into a new tab in `window_id`, which becomes the active tab
+    /// of that window.
+    /// Returns the id of the new tab.
+    pub fn move_pane_to_window(
+        &self,
+        pane_id: PaneId,
+        window_id: WindowId,
+    ) -> anyhow::Result<TabId> {
+        let (_domain_id, src_window_id, src_tab_id) = self
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+        if self.get_window(window_id).is_none() {
+            anyhow::bail!("window {} not found", window_id);
+        }
+        let src_tab = self
+            .get_tab(src_tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", src_tab_id))?;
+
+        let pane = src_tab
+            .remove_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found in tab {}", pane_id, src_tab_id))?;
+
+        let size = src_tab.get_size();
+        let tab = Rc::new(Tab::new(&size));
+        tab.assign_pane(&pane);
+        pane.resize(size)?;
+        self.add_tab_no_panes(&tab);
+        {
+            let mut window = self
+                .get_window_mut(window_id)
+                .ok_or_else(|| anyhow!("window {} not found", window_id))?;
+            window.push(&tab);
+            let idx = window.len() - 1;
+            window.save_and_then_set_active(idx);
+        }
+
+        // The source tab and window may now be empty; if not, their
+        // layout has changed and they need to be repainted
+        self.prune_dead_windows();
+        self.notify(MuxNotification::WindowInvalidated(src_window_id));
+        Ok(tab.tab_id())
+    }
+
+    /// Removes `pane_id` from its tab, without killing it, and places
+    /// it into a new window in `workspace`, or the active workspace
+    /// if None.
+    /// Returns the ids of the new tab and window.
+    pub fn move_pane_to_new_window(
+        &self,
+        pane_id: PaneId,
+        workspace: Option<String>,
+    ) -> anyhow::Result<(TabId, WindowId)> {
+        if self.resolve_pane_id(pane_id).is_none() {
+            anyhow::bail!("pane {} not found", pane_id);
+        }
+        let window_builder = self.new_empty_window(workspace);
+        let window_id = *window_builder;
+        let tab_id = self.move_pane_to_window(pane_id, window_id)?;
+        // Pruning is deferred while the window is being built, so
+        // finish building it and then clean up the source window
+        drop(window_builder);
+        self.prune_dead_windows();
+        Ok((tab_id, window_id))
+    }
+
+    /// Exchanges the positions of the panes `a` and `b`, which may be
+    /// in the same tab or in different tabs, without killing either.
+    pub fn swap_panes(&self, a: PaneId, b: PaneId) -> anyhow::Result<()> {
+        if a == b {
+            return Ok(());
+        }
+
+        let locate = |pane_id: PaneId| -> anyhow::Result<(WindowId, Rc<Tab>, usize)> {
+            let (_domain_id, window_id, tab_id) = self
+                .resolve_pane_id(pane_id)
+                .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+            let tab = self
+                .get_tab(tab_id)
+                .ok_or_else(|| anyhow!("tab {} not found", tab_id))?;
+            tab.set_zoomed(false);
+            let index = tab
+                .iter_panes()
+                .into_iter()
+                .find(|p| p.pane.pane_id() == pane_id)
+                .map(|p| p.index)
+                .ok_or_else(|| anyhow!("pane {} not found in tab {}", pane_id, tab_id))?;
+            Ok((window_id, tab, index))
+        };
+
+        let (window_a, tab_a, index_a) = locate(a)?;
+        let (window_b, tab_b, index_b) = locate(b)?;
+        let pane_a = self
+            .get_pane(a)
+            .ok_or_else(|| anyhow!("pane {} not found", a))?;
+        let pane_b = self
+            .get_pane(b)
+            .ok_or_else(|| anyhow!("pane {} not found", b))?;
+
+        // Indices are positional, so this works even when both
+        // panes are in the same tab
+        tab_a.replace_pane(index_a, &pane_b);
+        tab_b.replace_pane(index_b, &pane_a);
+
+        self.notify(MuxNotification::WindowInvalidated(window_a));
+        if window_b != window_a {
+            self.notify(MuxNotification::WindowInvalidated(window_b));
+        }
+        Ok(())
+    }
+
+    /// Moves each of `panes`, which may be in different tabs, into the
+    /// position of the next one, with the last moving into the position
+    /// of the first; or into the position of the previous one when
+    /// `forward` is false.
+    pub fn rotate_panes(&self, panes: &[PaneId], forward: bool) -> anyhow::Result<()> {
+        for (a, b) in rotation_swaps(panes, forward) {
+            self.swap_panes(a, b)?;
+        }
+        Ok(())
+    }
+
     pub fn window_containing_tab(&self, tab_id: TabId) -> Option<WindowId> {
         for w in self.windows.borrow().values() {
             for t in w.iter() {
@@ -592,6 +888,200 @@ impl Mux {
         self.windows.borrow().keys().cloned().collect()
     }
 
+    pub fn iter_windows_in_workspace(&self, workspace: &str) -> Vec<WindowId> {
+        let mut windows: Vec<WindowId> = self
+            .windows
+            .borrow()
+            .iter()
+            .filter_map(|(k, w)| {
+                if w.get_workspace() == workspace {
+                    Some(k)
+                } else {
+                    None
+                }
+            })
+            .cloned()
+            .collect();
+        windows.sort();
+        windows
+    }
+
+    /// Returns the list of distinct workspace names that are associated
+    /// with windows in the mux.  Workspaces that were positioned by
+    /// set_workspace_order come first, in that order, followed by the
+    /// others in sorted order.
+    pub fn iter_workspaces(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .windows
+            .borrow()
+            .values()
+            .map(|w| w.get_workspace().to_string())
+            .collect();
+        names.sort();
+        names.dedup();
+
+        let order = self.workspace_order.borrow();
+        let position = |name: &String| order.iter().position(|n| n == name).unwrap_or(order.len());
+        // This is a stable sort, so the unordered names remain sorted
+        names.sort_by_key(position);
+        names
+    }
+
+    /// Sets the preferred order of the workspaces returned by
+    /// iter_workspaces.  Workspaces that are not named in `order`
+    /// are placed after those that are.
+    pub fn set_workspace_order(&self, order: Vec<String>) {
+        *self.workspace_order.borrow_mut() = order;
+    }
+
+    /// Renames the workspace `old` to `new`, moving all of its
+    /// windows, along with any clients that are viewing it
+    pub fn rename_workspace(&self, old: &str, new: &str) -> anyhow::Result<()> {
+        if new.is_empty() {
+            anyhow::bail!("workspace name must not be empty");
+        }
+        if old == new {
+            return Ok(());
+        }
+        let windows = self.iter_windows_in_workspace(old);
+        if windows.is_empty() {
+            anyhow::bail!("there is no workspace named {}", old);
+        }
+        if !self.iter_windows_in_workspace(new).is_empty() {
+            anyhow::bail!("workspace {} already exists", new);
+        }
+
+        for window_id in windows {
+            if let Some(mut window) = self.get_window_mut(window_id) {
+                window.set_workspace(new);
+            }
+        }
+        for info in self.clients.borrow_mut().values_mut() {
+            if info.active_workspace == old {
+                info.active_workspace = new.to_string();
+            }
+        }
+        for name in self.workspace_order.borrow_mut().iter_mut() {
+            if name == old {
+                *name = new.to_string();
+            }
+        }
+        let is_active = self.active_workspace() == old;
+        if is_active {
+            *self.active_workspace.borrow_mut() = Some(new.to_string());
+        }
+
+        self.notify(MuxNotification::WorkspaceRenamed {
+            old: old.to_string(),
+            new: new.to_string(),
+        });
+        if is_active {
+            self.notify(MuxNotification::ActiveWorkspaceChanged(new.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Returns true if all of the panes in `workspace` can be closed
+    /// without prompting the user
+    pub fn can_close_workspace_without_prompting(&self, workspace: &str) -> bool {
+        self.iter_windows_in_workspace(workspace)
+            .into_iter()
+            .all(|window_id| match self.get_window(window_id) {
+                Some(window) => window.can_close_without_prompting(),
+                None => true,
+            })
+    }
+
+    /// Closes all of the windows in `workspace`, killing their panes
+    pub fn kill_workspace(&self, workspace: &str) {
+        let windows = self.iter_windows_in_workspace(workspace);
+        if windows.is_empty() {
+            return;
+        }
+        for window_id in windows {
+            self.kill_window(window_id);
+        }
+        self.workspace_order.borrow_mut().retain(|n| n != workspace);
+        self.notify(MuxNotification::WorkspaceClosed(workspace.to_string()));
+    }
+
+    /// Returns the workspace that is being displayed by the local
+    /// (in-process) GUI.  Remote clients track their own active
+    /// workspace via `active_workspace_for_client`.
+    pub fn active_workspace(&self) -> String {
+        self.active_workspace
+            .borrow()
+            .clone()
+            .unwrap_or_else(|| configuration().default_workspace.clone())
+    }
+
+    pub fn set_active_workspace(&self, workspace: &str) {
+        if workspace == self.active_workspace() {
+            return;
+        }
+        *self.active_workspace.borrow_mut() = Some(workspace.to_string());
+        self.notify(MuxNotification::ActiveWorkspaceChanged(
+            workspace.to_string(),
+        ));
+    }
+
+    pub fn register_client(&self, client_id: &ClientId, workspace: &str) {
+        self.clients
+            .borrow_mut()
+            .insert(client_id.clone(), ClientInfo::new(client_id, workspace));
+    }
+
+    pub fn unregister_client(&self, client_id: &ClientId) {
+        self.clients.borrow_mut().remove(client_id);
+    }
+
+    pub fn iter_clients(&self) -> Vec<ClientInfo> {
+        self.clients.borrow().values().cloned().collect()
+    }
+
+    pub fn active_workspace_for_client(&self, client_id: &ClientId) -> String {
+        self.clients
+            .borrow()
+            .get(client_id)
+            .map(|info| info.active_workspace.clone())
+            .unwrap_or_else(|| configuration().default_workspace.clone())
+    }
+
+    pub fn set_active_workspace_for_client(&self, client_id: &ClientId, workspace: &str) {
+        if let Some(info) = self.clients.borrow_mut().get_mut(client_id) {
+            info.active_workspace = workspace.to_string();
+        }
+    }
+
+    pub fn record_focus_for_client(&self, client_id: &ClientId, pane_id: PaneId) {
+        if let Some(info) = self.clients.borrow_mut().get_mut(client_id) {
+            info.focused_pane_id.replace(pane_id);
+        }
+    }
+
+    pub fn focused_pane_for_client(&self, client_id: &ClientId) -> Option<PaneId> {
+        self.clients
+            .borrow()
+            .get(client_id)
+            .and_then(|info| info.focused_pane_id)
+    }
+
+    /// Records that the gui has focused `window_id`
+    pub fn record_focused_window(&self, window_id: WindowId) {
+        self.focused_window.borrow_mut().replace(window_id);
+    }
+
+    /// Returns the window that the gui most recently focused,
+    /// provided that it still exists
+    pub fn focused_window(&self) -> Option<WindowId> {
+        let window_id = (*self.focused_window.borrow())?;
+        if self.windows.borrow().contains_key(&window_id) {
+            Some(window_id)
+        } else {
+            None
+        }
+    }
+
     pub fn iter_domains(&self) -> Vec<Arc<dyn Domain>> {
         self.domains.borrow().values().cloned().collect()
     }
@@ -660,3 +1150,77 @@ pub(crate) fn pty_size_to_terminal_size(size: portable_pty::PtySize) -> wezterm_
         pixel_height: size.pixel_height as usize,
     }
 }
+
+/// Returns the pairs of panes to exchange, in order, to rotate `panes`
+/// as described by `Mux::rotate_panes`.  The first pane is exchanged
+/// with each of the others in turn, which carries it around the cycle.
+fn rotation_swaps(panes: &[PaneId], forward: bool) -> Vec<(PaneId, PaneId)> {
+    let (first, rest) = match panes.split_first() {
+        Some(split) => split,
+        None => return vec![],
+    };
+    let mut swaps: Vec<(PaneId, PaneId)> = rest.iter().map(|&other| (*first, other)).collect();
+    if forward {
+        swaps.reverse();
+    }
+    swaps
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Applies `swaps` to the panes occupying `positions`
+    fn apply_swaps(positions: &mut Vec<PaneId>, swaps: &[(PaneId, PaneId)]) {
+        for &(a, b) in swaps {
+            let a_idx = positions.iter().position(|&p| p == a).unwrap();
+            let b_idx = positions.iter().position(|&p| p == b).unwrap();
+            positions.swap(a_idx, b_idx);
+        }
+    }
+
+    #[test]
+    fn rotation() {
+        let mut positions = vec![1, 2, 3, 4];
+        apply_swaps(&mut positions, &rotation_swaps(&[1, 2, 3, 4], true));
+        assert_eq!(positions, vec![4, 1, 2, 3]);
+
+        let mut positions = vec![1, 2, 3, 4];
+        apply_swaps(&mut positions, &rotation_swaps(&[1, 2, 3, 4], false));
+        assert_eq!(positions, vec![2, 3, 4, 1]);
+
+        assert!(rotation_swaps(&[1], true).is_empty());
+        assert!(rotation_swaps(&[], false).is_empty());
+    }
+
+    #[test]
+    fn batch_size() {
+        let mut batch = BatchSize::new(128 * 1024, 60, true);
+        assert_eq!(batch.limit, 128 * 1024);
+
+        // Slow batches shrink the limit, but not below the minimum
+        let slow = Duration::from_millis(20);
+        batch.update(128 * 1024, slow);
+        assert_eq!(batch.limit, 64 * 1024);
+        for _ in 0..10 {
+            batch.update(batch.limit, slow);
+        }
+        assert_eq!(batch.limit, MIN_BATCH_SIZE);
+
+        // Fast, full batches grow it back, up to the maximum
+        let fast = Duration::from_millis(1);
+        batch.update(100, fast);
+        assert_eq!(batch.limit, MIN_BATCH_SIZE);
+        for _ in 0..10 {
+            batch.update(batch.limit, fast);
+        }
+        assert_eq!(batch.limit, 128 * 1024);
+
+        // Without adaptive batching the limit is fixed
+        let mut batch = BatchSize::new(128 * 1024, 60, false);
+        batch.update(128 * 1024, slow);
+        assert_eq!(batch.limit, 128 * 1024);
+        batch.configure(256 * 1024, 60, false);
+        assert_eq!(batch.limit, 256 * 1024);
+    }
+}
@@ -1,31 +1,36 @@
 use crate::domain::DomainId;
+use crate::encoding::{CharacterEncoding, DecodingReader, EncodingWriter, PaneEncoding};
 use crate::pane::{Pane, PaneId, Pattern, SearchResult};
+use crate::procinfo::{process_name_matches, ProcessInfo};
 use crate::renderable::*;
+use crate::searchindex::{pattern_trigrams, SearchIndex};
 use crate::tmux::{TmuxDomain, TmuxDomainState};
 use crate::{Domain, Mux, MuxNotification};
 use anyhow::Error;
 use async_trait::async_trait;
 use config::keyassignment::ScrollbackEraseMode;
-use config::{configuration, ExitBehavior};
+use config::{configuration, CwdTracking, ExitBehavior};
 #[cfg(windows)]
 use filedescriptor::OwnedHandle;
-use portable_pty::{Child, ExitStatus, MasterPty, PtySize};
+use portable_pty::{Child, CommandBuilder, ExitStatus, MasterPty, PtySize};
 use rangeset::RangeSet;
 use smol::channel::{bounded, Receiver, TryRecvError};
-use std::cell::{RefCell, RefMut};
-use std::collections::{HashMap, HashSet};
+use smol::Timer;
+use std::cell::{Cell, RefCell, RefMut};
+use std::collections::HashMap;
 use std::io::Result as IoResult;
 use std::ops::Range;
 #[cfg(windows)]
 use std::os::windows::io::{AsRawHandle, RawHandle};
 use std::sync::Arc;
+use std::time::Duration;
 use termwiz::escape::DeviceControlMode;
 use termwiz::surface::{Line, SequenceNo, SEQ_ZERO};
 use url::Url;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    Alert, AlertHandler, CellAttributes, Clipboard, KeyCode, KeyModifiers, MouseEvent,
-    SemanticZone, StableRowIndex, Terminal, TerminalConfiguration,
+    Alert, AlertHandler, CellAttributes, Clipboard, CommandResult, KeyCode, KeyModifiers,
+    MouseEvent, SemanticZone, StableRowIndex, Terminal, TerminalConfiguration,
 };
 
 #[derive(Debug)]
@@ -49,6 +54,83 @@ pub struct LocalPane {
     pty: RefCell<Box<dyn MasterPty>>,
     domain_id: DomainId,
     tmux_domain: RefCell<Option<Arc<TmuxDomainState>>>,
+    command: Option<CommandBuilder>,
+    /// The encoding of the program running in the pane, along with
+    /// the writer that transcodes the input that is sent to it
+    encoding: Option<(
+        PaneEncoding,
+        RefCell<EncodingWriter<Box<dyn std::io::Write + Send>>>,
+    )>,
+    /// Present when enable_scrollback_search_index is set
+    search_index: RefCell<Option<SearchIndex>>,
+    /// Whether the scrollback is being rewrapped in the background
+    reflow_scheduled: Cell<bool>,
+    /// Whether the scrollback is being indexed in the background
+    search_index_scheduled: Cell<bool>,
+}
+
+/// How many rows of scrollback are rewrapped at a time after a resize
+const REFLOW_BATCH_ROWS: usize = 20_000;
+/// How long to wait between the batches, so that the gui stays responsive
+const REFLOW_INTERVAL: Duration = Duration::from_millis(5);
+/// How long to wait between indexing batches of the scrollback
+const SEARCH_INDEX_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The uri of the link that is shown alongside the exit status of a
+/// pane that is being held open; clicking it respawns the pane.
+/// Any program can print a link to this uri, so the link carries a
+/// token that is unique to this process, and only links that have
+/// it are honored; see `respawn_pane_link`.
+pub const RESPAWN_PANE_URI: &str = "wezterm:respawn-pane";
+
+lazy_static::lazy_static! {
+    /// The token that identifies the respawn links that we generated,
+    /// or None if no random numbers were available to make one
+    static ref RESPAWN_PANE_TOKEN: Option<String> = {
+        let mut bytes = [0u8; 16];
+        match getrandom::getrandom(&mut bytes) {
+            Ok(()) => Some(bytes.iter().map(|b| format!("{:02x}", b)).collect()),
+            Err(err) => {
+                log::error!("unable to generate the respawn link token: {}", err);
+                None
+            }
+        }
+    };
+}
+
+/// Returns the uri of the respawn link to show for a pane
+/// that is being held open
+fn respawn_pane_link() -> Option<String> {
+    RESPAWN_PANE_TOKEN
+        .as_ref()
+        .map(|token| format!("{}?token={}", RESPAWN_PANE_URI, token))
+}
+
+/// Returns true if `uri` is a respawn link, whether or not
+/// it was generated by this process
+pub fn is_respawn_pane_uri(uri: &str) -> bool {
+    match uri.strip_prefix(RESPAWN_PANE_URI) {
+        Some(rest) => rest.is_empty() || rest.starts_with('?'),
+        None => false,
+    }
+}
+
+/// Returns true if `uri` is a respawn link that was generated by
+/// this process, rather than printed by a program in a pane
+pub fn is_trusted_respawn_pane_uri(uri: &str) -> bool {
+    match respawn_pane_link() {
+        Some(link) => {
+            // Compare in constant time so that the token
+            // can't be discovered a character at a time
+            uri.len() == link.len()
+                && uri
+                    .bytes()
+                    .zip(link.bytes())
+                    .fold(0, |acc, (a, b)| acc | (a ^ b))
+                    == 0
+        }
+        None => false,
+    }
 }
 
 #[async_trait(?Send)]
@@ -147,12 +229,18 @@ impl Pane for LocalPane {
                     _ => Some(ExitStatus::with_exit_code(1)),
                 };
                 if let Some(status) = status {
+                    let respawn = match respawn_pane_link() {
+                        Some(link) if self.command.is_some() => {
+                            format!(" \x1b]8;;{}\x1b\\[Restart]\x1b]8;;\x1b\\", link)
+                        }
+                        _ => String::new(),
+                    };
                     match (configuration().exit_behavior, status.success(), killed) {
                         (ExitBehavior::Close, _, _) => *proc = ProcessState::Dead,
                         (ExitBehavior::CloseOnCleanExit, false, false) => {
                             notify = Some(format!(
-                                "\r\n[Process didn't exit cleanly. ({}=\"CloseOnCleanExit\")]\r\n",
-                                EXIT_BEHAVIOR
+                                "\r\n[Process didn't exit cleanly. ({}=\"CloseOnCleanExit\")]{}\r\n",
+                                EXIT_BEHAVIOR, respawn
                             ));
                             *proc = ProcessState::DeadPendingClose { killed: false }
                         }
@@ -160,13 +248,13 @@ impl Pane for LocalPane {
                         (ExitBehavior::Hold, success, false) => {
                             if success {
                                 notify = Some(format!(
-                                    "\r\n[Process completed. ({}=\"Hold\")]\r\n",
-                                    EXIT_BEHAVIOR
+                                    "\r\n[Process completed. ({}=\"Hold\")]{}\r\n",
+                                    EXIT_BEHAVIOR, respawn
                                 ));
                             } else {
                                 notify = Some(format!(
-                                    "\r\n[Process didn't exit cleanly. ({}=\"Hold\")]\r\n",
-                                    EXIT_BEHAVIOR
+                                    "\r\n[Process didn't exit cleanly. ({}=\"Hold\")]{}\r\n",
+                                    EXIT_BEHAVIOR, respawn
                                 ));
                             }
                             *proc = ProcessState::DeadPendingClose { killed: false }
@@ -219,8 +307,28 @@ impl Pane for LocalPane {
         Some(self.terminal.borrow().get_config())
     }
 
+    fn set_encoding(&self, encoding: &str) -> anyhow::Result<()> {
+        let (pane_encoding, _) = self
+            .encoding
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("this pane does not support changing its encoding"))?;
+        pane_encoding.set(CharacterEncoding::for_label(encoding)?);
+        Ok(())
+    }
+
+    fn get_encoding(&self) -> Option<String> {
+        self.encoding
+            .as_ref()
+            .map(|(encoding, _)| encoding.get().name().to_string())
+    }
+
+    fn memory_usage(&self) -> Option<usize> {
+        Some(self.terminal.borrow().memory_usage())
+    }
+
     fn perform_actions(&self, actions: Vec<termwiz::escape::Action>) {
-        self.terminal.borrow_mut().perform_actions(actions)
+        self.terminal.borrow_mut().perform_actions(actions);
+        self.update_search_index();
     }
 
     fn mouse_event(&self, event: MouseEvent) -> Result<(), Error> {
@@ -247,15 +355,24 @@ impl Pane for LocalPane {
             size.pixel_width as usize,
             size.pixel_height as usize,
         );
+        self.update_search_index();
+        self.schedule_reflow();
         Ok(())
     }
 
     fn writer(&self) -> RefMut<dyn std::io::Write> {
-        self.pty.borrow_mut()
+        match &self.encoding {
+            Some((_, writer)) => writer.borrow_mut(),
+            None => self.pty.borrow_mut(),
+        }
     }
 
     fn reader(&self) -> Result<Box<dyn std::io::Read + Send>, Error> {
-        self.pty.borrow_mut().try_clone_reader()
+        let reader = self.pty.borrow_mut().try_clone_reader()?;
+        match &self.encoding {
+            Some((encoding, _)) => Ok(Box::new(DecodingReader::new(reader, encoding.clone()))),
+            None => Ok(reader),
+        }
     }
 
     fn send_paste(&self, text: &str) -> Result<(), Error> {
@@ -287,6 +404,7 @@ impl Pane for LocalPane {
                 self.terminal.borrow_mut().erase_scrollback_and_viewport();
             }
         }
+        self.update_search_index();
     }
 
     fn focus_changed(&self, focused: bool) {
@@ -310,11 +428,29 @@ impl Pane for LocalPane {
     }
 
     fn get_current_working_dir(&self) -> Option<Url> {
-        self.terminal
+        let osc7 = || self.terminal.borrow().get_current_dir().cloned();
+        match configuration().cwd_tracking {
+            CwdTracking::PreferOsc7 => osc7().or_else(|| self.divine_current_working_dir()),
+            CwdTracking::PreferProcess => self.divine_current_working_dir().or_else(osc7),
+            CwdTracking::Osc7Only => osc7(),
+        }
+    }
+
+    fn get_foreground_process_tree(&self) -> Option<ProcessInfo> {
+        #[cfg(unix)]
+        let leader = self
+            .pty
             .borrow()
-            .get_current_dir()
-            .cloned()
-            .or_else(|| self.divine_current_working_dir())
+            .process_group_leader()
+            .map(|pid| pid as u32);
+        #[cfg(not(unix))]
+        let leader = None;
+
+        let pid = leader.or_else(|| match &*self.process.borrow() {
+            ProcessState::Running { signaller, .. } => signaller.pid,
+            _ => None,
+        })?;
+        crate::procinfo::process_tree(pid)
     }
 
     fn can_close_without_prompting(&self) -> bool {
@@ -322,14 +458,17 @@ impl Pane for LocalPane {
         if !proc_list.is_empty() {
             log::trace!("can_close_without_prompting? procs in pane {:?}", proc_list);
 
-            let skip = configuration()
-                .skip_close_confirmation_for_processes_named
-                .iter()
-                .cloned()
-                .collect::<HashSet<_>>();
+            let domain_name = Mux::get()
+                .and_then(|mux| mux.get_domain(self.domain_id))
+                .map(|domain| domain.domain_name().to_string())
+                .unwrap_or_default();
+            let config = configuration();
+            let (skip, confirm) = config.close_confirmation_lists(&domain_name);
 
             for proc in &proc_list {
-                if !skip.contains(proc) {
+                if confirm.iter().any(|p| process_name_matches(p, proc))
+                    || !skip.iter().any(|p| process_name_matches(p, proc))
+                {
                     return false;
                 }
             }
@@ -355,6 +494,10 @@ impl Pane for LocalPane {
         term.get_semantic_zones()
     }
 
+    fn get_command_results(&self) -> Vec<CommandResult> {
+        self.terminal.borrow().get_command_results()
+    }
+
     async fn search(&self, mut pattern: Pattern) -> anyhow::Result<Vec<SearchResult>> {
         let term = self.terminal.borrow();
         let screen = term.screen();
@@ -410,9 +553,11 @@ impl Pane for LocalPane {
                     }
                 }
                 Pattern::Regex(r) => {
-                    if let Ok(re) = regex::Regex::new(r) {
+                    // fancy_regex supports lookaround and backreferences,
+                    // deferring to the regex crate for everything else
+                    if let Ok(re) = fancy_regex::Regex::new(r) {
                         // Allow for the regex to contain captures
-                        for c in re.captures_iter(haystack) {
+                        for c in re.captures_iter(haystack).filter_map(Result::ok) {
                             // Look for the captures in reverse order, as index==0 is
                             // the whole matched string.  We can't just call
                             // `c.iter().rev()` as the capture iterator isn't double-ended.
@@ -436,8 +581,33 @@ impl Pane for LocalPane {
             }
         }
 
-        for (idx, line) in screen.lines.iter().enumerate() {
-            let stable_row = screen.phys_to_stable_row_index(idx);
+        // Only scan the rows that the index says might match
+        let all_rows =
+            screen.phys_to_stable_row_index(0)..screen.phys_to_stable_row_index(screen.lines.len());
+        let ranges = match (
+            self.search_index.borrow().as_ref(),
+            pattern_trigrams(&pattern),
+        ) {
+            (Some(index), Some(trigrams)) if !term.is_alt_screen_active() => {
+                index.rows_to_search(&trigrams, all_rows)
+            }
+            _ => vec![all_rows],
+        };
+
+        let mut prev_row = None;
+        for stable_row in ranges.into_iter().flatten() {
+            // A match can't span the gap between two ranges
+            if prev_row.map_or(false, |prev| prev + 1 != stable_row) {
+                collect_matches(&mut results, &pattern, &haystack, &coords);
+                haystack.clear();
+                coords.clear();
+            }
+            prev_row = Some(stable_row);
+
+            let line = match screen.stable_row_to_phys(stable_row) {
+                Some(idx) => &screen.lines[idx],
+                None => continue,
+            };
 
             let mut wrapped = false;
             for (grapheme_idx, cell) in line.visible_cells() {
@@ -554,6 +724,10 @@ struct LocalPaneNotifHandler {
 
 impl AlertHandler for LocalPaneNotifHandler {
     fn alert(&mut self, alert: Alert) {
+        if let Alert::RemoteControl(request) = alert {
+            crate::remotecontrol::handle_request(self.pane_id, request);
+            return;
+        }
         if let Some(mux) = Mux::get() {
             mux.notify(MuxNotification::Alert {
                 pane_id: self.pane_id,
@@ -597,6 +771,18 @@ impl ProcessSignaller {
     }
 }
 
+/// Produces a `file://` url for a working directory that was obtained
+/// from the operating system, in the same form as one reported via OSC 7
+#[cfg_attr(
+    not(any(windows, target_os = "linux", target_os = "macos")),
+    allow(dead_code)
+)]
+fn cwd_to_url(path: &std::path::Path) -> Option<Url> {
+    let mut url = Url::from_file_path(path).ok()?;
+    url.set_host(Some("localhost")).ok()?;
+    Some(url)
+}
+
 /// This is a little gross; on some systems, our pipe reader will continue
 /// to be blocked in read even after the child process has died.
 /// We need to wake up and notice that the child terminated in order
@@ -676,7 +862,141 @@ impl LocalPane {
             pty: RefCell::new(pty),
             domain_id,
             tmux_domain: RefCell::new(None),
+            command: None,
+            encoding: None,
+            search_index: RefCell::new(None),
+            reflow_scheduled: Cell::new(false),
+            search_index_scheduled: Cell::new(false),
+        }
+    }
+
+    /// Rewraps the scrollback that the last resize left to be done,
+    /// a batch at a time
+    fn schedule_reflow(&self) {
+        if !self.terminal.borrow().has_pending_reflow() || self.reflow_scheduled.replace(true) {
+            return;
+        }
+        let pane_id = self.pane_id;
+        promise::spawn::spawn(async move {
+            loop {
+                Timer::after(REFLOW_INTERVAL).await;
+                let mux = match Mux::get() {
+                    Some(mux) => mux,
+                    None => return,
+                };
+                let pane = match mux.get_pane(pane_id) {
+                    Some(pane) => pane,
+                    None => return,
+                };
+                let pane = match pane.downcast_ref::<LocalPane>() {
+                    Some(pane) => pane,
+                    None => return,
+                };
+                let more = pane.terminal.borrow_mut().reflow_pending(REFLOW_BATCH_ROWS);
+                if !more {
+                    pane.reflow_scheduled.set(false);
+                    pane.update_search_index();
+                }
+                mux.notify(MuxNotification::PaneOutput(pane_id));
+                if !more {
+                    return;
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Indexes the rows that have scrolled into the scrollback since
+    /// the last update, if enable_scrollback_search_index is set.
+    /// When there are too many to index at once, such as after a
+    /// resize, the rest are indexed a batch at a time.
+    fn update_search_index(&self) {
+        if !self.index_scrollback() || self.search_index_scheduled.replace(true) {
+            return;
+        }
+        let pane_id = self.pane_id;
+        promise::spawn::spawn(async move {
+            loop {
+                Timer::after(SEARCH_INDEX_INTERVAL).await;
+                let mux = match Mux::get() {
+                    Some(mux) => mux,
+                    None => return,
+                };
+                let pane = match mux.get_pane(pane_id) {
+                    Some(pane) => pane,
+                    None => return,
+                };
+                let pane = match pane.downcast_ref::<LocalPane>() {
+                    Some(pane) => pane,
+                    None => return,
+                };
+                if !pane.index_scrollback() {
+                    pane.search_index_scheduled.set(false);
+                    return;
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Indexes a batch of the scrollback.  Returns true if
+    /// there is more to index.
+    fn index_scrollback(&self) -> bool {
+        let config = configuration();
+        let mut index = self.search_index.borrow_mut();
+        if !config.enable_scrollback_search_index {
+            index.take();
+            return false;
+        }
+        let term = self.terminal.borrow();
+        // The alternate screen has no scrollback
+        if term.is_alt_screen_active() {
+            return false;
         }
+        let index =
+            index.get_or_insert_with(|| SearchIndex::new(config.scrollback_search_index_max_bytes));
+        index.set_max_bytes(config.scrollback_search_index_max_bytes);
+        index.update(term.screen())
+    }
+
+    /// Records the command that was spawned into this pane,
+    /// which allows the pane to be respawned after it exits
+    pub fn with_command(mut self, command: CommandBuilder) -> Self {
+        self.command.replace(command);
+        self
+    }
+
+    pub fn spawn_command(&self) -> Option<&CommandBuilder> {
+        self.command.as_ref()
+    }
+
+    /// Enables transcoding of the data exchanged with the program
+    /// in the pane.  `encoding` must be the same handle that is used
+    /// by the writer that was passed to the terminal, and `writer`
+    /// is used for input that is sent directly to the pane.
+    pub fn with_encoding(
+        mut self,
+        encoding: PaneEncoding,
+        writer: Box<dyn std::io::Write + Send>,
+    ) -> Self {
+        let writer = EncodingWriter::new(writer, encoding.clone());
+        self.encoding.replace((encoding, RefCell::new(writer)));
+        self
+    }
+
+    /// Returns the encoding of the program running in the pane
+    pub fn character_encoding(&self) -> CharacterEncoding {
+        self.encoding
+            .as_ref()
+            .map(|(encoding, _)| encoding.get())
+            .unwrap_or_default()
+    }
+
+    /// Returns true until the process running in the pane has exited
+    pub fn is_process_running(&self) -> bool {
+        // Let is_dead observe the exit status, if any
+        self.is_dead();
+        matches!(&*self.process.borrow(), ProcessState::Running { .. })
     }
 
     #[cfg(target_os = "macos")]
@@ -744,7 +1064,7 @@ impl LocalPane {
             if ret == size {
                 let path = unsafe { std::ffi::CStr::from_ptr(pathinfo.pvi_cdir.vip_path.as_ptr()) };
                 if let Ok(s) = path.to_str() {
-                    return Url::parse(&format!("file://localhost{}", s)).ok();
+                    return cwd_to_url(std::path::Path::new(s));
                 }
             }
         }
@@ -755,12 +1075,100 @@ impl LocalPane {
     fn divine_current_working_dir_linux(&self) -> Option<Url> {
         if let Some(pid) = self.pty.borrow().process_group_leader() {
             if let Ok(path) = std::fs::read_link(format!("/proc/{}/cwd", pid)) {
-                return Url::parse(&format!("file://localhost{}", path.display())).ok();
+                return cwd_to_url(&path);
             }
         }
         None
     }
 
+    /// Windows has no notion of a foreground process group, so this
+    /// reads the working directory of the process that was spawned into
+    /// the pane from its process parameters block.
+    #[cfg(windows)]
+    fn divine_current_working_dir_windows(&self) -> Option<Url> {
+        use std::ffi::OsString;
+        use std::mem::{size_of, MaybeUninit};
+        use std::os::windows::ffi::OsStringExt;
+        use winapi::shared::ntdef::UNICODE_STRING;
+        use winapi::um::memoryapi::ReadProcessMemory;
+        use winapi::um::winnt::HANDLE;
+        use winapi::um::winternl::{
+            NtQueryInformationProcess, ProcessBasicInformation, PEB, PROCESS_BASIC_INFORMATION,
+        };
+
+        /// The leading portion of RTL_USER_PROCESS_PARAMETERS, most of
+        /// which is marked as reserved in the SDK headers
+        #[repr(C)]
+        struct ProcessParameters {
+            maximum_length: u32,
+            length: u32,
+            flags: u32,
+            debug_flags: u32,
+            console_handle: HANDLE,
+            console_flags: u32,
+            standard_input: HANDLE,
+            standard_output: HANDLE,
+            standard_error: HANDLE,
+            current_directory: UNICODE_STRING,
+            current_directory_handle: HANDLE,
+        }
+
+        unsafe fn read_into(
+            handle: HANDLE,
+            addr: *const std::ffi::c_void,
+            buf: *mut std::ffi::c_void,
+            len: usize,
+        ) -> Option<()> {
+            let mut bytes_read = 0;
+            if ReadProcessMemory(handle, addr as _, buf as _, len, &mut bytes_read) == 0
+                || bytes_read != len
+            {
+                return None;
+            }
+            Some(())
+        }
+
+        unsafe fn read<T>(handle: HANDLE, addr: *const std::ffi::c_void) -> Option<T> {
+            let mut value = MaybeUninit::<T>::uninit();
+            read_into(handle, addr, value.as_mut_ptr() as _, size_of::<T>())?;
+            Some(value.assume_init())
+        }
+
+        let proc = self.process.borrow();
+        let handle = match &*proc {
+            ProcessState::Running { signaller, .. } => {
+                signaller.handle.as_ref()?.as_raw_handle() as HANDLE
+            }
+            _ => return None,
+        };
+
+        unsafe {
+            let mut info: PROCESS_BASIC_INFORMATION = std::mem::zeroed();
+            let status = NtQueryInformationProcess(
+                handle,
+                ProcessBasicInformation,
+                &mut info as *mut _ as _,
+                size_of::<PROCESS_BASIC_INFORMATION>() as u32,
+                std::ptr::null_mut(),
+            );
+            if status < 0 {
+                return None;
+            }
+            let peb: PEB = read(handle, info.PebBaseAddress as _)?;
+            let params: ProcessParameters = read(handle, peb.ProcessParameters as _)?;
+
+            let len = params.current_directory.Length as usize / 2;
+            let mut buf = vec![0u16; len];
+            read_into(
+                handle,
+                params.current_directory.Buffer as _,
+                buf.as_mut_ptr() as _,
+                len * 2,
+            )?;
+            cwd_to_url(&std::path::PathBuf::from(OsString::from_wide(&buf)))
+        }
+    }
+
     fn divine_current_working_dir(&self) -> Option<Url> {
         #[cfg(target_os = "linux")]
         {
@@ -772,10 +1180,17 @@ impl LocalPane {
             return self.divine_current_working_dir_macos();
         }
 
+        #[cfg(windows)]
+        {
+            return self.divine_current_working_dir_windows();
+        }
+
         #[allow(unreachable_code)]
         None
     }
 
+    /// Returns the executable paths of the processes in the pane,
+    /// or their names where the path is not available
     fn divine_process_list(&self) -> Vec<String> {
         #[allow(unused_mut)]
         let mut proc_names = vec![];
@@ -790,8 +1205,10 @@ impl LocalPane {
 
                 while let Some(pid) = pids_to_do.pop() {
                     if let Some(proc) = procs.get(&pid) {
-                        if let Some(exe) = proc.exe().file_name() {
-                            proc_names.push(exe.to_string_lossy().into_owned());
+                        if proc.exe().as_os_str().is_empty() {
+                            proc_names.push(proc.name().to_string());
+                        } else {
+                            proc_names.push(proc.exe().to_string_lossy().into_owned());
                         }
                     }
 
@@ -819,3 +1236,20 @@ impl Drop for LocalPane {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn cwd_url() {
+        let url = cwd_to_url(std::path::Path::new("/home/user/a #b")).unwrap();
+        assert_eq!(url.as_str(), "file://localhost/home/user/a%20%23b");
+        assert_eq!(
+            url.to_file_path().unwrap(),
+            std::path::PathBuf::from("/home/user/a #b")
+        );
+        assert!(cwd_to_url(std::path::Path::new("relative")).is_none());
+    }
+}
@@ -0,0 +1,107 @@
+//! Accounting of the memory used by the subsystems of wezterm, as
+//! reported by `wezterm cli memory-stats` and the debug overlay, to
+//! help triage reports of excessive memory usage or leaks.
+//! The figures are computed from the data structures that hold the
+//! memory rather than by instrumenting the allocator, so they are
+//! approximate and don't account for allocator overhead.
+use serde::{Deserialize, Serialize};
+
+/// The approximate number of bytes used by a subsystem, or by one
+/// of the items, such as a pane, that it manages
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryStat {
+    pub subsystem: String,
+    /// Identifies the item within the subsystem, eg: `pane 3`
+    pub item: Option<String>,
+    pub bytes: usize,
+}
+
+impl MemoryStat {
+    pub fn new(subsystem: &str, item: Option<String>, bytes: usize) -> Self {
+        Self {
+            subsystem: subsystem.to_string(),
+            item,
+            bytes,
+        }
+    }
+}
+
+/// Formats `bytes` using binary units, eg: `1.5 MiB`
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for u in UNITS {
+        if value < 1024. {
+            break;
+        }
+        value /= 1024.;
+        unit = u;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+/// Formats `stats` as a table with the total for each subsystem
+/// followed by its items, largest first
+pub fn format_stats(stats: &[MemoryStat]) -> String {
+    let mut subsystems: Vec<(&str, usize, Vec<&MemoryStat>)> = vec![];
+    for stat in stats {
+        match subsystems
+            .iter_mut()
+            .find(|(subsystem, _, _)| *subsystem == stat.subsystem)
+        {
+            Some((_, total, items)) => {
+                *total += stat.bytes;
+                items.push(stat);
+            }
+            None => subsystems.push((&stat.subsystem, stat.bytes, vec![stat])),
+        }
+    }
+    subsystems.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let mut output = String::new();
+    for (subsystem, total, mut items) in subsystems {
+        output.push_str(&format!("{:<30}{:>12}\n", subsystem, format_bytes(total)));
+        items.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+        for stat in items {
+            if let Some(item) = &stat.item {
+                output.push_str(&format!("  {:<28}{:>12}\n", item, format_bytes(stat.bytes)));
+            }
+        }
+    }
+    let total = stats.iter().map(|stat| stat.bytes).sum();
+    output.push_str(&format!("{:<30}{:>12}\n", "total", format_bytes(total)));
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bytes() {
+        assert_eq!(format_bytes(100), "100 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn table() {
+        let stats = vec![
+            MemoryStat::new("scrollback", Some("pane 0".to_string()), 1024),
+            MemoryStat::new("lua heap", None, 512),
+            MemoryStat::new("scrollback", Some("pane 1".to_string()), 2048),
+        ];
+        assert_eq!(
+            format_stats(&stats),
+            "scrollback                         3.0 KiB\n\
+             \x20 pane 1                           2.0 KiB\n\
+             \x20 pane 0                           1.0 KiB\n\
+             lua heap                             512 B\n\
+             total                              3.5 KiB\n"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::domain::DomainId;
+use crate::procinfo::ProcessInfo;
 use crate::renderable::*;
 use crate::Mux;
 use async_trait::async_trait;
@@ -16,7 +17,7 @@ use termwiz::surface::{Line, SequenceNo, SEQ_ZERO};
 use url::Url;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    Clipboard, KeyCode, KeyModifiers, MouseEvent, SemanticZone, StableRowIndex,
+    Clipboard, CommandResult, KeyCode, KeyModifiers, MouseEvent, SemanticZone, StableRowIndex,
     TerminalConfiguration,
 };
 
@@ -341,6 +342,17 @@ pub trait Pane: Downcast {
         Ok(vec![])
     }
 
+    /// Returns the most recently measured round trip time for input
+    /// sent to the pane, for panes that are hosted remotely
+    fn get_round_trip_latency(&self) -> Option<std::time::Duration> {
+        None
+    }
+
+    /// Retrieve the outcomes of the commands that have completed
+    fn get_command_results(&self) -> Vec<CommandResult> {
+        vec![]
+    }
+
     /// Returns true if the terminal has grabbed the mouse and wants to
     /// give the embedded application a chance to process events.
     /// In practice this controls whether the gui will perform local
@@ -354,8 +366,32 @@ pub trait Pane: Downcast {
         None
     }
 
+    /// Changes the character encoding that is used to exchange
+    /// data with the program running in the pane
+    fn set_encoding(&self, _encoding: &str) -> anyhow::Result<()> {
+        anyhow::bail!("this pane does not support changing its encoding")
+    }
+
+    /// Returns the name of the character encoding that is used to
+    /// exchange data with the program running in the pane
+    fn get_encoding(&self) -> Option<String> {
+        None
+    }
+
     fn get_current_working_dir(&self) -> Option<Url>;
 
+    /// Returns the foreground process of the pane along with its
+    /// descendants and their resource usage, if that is known
+    fn get_foreground_process_tree(&self) -> Option<ProcessInfo> {
+        None
+    }
+
+    /// Returns the approximate number of bytes used by the contents of
+    /// the pane, including its scrollback, if they are held in this process
+    fn memory_usage(&self) -> Option<usize> {
+        None
+    }
+
     fn trickle_paste(&self, text: String) -> anyhow::Result<()> {
         if text.len() <= PASTE_CHUNK_SIZE {
             // Send it all now
@@ -0,0 +1,119 @@
+//! Applies the parsed output of panes to their terminal models.
+//!
+//! The output of each pane is read and parsed on threads of its own
+//! (see `read_from_pane_pty`), so parsing a flood of output in one pane
+//! doesn't hold up parsing in the others.  Applying the parsed actions
+//! has to happen on the main thread though, so the parser threads queue
+//! their batches here, and the main thread applies them for at most half
+//! a frame at a time before yielding to the gui, so that a busy pane can
+//! neither starve the other panes nor delay painting and input handling.
+//!
+//! A parser thread blocks until its batch has been applied, so each pane
+//! has at most one batch in the queue.  Serving the queue in the order
+//! that the batches arrived therefore has the panes take turns, and
+//! applies the output of each pane in order.  The pane is notified of
+//! its output once per turn rather than once per batch.
+use crate::pane::PaneId;
+use crate::{Mux, MuxNotification};
+use config::configuration;
+use lazy_static::lazy_static;
+use metrics::histogram;
+use std::collections::VecDeque;
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use termwiz::escape::Action;
+
+lazy_static! {
+    static ref QUEUE: Mutex<OutputQueue> = Mutex::new(OutputQueue::default());
+}
+
+/// A batch of actions parsed from the output of a pane
+struct OutputBatch {
+    pane_id: PaneId,
+    actions: Vec<Action>,
+    /// Receives how long it took to apply the batch, or None if
+    /// the pane no longer exists
+    done: SyncSender<Option<Duration>>,
+}
+
+/// The batches that are waiting to be applied
+#[derive(Default)]
+struct OutputQueue {
+    batches: VecDeque<OutputBatch>,
+    /// Whether the main thread has a turn scheduled
+    scheduled: bool,
+}
+
+/// Queues `actions` to be applied to the pane, and blocks until they
+/// have been, which provides some back-pressure so that eg: ctrl-c can
+/// remain responsive.
+/// Returns how long it took to apply the actions, or None if the pane
+/// has been removed from the mux.
+pub(crate) fn apply_actions(pane_id: PaneId, actions: Vec<Action>) -> Option<Duration> {
+    let (tx, rx) = sync_channel(1);
+    let schedule = {
+        let mut queue = QUEUE.lock().unwrap();
+        queue.batches.push_back(OutputBatch {
+            pane_id,
+            actions,
+            done: tx,
+        });
+        !std::mem::replace(&mut queue.scheduled, true)
+    };
+    if schedule {
+        promise::spawn::spawn_into_main_thread(async move { apply_queued_output() }).detach();
+    }
+    rx.recv().unwrap_or(None)
+}
+
+/// Applies queued batches until the queue is empty or the budget for
+/// this turn has been used up, in which case another turn is scheduled
+/// at low priority so that the gui gets to run in between
+fn apply_queued_output() {
+    let start = Instant::now();
+    let budget = Duration::from_secs_f64(0.5 / configuration().max_fps.max(1) as f64);
+    let mux = Mux::get().unwrap();
+    let mut updated = vec![];
+
+    loop {
+        let batch = QUEUE.lock().unwrap().batches.pop_front();
+        let OutputBatch {
+            pane_id,
+            actions,
+            done,
+        } = match batch {
+            Some(batch) => batch,
+            None => break,
+        };
+
+        let result = mux.get_pane(pane_id).map(|pane| {
+            let start = Instant::now();
+            pane.perform_actions(actions);
+            let elapsed = start.elapsed();
+            histogram!("send_actions_to_mux.perform_actions.latency", elapsed);
+            if !updated.contains(&pane_id) {
+                updated.push(pane_id);
+            }
+            elapsed
+        });
+        done.send(result).ok();
+
+        if start.elapsed() >= budget {
+            break;
+        }
+    }
+
+    for pane_id in updated {
+        mux.notify(MuxNotification::PaneOutput(pane_id));
+    }
+
+    let more = {
+        let mut queue = QUEUE.lock().unwrap();
+        queue.scheduled = !queue.batches.is_empty();
+        queue.scheduled
+    };
+    if more {
+        promise::spawn::spawn_with_low_priority(async move { apply_queued_output() }).detach();
+    }
+}
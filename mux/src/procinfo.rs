@@ -0,0 +1,334 @@
+//! Samples the cpu and memory usage of the processes running in
+//! local panes.
+//! Sampling happens on a background thread that runs only while
+//! the information is being requested, so that querying it from
+//! the gui thread never blocks on walking the process table.
+use luahelper::impl_lua_conversion;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Describes a process and its descendants
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: Option<u32>,
+    pub name: String,
+    pub executable: PathBuf,
+    pub argv: Vec<String>,
+    /// Percentage of a single cpu used since the previous sample
+    pub cpu_usage: f32,
+    /// Resident memory, in bytes
+    pub memory: u64,
+    pub children: Vec<ProcessInfo>,
+}
+impl_lua_conversion!(ProcessInfo);
+
+impl ProcessInfo {
+    /// Returns the cpu usage of this process and all of its descendants
+    pub fn total_cpu_usage(&self) -> f32 {
+        self.cpu_usage
+            + self
+                .children
+                .iter()
+                .map(|c| c.total_cpu_usage())
+                .sum::<f32>()
+    }
+
+    /// Returns the memory used by this process and all of its descendants
+    pub fn total_memory(&self) -> u64 {
+        self.memory + self.children.iter().map(|c| c.total_memory()).sum::<u64>()
+    }
+}
+
+/// A single process from the most recent sample
+#[cfg_attr(
+    not(any(windows, target_os = "linux", target_os = "macos")),
+    allow(dead_code)
+)]
+#[derive(Debug, Clone)]
+struct ProcessSample {
+    ppid: Option<u32>,
+    name: String,
+    executable: PathBuf,
+    argv: Vec<String>,
+    cpu_usage: f32,
+    memory: u64,
+}
+
+/// Assembles the tree rooted at `pid` from a flat sample
+#[cfg_attr(
+    not(any(windows, target_os = "linux", target_os = "macos")),
+    allow(dead_code)
+)]
+fn build_tree(procs: &HashMap<u32, ProcessSample>, pid: u32) -> Option<ProcessInfo> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (child, sample) in procs {
+        if let Some(ppid) = sample.ppid {
+            children.entry(ppid).or_insert_with(Vec::new).push(*child);
+        }
+    }
+
+    fn build(
+        procs: &HashMap<u32, ProcessSample>,
+        children: &HashMap<u32, Vec<u32>>,
+        seen: &mut HashSet<u32>,
+        pid: u32,
+    ) -> Option<ProcessInfo> {
+        // pid reuse can make the parent links inconsistent; don't loop
+        if !seen.insert(pid) {
+            return None;
+        }
+        let sample = procs.get(&pid)?;
+        let mut kids = vec![];
+        if let Some(child_pids) = children.get(&pid) {
+            let mut child_pids = child_pids.clone();
+            child_pids.sort();
+            for child in child_pids {
+                if let Some(info) = build(procs, children, seen, child) {
+                    kids.push(info);
+                }
+            }
+        }
+        Some(ProcessInfo {
+            pid,
+            ppid: sample.ppid,
+            name: sample.name.clone(),
+            executable: sample.executable.clone(),
+            argv: sample.argv.clone(),
+            cpu_usage: sample.cpu_usage,
+            memory: sample.memory,
+            children: kids,
+        })
+    }
+
+    build(procs, &children, &mut HashSet::new(), pid)
+}
+
+/// Returns true if `pattern` matches the process whose executable
+/// is `exe`.
+/// A pattern that starts with `re:` is a regular expression that is
+/// matched against the full path of the executable.
+/// Otherwise the pattern may use the `*` and `?` wildcards; if it
+/// contains a path separator it is matched against the full path,
+/// otherwise against just the file name.
+pub fn process_name_matches(pattern: &str, exe: &str) -> bool {
+    if let Some(re) = pattern.strip_prefix("re:") {
+        return match regex::Regex::new(re) {
+            Ok(re) => re.is_match(exe),
+            Err(err) => {
+                log::error!("invalid process name pattern {}: {:#}", pattern, err);
+                false
+            }
+        };
+    }
+
+    let is_sep = |c: char| c == '/' || c == '\\';
+    let text = if pattern.contains(is_sep) {
+        exe
+    } else {
+        exe.rsplit(is_sep).next().unwrap_or(exe)
+    };
+    glob_match(pattern, text)
+}
+
+/// Matches `text` against `pattern`, where `*` matches any run of
+/// characters and `?` matches any single character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where to resume from if a mismatch follows the most recent `*`
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, star_t)) => {
+                    p = star + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(any(windows, target_os = "linux", target_os = "macos"))]
+mod sampler {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use sysinfo::{ProcessExt, RefreshKind, System, SystemExt};
+
+    const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+    /// Stop sampling if nobody has asked for a while
+    const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[derive(Default)]
+    struct State {
+        procs: Arc<HashMap<u32, ProcessSample>>,
+        last_request: Option<Instant>,
+        running: bool,
+    }
+
+    lazy_static::lazy_static! {
+        static ref STATE: Mutex<State> = Mutex::new(State::default());
+    }
+
+    fn sample_loop() {
+        let mut system = System::new_with_specifics(RefreshKind::new().with_processes());
+        loop {
+            system.refresh_processes();
+            let procs: HashMap<u32, ProcessSample> = system
+                .get_processes()
+                .iter()
+                .map(|(pid, proc)| {
+                    (
+                        *pid as u32,
+                        ProcessSample {
+                            ppid: proc.parent().map(|p| p as u32),
+                            name: proc.name().to_string(),
+                            executable: proc.exe().to_path_buf(),
+                            argv: proc.cmd().to_vec(),
+                            cpu_usage: proc.cpu_usage(),
+                            // sysinfo reports KiB
+                            memory: proc.memory() * 1024,
+                        },
+                    )
+                })
+                .collect();
+
+            {
+                let mut state = STATE.lock().unwrap();
+                let idle = state
+                    .last_request
+                    .map(|t| t.elapsed() > IDLE_TIMEOUT)
+                    .unwrap_or(true);
+                if idle {
+                    state.running = false;
+                    state.procs = Arc::new(HashMap::new());
+                    return;
+                }
+                state.procs = Arc::new(procs);
+            }
+
+            std::thread::sleep(SAMPLE_INTERVAL);
+        }
+    }
+
+    pub fn process_tree(pid: u32) -> Option<ProcessInfo> {
+        let procs = {
+            let mut state = STATE.lock().unwrap();
+            state.last_request.replace(Instant::now());
+            if !state.running {
+                state.running = true;
+                std::thread::spawn(sample_loop);
+            }
+            Arc::clone(&state.procs)
+        };
+        build_tree(&procs, pid)
+    }
+}
+
+/// Returns the most recently sampled information about the process
+/// `pid` and its descendants.
+/// Sampling starts on demand, so the first few calls may return None,
+/// and cpu usage is only meaningful once two samples have been taken.
+#[cfg(any(windows, target_os = "linux", target_os = "macos"))]
+pub fn process_tree(pid: u32) -> Option<ProcessInfo> {
+    sampler::process_tree(pid)
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+pub fn process_tree(_pid: u32) -> Option<ProcessInfo> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample(ppid: Option<u32>, name: &str, cpu_usage: f32, memory: u64) -> ProcessSample {
+        ProcessSample {
+            ppid,
+            name: name.to_string(),
+            executable: PathBuf::from(format!("/bin/{}", name)),
+            argv: vec![name.to_string()],
+            cpu_usage,
+            memory,
+        }
+    }
+
+    #[test]
+    fn tree() {
+        let mut procs = HashMap::new();
+        procs.insert(1, sample(None, "init", 0., 10));
+        procs.insert(10, sample(Some(1), "zsh", 1., 100));
+        procs.insert(12, sample(Some(10), "make", 2., 200));
+        procs.insert(11, sample(Some(10), "vim", 3., 300));
+        procs.insert(20, sample(Some(1), "other", 4., 400));
+
+        let tree = build_tree(&procs, 10).unwrap();
+        assert_eq!(tree.name, "zsh");
+        assert_eq!(
+            tree.children.iter().map(|c| c.pid).collect::<Vec<_>>(),
+            vec![11, 12]
+        );
+        assert_eq!(tree.total_cpu_usage(), 6.);
+        assert_eq!(tree.total_memory(), 600);
+
+        assert!(build_tree(&procs, 99).is_none());
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("bash", "bash"));
+        assert!(!glob_match("bash", "bashx"));
+        assert!(glob_match("*sh", "zsh"));
+        assert!(glob_match("*sh", "sh"));
+        assert!(!glob_match("*sh", "ssh-agent"));
+        assert!(glob_match("py*n?", "python3"));
+        assert!(glob_match("*a*b*", "xxaxxbxx"));
+        assert!(!glob_match("?", ""));
+    }
+
+    #[test]
+    fn process_names() {
+        assert!(process_name_matches("zsh", "/usr/bin/zsh"));
+        assert!(!process_name_matches("/bin/zsh", "/usr/bin/zsh"));
+        assert!(process_name_matches("/usr/*/zsh", "/usr/bin/zsh"));
+        assert!(process_name_matches(
+            "pwsh.exe",
+            "C:\\Program Files\\PowerShell\\7\\pwsh.exe"
+        ));
+        assert!(process_name_matches(
+            "re:/python[0-9.]*$",
+            "/usr/bin/python3.9"
+        ));
+        assert!(!process_name_matches("re:^python$", "/usr/bin/python"));
+        assert!(!process_name_matches("re:(", "/usr/bin/python"));
+    }
+
+    #[test]
+    fn cycle() {
+        let mut procs = HashMap::new();
+        procs.insert(1, sample(Some(2), "a", 0., 1));
+        procs.insert(2, sample(Some(1), "b", 0., 1));
+
+        let tree = build_tree(&procs, 1).unwrap();
+        assert_eq!(tree.children.len(), 1);
+        assert!(tree.children[0].children.is_empty());
+    }
+}
@@ -0,0 +1,293 @@
+//! Records the output of local panes, along with its timing, to files
+//! in the [asciicast v2](https://github.com/asciinema/asciinema/blob/develop/doc/asciicast-v2.md)
+//! format, and reads them back for playback.
+use crate::pane::PaneId;
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+
+/// The first line of an asciicast file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CastHeader {
+    pub version: u32,
+    pub width: usize,
+    pub height: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_time_limit: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+}
+
+/// Output that was written to the terminal `time` seconds after
+/// the start of the recording
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastEvent {
+    pub time: f64,
+    pub data: String,
+}
+
+/// A chunk of output, and the number of seconds after the
+/// start of the recording at which it was read
+type Chunk = (f64, Vec<u8>);
+
+struct Recorder {
+    path: PathBuf,
+    start: Instant,
+    /// Sends output to the thread that writes the recording,
+    /// so that the pty reader never waits for the disk
+    tx: Sender<Chunk>,
+}
+
+lazy_static::lazy_static! {
+    static ref RECORDINGS: Mutex<HashMap<PaneId, Recorder>> = Mutex::new(HashMap::new());
+}
+/// The number of active recordings, which allows the pty reader
+/// to skip taking the lock when nothing is being recorded
+static NUM_RECORDINGS: AtomicUsize = AtomicUsize::new(0);
+
+/// Starts recording the output of `pane_id` to a new file at `path`
+pub fn start_recording(
+    pane_id: PaneId,
+    path: &Path,
+    width: usize,
+    height: usize,
+    title: Option<String>,
+) -> anyhow::Result<()> {
+    if RECORDINGS.lock().unwrap().contains_key(&pane_id) {
+        anyhow::bail!("pane {} is already being recorded", pane_id);
+    }
+
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+
+    let mut env = HashMap::new();
+    env.insert("TERM".to_string(), config::configuration().term.clone());
+    let header = CastHeader {
+        version: 2,
+        width,
+        height,
+        timestamp: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs()),
+        idle_time_limit: None,
+        title,
+        env,
+    };
+    serde_json::to_writer(&mut out, &header)?;
+    out.write_all(b"\n")?;
+    out.flush()?;
+
+    let mut recordings = RECORDINGS.lock().unwrap();
+    if recordings.contains_key(&pane_id) {
+        anyhow::bail!("pane {} is already being recorded", pane_id);
+    }
+    let (tx, rx) = channel();
+    let thread_path = path.to_path_buf();
+    std::thread::Builder::new()
+        .name(format!("recording pane {}", pane_id))
+        .spawn(move || writer_thread(pane_id, thread_path, out, rx))
+        .context("spawning recording thread")?;
+    recordings.insert(
+        pane_id,
+        Recorder {
+            path: path.to_path_buf(),
+            start: Instant::now(),
+            tx,
+        },
+    );
+    NUM_RECORDINGS.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Stops recording `pane_id`, returning the path of the recording
+/// if it was being recorded.  The writer thread finishes writing
+/// the output that it has already received in the background.
+pub fn stop_recording(pane_id: PaneId) -> Option<PathBuf> {
+    let recorder = RECORDINGS.lock().unwrap().remove(&pane_id)?;
+    NUM_RECORDINGS.fetch_sub(1, Ordering::SeqCst);
+    Some(recorder.path)
+}
+
+/// Called with each chunk of output read from the pty of `pane_id`
+pub(crate) fn record_output(pane_id: PaneId, data: &[u8]) {
+    if NUM_RECORDINGS.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let mut recordings = RECORDINGS.lock().unwrap();
+    let failed = match recordings.get(&pane_id) {
+        Some(recorder) => {
+            let time = recorder.start.elapsed().as_secs_f64();
+            // This only fails if the writer thread gave up
+            recorder.tx.send((time, data.to_vec())).is_err()
+        }
+        None => false,
+    };
+    if failed {
+        recordings.remove(&pane_id);
+        NUM_RECORDINGS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn writer_thread(pane_id: PaneId, path: PathBuf, mut out: BufWriter<File>, rx: Receiver<Chunk>) {
+    // Bytes from the end of the most recent output that
+    // form an incomplete utf8 sequence
+    let mut pending = vec![];
+    let mut last_time = 0.;
+    while let Ok((time, data)) = rx.recv() {
+        last_time = time;
+        pending.extend_from_slice(&data);
+        let text = take_utf8(&mut pending);
+        if text.is_empty() {
+            continue;
+        }
+        if let Err(err) = write_event(&mut out, time, &text) {
+            log::error!(
+                "stopping recording of pane {} to {}: {:#}",
+                pane_id,
+                path.display(),
+                err
+            );
+            return;
+        }
+    }
+
+    if !pending.is_empty() {
+        let data = String::from_utf8_lossy(&pending).to_string();
+        if let Err(err) = write_event(&mut out, last_time, &data) {
+            log::error!("writing to {}: {:#}", path.display(), err);
+        }
+    }
+}
+
+fn write_event<W: Write>(out: &mut W, time: f64, data: &str) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut *out, &(time, "o", data))?;
+    out.write_all(b"\n")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Removes and returns the text from the start of `buf`.
+/// Invalid sequences are replaced by U+FFFD, while an incomplete
+/// sequence at the end is left in `buf` to be completed by the
+/// next chunk of output.
+fn take_utf8(buf: &mut Vec<u8>) -> String {
+    let mut text = String::new();
+    loop {
+        match std::str::from_utf8(buf) {
+            Ok(s) => {
+                text.push_str(s);
+                buf.clear();
+                return text;
+            }
+            Err(err) => {
+                let valid = err.valid_up_to();
+                text.push_str(std::str::from_utf8(&buf[..valid]).unwrap());
+                match err.error_len() {
+                    Some(len) => {
+                        text.push(std::char::REPLACEMENT_CHARACTER);
+                        buf.drain(..valid + len);
+                    }
+                    None => {
+                        buf.drain(..valid);
+                        return text;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reads an asciicast v2 recording.  Events other than output,
+/// such as input, are skipped.
+pub fn read_cast<R: BufRead>(reader: R) -> anyhow::Result<(CastHeader, Vec<CastEvent>)> {
+    let mut lines = reader.lines();
+    let header = lines
+        .next()
+        .ok_or_else(|| anyhow!("recording is empty"))??;
+    let header: CastHeader =
+        serde_json::from_str(&header).context("parsing the asciicast header")?;
+    if header.version != 2 {
+        anyhow::bail!("unsupported asciicast version {}", header.version);
+    }
+
+    let mut events = vec![];
+    for (idx, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (time, kind, data): (f64, String, String) = serde_json::from_str(&line)
+            .with_context(|| format!("parsing event on line {}", idx + 2))?;
+        if kind == "o" {
+            events.push(CastEvent { time, data });
+        }
+    }
+    Ok((header, events))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn utf8() {
+        let mut buf = b"hello \xe2\x9c".to_vec();
+        assert_eq!(take_utf8(&mut buf), "hello ");
+        assert_eq!(buf, b"\xe2\x9c");
+
+        buf.extend_from_slice(b"\x93 \xff!");
+        assert_eq!(take_utf8(&mut buf), "\u{2713} \u{fffd}!");
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn cast_events() {
+        let mut cast = vec![];
+        serde_json::to_writer(
+            &mut cast,
+            &CastHeader {
+                version: 2,
+                width: 80,
+                height: 24,
+                timestamp: None,
+                idle_time_limit: None,
+                title: Some("zsh".to_string()),
+                env: HashMap::new(),
+            },
+        )
+        .unwrap();
+        cast.push(b'\n');
+        write_event(&mut cast, 0.5, "\x1b[1mbold\r\n").unwrap();
+        cast.extend_from_slice(b"[0.75, \"i\", \"x\"]\n");
+        write_event(&mut cast, 1.25, "done").unwrap();
+
+        let (header, events) = read_cast(&cast[..]).unwrap();
+        assert_eq!(header.width, 80);
+        assert_eq!(header.title.as_deref(), Some("zsh"));
+        assert_eq!(
+            events,
+            vec![
+                CastEvent {
+                    time: 0.5,
+                    data: "\x1b[1mbold\r\n".to_string()
+                },
+                CastEvent {
+                    time: 1.25,
+                    data: "done".to_string()
+                },
+            ]
+        );
+    }
+}
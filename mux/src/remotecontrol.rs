@@ -0,0 +1,203 @@
+//! Performs the actions that programs running in panes request via
+//! the remote control escape sequence, subject to the `remote_control`
+//! configuration, and writes the outcome back to the requesting pane.
+//! Requests are handled by the process that hosts the pane, rather
+//! than by each gui that is attached to it.
+use crate::domain::DomainState;
+use crate::pane::PaneId;
+use crate::{Mux, MuxNotification};
+use anyhow::{anyhow, bail};
+use config::RemoteControlAction;
+use portable_pty::CommandBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Write;
+use wezterm_term::color::RgbColor;
+use wezterm_term::Alert;
+
+#[derive(Deserialize, Debug)]
+struct Request {
+    /// Echoed back in the response, so that the program can match
+    /// it up with its request
+    #[serde(default)]
+    id: Value,
+    action: RemoteControlAction,
+    password: Option<String>,
+    /// The program and arguments to spawn into a new tab
+    args: Option<Vec<String>>,
+    cwd: Option<String>,
+    domain: Option<String>,
+    /// The tab color; null clears it
+    color: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct Response {
+    id: Value,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Handles `request`, which was made by the program in `pane_id`
+pub fn handle_request(pane_id: PaneId, request: String) {
+    promise::spawn::spawn(async move {
+        let (id, result) = match serde_json::from_str::<Request>(&request) {
+            Ok(request) => (request.id.clone(), perform(pane_id, request).await),
+            Err(err) => (Value::Null, Err(anyhow!("invalid request: {}", err))),
+        };
+        let response = match result {
+            Ok(result) => Response {
+                id,
+                ok: true,
+                result: Some(result),
+                error: None,
+            },
+            Err(err) => {
+                log::warn!("remote control request from pane {}: {:#}", pane_id, err);
+                Response {
+                    id,
+                    ok: false,
+                    result: None,
+                    error: Some(format!("{:#}", err)),
+                }
+            }
+        };
+        if let Err(err) = send_response(pane_id, &response) {
+            log::error!("remote control response to pane {}: {:#}", pane_id, err);
+        }
+    })
+    .detach();
+}
+
+fn send_response(pane_id: PaneId, response: &Response) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    let pane = mux
+        .get_pane(pane_id)
+        .ok_or_else(|| anyhow!("pane has gone away"))?;
+    let json = serde_json::to_string(response)?;
+    write!(
+        pane.writer(),
+        "\x1b]1337;RemoteControlResponse={}\x1b\\",
+        base64::encode(json)
+    )?;
+    Ok(())
+}
+
+async fn perform(pane_id: PaneId, request: Request) -> anyhow::Result<Value> {
+    let config = config::configuration();
+    if !config::remote_control_permits(
+        &config.remote_control,
+        request.action,
+        request.password.as_deref(),
+    ) {
+        bail!("{:?} is not permitted", request.action);
+    }
+
+    let mux = Mux::get().unwrap();
+    let (domain_id, window_id, tab_id) = mux
+        .resolve_pane_id(pane_id)
+        .ok_or_else(|| anyhow!("pane {} is not in a window", pane_id))?;
+    let tab = mux
+        .get_tab(tab_id)
+        .ok_or_else(|| anyhow!("tab {} not found", tab_id))?;
+
+    match request.action {
+        RemoteControlAction::NewTab => {
+            let domain = match &request.domain {
+                Some(name) => mux
+                    .get_domain_by_name(name)
+                    .ok_or_else(|| anyhow!("unknown domain {}", name))?,
+                None => mux
+                    .get_domain(domain_id)
+                    .ok_or_else(|| anyhow!("domain {} not found", domain_id))?,
+            };
+            if domain.state() == DomainState::Detached {
+                domain.attach().await?;
+            }
+            let command = match &request.args {
+                Some(args) if !args.is_empty() => {
+                    let mut builder =
+                        CommandBuilder::from_argv(args.iter().map(Into::into).collect());
+                    if let Some(cwd) = &request.cwd {
+                        builder.cwd(cwd);
+                    }
+                    Some(builder)
+                }
+                _ => None,
+            };
+            let new_tab = domain
+                .spawn(
+                    tab.get_size(),
+                    command,
+                    request.cwd.clone(),
+                    None,
+                    window_id,
+                )
+                .await?;
+            if let Some(mut window) = mux.get_window_mut(window_id) {
+                if let Some(idx) = window.idx_by_id(new_tab.tab_id()) {
+                    window.save_and_then_set_active(idx);
+                }
+            }
+            Ok(json!({ "tab_id": new_tab.tab_id() }))
+        }
+        RemoteControlAction::SetTabColor => {
+            let color = match &request.color {
+                Some(color) => Some(
+                    RgbColor::from_named_or_rgb_string(color)
+                        .ok_or_else(|| anyhow!("invalid color {}", color))?,
+                ),
+                None => None,
+            };
+            tab.set_color(color);
+            // Prompts the tab bar to be recomputed
+            mux.notify(MuxNotification::Alert {
+                pane_id,
+                alert: Alert::TitleMaybeChanged,
+            });
+            Ok(Value::Null)
+        }
+        RemoteControlAction::GetLayout => {
+            let window = mux
+                .get_window(window_id)
+                .ok_or_else(|| anyhow!("window {} not found", window_id))?;
+            let active_idx = window.get_active_idx();
+            let tabs: Vec<Value> = window
+                .iter()
+                .enumerate()
+                .map(|(idx, tab)| {
+                    let panes: Vec<Value> = tab
+                        .iter_panes()
+                        .into_iter()
+                        .map(|pos| {
+                            json!({
+                                "pane_id": pos.pane.pane_id(),
+                                "is_active": pos.is_active,
+                                "is_zoomed": pos.is_zoomed,
+                                "left": pos.left,
+                                "top": pos.top,
+                                "width": pos.width,
+                                "height": pos.height,
+                                "title": pos.pane.get_title(),
+                            })
+                        })
+                        .collect();
+                    json!({
+                        "tab_id": tab.tab_id(),
+                        "is_active": idx == active_idx,
+                        "color": tab.get_color().map(|c| c.to_rgb_string()),
+                        "panes": panes,
+                    })
+                })
+                .collect();
+            Ok(json!({
+                "window_id": window_id,
+                "workspace": window.get_workspace(),
+                "tabs": tabs,
+            }))
+        }
+    }
+}
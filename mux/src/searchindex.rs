@@ -0,0 +1,494 @@
+//! An index over the scrollback of a pane that narrows down the rows
+//! that a search has to scan.  Rows are indexed once they have scrolled
+//! off the top of the screen, at which point their content no longer
+//! changes.  They are grouped into chunks, each of which records the
+//! trigrams of its text; a search only scans the chunks that contain
+//! every trigram of the text that a match requires.
+//!
+//! Each update indexes a bounded number of rows, so that indexing a large
+//! scrollback, such as after a resize rewraps it, is spread over many
+//! updates.  Searches scan the rows that haven't been indexed yet.
+use crate::pane::Pattern;
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+use wezterm_term::{Screen, StableRowIndex};
+
+/// How many rows are grouped into a chunk
+const CHUNK_ROWS: StableRowIndex = 256;
+
+/// About how many rows are indexed by each update
+const UPDATE_ROWS: StableRowIndex = 8192;
+
+/// The cost of remembering one trigram of a chunk
+const TRIGRAM_BYTES: usize = std::mem::size_of::<u32>();
+
+/// Hashes three characters, which were converted to lowercase, into a
+/// trigram.  Collisions only make the index a little less selective.
+fn trigram(a: char, b: char, c: char) -> u32 {
+    (a as u32).wrapping_mul(0x9e37_79b1)
+        ^ (b as u32).wrapping_mul(0x85eb_ca77).rotate_left(11)
+        ^ (c as u32).wrapping_mul(0xc2b2_ae3d).rotate_left(22)
+}
+
+fn add_trigrams(text: &str, trigrams: &mut HashSet<u32>) {
+    let chars: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    for window in chars.windows(3) {
+        trigrams.insert(trigram(window[0], window[1], window[2]));
+    }
+}
+
+/// Returns the literal text that every match of the regex `pattern`
+/// must contain, or None if the index can't be used for it: when a
+/// match could span more than one line, or the pattern uses anchors,
+/// or it contains an alternation outside of a group.  Only runs of
+/// at least three characters are returned.
+fn regex_literals(pattern: &str) -> Option<Vec<String>> {
+    let mut literals = vec![];
+    let mut current = String::new();
+    let mut depth = 0usize;
+    let mut chars = pattern.chars().peekable();
+
+    fn flush(current: &mut String, literals: &mut Vec<String>) {
+        if current.chars().count() >= 3 {
+            literals.push(current.clone());
+        }
+        current.clear();
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = chars.next()?;
+                if escaped.is_ascii_alphanumeric() {
+                    // Classes that can't match a newline are fine;
+                    // anything else might match one, or is an anchor
+                    if !matches!(escaped, 'd' | 'w' | 'b' | 'B') {
+                        return None;
+                    }
+                    flush(&mut current, &mut literals);
+                } else if depth == 0 {
+                    current.push(escaped);
+                }
+            }
+            '^' | '$' => return None,
+            '|' if depth == 0 => return None,
+            '(' => {
+                if chars.peek() == Some(&'?') {
+                    // Reject the flag that lets `.` match a newline
+                    let flags: String = chars
+                        .clone()
+                        .skip(1)
+                        .take_while(|c| c.is_ascii_alphabetic() || *c == '-')
+                        .collect();
+                    if flags.contains('s') {
+                        return None;
+                    }
+                }
+                depth += 1;
+                flush(&mut current, &mut literals);
+            }
+            ')' => {
+                depth = depth.checked_sub(1)?;
+                flush(&mut current, &mut literals);
+            }
+            '[' => {
+                if matches!(chars.peek(), Some('^') | Some(':') | Some('[')) {
+                    return None;
+                }
+                flush(&mut current, &mut literals);
+                // A `]` at the start of the class is a literal
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                }
+                loop {
+                    match chars.next()? {
+                        ']' => break,
+                        '\\' => {
+                            let escaped = chars.next()?;
+                            if escaped.is_ascii_alphanumeric() && !matches!(escaped, 'd' | 'w') {
+                                return None;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            '?' | '*' | '{' => {
+                // The preceding character is optional
+                current.pop();
+                flush(&mut current, &mut literals);
+                if c == '{' {
+                    for c in &mut chars {
+                        if c == '}' {
+                            break;
+                        }
+                    }
+                }
+            }
+            '.' | '+' => flush(&mut current, &mut literals),
+            c if depth == 0 => current.push(c),
+            _ => {}
+        }
+    }
+    flush(&mut current, &mut literals);
+    Some(literals)
+}
+
+/// Returns the trigrams that the text of every match of `pattern`
+/// contains, or None if the index can't narrow down the search
+pub fn pattern_trigrams(pattern: &Pattern) -> Option<Vec<u32>> {
+    let literals = match pattern {
+        Pattern::CaseSensitiveString(s) | Pattern::CaseInSensitiveString(s) => vec![s.clone()],
+        Pattern::Regex(r) => regex_literals(r)?,
+    };
+    let mut trigrams = HashSet::new();
+    for literal in &literals {
+        add_trigrams(literal, &mut trigrams);
+    }
+    if trigrams.is_empty() {
+        None
+    } else {
+        Some(trigrams.into_iter().collect())
+    }
+}
+
+struct IndexChunk {
+    rows: Range<StableRowIndex>,
+    /// Sorted, so that they can be binary searched
+    trigrams: Vec<u32>,
+}
+
+impl IndexChunk {
+    fn contains_all(&self, trigrams: &[u32]) -> bool {
+        trigrams
+            .iter()
+            .all(|t| self.trigrams.binary_search(t).is_ok())
+    }
+}
+
+/// The rows that are added to the index before they fill a chunk
+struct OpenChunk {
+    rows: Range<StableRowIndex>,
+    trigrams: HashSet<u32>,
+}
+
+pub struct SearchIndex {
+    chunks: VecDeque<IndexChunk>,
+    open: Option<OpenChunk>,
+    /// The first row that hasn't been indexed yet
+    next_row: StableRowIndex,
+    /// The size of the screen when the rows were indexed.  When the
+    /// width changes, they are reflowed, and when the height grows,
+    /// some of them move from the scrollback back onto the screen.
+    size: (usize, usize),
+    /// The memory used by `chunks`
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl SearchIndex {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            chunks: VecDeque::new(),
+            open: None,
+            next_row: 0,
+            size: (0, 0),
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.open.take();
+        self.bytes = 0;
+    }
+
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+        self.enforce_limit();
+    }
+
+    /// Returns the approximate amount of memory used by the index
+    pub fn memory_usage(&self) -> usize {
+        self.bytes
+            + self
+                .open
+                .as_ref()
+                .map(|open| open.trigrams.len() * TRIGRAM_BYTES)
+                .unwrap_or(0)
+    }
+
+    /// Adds the text of a logical line, which spans `rows`
+    fn add_line(&mut self, rows: Range<StableRowIndex>, text: &str) {
+        let open = self.open.get_or_insert_with(|| OpenChunk {
+            rows: rows.start..rows.start,
+            trigrams: HashSet::new(),
+        });
+        open.rows.end = rows.end;
+        add_trigrams(text, &mut open.trigrams);
+
+        if open.rows.end - open.rows.start >= CHUNK_ROWS {
+            let open = self.open.take().unwrap();
+            let mut trigrams: Vec<u32> = open.trigrams.into_iter().collect();
+            trigrams.sort_unstable();
+            trigrams.shrink_to_fit();
+            self.bytes += trigrams.len() * TRIGRAM_BYTES;
+            self.chunks.push_back(IndexChunk {
+                rows: open.rows,
+                trigrams,
+            });
+            self.enforce_limit();
+        }
+    }
+
+    /// Forgets the oldest chunks until the index fits within its limit.
+    /// Their rows are then scanned by every search.
+    fn enforce_limit(&mut self) {
+        while self.memory_usage() > self.max_bytes {
+            match self.chunks.pop_front() {
+                Some(chunk) => self.bytes -= chunk.trigrams.len() * TRIGRAM_BYTES,
+                None => break,
+            }
+        }
+    }
+
+    /// Forgets the rows from `row` onwards
+    fn truncate(&mut self, row: StableRowIndex) {
+        if self.next_row <= row {
+            return;
+        }
+        let mut next_row = row;
+        if let Some(open) = self.open.take() {
+            if open.rows.end <= row {
+                self.open.replace(open);
+                return;
+            }
+            next_row = open.rows.start;
+        }
+        while let Some(chunk) = self.chunks.back() {
+            if chunk.rows.end <= row {
+                break;
+            }
+            next_row = chunk.rows.start;
+            self.bytes -= chunk.trigrams.len() * TRIGRAM_BYTES;
+            self.chunks.pop_back();
+        }
+        self.next_row = next_row;
+    }
+
+    /// Indexes about `UPDATE_ROWS` of the rows of `screen` that have
+    /// scrolled into the scrollback since the last update, and forgets
+    /// those that have been removed from it.  Returns true if there are
+    /// more rows to index, which a later update will continue with.
+    pub fn update(&mut self, screen: &Screen) -> bool {
+        let first_row = screen.phys_to_stable_row_index(0);
+        let scrollback_end = screen
+            .phys_to_stable_row_index(screen.lines.len().saturating_sub(screen.physical_rows));
+
+        // The rows are renumbered as they are rewrapped, so
+        // they are indexed again once that has finished
+        if screen.has_pending_reflow() {
+            self.clear();
+            self.size = (0, 0);
+            self.next_row = first_row;
+            return false;
+        }
+
+        let size = (screen.physical_cols, screen.physical_rows);
+        if size.0 != self.size.0 {
+            self.clear();
+            self.next_row = first_row;
+        } else if size.1 != self.size.1 {
+            // The rows keep their numbers when only the height changes,
+            // but those that moved back onto the screen can change again
+            self.truncate(scrollback_end);
+        }
+        self.size = size;
+
+        while let Some(chunk) = self.chunks.front() {
+            if chunk.rows.end > first_row {
+                break;
+            }
+            self.bytes -= chunk.trigrams.len() * TRIGRAM_BYTES;
+            self.chunks.pop_front();
+        }
+        if self.next_row < first_row {
+            self.open.take();
+            self.next_row = first_row;
+        }
+
+        let start_row = self.next_row;
+        let mut line_start = self.next_row;
+        let mut text = String::new();
+        let mut more = false;
+        for row in self.next_row..scrollback_end {
+            if line_start == row && row - start_row >= UPDATE_ROWS {
+                more = true;
+                break;
+            }
+            let line = match screen.stable_row_to_phys(row) {
+                Some(phys) => &screen.lines[phys],
+                None => break,
+            };
+            let mut wrapped = false;
+            for (_, cell) in line.visible_cells() {
+                text.push_str(cell.str());
+                wrapped = cell.attrs().wrapped();
+            }
+            if !wrapped {
+                self.add_line(line_start..row + 1, &text);
+                text.clear();
+                line_start = row + 1;
+            }
+        }
+        // A logical line that continues onto the screen is
+        // indexed once all of it has scrolled off
+        self.next_row = line_start;
+        more
+    }
+
+    /// Returns the ranges of the rows in `all` that must be scanned for
+    /// text containing every one of `trigrams`: the indexed chunks that
+    /// contain all of them, along with the rows that aren't indexed
+    pub fn rows_to_search(
+        &self,
+        trigrams: &[u32],
+        all: Range<StableRowIndex>,
+    ) -> Vec<Range<StableRowIndex>> {
+        let indexed_start = self
+            .chunks
+            .front()
+            .map(|chunk| chunk.rows.start)
+            .or_else(|| self.open.as_ref().map(|open| open.rows.start))
+            .unwrap_or(self.next_row);
+
+        let mut ranges: Vec<Range<StableRowIndex>> = vec![all.start..indexed_start];
+        for chunk in &self.chunks {
+            if chunk.contains_all(trigrams) {
+                ranges.push(chunk.rows.clone());
+            }
+        }
+        if let Some(open) = self.open.as_ref() {
+            if trigrams.iter().all(|t| open.trigrams.contains(t)) {
+                ranges.push(open.rows.clone());
+            }
+        }
+        ranges.push(self.next_row..all.end);
+
+        let mut merged: Vec<Range<StableRowIndex>> = vec![];
+        for range in ranges {
+            let range = range.start.max(all.start)..range.end.min(all.end);
+            if range.start >= range.end {
+                continue;
+            }
+            match merged.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn index_of(lines: &[&str]) -> SearchIndex {
+        let mut index = SearchIndex::new(usize::max_value());
+        for (row, text) in lines.iter().enumerate() {
+            let row = row as StableRowIndex;
+            index.add_line(row..row + 1, text);
+        }
+        index.next_row = lines.len() as StableRowIndex;
+        index
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(
+            regex_literals("error: (\\d+) files?"),
+            Some(vec!["error: ".to_string(), " file".to_string()])
+        );
+        assert_eq!(
+            regex_literals("foo\\.rs|bar"),
+            None,
+            "alternation outside of a group"
+        );
+        assert_eq!(
+            regex_literals("(foo|bar)baz"),
+            Some(vec!["baz".to_string()])
+        );
+        assert_eq!(regex_literals("^cargo"), None);
+        assert_eq!(regex_literals("foo\\sbar"), None);
+        assert_eq!(regex_literals("foo[^x]bar"), None);
+        assert_eq!(regex_literals("(?s)foo.bar"), None);
+        assert_eq!(
+            regex_literals("wez[a-z]+term"),
+            Some(vec!["wez".to_string(), "term".to_string()])
+        );
+        assert_eq!(regex_literals("a.b"), Some(vec![]));
+    }
+
+    #[test]
+    fn search() {
+        let mut lines = vec!["nothing to see here"; 600];
+        lines[10] = "error: the needle";
+        lines[500] = "ERROR: another needle";
+        let index = index_of(&lines);
+        assert_eq!(index.chunks.len(), 2);
+
+        let trigrams = pattern_trigrams(&Pattern::CaseInSensitiveString("needle".into())).unwrap();
+        assert_eq!(
+            index.rows_to_search(&trigrams, 0..650),
+            // The two chunks that contain the word, but not the open
+            // chunk, followed by the rows that aren't indexed yet
+            vec![0..512, 600..650]
+        );
+
+        let trigrams = pattern_trigrams(&Pattern::Regex("another\\s+needle".into()));
+        assert!(trigrams.is_none());
+
+        let trigrams =
+            pattern_trigrams(&Pattern::CaseSensitiveString("the needle".into())).unwrap();
+        assert_eq!(
+            index.rows_to_search(&trigrams, 0..650),
+            vec![0..256, 600..650]
+        );
+    }
+
+    #[test]
+    fn truncate() {
+        let lines = vec!["some text"; 600];
+        let mut index = index_of(&lines);
+        assert_eq!(index.chunks.len(), 2);
+
+        // Rows that are still covered are kept
+        index.truncate(600);
+        assert_eq!((index.chunks.len(), index.next_row), (2, 600));
+
+        index.truncate(300);
+        assert!(index.open.is_none());
+        assert_eq!((index.chunks.len(), index.next_row), (1, 256));
+
+        let trigrams = pattern_trigrams(&Pattern::CaseSensitiveString("text".into())).unwrap();
+        assert_eq!(index.rows_to_search(&trigrams, 0..600), vec![0..600]);
+    }
+
+    #[test]
+    fn memory_limit() {
+        let lines: Vec<String> = (0..1024).map(|i| format!("line number {}", i)).collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let mut index = index_of(&lines);
+        assert_eq!(index.chunks.len(), 4);
+
+        let chunk_bytes = index.chunks[0].trigrams.len() * TRIGRAM_BYTES;
+        index.set_max_bytes(index.memory_usage() - chunk_bytes);
+        assert_eq!(index.chunks.len(), 3);
+
+        // The rows of the forgotten chunk are always searched
+        let trigrams = pattern_trigrams(&Pattern::CaseSensitiveString("no match".into())).unwrap();
+        assert_eq!(index.rows_to_search(&trigrams, 0..1024), vec![0..256]);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::connui::ConnectionUI;
 use crate::domain::{alloc_domain_id, Domain, DomainId, DomainState};
+use crate::encoding::CharacterEncoding;
 use crate::localpane::LocalPane;
 use crate::pane::{alloc_pane_id, Pane, PaneId};
 use crate::tab::{SplitDirection, Tab, TabId};
@@ -7,6 +8,7 @@ use crate::window::WindowId;
 use crate::Mux;
 use anyhow::{anyhow, bail, Context, Error};
 use async_trait::async_trait;
+use config::SshTerminfoAction;
 use filedescriptor::{poll, pollfd, socketpair, AsRawSocketDescriptor, FileDescriptor, POLLIN};
 use portable_pty::cmdbuilder::CommandBuilder;
 use portable_pty::{ExitStatus, MasterPty, PtySize};
@@ -134,20 +136,39 @@ pub struct RemoteSshDomain {
     id: DomainId,
     name: String,
     events: RefCell<Option<smol::channel::Receiver<SessionEvent>>>,
+    /// The host name, used to select the ssh_terminfo rule
+    host: String,
+    /// The TERM value chosen when the session was established,
+    /// which may be the fallback from the ssh_terminfo rule
+    term: Arc<Mutex<Option<String>>>,
 }
 
 impl RemoteSshDomain {
     pub fn with_ssh_config(name: &str, ssh_config: ConfigMap) -> anyhow::Result<Self> {
         let id = alloc_domain_id();
+        let host = ssh_config
+            .get("hostname")
+            .cloned()
+            .unwrap_or_else(|| name.to_string());
         let (session, events) = Session::connect(ssh_config.clone())?;
         Ok(Self {
             id,
             name: format!("SSH to {}", name),
             session,
             events: RefCell::new(Some(events)),
+            host,
+            term: Arc::new(Mutex::new(None)),
         })
     }
 
+    fn term(&self) -> String {
+        self.term
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| config::configuration().term.clone())
+    }
+
     // This is a method of its own so that we can ensure that the
     // borrow is released when we return
     fn take_events(&self) -> Option<smol::channel::Receiver<SessionEvent>> {
@@ -168,6 +189,8 @@ fn connect_ssh_session(
     size: Arc<Mutex<PtySize>>,
     command_line: Option<String>,
     env: HashMap<String, String>,
+    host: String,
+    resolved_term: Arc<Mutex<Option<String>>>,
 ) -> anyhow::Result<()> {
     struct StdoutShim<'a> {
         size: Arc<Mutex<PtySize>>,
@@ -327,6 +350,26 @@ fn connect_ssh_session(
         }
     }
 
+    impl<'a> TerminfoPrompt for TerminalShim<'a> {
+        fn output_line(&mut self, s: &str) -> anyhow::Result<()> {
+            Ok(TerminalShim::output_line(self, s)?)
+        }
+
+        fn confirm(&mut self, prompt: &str) -> anyhow::Result<bool> {
+            let mut editor = LineEditor::new(self);
+            let mut host = PasswordPromptHost::default();
+            host.echo = true;
+            editor.set_prompt(prompt);
+            Ok(match editor.read_line(&mut host)? {
+                Some(line) => match line.as_ref() {
+                    "y" | "Y" | "yes" | "YES" => true,
+                    _ => false,
+                },
+                None => false,
+            })
+        }
+    }
+
     // Process authentication related events
     while let Ok(event) = smol::block_on(events.recv()) {
         match event {
@@ -381,10 +424,19 @@ fn connect_ssh_session(
                 shim.output_line(&format!("Error: {}", err))?;
             }
             SessionEvent::Authenticated => {
+                let term = match resolve_term(&session, &host, &mut shim, true) {
+                    Ok(term) => term,
+                    Err(err) => {
+                        shim.output_line(&format!("Failed to check terminfo: {:#}", err))?;
+                        config::configuration().term.clone()
+                    }
+                };
+                resolved_term.lock().unwrap().replace(term.clone());
+
                 // Our session has been authenticated: we can now
                 // set up the real pty for the pane
                 match smol::block_on(session.request_pty(
-                    &config::configuration().term,
+                    &term,
                     *size.lock().unwrap(),
                     command_line.as_ref().map(|s| s.as_str()),
                     Some(env),
@@ -431,6 +483,142 @@ fn connect_ssh_session(
     Ok(())
 }
 
+/// How the user is asked whether to install the terminfo
+/// on the remote host
+trait TerminfoPrompt {
+    fn output_line(&mut self, s: &str) -> anyhow::Result<()>;
+    fn confirm(&mut self, prompt: &str) -> anyhow::Result<bool>;
+}
+
+impl TerminfoPrompt for ConnectionUI {
+    fn output_line(&mut self, s: &str) -> anyhow::Result<()> {
+        self.output_str(&format!("{}\n", s));
+        Ok(())
+    }
+
+    fn confirm(&mut self, prompt: &str) -> anyhow::Result<bool> {
+        Ok(match self.input(prompt)?.as_ref() {
+            "y" | "Y" | "yes" | "YES" => true,
+            _ => false,
+        })
+    }
+}
+
+/// Decides which TERM value to use on the remote host, installing
+/// the wezterm terminfo there if the ssh_terminfo rule calls for it.
+/// `can_fallback` is false when the caller cannot use the fallback
+/// TERM value of the rule, in which case the configured `term` is
+/// returned even if it is not available.
+fn resolve_term(
+    session: &Session,
+    host: &str,
+    prompt: &mut dyn TerminfoPrompt,
+    can_fallback: bool,
+) -> anyhow::Result<String> {
+    let config = config::configuration();
+    let term = config.term.clone();
+    let rule = match config.ssh_terminfo_rule(host) {
+        Some(rule) => rule.clone(),
+        None => return Ok(term),
+    };
+    if (can_fallback && term == rule.fallback_term) || remote_has_terminfo(session, &term)? {
+        return Ok(term);
+    }
+
+    // We can only install the definition that we ship
+    let install = term == "wezterm"
+        && match rule.action {
+            SshTerminfoAction::Install => true,
+            SshTerminfoAction::Fallback => false,
+            SshTerminfoAction::Ask => {
+                prompt.output_line(&format!(
+                    "The {} terminfo entry is not installed on {}.",
+                    term, host
+                ))?;
+                prompt.confirm(&if can_fallback {
+                    format!(
+                        "Install it to ~/.terminfo? (otherwise TERM={}) [y/n]> ",
+                        rule.fallback_term
+                    )
+                } else {
+                    "Install it to ~/.terminfo? [y/n]> ".to_string()
+                })?
+            }
+        };
+
+    if install {
+        match install_wezterm_terminfo(session) {
+            Ok(()) if remote_has_terminfo(session, &term)? => return Ok(term),
+            Ok(()) => prompt.output_line("Installed the terminfo, but it still cannot be found")?,
+            Err(err) => {
+                prompt.output_line(&format!("Failed to install the terminfo: {:#}", err))?
+            }
+        }
+    }
+
+    if !can_fallback {
+        return Ok(term);
+    }
+    log::info!(
+        "ssh: using TERM={} for {} as {} is not available",
+        rule.fallback_term,
+        host,
+        term
+    );
+    Ok(rule.fallback_term)
+}
+
+/// Applies the ssh_terminfo rule for `host` when connecting to the
+/// wezterm mux server on that host via an ssh multiplexing domain.
+/// The panes of such a domain are spawned by the remote mux server,
+/// which sets TERM from its own configuration, so the terminfo can be
+/// installed but the fallback TERM value of the rule cannot be used.
+pub fn ensure_remote_terminfo(
+    session: &Session,
+    host: &str,
+    ui: &mut ConnectionUI,
+) -> anyhow::Result<()> {
+    resolve_term(session, host, ui, false)?;
+    Ok(())
+}
+
+const WEZTERM_TERMINFO: &str = include_str!("../../termwiz/data/wezterm.terminfo");
+
+/// Runs `script` using `sh` on the remote host, returning true
+/// if it exited successfully.  The user's login shell may not
+/// be sh-compatible, so the script is passed as a single quoted
+/// argument, which all common shells interpret in the same way.
+fn run_remote_sh(session: &Session, script: &str) -> anyhow::Result<bool> {
+    let command_line = format!("sh -c '{}'", script.replace('\'', "'\\''"));
+    let mut exec = smol::block_on(session.exec(&command_line, None))?;
+    // Note that stdin must remain open until the command has
+    // completed, as closing it closes the channel
+    let status = smol::block_on(exec.child.async_wait())?;
+    Ok(status.success())
+}
+
+fn remote_has_terminfo(session: &Session, term: &str) -> anyhow::Result<bool> {
+    run_remote_sh(session, &format!("infocmp \"{}\" >/dev/null 2>&1", term))
+}
+
+/// Compiles our terminfo source into ~/.terminfo on the remote host
+fn install_wezterm_terminfo(session: &Session) -> anyhow::Result<()> {
+    let script = format!(
+        "tmp=$(mktemp) || exit 1\n\
+         cat > \"$tmp\" <<\"WEZTERM_TERMINFO_EOF\"\n{}WEZTERM_TERMINFO_EOF\n\
+         tic -x -o \"$HOME/.terminfo\" \"$tmp\"\n\
+         rc=$?\n\
+         rm -f \"$tmp\"\n\
+         exit $rc\n",
+        WEZTERM_TERMINFO
+    );
+    if run_remote_sh(session, &script)? {
+        Ok(())
+    } else {
+        bail!("tic failed; is ncurses installed on the remote host?")
+    }
+}
+
 #[async_trait(?Send)]
 impl Domain for RemoteSshDomain {
     async fn spawn(
@@ -438,8 +626,15 @@ impl Domain for RemoteSshDomain {
         size: PtySize,
         command: Option<CommandBuilder>,
         _command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
         window: WindowId,
     ) -> Result<Rc<Tab>, Error> {
+        if let Some(encoding) = encoding.filter(|encoding| !encoding.is_utf8()) {
+            bail!(
+                "ssh domains do not support the {} encoding",
+                encoding.name()
+            );
+        }
         let pane_id = alloc_pane_id();
 
         let cmd = match command {
@@ -510,6 +705,8 @@ impl Domain for RemoteSshDomain {
             // to perform the blocking (from its perspective) terminal
             // UI to carry out any authentication.
             let session = self.session.clone();
+            let host = self.host.clone();
+            let resolved_term = Arc::clone(&self.term);
             let mut stdout_write = BufWriter::new(stdout_write);
             std::thread::spawn(move || {
                 if let Err(err) = connect_ssh_session(
@@ -524,6 +721,8 @@ impl Domain for RemoteSshDomain {
                     size,
                     command_line,
                     env,
+                    host,
+                    resolved_term,
                 ) {
                     let _ = write!(stdout_write, "{:#}", err);
                     log::error!("Failed to connect ssh: {:#}", err);
@@ -534,7 +733,7 @@ impl Domain for RemoteSshDomain {
             let (concrete_pty, concrete_child) = self
                 .session
                 .request_pty(
-                    &config::configuration().term,
+                    &self.term(),
                     size,
                     command_line.as_ref().map(|s| s.as_str()),
                     Some(env),
@@ -573,10 +772,17 @@ impl Domain for RemoteSshDomain {
         &self,
         command: Option<CommandBuilder>,
         _command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
         tab: TabId,
         pane_id: PaneId,
         direction: SplitDirection,
     ) -> anyhow::Result<Rc<dyn Pane>> {
+        if let Some(encoding) = encoding.filter(|encoding| !encoding.is_utf8()) {
+            bail!(
+                "ssh domains do not support the {} encoding",
+                encoding.name()
+            );
+        }
         let mux = Mux::get().unwrap();
         let tab = match mux.get_tab(tab) {
             Some(t) => t,
@@ -621,7 +827,7 @@ impl Domain for RemoteSshDomain {
         let (pty, child) = self
             .session
             .request_pty(
-                &config::configuration().term,
+                &self.term(),
                 split_size.size(),
                 command_line.as_ref().map(|s| s.as_str()),
                 Some(env),
@@ -10,6 +10,7 @@ use std::cell::{RefCell, RefMut};
 use std::convert::TryInto;
 use std::rc::Rc;
 use url::Url;
+use wezterm_term::color::RgbColor;
 
 pub type Tree = bintree::Tree<Rc<dyn Pane>, SplitDirectionAndSize>;
 pub type Cursor = bintree::Cursor<Rc<dyn Pane>, SplitDirectionAndSize>;
@@ -24,6 +25,10 @@ pub struct Tab {
     size: RefCell<PtySize>,
     active: RefCell<usize>,
     zoomed: RefCell<Option<Rc<dyn Pane>>>,
+    /// Overrides the background color of the tab in the tab bar
+    color: RefCell<Option<RgbColor>>,
+    /// The profile that was requested when the tab was spawned
+    profile: RefCell<Option<String>>,
 }
 
 #[derive(Clone)]
@@ -155,12 +160,17 @@ fn pane_tree(
     window_id: WindowId,
     active: Option<&Rc<dyn Pane>>,
     zoomed: Option<&Rc<dyn Pane>>,
+    workspace: &str,
 ) -> PaneNode {
     match tree {
         Tree::Empty => PaneNode::Empty,
         Tree::Node { left, right, data } => PaneNode::Split {
-            left: Box::new(pane_tree(&*left, tab_id, window_id, active, zoomed)),
-            right: Box::new(pane_tree(&*right, tab_id, window_id, active, zoomed)),
+            left: Box::new(pane_tree(
+                &*left, tab_id, window_id, active, zoomed, workspace,
+            )),
+            right: Box::new(pane_tree(
+                &*right, tab_id, window_id, active, zoomed, workspace,
+            )),
             node: data.unwrap(),
         },
         Tree::Leaf(pane) => {
@@ -181,6 +191,7 @@ fn pane_tree(
                     pixel_width: 0,
                 },
                 working_dir: working_dir.map(Into::into),
+                workspace: workspace.to_string(),
             })
         }
     }
@@ -408,6 +419,8 @@ impl Tab {
             size: RefCell::new(*size),
             active: RefCell::new(0),
             zoomed: RefCell::new(None),
+            color: RefCell::new(None),
+            profile: RefCell::new(None),
         }
     }
 
@@ -483,10 +496,22 @@ impl Tab {
             }
         };
 
+        let workspace = match mux.get_window(window_id) {
+            Some(w) => w.get_workspace().to_string(),
+            None => return PaneNode::Empty,
+        };
+
         let zoomed = self.zoomed.borrow();
         let active = self.get_active_pane();
         if let Some(root) = self.pane.borrow().as_ref() {
-            pane_tree(root, tab_id, window_id, active.as_ref(), zoomed.as_ref())
+            pane_tree(
+                root,
+                tab_id,
+                window_id,
+                active.as_ref(),
+                zoomed.as_ref(),
+                &workspace,
+            )
         } else {
             PaneNode::Empty
         }
@@ -693,6 +718,25 @@ impl Tab {
         self.id
     }
 
+    pub fn get_color(&self) -> Option<RgbColor> {
+        *self.color.borrow()
+    }
+
+    pub fn set_color(&self, color: Option<RgbColor>) {
+        *self.color.borrow_mut() = color;
+    }
+
+    /// Returns the profile that was requested when the tab was spawned,
+    /// such as by `wezterm start --profile`.  The gui applies it unless
+    /// the tab has been switched to another profile.
+    pub fn get_profile(&self) -> Option<String> {
+        self.profile.borrow().clone()
+    }
+
+    pub fn set_profile(&self, profile: Option<String>) {
+        *self.profile.borrow_mut() = profile;
+    }
+
     pub fn get_size(&self) -> PtySize {
         *self.size.borrow()
     }
@@ -1103,18 +1147,75 @@ impl Tab {
     }
 
     pub fn prune_dead_panes(&self) -> bool {
-        self.remove_pane_if(|_, pane| pane.is_dead())
+        !self
+            .remove_pane_if(|_, pane| pane.is_dead(), true)
+            .is_empty()
     }
 
     pub fn kill_pane(&self, pane_id: PaneId) -> bool {
-        self.remove_pane_if(|_, pane| pane.pane_id() == pane_id)
+        !self
+            .remove_pane_if(|_, pane| pane.pane_id() == pane_id, true)
+            .is_empty()
     }
 
     pub fn kill_panes_in_domain(&self, domain: DomainId) -> bool {
-        self.remove_pane_if(|_, pane| pane.domain_id() == domain)
+        !self
+            .remove_pane_if(|_, pane| pane.domain_id() == domain, true)
+            .is_empty()
+    }
+
+    /// Removes the pane from this tab without killing it, so that
+    /// it can be placed into some other tab.
+    /// Returns None if the pane isn't part of this tab.
+    pub fn remove_pane(&self, pane_id: PaneId) -> Option<Rc<dyn Pane>> {
+        self.set_zoomed(false);
+        self.remove_pane_if(|_, pane| pane.pane_id() == pane_id, false)
+            .into_iter()
+            .next()
     }
 
-    fn remove_pane_if<F>(&self, f: F) -> bool
+    /// Puts `pane` in place of the pane at the topological `pane_index`,
+    /// resizing it to fit, and returns the pane that was replaced.
+    /// The replaced pane is not killed, so that it can be placed into
+    /// some other tab.
+    /// Returns None if `pane_index` is invalid.
+    pub fn replace_pane(&self, pane_index: usize, pane: &Rc<dyn Pane>) -> Option<Rc<dyn Pane>> {
+        self.set_zoomed(false);
+        let pos = self.iter_panes().into_iter().nth(pane_index)?;
+
+        let mut prior = None;
+        {
+            let mut root = self.pane.borrow_mut();
+            let cursor = match root.take().unwrap().cursor().go_to_nth_leaf(pos.index) {
+                Ok(mut cursor) => {
+                    prior = cursor
+                        .leaf_mut()
+                        .map(|leaf| std::mem::replace(leaf, Rc::clone(pane)));
+                    cursor
+                }
+                Err(cursor) => cursor,
+            };
+            root.replace(cursor.tree());
+        }
+
+        if prior.is_some() {
+            let size = PtySize {
+                rows: pos.height as u16,
+                cols: pos.width as u16,
+                pixel_width: pos.pixel_width as u16,
+                pixel_height: pos.pixel_height as u16,
+            };
+            if let Err(err) = pane.resize(size) {
+                log::error!("failed to resize pane {}: {:#}", pane.pane_id(), err);
+            }
+        }
+        prior
+    }
+
+    /// Removes the panes for which `f` returns true, returning them.
+    /// If `kill` is true, the removed panes are also removed from
+    /// the mux, which kills them.
+    fn remove_pane_if<F>(&self, f: F, kill: bool) -> Vec<Rc<dyn Pane>>
     where
         F: Fn(usize, &Rc<dyn Pane>) -> bool,
     {
@@ -1150,7 +1251,7 @@ impl Tab {
                         let parent;
                         match cursor.unsplit_leaf() {
                             Ok((c, dead, p)) => {
-                                dead_panes.push(dead.pane_id());
+                                dead_panes.push(dead);
                                 parent = p.unwrap();
                                 cursor = c;
                             }
@@ -1158,7 +1259,7 @@ impl Tab {
                                 // We might be the root, for example
                                 if c.is_top() && c.is_leaf() {
                                     root.replace(Tree::Empty);
-                                    dead_panes.push(pane.pane_id());
+                                    dead_panes.push(Rc::clone(&pane));
                                 } else {
                                     root.replace(c.tree());
                                 }
@@ -1200,18 +1301,17 @@ impl Tab {
             *self.active.borrow_mut() = active_idx;
         }
 
-        if !dead_panes.is_empty() {
+        if kill && !dead_panes.is_empty() {
+            let dead_pane_ids: Vec<PaneId> = dead_panes.iter().map(|p| p.pane_id()).collect();
             promise::spawn::spawn_into_main_thread(async move {
                 let mux = Mux::get().unwrap();
-                for pane_id in dead_panes.into_iter() {
+                for pane_id in dead_pane_ids.into_iter() {
                     mux.remove_pane(pane_id);
                 }
             })
             .detach();
-            true
-        } else {
-            false
         }
+        dead_panes
     }
 
     pub fn can_close_without_prompting(&self) -> bool {
@@ -1480,6 +1580,40 @@ impl PaneNode {
             PaneNode::Leaf(entry) => Some((entry.window_id, entry.tab_id)),
         }
     }
+
+    pub fn workspace(&self) -> Option<&str> {
+        match self {
+            PaneNode::Empty => None,
+            PaneNode::Split { left, right, .. } => match left.workspace() {
+                Some(res) => Some(res),
+                None => right.workspace(),
+            },
+            PaneNode::Leaf(entry) => Some(&entry.workspace),
+        }
+    }
+
+    pub fn contains_pane(&self, pane_id: PaneId) -> bool {
+        match self {
+            PaneNode::Empty => false,
+            PaneNode::Split { left, right, .. } => {
+                left.contains_pane(pane_id) || right.contains_pane(pane_id)
+            }
+            PaneNode::Leaf(entry) => entry.pane_id == pane_id,
+        }
+    }
+
+    /// Marks `pane_id` as the active pane in this tree, and
+    /// all of the other panes as inactive.
+    pub fn set_active_pane(&mut self, pane_id: PaneId) {
+        match self {
+            PaneNode::Empty => {}
+            PaneNode::Split { left, right, .. } => {
+                left.set_active_pane(pane_id);
+                right.set_active_pane(pane_id);
+            }
+            PaneNode::Leaf(entry) => entry.is_active_pane = entry.pane_id == pane_id,
+        }
+    }
 }
 
 /// This type is used directly by the codec, take care to bump
@@ -1494,6 +1628,7 @@ pub struct PaneEntry {
     pub working_dir: Option<SerdeUrl>,
     pub is_active_pane: bool,
     pub is_zoomed_pane: bool,
+    pub workspace: String,
 }
 
 #[derive(Deserialize, Clone, Serialize, PartialEq, Debug)]
@@ -1784,4 +1919,79 @@ mod test {
         assert_eq!(390, panes[2].pixel_width);
         assert_eq!(600, panes[2].pixel_height);
     }
+
+    #[test]
+    fn remove_pane() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        let horz_size = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new(2, horz_size.second),
+        )
+        .unwrap();
+        assert_eq!(2, tab.iter_panes().len());
+
+        assert!(tab.remove_pane(3).is_none());
+
+        let removed = tab.remove_pane(2).unwrap();
+        assert_eq!(2, removed.pane_id());
+
+        let panes = tab.iter_panes();
+        assert_eq!(1, panes.len());
+        assert_eq!(1, panes[0].pane.pane_id());
+        assert_eq!(80, panes[0].width);
+        assert_eq!(24, panes[0].height);
+
+        let removed = tab.remove_pane(1).unwrap();
+        assert_eq!(1, removed.pane_id());
+        assert!(tab.iter_panes().is_empty());
+        assert!(tab.is_dead());
+    }
+
+    #[test]
+    fn replace_pane() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        let horz_size = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new(2, horz_size.second),
+        )
+        .unwrap();
+
+        assert!(tab.replace_pane(2, &FakePane::new(3, size)).is_none());
+
+        let pane_1 = tab.iter_panes()[0].pane.clone();
+        let pane_2 = tab.iter_panes()[1].pane.clone();
+        assert_eq!(1, tab.replace_pane(0, &pane_2).unwrap().pane_id());
+        assert_eq!(2, tab.replace_pane(1, &pane_1).unwrap().pane_id());
+
+        let panes = tab.iter_panes();
+        assert_eq!(2, panes.len());
+        assert_eq!(2, panes[0].pane.pane_id());
+        assert_eq!(1, panes[1].pane.pane_id());
+        assert_eq!(0, panes[1].top);
+        assert_eq!(horz_size.second.cols as usize, panes[1].width);
+    }
 }
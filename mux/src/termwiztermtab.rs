@@ -4,6 +4,7 @@
 //! session.
 
 use crate::domain::{alloc_domain_id, Domain, DomainId, DomainState};
+use crate::encoding::CharacterEncoding;
 use crate::pane::{alloc_pane_id, Pane, PaneId};
 use crate::renderable::*;
 use crate::tab::{SplitDirection, Tab, TabId};
@@ -51,6 +52,7 @@ impl Domain for TermWizTerminalDomain {
         _size: PtySize,
         _command: Option<CommandBuilder>,
         _command_dir: Option<String>,
+        _encoding: Option<CharacterEncoding>,
         _window: WindowId,
     ) -> anyhow::Result<Rc<Tab>> {
         bail!("cannot spawn tabs in a TermWizTerminalPane");
@@ -59,6 +61,7 @@ impl Domain for TermWizTerminalDomain {
         &self,
         _command: Option<CommandBuilder>,
         _command_dir: Option<String>,
+        _encoding: Option<CharacterEncoding>,
         _tab: TabId,
         _pane_id: PaneId,
         _split_direction: SplitDirection,
@@ -215,7 +218,9 @@ impl Pane for TermWizTerminalPane {
             MouseButton::Right => Buttons::RIGHT,
             MouseButton::WheelUp(_) => Buttons::VERT_WHEEL | Buttons::WHEEL_POSITIVE,
             MouseButton::WheelDown(_) => Buttons::VERT_WHEEL,
-            MouseButton::None => Buttons::NONE,
+            MouseButton::WheelLeft(_) => Buttons::HORZ_WHEEL | Buttons::WHEEL_POSITIVE,
+            MouseButton::WheelRight(_) => Buttons::HORZ_WHEEL,
+            MouseButton::Back | MouseButton::Forward | MouseButton::None => Buttons::NONE,
         };
 
         let event = InputEvent::Mouse(TermWizMouseEvent {
@@ -488,7 +493,7 @@ pub async fn run<
         let domain: Arc<dyn Domain> = Arc::new(TermWizTerminalDomain::new());
         mux.add_domain(&domain);
 
-        let window_id = mux.new_empty_window();
+        let window_id = mux.new_empty_window(None);
 
         let pane = TermWizTerminalPane::new(domain.domain_id(), size, input_tx, render_rx);
         let pane: Rc<dyn Pane> = Rc::new(pane);
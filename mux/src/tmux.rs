@@ -1,4 +1,5 @@
 use crate::domain::{alloc_domain_id, Domain, DomainId, DomainState};
+use crate::encoding::CharacterEncoding;
 use crate::pane::{Pane, PaneId};
 use crate::tab::{SplitDirection, Tab, TabId};
 use crate::window::WindowId;
@@ -209,6 +210,7 @@ impl Domain for TmuxDomain {
         _size: PtySize,
         _command: Option<CommandBuilder>,
         _command_dir: Option<String>,
+        _encoding: Option<CharacterEncoding>,
         _window: WindowId,
     ) -> anyhow::Result<Rc<Tab>> {
         anyhow::bail!("Spawn not yet implemented for TmuxDomain");
@@ -218,6 +220,7 @@ impl Domain for TmuxDomain {
         &self,
         _command: Option<CommandBuilder>,
         _command_dir: Option<String>,
+        _encoding: Option<CharacterEncoding>,
         _tab: TabId,
         _pane_id: PaneId,
         _direction: SplitDirection,
@@ -1,3 +1,4 @@
+use crate::pane::Pane;
 use crate::{Mux, MuxNotification, Tab, TabId};
 use std::rc::Rc;
 use std::sync::Arc;
@@ -12,19 +13,33 @@ pub struct Window {
     active: usize,
     last_active: Option<TabId>,
     clipboard: Option<Arc<dyn Clipboard>>,
+    workspace: String,
 }
 
 impl Window {
-    pub fn new() -> Self {
+    pub fn new(workspace: String) -> Self {
         Self {
             id: WIN_ID.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed),
             tabs: vec![],
             active: 0,
             last_active: None,
             clipboard: None,
+            workspace,
         }
     }
 
+    pub fn get_workspace(&self) -> &str {
+        &self.workspace
+    }
+
+    pub fn set_workspace(&mut self, workspace: &str) {
+        if workspace == self.workspace {
+            return;
+        }
+        self.workspace = workspace.to_string();
+        self.invalidate();
+    }
+
     pub fn set_clipboard(&mut self, clipboard: &Arc<dyn Clipboard>) {
         self.clipboard.replace(Arc::clone(clipboard));
     }
@@ -47,6 +62,14 @@ impl Window {
         }
     }
 
+    /// Assigns the clipboard of this window to `pane`, for a pane
+    /// that has been placed into one of its tabs
+    pub fn assign_clipboard_to_pane(&self, pane: &Rc<dyn Pane>) {
+        if let Some(clip) = self.clipboard.as_ref() {
+            pane.set_clipboard(clip);
+        }
+    }
+
     fn invalidate(&self) {
         let mux = Mux::get().unwrap();
         mux.notify(MuxNotification::WindowInvalidated(self.id));
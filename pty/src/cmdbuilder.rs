@@ -8,7 +8,7 @@ use std::os::windows::ffi::OsStrExt;
 
 /// `CommandBuilder` is used to prepare a command to be spawned into a pty.
 /// The interface is intentionally similar to that of `std::process::Command`.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct CommandBuilder {
     args: Vec<OsString>,
@@ -96,6 +96,17 @@ impl CommandBuilder {
         self.cwd = Some(dir.as_ref().to_owned());
     }
 
+    /// Spawns the command in the working directory of the parent
+    pub fn clear_cwd(&mut self) {
+        self.cwd.take();
+    }
+
+    /// Returns the command and arguments; this is empty for a
+    /// builder created via `new_default_prog`
+    pub fn get_argv(&self) -> &Vec<OsString> {
+        &self.args
+    }
+
     pub fn get_cwd(&self) -> Option<&OsString> {
         self.cwd.as_ref()
     }
@@ -15,87 +15,247 @@ use std::ffi::{OsStr, OsString};
 use std::io::Result as IoResult;
 use std::io::{Read, Write};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+/// How long to wait before the first attempt to reopen a port
+/// that has disconnected
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(250);
+/// The longest that we'll wait between attempts to reopen a port
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(8);
+/// How often to check whether the pane has been closed while we
+/// are waiting for a port to reconnect
+const RECONNECT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The state of a serial connection that is shared between the
+/// reader, the writer and any `SerialHandle`s
+struct PortState {
+    port_name: OsString,
+    settings: PortSettings,
+    /// None while the device is disconnected
+    port: Option<SystemPort>,
+    /// Set when the pane that owns the connection goes away,
+    /// so that the reader stops trying to reconnect
+    closed: bool,
+}
+
+/// Allows the settings of a serial connection to be inspected and
+/// changed while it is in use
+#[derive(Clone)]
+pub struct SerialHandle {
+    state: Arc<Mutex<PortState>>,
+}
+
+impl SerialHandle {
+    pub fn port_name(&self) -> OsString {
+        self.state.lock().unwrap().port_name.clone()
+    }
+
+    pub fn settings(&self) -> PortSettings {
+        self.state.lock().unwrap().settings
+    }
+
+    /// Applies `settings` to the port.  If the port is currently
+    /// disconnected, the settings will be applied when it reconnects.
+    pub fn configure(&self, settings: &PortSettings) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(port) = state.port.as_mut() {
+            port.configure(settings)
+                .with_context(|| format!("configuring serial port {:?}", state.port_name))?;
+        }
+        state.settings = *settings;
+        Ok(())
+    }
+
+    fn open(&self) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let mut port = serial::open(&state.port_name)
+            .with_context(|| format!("openpty on serial port {:?}", state.port_name))?;
+
+        log::debug!("serial settings: {:#?}", state.settings);
+        port.configure(&state.settings)?;
 
-type Handle = Arc<Mutex<SystemPort>>;
+        // The timeout needs to be rather short because, at least on Windows,
+        // a read with a long timeout will block a concurrent write from
+        // happening.  In wezterm we tend to have a thread looping on read
+        // while writes happen occasionally from the gui thread, and if we
+        // make this timeout too long we can block the gui thread.
+        port.set_timeout(Duration::from_millis(50))?;
+
+        state.port.replace(port);
+        Ok(())
+    }
+
+    fn dup_fd(&self) -> anyhow::Result<FileDescriptor> {
+        let state = self.state.lock().unwrap();
+        let port = state
+            .port
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("serial port {:?} is not connected", state.port_name))?;
+        Ok(FileDescriptor::dup(port)?)
+    }
+
+    fn disconnect(&self) {
+        self.state.lock().unwrap().port.take();
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.port.take();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    #[cfg(unix)]
+    fn device_is_present(&self) -> bool {
+        std::path::Path::new(&self.state.lock().unwrap().port_name).exists()
+    }
+
+    #[cfg(windows)]
+    fn device_is_present(&self) -> bool {
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::fileapi::QueryDosDeviceW;
+
+        // The port may have been specified with the `\\.\` device
+        // namespace prefix, which QueryDosDeviceW doesn't accept
+        let port_name = self.port_name();
+        let name = port_name.to_string_lossy();
+        let name = name.trim_start_matches("\\\\.\\");
+        let wide: Vec<u16> = OsStr::new(name)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let mut buf = vec![0u16; 1024];
+        // Returns 0 if there is no such device
+        unsafe { QueryDosDeviceW(wide.as_ptr(), buf.as_mut_ptr(), buf.len() as u32) != 0 }
+    }
+}
+
+/// Returns the delay before reconnection attempt number `attempt`,
+/// which doubles after each failed attempt up to `RECONNECT_MAX_DELAY`
+fn reconnect_delay(attempt: u32) -> Duration {
+    RECONNECT_INITIAL_DELAY
+        .checked_mul(1 << attempt.min(16))
+        .unwrap_or(RECONNECT_MAX_DELAY)
+        .min(RECONNECT_MAX_DELAY)
+}
 
 pub struct SerialTty {
-    port: OsString,
-    baud: BaudRate,
-    char_size: CharSize,
-    parity: Parity,
-    stop_bits: StopBits,
-    flow_control: FlowControl,
+    handle: SerialHandle,
 }
 
 impl SerialTty {
     pub fn new<T: AsRef<OsStr> + ?Sized>(port: &T) -> Self {
         Self {
-            port: port.as_ref().to_owned(),
-            baud: BaudRate::Baud9600,
-            char_size: CharSize::Bits8,
-            parity: Parity::ParityNone,
-            stop_bits: StopBits::Stop1,
-            flow_control: FlowControl::FlowSoftware,
+            handle: SerialHandle {
+                state: Arc::new(Mutex::new(PortState {
+                    port_name: port.as_ref().to_owned(),
+                    settings: PortSettings {
+                        baud_rate: BaudRate::Baud9600,
+                        char_size: CharSize::Bits8,
+                        parity: Parity::ParityNone,
+                        stop_bits: StopBits::Stop1,
+                        flow_control: FlowControl::FlowSoftware,
+                    },
+                    port: None,
+                    closed: false,
+                })),
+            },
         }
     }
 
+    /// Returns a handle that can be used to change the settings
+    /// of the port after it has been opened
+    pub fn handle(&self) -> SerialHandle {
+        self.handle.clone()
+    }
+
+    fn update(&mut self, func: impl FnOnce(&mut PortSettings)) {
+        func(&mut self.handle.state.lock().unwrap().settings);
+    }
+
     pub fn set_baud_rate(&mut self, baud: BaudRate) {
-        self.baud = baud;
+        self.update(|s| s.baud_rate = baud);
     }
 
     pub fn set_char_size(&mut self, char_size: CharSize) {
-        self.char_size = char_size;
+        self.update(|s| s.char_size = char_size);
     }
 
     pub fn set_parity(&mut self, parity: Parity) {
-        self.parity = parity;
+        self.update(|s| s.parity = parity);
     }
 
     pub fn set_stop_bits(&mut self, stop_bits: StopBits) {
-        self.stop_bits = stop_bits;
+        self.update(|s| s.stop_bits = stop_bits);
     }
 
     pub fn set_flow_control(&mut self, flow_control: FlowControl) {
-        self.flow_control = flow_control;
+        self.update(|s| s.flow_control = flow_control);
     }
 }
 
 impl PtySystem for SerialTty {
     fn openpty(&self, _size: PtySize) -> anyhow::Result<PtyPair> {
-        let mut port = serial::open(&self.port)
-            .with_context(|| format!("openpty on serial port {:?}", self.port))?;
-
-        let settings = PortSettings {
-            baud_rate: self.baud,
-            char_size: self.char_size,
-            parity: self.parity,
-            stop_bits: self.stop_bits,
-            flow_control: self.flow_control,
-        };
-        log::debug!("serial settings: {:#?}", settings);
-        port.configure(&settings)?;
-
-        // The timeout needs to be rather short because, at least on Windows,
-        // a read with a long timeout will block a concurrent write from
-        // happening.  In wezterm we tend to have a thread looping on read
-        // while writes happen occasionally from the gui thread, and if we
-        // make this timeout too long we can block the gui thread.
-        port.set_timeout(Duration::from_millis(50))?;
-
-        let port: Handle = Arc::new(Mutex::new(port));
+        self.handle.open()?;
 
         Ok(PtyPair {
             slave: Box::new(Slave {
-                port: Arc::clone(&port),
+                port: self.handle.clone(),
+            }),
+            master: Box::new(Master {
+                port: self.handle.clone(),
             }),
-            master: Box::new(Master { port }),
         })
     }
 }
 
+/// Returns the names of the serial ports that appear to be present
+/// on this system.
+#[cfg(unix)]
+pub fn available_ports() -> Vec<OsString> {
+    const PREFIXES: &[&str] = &["ttyUSB", "ttyACM", "ttyAMA", "cu."];
+    let mut ports = vec![];
+    if let Ok(dir) = std::fs::read_dir("/dev") {
+        for entry in dir.flatten() {
+            let name = entry.file_name();
+            if PREFIXES
+                .iter()
+                .any(|prefix| name.to_string_lossy().starts_with(prefix))
+            {
+                ports.push(entry.path().into_os_string());
+            }
+        }
+    }
+    ports.sort();
+    ports
+}
+
+/// Returns the names of the serial ports that appear to be present
+/// on this system.
+#[cfg(windows)]
+pub fn available_ports() -> Vec<OsString> {
+    use std::os::windows::ffi::OsStringExt;
+    use winapi::um::fileapi::QueryDosDeviceW;
+
+    // QueryDosDeviceW with a NULL device name returns a list of all
+    // of the NUL separated device names, terminated by a double NUL
+    let mut buf = vec![0u16; 65536];
+    let len = unsafe { QueryDosDeviceW(std::ptr::null(), buf.as_mut_ptr(), buf.len() as u32) };
+    let mut ports: Vec<OsString> = buf[..len as usize]
+        .split(|&c| c == 0)
+        .filter(|name| !name.is_empty())
+        .map(OsString::from_wide)
+        .filter(|name| name.to_string_lossy().starts_with("COM"))
+        .collect();
+    ports.sort();
+    ports
+}
+
 struct Slave {
-    port: Handle,
+    port: SerialHandle,
 }
 
 impl SlavePty for Slave {
@@ -105,7 +265,7 @@ impl SlavePty for Slave {
             "can only use default prog commands with serial tty implementations"
         );
         Ok(Box::new(SerialChild {
-            _port: Arc::clone(&self.port),
+            port: self.port.clone(),
         }))
     }
 }
@@ -113,7 +273,7 @@ impl SlavePty for Slave {
 /// There isn't really a child process on the end of the serial connection,
 /// so all of the Child trait impls are NOP
 struct SerialChild {
-    _port: Handle,
+    port: SerialHandle,
 }
 
 // An anemic impl of Debug to satisfy some indirect trait bounds
@@ -123,12 +283,20 @@ impl std::fmt::Debug for SerialChild {
     }
 }
 
+impl Drop for SerialChild {
+    fn drop(&mut self) {
+        self.port.close();
+    }
+}
+
 impl Child for SerialChild {
     fn try_wait(&mut self) -> IoResult<Option<ExitStatus>> {
         Ok(None)
     }
 
     fn kill(&mut self) -> IoResult<()> {
+        // Stops the reader from waiting for the port to reconnect
+        self.port.close();
         Ok(())
     }
 
@@ -150,16 +318,29 @@ impl Child for SerialChild {
 }
 
 struct Master {
-    port: Handle,
+    port: SerialHandle,
+}
+
+fn not_connected() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotConnected,
+        "serial port is disconnected",
+    )
 }
 
 impl Write for Master {
     fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
-        self.port.lock().unwrap().write(buf)
+        match self.port.state.lock().unwrap().port.as_mut() {
+            Some(port) => port.write(buf),
+            None => Err(not_connected()),
+        }
     }
 
     fn flush(&mut self) -> Result<(), std::io::Error> {
-        self.port.lock().unwrap().flush()
+        match self.port.state.lock().unwrap().port.as_mut() {
+            Some(port) => port.flush(),
+            None => Err(not_connected()),
+        }
     }
 }
 
@@ -178,13 +359,18 @@ impl MasterPty for Master {
         // We rely on the fact that SystemPort implements the traits
         // that expose the underlying file descriptor, and that direct
         // reads from that return the raw data that we want
-        let fd = FileDescriptor::dup(&*self.port.lock().unwrap())?;
-        Ok(Box::new(Reader { fd }))
+        let fd = self.port.dup_fd()?;
+        Ok(Box::new(Reader {
+            fd,
+            port: self.port.clone(),
+            pending: vec![],
+        }))
     }
 
     fn try_clone_writer(&self) -> anyhow::Result<Box<dyn std::io::Write + Send>> {
-        let port = Arc::clone(&self.port);
-        Ok(Box::new(Master { port }))
+        Ok(Box::new(Master {
+            port: self.port.clone(),
+        }))
     }
 
     #[cfg(unix)]
@@ -196,26 +382,168 @@ impl MasterPty for Master {
 
 struct Reader {
     fd: FileDescriptor,
+    port: SerialHandle,
+    /// Status messages that are yet to be returned from read
+    pending: Vec<u8>,
+}
+
+impl Reader {
+    /// Called when the device has gone away, which typically happens
+    /// when a USB serial adapter is unplugged or re-enumerates.
+    /// Waits for the device to come back, reopens it with the
+    /// same settings and resumes reading.
+    /// Returns false if the connection was closed while waiting.
+    fn reconnect(&mut self) -> bool {
+        self.port.disconnect();
+        let name = self.port.port_name();
+        log::warn!(
+            "serial port {:?} disconnected; waiting for it to return",
+            name
+        );
+
+        let mut attempt = 0;
+        loop {
+            let deadline = Instant::now() + reconnect_delay(attempt);
+            while Instant::now() < deadline {
+                if self.port.is_closed() {
+                    return false;
+                }
+                std::thread::sleep(RECONNECT_POLL_INTERVAL);
+            }
+            if self.port.is_closed() {
+                return false;
+            }
+            attempt += 1;
+            if !self.port.device_is_present() {
+                continue;
+            }
+            match self.port.open().and_then(|_| self.port.dup_fd()) {
+                Ok(fd) => {
+                    self.fd = fd;
+                    log::warn!("serial port {:?} reconnected", name);
+                    self.pending.extend_from_slice(
+                        format!("\r\n[serial port {:?} reconnected]\r\n", name).as_bytes(),
+                    );
+                    return true;
+                }
+                Err(err) => {
+                    log::trace!("reconnecting to {:?}: {:#}", name, err);
+                }
+            }
+        }
+    }
 }
 
 impl Read for Reader {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
         loop {
+            if !self.pending.is_empty() {
+                let len = buf.len().min(self.pending.len());
+                buf[..len].copy_from_slice(&self.pending[..len]);
+                self.pending.drain(..len);
+                return Ok(len);
+            }
             match self.fd.read(buf) {
                 Ok(size) => {
                     if size == 0 {
                         // Read timeout, but we expect to mostly hit this.
                         // It just means that there was no data available
                         // right now.
+                        // Some systems report a removed device this way
+                        // rather than as an error, so check for that.
+                        if self.port.is_closed() {
+                            return Ok(0);
+                        }
+                        if !self.port.device_is_present() && !self.reconnect() {
+                            return Ok(0);
+                        }
                         continue;
                     }
                     return Ok(size);
                 }
-                Err(e) => {
-                    log::error!("serial read error: {}", e);
-                    return Err(e);
-                }
+                Err(e) => match e.kind() {
+                    std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::WouldBlock
+                    | std::io::ErrorKind::TimedOut => continue,
+                    _ if self.port.is_closed() => return Ok(0),
+                    _ => {
+                        log::error!("serial read error: {}", e);
+                        if !self.reconnect() {
+                            return Ok(0);
+                        }
+                    }
+                },
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        assert_eq!(reconnect_delay(0), RECONNECT_INITIAL_DELAY);
+        assert_eq!(reconnect_delay(1), RECONNECT_INITIAL_DELAY * 2);
+        assert_eq!(reconnect_delay(2), RECONNECT_INITIAL_DELAY * 4);
+        assert_eq!(reconnect_delay(10), RECONNECT_MAX_DELAY);
+        assert_eq!(reconnect_delay(u32::max_value()), RECONNECT_MAX_DELAY);
+    }
+
+    fn missing_port() -> SerialHandle {
+        SerialTty::new("/this/serial/port/does/not/exist").handle()
+    }
+
+    #[test]
+    fn reconnect_stops_when_closed() {
+        let port = missing_port();
+        let closer = port.clone();
+        let pipe = filedescriptor::Pipe::new().unwrap();
+        let mut reader = Reader {
+            fd: pipe.read,
+            port,
+            pending: vec![],
+        };
+
+        let started = Instant::now();
+        let thread = std::thread::spawn(move || reader.reconnect());
+        std::thread::sleep(Duration::from_millis(50));
+        closer.close();
+        assert!(!thread.join().unwrap());
+        assert!(started.elapsed() < RECONNECT_MAX_DELAY);
+    }
+
+    #[test]
+    fn read_after_close() {
+        let port = missing_port();
+        port.close();
+        let pipe = filedescriptor::Pipe::new().unwrap();
+        drop(pipe.write);
+        let mut reader = Reader {
+            fd: pipe.read,
+            port,
+            pending: b"hello".to_vec(),
+        };
+
+        // Pending status messages are returned first
+        let mut buf = [0u8; 3];
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf, b"hel");
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"lo");
+
+        // and then the closed connection reads as EOF rather
+        // than waiting for the port to return
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn child_closes_port() {
+        let port = missing_port();
+        let child = SerialChild { port: port.clone() };
+        assert!(!port.is_closed());
+        drop(child);
+        assert!(port.is_closed());
+    }
+}
@@ -30,6 +30,7 @@ fn main() -> Result<()> {
 
         parser.parse(&buf[0..len], |action| match action {
             Action::Print(c) => print!("{}", c),
+            Action::PrintString(s) => print!("{}", s),
             Action::Control(c) => match c {
                 ControlCode::HorizontalTab
                 | ControlCode::LineFeed
@@ -1,5 +1,75 @@
 use crate::color::ColorPalette;
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+use termwiz::cell::UnicodeVersion;
 use termwiz::hyperlink::Rule as HyperlinkRule;
+use termwiz::surface::CursorShape;
+
+/// The kinds of query that an application can make of the terminal.
+/// The embedding application can choose not to answer some of them;
+/// the replies to some queries reveal information, such as the window
+/// title or the contents of the screen, that could be abused by
+/// untrusted output.
+#[cfg_attr(feature = "use_serde", derive(Deserialize, Serialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TerminalQuery {
+    /// DA1, DA2, DA3, XTVERSION, DECREQTPARM and XTSMGRAPHICS
+    DeviceAttributes,
+    /// DSR operating status and cursor position reports
+    StatusReport,
+    /// DECRQM
+    ModeReport,
+    /// DECRQSS
+    StatusString,
+    /// XTGETTCAP
+    TermCap,
+    /// Reports of the size of the text area and cells
+    WindowSize,
+    /// Reports of the window title
+    WindowTitle,
+    /// OSC 4, 10, 11 and related color queries
+    ColorPalette,
+    /// DECRQCRA
+    ChecksumRectangularArea,
+}
+
+impl TerminalQuery {
+    /// All of the kinds of query
+    pub const ALL: &'static [TerminalQuery] = &[
+        TerminalQuery::DeviceAttributes,
+        TerminalQuery::StatusReport,
+        TerminalQuery::ModeReport,
+        TerminalQuery::StatusString,
+        TerminalQuery::TermCap,
+        TerminalQuery::WindowSize,
+        TerminalQuery::WindowTitle,
+        TerminalQuery::ColorPalette,
+        TerminalQuery::ChecksumRectangularArea,
+    ];
+}
+
+/// Which terminal the emulator presents itself as.  This influences
+/// how the device attribute queries are answered and whether 8-bit
+/// C1 control codes are recognized, which matters when talking to
+/// legacy systems that adjust their output to the terminal type.
+#[cfg_attr(feature = "use_serde", derive(Deserialize, Serialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TerminalCompatibility {
+    /// A DEC VT100 with the advanced video option.  Only the primary
+    /// device attributes are reported and C1 controls are ignored.
+    Vt100,
+    /// A DEC VT220.  The primary and secondary device attributes
+    /// are reported.
+    Vt220,
+    /// A modern xterm compatible terminal; this is the default.
+    Xterm,
+}
+
+impl Default for TerminalCompatibility {
+    fn default() -> Self {
+        Self::Xterm
+    }
+}
 
 /// TerminalConfiguration allows for the embedding application to pass configuration
 /// information to the Terminal.
@@ -73,11 +143,68 @@ pub trait TerminalConfiguration: std::fmt::Debug {
         3
     }
 
+    /// Returns the answerback string that is sent in response to
+    /// an ENQ control code.
     fn enq_answerback(&self) -> String {
         "".to_string()
     }
 
+    /// Returns the terminal type that the emulator presents itself as
+    fn compatibility(&self) -> TerminalCompatibility {
+        TerminalCompatibility::default()
+    }
+
+    /// Returns the parameters to report in response to a DA1 query,
+    /// for example `62;22`, in place of those implied by the
+    /// compatibility setting.
+    fn primary_device_attributes(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the parameters to report in response to a DA2 query,
+    /// for example `1;10;0`, in place of those implied by the
+    /// compatibility setting.
+    fn secondary_device_attributes(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the unit id, as 8 hex digits, to report in response
+    /// to a DA3 query, in place of the default of all zeroes.
+    fn terminal_unit_id(&self) -> Option<String> {
+        None
+    }
+
     fn enable_kitty_graphics(&self) -> bool {
         false
     }
+
+    /// Returns the shape of the cursor when it is set to its default
+    /// style.  This is used to resolve whether the default cursor is
+    /// blinking when an application toggles DEC private mode 12.
+    fn default_cursor_style(&self) -> CursorShape {
+        CursorShape::SteadyBlock
+    }
+
+    /// Returns true if the cursor style that was in effect on the
+    /// primary screen should be restored when an application leaves
+    /// the alternate screen, undoing any changes it made to the style.
+    fn restore_cursor_style_after_alt_screen(&self) -> bool {
+        true
+    }
+
+    /// Returns the unicode version and East Asian ambiguous width
+    /// convention used to compute the width of printed text.
+    /// The version can be changed at runtime by the application
+    /// running in the terminal using the iTerm2 `UnicodeVersion`
+    /// escape sequence.
+    fn unicode_version(&self) -> UnicodeVersion {
+        UnicodeVersion::default()
+    }
+
+    /// Returns true if queries of the kind `query` should be answered.
+    /// Queries that are not allowed are ignored as though the
+    /// terminal didn't recognize them.
+    fn allow_query(&self, _query: TerminalQuery) -> bool {
+        true
+    }
 }
@@ -20,6 +20,12 @@ pub enum MouseButton {
     Right,
     WheelUp(usize),
     WheelDown(usize),
+    WheelLeft(usize),
+    WheelRight(usize),
+    /// The "back" side button
+    Back,
+    /// The "forward" side button
+    Forward,
     None,
 }
 
@@ -37,6 +43,10 @@ pub struct MouseEvent {
     pub kind: MouseEventKind,
     pub x: usize,
     pub y: VisibleRowIndex,
+    /// The position of the mouse within the cell at `x`, `y`,
+    /// in pixels; used by the SGR-Pixels reporting mode
+    pub x_pixel_offset: isize,
+    pub y_pixel_offset: isize,
     pub button: MouseButton,
     pub modifiers: KeyModifiers,
 }
@@ -23,7 +23,7 @@ use std::str;
 use termwiz::surface::SequenceNo;
 
 pub mod config;
-pub use config::TerminalConfiguration;
+pub use config::{TerminalCompatibility, TerminalConfiguration, TerminalQuery};
 
 pub mod input;
 pub use crate::input::*;
@@ -126,6 +126,19 @@ pub struct SemanticZone {
     pub semantic_type: SemanticType,
 }
 
+/// Describes the outcome of a command that was marked up using
+/// OSC 133 semantic prompt sequences
+#[cfg_attr(feature = "use_serde", derive(Deserialize, Serialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CommandResult {
+    /// The row on which the prompt for the command started
+    pub prompt_row: StableRowIndex,
+    /// The exit status, if it was reported
+    pub exit_status: Option<i32>,
+    /// The wall clock time that it took to run
+    pub duration: std::time::Duration,
+}
+
 pub mod color;
 
 #[cfg(test)]
@@ -23,8 +23,11 @@ pub struct Screen {
 
     /// Whenever we scroll a line off the top of the scrollback, we
     /// increment this.  We use this offset to translate between
-    /// PhysRowIndex and StableRowIndex.
-    stable_row_index_offset: usize,
+    /// PhysRowIndex and StableRowIndex.  Rewrapping the scrollback in
+    /// the background changes the number of lines above the rows that
+    /// follow it, so the offset is reduced to compensate, and may then
+    /// be negative.
+    stable_row_index_offset: isize,
 
     /// config so we can access Maximum number of lines of scrollback
     config: Arc<dyn TerminalConfiguration>,
@@ -37,6 +40,84 @@ pub struct Screen {
     pub physical_rows: usize,
     /// Physical, visible width of the screen
     pub physical_cols: usize,
+
+    /// The number of lines at the start of the scrollback that have
+    /// yet to be rewrapped to `physical_cols` following a resize
+    pending_reflow: usize,
+
+    /// The wrap points of the lines that have yet to be rewrapped: the
+    /// rows at which their logical lines begin, in ascending order.
+    /// They are found in a single pass when the first batch is
+    /// rewrapped, so that `reflow_pending` can cut each batch at the
+    /// start of a logical line without searching for it.
+    pending_line_starts: Vec<PhysRowIndex>,
+}
+
+/// How many rows of scrollback above the screen are rewrapped as part
+/// of a resize; the rest are rewrapped later by `reflow_pending`
+const EAGER_REFLOW_ROWS: usize = 1000;
+
+/// Joins the logical lines in `lines` that were wrapped, and splits
+/// them again at `physical_cols`.  If `cursor` holds the position of
+/// the cursor as an x and an index into `lines`, its position in the
+/// result is returned alongside it.
+fn rewrap_lines(
+    lines: impl Iterator<Item = Line>,
+    physical_cols: usize,
+    cursor: Option<(usize, usize)>,
+    seqno: SequenceNo,
+) -> (VecDeque<Line>, Option<(usize, usize)>) {
+    let mut rewrapped = VecDeque::new();
+    let mut logical_line: Option<Line> = None;
+    let mut logical_cursor_x: Option<usize> = None;
+    let mut adjusted_cursor = None;
+
+    for (phys_idx, mut line) in lines.enumerate() {
+        line.invalidate_implicit_hyperlinks(seqno);
+        line.update_last_change_seqno(seqno);
+        let was_wrapped = line.last_cell_was_wrapped();
+
+        if was_wrapped {
+            line.set_last_cell_was_wrapped(false, seqno);
+        }
+
+        let is_cursor_row = cursor.map_or(false, |(_, y)| y == phys_idx);
+        let line = match logical_line.take() {
+            None => {
+                if is_cursor_row {
+                    logical_cursor_x = cursor.map(|(x, _)| x);
+                }
+                line
+            }
+            Some(mut prior) => {
+                if is_cursor_row {
+                    logical_cursor_x = cursor.map(|(x, _)| x + prior.cells().len());
+                }
+                prior.append_line(line, seqno);
+                prior
+            }
+        };
+
+        if was_wrapped {
+            logical_line.replace(line);
+            continue;
+        }
+
+        if let Some(x) = logical_cursor_x.take() {
+            let num_lines = x / physical_cols;
+            let last_x = x - (num_lines * physical_cols);
+            adjusted_cursor = Some((last_x, rewrapped.len() + num_lines));
+        }
+
+        if line.cells().len() <= physical_cols {
+            rewrapped.push_back(line);
+        } else {
+            for line in line.wrap(physical_cols, seqno) {
+                rewrapped.push_back(line);
+            }
+        }
+    }
+    (rewrapped, adjusted_cursor)
 }
 
 fn scrollback_size(config: &Arc<dyn TerminalConfiguration>, allow_scrollback: bool) -> usize {
@@ -73,6 +154,8 @@ impl Screen {
             physical_rows,
             physical_cols,
             stable_row_index_offset: 0,
+            pending_reflow: 0,
+            pending_line_starts: vec![],
         }
     }
 
@@ -88,56 +171,32 @@ impl Screen {
         cursor_y: PhysRowIndex,
         seqno: SequenceNo,
     ) -> (usize, PhysRowIndex) {
-        let mut rewrapped = VecDeque::new();
-        let mut logical_line: Option<Line> = None;
-        let mut logical_cursor_x: Option<usize> = None;
-        let mut adjusted_cursor = (cursor_y, cursor_y);
-
-        for (phys_idx, mut line) in self.lines.drain(..).enumerate() {
-            line.invalidate_implicit_hyperlinks(seqno);
-            line.update_last_change_seqno(seqno);
-            let was_wrapped = line.last_cell_was_wrapped();
-
-            if was_wrapped {
-                line.set_last_cell_was_wrapped(false, seqno);
-            }
-
-            let line = match logical_line.take() {
-                None => {
-                    if phys_idx == cursor_y {
-                        logical_cursor_x = Some(cursor_x);
-                    }
-                    line
-                }
-                Some(mut prior) => {
-                    if phys_idx == cursor_y {
-                        logical_cursor_x = Some(cursor_x + prior.cells().len());
-                    }
-                    prior.append_line(line, seqno);
-                    prior
-                }
-            };
-
-            if was_wrapped {
-                logical_line.replace(line);
-                continue;
-            }
-
-            if let Some(x) = logical_cursor_x.take() {
-                let num_lines = x / physical_cols;
-                let last_x = x - (num_lines * physical_cols);
-                adjusted_cursor = (last_x, rewrapped.len() + num_lines);
-            }
-
-            if line.cells().len() <= physical_cols {
-                rewrapped.push_back(line);
-            } else {
-                for line in line.wrap(physical_cols, seqno) {
-                    rewrapped.push_back(line);
-                }
-            }
+        // Rewrapping a large scrollback takes a while, so only the
+        // screen and the most recent part of the scrollback are done
+        // now.  The split is made at the start of a logical line.
+        let mut split = self
+            .lines
+            .len()
+            .saturating_sub(physical_rows + EAGER_REFLOW_ROWS)
+            .min(cursor_y);
+        while split > 0 && self.lines[split - 1].last_cell_was_wrapped() {
+            split -= 1;
         }
-        self.lines = rewrapped;
+        self.pending_reflow = split;
+        self.pending_line_starts.clear();
+
+        let recent = self.lines.split_off(split);
+        let (rewrapped, cursor) = rewrap_lines(
+            recent.into_iter(),
+            physical_cols,
+            Some((cursor_x, cursor_y - split)),
+            seqno,
+        );
+        self.lines.extend(rewrapped);
+        let adjusted_cursor = match cursor {
+            Some((x, y)) => (x, y + split),
+            None => (cursor_y, cursor_y),
+        };
 
         // If we resized narrower and generated additional lines,
         // we may need to scroll the lines to make room.  However,
@@ -168,7 +227,117 @@ impl Screen {
             return cursor;
         }
         log::debug!("resize screen to {}x{}", physical_cols, physical_rows);
+        let rewrap = physical_cols != self.physical_cols;
+        self.reflow(physical_rows, physical_cols, cursor, rewrap, seqno)
+    }
+
+    /// Returns true if some of the scrollback has yet to be rewrapped
+    /// following a resize
+    pub fn has_pending_reflow(&self) -> bool {
+        self.pending_reflow > 0
+    }
+
+    /// Rewraps about `max_rows` of the lines of scrollback that a resize
+    /// left to be rewrapped later, starting with the most recent.  The
+    /// rows that follow them keep their StableRowIndex, and the oldest
+    /// lines are discarded if the scrollback has grown beyond its limit.
+    /// Returns true if some remain.
+    pub fn reflow_pending(&mut self, max_rows: usize, seqno: SequenceNo) -> bool {
+        if self.pending_reflow == 0 {
+            return false;
+        }
+        if self.pending_line_starts.is_empty() {
+            let lines = &self.lines;
+            self.pending_line_starts = (0..self.pending_reflow)
+                .filter(|&idx| idx == 0 || !lines[idx - 1].last_cell_was_wrapped())
+                .collect();
+        }
+
+        // Start the batch at the last logical line that begins
+        // within `max_rows` of the end of the pending lines
+        let target = self.pending_reflow.saturating_sub(max_rows.max(1));
+        let first_line = self
+            .pending_line_starts
+            .partition_point(|&start| start <= target)
+            .saturating_sub(1);
+        let start = self.pending_line_starts[first_line];
+        self.pending_line_starts.truncate(first_line);
+
+        let mut batch = self.lines.split_off(start);
+        let mut rest = batch.split_off(self.pending_reflow - start);
+        let batch_len = batch.len();
+        let (rewrapped, _) = rewrap_lines(batch.into_iter(), self.physical_cols, None, seqno);
+        self.stable_row_index_offset -= rewrapped.len() as isize - batch_len as isize;
+        self.lines.extend(rewrapped);
+        self.lines.append(&mut rest);
+        self.pending_reflow = start;
+
+        let capacity = self.physical_rows + self.scrollback_size();
+        if self.lines.len() > capacity {
+            let excess = self.lines.len() - capacity;
+            self.lines.drain(0..excess);
+            self.stable_row_index_offset += excess as isize;
+            self.lines_removed_from_pending(excess);
+        }
+        self.has_pending_reflow()
+    }
+
+    /// Accounts for `count` lines having been removed from the
+    /// start of the scrollback
+    fn lines_removed_from_pending(&mut self, count: usize) {
+        self.pending_reflow = self.pending_reflow.saturating_sub(count);
+        if self.pending_reflow == 0 {
+            self.pending_line_starts.clear();
+            return;
+        }
+        if self.pending_line_starts.is_empty() {
+            // They haven't been found yet
+            return;
+        }
+        self.pending_line_starts.retain(|&start| start >= count);
+        for start in &mut self.pending_line_starts {
+            *start -= count;
+        }
+        // The rest of a logical line whose start was removed
+        if self.pending_line_starts.first() != Some(&0) {
+            self.pending_line_starts.insert(0, 0);
+        }
+    }
 
+    /// Recomputes the width of the text on the screen according to
+    /// the rules of `version`.  Lines whose text becomes wider are
+    /// rewrapped when the screen allows scrollback, and truncated
+    /// otherwise.
+    pub fn set_unicode_version(
+        &mut self,
+        version: UnicodeVersion,
+        cursor: CursorPosition,
+        seqno: SequenceNo,
+    ) -> CursorPosition {
+        let physical_cols = self.physical_cols;
+        for line in &mut self.lines {
+            line.set_unicode_version(version, seqno);
+            if !self.allow_scrollback && line.cells().len() > physical_cols {
+                line.resize(physical_cols, seqno);
+            }
+        }
+        self.reflow(
+            self.physical_rows,
+            physical_cols,
+            cursor,
+            self.allow_scrollback,
+            seqno,
+        )
+    }
+
+    fn reflow(
+        &mut self,
+        physical_rows: usize,
+        physical_cols: usize,
+        cursor: CursorPosition,
+        rewrap: bool,
+        seqno: SequenceNo,
+    ) -> CursorPosition {
         // pre-prune blank lines that range from the cursor position to the end of the display;
         // this avoids growing the scrollback size when rapidly switching between normal and
         // maximized states.
@@ -179,7 +348,7 @@ impl Screen {
             }
         }
 
-        let (cursor_x, cursor_y) = if physical_cols != self.physical_cols {
+        let (cursor_x, cursor_y) = if rewrap {
             // Check to see if we need to rewrap lines that were
             // wrapped due to reaching the right hand side of the terminal.
             // For each one that we find, we need to join it with its
@@ -287,6 +456,17 @@ impl Screen {
         lines
     }
 
+    /// Returns the approximate number of bytes used by the lines of
+    /// the screen, including the scrollback
+    pub fn memory_usage(&self) -> usize {
+        self.lines.capacity() * std::mem::size_of::<Line>()
+            + self
+                .lines
+                .iter()
+                .map(|line| line.memory_usage() - std::mem::size_of::<Line>())
+                .sum::<usize>()
+    }
+
     /// Returns a copy of the lines in the screen (including scrollback)
     #[cfg(test)]
     pub fn all_lines(&self) -> Vec<Line> {
@@ -342,6 +522,22 @@ impl Screen {
         line.set_cell(x, cell.clone(), seqno)
     }
 
+    /// Assigns a run of printable ASCII text, one cell per byte,
+    /// starting at `x`.  This is equivalent to calling `set_cell`
+    /// for each byte.
+    pub fn set_ascii_run(
+        &mut self,
+        x: usize,
+        y: VisibleRowIndex,
+        text: &str,
+        attrs: &CellAttributes,
+        seqno: SequenceNo,
+    ) {
+        let line_idx = self.phys_row(y);
+        let line = self.line_mut(line_idx);
+        line.set_ascii_run(x, text, attrs, seqno);
+    }
+
     pub fn cell_mut(&mut self, x: usize, y: VisibleRowIndex) -> Option<&mut Cell> {
         let line_idx = self.phys_row(y);
         let line = self.lines.get_mut(line_idx)?;
@@ -425,12 +621,12 @@ impl Screen {
 
     #[inline]
     pub fn phys_to_stable_row_index(&self, phys: PhysRowIndex) -> StableRowIndex {
-        (phys + self.stable_row_index_offset) as StableRowIndex
+        phys as StableRowIndex + self.stable_row_index_offset
     }
 
     #[inline]
     pub fn stable_row_to_phys(&self, stable: StableRowIndex) -> Option<PhysRowIndex> {
-        let idx = stable - self.stable_row_index_offset as isize;
+        let idx = stable - self.stable_row_index_offset;
         if idx < 0 || idx >= self.lines.len() as isize {
             // Index is no longer valid
             None
@@ -621,7 +817,8 @@ impl Screen {
         }
 
         if remove_idx == 0 {
-            self.stable_row_index_offset += lines_removed;
+            self.stable_row_index_offset += lines_removed as isize;
+            self.lines_removed_from_pending(lines_removed);
         }
 
         if scroll_region.end as usize == self.physical_rows {
@@ -652,6 +849,8 @@ impl Screen {
             self.lines.pop_front();
             self.stable_row_index_offset += 1;
         }
+        self.pending_reflow = 0;
+        self.pending_line_starts.clear();
     }
 
     /// ```text
@@ -50,6 +50,20 @@ pub enum Alert {
     TitleMaybeChanged,
     /// When the color palette has been updated
     PaletteChanged,
+    /// A command that was marked using OSC 133 semantic prompt
+    /// sequences has completed
+    CommandCompleted {
+        /// The text of the command line
+        command: String,
+        /// The exit status, if it was reported
+        exit_status: Option<i32>,
+        /// The wall clock time that it took to run
+        duration: std::time::Duration,
+    },
+    /// The program running in the terminal has asked for an action
+    /// to be performed.  The request is a JSON document, and the
+    /// embedding application decides whether it is permitted.
+    RemoteControl(String),
 }
 
 pub trait AlertHandler {
@@ -124,6 +138,7 @@ impl Terminal {
     /// The output is parsed and applied to the terminal model.
     pub fn advance_bytes<B: AsRef<[u8]>>(&mut self, bytes: B) {
         self.state.increment_seqno();
+        self.state.check_unicode_version_config();
         let bytes = bytes.as_ref();
 
         let mut performer = Performer::new(&mut self.state);
@@ -133,6 +148,7 @@ impl Terminal {
 
     pub fn perform_actions(&mut self, actions: Vec<termwiz::escape::Action>) {
         self.state.increment_seqno();
+        self.state.check_unicode_version_config();
         let mut performer = Performer::new(&mut self.state);
         for action in actions {
             performer.perform(action);
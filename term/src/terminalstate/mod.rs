@@ -9,11 +9,14 @@ use std::collections::HashMap;
 use std::sync::mpsc::{channel, Sender};
 use std::sync::Arc;
 use terminfo::{Database, Value};
+use termwiz::color::{ColorAttribute, ColorSpec};
 use termwiz::escape::csi::{
-    Cursor, CursorStyle, DecPrivateMode, DecPrivateModeCode, Device, Edit, EraseInDisplay,
-    EraseInLine, Mode, Sgr, TabulationClear, TerminalMode, TerminalModeCode, Window, XtSmGraphics,
-    XtSmGraphicsAction, XtSmGraphicsItem, XtSmGraphicsStatus,
+    AttributeChangeExtent, Cursor, CursorStyle, DecPrivateMode, DecPrivateModeCode, Device, Edit,
+    EraseInDisplay, EraseInLine, Mode, RectangularArea, Sgr, SgrCode, TabulationClear,
+    TerminalMode, TerminalModeCode, Window, XtSmGraphics, XtSmGraphicsAction, XtSmGraphicsItem,
+    XtSmGraphicsStatus,
 };
+use termwiz::escape::osc::ITermUnicodeVersionOp;
 use termwiz::escape::{OneBased, OperatingSystemCommand, CSI};
 use termwiz::image::ImageData;
 use termwiz::surface::{CursorShape, CursorVisibility, SequenceNo};
@@ -26,8 +29,10 @@ mod kitty;
 mod mouse;
 pub(crate) mod performer;
 mod sixel;
+mod softfont;
 use crate::terminalstate::image::*;
 use crate::terminalstate::kitty::*;
+use crate::terminalstate::softfont::LoadedSoftFont;
 
 lazy_static::lazy_static! {
     static ref DB: Database = {
@@ -46,6 +51,9 @@ pub(crate) enum CharSet {
     Ascii,
     Uk,
     DecLineDrawing,
+    /// A soft font loaded by DECDLD, identified by the final
+    /// byte of its designator
+    Soft(u8),
 }
 
 impl TabStop {
@@ -194,6 +202,21 @@ impl ScreenOrAlt {
         }
     }
 
+    pub fn set_unicode_version(
+        &mut self,
+        version: UnicodeVersion,
+        cursor: CursorPosition,
+        seqno: SequenceNo,
+    ) -> CursorPosition {
+        let cursor_main = self.screen.set_unicode_version(version, cursor, seqno);
+        let cursor_alt = self.alt_screen.set_unicode_version(version, cursor, seqno);
+        if self.alt_screen_is_active {
+            cursor_alt
+        } else {
+            cursor_main
+        }
+    }
+
     pub fn activate_alt_screen(&mut self, seqno: SequenceNo) {
         self.alt_screen_is_active = true;
         self.dirty_top_phys_rows(seqno);
@@ -221,6 +244,10 @@ impl ScreenOrAlt {
         self.alt_screen_is_active
     }
 
+    fn memory_usage(&self) -> usize {
+        self.screen.memory_usage() + self.alt_screen.memory_usage()
+    }
+
     pub fn saved_cursor(&mut self) -> &mut Option<SavedCursor> {
         if self.alt_screen_is_active {
             &mut self.alt_saved_cursor
@@ -269,6 +296,10 @@ pub struct TerminalState {
     left_and_right_margins: Range<usize>,
     left_and_right_margin_mode: bool,
 
+    /// Whether DECCARA and DECRARA apply to a rectangle or to a
+    /// stream of characters, as selected by DECSACE
+    attribute_change_extent: AttributeChangeExtent,
+
     /// When set, modifies the sequence of bytes sent for keys
     /// designated as cursor keys.  This includes various navigation
     /// keys.  The code in key_down() is responsible for interpreting this.
@@ -295,19 +326,32 @@ pub struct TerminalState {
     /// Movement events enabled
     any_event_mouse: bool,
     focus_tracking: bool,
+    /// Whether the terminal was most recently advised that it has
+    /// the focus, so that focus reports are only sent on a change
+    has_focus: bool,
     /// SGR style mouse tracking and reporting is enabled
     sgr_mouse: bool,
+    /// SGR style mouse reporting using pixel coordinates is enabled
+    sgr_pixels_mouse: bool,
     mouse_tracking: bool,
     /// Button events enabled
     button_event_mouse: bool,
     current_mouse_buttons: Vec<MouseButton>,
     last_mouse_move: Option<MouseEvent>,
     cursor_visible: bool,
+    /// The cursor shape that was in effect on the primary screen
+    /// when the alternate screen was activated
+    primary_cursor_shape: Option<CursorShape>,
 
     /// Support for US, UK, and DEC Special Graphics
     g0_charset: CharSet,
     g1_charset: CharSet,
     shift_out: bool,
+    /// When set, GR is mapped to G1 by LS1R
+    gr_is_g1: bool,
+    /// Soft fonts loaded by DECDLD, keyed by the final byte of
+    /// their designator
+    soft_fonts: HashMap<u8, LoadedSoftFont>,
 
     newline_mode: bool,
 
@@ -339,8 +383,32 @@ pub struct TerminalState {
 
     user_vars: HashMap<String, String>,
 
+    /// Where the most recent OSC 133 prompt began
+    prompt_start_row: Option<StableRowIndex>,
+    /// The command that is running, as reported by OSC 133
+    running_command: Option<RunningCommand>,
+    /// The outcomes of the commands that have completed
+    command_results: Vec<CommandResult>,
+
     kitty_img: KittyImageState,
     seqno: SequenceNo,
+
+    /// The rules used to compute the width of printed text
+    unicode_version: UnicodeVersion,
+    /// The unicode version from the configuration, and the
+    /// generation of the configuration that it was taken from
+    configured_unicode_version: UnicodeVersion,
+    config_generation: usize,
+    /// The versions saved by `UnicodeVersion=push`, along with
+    /// their optional labels
+    unicode_version_stack: Vec<(Option<String>, UnicodeVersion)>,
+}
+
+#[derive(Debug)]
+struct RunningCommand {
+    command: String,
+    prompt_row: StableRowIndex,
+    started: std::time::Instant,
 }
 
 fn default_color_map() -> HashMap<u16, RgbColor> {
@@ -413,6 +481,8 @@ impl TerminalState {
         let screen = ScreenOrAlt::new(size.physical_rows, size.physical_cols, &config);
 
         let color_map = default_color_map();
+        let unicode_version = config.unicode_version();
+        let config_generation = config.generation();
 
         TerminalState {
             config,
@@ -422,6 +492,7 @@ impl TerminalState {
             top_and_bottom_margins: 0..size.physical_rows as VisibleRowIndex,
             left_and_right_margins: 0..size.physical_cols,
             left_and_right_margin_mode: false,
+            attribute_change_extent: AttributeChangeExtent::Stream,
             wrap_next: false,
             // We default auto wrap to true even though the default for
             // a dec terminal is false, because it is more useful this way.
@@ -438,16 +509,21 @@ impl TerminalState {
             application_keypad: false,
             bracketed_paste: false,
             focus_tracking: false,
+            has_focus: true,
             sgr_mouse: false,
+            sgr_pixels_mouse: false,
             sixel_scrolls_right: false,
             any_event_mouse: false,
             button_event_mouse: false,
             mouse_tracking: false,
             last_mouse_move: None,
             cursor_visible: true,
+            primary_cursor_shape: None,
             g0_charset: CharSet::Ascii,
             g1_charset: CharSet::DecLineDrawing,
             shift_out: false,
+            gr_is_g1: false,
+            soft_fonts: HashMap::new(),
             newline_mode: false,
             current_mouse_buttons: vec![],
             tabs: TabStop::new(size.physical_cols, 8),
@@ -465,8 +541,15 @@ impl TerminalState {
             writer: Box::new(std::io::BufWriter::new(writer)),
             image_cache: lru::LruCache::new(16),
             user_vars: HashMap::new(),
+            prompt_start_row: None,
+            running_command: None,
+            command_results: vec![],
             kitty_img: Default::default(),
             seqno: 0,
+            unicode_version,
+            configured_unicode_version: unicode_version,
+            config_generation,
+            unicode_version_stack: vec![],
         }
     }
 
@@ -478,8 +561,107 @@ impl TerminalState {
         self.seqno += 1;
     }
 
+    /// Returns true if some of the scrollback has yet to be rewrapped
+    /// following a resize
+    pub fn has_pending_reflow(&self) -> bool {
+        self.screen.screen.has_pending_reflow()
+    }
+
+    /// Rewraps about `max_rows` more of the scrollback that a resize
+    /// left to be rewrapped later.  Returns true if some remains, in
+    /// which case this should be called again.
+    pub fn reflow_pending(&mut self, max_rows: usize) -> bool {
+        self.increment_seqno();
+        let seqno = self.seqno;
+        self.screen.screen.reflow_pending(max_rows, seqno)
+    }
+
     pub fn set_config(&mut self, config: Arc<dyn TerminalConfiguration>) {
         self.config = config;
+        self.apply_unicode_version_config();
+    }
+
+    /// Returns true if the configuration allows us to answer
+    /// queries of the kind `query`
+    fn query_allowed(&self, query: TerminalQuery) -> bool {
+        let allowed = self.config.allow_query(query);
+        if !allowed {
+            log::trace!("ignoring {:?} query as it is not allowed", query);
+        }
+        allowed
+    }
+
+    /// Picks up changes to the configured unicode version following
+    /// a configuration reload
+    pub(crate) fn check_unicode_version_config(&mut self) {
+        let generation = self.config.generation();
+        if generation != self.config_generation {
+            self.apply_unicode_version_config();
+        }
+    }
+
+    fn apply_unicode_version_config(&mut self) {
+        self.config_generation = self.config.generation();
+        let new_version = self.config.unicode_version();
+        let old_version = std::mem::replace(&mut self.configured_unicode_version, new_version);
+        if old_version == new_version {
+            return;
+        }
+
+        // Respect a version that was selected by the application
+        // using an escape sequence
+        if self.unicode_version_stack.is_empty()
+            && self.unicode_version.version == old_version.version
+        {
+            self.unicode_version.version = new_version.version;
+        }
+        self.unicode_version.ambiguous_are_wide = new_version.ambiguous_are_wide;
+        for (_, version) in &mut self.unicode_version_stack {
+            version.ambiguous_are_wide = new_version.ambiguous_are_wide;
+        }
+
+        if old_version.ambiguous_are_wide != new_version.ambiguous_are_wide {
+            // The width of text that is already on the screen has changed
+            let seqno = self.seqno;
+            let version = self.unicode_version;
+            let cursor = self.cursor;
+            let adjusted_cursor = self.screen.set_unicode_version(version, cursor, seqno);
+            self.set_cursor_pos(
+                &Position::Absolute(adjusted_cursor.x as i64),
+                &Position::Absolute(adjusted_cursor.y),
+            );
+        }
+    }
+
+    /// Returns the rules that are currently used to compute
+    /// the width of printed text
+    pub fn unicode_version(&self) -> UnicodeVersion {
+        self.unicode_version
+    }
+
+    fn apply_unicode_version_op(&mut self, op: ITermUnicodeVersionOp) {
+        match op {
+            ITermUnicodeVersionOp::Set(version) => {
+                self.unicode_version.version = version;
+            }
+            ITermUnicodeVersionOp::Push(label) => {
+                self.unicode_version_stack
+                    .push((label, self.unicode_version));
+            }
+            ITermUnicodeVersionOp::Pop(None) => {
+                if let Some((_, version)) = self.unicode_version_stack.pop() {
+                    self.unicode_version = version;
+                }
+            }
+            ITermUnicodeVersionOp::Pop(Some(label)) => {
+                while let Some((entry_label, version)) = self.unicode_version_stack.pop() {
+                    self.unicode_version = version;
+                    if entry_label.as_ref() == Some(&label) {
+                        break;
+                    }
+                }
+            }
+        }
     }
 
     pub fn get_config(&self) -> Arc<dyn TerminalConfiguration> {
@@ -498,6 +680,104 @@ impl TerminalState {
         self.alert_handler.replace(handler);
     }
 
+    /// Records the start of a prompt.  A command that was still running
+    /// is considered to have completed, with an unknown status.
+    fn semantic_prompt_started(&mut self) {
+        self.command_completed(None);
+        let row = self.screen().visible_row_to_stable_row(self.cursor.y);
+        self.prompt_start_row.replace(row);
+    }
+
+    /// Records that the input that follows the most recent prompt
+    /// is now running as a command
+    fn semantic_command_started(&mut self) {
+        let command = self.command_input_text();
+        let prompt_row = self
+            .prompt_start_row
+            .unwrap_or_else(|| self.screen().visible_row_to_stable_row(self.cursor.y));
+        self.running_command.replace(RunningCommand {
+            command,
+            prompt_row,
+            started: std::time::Instant::now(),
+        });
+    }
+
+    /// Reports the completion of the running command, if any
+    fn command_completed(&mut self, exit_status: Option<i32>) {
+        if let Some(running) = self.running_command.take() {
+            if running.command.is_empty() {
+                return;
+            }
+            let duration = running.started.elapsed();
+            self.record_command_result(CommandResult {
+                prompt_row: running.prompt_row,
+                exit_status,
+                duration,
+            });
+            if let Some(handler) = self.alert_handler.as_mut() {
+                handler.alert(Alert::CommandCompleted {
+                    command: running.command,
+                    exit_status,
+                    duration,
+                });
+            }
+        }
+    }
+
+    /// Remembers `result`, forgetting the results for rows that
+    /// have since scrolled out of the scrollback
+    fn record_command_result(&mut self, result: CommandResult) {
+        let first_row = self.screen().phys_to_stable_row_index(0);
+        self.command_results
+            .retain(|r| r.prompt_row >= first_row && r.prompt_row != result.prompt_row);
+        self.command_results.push(result);
+    }
+
+    /// Returns the outcomes of the completed commands whose prompts
+    /// are still present in the scrollback, ordered by row
+    pub fn get_command_results(&self) -> Vec<CommandResult> {
+        let first_row = self.screen().phys_to_stable_row_index(0);
+        let mut results: Vec<CommandResult> = self
+            .command_results
+            .iter()
+            .filter(|r| r.prompt_row >= first_row)
+            .cloned()
+            .collect();
+        results.sort_by_key(|r| r.prompt_row);
+        results
+    }
+
+    /// Extracts the text that was marked as input between the start
+    /// of the most recent prompt and the cursor
+    fn command_input_text(&self) -> String {
+        let screen = self.screen();
+        let end = screen.phys_row(self.cursor.y);
+        let start = self
+            .prompt_start_row
+            .and_then(|row| screen.stable_row_to_phys(row))
+            .unwrap_or(end);
+
+        let mut text = String::new();
+        for idx in start..=end.min(screen.lines.len().saturating_sub(1)) {
+            let line = &screen.lines[idx];
+            let mut line_text = String::new();
+            for (_, cell) in line.visible_cells() {
+                if cell.attrs().semantic_type() == SemanticType::Input {
+                    line_text.push_str(cell.str());
+                }
+            }
+            let line_text = line_text.trim_end();
+            if line_text.is_empty() {
+                continue;
+            }
+            if !text.is_empty() && !screen.lines[idx - 1].last_cell_was_wrapped() {
+                text.push('\n');
+            }
+            text.push_str(line_text);
+        }
+        text.trim().to_string()
+    }
+
     /// Returns the title text associated with the terminal session.
     /// The title can be changed by the application using a number
     /// of escape sequences:
@@ -562,6 +842,12 @@ impl TerminalState {
         &mut self.screen
     }
 
+    /// Returns the approximate number of bytes used by the primary
+    /// screen, its scrollback and the alternate screen
+    pub fn memory_usage(&self) -> usize {
+        self.screen.memory_usage()
+    }
+
     fn set_clipboard_contents(
         &self,
         selection: ClipboardSelection,
@@ -613,6 +899,11 @@ impl TerminalState {
 
     /// Advise the terminal about a change in its focus state
     pub fn focus_changed(&mut self, focused: bool) {
+        if focused == self.has_focus {
+            return;
+        }
+        self.has_focus = focused;
+
         if !focused {
             // notify app of release of buttons
             let buttons = self.current_mouse_buttons.clone();
@@ -623,6 +914,8 @@ impl TerminalState {
                     modifiers: KeyModifiers::NONE,
                     x: 0,
                     y: 0,
+                    x_pixel_offset: 0,
+                    y_pixel_offset: 0,
                 })
                 .ok();
             }
@@ -946,8 +1239,108 @@ impl TerminalState {
         });
     }
 
+    /// Returns the current value of the setting named by a DECRQSS
+    /// request, formatted as the control function that would select
+    /// it, or None if the setting isn't one that we report.
+    fn decrqss_response(&self, setting: &[u8]) -> Option<String> {
+        match setting {
+            // DECSCL - select conformance level
+            b"\"p" => Some("65;1\"p".to_string()),
+            // DECSTBM - top and bottom margins
+            b"r" => Some(format!(
+                "{};{}r",
+                self.top_and_bottom_margins.start + 1,
+                self.top_and_bottom_margins.end
+            )),
+            // DECSLRM - left and right margins
+            b"s" => Some(format!(
+                "{};{}s",
+                self.left_and_right_margins.start + 1,
+                self.left_and_right_margins.end
+            )),
+            // SGR - graphic rendition
+            b"m" => Some(format!("{}m", self.sgr_report())),
+            // DECSCUSR - cursor style
+            b" q" => {
+                let style = match self.cursor.shape {
+                    CursorShape::Default => CursorStyle::Default,
+                    CursorShape::BlinkingBlock => CursorStyle::BlinkingBlock,
+                    CursorShape::SteadyBlock => CursorStyle::SteadyBlock,
+                    CursorShape::BlinkingUnderline => CursorStyle::BlinkingUnderline,
+                    CursorShape::SteadyUnderline => CursorStyle::SteadyUnderline,
+                    CursorShape::BlinkingBar => CursorStyle::BlinkingBar,
+                    CursorShape::SteadyBar => CursorStyle::SteadyBar,
+                };
+                Some(format!("{} q", style as u8))
+            }
+            // DECSACE - select attribute change extent
+            b"*x" => Some(format!("{}*x", self.attribute_change_extent as u8)),
+            // DECSLPP - lines per page
+            b"t" => Some(format!("{}t", self.screen().physical_rows)),
+            // DECSCPP - columns per page
+            b"$|" => Some(format!("{}$|", self.screen().physical_cols)),
+            // DECSNLS - lines per screen
+            b"*|" => Some(format!("{}*|", self.screen().physical_rows)),
+            _ => None,
+        }
+    }
+
+    /// Returns the SGR parameters that select the current pen
+    fn sgr_report(&self) -> String {
+        fn color_spec(color: ColorAttribute) -> Option<ColorSpec> {
+            match color {
+                ColorAttribute::TrueColorWithPaletteFallback(c, _)
+                | ColorAttribute::TrueColorWithDefaultFallback(c) => Some(ColorSpec::TrueColor(c)),
+                ColorAttribute::PaletteIndex(idx) => Some(ColorSpec::PaletteIndex(idx)),
+                ColorAttribute::Default => None,
+            }
+        }
+
+        let pen = &self.pen;
+        let mut sgr = vec![Sgr::Reset];
+        if pen.intensity() != Intensity::Normal {
+            sgr.push(Sgr::Intensity(pen.intensity()));
+        }
+        if pen.underline() != Underline::None {
+            sgr.push(Sgr::Underline(pen.underline()));
+        }
+        if pen.blink() != Blink::None {
+            sgr.push(Sgr::Blink(pen.blink()));
+        }
+        if pen.italic() {
+            sgr.push(Sgr::Italic(true));
+        }
+        if pen.reverse() {
+            sgr.push(Sgr::Inverse(true));
+        }
+        if pen.invisible() {
+            sgr.push(Sgr::Invisible(true));
+        }
+        if pen.strikethrough() {
+            sgr.push(Sgr::StrikeThrough(true));
+        }
+        if pen.overline() {
+            sgr.push(Sgr::Overline(true));
+        }
+        if let Some(spec) = color_spec(pen.foreground()) {
+            sgr.push(Sgr::Foreground(spec));
+        }
+        if let Some(spec) = color_spec(pen.background()) {
+            sgr.push(Sgr::Background(spec));
+        }
+
+        // Each Sgr is rendered with its own trailing `m`
+        sgr.iter()
+            .map(|s| s.to_string().trim_end_matches('m').to_string())
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+
     /// <https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h4-Device-Control-functions:DCS-plus-q-Pt-ST.F95>
     fn xt_get_tcap(&mut self, names: Vec<String>) {
+        if !self.query_allowed(TerminalQuery::TermCap) {
+            return;
+        }
         let mut res = "\x1bP".to_string();
 
         for (i, name) in names.iter().enumerate() {
@@ -956,59 +1349,62 @@ impl TerminalState {
             }
 
             let encoded_name = hex::encode_upper(&name);
-            match name.as_str() {
-                "TN" | "name" => {
-                    res.push_str("1+r");
-                    res.push_str(&encoded_name);
-                    res.push('=');
-
-                    let encoded_val = hex::encode_upper(&self.term_program);
-                    res.push_str(&encoded_val);
-                }
-
-                "Co" | "colors" => {
+            let value = match name.as_str() {
+                "TN" | "name" => Some(Value::String(self.term_program.as_bytes().to_vec())),
+                "Co" | "colors" => Some(Value::Number(256)),
+                "RGB" => Some(Value::String(b"8/8/8".to_vec())),
+                _ => DB.raw(name).cloned(),
+            };
+
+            // Both the name and the value are hex encoded in the reply.
+            // Boolean capabilities are reported without a value.
+            match value {
+                Some(value) => {
                     res.push_str("1+r");
                     res.push_str(&encoded_name);
-                    res.push('=');
-                    res.push_str(&256.to_string());
-                }
-
-                "RGB" => {
-                    res.push_str("1+r");
-                    res.push_str(&encoded_name);
-                    res.push('=');
-                    res.push_str("8/8/8");
-                }
-
-                _ => {
-                    if let Some(value) = DB.raw(name) {
-                        res.push_str("1+r");
-                        res.push_str(&encoded_name);
-                        res.push('=');
-                        match value {
-                            Value::True => res.push('1'),
-                            Value::Number(n) => res.push_str(&n.to_string()),
-                            Value::String(s) => {
-                                for &b in s {
-                                    res.push(b as char);
-                                }
-                            }
+                    match value {
+                        Value::True => {}
+                        Value::Number(n) => {
+                            res.push('=');
+                            res.push_str(&hex::encode_upper(n.to_string()));
+                        }
+                        Value::String(s) => {
+                            res.push('=');
+                            res.push_str(&hex::encode_upper(s));
                         }
-                    } else {
-                        log::trace!("xt_get_tcap: unknown name {}", name);
-                        res.push_str("0+r");
-                        res.push_str(&encoded_name);
                     }
                 }
+                None => {
+                    log::trace!("xt_get_tcap: unknown name {}", name);
+                    res.push_str("0+r");
+                    res.push_str(&encoded_name);
+                }
             }
         }
 
         res.push_str("\x1b\\");
         log::trace!("responding with {}", res.escape_debug());
         self.writer.write_all(res.as_bytes()).ok();
+        self.writer.flush().ok();
     }
 
     fn perform_device(&mut self, dev: Device) {
+        let query = match &dev {
+            Device::RequestPrimaryDeviceAttributes
+            | Device::RequestSecondaryDeviceAttributes
+            | Device::RequestTertiaryDeviceAttributes
+            | Device::RequestTerminalNameAndVersion
+            | Device::RequestTerminalParameters(_)
+            | Device::XtSmGraphics(_) => Some(TerminalQuery::DeviceAttributes),
+            Device::StatusReport => Some(TerminalQuery::StatusReport),
+            _ => None,
+        };
+        if let Some(query) = query {
+            if !self.query_allowed(query) {
+                return;
+            }
+        }
+
         match dev {
             Device::DeviceAttributes(a) => log::warn!("unhandled: {:?}", a),
             Device::SoftReset => {
@@ -1023,6 +1419,7 @@ impl TerminalState {
                 self.application_keypad = false;
                 self.top_and_bottom_margins = 0..self.screen().physical_rows as i64;
                 self.left_and_right_margins = 0..self.screen().physical_cols;
+                self.attribute_change_extent = AttributeChangeExtent::Stream;
                 self.screen.activate_alt_screen(self.seqno);
                 self.screen.saved_cursor().take();
                 self.screen.activate_primary_screen(self.seqno);
@@ -1033,25 +1430,62 @@ impl TerminalState {
                 self.reverse_video_mode = false;
             }
             Device::RequestPrimaryDeviceAttributes => {
-                let mut ident = "\x1b[?65".to_string(); // Vt500
-                ident.push_str(";4"); // Sixel graphics
-                ident.push_str(";6"); // Selective erase
-                ident.push_str(";18"); // windowing extensions
-                ident.push_str(";22"); // ANSI color, vt525
-                ident.push('c');
+                let ident = match self.config.primary_device_attributes() {
+                    Some(params) => format!("\x1b[?{}c", params),
+                    None => match self.config.compatibility() {
+                        // VT100 with advanced video option
+                        TerminalCompatibility::Vt100 => "\x1b[?1;2c".to_string(),
+                        // VT220 with selective erase
+                        TerminalCompatibility::Vt220 => "\x1b[?62;6c".to_string(),
+                        TerminalCompatibility::Xterm => {
+                            let mut ident = "\x1b[?65".to_string(); // Vt500
+                            ident.push_str(";4"); // Sixel graphics
+                            ident.push_str(";6"); // Selective erase
+                            ident.push_str(";18"); // windowing extensions
+                            ident.push_str(";22"); // ANSI color, vt525
+                            ident.push_str(";28"); // Rectangular editing
+                            ident.push('c');
+                            ident
+                        }
+                    },
+                };
 
                 self.writer.write(ident.as_bytes()).ok();
                 self.writer.flush().ok();
             }
             Device::RequestSecondaryDeviceAttributes => {
-                self.writer.write(b"\x1b[>0;0;0c").ok();
+                let ident = match self.config.secondary_device_attributes() {
+                    Some(params) => format!("\x1b[>{}c", params),
+                    None => match self.config.compatibility() {
+                        // The VT100 didn't respond to DA2
+                        TerminalCompatibility::Vt100 => return,
+                        TerminalCompatibility::Vt220 => "\x1b[>1;10;0c".to_string(),
+                        TerminalCompatibility::Xterm => "\x1b[>0;0;0c".to_string(),
+                    },
+                };
+                self.writer.write(ident.as_bytes()).ok();
                 self.writer.flush().ok();
             }
             Device::RequestTertiaryDeviceAttributes => {
-                self.writer.write(b"\x1b[=00000000").ok();
+                let unit_id = match self.config.terminal_unit_id() {
+                    Some(unit_id) => unit_id,
+                    None => match self.config.compatibility() {
+                        // DECRPTUI was introduced with the VT400 series
+                        TerminalCompatibility::Vt100 | TerminalCompatibility::Vt220 => return,
+                        TerminalCompatibility::Xterm => "00000000".to_string(),
+                    },
+                };
+                self.writer.write(DCS.as_bytes()).ok();
+                self.writer.write(format!("!|{}", unit_id).as_bytes()).ok();
                 self.writer.write(ST.as_bytes()).ok();
                 self.writer.flush().ok();
             }
+            Device::RequestTerminalNameAndVersion | Device::XtSmGraphics(_)
+                if self.config.compatibility() != TerminalCompatibility::Xterm =>
+            {
+                // These are xterm extensions that the DEC terminals
+                // didn't recognize
+            }
             Device::RequestTerminalNameAndVersion => {
                 self.writer.write(DCS.as_bytes()).ok();
                 self.writer
@@ -1116,6 +1550,9 @@ impl TerminalState {
     }
 
     fn decqrm_response(&mut self, mode: Mode, mut recognized: bool, enabled: bool) {
+        if !self.query_allowed(TerminalQuery::ModeReport) {
+            return;
+        }
         let (is_dec, number) = match &mode {
             Mode::QueryDecPrivateMode(DecPrivateMode::Code(code)) => (true, code.to_u16().unwrap()),
             Mode::QueryDecPrivateMode(DecPrivateMode::Unspecified(code)) => {
@@ -1150,14 +1587,19 @@ impl TerminalState {
         match mode {
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::StartBlinkingCursor,
-            ))
-            | Mode::ResetDecPrivateMode(DecPrivateMode::Code(
+            )) => {
+                self.cursor.shape = self.effective_cursor_shape().with_blinking(true);
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::StartBlinkingCursor,
-            )) => {}
+            )) => {
+                self.cursor.shape = self.effective_cursor_shape().with_blinking(false);
+            }
             Mode::QueryDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::StartBlinkingCursor,
             )) => {
-                self.decqrm_response(mode, true, false);
+                let blinking = self.effective_cursor_shape().is_blinking();
+                self.decqrm_response(mode, true, blinking);
             }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::AutoRepeat))
@@ -1343,6 +1785,7 @@ impl TerminalState {
                 DecPrivateModeCode::EnableAlternateScreen,
             )) => {
                 if !self.screen.is_alt_screen_active() {
+                    self.primary_cursor_shape = Some(self.cursor.shape);
                     self.screen.activate_alt_screen(self.seqno);
                     self.pen = CellAttributes::default();
                 }
@@ -1354,6 +1797,7 @@ impl TerminalState {
                     self.pen = CellAttributes::default();
                     self.erase_in_display(EraseInDisplay::EraseDisplay);
                     self.screen.activate_primary_screen(self.seqno);
+                    self.restore_primary_cursor_shape(self.cursor.shape);
                 }
             }
 
@@ -1363,6 +1807,7 @@ impl TerminalState {
                 if self.screen.is_alt_screen_active() {
                     self.screen.activate_primary_screen(self.seqno);
                     self.pen = CellAttributes::default();
+                    self.restore_primary_cursor_shape(self.cursor.shape);
                 }
             }
 
@@ -1489,6 +1934,18 @@ impl TerminalState {
                 self.decqrm_response(mode, true, self.sgr_mouse);
             }
 
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SGRPixelsMouse)) => {
+                self.sgr_pixels_mouse = true;
+                self.last_mouse_move.take();
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SGRPixelsMouse)) => {
+                self.sgr_pixels_mouse = false;
+                self.last_mouse_move.take();
+            }
+            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SGRPixelsMouse)) => {
+                self.decqrm_response(mode, true, self.sgr_pixels_mouse);
+            }
+
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::SixelScrollsRight,
             )) => {
@@ -1510,6 +1967,7 @@ impl TerminalState {
             )) => {
                 if !self.screen.is_alt_screen_active() {
                     self.dec_save_cursor();
+                    self.primary_cursor_shape = Some(self.cursor.shape);
                     self.screen.activate_alt_screen(self.seqno);
                     self.set_cursor_pos(&Position::Absolute(0), &Position::Absolute(0));
                     self.pen = CellAttributes::default();
@@ -1520,8 +1978,10 @@ impl TerminalState {
                 DecPrivateModeCode::ClearAndEnableAlternateScreen,
             )) => {
                 if self.screen.is_alt_screen_active() {
+                    let alt_shape = self.cursor.shape;
                     self.screen.activate_primary_screen(self.seqno);
                     self.dec_restore_cursor();
+                    self.restore_primary_cursor_shape(alt_shape);
                 }
             }
             Mode::SaveDecPrivateMode(DecPrivateMode::Code(n))
@@ -1596,6 +2056,20 @@ impl TerminalState {
     }
 
     fn perform_csi_window(&mut self, window: Window) {
+        let query = match &window {
+            Window::ReportTextAreaSizeCells
+            | Window::ReportCellSizePixels
+            | Window::ReportTextAreaSizePixels => Some(TerminalQuery::WindowSize),
+            Window::ReportWindowTitle => Some(TerminalQuery::WindowTitle),
+            Window::ChecksumRectangularArea { .. } => Some(TerminalQuery::ChecksumRectangularArea),
+            _ => None,
+        };
+        if let Some(query) = query {
+            if !self.query_allowed(query) {
+                return;
+            }
+        }
+
         match window {
             Window::ReportTextAreaSizeCells => {
                 let screen = self.screen();
@@ -1855,6 +2329,263 @@ impl TerminalState {
                 self.cursor.x = x;
                 self.cursor.y = y;
             }
+            Edit::FillRectangularArea { ch, area } => {
+                // Only printable characters may be used to fill the area
+                if let Some(c) = char::from_u32(ch).filter(|c| !c.is_control()) {
+                    let cell = Cell::new(c, self.pen.clone_sgr_only());
+                    self.fill_rectangular_area(&area, &cell);
+                }
+            }
+            Edit::EraseRectangularArea(area) => {
+                let blank = Cell::blank_with_attrs(self.pen.clone_sgr_only());
+                self.fill_rectangular_area(&area, &blank);
+            }
+            Edit::SelectiveEraseRectangularArea(area) => {
+                // We don't support the protected attribute, so every cell is
+                // eligible for erasure, but DECSERA leaves the attributes alone
+                if let Some((rows, cols)) = self.rectangular_area_bounds(&area) {
+                    let screen = self.screen_mut();
+                    for y in rows {
+                        let line_idx = screen.phys_row(y);
+                        let line = screen.line_mut(line_idx);
+                        for x in cols.clone() {
+                            if let Some(attrs) = line.cells().get(x).map(|c| c.attrs().clone()) {
+                                line.set_cell(x, Cell::blank_with_attrs(attrs), seqno);
+                            }
+                        }
+                    }
+                }
+            }
+            Edit::CopyRectangularArea {
+                src,
+                dest_top,
+                dest_left,
+                ..
+            } => self.copy_rectangular_area(&src, dest_top, dest_left),
+            Edit::ChangeAttributesInRectangularArea { area, attributes } => {
+                self.change_attributes_in_area(&area, |attrs| {
+                    for code in &attributes {
+                        match code {
+                            SgrCode::Reset => {
+                                attrs.set_intensity(Intensity::Normal);
+                                attrs.set_underline(Underline::None);
+                                attrs.set_blink(Blink::None);
+                                attrs.set_reverse(false);
+                            }
+                            SgrCode::IntensityBold => {
+                                attrs.set_intensity(Intensity::Bold);
+                            }
+                            SgrCode::NormalIntensity => {
+                                attrs.set_intensity(Intensity::Normal);
+                            }
+                            SgrCode::UnderlineOn => {
+                                attrs.set_underline(Underline::Single);
+                            }
+                            SgrCode::UnderlineOff => {
+                                attrs.set_underline(Underline::None);
+                            }
+                            SgrCode::BlinkOn => {
+                                attrs.set_blink(Blink::Slow);
+                            }
+                            SgrCode::BlinkOff => {
+                                attrs.set_blink(Blink::None);
+                            }
+                            SgrCode::InverseOn => {
+                                attrs.set_reverse(true);
+                            }
+                            SgrCode::InverseOff => {
+                                attrs.set_reverse(false);
+                            }
+                            _ => {}
+                        }
+                    }
+                });
+            }
+            Edit::ReverseAttributesInRectangularArea { area, attributes } => {
+                self.change_attributes_in_area(&area, |attrs| {
+                    for code in &attributes {
+                        let all = *code == SgrCode::Reset;
+                        if all || *code == SgrCode::IntensityBold {
+                            attrs.set_intensity(match attrs.intensity() {
+                                Intensity::Bold => Intensity::Normal,
+                                _ => Intensity::Bold,
+                            });
+                        }
+                        if all || *code == SgrCode::UnderlineOn {
+                            attrs.set_underline(match attrs.underline() {
+                                Underline::None => Underline::Single,
+                                _ => Underline::None,
+                            });
+                        }
+                        if all || *code == SgrCode::BlinkOn {
+                            attrs.set_blink(match attrs.blink() {
+                                Blink::None => Blink::Slow,
+                                _ => Blink::None,
+                            });
+                        }
+                        if all || *code == SgrCode::InverseOn {
+                            attrs.set_reverse(!attrs.reverse());
+                        }
+                    }
+                });
+            }
+            Edit::SelectAttributeChangeExtent(extent) => {
+                self.attribute_change_extent = extent;
+            }
+        }
+    }
+
+    /// Resolves the corners of a rectangular area into the visible rows
+    /// and columns that lie between them.  The corners are relative to
+    /// the margins when DECOM is set, and are clipped to the screen, or
+    /// to the margins in origin mode, so the ranges may be empty.
+    fn rectangular_area_corners(
+        &self,
+        area: &RectangularArea,
+    ) -> (Range<VisibleRowIndex>, Range<usize>) {
+        let (rows, cols) = if self.dec_origin_mode {
+            (
+                self.top_and_bottom_margins.clone(),
+                self.left_and_right_margins.clone(),
+            )
+        } else {
+            (
+                0..self.screen().physical_rows as VisibleRowIndex,
+                0..self.screen().physical_cols,
+            )
+        };
+
+        let top = (rows.start + area.top.as_zero_based() as VisibleRowIndex).min(rows.end);
+        let bottom = (rows.start + area.bottom.as_one_based() as VisibleRowIndex).min(rows.end);
+        let left = cols
+            .start
+            .saturating_add(area.left.as_zero_based() as usize)
+            .min(cols.end);
+        let right = cols
+            .start
+            .saturating_add(area.right.as_one_based() as usize)
+            .min(cols.end);
+
+        (top..bottom, left..right)
+    }
+
+    /// Like `rectangular_area_corners`, but returns None if the
+    /// rectangle between the corners is empty
+    fn rectangular_area_bounds(
+        &self,
+        area: &RectangularArea,
+    ) -> Option<(Range<VisibleRowIndex>, Range<usize>)> {
+        let (rows, cols) = self.rectangular_area_corners(area);
+        if rows.is_empty() || cols.is_empty() {
+            None
+        } else {
+            Some((rows, cols))
+        }
+    }
+
+    /// Sets every cell in the area to a copy of `cell`; used by
+    /// DECFRA and DECERA
+    fn fill_rectangular_area(&mut self, area: &RectangularArea, cell: &Cell) {
+        let seqno = self.seqno;
+        if let Some((rows, cols)) = self.rectangular_area_bounds(area) {
+            let screen = self.screen_mut();
+            for y in rows {
+                for x in cols.clone() {
+                    screen.set_cell(x, y, cell, seqno);
+                }
+            }
+        }
+    }
+
+    /// https://vt100.net/docs/vt510-rm/DECCRA.html
+    /// We only have a single page, so the page numbers are ignored.
+    fn copy_rectangular_area(&mut self, src: &RectangularArea, top: OneBased, left: OneBased) {
+        let seqno = self.seqno;
+        let (rows, cols) = match self.rectangular_area_bounds(src) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        // The destination is clipped in the same way as the source
+        let height = (rows.end - rows.start) as u32;
+        let width = (cols.end - cols.start) as u32;
+        let dest = RectangularArea {
+            top,
+            left,
+            bottom: OneBased::new(top.as_one_based().saturating_add(height - 1)),
+            right: OneBased::new(left.as_one_based().saturating_add(width - 1)),
+        };
+        let (dest_rows, dest_cols) = match self.rectangular_area_bounds(&dest) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        // Take a copy of the source first, as it may overlap
+        // with the destination
+        let screen = self.screen_mut();
+        let cells: Vec<Vec<Cell>> = rows
+            .map(|y| {
+                cols.clone()
+                    .map(|x| screen.get_cell(x, y).cloned().unwrap_or_else(Cell::blank))
+                    .collect()
+            })
+            .collect();
+
+        for (y, row) in dest_rows.zip(cells.iter()) {
+            for (x, cell) in dest_cols.clone().zip(row.iter()) {
+                screen.set_cell(x, y, cell, seqno);
+            }
+        }
+    }
+
+    /// Applies `func` to the attributes of the cells affected by
+    /// DECCARA or DECRARA, which are either the rectangle described
+    /// by `area`, or the stream of characters that runs from its top
+    /// left corner to its bottom right corner, depending on DECSACE.
+    fn change_attributes_in_area<F: Fn(&mut CellAttributes)>(
+        &mut self,
+        area: &RectangularArea,
+        func: F,
+    ) {
+        let seqno = self.seqno;
+        // In stream mode the left corner may lie to the right of the
+        // right corner, so we can't reject an empty rectangle here
+        let (rows, cols) = self.rectangular_area_corners(area);
+        let full_width = if self.dec_origin_mode {
+            self.left_and_right_margins.clone()
+        } else {
+            0..self.screen().physical_cols
+        };
+        let extent = self.attribute_change_extent;
+
+        let screen = self.screen_mut();
+        for y in rows.clone() {
+            let cols = match extent {
+                AttributeChangeExtent::Rectangle => cols.clone(),
+                AttributeChangeExtent::Stream => {
+                    let start = if y == rows.start {
+                        cols.start
+                    } else {
+                        full_width.start
+                    };
+                    let end = if y == rows.end - 1 {
+                        cols.end
+                    } else {
+                        full_width.end
+                    };
+                    start..end
+                }
+            };
+            let line_idx = screen.phys_row(y);
+            let line = screen.line_mut(line_idx);
+            line.update_last_change_seqno(seqno);
+            for cell in line
+                .cells_mut_for_attr_changes_only()
+                .iter_mut()
+                .skip(cols.start)
+                .take(cols.end.saturating_sub(cols.start))
+            {
+                func(cell.attrs_mut());
+            }
         }
     }
 
@@ -2105,6 +2836,9 @@ impl TerminalState {
                 // we don't need to process it as a terminal command
             }
             Cursor::RequestActivePositionReport => {
+                if !self.query_allowed(TerminalQuery::StatusReport) {
+                    return;
+                }
                 let line = OneBased::from_zero_based(
                     (self.cursor.y.saturating_sub(if self.dec_origin_mode {
                         self.top_and_bottom_margins.start
@@ -2154,6 +2888,27 @@ impl TerminalState {
         }
     }
 
+    /// Returns the cursor shape, resolving the default style
+    /// to the shape that it is configured to have
+    fn effective_cursor_shape(&self) -> CursorShape {
+        match self.cursor.shape {
+            CursorShape::Default => self.config.default_cursor_style(),
+            shape => shape,
+        }
+    }
+
+    /// Called when returning to the primary screen from the alternate
+    /// screen, where the cursor had `alt_shape`.  Restores the shape
+    /// that the cursor had before the alternate screen was activated,
+    /// or keeps `alt_shape` if that has been disabled.
+    fn restore_primary_cursor_shape(&mut self, alt_shape: CursorShape) {
+        let primary_shape = self.primary_cursor_shape.take();
+        self.cursor.shape = match primary_shape {
+            Some(shape) if self.config.restore_cursor_style_after_alt_screen() => shape,
+            _ => alt_shape,
+        };
+    }
+
     /// https://vt100.net/docs/vt510-rm/DECSC.html
     fn dec_save_cursor(&mut self) {
         let saved = SavedCursor {
@@ -14,7 +14,23 @@ impl TerminalState {
         position.max(0).saturating_add(1 + 32).min(127) as u8 as char
     }
 
-    fn mouse_report_button_number(&self, event: &MouseEvent) -> (i8, MouseButton) {
+    /// Returns the 1-based coordinates to report for `event` using the
+    /// SGR encoding, which are in pixels when SGR-Pixels mode is enabled
+    fn sgr_mouse_coords(&self, event: &MouseEvent) -> (i64, i64) {
+        if self.sgr_pixels_mouse {
+            let screen = self.screen();
+            let cell_width = (self.pixel_width / screen.physical_cols.max(1)) as i64;
+            let cell_height = (self.pixel_height / screen.physical_rows.max(1)) as i64;
+            (
+                (event.x as i64 * cell_width + event.x_pixel_offset as i64).max(0) + 1,
+                (event.y * cell_height + event.y_pixel_offset as i64).max(0) + 1,
+            )
+        } else {
+            (event.x as i64 + 1, event.y + 1)
+        }
+    }
+
+    fn mouse_report_button_number(&self, event: &MouseEvent) -> (u8, MouseButton) {
         let button = match event.button {
             MouseButton::None => self
                 .current_mouse_buttons
@@ -30,6 +46,10 @@ impl TerminalState {
             MouseButton::Right => 2,
             MouseButton::WheelUp(_) => 64,
             MouseButton::WheelDown(_) => 65,
+            MouseButton::WheelLeft(_) => 66,
+            MouseButton::WheelRight(_) => 67,
+            MouseButton::Back => 128,
+            MouseButton::Forward => 129,
         };
 
         if event.modifiers.contains(KeyModifiers::SHIFT) {
@@ -48,16 +68,11 @@ impl TerminalState {
     fn mouse_wheel(&mut self, event: MouseEvent) -> anyhow::Result<()> {
         let (button, _button) = self.mouse_report_button_number(&event);
 
-        if self.sgr_mouse
+        if (self.sgr_mouse || self.sgr_pixels_mouse)
             && (self.mouse_tracking || self.button_event_mouse || self.any_event_mouse)
         {
-            write!(
-                self.writer,
-                "\x1b[<{};{};{}M",
-                button,
-                event.x + 1,
-                event.y + 1
-            )?;
+            let (x, y) = self.sgr_mouse_coords(&event);
+            write!(self.writer, "\x1b[<{};{};{}M", button, x, y)?;
             self.writer.flush()?;
         } else if self.mouse_tracking || self.button_event_mouse || self.any_event_mouse {
             write!(
@@ -70,15 +85,15 @@ impl TerminalState {
             self.writer.flush()?;
         } else if self.screen.is_alt_screen_active() {
             // Send cursor keys instead (equivalent to xterm's alternateScroll mode)
+            let key = match event.button {
+                MouseButton::WheelDown(_) => KeyCode::DownArrow,
+                MouseButton::WheelUp(_) => KeyCode::UpArrow,
+                // There is no alternate scroll equivalent for horizontal scrolling
+                MouseButton::WheelLeft(_) | MouseButton::WheelRight(_) => return Ok(()),
+                _ => bail!("unexpected mouse event"),
+            };
             for _ in 0..self.config.alternate_buffer_wheel_scroll_speed() {
-                self.key_down(
-                    match event.button {
-                        MouseButton::WheelDown(_) => KeyCode::DownArrow,
-                        MouseButton::WheelUp(_) => KeyCode::UpArrow,
-                        _ => bail!("unexpected mouse event"),
-                    },
-                    KeyModifiers::default(),
-                )?;
+                self.key_down(key, KeyModifiers::default())?;
             }
         }
         Ok(())
@@ -93,14 +108,9 @@ impl TerminalState {
             return Ok(());
         }
 
-        if self.sgr_mouse {
-            write!(
-                self.writer,
-                "\x1b[<{};{};{}M",
-                button,
-                event.x + 1,
-                event.y + 1
-            )?;
+        if self.sgr_mouse || self.sgr_pixels_mouse {
+            let (x, y) = self.sgr_mouse_coords(&event);
+            write!(self.writer, "\x1b[<{};{};{}M", button, x, y)?;
             self.writer.flush()?;
         } else {
             write!(
@@ -121,14 +131,9 @@ impl TerminalState {
         if !self.current_mouse_buttons.is_empty() {
             self.current_mouse_buttons.retain(|&b| b != button);
             if self.mouse_tracking || self.button_event_mouse || self.any_event_mouse {
-                if self.sgr_mouse {
-                    write!(
-                        self.writer,
-                        "\x1b[<{};{};{}m",
-                        release_button,
-                        event.x + 1,
-                        event.y + 1
-                    )?;
+                if self.sgr_mouse || self.sgr_pixels_mouse {
+                    let (x, y) = self.sgr_mouse_coords(&event);
+                    write!(self.writer, "\x1b[<{};{};{}m", release_button, x, y)?;
                     self.writer.flush()?;
                 } else {
                     let release_button = 3;
@@ -147,7 +152,13 @@ impl TerminalState {
         Ok(())
     }
 
-    fn mouse_move(&mut self, event: MouseEvent) -> anyhow::Result<()> {
+    fn mouse_move(&mut self, mut event: MouseEvent) -> anyhow::Result<()> {
+        if !self.sgr_pixels_mouse {
+            // Movement within a cell isn't reportable, so don't let it
+            // defeat the suppression of repeated reports below
+            event.x_pixel_offset = 0;
+            event.y_pixel_offset = 0;
+        }
         let reportable = self.any_event_mouse || !self.current_mouse_buttons.is_empty();
         // Note: self.mouse_tracking on its own is for clicks, not drags!
         if reportable && (self.button_event_mouse || self.any_event_mouse) {
@@ -162,14 +173,9 @@ impl TerminalState {
             let (button, _button) = self.mouse_report_button_number(&event);
             let button = 32 + button;
 
-            if self.sgr_mouse {
-                write!(
-                    self.writer,
-                    "\x1b[<{};{};{}M",
-                    button,
-                    event.x + 1,
-                    event.y + 1
-                )?;
+            if self.sgr_mouse || self.sgr_pixels_mouse {
+                let (x, y) = self.sgr_mouse_coords(&event);
+                write!(self.writer, "\x1b[<{};{};{}M", button, x, y)?;
                 self.writer.flush()?;
             } else {
                 write!(
@@ -208,6 +214,16 @@ impl TerminalState {
                 kind: MouseEventKind::Press,
                 button: MouseButton::WheelDown(_),
                 ..
+            }
+            | MouseEvent {
+                kind: MouseEventKind::Press,
+                button: MouseButton::WheelLeft(_),
+                ..
+            }
+            | MouseEvent {
+                kind: MouseEventKind::Press,
+                button: MouseButton::WheelRight(_),
+                ..
             } => self.mouse_wheel(event),
             MouseEvent {
                 kind: MouseEventKind::Press,
@@ -1,12 +1,15 @@
 use crate::terminal::Alert;
 use crate::terminalstate::{default_color_map, CharSet, TabStop};
-use crate::{ClipboardSelection, Position, TerminalState, VisibleRowIndex};
+use crate::{
+    ClipboardSelection, Position, TerminalCompatibility, TerminalQuery, TerminalState,
+    VisibleRowIndex,
+};
 use crate::{DCS, ST};
 use log::{debug, error};
 use num_traits::FromPrimitive;
 use std::fmt::Write;
 use std::ops::{Deref, DerefMut};
-use termwiz::cell::{grapheme_column_width, Cell, CellAttributes, SemanticType};
+use termwiz::cell::{grapheme_column_width_for_version, Cell, CellAttributes, SemanticType};
 use termwiz::escape::csi::EraseInDisplay;
 use termwiz::escape::osc::{
     ChangeColorPair, ColorOrQuery, FinalTermSemanticPrompt, ITermProprietary, Selection,
@@ -14,6 +17,8 @@ use termwiz::escape::osc::{
 use termwiz::escape::{
     Action, ControlCode, DeviceControlMode, Esc, EscCode, OperatingSystemCommand, CSI,
 };
+use termwiz::image::ImageCell;
+use termwiz::surface::{SequenceNo, TextureCoordinate};
 use url::Url;
 
 /// A helper struct for implementing `vtparse::VTActor` while compartmentalizing
@@ -59,8 +64,19 @@ impl<'a> Performer<'a> {
         let seqno = self.seqno;
         let mut p = std::mem::take(&mut self.print);
 
+        if self.can_print_ascii_run() && p.bytes().all(|b| (0x20..0x7f).contains(&b)) {
+            self.print_ascii_run(&p, seqno);
+            std::mem::swap(&mut self.print, &mut p);
+            self.print.clear();
+            return;
+        }
+
         for g in unicode_segmentation::UnicodeSegmentation::graphemes(p.as_str(), true) {
-            let g = if (self.shift_out && self.g1_charset == CharSet::DecLineDrawing)
+            let soft_glyph = self.soft_font_glyph(g);
+            let g = if soft_glyph.is_some() {
+                // The glyph is drawn by the image attached to the cell
+                " "
+            } else if (self.shift_out && self.g1_charset == CharSet::DecLineDrawing)
                 || (!self.shift_out && self.g0_charset == CharSet::DecLineDrawing)
             {
                 match g {
@@ -134,10 +150,17 @@ impl<'a> Performer<'a> {
             // they occupy a cell so that we can re-emit them when we output them.
             // If we didn't do this, then we'd effectively filter them out from
             // the model, which seems like a lossy design choice.
-            let print_width = grapheme_column_width(g).max(1);
+            let print_width = grapheme_column_width_for_version(g, self.unicode_version).max(1);
             let wrappable = x + print_width >= width;
 
-            let cell = Cell::new_grapheme_with_width(g, print_width, pen);
+            let mut cell = Cell::new_grapheme_with_width(g, print_width, pen);
+            if let Some(image) = soft_glyph {
+                cell.attrs_mut().set_image(Box::new(ImageCell::new(
+                    TextureCoordinate::new_f32(0., 0.),
+                    TextureCoordinate::new_f32(1., 1.),
+                    image,
+                )));
+            }
 
             if self.insert {
                 let margin = self.left_and_right_margins.end;
@@ -170,16 +193,74 @@ impl<'a> Performer<'a> {
         self.print.clear();
     }
 
+    /// Returns true if printable ASCII is drawn as-is, one cell per
+    /// byte, so that `print_ascii_run` can be used in place of the
+    /// per-grapheme logic in `flush_print`
+    fn can_print_ascii_run(&self) -> bool {
+        let charset = if self.shift_out {
+            self.g1_charset
+        } else {
+            self.g0_charset
+        };
+        !self.insert && charset == CharSet::Ascii && self.soft_fonts.is_empty()
+    }
+
+    /// Draws a run of printable ASCII text, excluding DEL.  This is
+    /// equivalent to the per-grapheme logic in `flush_print`, but
+    /// assigns the cells up to the right margin in bulk.
+    fn print_ascii_run(&mut self, mut text: &str, seqno: SequenceNo) {
+        let pen = self.pen.clone();
+        while !text.is_empty() {
+            if self.wrap_next {
+                let x = self.cursor.x;
+                let y = self.cursor.y;
+                if let Some(cell) = self.screen_mut().cell_mut(x, y) {
+                    cell.attrs_mut().set_wrapped(true);
+                }
+                self.new_line(true);
+            }
+
+            let x = self.cursor.x;
+            let y = self.cursor.y;
+            let width = self.left_and_right_margins.end;
+
+            // Fill up to the right margin; if the cursor is already
+            // at or beyond it, each character replaces the last
+            let len = width.saturating_sub(x).max(1).min(text.len());
+            let (run, rest) = text.split_at(len);
+            self.screen_mut().set_ascii_run(x, y, run, &pen, seqno);
+            text = rest;
+
+            if x + len < width {
+                self.cursor.x = x + len;
+                self.wrap_next = false;
+            } else {
+                self.cursor.x = x + len - 1;
+                self.wrap_next = self.dec_auto_wrap;
+                if !self.wrap_next && !text.is_empty() {
+                    // Without auto wrap, only the last character
+                    // remains visible at the margin
+                    text = &text[text.len() - 1..];
+                }
+            }
+        }
+    }
+
     pub fn perform(&mut self, action: Action) {
         debug!("perform {:?}", action);
         match action {
             Action::Print(c) => self.print(c),
+            Action::PrintString(s) => self.print.push_str(&s),
             Action::Control(code) => self.control(code),
             Action::DeviceControl(ctrl) => self.device_control(ctrl),
             Action::OperatingSystemCommand(osc) => self.osc_dispatch(*osc),
             Action::Esc(esc) => self.esc_dispatch(esc),
             Action::CSI(csi) => self.csi_dispatch(csi),
             Action::Sixel(sixel) => self.sixel(sixel),
+            Action::SoftFont(font) => {
+                self.flush_print();
+                self.soft_font(font);
+            }
             Action::XtGetTcap(names) => self.xt_get_tcap(names),
             Action::KittyImage(img) => {
                 self.flush_print();
@@ -202,47 +283,20 @@ impl<'a> Performer<'a> {
                         // but note that *that* text has the validity value
                         // inverted; there's a note about this in the xterm
                         // ctlseqs docs.
-                        match s.data.as_slice() {
-                            &[b'"', b'p'] => {
-                                // DECSCL - select conformance level
-                                write!(self.writer, "{}1$r65;1\"p{}", DCS, ST).ok();
-                                self.writer.flush().ok();
-                            }
-                            &[b'r'] => {
-                                // DECSTBM - top and bottom margins
-                                let margins = self.top_and_bottom_margins.clone();
-                                write!(
-                                    self.writer,
-                                    "{}1$r{};{}r{}",
-                                    DCS,
-                                    margins.start + 1,
-                                    margins.end,
-                                    ST
-                                )
-                                .ok();
-                                self.writer.flush().ok();
-                            }
-                            &[b's'] => {
-                                // DECSLRM - left and right margins
-                                let margins = self.left_and_right_margins.clone();
-                                write!(
-                                    self.writer,
-                                    "{}1$r{};{}s{}",
-                                    DCS,
-                                    margins.start + 1,
-                                    margins.end,
-                                    ST
-                                )
-                                .ok();
-                                self.writer.flush().ok();
+                        if !self.query_allowed(TerminalQuery::StatusString) {
+                            return;
+                        }
+                        match self.decrqss_response(&s.data) {
+                            Some(report) => {
+                                write!(self.writer, "{}1$r{}{}", DCS, report, ST).ok();
                             }
-                            _ => {
+                            None => {
                                 log::warn!("unhandled DECRQSS {:?}", s);
                                 // Reply that the request is invalid
                                 write!(self.writer, "{}0$r{}", DCS, ST).ok();
-                                self.writer.flush().ok();
                             }
                         }
+                        self.writer.flush().ok();
                     }
                     _ => log::warn!("unhandled {:?}", s),
                 }
@@ -263,6 +317,12 @@ impl<'a> Performer<'a> {
     fn control(&mut self, control: ControlCode) {
         let seqno = self.seqno;
         self.flush_print();
+        if control as u8 >= 0x80 && self.config.compatibility() == TerminalCompatibility::Vt100 {
+            // The VT100 only understands 7-bit controls; 8-bit C1 codes
+            // are likely to be line noise or text in a legacy charset
+            log::trace!("ignoring C1 control {:?} in VT100 mode", control);
+            return;
+        }
         match control {
             ControlCode::LineFeed | ControlCode::VerticalTab | ControlCode::FormFeed => {
                 if self.left_and_right_margins.contains(&self.cursor.x) {
@@ -420,6 +480,26 @@ impl<'a> Performer<'a> {
             Esc::Code(EscCode::UkCharacterSetG1) => {
                 self.g1_charset = CharSet::Uk;
             }
+            Esc::Unspecified {
+                intermediate: Some(b'('),
+                control,
+            } if self.has_soft_font(control) => {
+                self.g0_charset = CharSet::Soft(control);
+            }
+            Esc::Unspecified {
+                intermediate: Some(intermediate),
+                control,
+            } if (intermediate == b')' || intermediate == b'-') && self.has_soft_font(control) => {
+                self.g1_charset = CharSet::Soft(control);
+            }
+            Esc::Code(EscCode::LockingShiftG1Right) => {
+                self.gr_is_g1 = true;
+            }
+            Esc::Code(EscCode::LockingShiftG2Right) | Esc::Code(EscCode::LockingShiftG3Right) => {
+                // We don't support G2 and G3, so GR reverts to
+                // showing the characters as-is
+                self.gr_is_g1 = false;
+            }
             Esc::Code(EscCode::DecSaveCursorPosition) => self.dec_save_cursor(),
             Esc::Code(EscCode::DecRestoreCursorPosition) => self.dec_restore_cursor(),
 
@@ -486,19 +566,25 @@ impl<'a> Performer<'a> {
                 self.bracketed_paste = false;
                 self.focus_tracking = false;
                 self.sgr_mouse = false;
+                self.sgr_pixels_mouse = false;
                 self.sixel_scrolls_right = false;
                 self.any_event_mouse = false;
                 self.button_event_mouse = false;
                 self.current_mouse_buttons.clear();
                 self.cursor_visible = true;
+                self.primary_cursor_shape = None;
                 self.g0_charset = CharSet::Ascii;
                 self.g1_charset = CharSet::DecLineDrawing;
                 self.shift_out = false;
+                self.gr_is_g1 = false;
+                self.soft_fonts.clear();
                 self.newline_mode = false;
                 self.tabs = TabStop::new(self.screen().physical_cols, 8);
                 self.palette.take();
                 self.top_and_bottom_margins = 0..self.screen().physical_rows as VisibleRowIndex;
                 self.left_and_right_margins = 0..self.screen().physical_cols;
+                self.unicode_version = self.config.unicode_version();
+                self.unicode_version_stack.clear();
 
                 self.screen.activate_primary_screen(seqno);
                 self.erase_in_display(EraseInDisplay::EraseScrollback);
@@ -573,6 +659,12 @@ impl<'a> Performer<'a> {
                         handler.alert(Alert::TitleMaybeChanged);
                     }
                 }
+                ITermProprietary::UnicodeVersion(op) => self.apply_unicode_version_op(op),
+                ITermProprietary::RemoteControl(request) => {
+                    if let Some(handler) = self.alert_handler.as_mut() {
+                        handler.alert(Alert::RemoteControl(request));
+                    }
+                }
                 _ => log::warn!("unhandled iterm2: {:?}", iterm),
             },
 
@@ -583,6 +675,7 @@ impl<'a> Performer<'a> {
                 FinalTermSemanticPrompt::FreshLineAndStartPrompt { .. },
             ) => {
                 self.fresh_line();
+                self.semantic_prompt_started();
                 self.pen.set_semantic_type(SemanticType::Prompt);
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
@@ -594,6 +687,7 @@ impl<'a> Performer<'a> {
                 FinalTermSemanticPrompt::MarkEndOfCommandWithFreshLine { .. },
             ) => {
                 self.fresh_line();
+                self.semantic_prompt_started();
                 self.pen.set_semantic_type(SemanticType::Prompt);
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
@@ -604,12 +698,15 @@ impl<'a> Performer<'a> {
             OperatingSystemCommand::FinalTermSemanticPrompt(
                 FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { .. },
             ) => {
+                self.semantic_command_started();
                 self.pen.set_semantic_type(SemanticType::Output);
             }
 
             OperatingSystemCommand::FinalTermSemanticPrompt(
-                FinalTermSemanticPrompt::CommandStatus { .. },
-            ) => {}
+                FinalTermSemanticPrompt::CommandStatus { status, .. },
+            ) => {
+                self.command_completed(Some(status));
+            }
 
             OperatingSystemCommand::FinalTermSemanticPrompt(ft) => {
                 log::warn!("unhandled: {:?}", ft);
@@ -658,6 +755,9 @@ impl<'a> Performer<'a> {
                 for pair in specs {
                     match pair.color {
                         ColorOrQuery::Query => {
+                            if !self.query_allowed(TerminalQuery::ColorPalette) {
+                                continue;
+                            }
                             let response =
                                 OperatingSystemCommand::ChangeColorNumber(vec![ChangeColorPair {
                                     palette_index: pair.palette_index,
@@ -713,13 +813,16 @@ impl<'a> Performer<'a> {
                             ($name:ident) => {
                                 match color {
                                     ColorOrQuery::Query => {
-                                        let response = OperatingSystemCommand::ChangeDynamicColors(
-                                            which_color,
-                                            vec![ColorOrQuery::Color(self.palette().$name)],
-                                        );
-                                        log::trace!("Color Query response {:?}", response);
-                                        write!(self.writer, "{}", response).ok();
-                                        self.writer.flush().ok();
+                                        if self.query_allowed(TerminalQuery::ColorPalette) {
+                                            let response =
+                                                OperatingSystemCommand::ChangeDynamicColors(
+                                                    which_color,
+                                                    vec![ColorOrQuery::Color(self.palette().$name)],
+                                                );
+                                            log::trace!("Color Query response {:?}", response);
+                                            write!(self.writer, "{}", response).ok();
+                                            self.writer.flush().ok();
+                                        }
                                     }
                                     ColorOrQuery::Color(c) => self.palette_mut().$name = c,
                                 }
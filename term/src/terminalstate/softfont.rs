@@ -0,0 +1,155 @@
+use crate::terminalstate::CharSet;
+use crate::TerminalState;
+use std::collections::HashMap;
+use std::sync::Arc;
+use termwiz::escape::SoftFont;
+use termwiz::image::ImageDataType;
+use termwiz::surface::change::ImageData;
+
+/// How many rasterized glyphs are kept before they are discarded
+const MAX_RENDERED: usize = 1024;
+
+/// The glyphs of a soft font that was loaded by DECDLD
+#[derive(Default)]
+pub(crate) struct LoadedSoftFont {
+    width: usize,
+    height: usize,
+    /// The bitmap of each character, one entry per pixel in row major order
+    bitmaps: HashMap<char, Vec<bool>>,
+    /// Characters that have been rasterized in a given color
+    rendered: HashMap<(char, (u8, u8, u8)), Arc<ImageData>>,
+}
+
+impl LoadedSoftFont {
+    fn load(&mut self, font: &SoftFont) {
+        let (width, height) = match font.matrix_size() {
+            Some(size) => size,
+            None => {
+                log::warn!(
+                    "Ignoring DECDLD soft font with a character matrix larger than {}x{}",
+                    SoftFont::MAX_MATRIX_WIDTH,
+                    SoftFont::MAX_MATRIX_HEIGHT
+                );
+                return;
+            }
+        };
+        if (width, height) != (self.width, self.height) {
+            // Glyphs of different sizes can't be mixed in the same font
+            self.bitmaps.clear();
+            self.width = width;
+            self.height = height;
+        }
+        self.rendered.clear();
+
+        for (idx, glyph) in font.glyphs.iter().enumerate() {
+            let c = match font.char_for_glyph(idx) {
+                Some(c) => c,
+                None => break,
+            };
+
+            let mut bitmap = vec![false; width * height];
+            for (row_idx, row) in glyph.iter().enumerate() {
+                for (x, sixel) in row.iter().enumerate().take(width) {
+                    for bitno in 0..6 {
+                        let y = row_idx * 6 + bitno;
+                        if y < height && (sixel & (1 << bitno)) != 0 {
+                            bitmap[y * width + x] = true;
+                        }
+                    }
+                }
+            }
+            self.bitmaps.insert(c, bitmap);
+        }
+    }
+
+    fn image(&mut self, c: char, color: (u8, u8, u8)) -> Option<Arc<ImageData>> {
+        if let Some(image) = self.rendered.get(&(c, color)) {
+            return Some(Arc::clone(image));
+        }
+
+        let bitmap = self.bitmaps.get(&c)?;
+        let (red, green, blue) = color;
+        let mut data = Vec::with_capacity(bitmap.len() * 4);
+        for &on in bitmap {
+            // Unset pixels are transparent so that the background
+            // color of the cell shows through
+            data.extend_from_slice(&[red, green, blue, if on { 0xff } else { 0 }]);
+        }
+
+        let image = Arc::new(ImageData::with_data(ImageDataType::new_single_frame(
+            self.width as u32,
+            self.height as u32,
+            data,
+        )));
+        if self.rendered.len() >= MAX_RENDERED {
+            self.rendered.clear();
+        }
+        self.rendered.insert((c, color), Arc::clone(&image));
+        Some(image)
+    }
+}
+
+impl TerminalState {
+    pub(crate) fn soft_font(&mut self, font: Box<SoftFont>) {
+        // Only the final byte of the designator is significant to SCS
+        let dscs = match font.designator.last() {
+            Some(&dscs) => dscs,
+            None => return,
+        };
+
+        match font.erase_control {
+            2 => self.soft_fonts.clear(),
+            1 => {}
+            _ => {
+                self.soft_fonts.remove(&dscs);
+            }
+        }
+
+        self.soft_fonts.entry(dscs).or_default().load(&font);
+    }
+
+    /// If `g` is a character in a soft font that is currently invoked
+    /// into GL or GR, returns an image of its glyph in the current
+    /// foreground color.
+    pub(crate) fn soft_font_glyph(&mut self, g: &str) -> Option<Arc<ImageData>> {
+        if self.soft_fonts.is_empty() {
+            return None;
+        }
+
+        let mut chars = g.chars();
+        let c = chars.next()?;
+        if chars.next().is_some() {
+            return None;
+        }
+
+        let (charset, c) = if self.gr_is_g1 && ('\u{a0}'..='\u{ff}').contains(&c) {
+            (self.g1_charset, (c as u8 - 0x80) as char)
+        } else if self.shift_out {
+            (self.g1_charset, c)
+        } else {
+            (self.g0_charset, c)
+        };
+
+        let dscs = match charset {
+            CharSet::Soft(dscs) => dscs,
+            _ => return None,
+        };
+
+        let palette = self.palette();
+        let color = if self.pen.reverse() {
+            palette.resolve_bg(self.pen.background())
+        } else {
+            palette.resolve_fg(self.pen.foreground())
+        };
+
+        self.soft_fonts
+            .get_mut(&dscs)?
+            .image(c, color.to_tuple_rgb8())
+    }
+
+    /// Returns true if a soft font with the final designator
+    /// byte `dscs` has been loaded
+    pub(crate) fn has_soft_font(&self, dscs: u8) -> bool {
+        self.soft_fonts.contains_key(&dscs)
+    }
+}
@@ -70,3 +70,22 @@ fn test_ri() {
     term.assert_cursor_pos(1, 0, None, Some(seqno));
     assert_visible_contents(&term, file!(), line!(), &["  ", "a ", "b ", "c "]);
 }
+
+#[test]
+fn test_c1_ignored_by_vt100() {
+    let mut term = TestTerm::new(4, 4, 0);
+    term.print("a\u{84}");
+    term.assert_cursor_pos(1, 1, None, None);
+
+    term.set_config(Arc::new(TestTermConfig {
+        scrollback: 0,
+        unicode_version: UnicodeVersion::default(),
+        compatibility: TerminalCompatibility::Vt100,
+    }));
+    term.print("\u{84}");
+    term.assert_cursor_pos(1, 1, None, None);
+
+    // The 7-bit equivalent is still recognized
+    term.print("\x1bD");
+    term.assert_cursor_pos(1, 2, None, None);
+}
@@ -222,3 +222,116 @@ fn test_ed_erase_scrollback() {
     term.print("b");
     assert_all_contents(&term, file!(), line!(), &["111", "222", "ab "]);
 }
+
+#[test]
+fn test_decfra() {
+    let mut term = TestTerm::new(3, 4, 0);
+    term.print("hey!wat?");
+    term.print("\x1b[88;1;2;2;3$x");
+    assert_visible_contents(&term, file!(), line!(), &["hXX!", "wXX?", "    "]);
+
+    // Control characters can't be used to fill the area
+    term.print("\x1b[10;1;1;3;4$x");
+    assert_visible_contents(&term, file!(), line!(), &["hXX!", "wXX?", "    "]);
+
+    // The default corners cover the whole screen
+    term.print("\x1b[46;;;;$x");
+    assert_visible_contents(&term, file!(), line!(), &["....", "....", "...."]);
+}
+
+#[test]
+fn test_decera() {
+    let mut term = TestTerm::new(3, 4, 0);
+    term.print("hey!wat?1234");
+    term.print("\x1b[2;2;3;3$z");
+    assert_visible_contents(&term, file!(), line!(), &["hey!", "w  ?", "1  4"]);
+
+    // The area is clipped to the screen
+    term.print("\x1b[3;3;10;10$z");
+    assert_visible_contents(&term, file!(), line!(), &["hey!", "w  ?", "1   "]);
+}
+
+#[test]
+fn test_decsera() {
+    let mut term = TestTerm::new(1, 4, 0);
+    term.print("\x1b[44mhey!\x1b[0m");
+    term.print("\x1b[1;2;1;3${");
+
+    let attr = CellAttributes::default()
+        .set_background(color::AnsiColor::Navy)
+        .clone();
+    let line = Line::from_text("h  !", &attr);
+    assert_lines_equal(
+        file!(),
+        line!(),
+        &term.screen().visible_lines(),
+        &[line],
+        Compare::TEXT | Compare::ATTRS,
+    );
+}
+
+#[test]
+fn test_deccra() {
+    let mut term = TestTerm::new(3, 4, 0);
+    term.print("hey!wat?");
+    term.print("\x1b[1;1;2;2;1;2;2;1$v");
+    assert_visible_contents(&term, file!(), line!(), &["hey!", "whe?", " wa "]);
+
+    // Overlapping areas copy the original source cells
+    term.print("\x1b[1;1;1;3;1;1;2;1$v");
+    assert_visible_contents(&term, file!(), line!(), &["hhey", "whe?", " wa "]);
+}
+
+#[test]
+fn test_deccara() {
+    let mut term = TestTerm::new(2, 4, 0);
+    term.print("hey!wat?");
+
+    let attrs_of = |term: &TestTerm| -> Vec<Vec<bool>> {
+        term.screen()
+            .visible_lines()
+            .iter()
+            .map(|line| {
+                line.cells()
+                    .iter()
+                    .map(|cell| cell.attrs().intensity() == Intensity::Bold)
+                    .collect()
+            })
+            .collect()
+    };
+
+    // The default extent is the stream of characters between the corners
+    term.print("\x1b[1;3;2;2;1$r");
+    assert_eq!(
+        attrs_of(&term),
+        vec![
+            vec![false, false, true, true],
+            vec![true, true, false, false]
+        ]
+    );
+
+    term.print("\x1b[;;;;0$r");
+    assert_eq!(attrs_of(&term), vec![vec![false; 4], vec![false; 4]]);
+
+    // DECSACE selects the rectangle between the corners
+    term.print("\x1b[2*x");
+    term.print("\x1b[1;3;2;4;1$r");
+    assert_eq!(
+        attrs_of(&term),
+        vec![
+            vec![false, false, true, true],
+            vec![false, false, true, true]
+        ]
+    );
+
+    // DECRARA toggles the attributes
+    term.print("\x1b[1;2;2;3;1$t");
+    assert_eq!(
+        attrs_of(&term),
+        vec![
+            vec![false, true, false, true],
+            vec![false, true, false, true]
+        ]
+    );
+    assert_visible_contents(&term, file!(), line!(), &["hey!", "wat?"]);
+}
@@ -7,7 +7,7 @@ use bitflags::bitflags;
 mod c1;
 mod csi;
 // mod selection; FIXME: port to render layer
-use crate::color::ColorPalette;
+use crate::color::{ColorPalette, RgbColor};
 use pretty_assertions::assert_eq;
 use std::cell::RefCell;
 use std::sync::Arc;
@@ -46,12 +46,22 @@ struct TestTerm {
 #[derive(Debug)]
 struct TestTermConfig {
     scrollback: usize,
+    unicode_version: UnicodeVersion,
+    compatibility: TerminalCompatibility,
 }
 impl TerminalConfiguration for TestTermConfig {
     fn scrollback_size(&self) -> usize {
         self.scrollback
     }
 
+    fn unicode_version(&self) -> UnicodeVersion {
+        self.unicode_version
+    }
+
+    fn compatibility(&self) -> TerminalCompatibility {
+        self.compatibility
+    }
+
     fn color_palette(&self) -> ColorPalette {
         ColorPalette::default()
     }
@@ -71,7 +81,11 @@ impl TestTerm {
                 pixel_width: width * 8,
                 pixel_height: height * 16,
             },
-            Arc::new(TestTermConfig { scrollback }),
+            Arc::new(TestTermConfig {
+                scrollback,
+                unicode_version: UnicodeVersion::default(),
+                compatibility: TerminalCompatibility::default(),
+            }),
             "WezTerm",
             "O_o",
             Box::new(Vec::new()),
@@ -466,6 +480,80 @@ fn test_semantic() {
     );
 }
 
+#[test]
+fn test_semantic_command_completed() {
+    use std::rc::Rc;
+    use termwiz::escape::osc::FinalTermSemanticPrompt;
+
+    struct Alerts(Rc<RefCell<Vec<Alert>>>);
+    impl AlertHandler for Alerts {
+        fn alert(&mut self, alert: Alert) {
+            self.0.borrow_mut().push(alert);
+        }
+    }
+
+    let alerts = Rc::new(RefCell::new(vec![]));
+    let mut term = TestTerm::new(5, 10, 0);
+    term.set_notification_handler(Box::new(Alerts(Rc::clone(&alerts))));
+
+    let osc = |ft| format!("{}", OperatingSystemCommand::FinalTermSemanticPrompt(ft));
+
+    term.print(osc(FinalTermSemanticPrompt::FreshLineAndStartPrompt {
+        aid: None,
+        cl: None,
+    }));
+    term.print("> ");
+    term.print(osc(
+        FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilNextMarker,
+    ));
+    term.print("ls -l\r\n");
+    term.print(osc(
+        FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { aid: None },
+    ));
+    term.print("some file\r\n");
+    term.print(osc(FinalTermSemanticPrompt::CommandStatus {
+        status: 1,
+        aid: None,
+    }));
+
+    // An empty command line is not reported
+    term.print(osc(FinalTermSemanticPrompt::FreshLineAndStartPrompt {
+        aid: None,
+        cl: None,
+    }));
+    term.print("> ");
+    term.print(osc(
+        FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilNextMarker,
+    ));
+    term.print("\r\n");
+    term.print(osc(
+        FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { aid: None },
+    ));
+    term.print(osc(FinalTermSemanticPrompt::CommandStatus {
+        status: 0,
+        aid: None,
+    }));
+
+    let alerts = alerts.borrow();
+    assert_eq!(alerts.len(), 1);
+    match &alerts[0] {
+        Alert::CommandCompleted {
+            command,
+            exit_status,
+            ..
+        } => {
+            assert_eq!(command, "ls -l");
+            assert_eq!(*exit_status, Some(1));
+        }
+        alert => panic!("unexpected alert {:?}", alert),
+    }
+
+    let results = term.get_command_results();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].prompt_row, 0);
+    assert_eq!(results[0].exit_status, Some(1));
+}
+
 #[test]
 fn issue_1161() {
     let mut term = TestTerm::new(1, 5, 0);
@@ -559,6 +647,36 @@ fn basic_output() {
     );
 }
 
+/// Runs of plain text are assigned to cells in bulk; check that they
+/// wrap at the right margin in the same way as individual characters
+#[test]
+fn bulk_output_within_left_and_right_margins() {
+    let mut term = TestTerm::new(3, 10, 0);
+    term.set_mode("?69", true); // allow left/right margins to be set
+    term.set_left_and_right_margins(2, 6);
+    term.cup(2, 0);
+    term.print("abcdefghijkl");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["  abcde   ", "  fghij   ", "  kl      "],
+    );
+    term.assert_cursor_pos(4, 2, None, None);
+    assert!(term.screen().get_cell(6, 0).unwrap().attrs().wrapped());
+    assert!(!term.screen().get_cell(6, 2).unwrap().attrs().wrapped());
+
+    term.set_auto_wrap(false);
+    term.print("mnopq");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["  abcde   ", "  fghij   ", "  klmnq   "],
+    );
+    term.assert_cursor_pos(6, 2, None, None);
+}
+
 /// Ensure that we dirty lines as the cursor is moved around, otherwise
 /// the renderer won't draw the cursor in the right place
 #[test]
@@ -787,6 +905,44 @@ fn test_dec_special_graphics() {
     );
 }
 
+#[test]
+fn test_soft_font() {
+    let mut term = TestTerm::new(1, 8, 0);
+
+    // Load two 2x6 glyphs for `!` and `"`, then designate the font
+    // into G0 using a designator with an intermediate
+    term.print("\x1bP0;1;0;0;0;0;0;0{ @~~;@@\x1b\\");
+    term.print("a\x1b( @!\"#\x1b(Bb");
+    assert_visible_contents(&term, file!(), line!(), &["a  #b   "]);
+
+    let has_image = |x: usize| {
+        term.screen()
+            .get_cell(x, 0)
+            .and_then(|cell| cell.attrs().images())
+            .is_some()
+    };
+    assert!(!has_image(0));
+    assert!(has_image(1));
+    assert!(has_image(2));
+    // Characters that the font doesn't define are shown as-is
+    assert!(!has_image(3));
+    assert!(!has_image(4));
+
+    // RIS discards the soft fonts
+    term.print("\x1bc\x1b( @!");
+    assert_visible_contents(&term, file!(), line!(), &["!       "]);
+
+    // A character matrix larger than a DEC terminal allows is ignored
+    term.print("\x1bc\x1bP0;1;0;99999;0;0;99999;0{ A~~\x1b\\");
+    term.print("\x1b( A!");
+    assert_visible_contents(&term, file!(), line!(), &["!       "]);
+    assert!(term
+        .screen()
+        .get_cell(0, 0)
+        .and_then(|cell| cell.attrs().images())
+        .is_none());
+}
+
 /// Test double-width / double-height sequences.
 #[test]
 fn test_dec_double_width() {
@@ -941,6 +1097,76 @@ fn test_resize_wrap_escape_code_issue_978() {
     assert_visible_contents(&term, file!(), line!(), &["====", "SS  ", "    ", "    "]);
 }
 
+/// Returns the last two rows that contain text
+fn last_text_rows(term: &Terminal) -> Vec<String> {
+    let mut rows: Vec<String> = term
+        .screen()
+        .lines
+        .iter()
+        .map(|line| line.as_str().trim_end().to_string())
+        .filter(|text| !text.is_empty())
+        .collect();
+    rows.split_off(rows.len() - 2)
+}
+
+/// Resizing rewraps the screen and the most recent scrollback right
+/// away, leaving the rest of the scrollback to be rewrapped later
+#[test]
+fn test_resize_deferred_reflow() {
+    const LINES: usize = 4;
+    const NUM_PRINTED: usize = 1500;
+    let mut term = TestTerm::new(LINES, 8, 3000);
+    for i in 0..NUM_PRINTED {
+        term.print(format!("line{:04}\r\n", i));
+    }
+    term.resize(LINES, 4, 0, 0);
+    assert!(term.has_pending_reflow());
+    assert_eq!(term.screen().lines[0].as_str(), "line0000");
+    assert_eq!(last_text_rows(&term), vec!["line", "1499"]);
+
+    while term.reflow_pending(100) {}
+    assert!(!term.has_pending_reflow());
+    let screen = term.screen();
+    for i in 0..NUM_PRINTED {
+        assert_eq!(screen.lines[2 * i].as_str(), "line");
+        assert!(screen.lines[2 * i].last_cell_was_wrapped());
+        assert_eq!(screen.lines[2 * i + 1].as_str(), format!("{:04}", i));
+    }
+    assert_eq!(last_text_rows(&term), vec!["line", "1499"]);
+}
+
+/// The rows that follow the scrollback that is rewrapped in the
+/// background keep their StableRowIndex, and the scrollback is
+/// trimmed back to its limit as the rewrapped lines grow
+#[test]
+fn test_deferred_reflow_stable_rows() {
+    const LINES: usize = 4;
+    const SCROLLBACK: usize = 2500;
+    let mut term = TestTerm::new(LINES, 8, SCROLLBACK);
+    for i in 0..1500 {
+        term.print(format!("line{:04}\r\n", i));
+    }
+    term.resize(LINES, 4, 0, 0);
+    assert!(term.has_pending_reflow());
+
+    fn row_text(term: &Terminal, row: StableRowIndex) -> String {
+        let screen = term.screen();
+        screen.lines[screen.stable_row_to_phys(row).unwrap()]
+            .as_str()
+            .to_string()
+    }
+    let top = term.screen().visible_row_to_stable_row(0);
+    let recent = top - 10;
+    let recent_text = row_text(&term, recent);
+
+    while term.reflow_pending(100) {
+        assert_eq!(term.screen().visible_row_to_stable_row(0), top);
+        assert_eq!(row_text(&term, recent), recent_text);
+    }
+    assert!(term.screen().lines.len() <= LINES + SCROLLBACK);
+    assert_eq!(last_text_rows(&term), vec!["line", "1499"]);
+}
+
 #[test]
 fn test_scrollup() {
     let mut term = TestTerm::new(2, 1, 4);
@@ -1118,3 +1344,105 @@ fn test_hyperlinks() {
         Compare::TEXT | Compare::ATTRS,
     );
 }
+
+#[test]
+fn test_unicode_version() {
+    let mut term = TestTerm::new(3, 10, 0);
+    let emoji = "\u{1f600}";
+
+    term.print(emoji);
+    term.assert_cursor_pos(2, 0, None, None);
+
+    term.print("\x1b]1337;UnicodeVersion=push outer\x1b\\");
+    term.print("\x1b]1337;UnicodeVersion=8\x1b\\");
+    term.print("\x1b]1337;UnicodeVersion=push\x1b\\");
+    term.print("\x1b]1337;UnicodeVersion=9\x1b\\");
+    term.print("\x1b]1337;UnicodeVersion=pop\x1b\\");
+    assert_eq!(term.unicode_version().version, 8);
+    term.print(emoji);
+    term.assert_cursor_pos(3, 0, Some("emoji are narrow in unicode 8"), None);
+
+    term.print("\x1b]1337;UnicodeVersion=pop outer\x1b\\");
+    assert_eq!(term.unicode_version(), UnicodeVersion::default());
+    term.print(emoji);
+    term.assert_cursor_pos(5, 0, None, None);
+}
+
+#[test]
+fn test_ambiguous_width_reflow() {
+    let mut term = TestTerm::new(3, 4, 0);
+    term.print("\u{a7}\u{a7}a");
+    assert_visible_contents(&term, file!(), line!(), &["\u{a7}\u{a7}a ", "    ", "    "]);
+
+    term.set_config(Arc::new(TestTermConfig {
+        scrollback: 0,
+        unicode_version: UnicodeVersion {
+            version: 9,
+            ambiguous_are_wide: true,
+        },
+        compatibility: TerminalCompatibility::default(),
+    }));
+    assert_visible_contents(&term, file!(), line!(), &["\u{a7}\u{a7}", "a", "    "]);
+}
+
+#[test]
+fn test_cursor_style_alt_screen() {
+    let mut term = TestTerm::new(3, 4, 0);
+
+    // The style set by the shell is restored after a full screen
+    // application changes it on the alternate screen
+    term.print("\x1b[4 q");
+    for (enter, leave) in &[
+        ("\x1b[?1049h", "\x1b[?1049l"),
+        ("\x1b[?1047h", "\x1b[?1047l"),
+        ("\x1b[?47h", "\x1b[?47l"),
+    ] {
+        term.print(enter);
+        term.print("\x1b[6 q");
+        assert_eq!(term.cursor_pos().shape, CursorShape::SteadyBar);
+        term.print(leave);
+        assert_eq!(term.cursor_pos().shape, CursorShape::SteadyUnderline);
+    }
+
+    // Mode 12 toggles blinking, resolving the default style
+    term.print("\x1b[ q\x1b[?12h");
+    assert_eq!(term.cursor_pos().shape, CursorShape::BlinkingBlock);
+    term.print("\x1b[?12l");
+    assert_eq!(term.cursor_pos().shape, CursorShape::SteadyBlock);
+}
+
+#[test]
+fn test_dynamic_cursor_and_selection_colors() {
+    let mut term = TestTerm::new(1, 4, 0);
+    let defaults = ColorPalette::default();
+    let red = RgbColor::new_8bpc(0xff, 0, 0);
+    let blue = RgbColor::new_8bpc(0, 0, 0xff);
+
+    term.print("\x1b]12;red\x1b\\");
+    assert_eq!(term.palette().cursor_bg, red);
+    assert_eq!(term.palette().cursor_border, red);
+
+    // Selection background and foreground
+    term.print("\x1b]17;red\x1b\\\x1b]19;rgb:0000/0000/ffff\x1b\\");
+    assert_eq!(term.palette().selection_bg, red);
+    assert_eq!(term.palette().selection_fg, blue);
+
+    term.print("\x1b]112\x1b\\\x1b]117\x1b\\\x1b]119\x1b\\");
+    assert_eq!(term.palette(), defaults);
+}
+
+#[test]
+fn test_memory_usage() {
+    let mut term = TestTerm::new(3, 10, 100);
+    let empty = term.memory_usage();
+    for i in 0..50 {
+        term.print(format!("line {}\r\n", i));
+    }
+    let full = term.memory_usage();
+    assert!(full > empty);
+
+    // The alternate screen is counted as well
+    term.print("\x1b[?1049h");
+    term.print("alternate");
+    assert!(term.memory_usage() >= full);
+}
@@ -258,6 +258,19 @@ impl CellAttributes {
         }
     }
 
+    /// Returns the number of bytes that these attributes have allocated
+    /// on the heap, not counting image data that is shared with other cells
+    pub fn heap_size(&self) -> usize {
+        match &self.fat {
+            Some(fat) => {
+                std::mem::size_of::<FatAttributes>()
+                    + fat.image.capacity() * std::mem::size_of::<Box<ImageCell>>()
+                    + fat.image.len() * std::mem::size_of::<ImageCell>()
+            }
+            None => 0,
+        }
+    }
+
     /// Returns true if the attribute bits in both objects are equal.
     /// This can be used to cheaply test whether the styles of the two
     /// cells are the same, and is used by some `Renderer` implementations.
@@ -641,6 +654,17 @@ impl TeenyString {
         })
     }
 
+    /// Returns the number of bytes allocated on the heap for strings
+    /// that are too long to be stored inline
+    pub fn heap_size(&self) -> usize {
+        if Self::is_marker_bit_set(self.0) {
+            0
+        } else {
+            let vec = self.0 as *const usize as *const Vec<u8>;
+            std::mem::size_of::<Vec<u8>>() + unsafe { (*vec).capacity() }
+        }
+    }
+
     pub fn from_char(c: char) -> Self {
         let mut bytes = [0u8; 8];
         Self::from_str(c.encode_utf8(&mut bytes), None)
@@ -809,6 +833,11 @@ impl Cell {
         self.text.width()
     }
 
+    /// Returns the number of bytes that the cell has allocated on the heap
+    pub fn heap_size(&self) -> usize {
+        self.text.heap_size() + self.attrs.heap_size()
+    }
+
     /// Returns the attributes of the cell
     pub fn attrs(&self) -> &CellAttributes {
         &self.attrs
@@ -829,11 +858,49 @@ pub fn unicode_column_width(s: &str) -> usize {
 /// Returns the number of cells visually occupied by a grapheme.
 /// The input string must be a single grapheme.
 pub fn grapheme_column_width(s: &str) -> usize {
+    grapheme_column_width_for_version(s, UnicodeVersion::default())
+}
+
+/// Controls how the width of a grapheme is computed
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnicodeVersion {
+    /// The version of the unicode standard.  Characters with emoji
+    /// presentation are two cells wide from version 9 onwards, and
+    /// a single cell wide in earlier versions.
+    pub version: u8,
+    /// Whether characters whose East Asian Width property is
+    /// Ambiguous are two cells wide, as is the convention in
+    /// CJK locales
+    pub ambiguous_are_wide: bool,
+}
+
+impl Default for UnicodeVersion {
+    fn default() -> Self {
+        Self {
+            version: 9,
+            ambiguous_are_wide: false,
+        }
+    }
+}
+
+/// Returns the number of cells visually occupied by a grapheme,
+/// according to the rules of `version`.
+/// The input string must be a single grapheme.
+pub fn grapheme_column_width_for_version(s: &str, version: UnicodeVersion) -> usize {
+    let text_width = || {
+        if version.ambiguous_are_wide {
+            UnicodeWidthStr::width_cjk(s).min(2)
+        } else {
+            UnicodeWidthStr::width(s).min(2)
+        }
+    };
+    let emoji_width = if version.version >= 9 { 2 } else { 1 };
     match Presentation::for_grapheme(s) {
-        (_, Some(Presentation::Emoji)) => 2,
+        (_, Some(Presentation::Emoji)) => emoji_width,
         (_, Some(Presentation::Text)) => 1,
-        (Presentation::Emoji, None) => 2,
-        (Presentation::Text, None) => UnicodeWidthStr::width(s).min(2),
+        (Presentation::Emoji, None) => emoji_width,
+        (Presentation::Text, None) => text_width(),
     }
 }
 
@@ -899,6 +966,25 @@ mod test {
         }
     }
 
+    #[test]
+    fn width_for_version() {
+        let ambiguous = "\u{00a7}";
+        let emoji = "\u{1f600}";
+        let cjk = UnicodeVersion {
+            version: 9,
+            ambiguous_are_wide: true,
+        };
+        let v8 = UnicodeVersion {
+            version: 8,
+            ambiguous_are_wide: false,
+        };
+        assert_eq!(grapheme_column_width(ambiguous), 1);
+        assert_eq!(grapheme_column_width_for_version(ambiguous, cjk), 2);
+        assert_eq!(grapheme_column_width_for_version("a", cjk), 1);
+        assert_eq!(grapheme_column_width(emoji), 2);
+        assert_eq!(grapheme_column_width_for_version(emoji, v8), 1);
+    }
+
     #[test]
     fn test_width() {
         let foot = "\u{1f9b6}";
@@ -32,6 +32,18 @@ impl CellCluster {
     pub fn make_cluster<'a>(
         hint: usize,
         iter: impl Iterator<Item = (usize, &'a Cell)>,
+    ) -> Vec<CellCluster> {
+        Self::make_cluster_with_breaks(hint, iter, |_| false)
+    }
+
+    /// Like `make_cluster`, but additionally starts a new cluster at
+    /// each cell index for which `break_before` returns true.
+    /// Text is shaped one cluster at a time, so this prevents
+    /// ligatures from being formed across those cells.
+    pub fn make_cluster_with_breaks<'a>(
+        hint: usize,
+        iter: impl Iterator<Item = (usize, &'a Cell)>,
+        break_before: impl Fn(usize) -> bool,
     ) -> Vec<CellCluster> {
         let mut last_cluster = None;
         let mut clusters = Vec::new();
@@ -63,7 +75,10 @@ impl CellCluster {
                     ))
                 }
                 Some(mut last) => {
-                    if last.attrs != *normalized_attr || last.presentation != presentation {
+                    if last.attrs != *normalized_attr
+                        || last.presentation != presentation
+                        || break_before(cell_idx)
+                    {
                         // Flush pending cluster and start a new one
                         clusters.push(last);
 
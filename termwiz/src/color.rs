@@ -187,6 +187,70 @@ impl RgbColor {
         (to_linear(red), to_linear(green), to_linear(blue), 1.0)
     }
 
+    /// Returns the relative luminance of the color as defined by
+    /// WCAG 2.0, in the range 0.0 (black) to 1.0 (white).
+    pub fn relative_luminance(self) -> f32 {
+        let (red, green, blue, _alpha) = self.to_linear_tuple_rgba();
+        0.2126 * red + 0.7152 * green + 0.0722 * blue
+    }
+
+    /// Returns the WCAG 2.0 contrast ratio between this color and `other`,
+    /// in the range 1.0 (identical luminance) to 21.0 (black and white).
+    pub fn contrast_ratio(self, other: Self) -> f32 {
+        let a = self.relative_luminance();
+        let b = other.relative_luminance();
+        let (lighter, darker) = if a > b { (a, b) } else { (b, a) };
+        (lighter + 0.05) / (darker + 0.05)
+    }
+
+    /// Treating this color as a foreground color drawn over `bg`,
+    /// returns a version of it that has a contrast ratio of at least
+    /// `min_ratio` with `bg`.
+    /// The color is blended towards white if it is lighter than `bg`,
+    /// or towards black if it is darker, by the smallest amount that
+    /// reaches `min_ratio`.  If that direction cannot reach `min_ratio`
+    /// then whichever direction produces the most contrast is used.
+    pub fn ensure_contrast_ratio(self, bg: Self, min_ratio: f32) -> Self {
+        if self.contrast_ratio(bg) >= min_ratio {
+            return self;
+        }
+
+        let white = Self::new_8bpc(0xff, 0xff, 0xff);
+        let black = Self::new_8bpc(0, 0, 0);
+        let (preferred, other) = if self.relative_luminance() >= bg.relative_luminance() {
+            (white, black)
+        } else {
+            (black, white)
+        };
+        let target = if preferred.contrast_ratio(bg) >= min_ratio
+            || preferred.contrast_ratio(bg) >= other.contrast_ratio(bg)
+        {
+            preferred
+        } else {
+            other
+        };
+
+        let (red, green, blue, _) = self.to_tuple_rgba();
+        let (t_red, t_green, t_blue, _) = target.to_tuple_rgba();
+        let blend = |amount: f32| {
+            let mix = |from: f32, to: f32| ((from + (to - from) * amount) * 255.).round() as u8;
+            Self::new_8bpc(mix(red, t_red), mix(green, t_green), mix(blue, t_blue))
+        };
+
+        // Binary search for the smallest blend that is sufficient
+        let mut low = 0.0f32;
+        let mut high = 1.0f32;
+        for _ in 0..12 {
+            let mid = (low + high) / 2.;
+            if blend(mid).contrast_ratio(bg) >= min_ratio {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        blend(high)
+    }
+
     /// Construct a color from an X11/SVG/CSS3 color name.
     /// Returns None if the supplied name is not recognized.
     /// The list of names can be found here:
@@ -439,6 +503,29 @@ mod tests {
         assert_eq!(dark_green.bits, 0x006400);
     }
 
+    #[test]
+    fn contrast() {
+        let white = RgbColor::new_8bpc(0xff, 0xff, 0xff);
+        let black = RgbColor::new_8bpc(0, 0, 0);
+        assert!((white.contrast_ratio(black) - 21.0).abs() < 0.01);
+        assert_eq!(white.contrast_ratio(white), 1.0);
+
+        let grey = RgbColor::new_8bpc(0x80, 0x80, 0x80);
+        let dark_grey = RgbColor::new_8bpc(0x70, 0x70, 0x70);
+        // Already sufficient contrast: unchanged
+        assert_eq!(white.ensure_contrast_ratio(black, 4.5), white);
+
+        // Darker than the background: pushed towards black
+        let adjusted = dark_grey.ensure_contrast_ratio(grey, 3.0);
+        assert!(adjusted.contrast_ratio(grey) >= 3.0);
+        assert!(adjusted.relative_luminance() < dark_grey.relative_luminance());
+
+        // Lighter than a dark background: pushed towards white
+        let adjusted = grey.ensure_contrast_ratio(dark_grey, 3.0);
+        assert!(adjusted.contrast_ratio(dark_grey) >= 3.0);
+        assert!(adjusted.relative_luminance() > grey.relative_luminance());
+    }
+
     #[test]
     fn from_rgb() {
         assert!(RgbColor::from_rgb_str("").is_none());
@@ -692,6 +692,9 @@ pub enum DecPrivateModeCode {
     /// enable mouse reporting itself, it just controls how reports
     /// will be encoded.
     SGRMouse = 1006,
+    /// Like SGRMouse, but reports the position of the mouse
+    /// in pixels rather than cells
+    SGRPixelsMouse = 1016,
     /// Save cursor as in DECSC
     SaveCursor = 1048,
     ClearAndEnableAlternateScreen = 1049,
@@ -971,6 +974,87 @@ pub enum Edit {
 
     /// REP - Repeat the preceding character n times
     Repeat(u32),
+
+    /// DECFRA - Fill Rectangular Area with the character whose
+    /// code is `ch`, using the current graphic rendition
+    FillRectangularArea { ch: u32, area: RectangularArea },
+
+    /// DECERA - Erase Rectangular Area
+    EraseRectangularArea(RectangularArea),
+
+    /// DECSERA - Selective Erase Rectangular Area.
+    /// Erases the characters in the area, leaving their
+    /// attributes unchanged
+    SelectiveEraseRectangularArea(RectangularArea),
+
+    /// DECCRA - Copy Rectangular Area.
+    /// The characters and attributes of `src` are copied so that
+    /// the top left corner of `src` is placed at `dest_top`, `dest_left`
+    CopyRectangularArea {
+        src: RectangularArea,
+        src_page: u32,
+        dest_top: OneBased,
+        dest_left: OneBased,
+        dest_page: u32,
+    },
+
+    /// DECCARA - Change Attributes in Rectangular Area.
+    /// The attributes are a subset of the SGR codes: Reset,
+    /// IntensityBold, UnderlineOn, BlinkOn and InverseOn, along with
+    /// their corresponding Off codes
+    ChangeAttributesInRectangularArea {
+        area: RectangularArea,
+        attributes: Vec<SgrCode>,
+    },
+
+    /// DECRARA - Reverse Attributes in Rectangular Area.
+    /// Each of the attributes, which may be IntensityBold, UnderlineOn,
+    /// BlinkOn or InverseOn, is toggled.  Reset toggles all of them.
+    ReverseAttributesInRectangularArea {
+        area: RectangularArea,
+        attributes: Vec<SgrCode>,
+    },
+
+    /// DECSACE - Select Attribute Change Extent.
+    /// Controls whether DECCARA and DECRARA apply to the rectangle
+    /// between their corners, or to the stream of characters from
+    /// the first corner to the second
+    SelectAttributeChangeExtent(AttributeChangeExtent),
+}
+
+/// The corners of the area affected by one of the DEC
+/// rectangular area operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RectangularArea {
+    pub top: OneBased,
+    pub left: OneBased,
+    /// Defaults to the bottom of the screen
+    pub bottom: OneBased,
+    /// Defaults to the right of the screen
+    pub right: OneBased,
+}
+
+impl Display for RectangularArea {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(f, "{};{};", self.top, self.left)?;
+        // Leave the defaults empty
+        if self.bottom.as_one_based() != u32::max_value() {
+            write!(f, "{}", self.bottom)?;
+        }
+        write!(f, ";")?;
+        if self.right.as_one_based() != u32::max_value() {
+            write!(f, "{}", self.right)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+pub enum AttributeChangeExtent {
+    /// The stream of characters from the first corner to the second
+    Stream = 1,
+    /// The rectangle between the corners
+    Rectangle = 2,
 }
 
 trait EncodeCSIParam {
@@ -1021,6 +1105,35 @@ impl Display for Edit {
             Edit::ScrollUp(n) => n.write_csi(f, "S")?,
             Edit::EraseInDisplay(n) => n.write_csi(f, "J")?,
             Edit::Repeat(n) => n.write_csi(f, "b")?,
+            Edit::FillRectangularArea { ch, area } => write!(f, "{};{}$x", ch, area)?,
+            Edit::EraseRectangularArea(area) => write!(f, "{}$z", area)?,
+            Edit::SelectiveEraseRectangularArea(area) => write!(f, "{}${{", area)?,
+            Edit::CopyRectangularArea {
+                src,
+                src_page,
+                dest_top,
+                dest_left,
+                dest_page,
+            } => write!(
+                f,
+                "{};{};{};{};{};{}$v",
+                src, src_page, dest_top, dest_left, dest_page
+            )?,
+            Edit::ChangeAttributesInRectangularArea { area, attributes } => {
+                write!(f, "{}", area)?;
+                for code in attributes {
+                    write!(f, ";{}", code.clone() as i64)?;
+                }
+                write!(f, "$r")?;
+            }
+            Edit::ReverseAttributesInRectangularArea { area, attributes } => {
+                write!(f, "{}", area)?;
+                for code in attributes {
+                    write!(f, ";{}", code.clone() as i64)?;
+                }
+                write!(f, "$t")?;
+            }
+            Edit::SelectAttributeChangeExtent(extent) => write!(f, "{}*x", *extent as i64)?,
         }
         Ok(())
     }
@@ -1522,6 +1635,33 @@ impl<'a> CSIParser<'a> {
         match (self.control, self.orig_params) {
             ('q', [.., CsiParam::P(b' ')]) => self.cursor_style(params),
             ('y', [.., CsiParam::P(b'*')]) => self.checksum_area(params),
+            ('x', [.., CsiParam::P(b'*')]) => self.decsace(params),
+            ('x', [.., CsiParam::P(b'$')]) => self.decfra(params),
+            ('z', [.., CsiParam::P(b'$')]) => self
+                .rectangular_area(params)
+                .map(|(area, _)| CSI::Edit(Edit::EraseRectangularArea(area))),
+            ('{', [.., CsiParam::P(b'$')]) => self
+                .rectangular_area(params)
+                .map(|(area, _)| CSI::Edit(Edit::SelectiveEraseRectangularArea(area))),
+            ('v', [.., CsiParam::P(b'$')]) => self.deccra(params),
+            ('r', [.., CsiParam::P(b'$')]) => {
+                self.rectangular_area(params)
+                    .and_then(|(area, attributes)| {
+                        Ok(CSI::Edit(Edit::ChangeAttributesInRectangularArea {
+                            area,
+                            attributes: Self::area_attributes(&attributes, true)?,
+                        }))
+                    })
+            }
+            ('t', [.., CsiParam::P(b'$')]) => {
+                self.rectangular_area(params)
+                    .and_then(|(area, attributes)| {
+                        Ok(CSI::Edit(Edit::ReverseAttributesInRectangularArea {
+                            area,
+                            attributes: Self::area_attributes(&attributes, false)?,
+                        }))
+                    })
+            }
 
             ('c', [CsiParam::P(b'='), ..]) => self
                 .req_tertiary_device_attributes(params)
@@ -1656,6 +1796,12 @@ impl<'a> CSIParser<'a> {
                     Ok(self.advance_by(2, params, CSI::Cursor(Cursor::CursorStyle(style))))
                 }
             },
+            // An omitted parameter selects the default style
+            [CsiParam::P(b' ')] => Ok(self.advance_by(
+                1,
+                params,
+                CSI::Cursor(Cursor::CursorStyle(CursorStyle::Default)),
+            )),
             _ => Err(()),
         }
     }
@@ -1679,6 +1825,96 @@ impl<'a> CSIParser<'a> {
         }))
     }
 
+    /// Parses the corners of a rectangular area from the start of the
+    /// parameters of one of the DEC rectangular area operations, whose
+    /// final parameter is its `$` intermediate.
+    /// Returns the area and the values of any remaining parameters.
+    fn rectangular_area(
+        &mut self,
+        params: &'a [CsiParam],
+    ) -> Result<(RectangularArea, Vec<i64>), ()> {
+        let params = Cracked::parse(&params[..params.len() - 1])?;
+        let area = Self::area_at(&params, 0)?;
+        let rest = (4..params.len())
+            .map(|idx| params.opt_int(idx).unwrap_or(0))
+            .collect();
+        Ok((area, rest))
+    }
+
+    fn area_at(params: &Cracked, idx: usize) -> Result<RectangularArea, ()> {
+        let big = |p: Option<&CsiParam>| match p {
+            Some(p) => OneBased::from_esc_param_with_big_default(p),
+            None => Ok(OneBased::new(u32::max_value())),
+        };
+        Ok(RectangularArea {
+            top: OneBased::from_optional_esc_param(params.get(idx))?,
+            left: OneBased::from_optional_esc_param(params.get(idx + 1))?,
+            bottom: big(params.get(idx + 2))?,
+            right: big(params.get(idx + 3))?,
+        })
+    }
+
+    /// Validates the attributes of DECCARA, which may turn attributes
+    /// off as well as on, or DECRARA, which may only toggle them
+    fn area_attributes(values: &[i64], allow_off: bool) -> Result<Vec<SgrCode>, ()> {
+        if values.is_empty() {
+            return Ok(vec![SgrCode::Reset]);
+        }
+        values
+            .iter()
+            .map(|&value| match FromPrimitive::from_i64(value) {
+                Some(
+                    code @ SgrCode::Reset
+                    | code @ SgrCode::IntensityBold
+                    | code @ SgrCode::UnderlineOn
+                    | code @ SgrCode::BlinkOn
+                    | code @ SgrCode::InverseOn,
+                ) => Ok(code),
+                Some(
+                    code @ SgrCode::NormalIntensity
+                    | code @ SgrCode::UnderlineOff
+                    | code @ SgrCode::BlinkOff
+                    | code @ SgrCode::InverseOff,
+                ) if allow_off => Ok(code),
+                _ => Err(()),
+            })
+            .collect()
+    }
+
+    fn decfra(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
+        let params = Cracked::parse(&params[..params.len() - 1])?;
+        let ch = params.int(0)?;
+        if ch < 0 || ch > i64::from(u32::max_value()) {
+            return Err(());
+        }
+        Ok(CSI::Edit(Edit::FillRectangularArea {
+            ch: ch as u32,
+            area: Self::area_at(&params, 1)?,
+        }))
+    }
+
+    fn deccra(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
+        let params = Cracked::parse(&params[..params.len() - 1])?;
+        let page = |idx: usize| params.opt_int(idx).unwrap_or(1).max(1) as u32;
+        Ok(CSI::Edit(Edit::CopyRectangularArea {
+            src: Self::area_at(&params, 0)?,
+            src_page: page(4),
+            dest_top: OneBased::from_optional_esc_param(params.get(5))?,
+            dest_left: OneBased::from_optional_esc_param(params.get(6))?,
+            dest_page: page(7),
+        }))
+    }
+
+    fn decsace(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
+        let params = Cracked::parse(&params[..params.len() - 1])?;
+        let extent = match params.opt_int(0).unwrap_or(0) {
+            0 | 1 => AttributeChangeExtent::Stream,
+            2 => AttributeChangeExtent::Rectangle,
+            _ => return Err(()),
+        };
+        Ok(CSI::Edit(Edit::SelectAttributeChangeExtent(extent)))
+    }
+
     fn dsr(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
         match params {
             [CsiParam::Integer(5)] => {
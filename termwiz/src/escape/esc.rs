@@ -42,6 +42,12 @@ pub enum EscCode {
     SingleShiftG2 = esc!('N'),
     /// SS3 Single shift of G3 character set affects next character only
     SingleShiftG3 = esc!('O'),
+    /// LS1R - Locking shift of the G1 character set into GR
+    LockingShiftG1Right = esc!('~'),
+    /// LS2R - Locking shift of the G2 character set into GR
+    LockingShiftG2Right = esc!('}'),
+    /// LS3R - Locking shift of the G3 character set into GR
+    LockingShiftG3Right = esc!('|'),
     /// SPA - Start of Guarded Area
     StartOfGuardedArea = esc!('V'),
     /// EPA - End of Guarded Area
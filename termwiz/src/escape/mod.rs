@@ -27,6 +27,10 @@ use vtparse::CsiParam;
 pub enum Action {
     /// Send a single printable character to the display
     Print(char),
+    /// Send a run of printable characters to the display.  This is
+    /// equivalent to a `Print` for each character, and is produced by
+    /// the parser for plain text that has no escapes or controls.
+    PrintString(String),
     /// A C0 or C1 control code
     Control(ControlCode),
     /// Device control.  This is uncommon wrt. terminal emulation.
@@ -38,6 +42,8 @@ pub enum Action {
     CSI(CSI),
     Esc(Esc),
     Sixel(Box<Sixel>),
+    /// DECDLD - load a soft font
+    SoftFont(Box<SoftFont>),
     /// A list of termcap, terminfo names for which the application
     /// whats information
     XtGetTcap(Vec<String>),
@@ -50,12 +56,14 @@ impl Display for Action {
     fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
         match self {
             Action::Print(c) => write!(f, "{}", c),
+            Action::PrintString(s) => write!(f, "{}", s),
             Action::Control(c) => f.write_char(*c as u8 as char),
             Action::DeviceControl(c) => c.fmt(f),
             Action::OperatingSystemCommand(osc) => osc.fmt(f),
             Action::CSI(csi) => csi.fmt(f),
             Action::Esc(esc) => esc.fmt(f),
             Action::Sixel(sixel) => sixel.fmt(f),
+            Action::SoftFont(font) => font.fmt(f),
             Action::XtGetTcap(names) => {
                 write!(f, "\x1bP+q")?;
                 for (i, name) in names.iter().enumerate() {
@@ -333,6 +341,136 @@ impl Display for Sixel {
     }
 }
 
+/// DECDLD - a soft font, also known as a dynamically redefinable
+/// character set (DRCS).  Once loaded, the font is selected into
+/// G0 or G1 using its designator, just like the built-in character sets.
+/// See <https://vt100.net/docs/vt510-rm/DECDLD.html>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoftFont {
+    /// Pfn - the font buffer to load
+    pub font_number: i64,
+    /// Pcn - the position of the first character to load
+    pub starting_char: i64,
+    /// Pe - which of the existing characters to erase:
+    /// 0 erases this font, 1 erases only the characters being
+    /// loaded and 2 erases all soft fonts
+    pub erase_control: i64,
+    /// Pcmw - the character matrix width.  0 selects the default,
+    /// 2, 3 and 4 select the VT220 5x10, 6x10 and 7x10 matrices,
+    /// otherwise it is the width in pixels.
+    pub matrix_width: i64,
+    /// Pss - the font set size, which selects the number of columns
+    /// and lines of the screen that the font was designed for
+    pub font_set_size: i64,
+    /// Pt - 0 and 1 indicate a text font, 2 a full cell font
+    pub text_or_full_cell: i64,
+    /// Pcmh - the character matrix height in pixels, or 0 for the default
+    pub matrix_height: i64,
+    /// Pcss - true if this is a 96 character set, false for 94
+    pub is_96_char_set: bool,
+    /// Dscs - the intermediates and final byte used to designate
+    /// this font with SCS
+    pub designator: Vec<u8>,
+    /// The sixel bitmaps of the characters, in order starting with
+    /// `starting_char`.  Each glyph is a list of sixel rows, with
+    /// each row being a list of sixel values, one per column.
+    pub glyphs: Vec<Vec<Vec<SixelValue>>>,
+}
+
+impl SoftFont {
+    /// The widest character matrix that a DEC terminal accepts
+    pub const MAX_MATRIX_WIDTH: usize = 15;
+    /// The tallest character matrix that a DEC terminal accepts
+    pub const MAX_MATRIX_HEIGHT: usize = 16;
+
+    /// Returns the width, height of the character matrix, or None if
+    /// it is larger than `MAX_MATRIX_WIDTH` by `MAX_MATRIX_HEIGHT`
+    pub fn matrix_size(&self) -> Option<(usize, usize)> {
+        let data_width = self
+            .glyphs
+            .iter()
+            .flat_map(|glyph| glyph.iter().map(|row| row.len()))
+            .max()
+            .unwrap_or(0);
+        let data_height = self
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.len() * 6)
+            .max()
+            .unwrap_or(0);
+
+        let width = match self.matrix_width {
+            2 => 5,
+            3 => 6,
+            4 => 7,
+            w if w > 4 => w as usize,
+            _ => data_width,
+        };
+        let height = if self.matrix_height > 0 {
+            self.matrix_height as usize
+        } else {
+            data_height
+        };
+
+        if width > Self::MAX_MATRIX_WIDTH || height > Self::MAX_MATRIX_HEIGHT {
+            None
+        } else {
+            Some((width.max(1), height.max(1)))
+        }
+    }
+
+    /// Returns the character that the glyph at `idx` replaces
+    pub fn char_for_glyph(&self, idx: usize) -> Option<char> {
+        let mut first = 0x20 + self.starting_char.max(0) as u32;
+        if !self.is_96_char_set && first == 0x20 {
+            // Space can't be redefined in a 94 character set
+            first = 0x21;
+        }
+        let last = if self.is_96_char_set { 0x7f } else { 0x7e };
+        let c = first + idx as u32;
+        if c <= last {
+            std::char::from_u32(c)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for SoftFont {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(
+            f,
+            "\x1bP{};{};{};{};{};{};{};{}{{",
+            self.font_number,
+            self.starting_char,
+            self.erase_control,
+            self.matrix_width,
+            self.font_set_size,
+            self.text_or_full_cell,
+            self.matrix_height,
+            if self.is_96_char_set { 1 } else { 0 }
+        )?;
+        for b in &self.designator {
+            f.write_char(*b as char)?;
+        }
+        for (idx, glyph) in self.glyphs.iter().enumerate() {
+            if idx > 0 {
+                f.write_char(';')?;
+            }
+            for (row_idx, row) in glyph.iter().enumerate() {
+                if row_idx > 0 {
+                    f.write_char('/')?;
+                }
+                for d in row {
+                    f.write_char((d + 0x3f) as char)?;
+                }
+            }
+        }
+        // As for Sixel, the data itself doesn't contain the ST
+        Ok(())
+    }
+}
+
 /// A decoded 6-bit sixel value.
 /// Each sixel represents a six-pixel tall bitmap where
 /// the least significant bit is the topmost bit.
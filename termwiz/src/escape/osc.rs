@@ -826,6 +826,52 @@ pub enum ITermProprietary {
     SetBadgeFormat(String),
     /// Download file data from the application.
     File(Box<ITermFileData>),
+    /// Changes the version of unicode used to compute the width
+    /// of characters
+    UnicodeVersion(ITermUnicodeVersionOp),
+    /// A wezterm extension that asks the terminal to perform an
+    /// action on behalf of the program.  The request is a JSON
+    /// document, which is base64 encoded in the sequence.
+    RemoteControl(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ITermUnicodeVersionOp {
+    /// Use this version of unicode
+    Set(u8),
+    /// Save the current version on a stack, optionally
+    /// associating it with a label
+    Push(Option<String>),
+    /// Restore the most recently pushed version.  If a label is
+    /// specified, the stack is popped until an entry with that
+    /// label has been removed.
+    Pop(Option<String>),
+}
+
+impl ITermUnicodeVersionOp {
+    fn parse(param: &str) -> Result<Self> {
+        let mut iter = param.splitn(2, ' ');
+        let op = iter.next().ok_or_else(|| format!("bad params"))?;
+        let label = iter.next().map(|label| label.to_string());
+        match op {
+            "push" => Ok(Self::Push(label)),
+            "pop" => Ok(Self::Pop(label)),
+            version if label.is_none() => Ok(Self::Set(version.parse()?)),
+            _ => bail!("invalid UnicodeVersion {}", param),
+        }
+    }
+}
+
+impl Display for ITermUnicodeVersionOp {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::Set(version) => write!(f, "{}", version),
+            Self::Push(Some(label)) => write!(f, "push {}", label),
+            Self::Push(None) => write!(f, "push"),
+            Self::Pop(Some(label)) => write!(f, "pop {}", label),
+            Self::Pop(None) => write!(f, "pop"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -1131,6 +1177,22 @@ impl ITermProprietary {
             }
         }
 
+        if osc.len() == 2 && keyword == "RemoteControl" {
+            if let Some(p1) = p1 {
+                return Ok(ITermProprietary::RemoteControl(String::from_utf8(
+                    base64::decode(p1)?,
+                )?));
+            }
+        }
+
+        if osc.len() == 2 && keyword == "UnicodeVersion" {
+            if let Some(p1) = p1 {
+                return Ok(ITermProprietary::UnicodeVersion(
+                    ITermUnicodeVersionOp::parse(p1)?,
+                ));
+            }
+        }
+
         if keyword == "File" {
             return Ok(ITermProprietary::File(Box::new(ITermFileData::parse(osc)?)));
         }
@@ -1166,6 +1228,8 @@ impl Display for ITermProprietary {
             }
             SetBadgeFormat(s) => write!(f, "SetBadgeFormat={}", base64::encode(s))?,
             File(file) => file.fmt(f)?,
+            UnicodeVersion(op) => write!(f, "UnicodeVersion={}", op)?,
+            RemoteControl(s) => write!(f, "RemoteControl={}", base64::encode(s))?,
         }
         Ok(())
     }
@@ -1541,6 +1605,46 @@ mod test {
             ))
         );
 
+        assert_eq!(
+            parse(
+                &["1337", "RemoteControl=eyJhY3Rpb24iOiJHZXRMYXlvdXQifQ=="],
+                "\x1b]1337;RemoteControl=eyJhY3Rpb24iOiJHZXRMYXlvdXQifQ==\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::RemoteControl(
+                r#"{"action":"GetLayout"}"#.into()
+            ))
+        );
+
+        assert_eq!(
+            parse(
+                &["1337", "UnicodeVersion=8"],
+                "\x1b]1337;UnicodeVersion=8\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::UnicodeVersion(
+                ITermUnicodeVersionOp::Set(8)
+            ))
+        );
+
+        assert_eq!(
+            parse(
+                &["1337", "UnicodeVersion=push vim"],
+                "\x1b]1337;UnicodeVersion=push vim\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::UnicodeVersion(
+                ITermUnicodeVersionOp::Push(Some("vim".into()))
+            ))
+        );
+
+        assert_eq!(
+            parse(
+                &["1337", "UnicodeVersion=pop"],
+                "\x1b]1337;UnicodeVersion=pop\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::UnicodeVersion(
+                ITermUnicodeVersionOp::Pop(None)
+            ))
+        );
+
         assert_eq!(
             parse(
                 &["1337", "ReportCellSize=12.0", "15.5"],
@@ -2,7 +2,7 @@
 use crate::color::RgbColor;
 use crate::escape::{
     Action, DeviceControlMode, EnterDeviceControlMode, Esc, OperatingSystemCommand,
-    ShortDeviceControl, Sixel, SixelData, CSI,
+    ShortDeviceControl, Sixel, SixelData, SoftFont, CSI,
 };
 use log::error;
 use num_traits::FromPrimitive;
@@ -48,11 +48,79 @@ impl GetTcapBuilder {
     }
 }
 
+struct SoftFontBuilder {
+    font: SoftFont,
+    /// true once the Dscs designator has been read
+    have_designator: bool,
+}
+
+impl SoftFontBuilder {
+    fn new(params: &[i64]) -> Self {
+        let param = |idx: usize| params.get(idx).cloned().unwrap_or(0);
+        Self {
+            font: SoftFont {
+                font_number: param(0),
+                starting_char: param(1),
+                erase_control: param(2),
+                matrix_width: param(3),
+                font_set_size: param(4),
+                text_or_full_cell: param(5),
+                matrix_height: param(6),
+                is_96_char_set: param(7) == 1,
+                designator: vec![],
+                glyphs: vec![vec![vec![]]],
+            },
+            have_designator: false,
+        }
+    }
+
+    fn push(&mut self, data: u8) {
+        if !self.have_designator {
+            // Dscs is zero or more intermediates followed by a final byte
+            match data {
+                0x20..=0x2f => self.font.designator.push(data),
+                0x30..=0x7e => {
+                    self.font.designator.push(data);
+                    self.have_designator = true;
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        match data {
+            b';' => self.font.glyphs.push(vec![vec![]]),
+            b'/' => {
+                if let Some(glyph) = self.font.glyphs.last_mut() {
+                    glyph.push(vec![]);
+                }
+            }
+            0x3f..=0x7e => {
+                if let Some(row) = self.font.glyphs.last_mut().and_then(|g| g.last_mut()) {
+                    row.push(data - 0x3f);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(mut self) -> SoftFont {
+        // A trailing separator doesn't introduce another glyph
+        if let Some(glyph) = self.font.glyphs.last() {
+            if glyph.len() == 1 && glyph[0].is_empty() {
+                self.font.glyphs.pop();
+            }
+        }
+        self.font
+    }
+}
+
 #[derive(Default)]
 struct ParseState {
     sixel: Option<SixelBuilder>,
     dcs: Option<ShortDeviceControl>,
     get_tcap: Option<GetTcapBuilder>,
+    soft_font: Option<SoftFontBuilder>,
 }
 
 /// The `Parser` struct holds the state machine that is used to decode
@@ -175,6 +243,10 @@ impl<'a, F: FnMut(Action)> VTActor for Performer<'a, F> {
         (self.callback)(Action::Print(c));
     }
 
+    fn print_str(&mut self, text: &str) {
+        (self.callback)(Action::PrintString(text.to_string()));
+    }
+
     fn execute_c0_or_c1(&mut self, byte: u8) {
         match FromPrimitive::from_u8(byte) {
             Some(code) => (self.callback)(Action::Control(code)),
@@ -203,10 +275,13 @@ impl<'a, F: FnMut(Action)> VTActor for Performer<'a, F> {
         self.state.sixel.take();
         self.state.get_tcap.take();
         self.state.dcs.take();
+        self.state.soft_font.take();
         if byte == b'q' && intermediates.is_empty() && !ignored_extra_intermediates {
             self.state.sixel.replace(SixelBuilder::new(params));
         } else if byte == b'q' && intermediates == [b'+'] {
             self.state.get_tcap.replace(GetTcapBuilder::default());
+        } else if byte == b'{' && intermediates.is_empty() && !ignored_extra_intermediates {
+            self.state.soft_font.replace(SoftFontBuilder::new(params));
         } else if !ignored_extra_intermediates && is_short_dcs(intermediates, byte) {
             self.state.dcs.replace(ShortDeviceControl {
                 params: params.to_vec(),
@@ -233,6 +308,8 @@ impl<'a, F: FnMut(Action)> VTActor for Performer<'a, F> {
             sixel.push(data);
         } else if let Some(tcap) = self.state.get_tcap.as_mut() {
             tcap.push(data);
+        } else if let Some(font) = self.state.soft_font.as_mut() {
+            font.push(data);
         } else {
             (self.callback)(Action::DeviceControl(DeviceControlMode::Data(data)));
         }
@@ -248,6 +325,8 @@ impl<'a, F: FnMut(Action)> VTActor for Performer<'a, F> {
             (self.callback)(Action::Sixel(Box::new(sixel.sixel)));
         } else if let Some(tcap) = self.state.get_tcap.take() {
             (self.callback)(Action::XtGetTcap(tcap.finish()));
+        } else if let Some(font) = self.state.soft_font.take() {
+            (self.callback)(Action::SoftFont(Box::new(font.finish())));
         } else {
             (self.callback)(Action::DeviceControl(DeviceControlMode::Exit));
         }
@@ -274,14 +353,15 @@ impl<'a, F: FnMut(Action)> VTActor for Performer<'a, F> {
         // It doesn't appear to be possible for params.len() > 1 due to the way
         // that the state machine in vte functions.  As such, it also seems to
         // be impossible for ignored_extra_intermediates to be true too.
-        (self.callback)(Action::Esc(Esc::parse(
-            if intermediates.len() == 1 {
-                Some(intermediates[0])
-            } else {
-                None
-            },
-            control,
-        )));
+        let intermediate = match intermediates {
+            [i] => Some(*i),
+            // Soft font designators are often of the form `SP Ft`.
+            // The space carries no meaning for us, so fold it away so
+            // that the SCS sequence can be recognized.
+            [i, b' '] if b"()*+-./".contains(i) => Some(*i),
+            _ => None,
+        };
+        (self.callback)(Action::Esc(Esc::parse(intermediate, control)));
     }
 }
 
@@ -475,10 +555,11 @@ mod test {
     use crate::cell::{Intensity, Underline};
     use crate::color::ColorSpec;
     use crate::escape::csi::{
-        DecPrivateMode, DecPrivateModeCode, Device, Mode, Sgr, Window, XtSmGraphics,
-        XtSmGraphicsItem, XtermKeyModifierResource,
+        AttributeChangeExtent, Cursor, CursorStyle, DecPrivateMode, DecPrivateModeCode, Device,
+        Edit, Mode, RectangularArea, Sgr, SgrCode, Window, XtSmGraphics, XtSmGraphicsItem,
+        XtermKeyModifierResource,
     };
-    use crate::escape::{EscCode, OneBased};
+    use crate::escape::{ControlCode, EscCode, OneBased};
     use pretty_assertions::assert_eq;
     use std::io::Write;
 
@@ -494,17 +575,28 @@ mod test {
     fn basic_parse() {
         let mut p = Parser::new();
         let actions = p.parse_as_vec(b"hello");
+        assert_eq!(vec![Action::PrintString("hello".to_string())], actions);
+        assert_eq!(encode(&actions), "hello");
+    }
+
+    #[test]
+    fn print_runs() {
+        let mut p = Parser::new();
+        let actions = p.parse_as_vec("a\x1b[1mb\u{f6}c\r\nd".as_bytes());
         assert_eq!(
             vec![
-                Action::Print('h'),
-                Action::Print('e'),
-                Action::Print('l'),
-                Action::Print('l'),
-                Action::Print('o'),
+                Action::Print('a'),
+                Action::CSI(CSI::Sgr(Sgr::Intensity(Intensity::Bold))),
+                Action::PrintString("b\u{f6}c".to_string()),
+                Action::Control(ControlCode::CarriageReturn),
+                Action::Control(ControlCode::LineFeed),
+                Action::Print('d'),
             ],
             actions
         );
-        assert_eq!(encode(&actions), "hello");
+
+        // parse_first still yields one character at a time
+        assert_eq!(p.parse_first(b"xyz"), Some((Action::Print('x'), 1)));
     }
 
     #[test]
@@ -647,6 +739,52 @@ mod test {
         assert_eq!(encode(&actions), "\x1b%H");
     }
 
+    #[test]
+    fn soft_font() {
+        let mut p = Parser::new();
+        let actions = p.parse_as_vec(b"\x1bP1;1;1;4;0;0;12;0{ @~~/??;@@\x1b\\");
+        assert_eq!(
+            vec![
+                Action::SoftFont(Box::new(SoftFont {
+                    font_number: 1,
+                    starting_char: 1,
+                    erase_control: 1,
+                    matrix_width: 4,
+                    font_set_size: 0,
+                    text_or_full_cell: 0,
+                    matrix_height: 12,
+                    is_96_char_set: false,
+                    designator: b" @".to_vec(),
+                    glyphs: vec![vec![vec![63, 63], vec![0, 0]], vec![vec![1, 1]]],
+                })),
+                Action::Esc(Esc::Code(EscCode::StringTerminator)),
+            ],
+            actions
+        );
+
+        assert_eq!(
+            format!("{}", actions[0]),
+            "\x1bP1;1;1;4;0;0;12;0{ @~~/??;@@"
+        );
+
+        match &actions[0] {
+            Action::SoftFont(font) => {
+                assert_eq!(font.matrix_size(), Some((7, 12)));
+
+                let mut huge = font.clone();
+                huge.matrix_width = 100_000;
+                assert_eq!(huge.matrix_size(), None);
+                let mut huge = font.clone();
+                huge.matrix_height = 17;
+                assert_eq!(huge.matrix_size(), None);
+                assert_eq!(font.char_for_glyph(0), Some('!'));
+                assert_eq!(font.char_for_glyph(1), Some('"'));
+                assert_eq!(font.char_for_glyph(94), None);
+            }
+            _ => unreachable!(),
+        }
+    }
+
     #[test]
     fn sixel() {
         let mut p = Parser::new();
@@ -808,6 +946,22 @@ mod test {
         );
     }
 
+    #[test]
+    fn cursor_style() {
+        assert_eq!(
+            round_trip_parse("\x1b[5 q"),
+            vec![Action::CSI(CSI::Cursor(Cursor::CursorStyle(
+                CursorStyle::BlinkingBar
+            )))],
+        );
+        assert_eq!(
+            parse_as("\x1b[ q", "\x1b[0 q"),
+            vec![Action::CSI(CSI::Cursor(Cursor::CursorStyle(
+                CursorStyle::Default
+            )))],
+        );
+    }
+
     #[test]
     fn window() {
         assert_eq!(
@@ -831,6 +985,74 @@ mod test {
         );
     }
 
+    #[test]
+    fn rectangular_area() {
+        let area = RectangularArea {
+            top: OneBased::new(2),
+            left: OneBased::new(3),
+            bottom: OneBased::new(4),
+            right: OneBased::new(5),
+        };
+        assert_eq!(
+            round_trip_parse("\x1b[65;2;3;4;5$x"),
+            vec![Action::CSI(CSI::Edit(Edit::FillRectangularArea {
+                ch: 65,
+                area
+            }))]
+        );
+        assert_eq!(
+            round_trip_parse("\x1b[2;3;;$z"),
+            vec![Action::CSI(CSI::Edit(Edit::EraseRectangularArea(
+                RectangularArea {
+                    top: OneBased::new(2),
+                    left: OneBased::new(3),
+                    bottom: OneBased::new(u32::max_value()),
+                    right: OneBased::new(u32::max_value()),
+                }
+            )))]
+        );
+        assert_eq!(
+            round_trip_parse("\x1b[2;3;4;5${"),
+            vec![Action::CSI(CSI::Edit(Edit::SelectiveEraseRectangularArea(
+                area
+            )))]
+        );
+        assert_eq!(
+            round_trip_parse("\x1b[2;3;4;5;1;6;7;1$v"),
+            vec![Action::CSI(CSI::Edit(Edit::CopyRectangularArea {
+                src: area,
+                src_page: 1,
+                dest_top: OneBased::new(6),
+                dest_left: OneBased::new(7),
+                dest_page: 1,
+            }))]
+        );
+        assert_eq!(
+            round_trip_parse("\x1b[2;3;4;5;1;27$r"),
+            vec![Action::CSI(CSI::Edit(
+                Edit::ChangeAttributesInRectangularArea {
+                    area,
+                    attributes: vec![SgrCode::IntensityBold, SgrCode::InverseOff],
+                }
+            ))]
+        );
+        assert_eq!(
+            round_trip_parse("\x1b[2;3;4;5;7$t"),
+            vec![Action::CSI(CSI::Edit(
+                Edit::ReverseAttributesInRectangularArea {
+                    area,
+                    attributes: vec![SgrCode::InverseOn],
+                }
+            ))]
+        );
+        assert_eq!(
+            round_trip_parse("\x1b[2*x"),
+            vec![Action::CSI(CSI::Edit(Edit::SelectAttributeChangeExtent(
+                AttributeChangeExtent::Rectangle
+            )))]
+        );
+    }
+
     #[test]
     fn dec_private_modes() {
         assert_eq!(
@@ -148,8 +148,26 @@ pub struct Rule {
     /// with ambiguous replacement of `$11` vs `$1` in the case of
     /// more complex regexes.
     format: String,
+    /// If set, activating a link produced by this rule emits the
+    /// named event, rather than opening the link.
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    event: Option<String>,
+    /// If set, a link produced by this rule is opened using this
+    /// program, rather than the default handler for the link.
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    open_with: Option<String>,
+    /// If set, a link produced by this rule can only be activated
+    /// while holding these modifiers, for example `CTRL`.
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    mods: Option<String>,
 }
 
+/// The parameters of an implicit hyperlink that record the
+/// corresponding routing options of the Rule that produced it
+pub const RULE_EVENT_PARAM: &str = "event";
+pub const RULE_OPEN_WITH_PARAM: &str = "open_with";
+pub const RULE_MODS_PARAM: &str = "mods";
+
 #[cfg(feature = "use_serde")]
 fn deserialize_regex<'de, D>(deserializer: D) -> std::result::Result<Regex, D::Error>
 where
@@ -217,9 +235,50 @@ impl Rule {
         Ok(Self {
             regex: Regex::new(regex)?,
             format: format.to_owned(),
+            event: None,
+            open_with: None,
+            mods: None,
         })
     }
 
+    /// Links produced by this rule will emit the named event
+    /// when they are activated
+    pub fn with_event(mut self, event: &str) -> Self {
+        self.event.replace(event.to_owned());
+        self
+    }
+
+    /// Links produced by this rule will be opened using `program`
+    pub fn with_open_with(mut self, program: &str) -> Self {
+        self.open_with.replace(program.to_owned());
+        self
+    }
+
+    /// Links produced by this rule can only be activated while
+    /// holding `mods`
+    pub fn with_mods(mut self, mods: &str) -> Self {
+        self.mods.replace(mods.to_owned());
+        self
+    }
+
+    /// Returns the routing parameters to attach to links
+    /// produced by this rule
+    fn link_params(&self) -> HashMap<String, String> {
+        let mut params = HashMap::new();
+        for (key, value) in [
+            (RULE_EVENT_PARAM, &self.event),
+            (RULE_OPEN_WITH_PARAM, &self.open_with),
+            (RULE_MODS_PARAM, &self.mods),
+        ]
+        .iter()
+        {
+            if let Some(value) = value {
+                params.insert(key.to_string(), value.to_string());
+            }
+        }
+        params
+    }
+
     /// Given a line of text from the terminal screen, and a set of
     /// rules, return the set of RuleMatches.
     pub fn match_hyperlinks(line: &str, rules: &[Rule]) -> Vec<RuleMatch> {
@@ -238,7 +297,9 @@ impl Rule {
             .into_iter()
             .map(|m| {
                 let url = m.expand();
-                let link = Arc::new(Hyperlink::new_implicit(url));
+                let mut link = Hyperlink::new_implicit(url);
+                link.params = m.rule.link_params();
+                let link = Arc::new(link);
                 RuleMatch {
                     link,
                     range: m.range(),
@@ -282,4 +343,41 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn rule_params() {
+        let rules = vec![
+            Rule::new(r"\b(\w+\.rs):(\d+)\b", "$1:$2")
+                .unwrap()
+                .with_event("open-in-editor")
+                .with_mods("CTRL"),
+            Rule::new(r"\bT(\d+)\b", "https://example.com/tasks/?t=$1")
+                .unwrap()
+                .with_open_with("firefox"),
+        ];
+
+        let matches = Rule::match_hyperlinks("  src/main.rs:12 T123", &rules);
+        assert_eq!(matches.len(), 2);
+
+        let link = &matches[0].link;
+        assert_eq!(link.uri(), "main.rs:12");
+        assert!(link.is_implicit());
+        assert_eq!(
+            link.params().get(RULE_EVENT_PARAM).map(String::as_str),
+            Some("open-in-editor")
+        );
+        assert_eq!(
+            link.params().get(RULE_MODS_PARAM).map(String::as_str),
+            Some("CTRL")
+        );
+        assert!(link.params().get(RULE_OPEN_WITH_PARAM).is_none());
+
+        let link = &matches[1].link;
+        assert_eq!(link.uri(), "https://example.com/tasks/?t=123");
+        assert_eq!(
+            link.params().get(RULE_OPEN_WITH_PARAM).map(String::as_str),
+            Some("firefox")
+        );
+        assert_eq!(link.params().len(), 1);
+    }
 }
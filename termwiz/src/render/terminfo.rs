@@ -803,9 +803,18 @@ mod test {
             Self { write, renderer }
         }
 
+        /// Parses the output, splitting runs of text into characters
+        /// so that the expectations don't depend on how text is batched
         fn parse(&self) -> Vec<Action> {
             let mut p = Parser::new();
-            p.parse_as_vec(&self.write.buf)
+            let mut result = vec![];
+            for action in p.parse_as_vec(&self.write.buf) {
+                match action {
+                    Action::PrintString(s) => result.extend(s.chars().map(Action::Print)),
+                    action => result.push(action),
+                }
+            }
+            result
         }
     }
 
@@ -1,4 +1,4 @@
-use crate::cell::{Cell, CellAttributes};
+use crate::cell::{grapheme_column_width_for_version, Cell, CellAttributes, UnicodeVersion};
 use crate::cellcluster::CellCluster;
 use crate::hyperlink::Rule;
 use crate::surface::{Change, SequenceNo, SEQ_ZERO};
@@ -6,7 +6,7 @@ use bitflags::bitflags;
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Serialize};
 use std::ops::Range;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use unicode_segmentation::UnicodeSegmentation;
 
 bitflags! {
@@ -51,6 +51,40 @@ pub struct Line {
     cells: Vec<Cell>,
     seqno: SequenceNo,
     bits: LineBits,
+    #[cfg_attr(feature = "use_serde", serde(skip))]
+    graphemes: GraphemeCache,
+}
+
+/// Caches the index of the first cell of the grapheme that covers
+/// each cell of a line, so that repeatedly moving over the graphemes
+/// of a line doesn't need to walk its cells each time.
+/// The cache is derived from the cells, so it is ignored when
+/// comparing lines.
+#[derive(Default)]
+struct GraphemeCache(Mutex<Option<Arc<[usize]>>>);
+
+impl GraphemeCache {
+    fn invalidate(&mut self) {
+        *self.0.get_mut().unwrap() = None;
+    }
+}
+
+impl Clone for GraphemeCache {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.0.lock().unwrap().clone()))
+    }
+}
+
+impl PartialEq for GraphemeCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl std::fmt::Debug for GraphemeCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("GraphemeCache")
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -68,6 +102,7 @@ impl Line {
             bits,
             cells,
             seqno: SEQ_ZERO,
+            graphemes: GraphemeCache::default(),
         }
     }
 
@@ -79,6 +114,7 @@ impl Line {
             bits,
             cells,
             seqno: SEQ_ZERO,
+            graphemes: GraphemeCache::default(),
         }
     }
 
@@ -98,6 +134,7 @@ impl Line {
             cells,
             bits: LineBits::NONE,
             seqno: SEQ_ZERO,
+            graphemes: GraphemeCache::default(),
         }
     }
 
@@ -130,6 +167,42 @@ impl Line {
         self.update_last_change_seqno(seqno);
     }
 
+    /// Recomputes the width of each cell according to the rules of
+    /// `version`.  The line keeps its length where possible; if its
+    /// text has become wider, it may be longer than before and may
+    /// need to be wrapped by the caller.
+    pub fn set_unicode_version(&mut self, version: UnicodeVersion, seqno: SequenceNo) {
+        let len = self.cells.len();
+        let mut cells = Vec::with_capacity(len);
+        let mut content_end = 0;
+        for (_, cell) in self.visible_cells() {
+            let width = grapheme_column_width_for_version(cell.str(), version).max(1);
+            if width == cell.width() {
+                cells.push(cell.clone());
+            } else {
+                cells.push(Cell::new_grapheme_with_width(
+                    cell.str(),
+                    width,
+                    cell.attrs().clone(),
+                ));
+            }
+            for _ in 1..width {
+                cells.push(Cell::blank_with_attrs(cell.attrs().clone()));
+            }
+            if cell.str() != " " {
+                content_end = cells.len();
+            }
+        }
+        // Trailing whitespace doesn't need to grow the line
+        cells.truncate(len.max(content_end));
+        if cells.len() < len {
+            cells.resize_with(len, Cell::blank);
+        }
+        self.cells = cells;
+        self.invalidate_implicit_hyperlinks(seqno);
+        self.update_last_change_seqno(seqno);
+    }
+
     /// Wrap the line so that it fits within the provided width.
     /// Returns the list of resultant line(s)
     pub fn wrap(mut self, width: usize, seqno: SequenceNo) -> Vec<Self> {
@@ -144,6 +217,7 @@ impl Line {
                         cells: chunk.to_vec(),
                         bits: LineBits::NONE,
                         seqno: seqno,
+                        graphemes: GraphemeCache::default(),
                     };
                     if line.cells.len() == width {
                         // Ensure that we don't forget that we wrapped
@@ -172,12 +246,21 @@ impl Line {
         self.seqno
     }
 
+    /// Returns the approximate number of bytes used by the line,
+    /// including its cells
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.cells.capacity() * std::mem::size_of::<Cell>()
+            + self.cells.iter().map(Cell::heap_size).sum::<usize>()
+    }
+
     /// Annotate the line with the sequence number of a change.
     /// This can be used together with Line::changed_since to
     /// manage caching and rendering
     #[inline]
     pub fn update_last_change_seqno(&mut self, seqno: SequenceNo) {
         self.seqno = self.seqno.max(seqno);
+        self.graphemes.invalidate();
     }
 
     /// Check whether the reverse video bit is set.  If it is set,
@@ -363,13 +446,82 @@ impl Line {
 
     pub fn split_off(&mut self, idx: usize) -> Self {
         let cells = self.cells.split_off(idx);
+        self.graphemes.invalidate();
         Self {
             bits: self.bits,
             cells,
             seqno: SEQ_ZERO,
+            graphemes: GraphemeCache::default(),
+        }
+    }
+
+    /// Returns the index of the first cell of the grapheme at each
+    /// cell index, computing it if it isn't already cached
+    fn grapheme_starts(&self) -> Arc<[usize]> {
+        let mut cache = self.graphemes.0.lock().unwrap();
+        if let Some(starts) = cache.as_ref() {
+            return Arc::clone(starts);
+        }
+        let mut starts = Vec::with_capacity(self.cells.len());
+        for (idx, cell) in self.visible_cells() {
+            let width = cell.width().max(1);
+            for _ in 0..width.min(self.cells.len() - idx) {
+                starts.push(idx);
+            }
+        }
+        let starts: Arc<[usize]> = starts.into();
+        cache.replace(Arc::clone(&starts));
+        starts
+    }
+
+    /// Returns the range of cells occupied by the grapheme that
+    /// covers cell `idx`.  A wide grapheme occupies its own cell and
+    /// the blank cells that follow it.  Beyond the end of the line,
+    /// each cell is its own grapheme.
+    pub fn grapheme_range(&self, idx: usize) -> Range<usize> {
+        let starts = self.grapheme_starts();
+        match starts.get(idx) {
+            Some(&start) => {
+                let width = self.cells[start].width().max(1);
+                start..(start + width).min(starts.len())
+            }
+            None => idx..idx + 1,
         }
     }
 
+    /// Widens `cols` so that it covers whole graphemes, rather than
+    /// splitting a wide grapheme at either end of it
+    pub fn grapheme_columns(&self, cols: Range<usize>) -> Range<usize> {
+        if cols.start >= cols.end {
+            return cols;
+        }
+        let start = self.grapheme_range(cols.start).start;
+        let end = if cols.end <= self.cells.len() {
+            self.grapheme_range(cols.end - 1).end
+        } else {
+            cols.end
+        };
+        start..end
+    }
+
+    /// Returns the index of the first cell of the grapheme that
+    /// precedes the one that covers cell `idx`, or None if it is
+    /// the first grapheme of the line
+    pub fn prev_grapheme(&self, idx: usize) -> Option<usize> {
+        let start = self.grapheme_range(idx).start;
+        if start == 0 {
+            None
+        } else {
+            Some(self.grapheme_range(start - 1).start)
+        }
+    }
+
+    /// Returns the index of the first cell of the grapheme that
+    /// follows the one that covers cell `idx`
+    pub fn next_grapheme(&self, idx: usize) -> usize {
+        self.grapheme_range(idx).end
+    }
+
     pub fn compute_double_click_range<F: Fn(&str) -> bool>(
         &self,
         click_col: usize,
@@ -381,25 +533,27 @@ impl Line {
             return DoubleClickRange::Range(click_col..click_col);
         }
 
-        let mut lower = click_col;
-        let mut upper = click_col;
+        // Work in whole graphemes, so that the cells that are hidden
+        // by a preceding multi-wide cell are part of the same word
+        let clicked = self.grapheme_range(click_col);
+        if !is_word(self.cells[clicked.start].str()) {
+            return DoubleClickRange::Range(click_col..click_col);
+        }
 
-        // TODO: look back and look ahead for cells that are hidden by
-        // a preceding multi-wide cell
-        for (idx, cell) in self.cells.iter().enumerate().skip(click_col) {
-            if !is_word(cell.str()) {
+        let mut lower = clicked.start;
+        let mut upper = clicked.end;
+        while upper < len {
+            let next = self.grapheme_range(upper);
+            if !is_word(self.cells[next.start].str()) {
                 break;
             }
-            upper = idx + 1;
+            upper = next.end;
         }
-        for (idx, cell) in self.cells.iter().enumerate().rev() {
-            if idx > click_col {
-                continue;
-            }
-            if !is_word(cell.str()) {
+        while let Some(prev) = self.prev_grapheme(lower) {
+            if !is_word(self.cells[prev].str()) {
                 break;
             }
-            lower = idx;
+            lower = prev;
         }
 
         if upper > lower && self.cells[upper.min(len) - 1].attrs().wrapped() {
@@ -486,6 +640,36 @@ impl Line {
         &self.cells[idx]
     }
 
+    /// Assigns a run of printable ASCII text, excluding DEL, starting
+    /// at the specified column index, one cell per byte, each with the
+    /// same attributes.  This is equivalent to calling `set_cell` for
+    /// each byte, but only performs the per-line bookkeeping once.
+    pub fn set_ascii_run(
+        &mut self,
+        start_idx: usize,
+        text: &str,
+        attrs: &CellAttributes,
+        seqno: SequenceNo,
+    ) {
+        debug_assert!(text.bytes().all(|b| (0x20..0x7f).contains(&b)));
+        let end_idx = start_idx + text.len();
+        if end_idx > self.cells.len() {
+            self.cells.resize_with(end_idx, Cell::blank);
+        }
+
+        self.invalidate_implicit_hyperlinks(seqno);
+        self.update_last_change_seqno(seqno);
+        if attrs.hyperlink().is_some() {
+            self.bits |= LineBits::HAS_HYPERLINK;
+        }
+        self.invalidate_grapheme_at_or_before(start_idx);
+
+        for i in 0..text.len() {
+            let cell = Cell::new_grapheme_with_width(&text[i..i + 1], 1, attrs.clone());
+            self.raw_set_cell(start_idx + i, cell, false);
+        }
+    }
+
     /// Place text starting at the specified column index.
     /// Each grapheme of the text run has the same attributes.
     pub fn overlay_text_with_attribute(
@@ -622,11 +806,18 @@ impl Line {
         CellCluster::make_cluster(self.cells.len(), self.visible_cells())
     }
 
+    /// Like `cluster`, but additionally breaks the clusters before each
+    /// cell index for which `break_before` returns true
+    pub fn cluster_with_breaks(&self, break_before: impl Fn(usize) -> bool) -> Vec<CellCluster> {
+        CellCluster::make_cluster_with_breaks(self.cells.len(), self.visible_cells(), break_before)
+    }
+
     pub fn cells(&self) -> &[Cell] {
         &self.cells
     }
 
     pub fn cells_mut(&mut self) -> &mut [Cell] {
+        self.graphemes.invalidate();
         &mut self.cells
     }
 
@@ -750,6 +941,96 @@ mod test {
     use crate::hyperlink::*;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn cluster_breaks() {
+        let line: Line = "a != b".into();
+        let texts = |clusters: Vec<CellCluster>| {
+            clusters
+                .into_iter()
+                .map(|cluster| (cluster.first_cell_idx, cluster.text))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts(line.cluster()), vec![(0, "a != b".to_string())]);
+        assert_eq!(
+            texts(line.cluster_with_breaks(|idx| idx == 3 || idx == 4)),
+            vec![
+                (0, "a !".to_string()),
+                (3, "=".to_string()),
+                (4, " b".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn graphemes() {
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let flag = "\u{1f1ef}\u{1f1f5}";
+        let line: Line = format!("a{} {}b", family, flag).as_str().into();
+        assert_eq!(line.cells().len(), 7);
+
+        assert_eq!(line.grapheme_range(0), 0..1);
+        assert_eq!(line.grapheme_range(2), 1..3);
+        assert_eq!(line.grapheme_range(5), 4..6);
+        assert_eq!(line.grapheme_range(9), 9..10);
+        assert_eq!(line.next_grapheme(1), 3);
+        assert_eq!(line.next_grapheme(4), 6);
+        assert_eq!(line.prev_grapheme(5), Some(3));
+        assert_eq!(line.prev_grapheme(2), Some(0));
+        assert_eq!(line.prev_grapheme(0), None);
+        assert_eq!(line.grapheme_columns(2..5), 1..6);
+        assert_eq!(
+            line.grapheme_columns(5..usize::max_value()),
+            4..usize::max_value()
+        );
+        assert_eq!(line.grapheme_columns(0..0), 0..0);
+
+        let is_word = |s: &str| s != " ";
+        assert_eq!(
+            line.compute_double_click_range(2, is_word),
+            DoubleClickRange::Range(0..3)
+        );
+        assert_eq!(
+            line.compute_double_click_range(5, is_word),
+            DoubleClickRange::Range(4..7)
+        );
+        assert_eq!(
+            line.compute_double_click_range(3, is_word),
+            DoubleClickRange::Range(3..3)
+        );
+    }
+
+    #[test]
+    fn grapheme_cache_is_invalidated() {
+        let mut line: Line = "abc".into();
+        assert_eq!(line.grapheme_range(1), 1..2);
+        line.set_cell(
+            0,
+            Cell::new_grapheme("\u{1f600}", CellAttributes::default()),
+            1,
+        );
+        assert_eq!(line.grapheme_range(1), 0..2);
+        assert_eq!(line.next_grapheme(0), 2);
+    }
+
+    #[test]
+    fn unicode_version() {
+        let mut line: Line = "\u{a7}\u{a7}ab".into();
+        let widths = |line: &Line| line.cells().iter().map(Cell::width).collect::<Vec<_>>();
+        assert_eq!(widths(&line), vec![1, 1, 1, 1]);
+
+        let cjk = UnicodeVersion {
+            version: 9,
+            ambiguous_are_wide: true,
+        };
+        line.set_unicode_version(cjk, SEQ_ZERO);
+        assert_eq!(widths(&line), vec![2, 1, 2, 1, 1, 1]);
+        assert_eq!(line.as_str(), "\u{a7}\u{a7}ab");
+
+        line.set_unicode_version(UnicodeVersion::default(), SEQ_ZERO);
+        assert_eq!(widths(&line), vec![1, 1, 1, 1, 1, 1]);
+        assert_eq!(line.as_str(), "\u{a7}\u{a7}ab  ");
+    }
+
     #[test]
     fn hyperlinks() {
         let text =
@@ -832,4 +1113,21 @@ mod test {
         let r = line.compute_double_click_range(200, |_| true);
         assert_eq!(r, DoubleClickRange::Range(200..200));
     }
+
+    #[test]
+    fn memory_usage() {
+        let mut line: Line = "hello".into();
+        let inline = line.memory_usage();
+        assert!(inline >= std::mem::size_of::<Line>() + 5 * std::mem::size_of::<Cell>());
+
+        // A grapheme that is too long to be stored in the cell
+        // is allocated separately
+        let grapheme = "e\u{301}\u{302}\u{303}\u{304}";
+        line.set_cell(
+            0,
+            Cell::new_grapheme(grapheme, CellAttributes::default()),
+            SEQ_ZERO,
+        );
+        assert!(line.memory_usage() >= inline + grapheme.len());
+    }
 }
@@ -68,6 +68,20 @@ impl CursorShape {
             Self::BlinkingBlock | Self::BlinkingUnderline | Self::BlinkingBar
         )
     }
+
+    /// Returns the blinking or steady variant of this shape.
+    /// `Default` is returned unchanged.
+    pub fn with_blinking(self, blinking: bool) -> Self {
+        match (self, blinking) {
+            (Self::BlinkingBlock, false) => Self::SteadyBlock,
+            (Self::SteadyBlock, true) => Self::BlinkingBlock,
+            (Self::BlinkingUnderline, false) => Self::SteadyUnderline,
+            (Self::SteadyUnderline, true) => Self::BlinkingUnderline,
+            (Self::BlinkingBar, false) => Self::SteadyBar,
+            (Self::SteadyBar, true) => Self::BlinkingBar,
+            (shape, _) => shape,
+        }
+    }
 }
 
 /// SequenceNo indicates a logical position within a stream of changes.
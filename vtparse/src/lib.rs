@@ -92,6 +92,17 @@ pub trait VTActor {
     /// character.
     fn print(&mut self, b: char);
 
+    /// A run of printable text was parsed in the ground state.  The
+    /// text contains no C0 or C1 controls and is equivalent to calling
+    /// `print` for each of its characters, which is what the default
+    /// implementation does.  Implementations that buffer text can
+    /// override this to avoid the per-character overhead.
+    fn print_str(&mut self, text: &str) {
+        for c in text.chars() {
+            self.print(c);
+        }
+    }
+
     /// The C0 or C1 control function should be executed, which may have any one of a variety of
     /// effects, including changing the cursor position, suspending or resuming communications or
     /// changing the shift states in effect.
@@ -677,10 +688,42 @@ impl VTParser {
     /// Parse a sequence of bytes.  The sequence need not be complete.
     /// This may result in some number of calls to the methods on the
     /// provided `actor`.
+    /// Runs of printable text in the ground state bypass the state
+    /// machine and are passed to `VTActor::print_str` in one call.
     pub fn parse(&mut self, bytes: &[u8], actor: &mut dyn VTActor) {
-        for b in bytes {
-            self.parse_byte(*b, actor);
+        let mut idx = 0;
+        while idx < bytes.len() {
+            if self.state == State::Ground {
+                let text = printable_run(&bytes[idx..]);
+                if text.len() > 1 {
+                    actor.print_str(text);
+                    idx += text.len();
+                    continue;
+                }
+            }
+            self.parse_byte(bytes[idx], actor);
+            idx += 1;
+        }
+    }
+}
+
+/// Returns the longest prefix of `bytes` that is valid UTF-8 and that
+/// has no C0 or C1 controls, and that can therefore be printed without
+/// consulting the state machine.  An incomplete or invalid UTF-8
+/// sequence ends the run, leaving it to the state machine to decode.
+fn printable_run(bytes: &[u8]) -> &str {
+    let end = bytes.iter().position(|&b| b < 0x20).unwrap_or(bytes.len());
+    let text = match std::str::from_utf8(&bytes[..end]) {
+        Ok(text) => text,
+        Err(err) => {
+            // Safe because from_utf8 verified this prefix
+            unsafe { std::str::from_utf8_unchecked(&bytes[..err.valid_up_to()]) }
         }
+    };
+    // C1 controls may be encoded as UTF-8
+    match text.find(|c| ('\u{80}'..='\u{9f}').contains(&c)) {
+        Some(idx) => &text[..idx],
+        None => text,
     }
 }
 
@@ -993,6 +1036,36 @@ mod test {
         );
     }
 
+    #[test]
+    fn print_runs() {
+        // Printable runs take a fast path, which must be equivalent
+        // to feeding the bytes to the state machine one at a time
+        let mut bytes = "hello w\u{f6}rld\u{8d}x\u{1f600}\x1b[1mbold\x7f!"
+            .as_bytes()
+            .to_vec();
+        bytes.extend_from_slice(b"\xffok\xe2\x9c");
+        bytes.extend_from_slice("\u{2764}".as_bytes());
+
+        let mut parser = VTParser::new();
+        let mut actor = CollectingVTActor::default();
+        for b in &bytes {
+            parser.parse_byte(*b, &mut actor);
+        }
+        let expected = actor.into_vec();
+
+        assert_eq!(parse_as_vec(&bytes), expected);
+
+        // Splitting the input, even within a UTF-8 sequence, gives
+        // the same result
+        for split in 0..bytes.len() {
+            let mut parser = VTParser::new();
+            let mut actor = CollectingVTActor::default();
+            parser.parse(&bytes[..split], &mut actor);
+            parser.parse(&bytes[split..], &mut actor);
+            assert_eq!(actor.into_vec(), expected, "split at {}", split);
+        }
+    }
+
     #[test]
     fn tmux_control() {
         assert_eq!(
@@ -8,10 +8,11 @@ use codec::*;
 use config::{configuration, SshDomain, TlsDomainClient, UnixDomain};
 use filedescriptor::FileDescriptor;
 use futures::FutureExt;
+use mux::client::ClientId;
 use mux::connui::ConnectionUI;
 use mux::domain::{alloc_domain_id, DomainId};
 use mux::pane::PaneId;
-use mux::ssh::ssh_connect_with_ui;
+use mux::ssh::{ensure_remote_terminfo, ssh_connect_with_ui};
 use mux::Mux;
 use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
 use openssl::x509::X509;
@@ -40,6 +41,7 @@ enum ReaderMessage {
 pub struct Client {
     sender: Sender<ReaderMessage>,
     local_domain_id: DomainId,
+    client_id: ClientId,
     pub is_reconnectable: bool,
     pub is_local: bool,
 }
@@ -213,6 +215,7 @@ async fn client_thread_async(
     };
 
     let mut stream = reconnectable.take_stream().unwrap();
+    let mut decode_buffer = vec![];
 
     loop {
         let rx_msg = rx.recv();
@@ -231,34 +234,36 @@ async fn client_thread_async(
                     .context("encoding a PDU to send to the server")?;
                 stream.flush().await.context("flushing PDU to server")?;
             }
-            Ok(ReaderMessage::Readable) => match Pdu::decode_async(&mut stream).await {
-                Ok(decoded) => {
-                    log::trace!("decoded serial {}", decoded.serial);
-                    if decoded.serial == 0 {
-                        process_unilateral(local_domain_id, decoded)
-                            .context("processing unilateral PDU from server")
-                            .map_err(|e| {
-                                log::error!("process_unilateral: {:?}", e);
-                                e
-                            })?;
-                    } else if let Some(promise) = promises.map.remove(&decoded.serial) {
-                        if promise.try_send(Ok(decoded.pdu)).is_err() {
-                            return Err(NotReconnectableError::ClientWasDestroyed.into());
+            Ok(ReaderMessage::Readable) => {
+                match Pdu::decode_async_with_buffer(&mut stream, &mut decode_buffer).await {
+                    Ok(decoded) => {
+                        log::trace!("decoded serial {}", decoded.serial);
+                        if decoded.serial == 0 {
+                            process_unilateral(local_domain_id, decoded)
+                                .context("processing unilateral PDU from server")
+                                .map_err(|e| {
+                                    log::error!("process_unilateral: {:?}", e);
+                                    e
+                                })?;
+                        } else if let Some(promise) = promises.map.remove(&decoded.serial) {
+                            if promise.try_send(Ok(decoded.pdu)).is_err() {
+                                return Err(NotReconnectableError::ClientWasDestroyed.into());
+                            }
+                        } else {
+                            log::error!(
+                                "got serial {} without a corresponding promise",
+                                decoded.serial
+                            );
                         }
-                    } else {
-                        log::error!(
-                            "got serial {} without a corresponding promise",
-                            decoded.serial
-                        );
+                    }
+                    Err(err) => {
+                        let reason = format!("Error while decoding response pdu: {:#}", err);
+                        log::error!("{}", reason);
+                        promises.fail_all(&reason);
+                        return Err(err).context("Error while decoding response pdu");
                     }
                 }
-                Err(err) => {
-                    let reason = format!("Error while decoding response pdu: {:#}", err);
-                    log::error!("{}", reason);
-                    promises.fail_all(&reason);
-                    return Err(err).context("Error while decoding response pdu");
-                }
-            },
+            }
             Err(_) => {
                 return Err(NotReconnectableError::ClientWasDestroyed.into());
             }
@@ -456,7 +461,14 @@ impl Reconnectable {
             ssh_config.insert("identitiesonly".to_string(), "yes".to_string());
         }
 
+        let host = ssh_config
+            .get("hostname")
+            .cloned()
+            .unwrap_or_else(|| remote_host_name.to_string());
         let sess = ssh_connect_with_ui(ssh_config, ui)?;
+        if let Err(err) = ensure_remote_terminfo(&sess, &host, ui) {
+            ui.output_str(&format!("Failed to check terminfo: {:#}\n", err));
+        }
         let proxy_bin = Self::wezterm_bin_path(&ssh_dom.remote_wezterm_path);
 
         let cmd = if initial {
@@ -875,6 +887,7 @@ impl Client {
         Self {
             sender,
             local_domain_id,
+            client_id: ClientId::new(),
             is_reconnectable,
             is_local,
         }
@@ -919,6 +932,18 @@ impl Client {
         self.local_domain_id
     }
 
+    /// Identifies this client to the server and selects the workspace
+    /// that it will be viewing
+    pub async fn identify(&self, workspace: String, read_only: bool) -> anyhow::Result<()> {
+        self.set_client_id(SetClientId {
+            client_id: self.client_id.clone(),
+            workspace,
+            read_only,
+        })
+        .await?;
+        Ok(())
+    }
+
     pub fn new_default_unix_domain(initial: bool, ui: &mut ConnectionUI) -> anyhow::Result<Self> {
         let config = configuration();
 
@@ -994,6 +1019,7 @@ impl Client {
     rpc!(mouse_event, SendMouseEvent, UnitResponse);
     rpc!(resize, Resize, UnitResponse);
     rpc!(set_zoomed, SetPaneZoomed, UnitResponse);
+    rpc!(set_ambiguous_width, SetPaneAmbiguousWidth, UnitResponse);
     rpc!(
         get_tab_render_changes,
         GetPaneRenderChanges,
@@ -1008,4 +1034,27 @@ impl Client {
         SearchScrollbackResponse
     );
     rpc!(kill_pane, KillPane, UnitResponse);
+    rpc!(set_client_id, SetClientId, UnitResponse);
+    rpc!(set_focused_pane, SetFocusedPane, UnitResponse);
+    rpc!(set_active_workspace, SetActiveWorkspace, UnitResponse);
+    rpc!(get_file_info, GetFileInfo, FileInfoResponse);
+    rpc!(read_file_chunk, ReadFileChunk, FileChunkResponse);
+    rpc!(write_file_chunk, WriteFileChunk, UnitResponse);
+    rpc!(set_log_level, SetLogLevel, UnitResponse);
+    rpc!(move_pane, MovePane, MovePaneResponse);
+    rpc!(list_workspaces, ListWorkspaces = (), ListWorkspacesResponse);
+    rpc!(rename_workspace, RenameWorkspace, UnitResponse);
+    rpc!(kill_workspace, KillWorkspace, UnitResponse);
+    rpc!(set_workspace_order, SetWorkspaceOrder, UnitResponse);
+    rpc!(show_windows, ShowWindows = (), UnitResponse);
+    rpc!(
+        get_focused_window,
+        GetFocusedWindow = (),
+        GetFocusedWindowResponse
+    );
+    rpc!(
+        get_memory_stats,
+        GetMemoryStats = (),
+        GetMemoryStatsResponse
+    );
 }
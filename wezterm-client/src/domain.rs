@@ -2,15 +2,16 @@ use crate::client::Client;
 use crate::pane::ClientPane;
 use anyhow::{anyhow, bail};
 use async_trait::async_trait;
-use codec::{ListPanesResponse, Spawn, SplitPane};
+use codec::{ListPanesResponse, SetActiveWorkspace, Spawn, SplitPane};
 use config::keyassignment::SpawnTabDomain;
 use config::{SshDomain, TlsDomainClient, UnixDomain};
 use mux::connui::ConnectionUI;
 use mux::domain::{alloc_domain_id, Domain, DomainId, DomainState};
+use mux::encoding::CharacterEncoding;
 use mux::pane::{Pane, PaneId};
 use mux::tab::{SplitDirection, Tab, TabId};
 use mux::window::WindowId;
-use mux::Mux;
+use mux::{Mux, MuxNotification};
 use portable_pty::{CommandBuilder, PtySize};
 use promise::spawn::spawn_into_new_thread;
 use std::cell::RefCell;
@@ -22,6 +23,7 @@ pub struct ClientInner {
     pub client: Client,
     pub local_domain_id: DomainId,
     pub remote_domain_id: DomainId,
+    pub read_only: bool,
     remote_to_local_window: Mutex<HashMap<WindowId, WindowId>>,
     remote_to_local_tab: Mutex<HashMap<TabId, TabId>>,
     remote_to_local_pane: Mutex<HashMap<PaneId, PaneId>>,
@@ -152,7 +154,7 @@ impl ClientDomainConfig {
 }
 
 impl ClientInner {
-    pub fn new(local_domain_id: DomainId, client: Client) -> Self {
+    pub fn new(local_domain_id: DomainId, client: Client, read_only: bool) -> Self {
         // Assumption: that the domain id on the other end is
         // always the first created default domain.  In the future
         // we'll add a way to discover/enumerate domains to populate
@@ -162,6 +164,7 @@ impl ClientInner {
             client,
             local_domain_id,
             remote_domain_id,
+            read_only,
             remote_to_local_window: Mutex::new(HashMap::new()),
             remote_to_local_tab: Mutex::new(HashMap::new()),
             remote_to_local_pane: Mutex::new(HashMap::new()),
@@ -174,6 +177,7 @@ pub struct ClientDomain {
     label: String,
     inner: RefCell<Option<Arc<ClientInner>>>,
     local_domain_id: DomainId,
+    read_only: bool,
 }
 
 impl ClientDomain {
@@ -185,9 +189,17 @@ impl ClientDomain {
             label,
             inner: RefCell::new(None),
             local_domain_id,
+            read_only: false,
         }
     }
 
+    /// When set, the domain attaches as an observer: the remote panes
+    /// are displayed but no input is sent to them.  The server is told
+    /// about this so that it can refuse any input that slips through.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
     fn inner(&self) -> Option<Arc<ClientInner>> {
         self.inner.borrow().as_ref().map(|i| Arc::clone(i))
     }
@@ -227,6 +239,8 @@ impl ClientDomain {
     pub async fn reattach(domain_id: DomainId, ui: ConnectionUI) -> anyhow::Result<()> {
         let inner = Self::get_client_inner_for_domain(domain_id)?;
 
+        let workspace = Mux::get().unwrap().active_workspace();
+        inner.client.identify(workspace, inner.read_only).await?;
         let panes = inner.client.list_panes().await?;
         Self::process_pane_list(inner, panes)?;
 
@@ -252,6 +266,7 @@ impl ClientDomain {
                 None => continue,
             };
 
+            let tabroot_workspace = tabroot.workspace().map(|w| w.to_string());
             if let Some((remote_window_id, remote_tab_id)) = tabroot.window_and_tab_ids() {
                 let tab;
 
@@ -321,7 +336,7 @@ impl ClientDomain {
                         window.push(&tab);
                     }
                 } else {
-                    let local_window_id = mux.new_empty_window();
+                    let local_window_id = mux.new_empty_window(tabroot_workspace);
                     inner.record_remote_to_local_window_mapping(remote_window_id, *local_window_id);
                     mux.add_tab_to_window(&tab, *local_window_id)?;
                 }
@@ -335,6 +350,7 @@ impl ClientDomain {
         domain_id: DomainId,
         client: Client,
         panes: ListPanesResponse,
+        read_only: bool,
     ) -> anyhow::Result<()> {
         let mux = Mux::get().unwrap();
         let domain = mux
@@ -344,13 +360,41 @@ impl ClientDomain {
             .downcast_ref::<Self>()
             .ok_or_else(|| anyhow!("domain {} is not a ClientDomain", domain_id))?;
 
-        let inner = Arc::new(ClientInner::new(domain_id, client));
+        let inner = Arc::new(ClientInner::new(domain_id, client, read_only));
         *domain.inner.borrow_mut() = Some(Arc::clone(&inner));
 
         Self::process_pane_list(inner, panes)?;
+        Self::subscribe_to_workspace_changes(domain_id);
 
         Ok(())
     }
+
+    /// When the local GUI switches workspace, let the server know
+    /// and pull in the windows that belong to the new workspace.
+    fn subscribe_to_workspace_changes(domain_id: DomainId) {
+        let mux = Mux::get().unwrap();
+        mux.subscribe(move |n| {
+            let workspace = match n {
+                MuxNotification::ActiveWorkspaceChanged(workspace) => workspace,
+                _ => return true,
+            };
+            let inner = match Self::get_client_inner_for_domain(domain_id) {
+                Ok(inner) => inner,
+                // The domain was detached; cancel the subscription
+                Err(_) => return false,
+            };
+            promise::spawn::spawn(async move {
+                inner
+                    .client
+                    .set_active_workspace(SetActiveWorkspace { workspace })
+                    .await?;
+                let panes = inner.client.list_panes().await?;
+                Self::process_pane_list(inner, panes)
+            })
+            .detach();
+            true
+        });
+    }
 }
 
 #[async_trait(?Send)]
@@ -372,6 +416,7 @@ impl Domain for ClientDomain {
         size: PtySize,
         command: Option<CommandBuilder>,
         command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
         window: WindowId,
     ) -> anyhow::Result<Rc<Tab>> {
         let inner = self
@@ -385,6 +430,7 @@ impl Domain for ClientDomain {
                 size,
                 command,
                 command_dir,
+                encoding: encoding.map(|encoding| encoding.name().to_string()),
             })
             .await?;
 
@@ -411,6 +457,7 @@ impl Domain for ClientDomain {
         &self,
         command: Option<CommandBuilder>,
         command_dir: Option<String>,
+        encoding: Option<CharacterEncoding>,
         tab_id: TabId,
         pane_id: PaneId,
         direction: SplitDirection,
@@ -439,6 +486,7 @@ impl Domain for ClientDomain {
                 direction,
                 command,
                 command_dir,
+                encoding: encoding.map(|encoding| encoding.name().to_string()),
             })
             .await?;
 
@@ -471,6 +519,9 @@ impl Domain for ClientDomain {
         let domain_id = self.local_domain_id;
         let config = self.config.clone();
 
+        let workspace = Mux::get().unwrap().active_workspace();
+        let read_only = self.read_only;
+
         let activity = mux::activity::Activity::new();
         let ui = ConnectionUI::new();
         ui.title("wezterm: Connecting...");
@@ -491,6 +542,7 @@ impl Domain for ClientDomain {
 
                 ui.output_str("Checking server version\n");
                 client.verify_version_compat(&ui).await?;
+                client.identify(workspace, read_only).await?;
 
                 ui.output_str("Version check OK!  Requesting pane list...\n");
                 let panes = client.list_panes().await?;
@@ -498,7 +550,7 @@ impl Domain for ClientDomain {
                     "Server has {} tabs.  Attaching to local UI...\n",
                     panes.tabs.len()
                 ));
-                ClientDomain::finish_attach(domain_id, client, panes)
+                ClientDomain::finish_attach(domain_id, client, panes, read_only)
             }
         })
         .await
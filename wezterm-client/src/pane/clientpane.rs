@@ -1,7 +1,7 @@
 use crate::domain::ClientInner;
 use crate::pane::mousestate::MouseState;
 use crate::pane::renderable::{RenderableInner, RenderableState};
-use anyhow::bail;
+use anyhow::{bail, Context};
 use async_trait::async_trait;
 use codec::*;
 use config::configuration;
@@ -14,16 +14,25 @@ use mux::{Mux, MuxNotification};
 use portable_pty::PtySize;
 use rangeset::RangeSet;
 use ratelim::RateLimiter;
+use smol::io::AsyncReadExt;
 use std::cell::RefCell;
 use std::cell::RefMut;
+use std::future::Future;
 use std::ops::Range;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
 use termwiz::input::KeyEvent;
 use termwiz::surface::SequenceNo;
 use url::Url;
 use wezterm_term::color::ColorPalette;
-use wezterm_term::{Alert, Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
+use wezterm_term::{
+    Alert, Clipboard, CommandResult, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex,
+    TerminalConfiguration,
+};
+
+/// How much of a file to send in each WriteFileChunk request
+const UPLOAD_CHUNK_SIZE: usize = 256 * 1024;
 
 pub struct ClientPane {
     client: Arc<ClientInner>,
@@ -37,6 +46,8 @@ pub struct ClientPane {
     mouse: Rc<RefCell<MouseState>>,
     clipboard: RefCell<Option<Arc<dyn Clipboard>>>,
     mouse_grabbed: RefCell<bool>,
+    /// The ambiguous width override that was last sent to the server
+    ambiguous_are_wide: RefCell<Option<bool>>,
 }
 
 impl ClientPane {
@@ -82,7 +93,7 @@ impl ClientPane {
         let config = configuration();
         let palette: ColorPalette = config.resolved_palette.clone().into();
 
-        Self {
+        let pane = Self {
             client: Arc::clone(client),
             mouse,
             remote_pane_id,
@@ -94,7 +105,45 @@ impl ClientPane {
             reader,
             clipboard: RefCell::new(None),
             mouse_grabbed: RefCell::new(false),
+            ambiguous_are_wide: RefCell::new(None),
+        };
+        pane.send_ambiguous_width_override();
+        pane
+    }
+
+    /// The text is measured by the terminal on the server, so the
+    /// `treat_east_asian_ambiguous_width_as_wide` override of our
+    /// domain has to be applied there
+    fn send_ambiguous_width_override(&self) {
+        if self.client.read_only {
+            return;
         }
+        let mux = match Mux::get() {
+            Some(mux) => mux,
+            None => return,
+        };
+        let ambiguous_are_wide = match mux.get_domain(self.client.local_domain_id) {
+            Some(domain) => {
+                configuration().ambiguous_width_override_for_domain(domain.domain_name())
+            }
+            None => return,
+        };
+        if self.ambiguous_are_wide.replace(ambiguous_are_wide) == ambiguous_are_wide {
+            return;
+        }
+
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .set_ambiguous_width(SetPaneAmbiguousWidth {
+                    pane_id: remote_pane_id,
+                    ambiguous_are_wide,
+                })
+                .await
+        })
+        .detach();
     }
 
     pub fn process_unilateral(&self, pdu: Pdu) -> anyhow::Result<()> {
@@ -155,6 +204,72 @@ impl ClientPane {
     pub fn remote_pane_id(&self) -> TabId {
         self.remote_pane_id
     }
+
+    /// Returns a future that resolves to true if `remote_path` exists
+    /// on the host where this pane is running.
+    /// A relative `remote_path` is resolved against the working
+    /// directory of the pane.
+    pub fn remote_file_exists(
+        &self,
+        remote_path: String,
+    ) -> impl Future<Output = anyhow::Result<bool>> {
+        let client = self.client.client.clone();
+        let pane_id = self.remote_pane_id;
+        async move {
+            let info = client
+                .get_file_info(GetFileInfo {
+                    pane_id,
+                    path: remote_path,
+                })
+                .await?;
+            Ok(info.size.is_some())
+        }
+    }
+
+    /// Returns a future that copies the local file `path` to
+    /// `remote_path` on the host where this pane is running.
+    /// A relative `remote_path` is resolved against the working
+    /// directory of the pane.
+    pub fn upload_file(
+        &self,
+        path: PathBuf,
+        remote_path: String,
+    ) -> impl Future<Output = anyhow::Result<()>> {
+        let client = self.client.client.clone();
+        let pane_id = self.remote_pane_id;
+        async move {
+            let mut file = smol::fs::File::open(&path)
+                .await
+                .with_context(|| format!("opening {}", path.display()))?;
+            let mut offset = 0;
+            loop {
+                let mut data = vec![0u8; UPLOAD_CHUNK_SIZE];
+                let len = file
+                    .read(&mut data)
+                    .await
+                    .with_context(|| format!("reading {}", path.display()))?;
+                data.truncate(len);
+                // The first chunk is always sent, even when it is
+                // empty, so that an empty file is still created
+                if len == 0 && offset > 0 {
+                    break;
+                }
+                client
+                    .write_file_chunk(WriteFileChunk {
+                        pane_id,
+                        path: remote_path.clone(),
+                        offset,
+                        data,
+                    })
+                    .await?;
+                if len == 0 {
+                    break;
+                }
+                offset += len as u64;
+            }
+            Ok(())
+        }
+    }
 }
 
 #[async_trait(?Send)]
@@ -190,13 +305,26 @@ impl Pane for ClientPane {
         self.clipboard.borrow_mut().replace(Arc::clone(clipboard));
     }
 
+    fn set_config(&self, _config: Arc<dyn TerminalConfiguration>) {
+        self.send_ambiguous_width_override();
+    }
+
     fn get_title(&self) -> String {
         let renderable = self.renderable.borrow();
         let inner = renderable.inner.borrow();
         inner.title.clone()
     }
 
+    fn get_round_trip_latency(&self) -> Option<std::time::Duration> {
+        let renderable = self.renderable.borrow();
+        let inner = renderable.inner.borrow();
+        inner.last_input_rtt()
+    }
+
     fn send_paste(&self, text: &str) -> anyhow::Result<()> {
+        if self.client.read_only {
+            return Ok(());
+        }
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
         self.renderable
@@ -234,6 +362,9 @@ impl Pane for ClientPane {
     }
 
     fn set_zoomed(&self, zoomed: bool) {
+        if self.client.read_only {
+            return;
+        }
         let render = self.renderable.borrow();
         let mut inner = render.inner.borrow_mut();
         let client = Arc::clone(&self.client);
@@ -255,6 +386,23 @@ impl Pane for ClientPane {
         inner.update_last_send();
     }
 
+    fn focus_changed(&self, focused: bool) {
+        if !focused {
+            return;
+        }
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .set_focused_pane(SetFocusedPane {
+                    pane_id: remote_pane_id,
+                })
+                .await
+        })
+        .detach();
+    }
+
     fn resize(&self, size: PtySize) -> anyhow::Result<()> {
         let render = self.renderable.borrow();
         let mut inner = render.inner.borrow_mut();
@@ -269,6 +417,11 @@ impl Pane for ClientPane {
             // Invalidate any cached rows on a resize
             inner.make_all_stale();
 
+            if self.client.read_only {
+                // We don't get a say in the size of the remote pane
+                return Ok(());
+            }
+
             let client = Arc::clone(&self.client);
             let remote_pane_id = self.remote_pane_id;
             let remote_tab_id = self.remote_tab_id;
@@ -304,6 +457,9 @@ impl Pane for ClientPane {
     }
 
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
+        if self.client.read_only {
+            return Ok(());
+        }
         let input_serial;
         {
             let renderable = self.renderable.borrow();
@@ -337,6 +493,9 @@ impl Pane for ClientPane {
     }
 
     fn kill(&self) {
+        if self.client.read_only {
+            return;
+        }
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
         promise::spawn::spawn(async move {
@@ -351,6 +510,9 @@ impl Pane for ClientPane {
     }
 
     fn mouse_event(&self, event: MouseEvent) -> anyhow::Result<()> {
+        if self.client.read_only {
+            return Ok(());
+        }
         self.mouse.borrow_mut().append(event);
         if MouseState::next(Rc::clone(&self.mouse)) {
             self.renderable
@@ -392,6 +554,15 @@ impl Pane for ClientPane {
     fn get_current_working_dir(&self) -> Option<Url> {
         self.renderable.borrow().inner.borrow().working_dir.clone()
     }
+
+    fn get_command_results(&self) -> Vec<CommandResult> {
+        self.renderable
+            .borrow()
+            .inner
+            .borrow()
+            .command_results
+            .clone()
+    }
 }
 
 struct PaneWriter {
@@ -401,6 +572,11 @@ struct PaneWriter {
 
 impl std::io::Write for PaneWriter {
     fn write(&mut self, data: &[u8]) -> Result<usize, std::io::Error> {
+        if self.client.read_only {
+            // Discard the input rather than erroring out, as the GUI
+            // treats write errors as fatal for the pane
+            return Ok(data.len());
+        }
         promise::spawn::block_on(self.client.client.write_to_pane(WriteToPane {
             pane_id: self.remote_pane_id,
             data: data.to_vec(),
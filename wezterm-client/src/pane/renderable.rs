@@ -19,7 +19,7 @@ use termwiz::cell::{Cell, CellAttributes, Underline};
 use termwiz::color::AnsiColor;
 use termwiz::surface::{SequenceNo, SEQ_ZERO};
 use url::Url;
-use wezterm_term::{KeyCode, KeyModifiers};
+use wezterm_term::{CommandResult, KeyCode, KeyModifiers};
 use wezterm_term::{Line, StableRowIndex};
 
 const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
@@ -69,6 +69,7 @@ pub struct RenderableInner {
     lines: LruCache<StableRowIndex, LineEntry>,
     pub title: String,
     pub working_dir: Option<Url>,
+    pub command_results: Vec<CommandResult>,
     pub seqno: SequenceNo,
 
     fetch_limiter: RateLimiter,
@@ -109,6 +110,7 @@ impl RenderableInner {
             lines: LruCache::new(configuration().scrollback_lines),
             title: title.to_string(),
             working_dir: None,
+            command_results: vec![],
             fetch_limiter,
             last_send_time: now,
             last_recv_time: now,
@@ -132,6 +134,16 @@ impl RenderableInner {
         }
     }
 
+    /// Returns the round trip time of the most recent input,
+    /// if any input has been acknowledged
+    pub fn last_input_rtt(&self) -> Option<Duration> {
+        if self.last_input_rtt == 0 {
+            None
+        } else {
+            Some(Duration::from_millis(self.last_input_rtt))
+        }
+    }
+
     /// Predictive echo can be noisy when the link is working well,
     /// so we only employ it when it looks like the latency is high.
     /// We pick 100ms as the threshold for this.
@@ -343,6 +355,7 @@ impl RenderableInner {
         self.dimensions = delta.dimensions;
         self.title = delta.title;
         self.working_dir = delta.working_dir.map(Into::into);
+        self.command_results = delta.command_results;
         self.seqno = delta.seqno;
 
         let config = configuration();
@@ -1,11 +1,84 @@
 //! A font-database to keep track of fonts that we've located
 
-use crate::locator::{FontDataSource, FontOrigin};
-use crate::parser::{load_built_in_fonts, parse_and_collect_font_info, ParsedFont};
+use crate::locator::{FontDataSource, FontLocator, FontLocatorSelection, FontOrigin};
+use crate::parser::{load_built_in_fonts, parse_and_collect_font_info, CachedFace, ParsedFont};
 use anyhow::Context;
 use config::{Config, FontAttributes};
 use rangeset::RangeSet;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Bump this when the contents of `CachedFace` change
+const FONT_DIRS_CACHE_VERSION: u32 = 1;
+
+lazy_static::lazy_static! {
+    /// The fonts enumerated by each kind of locator; see
+    /// `FontDatabase::with_system_fonts`
+    static ref SYSTEM_FONTS: Mutex<Vec<(FontLocatorSelection, Arc<FontDatabase>)>> =
+        Mutex::new(vec![]);
+}
+
+/// The fonts found in a file by an earlier scan of the font dirs,
+/// which remain valid while the size and modification time of the
+/// file are unchanged
+#[derive(Serialize, Deserialize)]
+struct CachedFontFile {
+    len: u64,
+    modified: SystemTime,
+    faces: Vec<CachedFace>,
+}
+
+/// Remembers the results of scanning the font dirs across runs, so
+/// that starting up doesn't have to open and parse every font file
+/// in them
+#[derive(Serialize, Deserialize)]
+struct FontDirsCache {
+    version: u32,
+    files: HashMap<PathBuf, CachedFontFile>,
+}
+
+impl Default for FontDirsCache {
+    fn default() -> Self {
+        Self {
+            version: FONT_DIRS_CACHE_VERSION,
+            files: HashMap::new(),
+        }
+    }
+}
+
+impl FontDirsCache {
+    fn default_path() -> PathBuf {
+        config::DATA_DIR.join("font-dirs-cache.json")
+    }
+
+    fn load(path: &Path) -> Self {
+        let cache: Option<Self> = std::fs::read(path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok());
+        match cache {
+            Some(cache) if cache.version == FONT_DIRS_CACHE_VERSION => cache,
+            _ => Self::default(),
+        }
+    }
+
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating {}", parent.display()))?;
+        }
+        // Write to a temporary file first so that another instance
+        // never reads a partially written cache
+        let temp = path.with_extension(format!("tmp{}", std::process::id()));
+        std::fs::write(&temp, serde_json::to_vec(self)?)
+            .with_context(|| format!("writing {}", temp.display()))?;
+        std::fs::rename(&temp, path)
+            .with_context(|| format!("renaming {} to {}", temp.display(), path.display()))?;
+        Ok(())
+    }
+}
 
 pub struct FontDatabase {
     by_full_name: HashMap<String, ParsedFont>,
@@ -29,24 +102,81 @@ impl FontDatabase {
     /// Build up the database from the fonts found in the configured font dirs
     /// and from the built-in selection of fonts
     pub fn with_font_dirs(config: &Config) -> anyhow::Result<Self> {
+        if config.font_dirs.is_empty() {
+            return Ok(Self::new());
+        }
+        Self::with_font_dirs_cached(&config.font_dirs, &FontDirsCache::default_path())
+    }
+
+    /// Scans `font_dirs`, parsing only the files that have changed since
+    /// the scan recorded in the cache at `cache_path`, and updates the cache
+    fn with_font_dirs_cached(font_dirs: &[PathBuf], cache_path: &Path) -> anyhow::Result<Self> {
+        let start = std::time::Instant::now();
+        let mut cache = FontDirsCache::load(cache_path);
+        let mut scanned = FontDirsCache::default();
+        let mut parsed_files = 0;
         let mut font_info = vec![];
-        for path in &config.font_dirs {
+
+        for path in font_dirs {
             for entry in walkdir::WalkDir::new(path).into_iter() {
                 let entry = match entry {
                     Ok(entry) => entry,
                     Err(_) => continue,
                 };
+                let meta = match entry.metadata() {
+                    Ok(meta) if meta.is_file() => meta,
+                    _ => continue,
+                };
+                let path = entry.path();
+                let len = meta.len();
+                let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+
+                let file = match cache.files.remove(path) {
+                    Some(file) if file.len == len && file.modified == modified => file,
+                    _ => {
+                        parsed_files += 1;
+                        let mut parsed = vec![];
+                        let source = FontDataSource::OnDisk(path.to_path_buf());
+                        parse_and_collect_font_info(&source, &mut parsed, FontOrigin::FontDirs)
+                            .map_err(|err| {
+                                log::trace!("failed to read {:?}: {:#}", source, err);
+                                err
+                            })
+                            .ok();
+                        // Files that aren't fonts are remembered too, so
+                        // that they aren't read again next time
+                        CachedFontFile {
+                            len,
+                            modified,
+                            faces: parsed.iter().map(ParsedFont::to_cached_face).collect(),
+                        }
+                    }
+                };
 
-                let source = FontDataSource::OnDisk(entry.path().to_path_buf());
-                parse_and_collect_font_info(&source, &mut font_info, FontOrigin::FontDirs)
-                    .map_err(|err| {
-                        log::trace!("failed to read {:?}: {:#}", source, err);
-                        err
-                    })
-                    .ok();
+                for face in &file.faces {
+                    font_info.push(ParsedFont::from_cached_face(
+                        path,
+                        face,
+                        FontOrigin::FontDirs,
+                    ));
+                }
+                scanned.files.insert(path.to_path_buf(), file);
             }
         }
 
+        // Anything left in the old cache has been removed from the dirs
+        if parsed_files > 0 || !cache.files.is_empty() {
+            if let Err(err) = scanned.save(cache_path) {
+                log::warn!("failed to save font dirs cache: {:#}", err);
+            }
+        }
+        log::debug!(
+            "scanned {} font files in font_dirs ({} parsed) in {:?}",
+            scanned.files.len(),
+            parsed_files,
+            start.elapsed()
+        );
+
         let mut db = Self::new();
         db.load_font_info(font_info);
         Ok(db)
@@ -67,6 +197,32 @@ impl FontDatabase {
         Ok(db)
     }
 
+    /// Returns a database of every font that `locator`, which was
+    /// created for `selection`, knows of.  Enumerating and parsing all
+    /// of the system fonts is slow, so this isn't done at startup:
+    /// it is only done the first time that a fallback lookup misses
+    /// in every other source, and the result is shared thereafter.
+    pub fn with_system_fonts(
+        selection: FontLocatorSelection,
+        locator: &dyn FontLocator,
+    ) -> anyhow::Result<Arc<Self>> {
+        let mut system_fonts = SYSTEM_FONTS.lock().unwrap();
+        if let Some((_, db)) = system_fonts.iter().find(|(s, _)| *s == selection) {
+            return Ok(Arc::clone(db));
+        }
+        let start = std::time::Instant::now();
+        let mut db = Self::new();
+        db.load_font_info(locator.enumerate_all_fonts()?);
+        log::debug!(
+            "enumerated {} system fonts in {:?}",
+            db.by_full_name.len(),
+            start.elapsed()
+        );
+        let db = Arc::new(db);
+        system_fonts.push((selection, Arc::clone(&db)));
+        Ok(db)
+    }
+
     pub fn resolve_multiple(
         &self,
         fonts: &[FontAttributes],
@@ -131,3 +287,36 @@ impl FontDatabase {
         None
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn font_dirs_cache() {
+        let font_dirs = vec![PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../assets/fonts"
+        ))];
+        let cache_path = std::env::temp_dir().join(format!(
+            "wezterm-font-dirs-cache-test-{}.json",
+            std::process::id()
+        ));
+        std::fs::remove_file(&cache_path).ok();
+
+        let scanned = FontDatabase::with_font_dirs_cached(&font_dirs, &cache_path).unwrap();
+        let cache = FontDirsCache::load(&cache_path);
+        assert!(cache.files.len() > 0);
+
+        // The second scan finds the same fonts in the cache
+        let cached = FontDatabase::with_font_dirs_cached(&font_dirs, &cache_path).unwrap();
+        let mut scanned = scanned.list_available();
+        let mut cached = cached.list_available();
+        scanned.sort();
+        cached.sort();
+        assert_eq!(scanned, cached);
+        assert!(cached.iter().any(|p| p.names().family == "JetBrains Mono"));
+
+        std::fs::remove_file(&cache_path).ok();
+    }
+}
@@ -1,5 +1,5 @@
 use crate::db::FontDatabase;
-use crate::locator::{new_locator, FontLocator};
+use crate::locator::{new_locator, FontLocator, FontLocatorSelection};
 use crate::parser::ParsedFont;
 use crate::rasterizer::{new_rasterizer, FontRasterizer};
 use crate::shaper::{new_shaper, FontShaper};
@@ -86,6 +86,31 @@ impl LoadedFont {
         completion: F,
         filter_out_synthetic: FS,
         presentation: Option<Presentation>,
+    ) -> anyhow::Result<Vec<GlyphInfo>> {
+        self.shape_impl(text, completion, filter_out_synthetic, presentation, true)
+    }
+
+    /// Shapes text as `shape` does, but without forming ligatures
+    pub fn shape_without_ligatures<
+        F: FnOnce() + Send + Sync + 'static,
+        FS: FnOnce(&mut Vec<char>),
+    >(
+        &self,
+        text: &str,
+        completion: F,
+        filter_out_synthetic: FS,
+        presentation: Option<Presentation>,
+    ) -> anyhow::Result<Vec<GlyphInfo>> {
+        self.shape_impl(text, completion, filter_out_synthetic, presentation, false)
+    }
+
+    fn shape_impl<F: FnOnce() + Send + Sync + 'static, FS: FnOnce(&mut Vec<char>)>(
+        &self,
+        text: &str,
+        completion: F,
+        filter_out_synthetic: FS,
+        presentation: Option<Presentation>,
+        ligatures: bool,
     ) -> anyhow::Result<Vec<GlyphInfo>> {
         let mut no_glyphs = vec![];
 
@@ -102,13 +127,20 @@ impl LoadedFont {
             }
         }
 
-        let result = self.shaper.borrow().shape(
-            text,
-            self.font_size,
-            self.dpi,
-            &mut no_glyphs,
-            presentation,
-        );
+        let result = {
+            let shaper = self.shaper.borrow();
+            if ligatures {
+                shaper.shape(text, self.font_size, self.dpi, &mut no_glyphs, presentation)
+            } else {
+                shaper.shape_without_ligatures(
+                    text,
+                    self.font_size,
+                    self.dpi,
+                    &mut no_glyphs,
+                    presentation,
+                )
+            }
+        };
 
         no_glyphs.retain(|&c| c != '\u{FE0F}' && c != '\u{FE0E}');
         filter_out_synthetic(&mut no_glyphs);
@@ -180,6 +212,7 @@ struct FallbackResolveInfo {
     font_dirs: Arc<FontDatabase>,
     built_in: Arc<FontDatabase>,
     locator: Arc<dyn FontLocator + Send + Sync>,
+    locator_selection: FontLocatorSelection,
     config: ConfigHandle,
 }
 
@@ -255,16 +288,30 @@ impl FallbackResolveInfo {
         }
 
         // iteratively reduce to just the fonts that we need
-        extra_handles.retain(|p| match p.coverage_intersection(&wanted) {
-            Ok(cov) if cov.is_empty() => false,
-            Ok(cov) => {
-                // Remove the matches from the set, so that we avoid
-                // picking up multiple fonts for the same glyphs
-                wanted = wanted.difference(&cov);
-                true
+        reduce_to_coverage(&mut extra_handles, &mut wanted);
+
+        if !wanted.is_empty() {
+            // Only now is it worth enumerating every system font, in
+            // case one of them that the locator didn't suggest has them
+            let missing: Vec<char> = wanted
+                .iter_values()
+                .filter_map(std::char::from_u32)
+                .collect();
+            match FontDatabase::with_system_fonts(self.locator_selection, &*self.locator)
+                .and_then(|system| system.locate_fallback_for_codepoints(&missing))
+            {
+                Ok(mut handles) => {
+                    handles.sort();
+                    reduce_to_coverage(&mut handles, &mut wanted);
+                    extra_handles.append(&mut handles);
+                }
+                Err(err) => log::error!(
+                    "Error: {:#} while resolving fallback for {} from system fonts",
+                    err,
+                    fallback_str.escape_unicode()
+                ),
             }
-            Err(_) => false,
-        });
+        }
 
         if !extra_handles.is_empty() {
             let mut pending = self.pending.lock().unwrap();
@@ -317,6 +364,20 @@ impl FallbackResolveInfo {
     }
 }
 
+/// Removes from `handles` the fonts that don't cover any of the
+/// codepoints in `wanted`, removing from `wanted` those that they cover,
+/// so that only one font is picked up for each codepoint
+fn reduce_to_coverage(handles: &mut Vec<ParsedFont>, wanted: &mut RangeSet<u32>) {
+    handles.retain(|p| match p.coverage_intersection(wanted) {
+        Ok(cov) if cov.is_empty() => false,
+        Ok(cov) => {
+            *wanted = wanted.difference(&cov);
+            true
+        }
+        Err(_) => false,
+    });
+}
+
 struct FontConfigInner {
     fonts: RefCell<HashMap<TextStyle, Rc<LoadedFont>>>,
     metrics: RefCell<Option<FontMetrics>>,
@@ -324,6 +385,7 @@ struct FontConfigInner {
     font_scale: RefCell<f64>,
     config: RefCell<ConfigHandle>,
     locator: Arc<dyn FontLocator + Send + Sync>,
+    locator_selection: FontLocatorSelection,
     font_dirs: RefCell<Arc<FontDatabase>>,
     built_in: RefCell<Arc<FontDatabase>>,
     no_glyphs: RefCell<HashSet<char>>,
@@ -344,6 +406,7 @@ impl FontConfigInner {
         Ok(Self {
             fonts: RefCell::new(HashMap::new()),
             locator,
+            locator_selection: config.font_locator,
             metrics: RefCell::new(None),
             title_font: RefCell::new(None),
             font_scale: RefCell::new(1.0),
@@ -390,6 +453,7 @@ impl FontConfigInner {
             font_dirs: Arc::clone(&*self.font_dirs.borrow()),
             built_in: Arc::clone(&*self.built_in.borrow()),
             locator: Arc::clone(&self.locator),
+            locator_selection: self.locator_selection,
             config: self.config.borrow().clone(),
         };
 
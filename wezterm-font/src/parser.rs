@@ -3,7 +3,9 @@ use crate::shaper::GlyphInfo;
 use config::FontAttributes;
 pub use config::{FontStretch, FontWeight};
 use rangeset::RangeSet;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::path::Path;
 use std::sync::Mutex;
 
 #[derive(Debug)]
@@ -97,7 +99,21 @@ impl PartialOrd for ParsedFont {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+/// The properties of a font face that are needed to match it,
+/// as recorded in the font dirs cache
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CachedFace {
+    index: u32,
+    variation: u32,
+    names: Names,
+    weight: u16,
+    stretch: FontStretch,
+    italic: bool,
+    cap_height: Option<f64>,
+    assume_emoji_presentation: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Names {
     pub full_name: String,
     pub family: String,
@@ -213,6 +229,46 @@ impl ParsedFont {
         })
     }
 
+    /// Returns the properties of this font that are remembered
+    /// between scans of the font dirs
+    pub(crate) fn to_cached_face(&self) -> CachedFace {
+        CachedFace {
+            index: self.handle.index,
+            variation: self.handle.variation,
+            names: self.names.clone(),
+            weight: self.weight.to_opentype_weight(),
+            stretch: self.stretch,
+            italic: self.italic,
+            cap_height: self.cap_height,
+            assume_emoji_presentation: self.assume_emoji_presentation,
+        }
+    }
+
+    /// Reconstructs a font found in `path` by an earlier scan of the
+    /// font dirs, without opening the file.  Its coverage is computed
+    /// when it is first needed to resolve a fallback.
+    pub(crate) fn from_cached_face(path: &Path, face: &CachedFace, origin: FontOrigin) -> Self {
+        Self {
+            names: face.names.clone(),
+            weight: FontWeight::from_opentype_weight(face.weight),
+            stretch: face.stretch,
+            italic: face.italic,
+            synthesize_italic: false,
+            synthesize_bold: false,
+            synthesize_dim: false,
+            assume_emoji_presentation: face.assume_emoji_presentation,
+            handle: FontDataHandle {
+                source: FontDataSource::OnDisk(path.to_path_buf()),
+                index: face.index,
+                variation: face.variation,
+                origin,
+                coverage: None,
+            },
+            coverage: Mutex::new(RangeSet::new()),
+            cap_height: face.cap_height,
+        }
+    }
+
     /// Computes the intersection of the wanted set of codepoints with
     /// the set of codepoints covered by this font entry.
     /// Computes the codepoint coverage for this font entry if we haven't
@@ -62,6 +62,8 @@ pub struct HarfbuzzShaper {
     lib: ftwrap::Library,
     metrics: RefCell<HashMap<MetricsKey, FontMetrics>>,
     features: Vec<harfbuzz::hb_feature_t>,
+    /// `features`, followed by those that turn off ligatures
+    features_without_ligatures: Vec<harfbuzz::hb_feature_t>,
     lang: harfbuzz::hb_language_t,
 }
 
@@ -114,6 +116,11 @@ impl HarfbuzzShaper {
             .iter()
             .filter_map(|s| harfbuzz::feature_from_string(s).ok())
             .collect();
+        // Later features take precedence over earlier ones for the same tag
+        let mut features_without_ligatures = features.clone();
+        for s in &["liga=0", "calt=0", "clig=0"] {
+            features_without_ligatures.push(harfbuzz::feature_from_string(s)?);
+        }
 
         Ok(Self {
             fonts,
@@ -121,6 +128,7 @@ impl HarfbuzzShaper {
             lib,
             metrics: RefCell::new(HashMap::new()),
             features,
+            features_without_ligatures,
             lang,
         })
     }
@@ -167,6 +175,7 @@ impl HarfbuzzShaper {
         dpi: u32,
         no_glyphs: &mut Vec<char>,
         presentation: Option<Presentation>,
+        features: &[harfbuzz::hb_feature_t],
     ) -> anyhow::Result<Vec<GlyphInfo>> {
         let mut buf = harfbuzz::Buffer::new()?;
         buf.set_script(harfbuzz::hb_script_t::HB_SCRIPT_LATIN);
@@ -199,7 +208,7 @@ impl HarfbuzzShaper {
                     pair.font.font_changed();
                     cell_width = size.width;
                     shaped_any = pair.shaped_any;
-                    pair.font.shape(&mut buf, self.features.as_slice());
+                    pair.font.shape(&mut buf, features);
                     /*
                     log::info!(
                         "shaped font_idx={} as: {}",
@@ -243,7 +252,15 @@ impl HarfbuzzShaper {
                 // but might potentially discover the text presentation for
                 // that glyph in a fallback font and swap it out a little
                 // later after a flash of showing the emoji one.
-                return self.do_shape(initial_font_idx, s, font_size, dpi, no_glyphs, None);
+                return self.do_shape(
+                    initial_font_idx,
+                    s,
+                    font_size,
+                    dpi,
+                    no_glyphs,
+                    None,
+                    features,
+                );
             }
         }
 
@@ -331,6 +348,7 @@ impl HarfbuzzShaper {
                     dpi,
                     no_glyphs,
                     presentation,
+                    features,
                 ) {
                     Ok(shape) => Ok(shape),
                     Err(e) => {
@@ -342,6 +360,7 @@ impl HarfbuzzShaper {
                             dpi,
                             no_glyphs,
                             presentation,
+                            features,
                         )
                     }
                 }?;
@@ -425,7 +444,7 @@ impl FontShaper for HarfbuzzShaper {
     ) -> anyhow::Result<Vec<GlyphInfo>> {
         log::trace!("shape byte_len={} `{}`", text.len(), text.escape_debug());
         let start = std::time::Instant::now();
-        let result = self.do_shape(0, text, size, dpi, no_glyphs, presentation);
+        let result = self.do_shape(0, text, size, dpi, no_glyphs, presentation, &self.features);
         metrics::histogram!("shape.harfbuzz", start.elapsed());
         /*
         if let Ok(glyphs) = &result {
@@ -440,6 +459,25 @@ impl FontShaper for HarfbuzzShaper {
         result
     }
 
+    fn shape_without_ligatures(
+        &self,
+        text: &str,
+        size: f64,
+        dpi: u32,
+        no_glyphs: &mut Vec<char>,
+        presentation: Option<Presentation>,
+    ) -> anyhow::Result<Vec<GlyphInfo>> {
+        self.do_shape(
+            0,
+            text,
+            size,
+            dpi,
+            no_glyphs,
+            presentation,
+            &self.features_without_ligatures,
+        )
+    }
+
     fn metrics_for_idx(&self, font_idx: usize, size: f64, dpi: u32) -> anyhow::Result<FontMetrics> {
         let mut pair = self
             .load_fallback(font_idx)?
@@ -72,6 +72,20 @@ pub trait FontShaper {
         presentation: Option<termwiz::cell::Presentation>,
     ) -> anyhow::Result<Vec<GlyphInfo>>;
 
+    /// Shape text as `shape` does, but with the font features that
+    /// form ligatures (liga, calt and clig) turned off.  Shapers that
+    /// don't apply font features shape the text as usual.
+    fn shape_without_ligatures(
+        &self,
+        text: &str,
+        size: f64,
+        dpi: u32,
+        no_glyphs: &mut Vec<char>,
+        presentation: Option<termwiz::cell::Presentation>,
+    ) -> anyhow::Result<Vec<GlyphInfo>> {
+        self.shape(text, size, dpi, no_glyphs, presentation)
+    }
+
     /// Compute the font metrics for the preferred font
     /// at the specified size.
     fn metrics(&self, size: f64, dpi: u32) -> anyhow::Result<FontMetrics>;
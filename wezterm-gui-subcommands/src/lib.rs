@@ -39,9 +39,52 @@ pub struct StartCommand {
     /// This changes the class for all windows spawned by this
     /// instance of wezterm, including error, update and ssh
     /// authentication dialogs.
-    #[structopt(long = "class")]
+    /// `--app-id` is accepted as an alias, as used by the
+    /// xdg-terminal-exec specification.
+    #[structopt(long = "class", visible_alias = "app-id")]
     pub class: Option<String>,
 
+    /// Keep the pane open after the program exits, regardless
+    /// of the `exit_behavior` configuration.
+    #[structopt(long = "hold")]
+    pub hold: bool,
+
+    /// Run the program that follows.  This is equivalent to `--`,
+    /// and is accepted for compatibility with other terminal
+    /// emulators and the xdg-terminal-exec specification.
+    #[structopt(short = "e")]
+    pub execute: bool,
+
+    /// Override the default workspace with the provided name.
+    /// If the workspace is described by the `workspaces` section
+    /// of the configuration, its tabs and panes are created
+    /// when the gui starts.
+    #[structopt(long = "workspace")]
+    pub workspace: Option<String>,
+
+    /// Use the named profile from the `profiles` section of the
+    /// configuration, rather than `default_profile`, for the windows
+    /// of the new gui process.
+    #[structopt(long = "profile")]
+    pub profile: Option<String>,
+
+    /// Open the program in a new tab of the window that the
+    /// already running wezterm gui most recently focused, rather
+    /// than starting a new process.  This is the default when
+    /// `single_instance` is enabled in the configuration.
+    #[structopt(long = "single-instance")]
+    pub single_instance: bool,
+
+    /// Always start a new process, even when `single_instance`
+    /// is enabled in the configuration.
+    #[structopt(long = "always-new-process")]
+    pub always_new_process: bool,
+
+    /// When the program is opened by an already running wezterm
+    /// gui, open it in a new window rather than a new tab.
+    #[structopt(long = "new-window")]
+    pub new_window: bool,
+
     /// Instead of executing your shell, run PROG.
     /// For example: `wezterm start -- bash -l` will spawn bash
     /// as if it were a login shell.
@@ -89,6 +132,11 @@ pub struct SerialCommand {
     #[structopt(long = "baud")]
     pub baud: Option<usize>,
 
+    /// Set the character encoding used by the device, such as
+    /// `cp437` or `iso-8859-1`.  The default is UTF-8.
+    #[structopt(long = "encoding")]
+    pub encoding: Option<String>,
+
     /// Specifies the serial device name.
     /// On Windows systems this can be a name like `COM0`.
     /// On posix systems this will be something like `/dev/ttyUSB0`
@@ -112,6 +160,13 @@ pub struct ConnectCommand {
     #[structopt(long = "class")]
     pub class: Option<String>,
 
+    /// Attach without the ability to send input to the panes in
+    /// the domain.  The panes can be viewed and scrolled, but
+    /// keyboard, mouse, paste, spawn, resize and kill requests
+    /// are refused by the server.
+    #[structopt(long = "read-only")]
+    pub read_only: bool,
+
     /// Instead of executing your shell, run PROG.
     /// For example: `wezterm start -- bash -l` will spawn bash
     /// as if it were a login shell.
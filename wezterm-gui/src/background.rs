@@ -0,0 +1,77 @@
+//! When `quit_when_all_windows_are_closed` is false, closing the last
+//! window leaves its panes running in the background, and this process
+//! advertises itself so that the next `wezterm start` shows those panes
+//! again rather than starting afresh.
+use crate::frontend::front_end;
+use crate::guimarker::GuiMarker;
+use crate::TermWindow;
+use ::window::WindowOps;
+use mux::Mux;
+use std::path::PathBuf;
+
+/// Holds the path to the mux socket of the gui that is running
+/// in the background
+const MARKER: GuiMarker = GuiMarker::new("background-gui", "background gui");
+
+fn own_socket_path() -> Option<PathBuf> {
+    std::env::var_os("WEZTERM_UNIX_SOCKET").map(PathBuf::from)
+}
+
+/// Called when the last window has been closed but its panes
+/// have been kept running
+pub fn enter_background() {
+    if let Some(sock_path) = own_socket_path() {
+        MARKER.publish(&sock_path);
+    }
+}
+
+/// Stops advertising this process once it has windows again,
+/// or when it is about to terminate
+pub fn leave_background() {
+    if let Some(sock_path) = own_socket_path() {
+        MARKER.withdraw(&sock_path);
+    }
+}
+
+/// Asks the gui that is running in the background, if any, to show
+/// its windows.  Returns true if it did so, in which case there is
+/// nothing left for this process to do.
+pub fn show_background_gui() -> bool {
+    MARKER.request(|client| async move {
+        client.show_windows().await?;
+        Ok(())
+    })
+}
+
+/// Shows the windows of the active workspace, creating gui windows
+/// for those whose panes were kept running in the background
+pub fn show_windows() {
+    leave_background();
+    let mux = Mux::get().unwrap();
+    let front_end = match front_end() {
+        Some(fe) => fe,
+        None => return,
+    };
+
+    let mut windows = mux.iter_windows_in_workspace(&mux.active_workspace());
+    if windows.is_empty() {
+        if let Some(workspace) = mux.iter_workspaces().first() {
+            mux.set_active_workspace(workspace);
+            windows = mux.iter_windows_in_workspace(workspace);
+        }
+    }
+
+    for window_id in windows {
+        match front_end.gui_window_for_mux_window(window_id) {
+            Some(window) => window.focus(),
+            None => {
+                promise::spawn::spawn(async move {
+                    if let Err(err) = TermWindow::new_window(window_id).await {
+                        log::error!("Failed to show window {}: {:#}", window_id, err);
+                    }
+                })
+                .detach();
+            }
+        }
+    }
+}
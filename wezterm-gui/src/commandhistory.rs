@@ -0,0 +1,280 @@
+//! Records the commands that are reported via OSC 133 shell
+//! integration into `command_history.jsonl` in the wezterm data
+//! directory, so that they can be recalled across sessions.
+use anyhow::Context;
+use mux::pane::PaneId;
+use mux::Mux;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The maximum number of entries to present in the history overlay
+pub const MAX_HISTORY_ENTRIES: usize = 5000;
+
+/// Once the history file holds this many entries, it is rewritten
+/// to keep only the most recent `MAX_HISTORY_ENTRIES` of them
+const MAX_HISTORY_FILE_ENTRIES: usize = MAX_HISTORY_ENTRIES * 2;
+
+lazy_static::lazy_static! {
+    /// The distinct commands from the history, most recent first.
+    /// Loaded on first use and kept up to date by append_record.
+    static ref RECENT_COMMANDS: Mutex<Option<Vec<String>>> = Mutex::new(None);
+    /// Sends records to the thread that writes them to the history
+    /// file; started by the first call to append_record.
+    static ref WRITER: Mutex<Option<Sender<CommandRecord>>> = Mutex::new(None);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandRecord {
+    pub command: String,
+    /// The working directory, as reported via OSC 7
+    pub cwd: Option<String>,
+    /// The host on which the command ran
+    pub host: Option<String>,
+    pub exit_status: Option<i32>,
+    pub duration_ms: u64,
+    pub pane_id: PaneId,
+    pub domain: String,
+    /// When the command completed, in seconds since the unix epoch
+    pub timestamp: u64,
+}
+
+fn history_file_path() -> PathBuf {
+    config::DATA_DIR.join("command_history.jsonl")
+}
+
+/// Builds a record for a command that just completed in `pane_id`
+pub fn command_record(
+    pane_id: PaneId,
+    command: String,
+    exit_status: Option<i32>,
+    duration: Duration,
+) -> CommandRecord {
+    let mux = Mux::get().unwrap();
+    let pane = mux.get_pane(pane_id);
+    let cwd_url = pane
+        .as_ref()
+        .and_then(|pane| pane.get_current_working_dir());
+    let domain = pane
+        .as_ref()
+        .and_then(|pane| mux.get_domain(pane.domain_id()))
+        .map(|domain| domain.domain_name().to_string())
+        .unwrap_or_default();
+
+    let host = cwd_url
+        .as_ref()
+        .and_then(|url| url.host_str())
+        .filter(|host| !host.is_empty() && *host != "localhost")
+        .map(|host| host.to_string())
+        .or_else(|| {
+            hostname::get()
+                .ok()
+                .map(|host| host.to_string_lossy().to_string())
+        });
+
+    CommandRecord {
+        command,
+        cwd: cwd_url.map(|url| url.path().to_string()),
+        host,
+        exit_status,
+        duration_ms: duration.as_millis() as u64,
+        pane_id,
+        domain,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    }
+}
+
+/// Appends `record` to the history.  The file is written by a
+/// background thread so that a slow disk doesn't stall the gui.
+pub fn append_record(record: CommandRecord) {
+    if let Some(commands) = RECENT_COMMANDS.lock().unwrap().as_mut() {
+        commands.retain(|command| *command != record.command);
+        commands.insert(0, record.command.clone());
+        commands.truncate(MAX_HISTORY_ENTRIES);
+    }
+
+    let mut writer = WRITER.lock().unwrap();
+    let sender = writer.get_or_insert_with(|| {
+        let (tx, rx) = channel();
+        std::thread::Builder::new()
+            .name("command_history".to_string())
+            .spawn(move || writer_thread(rx))
+            .expect("failed to spawn command history thread");
+        tx
+    });
+    sender.send(record).ok();
+}
+
+fn writer_thread(rx: Receiver<CommandRecord>) {
+    let path = history_file_path();
+    // The number of entries in the file, counted on the first write
+    let mut entries = None;
+    while let Ok(record) = rx.recv() {
+        if let Err(err) = write_record(&path, &record, &mut entries) {
+            log::error!("Failed to record command history: {:#}", err);
+            entries = None;
+        }
+    }
+}
+
+fn write_record(
+    path: &Path,
+    record: &CommandRecord,
+    entries: &mut Option<usize>,
+) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("creating directory {}", dir.display()))?;
+    }
+    let count = match *entries {
+        Some(count) => count,
+        None => count_entries(path)?,
+    };
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("opening {}", path.display()))?;
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+
+    let count = count + 1;
+    entries.replace(if count > MAX_HISTORY_FILE_ENTRIES {
+        truncate_history(path, MAX_HISTORY_ENTRIES)?
+    } else {
+        count
+    });
+    Ok(())
+}
+
+fn count_entries(path: &Path) -> anyhow::Result<usize> {
+    match std::fs::File::open(path) {
+        Ok(file) => Ok(BufReader::new(file).lines().count()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(err) => Err(err).with_context(|| format!("opening {}", path.display())),
+    }
+}
+
+/// Rewrites the history file to contain only its last `keep` entries,
+/// returning the number of entries that remain
+fn truncate_history(path: &Path, keep: usize) -> anyhow::Result<usize> {
+    let file = std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let lines = BufReader::new(file)
+        .lines()
+        .collect::<Result<Vec<_>, _>>()?;
+    let lines = &lines[lines.len().saturating_sub(keep)..];
+
+    // Write a new file and rename it into place, so that the history
+    // isn't lost if we are interrupted part way through
+    let temp = path.with_extension("jsonl.tmp");
+    let mut file =
+        std::fs::File::create(&temp).with_context(|| format!("creating {}", temp.display()))?;
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
+    drop(file);
+    std::fs::rename(&temp, path)
+        .with_context(|| format!("renaming {} to {}", temp.display(), path.display()))?;
+    Ok(lines.len())
+}
+
+/// Calls `func` with the distinct commands from the history, most
+/// recent first.  The history file is only read the first time.
+pub fn with_recent_commands<F, R>(func: F) -> R
+where
+    F: FnOnce(&[String]) -> R,
+{
+    let mut commands = RECENT_COMMANDS.lock().unwrap();
+    if commands.is_none() {
+        let loaded = match load_history() {
+            Ok(records) => records.into_iter().map(|record| record.command).collect(),
+            Err(err) => {
+                log::error!("Failed to load command history: {:#}", err);
+                vec![]
+            }
+        };
+        commands.replace(loaded);
+    }
+    func(commands.as_ref().map(Vec::as_slice).unwrap_or(&[]))
+}
+
+/// Loads the history, most recent first.  Only the most recent
+/// instance of each distinct command is returned.
+pub fn load_history() -> anyhow::Result<Vec<CommandRecord>> {
+    let path = history_file_path();
+    let file = match std::fs::File::open(&path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err).with_context(|| format!("opening {}", path.display())),
+    };
+
+    let mut records = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        match serde_json::from_str::<CommandRecord>(&line) {
+            Ok(record) => records.push(record),
+            Err(err) => log::trace!("skipping malformed history entry: {}", err),
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    let mut result = vec![];
+    for record in records.into_iter().rev() {
+        if seen.insert(record.command.clone()) {
+            result.push(record);
+            if result.len() >= MAX_HISTORY_ENTRIES {
+                break;
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn truncate() {
+        let path = std::env::temp_dir().join(format!(
+            "wezterm-command-history-{}.jsonl",
+            std::process::id()
+        ));
+        std::fs::remove_file(&path).ok();
+        assert_eq!(count_entries(&path).unwrap(), 0);
+
+        let mut entries = None;
+        for i in 0..10 {
+            let record = CommandRecord {
+                command: format!("echo {}", i),
+                cwd: None,
+                host: None,
+                exit_status: Some(0),
+                duration_ms: 0,
+                pane_id: 0,
+                domain: "local".to_string(),
+                timestamp: i,
+            };
+            write_record(&path, &record, &mut entries).unwrap();
+        }
+        assert_eq!(entries, Some(10));
+        assert_eq!(count_entries(&path).unwrap(), 10);
+
+        assert_eq!(truncate_history(&path, 3).unwrap(), 3);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let commands: Vec<String> = contents
+            .lines()
+            .map(|line| serde_json::from_str::<CommandRecord>(line).unwrap().command)
+            .collect();
+        assert_eq!(commands, vec!["echo 7", "echo 8", "echo 9"]);
+        std::fs::remove_file(&path).ok();
+    }
+}
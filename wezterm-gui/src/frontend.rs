@@ -2,18 +2,24 @@ use crate::TermWindow;
 use ::window::*;
 use anyhow::Error;
 pub use config::FrontEndSelection;
+use mux::window::WindowId as MuxWindowId;
 use mux::{Mux, MuxNotification};
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 use wezterm_term::Alert;
 use wezterm_toast_notification::*;
 
 pub struct GuiFrontEnd {
     connection: Rc<Connection>,
+    /// The gui windows, keyed by the mux window that they display
+    known_windows: RefCell<HashMap<MuxWindowId, Window>>,
+    _config_subscription: config::ConfigSubscription,
 }
 
 impl Drop for GuiFrontEnd {
     fn drop(&mut self) {
+        wezterm_tray::hide();
         ::window::shutdown();
     }
 }
@@ -21,7 +27,19 @@ impl Drop for GuiFrontEnd {
 impl GuiFrontEnd {
     pub fn try_new() -> anyhow::Result<Rc<GuiFrontEnd>> {
         let connection = Connection::init()?;
-        let front_end = Rc::new(GuiFrontEnd { connection });
+        // The tray icon may have been enabled or disabled
+        let config_subscription = config::subscribe_to_config_reload(|| {
+            promise::spawn::spawn_into_main_thread(async {
+                crate::tray::update();
+            })
+            .detach();
+            true
+        });
+        let front_end = Rc::new(GuiFrontEnd {
+            connection,
+            known_windows: RefCell::new(HashMap::new()),
+            _config_subscription: config_subscription,
+        });
         let mux = Mux::get().expect("mux started and running on main thread");
         let fe = Rc::downgrade(&front_end);
         mux.subscribe(move |n| {
@@ -37,12 +55,38 @@ impl GuiFrontEnd {
                             anyhow::Result::<()>::Ok(())
                         })
                         .detach();
+                        crate::tray::update();
+                    }
+                    MuxNotification::WindowRemoved(_) => {
+                        crate::tray::update();
+                    }
+                    MuxNotification::PaneRemoved(pane_id) => {
+                        crate::tray::pane_removed(pane_id);
+                        crate::scripting::pane::unregister_serial_pane(pane_id);
                     }
-                    MuxNotification::WindowRemoved(_) => {}
-                    MuxNotification::PaneRemoved(_) => {}
                     MuxNotification::WindowInvalidated(_) => {}
-                    MuxNotification::PaneOutput(_) => {}
+                    MuxNotification::PaneOutput(pane_id) => {
+                        crate::tray::pane_output(pane_id);
+                    }
                     MuxNotification::PaneAdded(_) => {}
+                    MuxNotification::ActiveWorkspaceChanged(_) => {
+                        crate::tray::update();
+                    }
+                    MuxNotification::WorkspaceCreated(name) => {
+                        crate::workspace::emit_workspace_event("workspace-created", vec![name]);
+                        crate::tray::update();
+                    }
+                    MuxNotification::WorkspaceRenamed { old, new } => {
+                        crate::workspace::emit_workspace_event("workspace-renamed", vec![old, new]);
+                        crate::tray::update();
+                    }
+                    MuxNotification::WorkspaceClosed(name) => {
+                        crate::workspace::emit_workspace_event("workspace-closed", vec![name]);
+                        crate::tray::update();
+                    }
+                    MuxNotification::ShowWindowsRequested => {
+                        crate::background::show_windows();
+                    }
                     MuxNotification::Alert {
                         pane_id: _,
                         alert:
@@ -65,6 +109,31 @@ impl GuiFrontEnd {
                     } => {
                         // Handled via TermWindowNotif; NOP it here.
                     }
+                    MuxNotification::Alert {
+                        pane_id,
+                        alert:
+                            Alert::CommandCompleted {
+                                command,
+                                exit_status,
+                                duration,
+                            },
+                    } => {
+                        if config::configuration().enable_command_history {
+                            let record = crate::commandhistory::command_record(
+                                pane_id,
+                                command,
+                                exit_status,
+                                duration,
+                            );
+                            crate::commandhistory::append_record(record);
+                        }
+                    }
+                    MuxNotification::Alert {
+                        pane_id: _,
+                        alert: Alert::RemoteControl(_),
+                    } => {
+                        // Handled by the process that hosts the pane
+                    }
                     MuxNotification::Alert {
                         pane_id: _,
                         alert: Alert::PaletteChanged,
@@ -85,9 +154,29 @@ impl GuiFrontEnd {
                 false
             }
         });
+        crate::tray::update();
         Ok(front_end)
     }
 
+    pub fn record_known_window(&self, window: Window, mux_window_id: MuxWindowId) {
+        self.known_windows
+            .borrow_mut()
+            .insert(mux_window_id, window);
+    }
+
+    pub fn forget_known_window(&self, mux_window_id: MuxWindowId) {
+        self.known_windows.borrow_mut().remove(&mux_window_id);
+    }
+
+    pub fn known_window_count(&self) -> usize {
+        self.known_windows.borrow().len()
+    }
+
+    /// Returns the gui window that displays `mux_window_id`
+    pub fn gui_window_for_mux_window(&self, mux_window_id: MuxWindowId) -> Option<Window> {
+        self.known_windows.borrow().get(&mux_window_id).cloned()
+    }
+
     pub fn run_forever(&self) -> anyhow::Result<()> {
         self.connection.run_message_loop()
     }
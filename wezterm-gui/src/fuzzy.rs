@@ -0,0 +1,82 @@
+/// Scores `text` against the fuzzy `pattern`: each character of the
+/// pattern must appear in order, ignoring case.  Returns None if it
+/// doesn't match; otherwise a higher score is a better match.
+/// Consecutive runs and matches at word boundaries score higher.
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut pattern = pattern.chars().flat_map(char::to_lowercase).peekable();
+    let mut prev_matched = false;
+    let mut prev_char = ' ';
+
+    for c in text.chars() {
+        let wanted = match pattern.peek() {
+            Some(wanted) => *wanted,
+            None => break,
+        };
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        if lower == wanted {
+            pattern.next();
+            score += 1;
+            if prev_matched {
+                score += 5;
+            }
+            if !prev_char.is_alphanumeric() {
+                score += 3;
+            }
+            prev_matched = true;
+        } else {
+            prev_matched = false;
+        }
+        prev_char = c;
+    }
+
+    if pattern.peek().is_some() {
+        None
+    } else {
+        Some(score)
+    }
+}
+
+/// Returns the indices of the `items` that `score` matches, best
+/// match first.  The sort is stable, so equally good matches remain
+/// in the order in which they appear in `items`.
+pub fn filter_by_score<T>(items: &[T], score: impl Fn(&T) -> Option<i64>) -> Vec<usize> {
+    let mut matches: Vec<(i64, usize)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, item)| score(item).map(|score| (score, idx)))
+        .collect();
+    matches.sort_by(|a, b| b.0.cmp(&a.0));
+    matches.into_iter().map(|(_, idx)| idx).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert!(fuzzy_score("gco", "git checkout").is_some());
+        assert!(fuzzy_score("GCO", "git checkout").is_some());
+        assert!(fuzzy_score("xyz", "git checkout").is_none());
+        assert!(fuzzy_score("ba", "abc").is_none());
+        assert!(
+            fuzzy_score("check", "git checkout").unwrap()
+                > fuzzy_score("check", "cargo heck").unwrap()
+        );
+    }
+
+    #[test]
+    fn filter() {
+        let items = ["cargo heck", "git checkout", "ls"];
+        assert_eq!(
+            filter_by_score(&items, |item| fuzzy_score("", item)),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            filter_by_score(&items, |item| fuzzy_score("check", item)),
+            vec![1, 0]
+        );
+    }
+}
@@ -306,6 +306,15 @@ impl GlyphCache<SrgbTexture2d> {
 }
 
 impl<T: Texture2d> GlyphCache<T> {
+    /// Returns the number of bytes used by the decoded images
+    /// held in the image cache
+    pub fn image_cache_memory_usage(&self) -> usize {
+        self.image_cache
+            .iter()
+            .map(|(_, decoded)| decoded.image.len())
+            .sum()
+    }
+
     /// Resolve a glyph from the cache, rendering the glyph on-demand if
     /// the cache doesn't already hold the desired glyph.
     pub fn cached_glyph(
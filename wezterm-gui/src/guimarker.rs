@@ -0,0 +1,96 @@
+//! A gui process can advertise the path of its mux socket in a marker
+//! file in the runtime directory, so that a later invocation of
+//! `wezterm start` can find it and make requests of it rather than
+//! starting afresh.
+use config::UnixDomain;
+use mux::connui::ConnectionUI;
+use mux::domain::alloc_domain_id;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use wezterm_client::client::Client;
+
+/// A marker file that advertises a gui process
+pub struct GuiMarker {
+    /// The name of the marker file in the runtime directory
+    name: &'static str,
+    /// Describes the advertised gui in log messages
+    description: &'static str,
+}
+
+impl GuiMarker {
+    pub const fn new(name: &'static str, description: &'static str) -> Self {
+        Self { name, description }
+    }
+
+    fn path(&self) -> PathBuf {
+        config::RUNTIME_DIR.join(self.name)
+    }
+
+    /// Advertises the gui whose mux socket is `sock_path`
+    pub fn publish(&self, sock_path: &Path) {
+        if let Err(err) = std::fs::write(self.path(), sock_path.to_string_lossy().as_bytes()) {
+            log::error!(
+                "Failed to record {} as the {}: {:#}",
+                sock_path.display(),
+                self.description,
+                err
+            );
+        }
+    }
+
+    /// Stops advertising the gui whose mux socket is `sock_path`,
+    /// if it is still the one that is being advertised
+    pub fn withdraw(&self, sock_path: &Path) {
+        if let Ok(recorded) = std::fs::read_to_string(self.path()) {
+            if PathBuf::from(recorded) == sock_path {
+                std::fs::remove_file(self.path()).ok();
+            }
+        }
+    }
+
+    /// Connects to the advertised gui, if any, and makes the requests
+    /// of `func`.  Returns true if they succeeded.  If the gui doesn't
+    /// respond, the marker is stale and is removed.
+    pub fn request<F, FUT>(&self, func: F) -> bool
+    where
+        F: FnOnce(Client) -> FUT,
+        FUT: Future<Output = anyhow::Result<()>>,
+    {
+        let sock_path = match std::fs::read_to_string(self.path()) {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => return false,
+        };
+
+        // The client may schedule work on the main thread when it is
+        // torn down; give it somewhere to go
+        let _executor = promise::spawn::SimpleExecutor::new();
+        let result = promise::spawn::block_on(async {
+            let mut ui = ConnectionUI::new_headless();
+            let client = Client::new_unix_domain(
+                alloc_domain_id(),
+                &UnixDomain {
+                    socket_path: Some(sock_path.clone()),
+                    no_serve_automatically: true,
+                    ..Default::default()
+                },
+                true,
+                &mut ui,
+            )?;
+            func(client).await
+        });
+
+        match result {
+            Ok(()) => true,
+            Err(err) => {
+                log::warn!(
+                    "{} at {} is not responding: {:#}",
+                    self.description,
+                    sock_path.display(),
+                    err
+                );
+                std::fs::remove_file(self.path()).ok();
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Single instance mode: rather than starting another gui process,
+//! `wezterm start` asks the gui that is already running to open its
+//! program in a new tab of the window that it most recently focused.
+use crate::guimarker::GuiMarker;
+use codec::SpawnV2;
+use config::keyassignment::SpawnTabDomain;
+use portable_pty::cmdbuilder::CommandBuilder;
+use std::path::{Path, PathBuf};
+use wezterm_gui_subcommands::StartCommand;
+
+/// Holds the path to the mux socket of the most recently started gui
+const MARKER: GuiMarker = GuiMarker::new("gui-instance", "running gui");
+
+/// Advertises this process as the gui that subsequent invocations
+/// of `wezterm start` can hand their programs to
+pub fn publish(sock_path: &Path) {
+    MARKER.publish(sock_path);
+}
+
+/// Stops advertising this process, if it is still the one that
+/// is being advertised
+pub fn withdraw(sock_path: &Path) {
+    MARKER.withdraw(sock_path);
+}
+
+/// Returns true if `opts` should be handed to the gui that is
+/// already running, rather than starting a new process
+pub fn should_use_existing_instance(opts: &StartCommand) -> bool {
+    if opts.always_new_process {
+        return false;
+    }
+    // The class, workspace and exit behavior are properties of
+    // the process that is started, so they always require a new one
+    if opts.class.is_some() || opts.workspace.is_some() || opts.hold {
+        return false;
+    }
+    opts.single_instance || config::configuration().single_instance
+}
+
+/// Asks the gui that is already running, if any, to spawn the program
+/// described by `opts`.  Returns true if it did so, in which case
+/// there is nothing left for this process to do.
+pub fn spawn_in_existing_instance(opts: &StartCommand) -> bool {
+    let command = if opts.prog.is_empty() {
+        None
+    } else {
+        Some(CommandBuilder::from_argv(opts.prog.clone()))
+    };
+    // The running gui has its own working directory, so relative
+    // paths must be resolved here
+    let command_dir = opts.cwd.as_ref().map(|cwd| {
+        std::env::current_dir()
+            .map(|dir| dir.join(cwd))
+            .unwrap_or_else(|_| PathBuf::from(cwd))
+            .to_string_lossy()
+            .to_string()
+    });
+    let new_window = opts.new_window;
+    let profile = opts.profile.clone();
+
+    MARKER.request(|client| async move {
+        let window_id = if new_window {
+            None
+        } else {
+            client.get_focused_window().await?.window_id
+        };
+        client
+            .spawn_v2(SpawnV2 {
+                domain: SpawnTabDomain::DefaultDomain,
+                window_id,
+                command,
+                command_dir,
+                size: config::configuration().initial_size(),
+                encoding: None,
+                profile,
+            })
+            .await?;
+        client.show_windows().await?;
+        Ok(())
+    })
+}
@@ -6,6 +6,7 @@ use ::window::*;
 use anyhow::anyhow;
 use mux::activity::Activity;
 use mux::domain::{Domain, LocalDomain};
+use mux::encoding::CharacterEncoding;
 use mux::Mux;
 use portable_pty::cmdbuilder::CommandBuilder;
 use promise::spawn::block_on;
@@ -20,10 +21,15 @@ use wezterm_gui_subcommands::*;
 use wezterm_ssh::*;
 use wezterm_toast_notification::*;
 
+mod background;
 mod cache;
+mod commandhistory;
 mod customglyph;
 mod frontend;
+mod fuzzy;
 mod glyphcache;
+mod guimarker;
+mod instance;
 mod markdown;
 mod overlay;
 mod quad;
@@ -32,11 +38,15 @@ mod scripting;
 mod scrollbar;
 mod selection;
 mod shapecache;
+mod sound;
+mod startupprofile;
 mod stats;
 mod tabbar;
 mod termwindow;
+mod tray;
 mod update;
 mod utilsprites;
+mod workspace;
 
 pub use selection::SelectionMode;
 pub use termwindow::set_window_class;
@@ -71,13 +81,25 @@ struct Opt {
         number_of_values = 1)]
     config_override: Vec<(String, String)>,
 
+    /// Print how long the phases of startup, such as evaluating the
+    /// configuration and initializing fonts, took once the first
+    /// window has been painted
+    #[structopt(long = "profile-startup")]
+    profile_startup: bool,
+
     #[structopt(subcommand)]
     cmd: Option<SubCommand>,
 }
 
 #[derive(Debug, StructOpt, Clone)]
 enum SubCommand {
-    #[structopt(name = "start", about = "Start a front-end")]
+    // Everything that follows the program name is passed to the
+    // program, so that `wezterm start -e prog --flag` works
+    #[structopt(
+        name = "start",
+        about = "Start a front-end",
+        setting = structopt::clap::AppSettings::TrailingVarArg
+    )]
     Start(StartCommand),
 
     #[structopt(name = "ssh", about = "Establish an ssh session")]
@@ -137,10 +159,12 @@ async fn async_run_ssh(opts: SshCommand) -> anyhow::Result<()> {
     // Allow spawning local commands into new tabs/panes
     let local_domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
     mux.add_domain(&local_domain);
+    mux::exec::register_exec_domains()?;
+    mux::container::register_container_domains();
 
-    let window_id = mux.new_empty_window();
+    let window_id = mux.new_empty_window(None);
     let _tab = domain
-        .spawn(config.initial_size(), cmd, None, *window_id)
+        .spawn(config.initial_size(), cmd, None, None, *window_id)
         .await?;
 
     Ok(())
@@ -175,8 +199,13 @@ fn run_serial(config: config::ConfigHandle, opts: &SerialCommand) -> anyhow::Res
         serial.set_baud_rate(serial::BaudRate::from_speed(baud));
     }
 
+    let serial_handle = serial.handle();
     let pty_system = Box::new(serial);
-    let domain: Arc<dyn Domain> = Arc::new(LocalDomain::with_pty_system("local", pty_system));
+    let mut domain = LocalDomain::with_pty_system("local", pty_system);
+    if let Some(encoding) = opts.encoding.as_ref() {
+        domain = domain.with_encoding(CharacterEncoding::for_label(encoding)?);
+    }
+    let domain: Arc<dyn Domain> = Arc::new(domain);
     let mux = Rc::new(mux::Mux::new(Some(domain.clone())));
     Mux::set_mux(&mux);
     crate::update::load_last_release_info_and_set_banner();
@@ -185,9 +214,12 @@ fn run_serial(config: config::ConfigHandle, opts: &SerialCommand) -> anyhow::Res
     block_on(domain.attach())?; // FIXME: blocking
 
     {
-        let window_id = mux.new_empty_window();
+        let window_id = mux.new_empty_window(None);
         // FIXME: blocking
-        let _tab = block_on(domain.spawn(config.initial_size(), None, None, *window_id))?;
+        let tab = block_on(domain.spawn(config.initial_size(), None, None, None, *window_id))?;
+        if let Some(pane) = tab.get_active_pane() {
+            crate::scripting::pane::register_serial_pane(pane.pane_id(), serial_handle);
+        }
     }
 
     maybe_show_configuration_error_window();
@@ -225,15 +257,19 @@ fn run_mux_client(config: config::ConfigHandle, opts: &ConnectCommand) -> anyhow
             )
         })?;
 
-    let domain: Arc<dyn Domain> = Arc::new(ClientDomain::new(client_config));
+    let mut client_domain = ClientDomain::new(client_config);
+    client_domain.set_read_only(opts.read_only);
+    let domain: Arc<dyn Domain> = Arc::new(client_domain);
     let mux = Rc::new(mux::Mux::new(Some(domain.clone())));
     Mux::set_mux(&mux);
     crate::update::load_last_release_info_and_set_banner();
     // Allow spawning local commands into new tabs/panes
     let local_domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
     mux.add_domain(&local_domain);
+    mux::exec::register_exec_domains()?;
 
     let gui = crate::frontend::try_new()?;
+    mux::container::register_container_domains();
     let opts = opts.clone();
 
     let cmd = if !opts.prog.is_empty() {
@@ -275,10 +311,18 @@ async fn spawn_tab_in_default_domain_if_mux_is_empty(
         return Ok(());
     }
 
+    if cmd.is_none() {
+        if let Some(def) = crate::workspace::gui_startup_definition(&mux.active_workspace()) {
+            // The gui-startup event may have chosen a different workspace
+            mux.set_active_workspace(&def.name);
+            return crate::workspace::spawn_workspace(&def).await;
+        }
+    }
+
     let config = config::configuration();
-    let window_id = mux.new_empty_window();
+    let window_id = mux.new_empty_window(None);
     let _tab = domain
-        .spawn(config.initial_size(), cmd, None, *window_id)
+        .spawn(config.initial_size(), cmd, None, None, *window_id)
         .await?;
     Ok(())
 }
@@ -329,9 +373,26 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
         });
     }
 
+    let sock_path = unix_socket_path.clone();
     let run = move || -> anyhow::Result<()> {
+        if crate::instance::should_use_existing_instance(&opts)
+            && crate::instance::spawn_in_existing_instance(&opts)
+        {
+            return Ok(());
+        }
+
         let need_builder = !opts.prog.is_empty() || opts.cwd.is_some();
 
+        // Rather than starting afresh, show the panes that a previous
+        // instance kept running in the background
+        if !need_builder
+            && opts.workspace.is_none()
+            && !config::configuration().quit_when_all_windows_are_closed
+            && crate::background::show_background_gui()
+        {
+            return Ok(());
+        }
+
         let cmd = if need_builder {
             let config = config::configuration();
             let prog = opts.prog.iter().map(|s| s.as_os_str()).collect::<Vec<_>>();
@@ -347,9 +408,15 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
         let domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
         let mux = Rc::new(mux::Mux::new(Some(domain.clone())));
         Mux::set_mux(&mux);
+        if let Some(workspace) = opts.workspace.as_ref() {
+            mux.set_active_workspace(workspace);
+        }
+        mux::exec::register_exec_domains()?;
         crate::update::load_last_release_info_and_set_banner();
 
         let gui = crate::frontend::try_new()?;
+        crate::instance::publish(&sock_path);
+        mux::container::register_container_domains();
         let activity = Activity::new();
         let do_auto_connect = !opts.no_auto_connect;
 
@@ -362,11 +429,14 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
         .detach();
 
         maybe_show_configuration_error_window();
+        maybe_offer_crash_report();
         gui.run_forever()
     };
 
     let res = run();
 
+    crate::background::leave_background();
+    crate::instance::withdraw(&unix_socket_path);
     std::fs::remove_file(unix_socket_path).ok();
 
     res
@@ -418,6 +488,49 @@ fn maybe_show_configuration_error_window() {
     }
 }
 
+/// If a crash report was written by a previous run, offer to show it.
+/// Each report is offered only once.
+fn maybe_offer_crash_report() {
+    if !config::configuration().enable_crash_reports {
+        return;
+    }
+    let report = match env_bootstrap::crash::pending_crash_reports().pop() {
+        Some(report) => report,
+        None => return,
+    };
+
+    // Prompting blocks, so it must not happen on the main thread
+    std::thread::spawn(move || {
+        let ui = mux::connui::ConnectionUI::new_with_no_close_delay();
+        ui.title("wezterm Crash Report");
+        let result = ui.run_and_log_error(|| {
+            let text = std::fs::read_to_string(&report)?;
+            let seen = env_bootstrap::crash::mark_crash_report_seen(&report)?;
+            for older in env_bootstrap::crash::pending_crash_reports() {
+                env_bootstrap::crash::mark_crash_report_seen(&older)?;
+            }
+
+            ui.output_str(&format!(
+                "wezterm crashed since it was last started.\n\
+                 A report was saved to {}\n\
+                 Please consider attaching it to an issue.\n\n",
+                seen.display()
+            ));
+            let answer = ui.input("View the report now? [y/N] ")?;
+            if answer.trim().eq_ignore_ascii_case("y") {
+                ui.output_str(&format!("\n{}\n", text));
+                ui.input("Press Enter to close this window")?;
+            }
+            Ok(())
+        });
+        // Leave the window open if something went wrong, so that
+        // the error can be seen
+        if result.is_ok() {
+            ui.close();
+        }
+    });
+}
+
 pub fn run_ls_fonts(config: config::ConfigHandle, cmd: &LsFontsCommand) -> anyhow::Result<()> {
     use wezterm_font::parser::ParsedFont;
 
@@ -580,11 +693,29 @@ fn run() -> anyhow::Result<()> {
     let _saver = umask::UmaskSaver::new();
 
     let opts = Opt::from_args();
-    config::common_init(
-        opts.config_file.as_ref(),
-        &opts.config_override,
-        opts.skip_config,
-    );
+    if opts.profile_startup {
+        startupprofile::enable();
+    }
+    let mut config_override = opts.config_override.clone();
+    if let Some(SubCommand::Start(start)) = &opts.cmd {
+        if start.hold {
+            config_override.push(("exit_behavior".to_string(), "\"Hold\"".to_string()));
+        }
+        if let Some(profile) = &start.profile {
+            config_override.push((
+                "default_profile".to_string(),
+                serde_json::to_string(profile)?,
+            ));
+        }
+    }
+    startupprofile::time("config evaluation", || {
+        config::common_init(
+            opts.config_file.as_ref(),
+            &config_override,
+            opts.skip_config,
+        )
+    });
+    env_bootstrap::crash::install_crash_handler("wezterm-gui");
     let config = config::configuration();
 
     match opts
@@ -0,0 +1,189 @@
+use crate::commandhistory::{load_history, CommandRecord};
+use crate::fuzzy::{filter_by_score, fuzzy_score};
+use crate::termwindow::clipboard::ClipboardHelper;
+use mux::pane::PaneId;
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::io::Write;
+use termwiz::cell::{AttributeChange, CellAttributes, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+use wezterm_term::{Clipboard, ClipboardSelection};
+
+/// Returns the indices of the entries in `history` that match
+/// `filter`, best match first
+fn filter_history(history: &[CommandRecord], filter: &str) -> Vec<usize> {
+    filter_by_score(history, |record| fuzzy_score(filter, &record.command))
+}
+
+fn describe(record: &CommandRecord) -> String {
+    let mut info = vec![];
+    match record.exit_status {
+        Some(0) => info.push("✓".to_string()),
+        Some(status) => info.push(format!("✗ {}", status)),
+        None => {}
+    }
+    info.push(format!("{:.1}s", record.duration_ms as f64 / 1000.));
+    if let Some(cwd) = &record.cwd {
+        match &record.host {
+            Some(host) => info.push(format!("{}:{}", host, cwd)),
+            None => info.push(cwd.to_string()),
+        }
+    }
+    info.join("  ")
+}
+
+pub fn command_history(
+    _tab_id: TabId,
+    mut term: TermWizTerminal,
+    pane_id: PaneId,
+    clipboard: ClipboardHelper,
+) -> anyhow::Result<()> {
+    term.render(&[
+        Change::Title("Command History".to_string()),
+        Change::Text("Loading command history...".to_string()),
+    ])?;
+    term.flush()?;
+
+    // This runs on the overlay thread, so a large history file
+    // doesn't stall the gui while it is read
+    let history = match load_history() {
+        Ok(history) => history,
+        Err(err) => {
+            log::error!("Failed to load command history: {:#}", err);
+            return Ok(());
+        }
+    };
+
+    let mut filter = String::new();
+    let mut matches = filter_history(&history, &filter);
+    let mut active_idx = 0;
+
+    term.set_raw_mode()?;
+
+    fn render(
+        active_idx: usize,
+        filter: &str,
+        matches: &[usize],
+        history: &[CommandRecord],
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let max_rows = size.rows.saturating_sub(2);
+        // Scroll so that the active entry is visible
+        let top = active_idx.saturating_sub(max_rows.saturating_sub(1));
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(
+                "Type to filter.  Enter: run  Ctrl-Y: copy  Escape: cancel\r\n".to_string(),
+            ),
+            Change::AllAttributes(CellAttributes::default()),
+            Change::Text(format!("> {}\r\n", filter)),
+        ];
+
+        for (row, &idx) in matches.iter().enumerate().skip(top).take(max_rows) {
+            let record = &history[idx];
+            if row == active_idx {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            changes.push(Change::Text(format!(
+                " {} ",
+                record.command.replace('\n', "⏎")
+            )));
+            changes.push(AttributeChange::Intensity(Intensity::Half).into());
+            changes.push(Change::Text(format!(" {}", describe(record))));
+            changes.push(Change::AllAttributes(CellAttributes::default()));
+            changes.push(Change::Text("\r\n".to_string()));
+        }
+
+        term.render(&changes)?;
+        term.flush()
+    }
+
+    render(active_idx, &filter, &matches, &history, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                active_idx = (active_idx + 1).min(matches.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('y'),
+                modifiers: Modifiers::CTRL,
+            }) => {
+                if let Some(&idx) = matches.get(active_idx) {
+                    clipboard.set_contents(
+                        ClipboardSelection::Clipboard,
+                        Some(history[idx].command.clone()),
+                    )?;
+                }
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                if let Some(&idx) = matches.get(active_idx) {
+                    let command = format!("{}\r", history[idx].command);
+                    promise::spawn::spawn_into_main_thread(async move {
+                        let mux = Mux::get().unwrap();
+                        if let Some(pane) = mux.get_pane(pane_id) {
+                            if let Err(err) = pane.writer().write_all(command.as_bytes()) {
+                                log::error!("Failed to send command to pane: {:#}", err);
+                            }
+                        }
+                    })
+                    .detach();
+                }
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) => {
+                filter.pop();
+                matches = filter_history(&history, &filter);
+                active_idx = 0;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) => {
+                filter.push(c);
+                matches = filter_history(&history, &filter);
+                active_idx = 0;
+            }
+            _ => {}
+        }
+        render(active_idx, &filter, &matches, &history, &mut term)?;
+    }
+
+    Ok(())
+}
@@ -1,16 +1,26 @@
+use crate::termwindow::clipboard::ClipboardHelper;
+use crate::termwindow::spawn::{apply_env_file, SpawnWhere};
 use crate::TermWindow;
+use config::keyassignment::SpawnCommand;
+use config::TermConfig;
 use mux::pane::PaneId;
 use mux::tab::TabId;
 use mux::termwiztermtab::TermWizTerminal;
 use mux::window::WindowId;
 use mux::Mux;
+use portable_pty::PtySize;
+use std::path::PathBuf;
+use std::sync::Arc;
 use termwiz::cell::AttributeChange;
 use termwiz::color::ColorAttribute;
 use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
 use termwiz::surface::{Change, CursorVisibility, Position};
 use termwiz::terminal::Terminal;
 
-fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Result<bool> {
+pub(crate) fn run_confirmation_app(
+    message: &str,
+    term: &mut TermWizTerminal,
+) -> anyhow::Result<bool> {
     term.set_raw_mode()?;
 
     let size = term.get_screen_size()?;
@@ -172,8 +182,10 @@ pub fn confirm_close_pane(
     mut term: TermWizTerminal,
     mux_window_id: WindowId,
     window: ::window::Window,
+    message: Option<String>,
 ) -> anyhow::Result<()> {
-    if run_confirmation_app("🛑 Really kill this pane?", &mut term)? {
+    let message = message.unwrap_or_else(|| "🛑 Really kill this pane?".to_string());
+    if run_confirmation_app(&message, &mut term)? {
         promise::spawn::spawn_into_main_thread(async move {
             let mux = Mux::get().unwrap();
             let tab = match mux.get_active_tab_for_window(mux_window_id) {
@@ -194,11 +206,11 @@ pub fn confirm_close_tab(
     mut term: TermWizTerminal,
     _mux_window_id: WindowId,
     window: ::window::Window,
+    message: Option<String>,
 ) -> anyhow::Result<()> {
-    if run_confirmation_app(
-        "🛑 Really kill this tab and all contained panes?",
-        &mut term,
-    )? {
+    let message =
+        message.unwrap_or_else(|| "🛑 Really kill this tab and all contained panes?".to_string());
+    if run_confirmation_app(&message, &mut term)? {
         promise::spawn::spawn_into_main_thread(async move {
             let mux = Mux::get().unwrap();
             mux.remove_tab(tab_id);
@@ -215,11 +227,12 @@ pub fn confirm_close_window(
     mux_window_id: WindowId,
     window: ::window::Window,
     tab_id: TabId,
+    message: Option<String>,
 ) -> anyhow::Result<()> {
-    if run_confirmation_app(
-        "🛑 Really kill this window and all contained tabs and panes?",
-        &mut term,
-    )? {
+    let message = message.unwrap_or_else(|| {
+        "🛑 Really kill this window and all contained tabs and panes?".to_string()
+    });
+    if run_confirmation_app(&message, &mut term)? {
         promise::spawn::spawn_into_main_thread(async move {
             let mux = Mux::get().unwrap();
             mux.kill_window(mux_window_id);
@@ -231,12 +244,38 @@ pub fn confirm_close_window(
     Ok(())
 }
 
+pub fn confirm_close_workspace(
+    mut term: TermWizTerminal,
+    workspace: String,
+    window: ::window::Window,
+    tab_id: TabId,
+    message: Option<String>,
+) -> anyhow::Result<()> {
+    let message = message.unwrap_or_else(|| {
+        format!(
+            "🛑 Really kill all of the windows in workspace {}, along with their tabs and panes?",
+            workspace
+        )
+    });
+    if run_confirmation_app(&message, &mut term)? {
+        promise::spawn::spawn_into_main_thread(async move {
+            crate::workspace::close_workspace(&workspace);
+        })
+        .detach();
+    }
+    TermWindow::schedule_cancel_overlay(window, tab_id, None);
+
+    Ok(())
+}
+
 pub fn confirm_quit_program(
     mut term: TermWizTerminal,
     window: ::window::Window,
     tab_id: TabId,
+    message: Option<String>,
 ) -> anyhow::Result<()> {
-    if run_confirmation_app("🛑 Really Quit WezTerm?", &mut term)? {
+    let message = message.unwrap_or_else(|| "🛑 Really Quit WezTerm?".to_string());
+    if run_confirmation_app(&message, &mut term)? {
         promise::spawn::spawn_into_main_thread(async move {
             use ::window::{Connection, ConnectionOps};
             let con = Connection::get().expect("call on gui thread");
@@ -248,3 +287,42 @@ pub fn confirm_quit_program(
 
     Ok(())
 }
+
+/// Asks whether to load the environment from `env_file`, which has
+/// not been used before, and then spawns `spawn`
+pub fn confirm_load_env_file(
+    mut term: TermWizTerminal,
+    env_file: PathBuf,
+    mut spawn: SpawnCommand,
+    spawn_where: SpawnWhere,
+    size: PtySize,
+    mux_window_id: WindowId,
+    clipboard: ClipboardHelper,
+    term_config: Arc<TermConfig>,
+) -> anyhow::Result<()> {
+    let message = format!(
+        "⚠ Load environment variables from {}? It will be trusted from now on.",
+        env_file.display()
+    );
+    let load = run_confirmation_app(&message, &mut term)?;
+    if load {
+        if let Err(err) = config::trust_env_file(&env_file) {
+            log::error!("Failed to trust {}: {:#}", env_file.display(), err);
+        }
+    }
+    promise::spawn::spawn_into_main_thread(async move {
+        let env_file_loaded = load && apply_env_file(&mut spawn, env_file).await;
+        TermWindow::spawn_command_impl(
+            &spawn,
+            spawn_where,
+            size,
+            mux_window_id,
+            clipboard,
+            term_config,
+            env_file_loaded,
+        );
+    })
+    .detach();
+
+    Ok(())
+}
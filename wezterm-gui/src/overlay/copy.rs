@@ -14,9 +14,7 @@ use termwiz::surface::{CursorVisibility, SequenceNo};
 use unicode_segmentation::*;
 use url::Url;
 use wezterm_term::color::ColorPalette;
-use wezterm_term::{
-    unicode_column_width, Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex,
-};
+use wezterm_term::{Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
 use window::WindowOps;
 
 pub struct CopyOverlay {
@@ -79,6 +77,16 @@ impl CopyRenderable {
         if self.cursor.y >= max_row {
             self.cursor.y = max_row - 1;
         }
+
+        // Keep the cursor on the first cell of a wide grapheme
+        if let Some(line) = self.line_at(self.cursor.y) {
+            self.cursor.x = line.grapheme_range(self.cursor.x).start;
+        }
+    }
+
+    fn line_at(&self, y: StableRowIndex) -> Option<Line> {
+        let (_top, mut lines) = self.delegate.get_lines(y..y + 1);
+        lines.pop()
     }
 
     fn select_to_cursor_pos(&mut self) {
@@ -109,6 +117,7 @@ impl CopyRenderable {
                 let mut selection = term_window.selection(pane_id);
                 selection.start = Some(start);
                 selection.range = Some(range);
+                selection.rectangular = false;
                 window.invalidate();
             })));
         self.adjust_viewport_for_cursor_position();
@@ -197,12 +206,18 @@ impl CopyRenderable {
     }
 
     fn move_left_single_cell(&mut self) {
-        self.cursor.x = self.cursor.x.saturating_sub(1);
+        self.cursor.x = match self.line_at(self.cursor.y) {
+            Some(line) => line.prev_grapheme(self.cursor.x).unwrap_or(0),
+            None => self.cursor.x.saturating_sub(1),
+        };
         self.select_to_cursor_pos();
     }
 
     fn move_right_single_cell(&mut self) {
-        self.cursor.x += 1;
+        self.cursor.x = match self.line_at(self.cursor.y) {
+            Some(line) => line.next_grapheme(self.cursor.x),
+            None => self.cursor.x + 1,
+        };
         self.select_to_cursor_pos();
     }
 
@@ -282,7 +297,9 @@ impl CopyRenderable {
         if let Some(line) = lines.get(0) {
             self.cursor.y = top;
             if self.cursor.x == usize::max_value() {
-                self.cursor.x = line.cells().len().saturating_sub(1);
+                self.cursor.x = line
+                    .grapheme_range(line.cells().len().saturating_sub(1))
+                    .start;
             }
             let s = line.columns_as_str(0..self.cursor.x.saturating_add(1));
 
@@ -296,22 +313,22 @@ impl CopyRenderable {
             let mut last_was_whitespace = false;
 
             for (idx, word) in s.split_word_bounds().rev().enumerate() {
-                let width = unicode_column_width(word);
+                let graphemes = word.graphemes(true).count();
 
                 if is_whitespace_word(word) {
-                    self.cursor.x = self.cursor.x.saturating_sub(width);
+                    self.cursor.x = retreat_graphemes(line, self.cursor.x, graphemes);
                     last_was_whitespace = true;
                     continue;
                 }
                 last_was_whitespace = false;
 
-                if idx == 0 && width == 1 {
+                if idx == 0 && graphemes == 1 {
                     // We were at the start of the initial word
-                    self.cursor.x = self.cursor.x.saturating_sub(width);
+                    self.cursor.x = retreat_graphemes(line, self.cursor.x, 1);
                     continue;
                 }
 
-                self.cursor.x = self.cursor.x.saturating_sub(width.saturating_sub(1));
+                self.cursor.x = retreat_graphemes(line, self.cursor.x, graphemes.saturating_sub(1));
                 break;
             }
 
@@ -335,13 +352,13 @@ impl CopyRenderable {
             let mut words = s.split_word_bounds();
 
             if let Some(word) = words.next() {
-                self.cursor.x += unicode_column_width(word);
+                self.cursor.x = advance_graphemes(line, self.cursor.x, word);
                 if !is_whitespace_word(word) {
                     // We were part-way through a word, so look
                     // at the next word
                     if let Some(word) = words.next() {
                         if is_whitespace_word(word) {
-                            self.cursor.x += unicode_column_width(word);
+                            self.cursor.x = advance_graphemes(line, self.cursor.x, word);
                             // If we advance off the RHS, move to the start of the word on the
                             // next line, if any!
                             if self.cursor.x >= width {
@@ -543,6 +560,23 @@ impl Pane for CopyOverlay {
     }
 }
 
+/// Returns the cell index that is `count` graphemes before cell `x` of `line`
+fn retreat_graphemes(line: &Line, mut x: usize, count: usize) -> usize {
+    for _ in 0..count {
+        x = line.prev_grapheme(x).unwrap_or(0);
+    }
+    x
+}
+
+/// Returns the cell index that follows `text`, which is the text
+/// of `line` starting at cell `x`
+fn advance_graphemes(line: &Line, mut x: usize, text: &str) -> usize {
+    for _ in text.graphemes(true) {
+        x = line.next_grapheme(x);
+    }
+    x
+}
+
 fn is_whitespace_word(word: &str) -> bool {
     if let Some(c) = word.chars().next() {
         c.is_whitespace()
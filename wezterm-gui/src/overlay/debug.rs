@@ -2,8 +2,10 @@ use crate::scripting::guiwin::GuiWin;
 use chrono::prelude::*;
 use log::Level;
 use luahelper::ValueWrapper;
-use mlua::Value;
+use mlua::{Table, Value};
 use mux::termwiztermtab::TermWizTerminal;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use termwiz::cell::{AttributeChange, CellAttributes, Intensity};
 use termwiz::color::AnsiColor;
 use termwiz::input::{InputEvent, KeyCode, KeyEvent};
@@ -11,11 +13,176 @@ use termwiz::lineedit::*;
 use termwiz::surface::Change;
 use termwiz::terminal::Terminal;
 
+/// The maximum number of entries retained in the history file
+const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// Once the history file holds this many entries, it is rewritten
+/// to keep only the most recent `MAX_HISTORY_ENTRIES` of them
+const MAX_HISTORY_FILE_ENTRIES: usize = MAX_HISTORY_ENTRIES * 2;
+
+/// Tables nested deeper than this are abbreviated when printed
+const MAX_PRINT_DEPTH: usize = 4;
+
 struct LuaReplHost {
     history: BasicHistory,
     lua: mlua::Lua,
 }
 
+fn history_file_path() -> PathBuf {
+    config::DATA_DIR.join("repl_history.jsonl")
+}
+
+/// Parses the history file, which holds one JSON string per line so
+/// that multi-line entries, and entries containing backslashes,
+/// round-trip exactly.  Only the most recent entries are returned.
+fn parse_history(contents: &str) -> Vec<String> {
+    let entries: Vec<String> = contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let skip = entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+    entries.into_iter().skip(skip).collect()
+}
+
+/// Loads the history from prior sessions
+fn load_history() -> BasicHistory {
+    let mut history = BasicHistory::default();
+    if let Ok(contents) = std::fs::read_to_string(history_file_path()) {
+        for entry in parse_history(&contents) {
+            history.add(&entry);
+        }
+    }
+    history
+}
+
+fn save_history_entry(entry: &str) -> anyhow::Result<()> {
+    std::fs::create_dir_all(&*config::DATA_DIR)?;
+    append_history_entry(&history_file_path(), entry)
+}
+
+fn append_history_entry(path: &Path, entry: &str) -> anyhow::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    drop(file);
+
+    let contents = std::fs::read_to_string(path)?;
+    if contents.lines().count() > MAX_HISTORY_FILE_ENTRIES {
+        // Write a new file and rename it into place, so that the
+        // history isn't lost if we are interrupted part way through
+        let temp = path.with_extension("jsonl.tmp");
+        let mut file = std::fs::File::create(&temp)?;
+        for entry in parse_history(&contents) {
+            writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        }
+        drop(file);
+        std::fs::rename(&temp, path)?;
+    }
+    Ok(())
+}
+
+/// Splits the identifier chain that ends at `cursor` into the
+/// path of table names that precede it and the partially typed
+/// final component.  For example, `x = wezterm.tar` yields
+/// `(["wezterm"], "tar", 12)` where 12 is the byte offset at
+/// which the partial name begins.
+fn completion_context(line: &str, cursor: usize) -> (Vec<&str>, &str, usize) {
+    let before = &line[..cursor];
+    let start = before
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == ':'))
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+    let chain = &before[start..];
+    match chain.rfind(|c| c == '.' || c == ':') {
+        Some(idx) => {
+            let path = chain[..idx].split(|c| c == '.' || c == ':').collect();
+            (path, &chain[idx + 1..], start + idx + 1)
+        }
+        None => (vec![], chain, start),
+    }
+}
+
+/// Formats a table key for display
+fn format_key(key: &Value) -> String {
+    match key {
+        Value::String(s) => match s.to_str() {
+            Ok(s) if is_identifier(s) => s.to_string(),
+            Ok(s) => format!("[{:?}]", s),
+            Err(_) => "[?]".to_string(),
+        },
+        other => format!("[{:?}]", ValueWrapper(other.clone())),
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Renders `value` as lua-like source text, with one table
+/// entry per line
+fn pretty_print(value: &Value, indent: usize, depth: usize, out: &mut String) {
+    match value {
+        Value::Table(table) => pretty_print_table(table, indent, depth, out),
+        Value::String(s) => out.push_str(&format!("{:?}", s.to_string_lossy())),
+        Value::Function(_) => out.push_str("function"),
+        other => out.push_str(&format!("{:?}", ValueWrapper(other.clone()))),
+    }
+}
+
+fn pretty_print_table(table: &Table, indent: usize, depth: usize, out: &mut String) {
+    if depth >= MAX_PRINT_DEPTH {
+        out.push_str("{...}");
+        return;
+    }
+    let mut entries = vec![];
+    for pair in table.clone().pairs::<Value, Value>() {
+        if let Ok(pair) = pair {
+            entries.push(pair);
+        }
+    }
+    if entries.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+
+    let len = entries.len() as i64;
+    let is_sequence = entries.iter().all(|(k, _)| match k {
+        Value::Integer(i) => *i >= 1 && *i <= len,
+        _ => false,
+    });
+
+    fn sort_key(key: &Value) -> (u8, i64, String) {
+        match key {
+            Value::Integer(i) => (0, *i, String::new()),
+            Value::Number(n) => (0, *n as i64, String::new()),
+            Value::String(s) => (1, 0, s.to_string_lossy().to_string()),
+            _ => (2, 0, String::new()),
+        }
+    }
+    entries.sort_by_key(|(k, _)| sort_key(k));
+
+    let pad = "  ".repeat(indent + 1);
+    out.push_str("{\n");
+    for (key, value) in entries {
+        out.push_str(&pad);
+        if !is_sequence {
+            out.push_str(&format_key(&key));
+            out.push_str(" = ");
+        }
+        pretty_print(&value, indent + 1, depth + 1, out);
+        out.push_str(",\n");
+    }
+    out.push_str(&"  ".repeat(indent));
+    out.push('}');
+}
+
 impl LineEditorHost for LuaReplHost {
     fn history(&mut self) -> &mut dyn History {
         &mut self.history
@@ -42,6 +209,38 @@ impl LineEditorHost for LuaReplHost {
         }
     }
 
+    fn complete(&self, line: &str, cursor_position: usize) -> Vec<CompletionCandidate> {
+        let (path, partial, start) = completion_context(line, cursor_position);
+
+        let mut table = self.lua.globals();
+        for name in path {
+            table = match table.get::<_, Value>(name) {
+                Ok(Value::Table(t)) => t,
+                _ => return vec![],
+            };
+        }
+
+        let mut names = vec![];
+        for pair in table.pairs::<Value, Value>() {
+            if let Ok((Value::String(key), _)) = pair {
+                if let Ok(key) = key.to_str() {
+                    if key.starts_with(partial) && is_identifier(key) {
+                        names.push(key.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+
+        names
+            .into_iter()
+            .map(|text| CompletionCandidate {
+                range: start..cursor_position,
+                text,
+            })
+            .collect()
+    }
+
     fn render_preview(&self, line: &str) -> Vec<OutputElement> {
         let expr = format!("return {}", line);
         let mut preview = vec![];
@@ -65,7 +264,94 @@ impl LineEditorHost for LuaReplHost {
     }
 }
 
-pub fn show_debug_overlay(mut term: TermWizTerminal, gui_win: GuiWin) -> anyhow::Result<()> {
+/// Prints `text`, pausing after each screenful until a key is pressed.
+/// Pressing `q` or Escape skips the remainder.
+fn print_paginated(term: &mut TermWizTerminal, text: &str) -> anyhow::Result<()> {
+    let rows = term.get_screen_size()?.rows.saturating_sub(1).max(1);
+    let lines: Vec<&str> = text.lines().collect();
+    for (page_idx, page) in lines.chunks(rows).enumerate() {
+        if page_idx > 0 {
+            term.render(&[
+                AttributeChange::Reverse(true).into(),
+                Change::Text("-- more -- (q to stop)".to_string()),
+                Change::AllAttributes(CellAttributes::default()),
+            ])?;
+            let stop = loop {
+                match term.poll_input(None)? {
+                    Some(InputEvent::Key(KeyEvent {
+                        key: KeyCode::Char('q'),
+                        ..
+                    }))
+                    | Some(InputEvent::Key(KeyEvent {
+                        key: KeyCode::Escape,
+                        ..
+                    })) => break true,
+                    Some(InputEvent::Key(_)) => break false,
+                    Some(_) => continue,
+                    None => break true,
+                }
+            };
+            term.render(&[
+                Change::Text("\r".to_string()),
+                Change::ClearToEndOfLine(Default::default()),
+            ])?;
+            if stop {
+                break;
+            }
+        }
+        let mut chunk = page.join("\r\n");
+        chunk.push_str("\r\n");
+        term.render(&[Change::Text(chunk)])?;
+    }
+    Ok(())
+}
+
+enum EvalResult {
+    Value(String),
+    Error(String),
+    /// The input is not yet a complete chunk
+    Incomplete,
+}
+
+fn is_incomplete(err: &mlua::Error) -> bool {
+    matches!(
+        err,
+        mlua::Error::SyntaxError {
+            incomplete_input: true,
+            ..
+        }
+    )
+}
+
+/// Evaluates `input`, first as an expression and then, if that
+/// is not valid syntax, as a sequence of statements
+fn evaluate(lua: &mlua::Lua, input: &str) -> EvalResult {
+    let expr = format!("return {}", input);
+    let result = match lua.load(&expr).into_function() {
+        Ok(func) => func.call::<_, Value>(()),
+        Err(_) => match lua.load(input).into_function() {
+            Ok(func) => func.call::<_, Value>(()),
+            Err(err) if is_incomplete(&err) => return EvalResult::Incomplete,
+            Err(err) => Err(err),
+        },
+    };
+    match result {
+        Ok(value) => {
+            let mut text = String::new();
+            pretty_print(&value, 0, 0, &mut text);
+            EvalResult::Value(text)
+        }
+        Err(err) => EvalResult::Error(format!("{:#}", err)),
+    }
+}
+
+/// Shows the debug overlay, which starts with the memory usage
+/// summary in `memory` and then shows the log and a lua repl
+pub fn show_debug_overlay(
+    mut term: TermWizTerminal,
+    gui_win: GuiWin,
+    memory: String,
+) -> anyhow::Result<()> {
     term.no_grab_mouse_in_raw_mode();
 
     let lua = config::Config::load()?
@@ -76,11 +362,19 @@ pub fn show_debug_overlay(mut term: TermWizTerminal, gui_win: GuiWin) -> anyhow:
 
     let mut latest_log_entry = None;
     let mut host = LuaReplHost {
-        history: BasicHistory::default(),
+        history: load_history(),
         lua,
     };
+    // Lines of a multi-line chunk that has not been completed yet
+    let mut pending = String::new();
 
-    term.render(&[Change::Title("Debug".to_string())])?;
+    term.render(&[
+        Change::Title("Debug".to_string()),
+        AttributeChange::Intensity(Intensity::Bold).into(),
+        Change::Text("Memory usage\r\n".to_string()),
+        Change::AllAttributes(CellAttributes::default()),
+        Change::Text(format!("{}\r\n", memory.replace('\n', "\r\n"))),
+    ])?;
 
     fn print_new_log_entries(
         term: &mut TermWizTerminal,
@@ -132,33 +426,94 @@ pub fn show_debug_overlay(mut term: TermWizTerminal, gui_win: GuiWin) -> anyhow:
     loop {
         print_new_log_entries(&mut term, &mut latest_log_entry)?;
         let mut editor = LineEditor::new(&mut term);
-        editor.set_prompt("> ");
-        if let Some(line) = editor.read_line(&mut host)? {
-            if line.is_empty() {
+        editor.set_prompt(if pending.is_empty() { "> " } else { ">> " });
+        let line = match editor.read_line(&mut host)? {
+            Some(line) => line,
+            None if !pending.is_empty() => {
+                // Cancelling a continuation line abandons the chunk
+                pending.clear();
                 continue;
             }
-            host.history().add(&line);
-
-            let expr = format!("return {}", line);
-            let chunk = host.lua.load(&expr);
-            match chunk.eval::<Value>() {
-                Ok(result) => {
-                    let text = format!("{:?}", ValueWrapper(result));
-                    term.render(&[Change::Text(format!("{}\r\n", text.replace("\n", "\r\n")))])?;
-                }
-                Err(err) => {
-                    let text = match &err {
-                        mlua::Error::SyntaxError {
-                            incomplete_input: true,
-                            ..
-                        } => "...".to_string(),
-                        _ => format!("{:#}", err),
-                    };
-                    term.render(&[Change::Text(format!("{}\r\n", text.replace("\n", "\r\n")))])?;
+            None => return Ok(()),
+        };
+        if line.is_empty() && pending.is_empty() {
+            continue;
+        }
+
+        if !pending.is_empty() {
+            pending.push('\n');
+        }
+        pending.push_str(&line);
+
+        match evaluate(&host.lua, &pending) {
+            EvalResult::Incomplete => continue,
+            EvalResult::Value(text) | EvalResult::Error(text) => {
+                host.history().add(&pending);
+                if let Err(err) = save_history_entry(&pending) {
+                    log::warn!("Failed to save repl history: {:#}", err);
                 }
+                pending.clear();
+                print_paginated(&mut term, &text)?;
             }
-        } else {
-            return Ok(());
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn completion() {
+        assert_eq!(completion_context("wez", 3), (vec![], "wez", 0));
+        assert_eq!(
+            completion_context("x = wezterm.tar", 15),
+            (vec!["wezterm"], "tar", 12)
+        );
+        assert_eq!(
+            completion_context("f(wezterm.a.b", 13),
+            (vec!["wezterm", "a"], "b", 12)
+        );
+        assert_eq!(completion_context("window:", 7), (vec!["window"], "", 7));
+    }
+
+    #[test]
+    fn history() {
+        let entries = ["print(1)", "for i = 1, 2 do\n  print(\"\\n\")\nend"];
+        let mut contents = String::new();
+        for entry in &entries {
+            contents.push_str(&serde_json::to_string(entry).unwrap());
+            contents.push('\n');
+        }
+        // Malformed lines are skipped
+        contents.push_str("not json\n");
+        assert_eq!(parse_history(&contents), entries);
+
+        let contents: String = (0..MAX_HISTORY_ENTRIES + 5)
+            .map(|i| format!("\"{}\"\n", i))
+            .collect();
+        let history = parse_history(&contents);
+        assert_eq!(history.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(history[0], "5");
+    }
+
+    #[test]
+    fn history_file_is_capped() {
+        let path =
+            std::env::temp_dir().join(format!("wezterm-repl-history-{}.jsonl", std::process::id()));
+        std::fs::remove_file(&path).ok();
+
+        for i in 0..=MAX_HISTORY_FILE_ENTRIES {
+            append_history_entry(&path, &i.to_string()).unwrap();
+        }
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let history = parse_history(&contents);
+        assert_eq!(contents.lines().count(), MAX_HISTORY_ENTRIES);
+        assert_eq!(history[0], (MAX_HISTORY_ENTRIES + 1).to_string());
+        assert_eq!(
+            history.last().unwrap(),
+            &MAX_HISTORY_FILE_ENTRIES.to_string()
+        );
+        std::fs::remove_file(&path).ok();
+    }
+}
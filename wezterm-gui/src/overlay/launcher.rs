@@ -7,7 +7,8 @@
 //! menus.
 use crate::termwindow::clipboard::ClipboardHelper;
 use crate::termwindow::spawn::SpawnWhere;
-use crate::termwindow::TermWindow;
+use crate::termwindow::{TermWindow, TermWindowNotif};
+use ::window::WindowOps;
 use anyhow::anyhow;
 use config::keyassignment::{SpawnCommand, SpawnTabDomain};
 use config::{configuration, TermConfig};
@@ -17,6 +18,7 @@ use mux::termwiztermtab::TermWizTerminal;
 use mux::window::WindowId;
 use mux::Mux;
 use portable_pty::PtySize;
+use std::ffi::{OsStr, OsString};
 use std::sync::Arc;
 use termwiz::cell::{AttributeChange, CellAttributes};
 use termwiz::color::ColorAttribute;
@@ -35,6 +37,14 @@ enum Entry {
         label: String,
         domain: DomainId,
     },
+    Serial {
+        label: String,
+        port: OsString,
+    },
+    Workspace {
+        label: String,
+        name: String,
+    },
 }
 
 impl Entry {
@@ -42,6 +52,8 @@ impl Entry {
         match self {
             Entry::Spawn { label, .. } => label,
             Entry::Attach { label, .. } => label,
+            Entry::Serial { label, .. } => label,
+            Entry::Workspace { label, .. } => label,
         }
     }
 }
@@ -132,6 +144,13 @@ pub fn launcher(
         });
     }
 
+    for workspace in &config.workspaces {
+        entries.push(Entry::Workspace {
+            label: format!("Workspace: {}", workspace.name),
+            name: workspace.name.clone(),
+        });
+    }
+
     #[cfg(windows)]
     {
         if config.add_wsl_distributions_to_launch_menu {
@@ -139,6 +158,15 @@ pub fn launcher(
         }
     }
 
+    if config.add_serial_ports_to_launch_menu {
+        for port in portable_pty::serial::available_ports() {
+            entries.push(Entry::Serial {
+                label: format!("Serial Port {}", port.to_string_lossy()),
+                port,
+            });
+        }
+    }
+
     for (domain_id, domain_name, domain_state, domain_label) in &domains {
         let entry = if *domain_state == DomainState::Attached {
             Entry::Spawn {
@@ -223,6 +251,7 @@ pub fn launcher(
                         mux_window_id,
                         clipboard,
                         term_config,
+                        false,
                     );
                 })
                 .detach();
@@ -236,6 +265,20 @@ pub fn launcher(
                 })
                 .detach();
             }
+            Entry::Serial { port, .. } => {
+                // A serial connection takes over the whole process,
+                // so it is opened in a new instance of wezterm
+                if let Err(err) = open_serial_port(&port) {
+                    log::error!("failed to open serial port {:?}: {:#}", port, err);
+                }
+            }
+            Entry::Workspace { name, .. } => {
+                clipboard
+                    .window
+                    .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                        term_window.switch_to_workspace(&name);
+                    })));
+            }
         }
     }
 
@@ -322,3 +365,15 @@ fn do_domain_attach(domain: DomainId) {
     })
     .detach();
 }
+
+fn open_serial_port(port: &OsStr) -> anyhow::Result<()> {
+    let mut cmd = std::process::Command::new(std::env::current_exe()?);
+    cmd.arg("serial").arg(port);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(winapi::um::winbase::DETACHED_PROCESS);
+    }
+    cmd.spawn()?;
+    Ok(())
+}
@@ -6,24 +6,42 @@ use portable_pty::PtySize;
 use std::pin::Pin;
 use std::rc::Rc;
 
+mod commandhistory;
 mod confirm_close_pane;
 mod copy;
 mod debug;
 mod launcher;
+mod modal;
+mod prompt;
 mod quickselect;
 mod search;
+mod snippets;
 mod tabnavigator;
+mod taboverview;
+mod upload;
+mod urlpicker;
+mod viewer;
 
+pub use commandhistory::command_history;
 pub use confirm_close_pane::confirm_close_pane;
 pub use confirm_close_pane::confirm_close_tab;
 pub use confirm_close_pane::confirm_close_window;
+pub use confirm_close_pane::confirm_close_workspace;
+pub use confirm_close_pane::confirm_load_env_file;
 pub use confirm_close_pane::confirm_quit_program;
 pub use copy::CopyOverlay;
 pub use debug::show_debug_overlay;
 pub use launcher::launcher;
-pub use quickselect::QuickSelectOverlay;
+pub use modal::{lua_modal, ModalSpec};
+pub use prompt::rename_workspace_prompt;
+pub use quickselect::{compute_labels_for_alphabet, QuickSelectOverlay};
 pub use search::SearchOverlay;
+pub use snippets::{send_snippet_text, snippet_cwd, snippet_picker, snippet_prompt};
 pub use tabnavigator::tab_navigator;
+pub use taboverview::{tab_overview, tab_preview, ThumbnailLayout, ThumbnailSlot};
+pub use upload::confirm_upload_files;
+pub use urlpicker::{collect_links, url_picker};
+pub use viewer::text_viewer;
 
 pub fn start_overlay<T, F>(
     term_window: &TermWindow,
@@ -0,0 +1,392 @@
+//! A picker whose rows, prompt and key bindings are defined by lua code,
+//! so that plugins can present choices such as projects or passwords.
+//! Key presses are handled by the `modal-key` event, which decides
+//! whether the modal closes, stays open, or shows different rows.
+use crate::fuzzy::{filter_by_score, fuzzy_score};
+use crate::scripting::guiwin::GuiWin;
+use crate::scripting::pane::PaneObject;
+use luahelper::impl_lua_conversion;
+use mlua::FromLua;
+use mux::pane::PaneId;
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use serde::{Deserialize, Serialize};
+use termwiz::cell::{AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ModalRow {
+    /// Passed to `modal-key` to identify the row
+    #[serde(default)]
+    pub id: String,
+    pub label: String,
+}
+impl_lua_conversion!(ModalRow);
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ModalSpec {
+    /// Passed to `modal-key` to identify the modal
+    pub name: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Shown above the rows in place of the default help text
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "default_prompt")]
+    pub prompt: String,
+    #[serde(default)]
+    pub rows: Vec<ModalRow>,
+    /// When true, typing filters the rows rather than being
+    /// treated as key presses
+    #[serde(default = "default_true")]
+    pub filter: bool,
+    /// Keys, such as "CTRL+d", that are passed to `modal-key` in
+    /// addition to Enter
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+impl_lua_conversion!(ModalSpec);
+
+fn default_prompt() -> String {
+    "> ".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// What the `modal-key` event asked to happen next
+#[derive(Debug, PartialEq)]
+enum ModalResponse {
+    Close,
+    KeepOpen,
+    SetRows(Vec<ModalRow>),
+}
+
+/// Converts the modifiers of a key press in the overlay to those
+/// used by key assignments
+fn window_mods(mods: Modifiers) -> ::window::Modifiers {
+    let mut result = ::window::Modifiers::NONE;
+    for (flag, window_flag) in &[
+        (Modifiers::SHIFT, ::window::Modifiers::SHIFT),
+        (Modifiers::ALT, ::window::Modifiers::ALT),
+        (Modifiers::CTRL, ::window::Modifiers::CTRL),
+        (Modifiers::SUPER, ::window::Modifiers::SUPER),
+        (Modifiers::LEADER, ::window::Modifiers::LEADER),
+    ] {
+        if mods.contains(*flag) {
+            result.insert(*window_flag);
+        }
+    }
+    result
+}
+
+/// Converts a key pressed in the overlay to the key code used by key
+/// assignments; this is the inverse of `win_key_code_to_termwiz_key_code`
+fn window_key_code(key: KeyCode) -> Option<::window::KeyCode> {
+    use ::window::KeyCode as WK;
+    Some(match key {
+        KeyCode::Enter => WK::Char('\r'),
+        KeyCode::Tab => WK::Char('\t'),
+        KeyCode::Backspace => WK::Char('\u{08}'),
+        KeyCode::Delete => WK::Char('\u{7f}'),
+        KeyCode::Escape => WK::Char('\u{1b}'),
+        KeyCode::Char(c) => WK::Char(c),
+        KeyCode::Function(n) => WK::Function(n),
+        KeyCode::LeftArrow => WK::LeftArrow,
+        KeyCode::RightArrow => WK::RightArrow,
+        KeyCode::UpArrow => WK::UpArrow,
+        KeyCode::DownArrow => WK::DownArrow,
+        KeyCode::Home => WK::Home,
+        KeyCode::End => WK::End,
+        KeyCode::PageUp => WK::PageUp,
+        KeyCode::PageDown => WK::PageDown,
+        KeyCode::Insert => WK::Insert,
+        KeyCode::Numpad0 => WK::Numpad(0),
+        KeyCode::Numpad1 => WK::Numpad(1),
+        KeyCode::Numpad2 => WK::Numpad(2),
+        KeyCode::Numpad3 => WK::Numpad(3),
+        KeyCode::Numpad4 => WK::Numpad(4),
+        KeyCode::Numpad5 => WK::Numpad(5),
+        KeyCode::Numpad6 => WK::Numpad(6),
+        KeyCode::Numpad7 => WK::Numpad(7),
+        KeyCode::Numpad8 => WK::Numpad(8),
+        KeyCode::Numpad9 => WK::Numpad(9),
+        KeyCode::Multiply => WK::Multiply,
+        KeyCode::Add => WK::Add,
+        KeyCode::Separator => WK::Separator,
+        KeyCode::Subtract => WK::Subtract,
+        KeyCode::Decimal => WK::Decimal,
+        KeyCode::Divide => WK::Divide,
+        KeyCode::Help => WK::Help,
+        KeyCode::Pause => WK::Pause,
+        KeyCode::Print => WK::Print,
+        KeyCode::PrintScreen => WK::PrintScreen,
+        KeyCode::Applications => WK::Applications,
+        _ => return None,
+    })
+}
+
+/// Describes a key in the same way as the `pane-widget-key` event
+/// and the key assignment UI
+fn describe_key(key: KeyCode, mods: Modifiers) -> Option<String> {
+    let key = window_key_code(key)?;
+    Some(config::describe_key(&key, window_mods(mods)))
+}
+
+/// Returns the index of the first row that is shown, scrolled so that
+/// the active row is visible, and the number of rows that fit
+fn visible_rows(active_idx: usize, screen_rows: usize, filter: bool) -> (usize, usize) {
+    let header_rows = if filter { 2 } else { 1 };
+    let max_rows = screen_rows.saturating_sub(header_rows);
+    let top = active_idx.saturating_sub(max_rows.saturating_sub(1));
+    (top, max_rows)
+}
+
+/// Returns the indices of the rows that match `filter`, best match first
+fn filter_rows(rows: &[ModalRow], filter: &str) -> Vec<usize> {
+    filter_by_score(rows, |row| fuzzy_score(filter, &row.label))
+}
+
+/// Runs the `modal-key` event on the gui thread and waits for its answer
+fn emit_modal_key(
+    window: &GuiWin,
+    pane_id: PaneId,
+    name: &str,
+    key: String,
+    row: Option<ModalRow>,
+) -> ModalResponse {
+    let window = window.clone();
+    let name = name.to_string();
+    let result = promise::spawn::block_on(promise::spawn::spawn_into_main_thread(async move {
+        config::run_immediate_with_lua_config(|lua| {
+            let lua = match lua {
+                Some(lua) => lua,
+                None => return Ok(ModalResponse::Close),
+            };
+            let pane = PaneObject { pane: pane_id };
+            let v = config::lua::emit_sync_callback(
+                &*lua,
+                ("modal-key".to_string(), (window, pane, name, key, row)),
+            )?;
+            match v {
+                mlua::Value::Nil | mlua::Value::Boolean(false) => Ok(ModalResponse::Close),
+                mlua::Value::Boolean(true) => Ok(ModalResponse::KeepOpen),
+                v => Ok(ModalResponse::SetRows(Vec::<ModalRow>::from_lua(v, &*lua)?)),
+            }
+        })
+    }));
+    match result {
+        Ok(response) => response,
+        Err(err) => {
+            log::warn!("modal-key: {:#}", err);
+            ModalResponse::Close
+        }
+    }
+}
+
+pub fn lua_modal(
+    _tab_id: TabId,
+    mut term: TermWizTerminal,
+    spec: ModalSpec,
+    window: GuiWin,
+    pane_id: PaneId,
+) -> anyhow::Result<()> {
+    let mut rows = spec.rows.clone();
+    let mut filter = String::new();
+    let mut matches = filter_rows(&rows, &filter);
+    let mut active_idx = 0;
+
+    term.set_raw_mode()?;
+
+    let render = |active_idx: usize,
+                  filter: &str,
+                  matches: &[usize],
+                  rows: &[ModalRow],
+                  term: &mut TermWizTerminal|
+     -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let (top, max_rows) = visible_rows(active_idx, size.rows, spec.filter);
+
+        let description = spec.description.clone().unwrap_or_else(|| {
+            if spec.filter {
+                "Type to filter.  Enter: select  Escape: cancel".to_string()
+            } else {
+                "Enter: select  Escape: cancel".to_string()
+            }
+        });
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(format!("{}\r\n", description)),
+            Change::AllAttributes(CellAttributes::default()),
+        ];
+        if spec.filter {
+            changes.push(Change::Text(format!("{}{}\r\n", spec.prompt, filter)));
+        }
+
+        for (row, &idx) in matches.iter().enumerate().skip(top).take(max_rows) {
+            if row == active_idx {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            changes.push(Change::Text(format!(" {} ", rows[idx].label)));
+            changes.push(Change::AllAttributes(CellAttributes::default()));
+            changes.push(Change::Text("\r\n".to_string()));
+        }
+
+        term.render(&changes)?;
+        term.flush()
+    };
+
+    term.render(&[Change::Title(
+        spec.title.clone().unwrap_or_else(|| spec.name.clone()),
+    )])?;
+    render(active_idx, &filter, &matches, &rows, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let key = match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => break,
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+                None
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                active_idx = (active_idx + 1).min(matches.len().saturating_sub(1));
+                None
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => Some("Enter".to_string()),
+            InputEvent::Key(KeyEvent { key, modifiers })
+                if describe_key(key, modifiers)
+                    .map(|desc| spec.keys.contains(&desc))
+                    .unwrap_or(false) =>
+            {
+                describe_key(key, modifiers)
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) if spec.filter => {
+                filter.pop();
+                matches = filter_rows(&rows, &filter);
+                active_idx = 0;
+                None
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) if spec.filter => {
+                filter.push(c);
+                matches = filter_rows(&rows, &filter);
+                active_idx = 0;
+                None
+            }
+            InputEvent::Mouse(MouseEvent {
+                y, mouse_buttons, ..
+            }) => {
+                let size = term.get_screen_size()?;
+                let header_rows = if spec.filter { 2 } else { 1 };
+                let (top, _) = visible_rows(active_idx, size.rows, spec.filter);
+                let row = (y as usize).checked_sub(header_rows).map(|row| top + row);
+                match row {
+                    Some(row) if row < matches.len() => {
+                        active_idx = row;
+                        if mouse_buttons == MouseButtons::LEFT {
+                            Some("Enter".to_string())
+                        } else {
+                            None
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        if let Some(key) = key {
+            let row = matches.get(active_idx).map(|&idx| rows[idx].clone());
+            match emit_modal_key(&window, pane_id, &spec.name, key, row) {
+                ModalResponse::Close => break,
+                ModalResponse::KeepOpen => {}
+                ModalResponse::SetRows(new_rows) => {
+                    rows = new_rows;
+                    matches = filter_rows(&rows, &filter);
+                    active_idx = active_idx.min(matches.len().saturating_sub(1));
+                }
+            }
+        }
+        render(active_idx, &filter, &matches, &rows, &mut term)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_description() {
+        assert_eq!(
+            describe_key(KeyCode::Char('d'), Modifiers::CTRL).as_deref(),
+            Some("CTRL+d")
+        );
+        assert_eq!(
+            describe_key(KeyCode::Char(' '), Modifiers::NONE).as_deref(),
+            Some("Space")
+        );
+        assert_eq!(
+            describe_key(KeyCode::Delete, Modifiers::CTRL | Modifiers::SHIFT).as_deref(),
+            Some("CTRL+SHIFT+Delete")
+        );
+        assert_eq!(
+            describe_key(KeyCode::Enter, Modifiers::NONE).as_deref(),
+            Some("Enter")
+        );
+        assert_eq!(describe_key(KeyCode::Menu, Modifiers::NONE), None);
+    }
+
+    #[test]
+    fn scrolling() {
+        assert_eq!(visible_rows(0, 10, false), (0, 9));
+        assert_eq!(visible_rows(8, 10, false), (0, 9));
+        assert_eq!(visible_rows(12, 10, false), (4, 9));
+        assert_eq!(visible_rows(12, 10, true), (5, 8));
+    }
+
+    #[test]
+    fn filter() {
+        let rows: Vec<ModalRow> = ["wezterm", "termwiz", "zsh"]
+            .iter()
+            .map(|label| ModalRow {
+                id: label.to_string(),
+                label: label.to_string(),
+            })
+            .collect();
+        assert_eq!(filter_rows(&rows, ""), vec![0, 1, 2]);
+        // A match at the start of a word scores higher
+        assert_eq!(filter_rows(&rows, "term"), vec![1, 0]);
+        assert_eq!(filter_rows(&rows, "zh"), vec![2]);
+    }
+}
@@ -0,0 +1,42 @@
+use crate::TermWindow;
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use termwiz::lineedit::*;
+use termwiz::surface::Change;
+use termwiz::terminal::Terminal;
+
+/// Prompts for a new name for the workspace `old`.
+/// Entering an empty name, or pressing CTRL-C, leaves it unchanged.
+pub fn rename_workspace_prompt(
+    mut term: TermWizTerminal,
+    old: String,
+    window: ::window::Window,
+    tab_id: TabId,
+) -> anyhow::Result<()> {
+    term.render(&[Change::Text(format!(
+        "Renaming workspace {}.  Enter an empty name to cancel.\r\n",
+        old
+    ))])?;
+
+    let mut editor = LineEditor::new(&mut term);
+    editor.set_prompt("New name: ");
+    let mut host = NopLineEditorHost::default();
+    let new = editor.read_line(&mut host)?;
+
+    if let Some(new) = new {
+        let new = new.trim().to_string();
+        if !new.is_empty() {
+            promise::spawn::spawn_into_main_thread(async move {
+                let mux = Mux::get().unwrap();
+                if let Err(err) = mux.rename_workspace(&old, &new) {
+                    log::error!("Failed to rename workspace {}: {:#}", old, err);
+                }
+            })
+            .detach();
+        }
+    }
+    TermWindow::schedule_cancel_overlay(window, tab_id, None);
+
+    Ok(())
+}
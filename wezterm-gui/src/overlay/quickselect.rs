@@ -54,7 +54,7 @@ const PATTERNS: [&str; 14] = [
 /// This function computes a set of labels for a given alphabet.
 /// It is derived from https://github.com/fcsonline/tmux-thumbs/blob/master/src/alphabets.rs
 /// which is Copyright (c) 2019 Ferran Basora and provided under the MIT license
-fn compute_labels_for_alphabet(alphabet: &str, num_matches: usize) -> Vec<String> {
+pub fn compute_labels_for_alphabet(alphabet: &str, num_matches: usize) -> Vec<String> {
     let alphabet = alphabet
         .chars()
         .map(|c| c.to_lowercase().to_string())
@@ -637,6 +637,7 @@ impl QuickSelectRenderable {
                         y: result.start_y,
                     };
                     selection.start = Some(start);
+                    selection.rectangular = false;
                     selection.range = Some(SelectionRange {
                         start,
                         end: SelectionCoordinate {
@@ -652,6 +653,7 @@ impl QuickSelectRenderable {
                 if let Some(pane) = mux.get_pane(pane_id) {
                     let text = term_window.selection_text(&pane);
                     if !text.is_empty() {
+                        let text = term_window.format_selection(&pane, text);
                         if paste {
                             let _ = pane.send_paste(&text);
                         }
@@ -34,6 +34,8 @@ struct SearchRenderable {
     delegate: Rc<dyn Pane>,
     /// The text that the user entered
     pattern: Pattern,
+    /// Only match whole words
+    whole_word: bool,
     /// The most recently queried set of matches
     results: Vec<SearchResult>,
     by_line: HashMap<StableRowIndex, Vec<MatchResult>>,
@@ -55,6 +57,7 @@ impl SearchOverlay {
         term_window: &TermWindow,
         pane: &Rc<dyn Pane>,
         pattern: Pattern,
+        whole_word: bool,
     ) -> Rc<dyn Pane> {
         let viewport = term_window.get_viewport(pane.pane_id());
         let dims = pane.get_dimensions();
@@ -63,6 +66,7 @@ impl SearchOverlay {
         let mut renderer = SearchRenderable {
             delegate: Rc::clone(pane),
             pattern,
+            whole_word,
             results: vec![],
             by_line: HashMap::new(),
             dirty_results: RangeSet::default(),
@@ -84,6 +88,18 @@ impl SearchOverlay {
         })
     }
 
+    /// Returns the pattern that is used to search the pane, which
+    /// takes the whole word option into account
+    pub fn pattern(&self) -> Pattern {
+        self.renderer.borrow().effective_pattern()
+    }
+
+    /// Returns the match that is currently selected, if any
+    pub fn active_result(&self) -> Option<SearchResult> {
+        let r = self.renderer.borrow();
+        r.result_pos.and_then(|pos| r.results.get(pos).cloned())
+    }
+
     pub fn viewport_changed(&self, viewport: Option<StableRowIndex>) {
         let mut render = self.renderer.borrow_mut();
         if render.viewport != viewport {
@@ -202,6 +218,12 @@ impl Pane for SearchOverlay {
                 r.pattern = pattern;
                 r.update_search();
             }
+            (KeyCode::Char('w'), KeyModifiers::ALT) => {
+                // ALT-w toggles matching whole words only
+                let mut r = self.renderer.borrow_mut();
+                r.whole_word = !r.whole_word;
+                r.update_search();
+            }
             (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
                 // Type to add to the pattern
                 let mut r = self.renderer.borrow_mut();
@@ -308,11 +330,15 @@ impl Pane for SearchOverlay {
                 // Replace with search UI
                 let rev = CellAttributes::default().set_reverse(true).clone();
                 line.fill_range(0..dims.cols, &Cell::new(' ', rev.clone()), SEQ_ZERO);
-                let mode = &match renderer.pattern {
+                let mut mode = match renderer.pattern {
                     Pattern::CaseSensitiveString(_) => "case-sensitive",
                     Pattern::CaseInSensitiveString(_) => "ignore-case",
                     Pattern::Regex(_) => "regex",
-                };
+                }
+                .to_string();
+                if renderer.whole_word {
+                    mode.push_str(", whole-word");
+                }
                 line.overlay_text_with_attribute(
                     0,
                     &format!(
@@ -365,8 +391,33 @@ impl SearchRenderable {
         bottom
     }
 
+    /// Returns the pattern to search for.  Whole word matching is
+    /// implemented by turning the pattern into a regex with lookaround
+    /// assertions, so that it also works for text that starts or ends
+    /// with punctuation.
+    fn effective_pattern(&self) -> Pattern {
+        if !self.whole_word || self.pattern.is_empty() {
+            return self.pattern.clone();
+        }
+        let re = match &self.pattern {
+            Pattern::CaseSensitiveString(s) => regex::escape(s),
+            Pattern::CaseInSensitiveString(s) => format!("(?i){}", regex::escape(s)),
+            Pattern::Regex(r) => format!("(?:{})", r),
+        };
+        Pattern::Regex(format!(r"(?<!\w){}(?!\w)", re))
+    }
+
     fn close(&self) {
-        TermWindow::schedule_cancel_overlay_for_pane(self.window.clone(), self.delegate.pane_id());
+        let pane_id = self.delegate.pane_id();
+        let pattern = self.effective_pattern();
+        let mode = self.pattern.clone();
+        let whole_word = self.whole_word;
+        self.window
+            .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                term_window.set_last_search(pane_id, pattern);
+                term_window.remember_search_mode(mode, whole_word);
+            })));
+        TermWindow::schedule_cancel_overlay_for_pane(self.window.clone(), pane_id);
     }
 
     fn set_viewport(&self, row: Option<StableRowIndex>) {
@@ -440,7 +491,7 @@ impl SearchRenderable {
         if !self.pattern.is_empty() {
             let pane: Rc<dyn Pane> = self.delegate.clone();
             let window = self.window.clone();
-            let pattern = self.pattern.clone();
+            let pattern = self.effective_pattern();
             promise::spawn::spawn(async move {
                 let mut results = pane.search(pattern).await?;
                 results.sort();
@@ -497,6 +548,7 @@ impl SearchRenderable {
                     y: result.start_y,
                 };
                 selection.start = Some(start);
+                selection.rectangular = false;
                 selection.range = Some(SelectionRange {
                     start,
                     end: SelectionCoordinate {
@@ -0,0 +1,262 @@
+//! The snippet picker, and the prompts for the values that are
+//! substituted into the placeholders of a snippet
+use crate::fuzzy::{filter_by_score, fuzzy_score};
+use config::Snippet;
+use mux::pane::{Pane, PaneId};
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::collections::HashMap;
+use termwiz::cell::{AttributeChange, CellAttributes, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::lineedit::{LineEditor, NopLineEditorHost};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+use window::{Clipboard, Window, WindowOps};
+
+/// Returns the indices of the snippets whose name or description
+/// match `filter`, best match first
+fn filter_snippets(snippets: &[Snippet], filter: &str) -> Vec<usize> {
+    filter_by_score(snippets, |snippet| {
+        let description = snippet
+            .description
+            .as_ref()
+            .and_then(|description| fuzzy_score(filter, description));
+        fuzzy_score(filter, &snippet.name).max(description)
+    })
+}
+
+/// Returns the working directory of `pane`, for the `{cwd}` placeholder
+pub fn snippet_cwd(pane: &dyn Pane) -> String {
+    pane.get_current_working_dir()
+        .and_then(|mut url| {
+            // The host is that of the shell, which may be remote;
+            // only the path is wanted here
+            url.set_host(None).ok()?;
+            url.to_file_path().ok()
+        })
+        .map(|path| path.display().to_string())
+        .unwrap_or_default()
+}
+
+/// Pastes the expanded text of a snippet into the pane
+pub fn send_snippet_text(pane_id: PaneId, text: String) {
+    promise::spawn::spawn_into_main_thread(async move {
+        let mux = Mux::get().unwrap();
+        if let Some(pane) = mux.get_pane(pane_id) {
+            if let Err(err) = pane.send_paste(&text) {
+                log::error!("Failed to send snippet to pane: {:#}", err);
+            }
+        }
+    })
+    .detach();
+}
+
+/// Reads the clipboard via the gui thread
+fn read_clipboard(window: &Window) -> String {
+    let window = window.clone();
+    promise::spawn::block_on(promise::spawn::spawn_into_main_thread(async move {
+        window.get_clipboard(Clipboard::Clipboard).await
+    }))
+    .unwrap_or_default()
+}
+
+/// Prompts for the fields of `snippet`, then sends it to the pane.
+/// Pressing CTRL-C at a prompt cancels.
+fn fill_in_snippet(
+    term: &mut TermWizTerminal,
+    snippet: &Snippet,
+    pane_id: PaneId,
+    cwd: &str,
+    window: &Window,
+) -> anyhow::Result<()> {
+    let fields = snippet.fields();
+    let mut values = HashMap::new();
+    if !fields.is_empty() {
+        term.render(&[
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::Text(format!(
+                "Inserting snippet {}.  Press CTRL-C to cancel.\r\n",
+                snippet.name
+            )),
+        ])?;
+        for label in fields {
+            let mut editor = LineEditor::new(&mut *term);
+            editor.set_prompt(&format!("{}: ", label));
+            let mut host = NopLineEditorHost::default();
+            match editor.read_line(&mut host)? {
+                Some(value) => {
+                    values.insert(label, value);
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+
+    let clipboard = if snippet.uses_clipboard() {
+        read_clipboard(window)
+    } else {
+        String::new()
+    };
+    send_snippet_text(pane_id, snippet.expand(cwd, &clipboard, &values));
+    Ok(())
+}
+
+/// Prompts for the fields of a snippet that was chosen by the
+/// SendSnippet action
+pub fn snippet_prompt(
+    _tab_id: TabId,
+    mut term: TermWizTerminal,
+    snippet: Snippet,
+    pane_id: PaneId,
+    cwd: String,
+    window: Window,
+) -> anyhow::Result<()> {
+    term.render(&[Change::Title(format!("Snippet: {}", snippet.name))])?;
+    fill_in_snippet(&mut term, &snippet, pane_id, &cwd, &window)
+}
+
+pub fn snippet_picker(
+    _tab_id: TabId,
+    mut term: TermWizTerminal,
+    snippets: Vec<Snippet>,
+    pane_id: PaneId,
+    cwd: String,
+    window: Window,
+) -> anyhow::Result<()> {
+    let mut filter = String::new();
+    let mut matches = filter_snippets(&snippets, &filter);
+    let mut active_idx = 0;
+
+    term.set_raw_mode()?;
+
+    fn render(
+        active_idx: usize,
+        filter: &str,
+        matches: &[usize],
+        snippets: &[Snippet],
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let max_rows = size.rows.saturating_sub(2);
+        // Scroll so that the active snippet is visible
+        let top = active_idx.saturating_sub(max_rows.saturating_sub(1));
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text("Type to filter.  Enter: insert  Escape: cancel\r\n".to_string()),
+            Change::AllAttributes(CellAttributes::default()),
+            Change::Text(format!("> {}\r\n", filter)),
+        ];
+
+        for (row, &idx) in matches.iter().enumerate().skip(top).take(max_rows) {
+            let snippet = &snippets[idx];
+            if row == active_idx {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            changes.push(Change::Text(format!(" {} ", snippet.name)));
+            changes.push(AttributeChange::Intensity(Intensity::Half).into());
+            let summary = snippet
+                .description
+                .clone()
+                .unwrap_or_else(|| snippet.text.replace('\n', "⏎"));
+            changes.push(Change::Text(format!(" {}", summary)));
+            changes.push(Change::AllAttributes(CellAttributes::default()));
+            changes.push(Change::Text("\r\n".to_string()));
+        }
+
+        term.render(&changes)?;
+        term.flush()
+    }
+
+    term.render(&[Change::Title("Snippets".to_string())])?;
+    render(active_idx, &filter, &matches, &snippets, &mut term)?;
+
+    let mut chosen = None;
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                active_idx = (active_idx + 1).min(matches.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                chosen = matches.get(active_idx).map(|&idx| snippets[idx].clone());
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) => {
+                filter.pop();
+                matches = filter_snippets(&snippets, &filter);
+                active_idx = 0;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) => {
+                filter.push(c);
+                matches = filter_snippets(&snippets, &filter);
+                active_idx = 0;
+            }
+            _ => {}
+        }
+        render(active_idx, &filter, &matches, &snippets, &mut term)?;
+    }
+
+    match chosen {
+        Some(snippet) => fill_in_snippet(&mut term, &snippet, pane_id, &cwd, &window),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn filter() {
+        let snippets = vec![
+            Snippet {
+                name: "tar".to_string(),
+                text: "tar xzf {file}".to_string(),
+                description: Some("extract an archive".to_string()),
+            },
+            Snippet {
+                name: "ssh".to_string(),
+                text: "ssh {host}".to_string(),
+                description: None,
+            },
+        ];
+        assert_eq!(filter_snippets(&snippets, ""), vec![0, 1]);
+        assert_eq!(filter_snippets(&snippets, "ssh"), vec![1]);
+        // The description is matched too
+        assert_eq!(filter_snippets(&snippets, "archive"), vec![0]);
+    }
+}
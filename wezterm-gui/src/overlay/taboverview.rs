@@ -0,0 +1,318 @@
+use crate::fuzzy::{filter_by_score, fuzzy_score};
+use anyhow::anyhow;
+use mux::tab::{Tab, TabId};
+use mux::termwiztermtab::TermWizTerminal;
+use mux::window::WindowId;
+use mux::Mux;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use termwiz::cell::{AttributeChange, CellAttributes, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The number of rows above the grid of tabs
+const HEADER_ROWS: usize = 2;
+
+/// Describes a tab that is shown in the overview
+pub struct TabPreview {
+    pub tab_id: TabId,
+    /// The position of the tab in its window at the time that the
+    /// overview was opened; it is only used for display purposes
+    pub tab_idx: usize,
+    pub title: String,
+}
+
+pub fn tab_preview(tab: &Rc<Tab>, tab_idx: usize) -> TabPreview {
+    TabPreview {
+        tab_id: tab.tab_id(),
+        tab_idx,
+        title: tab
+            .get_active_pane()
+            .map(|pane| pane.get_title())
+            .unwrap_or_default(),
+    }
+}
+
+/// The area of the overlay, in cells, over which the GUI paints
+/// the downscaled rendering of the tab identified by `tab_id`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThumbnailSlot {
+    pub tab_id: TabId,
+    pub left: usize,
+    pub top: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// The thumbnail slots of the cards that are currently visible in
+/// the overview.  The overlay updates this as it lays out the cards,
+/// and the GUI reads it when it paints the overlay.
+pub type ThumbnailLayout = Arc<Mutex<Vec<ThumbnailSlot>>>;
+
+/// Returns the indices of the previews whose titles match `filter`,
+/// best match first
+fn filter_previews(previews: &[TabPreview], filter: &str) -> Vec<usize> {
+    filter_by_score(previews, |preview| fuzzy_score(filter, &preview.title))
+}
+
+/// How the matching tabs are laid out on a screen that
+/// is `cols` by `rows` cells
+#[derive(Debug, PartialEq)]
+struct Grid {
+    columns: usize,
+    card_width: usize,
+    card_height: usize,
+}
+
+impl Grid {
+    fn new(num_cards: usize, cols: usize, rows: usize) -> Self {
+        let columns = ((num_cards as f64).sqrt().ceil() as usize).max(1);
+        let grid_rows = ((num_cards + columns - 1) / columns).max(1);
+        Self {
+            columns,
+            card_width: cols / columns,
+            card_height: rows.saturating_sub(HEADER_ROWS) / grid_rows,
+        }
+    }
+
+    /// Returns the index of the card at screen position `x`, `y`
+    fn card_at(&self, x: usize, y: usize) -> Option<usize> {
+        let row = y.checked_sub(HEADER_ROWS)? / self.card_height.max(1);
+        let col = x / self.card_width.max(1);
+        if col >= self.columns {
+            return None;
+        }
+        Some(row * self.columns + col)
+    }
+}
+
+pub fn tab_overview(
+    mut term: TermWizTerminal,
+    previews: Vec<TabPreview>,
+    active_tab_idx: usize,
+    mux_window_id: WindowId,
+    layout: ThumbnailLayout,
+) -> anyhow::Result<()> {
+    let mut filter = String::new();
+    let mut matches = filter_previews(&previews, &filter);
+    let mut active_idx = matches
+        .iter()
+        .position(|&idx| previews[idx].tab_idx == active_tab_idx)
+        .unwrap_or(0);
+
+    term.set_raw_mode()?;
+
+    fn render(
+        active_idx: usize,
+        filter: &str,
+        matches: &[usize],
+        previews: &[TabPreview],
+        layout: &ThumbnailLayout,
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let grid = Grid::new(matches.len(), size.cols, size.rows);
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(
+                "Type to filter, select a tab and press Enter to activate it.  \
+                 Press Escape to cancel\r\n"
+                    .to_string(),
+            ),
+            Change::AllAttributes(CellAttributes::default()),
+            Change::Text(format!("> {}", filter)),
+        ];
+
+        // Leave a gap between the cards
+        let width = grid.card_width.saturating_sub(1);
+        let mut slots = vec![];
+        for (card, &idx) in matches.iter().enumerate() {
+            let preview = &previews[idx];
+            let x = (card % grid.columns) * grid.card_width;
+            let y = HEADER_ROWS + (card / grid.columns) * grid.card_height;
+            if grid.card_height == 0 || y >= size.rows {
+                break;
+            }
+
+            let title: String = format!(" {}. {}", preview.tab_idx + 1, preview.title)
+                .graphemes(true)
+                .take(width)
+                .collect();
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(x),
+                y: Position::Absolute(y),
+            });
+            if card == active_idx {
+                changes.push(AttributeChange::Reverse(true).into());
+            } else {
+                changes.push(AttributeChange::Intensity(Intensity::Bold).into());
+            }
+            changes.push(Change::Text(format!("{:width$}", title, width = width)));
+            changes.push(Change::AllAttributes(CellAttributes::default()));
+
+            // The rows below the title are left blank; the GUI paints
+            // the tab over them
+            slots.push(ThumbnailSlot {
+                tab_id: preview.tab_id,
+                left: x,
+                top: y + 1,
+                width,
+                height: grid.card_height.saturating_sub(2),
+            });
+        }
+        // Publish the slots before the output of this render can
+        // cause the GUI to repaint
+        *layout.lock().unwrap() = slots;
+
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(2 + filter.graphemes(true).count()),
+            y: Position::Absolute(1),
+        });
+        term.render(&changes)?;
+        term.flush()
+    }
+
+    fn select_tab(tab_id: TabId, mux_window_id: WindowId) {
+        promise::spawn::spawn_into_main_thread(async move {
+            let mux = Mux::get().unwrap();
+            let mut window = mux
+                .get_window_mut(mux_window_id)
+                .ok_or_else(|| anyhow!("no such window"))?;
+
+            // The tab may have been moved or closed while the overview
+            // was shown, so look up its current position
+            let tab_idx = window
+                .idx_by_id(tab_id)
+                .ok_or_else(|| anyhow!("tab {} is no longer in the window", tab_id))?;
+            window.save_and_then_set_active(tab_idx);
+            anyhow::Result::<()>::Ok(())
+        })
+        .detach();
+    }
+
+    term.render(&[Change::Title("Tab Overview".to_string())])?;
+    render(active_idx, &filter, &matches, &previews, &layout, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let size = term.get_screen_size()?;
+        let grid = Grid::new(matches.len(), size.cols, size.rows);
+        let last_idx = matches.len().saturating_sub(1);
+
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::LeftArrow,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::RightArrow,
+                ..
+            }) => {
+                active_idx = (active_idx + 1).min(last_idx);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(grid.columns);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                active_idx = (active_idx + grid.columns).min(last_idx);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                if let Some(&idx) = matches.get(active_idx) {
+                    select_tab(previews[idx].tab_id, mux_window_id);
+                }
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) => {
+                filter.pop();
+                matches = filter_previews(&previews, &filter);
+                active_idx = 0;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) => {
+                filter.push(c);
+                matches = filter_previews(&previews, &filter);
+                active_idx = 0;
+            }
+            InputEvent::Mouse(MouseEvent {
+                x,
+                y,
+                mouse_buttons,
+                ..
+            }) => {
+                if let Some(card) = grid
+                    .card_at(x as usize, y as usize)
+                    .filter(|&card| card < matches.len())
+                {
+                    active_idx = card;
+                    if mouse_buttons == MouseButtons::LEFT {
+                        select_tab(previews[matches[card]].tab_id, mux_window_id);
+                        break;
+                    }
+                }
+                if mouse_buttons != MouseButtons::NONE {
+                    // Treat any other mouse button as cancel
+                    break;
+                }
+            }
+            _ => {}
+        }
+        render(active_idx, &filter, &matches, &previews, &layout, &mut term)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grid() {
+        let grid = Grid::new(5, 90, 32);
+        assert_eq!(
+            grid,
+            Grid {
+                columns: 3,
+                card_width: 30,
+                card_height: 15,
+            }
+        );
+        assert_eq!(grid.card_at(0, 0), None);
+        assert_eq!(grid.card_at(31, 2), Some(1));
+        assert_eq!(grid.card_at(0, 17), Some(3));
+        assert_eq!(grid.card_at(95, 2), None);
+    }
+}
@@ -0,0 +1,142 @@
+use super::confirm_close_pane::run_confirmation_app;
+use crate::TermWindow;
+use anyhow::anyhow;
+use config::{dropped_files_text, DroppedFileQuoting};
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::path::PathBuf;
+use wezterm_client::pane::ClientPane;
+
+/// Asks whether to upload the files that were dropped onto `pane_id`,
+/// which is in a multiplexer domain, into the working directory of the
+/// pane.  Files that already exist there are only replaced if that is
+/// confirmed too.  Once they have been uploaded, their names are pasted
+/// into the pane.
+pub fn confirm_upload_files(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    window: ::window::Window,
+    paths: Vec<PathBuf>,
+    quoting: DroppedFileQuoting,
+) -> anyhow::Result<()> {
+    let mut files: Vec<(PathBuf, String)> = paths
+        .into_iter()
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            Some((path, name))
+        })
+        .collect();
+    let names: Vec<String> = files.iter().map(|(_, name)| name.clone()).collect();
+    let message = format!(
+        "⬆ Upload {} to the working directory of this pane?",
+        names.join(", ")
+    );
+
+    if run_confirmation_app(&message, &mut term)? {
+        match existing_remote_files(pane_id, names) {
+            Ok(existing) if !existing.is_empty() => {
+                let message = format!(
+                    "⚠ {} already {} in the working directory of this pane. Overwrite?",
+                    existing.join(", "),
+                    if existing.len() == 1 {
+                        "exists"
+                    } else {
+                        "exist"
+                    }
+                );
+                if !run_confirmation_app(&message, &mut term)? {
+                    files.retain(|(_, name)| !existing.contains(name));
+                }
+                upload_files(pane_id, files, quoting);
+            }
+            Ok(_) => upload_files(pane_id, files, quoting),
+            Err(err) => {
+                log::error!(
+                    "Not uploading to pane {}: failed to check for existing files: {:#}",
+                    pane_id,
+                    err
+                );
+            }
+        }
+    }
+    TermWindow::schedule_cancel_overlay_for_pane(window, pane_id);
+
+    Ok(())
+}
+
+/// Returns those of `names` that already exist in the working
+/// directory of `pane_id`.  This is called from the overlay thread,
+/// so it waits for the checks that are made on the gui thread.
+fn existing_remote_files(pane_id: PaneId, names: Vec<String>) -> anyhow::Result<Vec<String>> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    promise::spawn::spawn_into_main_thread(async move {
+        let mux = Mux::get().unwrap();
+        let checks: Vec<_> = match mux
+            .get_pane(pane_id)
+            .as_ref()
+            .and_then(|pane| pane.downcast_ref::<ClientPane>())
+        {
+            Some(client_pane) => names
+                .into_iter()
+                .map(|name| (client_pane.remote_file_exists(name.clone()), name))
+                .collect(),
+            None => {
+                tx.send(Err(anyhow!("pane {} is no longer available", pane_id)))
+                    .ok();
+                return;
+            }
+        };
+        promise::spawn::spawn(async move {
+            let mut existing = vec![];
+            for (check, name) in checks {
+                match check.await {
+                    Ok(true) => existing.push(name),
+                    Ok(false) => {}
+                    Err(err) => {
+                        tx.send(Err(err)).ok();
+                        return;
+                    }
+                }
+            }
+            tx.send(Ok(existing)).ok();
+        })
+        .detach();
+    })
+    .detach();
+    rx.recv()?
+}
+
+/// Uploads `files` into the working directory of `pane_id` and then
+/// pastes their names into it
+fn upload_files(pane_id: PaneId, files: Vec<(PathBuf, String)>, quoting: DroppedFileQuoting) {
+    if files.is_empty() {
+        return;
+    }
+    promise::spawn::spawn_into_main_thread(async move {
+        let mux = Mux::get().unwrap();
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+        let names: Vec<String> = files.iter().map(|(_, name)| name.clone()).collect();
+        let uploads: Vec<_> = match pane.downcast_ref::<ClientPane>() {
+            Some(client_pane) => files
+                .into_iter()
+                .map(|(path, name)| client_pane.upload_file(path, name))
+                .collect(),
+            None => return,
+        };
+        promise::spawn::spawn(async move {
+            for upload in uploads {
+                if let Err(err) = upload.await {
+                    log::error!("Failed to upload to pane {}: {:#}", pane_id, err);
+                    return;
+                }
+            }
+            pane.trickle_paste(dropped_files_text(quoting, &names)).ok();
+        })
+        .detach();
+    })
+    .detach();
+}
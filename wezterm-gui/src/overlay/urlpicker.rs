@@ -0,0 +1,204 @@
+use crate::fuzzy::{filter_by_score, fuzzy_score};
+use crate::termwindow::clipboard::ClipboardHelper;
+use crate::termwindow::TermWindowNotif;
+use mux::pane::PaneId;
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::sync::Arc;
+use termwiz::cell::{AttributeChange, CellAttributes, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::hyperlink::Hyperlink;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::surface::{Change, Line, Position};
+use termwiz::terminal::Terminal;
+use wezterm_term::{Clipboard, ClipboardSelection};
+use window::WindowOps;
+
+/// A link that is displayed in the pane
+pub struct UrlEntry {
+    pub link: Arc<Hyperlink>,
+    /// The text that is displayed for the link
+    pub text: String,
+}
+
+/// Collects the distinct links from `lines`, starting with the one
+/// closest to the bottom
+pub fn collect_links(lines: &[Line]) -> Vec<UrlEntry> {
+    let mut entries: Vec<UrlEntry> = vec![];
+    for line in lines.iter().rev() {
+        let mut line_entries: Vec<UrlEntry> = vec![];
+        let mut prior: Option<&Arc<Hyperlink>> = None;
+        for cell in line.cells() {
+            let link = cell.attrs().hyperlink();
+            match (link, prior) {
+                (Some(link), Some(prior)) if Arc::ptr_eq(link, prior) || link == prior => {
+                    if let Some(entry) = line_entries.last_mut() {
+                        entry.text.push_str(cell.str());
+                    }
+                }
+                (Some(link), _) => line_entries.push(UrlEntry {
+                    link: Arc::clone(link),
+                    text: cell.str().to_string(),
+                }),
+                (None, _) => {}
+            }
+            prior = link;
+        }
+        for entry in line_entries.into_iter().rev() {
+            if !entries.iter().any(|e| e.link.uri() == entry.link.uri()) {
+                entries.push(entry);
+            }
+        }
+    }
+    entries
+}
+
+/// Returns the indices of the entries that match `filter`,
+/// best match first
+fn filter_links(entries: &[UrlEntry], filter: &str) -> Vec<usize> {
+    filter_by_score(entries, |entry| {
+        fuzzy_score(filter, &entry.text).max(fuzzy_score(filter, entry.link.uri()))
+    })
+}
+
+pub fn url_picker(
+    _tab_id: TabId,
+    mut term: TermWizTerminal,
+    entries: Vec<UrlEntry>,
+    pane_id: PaneId,
+    clipboard: ClipboardHelper,
+) -> anyhow::Result<()> {
+    let mut filter = String::new();
+    let mut matches = filter_links(&entries, &filter);
+    let mut active_idx = 0;
+
+    term.set_raw_mode()?;
+
+    fn render(
+        active_idx: usize,
+        filter: &str,
+        matches: &[usize],
+        entries: &[UrlEntry],
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let max_rows = size.rows.saturating_sub(2);
+        // Scroll so that the active entry is visible
+        let top = active_idx.saturating_sub(max_rows.saturating_sub(1));
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(
+                "Type to filter.  Enter: open  Ctrl-Y: copy  Escape: cancel\r\n".to_string(),
+            ),
+            Change::AllAttributes(CellAttributes::default()),
+            Change::Text(format!("> {}\r\n", filter)),
+        ];
+
+        if entries.is_empty() {
+            changes.push(Change::Text(" No links found\r\n".to_string()));
+        }
+
+        for (row, &idx) in matches.iter().enumerate().skip(top).take(max_rows) {
+            let entry = &entries[idx];
+            if row == active_idx {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            changes.push(Change::Text(format!(" {} ", entry.link.uri())));
+            let text = entry.text.trim();
+            if text != entry.link.uri() {
+                changes.push(AttributeChange::Intensity(Intensity::Half).into());
+                changes.push(Change::Text(format!(" {}", text)));
+            }
+            changes.push(Change::AllAttributes(CellAttributes::default()));
+            changes.push(Change::Text("\r\n".to_string()));
+        }
+
+        term.render(&changes)?;
+        term.flush()
+    }
+
+    term.render(&[Change::Title("Links".to_string())])?;
+    render(active_idx, &filter, &matches, &entries, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                active_idx = (active_idx + 1).min(matches.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('y'),
+                modifiers: Modifiers::CTRL,
+            }) => {
+                if let Some(&idx) = matches.get(active_idx) {
+                    clipboard.set_contents(
+                        ClipboardSelection::Clipboard,
+                        Some(entries[idx].link.uri().to_string()),
+                    )?;
+                }
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                if let Some(&idx) = matches.get(active_idx) {
+                    let link = Arc::clone(&entries[idx].link);
+                    clipboard
+                        .window
+                        .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                            let mux = Mux::get().unwrap();
+                            if let Some(pane) = mux.get_pane(pane_id) {
+                                term_window.open_hyperlink(&pane, &link);
+                            }
+                        })));
+                }
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) => {
+                filter.pop();
+                matches = filter_links(&entries, &filter);
+                active_idx = 0;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) => {
+                filter.push(c);
+                matches = filter_links(&entries, &filter);
+                active_idx = 0;
+            }
+            _ => {}
+        }
+        render(active_idx, &filter, &matches, &entries, &mut term)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,561 @@
+//! A read-only viewer for text that is too large to comfortably
+//! present in any other way, such as the captured output of a command.
+//! The text may contain ANSI color sequences.
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::escape::csi::Sgr;
+use termwiz::escape::parser::Parser;
+use termwiz::escape::{Action, ControlCode, CSI};
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+use unicode_segmentation::UnicodeSegmentation;
+
+const TAB_WIDTH: usize = 8;
+
+/// A run of text that shares the same attributes
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    attrs: CellAttributes,
+    text: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ViewerLine {
+    segments: Vec<Segment>,
+    /// The text without any attributes, used for searching
+    plain: String,
+}
+
+impl ViewerLine {
+    fn push(&mut self, attrs: &CellAttributes, c: char) {
+        match self.segments.last_mut() {
+            Some(seg) if seg.attrs == *attrs => seg.text.push(c),
+            _ => self.segments.push(Segment {
+                attrs: attrs.clone(),
+                text: c.to_string(),
+            }),
+        }
+        self.plain.push(c);
+    }
+}
+
+fn apply_sgr(attrs: &mut CellAttributes, sgr: Sgr) {
+    match sgr {
+        Sgr::Reset => *attrs = CellAttributes::default(),
+        Sgr::Intensity(intensity) => {
+            attrs.set_intensity(intensity);
+        }
+        Sgr::Underline(underline) => {
+            attrs.set_underline(underline);
+        }
+        Sgr::Italic(italic) => {
+            attrs.set_italic(italic);
+        }
+        Sgr::Inverse(inverse) => {
+            attrs.set_reverse(inverse);
+        }
+        Sgr::StrikeThrough(strike) => {
+            attrs.set_strikethrough(strike);
+        }
+        Sgr::Foreground(color) => {
+            attrs.set_foreground(color);
+        }
+        Sgr::Background(color) => {
+            attrs.set_background(color);
+        }
+        _ => {}
+    }
+}
+
+/// Splits `text` into lines, interpreting SGR escape sequences.
+/// Other escape sequences are discarded.
+fn parse_text(text: &str) -> Vec<ViewerLine> {
+    let mut lines = vec![];
+    let mut line = ViewerLine::default();
+    let mut attrs = CellAttributes::default();
+
+    let mut parser = Parser::new();
+    parser.parse(text.as_bytes(), |action| match action {
+        Action::Print(c) => line.push(&attrs, c),
+        Action::PrintString(s) => {
+            for c in s.chars() {
+                line.push(&attrs, c);
+            }
+        }
+        Action::Control(ControlCode::LineFeed) => {
+            lines.push(std::mem::take(&mut line));
+        }
+        Action::Control(ControlCode::HorizontalTab) => {
+            let col = unicode_column_width(&line.plain);
+            for _ in 0..TAB_WIDTH - (col % TAB_WIDTH) {
+                line.push(&attrs, ' ');
+            }
+        }
+        Action::CSI(CSI::Sgr(sgr)) => apply_sgr(&mut attrs, sgr),
+        _ => {}
+    });
+
+    if !line.plain.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Returns the indices of the lines that contain `pattern`.
+/// The search is case insensitive unless the pattern contains
+/// an uppercase character.
+fn find_matches(lines: &[ViewerLine], pattern: &str) -> Vec<usize> {
+    if pattern.is_empty() {
+        return vec![];
+    }
+    let case_sensitive = pattern.chars().any(char::is_uppercase);
+    let pattern_lower = pattern.to_lowercase();
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| {
+            if case_sensitive {
+                line.plain.contains(pattern)
+            } else {
+                line.plain.to_lowercase().contains(&pattern_lower)
+            }
+        })
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// Truncates `text` so that it occupies no more than `width` columns
+fn truncate_to_width(text: &str, width: usize) -> (&str, usize) {
+    let mut used = 0;
+    for (idx, grapheme) in text.grapheme_indices(true) {
+        let w = unicode_column_width(grapheme);
+        if used + w > width {
+            return (&text[..idx], used);
+        }
+        used += w;
+    }
+    (text, used)
+}
+
+/// Removes the first `skip` columns from `text`, returning the
+/// remaining text and the number of columns that were removed.
+/// A double width character that is only partly scrolled out of
+/// view is replaced by spaces.
+fn skip_columns(text: &str, skip: usize) -> (String, usize) {
+    let mut skipped = 0;
+    for (idx, grapheme) in text.grapheme_indices(true) {
+        if skipped >= skip {
+            return (text[idx..].to_string(), skipped);
+        }
+        let w = unicode_column_width(grapheme);
+        if skipped + w > skip {
+            let rest = &text[idx + grapheme.len()..];
+            return (format!("{}{}", " ".repeat(skipped + w - skip), rest), skip);
+        }
+        skipped += w;
+    }
+    (String::new(), skipped)
+}
+
+struct Viewer {
+    title: String,
+    lines: Vec<ViewerLine>,
+    top: usize,
+    /// The number of columns scrolled out of view on the left
+    left: usize,
+    /// The width of the longest line
+    width: usize,
+    /// The text of the search that is being typed, if any
+    search_input: Option<String>,
+    pattern: String,
+    matches: Vec<usize>,
+    /// Index into matches of the current match
+    match_idx: Option<usize>,
+}
+
+impl Viewer {
+    fn page_rows(term: &mut TermWizTerminal) -> usize {
+        term.get_screen_size()
+            .map(|size| size.rows.saturating_sub(1).max(1))
+            .unwrap_or(1)
+    }
+
+    fn scroll_to(&mut self, top: usize, page_rows: usize) {
+        let max_top = self.lines.len().saturating_sub(page_rows);
+        self.top = top.min(max_top);
+    }
+
+    fn scroll_by(&mut self, amount: isize, page_rows: usize) {
+        let top = (self.top as isize + amount).max(0) as usize;
+        self.scroll_to(top, page_rows);
+    }
+
+    fn page_cols(term: &mut TermWizTerminal) -> usize {
+        term.get_screen_size()
+            .map(|size| size.cols.max(1))
+            .unwrap_or(1)
+    }
+
+    /// Scrolls horizontally by `amount` columns, stopping once the end
+    /// of the longest line is visible
+    fn scroll_horizontally(&mut self, amount: isize, page_cols: usize) {
+        let max_left = self.width.saturating_sub(page_cols);
+        self.left = ((self.left as isize + amount).max(0) as usize).min(max_left);
+    }
+
+    fn search(&mut self, pattern: String, page_rows: usize) {
+        self.matches = find_matches(&self.lines, &pattern);
+        self.pattern = pattern;
+        // Start at the first match that is visible or below
+        self.match_idx = self
+            .matches
+            .iter()
+            .position(|&row| row >= self.top)
+            .or_else(|| {
+                if self.matches.is_empty() {
+                    None
+                } else {
+                    Some(0)
+                }
+            });
+        self.show_current_match(page_rows);
+    }
+
+    fn next_match(&mut self, forwards: bool, page_rows: usize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let len = self.matches.len();
+        self.match_idx = Some(match self.match_idx {
+            Some(idx) if forwards => (idx + 1) % len,
+            Some(idx) => (idx + len - 1) % len,
+            None => 0,
+        });
+        self.show_current_match(page_rows);
+    }
+
+    fn show_current_match(&mut self, page_rows: usize) {
+        if let Some(row) = self.match_idx.map(|idx| self.matches[idx]) {
+            if row < self.top || row >= self.top + page_rows {
+                self.scroll_to(row.saturating_sub(page_rows / 2), page_rows);
+            }
+        }
+    }
+
+    fn render(&self, term: &mut TermWizTerminal) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let page_rows = size.rows.saturating_sub(1).max(1);
+        let current_match = self.match_idx.map(|idx| self.matches[idx]);
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+        ];
+
+        for (row, line) in self.lines.iter().enumerate().skip(self.top).take(page_rows) {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(row - self.top),
+            });
+            let mut skip = self.left;
+            let mut remaining = size.cols;
+            for seg in &line.segments {
+                let (visible, skipped) = skip_columns(&seg.text, skip);
+                skip -= skipped;
+                if visible.is_empty() {
+                    continue;
+                }
+                let (text, used) = truncate_to_width(&visible, remaining);
+                let mut attrs = seg.attrs.clone();
+                if Some(row) == current_match {
+                    attrs.set_reverse(!attrs.reverse());
+                }
+                changes.push(Change::AllAttributes(attrs));
+                changes.push(Change::Text(text.to_string()));
+                remaining -= used;
+                if text.len() < visible.len() {
+                    break;
+                }
+            }
+            changes.push(Change::AllAttributes(CellAttributes::default()));
+        }
+
+        let status = match &self.search_input {
+            Some(input) => format!("/{}", input),
+            None => {
+                let mut position = format!(
+                    "{} lines {}-{}/{}",
+                    self.title,
+                    (self.top + 1).min(self.lines.len()),
+                    (self.top + page_rows).min(self.lines.len()),
+                    self.lines.len()
+                );
+                if self.left > 0 {
+                    position.push_str(&format!(" col {}", self.left + 1));
+                }
+                match self.match_idx {
+                    Some(idx) => format!(
+                        "{}  /{} ({}/{})",
+                        position,
+                        self.pattern,
+                        idx + 1,
+                        self.matches.len()
+                    ),
+                    None if !self.pattern.is_empty() => {
+                        format!("{}  /{} (no matches)", position, self.pattern)
+                    }
+                    None => format!("{}  q: close  /: search", position),
+                }
+            }
+        };
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(page_rows),
+        });
+        changes.push(AttributeChange::Reverse(true).into());
+        changes.push(Change::Text(
+            truncate_to_width(&status, size.cols).0.to_string(),
+        ));
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+
+        term.render(&changes)?;
+        term.flush()
+    }
+}
+
+/// Presents `text` in a read-only viewer until the user closes it.
+/// The key bindings follow those of copy mode where possible.
+pub fn text_viewer(
+    _tab_id: TabId,
+    mut term: TermWizTerminal,
+    title: String,
+    text: String,
+) -> anyhow::Result<()> {
+    let lines = parse_text(&text);
+    let width = lines
+        .iter()
+        .map(|line| unicode_column_width(&line.plain))
+        .max()
+        .unwrap_or(0);
+    let mut viewer = Viewer {
+        lines,
+        title,
+        top: 0,
+        left: 0,
+        width,
+        search_input: None,
+        pattern: String::new(),
+        matches: vec![],
+        match_idx: None,
+    };
+
+    term.set_raw_mode()?;
+    term.render(&[
+        Change::Title(viewer.title.clone()),
+        Change::CursorVisibility(termwiz::surface::CursorVisibility::Hidden),
+    ])?;
+    viewer.render(&mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let page_rows = Viewer::page_rows(&mut term);
+        let page_cols = Viewer::page_cols(&mut term);
+
+        if let Some(input) = viewer.search_input.as_mut() {
+            match event {
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Escape,
+                    ..
+                }) => {
+                    viewer.search_input.take();
+                }
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Enter,
+                    ..
+                }) => {
+                    let pattern = viewer.search_input.take().unwrap_or_default();
+                    viewer.search(pattern, page_rows);
+                }
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Backspace,
+                    ..
+                }) => {
+                    input.pop();
+                }
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Char(c),
+                    modifiers: Modifiers::NONE,
+                })
+                | InputEvent::Key(KeyEvent {
+                    key: KeyCode::Char(c),
+                    modifiers: Modifiers::SHIFT,
+                }) => {
+                    input.push(c);
+                }
+                _ => {}
+            }
+            viewer.render(&mut term)?;
+            continue;
+        }
+
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('q'),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('c'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('g'),
+                modifiers: Modifiers::CTRL,
+            }) => break,
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('j'),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => viewer.scroll_by(1, page_rows),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('k'),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => viewer.scroll_by(-1, page_rows),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('l'),
+                modifiers: Modifiers::NONE,
+            }) => viewer.scroll_horizontally(1, page_cols),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('h'),
+                modifiers: Modifiers::NONE,
+            }) => viewer.scroll_horizontally(-1, page_cols),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::RightArrow,
+                ..
+            }) => viewer.scroll_horizontally(page_cols as isize / 2, page_cols),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::LeftArrow,
+                ..
+            }) => viewer.scroll_horizontally(-(page_cols as isize / 2), page_cols),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('0'),
+                modifiers: Modifiers::NONE,
+            }) => viewer.left = 0,
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('f'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(' '),
+                modifiers: Modifiers::NONE,
+            }) => viewer.scroll_by(page_rows as isize, page_rows),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('b'),
+                modifiers: Modifiers::CTRL,
+            }) => viewer.scroll_by(-(page_rows as isize), page_rows),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('g'),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Home, ..
+            }) => viewer.scroll_to(0, page_rows),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('G'),
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::End, ..
+            }) => viewer.scroll_to(usize::max_value(), page_rows),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('/'),
+                ..
+            }) => {
+                viewer.search_input.replace(String::new());
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('n'),
+                modifiers: Modifiers::NONE,
+            }) => viewer.next_match(true, page_rows),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('N'),
+                ..
+            }) => viewer.next_match(false, page_rows),
+            _ => {}
+        }
+        viewer.render(&mut term)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::color::AnsiColor;
+
+    #[test]
+    fn parse_ansi() {
+        let lines = parse_text("plain\n\x1b[31mred\x1b[0m text\n\ta\x1b[2J");
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].plain, "plain");
+
+        assert_eq!(lines[1].plain, "red text");
+        assert_eq!(lines[1].segments.len(), 2);
+        assert_eq!(
+            lines[1].segments[0].attrs.foreground(),
+            ColorAttribute::PaletteIndex(AnsiColor::Maroon as u8)
+        );
+        assert_eq!(lines[1].segments[1].attrs, CellAttributes::default());
+
+        assert_eq!(lines[2].plain, "        a");
+    }
+
+    #[test]
+    fn search() {
+        let lines = parse_text("Foo\nbar\nfoo bar\n");
+        assert_eq!(find_matches(&lines, "foo"), vec![0, 2]);
+        assert_eq!(find_matches(&lines, "Foo"), vec![0]);
+        assert_eq!(find_matches(&lines, ""), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn truncate() {
+        assert_eq!(truncate_to_width("hello", 3), ("hel", 3));
+        assert_eq!(truncate_to_width("hi", 3), ("hi", 2));
+        // A double width character that doesn't fit is dropped
+        assert_eq!(truncate_to_width("a\u{1f600}", 2), ("a", 1));
+    }
+
+    #[test]
+    fn skip() {
+        assert_eq!(skip_columns("hello", 2), ("llo".to_string(), 2));
+        assert_eq!(skip_columns("hi", 3), (String::new(), 2));
+        // A double width character that is partly scrolled out of view
+        // leaves a space in its place
+        assert_eq!(skip_columns("a\u{1f600}b", 2), (" b".to_string(), 2));
+    }
+}
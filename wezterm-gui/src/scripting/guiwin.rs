@@ -1,13 +1,17 @@
 //! GuiWin represents a Gui TermWindow (as opposed to a Mux window) in lua code
 use super::luaerr;
 use super::pane::PaneObject;
+use crate::overlay::ModalSpec;
+use crate::termwindow::widget::PaneWidgetSpec;
 use crate::termwindow::TermWindowNotif;
 use crate::TermWindow;
 use config::keyassignment::KeyAssignment;
 use luahelper::*;
 use mlua::{UserData, UserDataMethods};
 use mux::window::WindowId as MuxWindowId;
+use mux::Mux;
 use serde::*;
+use wezterm_term::color::RgbColor;
 use wezterm_toast_notification::ToastNotification;
 use window::{Connection, ConnectionOps, WindowOps, WindowState};
 
@@ -46,6 +50,24 @@ impl UserData for GuiWin {
         methods.add_method("get_appearance", |_, _, _: ()| {
             Ok(Connection::get().unwrap().get_appearance().to_string())
         });
+        methods.add_method(
+            "flash_visual_bell",
+            |_, this, (pane, color): (PaneObject, Option<String>)| {
+                let color = match color {
+                    Some(color) => Some(
+                        RgbColor::from_named_or_rgb_string(&color)
+                            .ok_or_else(|| anyhow::anyhow!("invalid color {}", color))
+                            .map_err(luaerr)?,
+                    ),
+                    None => None,
+                };
+                this.window.notify(TermWindowNotif::FlashVisualBell {
+                    pane_id: pane.pane,
+                    color,
+                });
+                Ok(())
+            },
+        );
         methods.add_method("set_right_status", |_, this, status: String| {
             this.window.notify(TermWindowNotif::SetRightStatus(status));
             Ok(())
@@ -132,5 +154,70 @@ impl UserData for GuiWin {
                 .notify(TermWindowNotif::SetConfigOverrides(value.0));
             Ok(())
         });
+        methods.add_method("active_workspace", |_, _, _: ()| {
+            let mux = Mux::get().unwrap();
+            Ok(mux.active_workspace())
+        });
+        methods.add_method("get_workspaces", |_, _, _: ()| {
+            let mux = Mux::get().unwrap();
+            Ok(mux.iter_workspaces())
+        });
+        methods.add_method("set_workspace_order", |_, _, order: Vec<String>| {
+            let mux = Mux::get().unwrap();
+            mux.set_workspace_order(order);
+            Ok(())
+        });
+        methods.add_method("rename_workspace", |_, _, (old, new): (String, String)| {
+            let mux = Mux::get().unwrap();
+            mux.rename_workspace(&old, &new).map_err(luaerr)
+        });
+        methods.add_method("close_workspace", |_, _, name: String| {
+            crate::workspace::close_workspace(&name);
+            Ok(())
+        });
+        methods.add_method(
+            "show_modal",
+            |_, this, (pane, spec): (PaneObject, ModalSpec)| {
+                let pane_id = pane.pane;
+                this.window
+                    .notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                        tw.show_lua_modal(pane_id, spec)
+                    })));
+                Ok(())
+            },
+        );
+        methods.add_method(
+            "set_pane_widget",
+            |_, this, (pane, id, spec): (PaneObject, String, PaneWidgetSpec)| {
+                let pane_id = pane.pane;
+                this.window
+                    .notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                        tw.set_pane_widget(pane_id, id, spec)
+                    })));
+                Ok(())
+            },
+        );
+        methods.add_method(
+            "remove_pane_widget",
+            |_, this, (pane, id): (PaneObject, String)| {
+                let pane_id = pane.pane;
+                this.window
+                    .notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                        tw.remove_pane_widget(pane_id, &id)
+                    })));
+                Ok(())
+            },
+        );
+        methods.add_method(
+            "invalidate_pane_widget",
+            |_, this, (pane, id): (PaneObject, String)| {
+                let pane_id = pane.pane;
+                this.window
+                    .notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                        tw.update_pane_widget(pane_id, &id)
+                    })));
+                Ok(())
+            },
+        );
     }
 }
@@ -1,10 +1,32 @@
 //! PaneObject represents a Mux Pane instance in lua code
 use super::luaerr;
+use crate::termwindow::highlight::{HighlightRange, HighlightStyle, PaneHighlight};
+use crate::termwindow::TermWindowNotif;
 use anyhow::anyhow;
 use mlua::{UserData, UserDataMethods};
 use mux::pane::{Pane, PaneId};
 use mux::Mux;
+use portable_pty::serial::SerialHandle;
+use serial::{BaudRate, CharSize, FlowControl, Parity, PortSettings, StopBits};
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::Mutex;
+use window::WindowOps;
+
+lazy_static::lazy_static! {
+    static ref SERIAL_PANES: Mutex<HashMap<PaneId, SerialHandle>> = Mutex::new(HashMap::new());
+}
+
+/// Associates `pane_id` with the serial port that it is connected
+/// to, so that the port can be configured from lua
+pub fn register_serial_pane(pane_id: PaneId, handle: SerialHandle) {
+    SERIAL_PANES.lock().unwrap().insert(pane_id, handle);
+}
+
+/// Forgets the serial port of `pane_id` once the pane has been removed
+pub fn unregister_serial_pane(pane_id: PaneId) {
+    SERIAL_PANES.lock().unwrap().remove(&pane_id);
+}
 
 #[derive(Clone)]
 pub struct PaneObject {
@@ -18,6 +40,29 @@ impl PaneObject {
         }
     }
 
+    fn serial(&self) -> mlua::Result<SerialHandle> {
+        SERIAL_PANES
+            .lock()
+            .unwrap()
+            .get(&self.pane)
+            .cloned()
+            .ok_or_else(|| anyhow!("pane id {} is not a serial port", self.pane))
+            .map_err(luaerr)
+    }
+
+    /// Returns the gui window in which the pane is displayed
+    fn gui_window(&self) -> mlua::Result<::window::Window> {
+        let mux = Mux::get()
+            .ok_or_else(|| anyhow!("must be called on main thread"))
+            .map_err(luaerr)?;
+        mux.resolve_pane_id(self.pane)
+            .and_then(|(_domain_id, window_id, _tab_id)| {
+                crate::frontend::front_end()?.gui_window_for_mux_window(window_id)
+            })
+            .ok_or_else(|| anyhow!("pane id {} is not displayed in a gui window", self.pane))
+            .map_err(luaerr)
+    }
+
     pub fn pane(&self) -> mlua::Result<Rc<dyn Pane>> {
         let mux = Mux::get()
             .ok_or_else(|| anyhow!("must be called on main thread"))
@@ -38,6 +83,9 @@ impl UserData for PaneObject {
                 .get_current_working_dir()
                 .map(|u| u.to_string()))
         });
+        methods.add_method("get_foreground_process_tree", |_, this, _: ()| {
+            Ok(this.pane()?.get_foreground_process_tree())
+        });
         methods.add_method("paste", |_, this, text: String| {
             this.pane()?.send_paste(&text).map_err(luaerr)?;
             Ok(())
@@ -51,6 +99,123 @@ impl UserData for PaneObject {
         methods.add_method("get_user_vars", |_, this, _: ()| {
             Ok(this.pane()?.copy_user_vars())
         });
+        methods.add_method("get_encoding", |_, this, _: ()| {
+            Ok(this.pane()?.get_encoding())
+        });
+        methods.add_method("set_encoding", |_, this, encoding: String| {
+            this.pane()?.set_encoding(&encoding).map_err(luaerr)
+        });
+
+        // Returns a table describing the settings of the serial port
+        // that the pane is connected to
+        methods.add_method("serial_get", |lua, this, _: ()| {
+            let handle = this.serial()?;
+            let settings = handle.settings();
+            let result = lua.create_table()?;
+            result.set("port", handle.port_name().to_string_lossy().to_string())?;
+            result.set("baud", settings.baud_rate.speed())?;
+            result.set(
+                "char_size",
+                match settings.char_size {
+                    CharSize::Bits5 => 5,
+                    CharSize::Bits6 => 6,
+                    CharSize::Bits7 => 7,
+                    CharSize::Bits8 => 8,
+                },
+            )?;
+            result.set(
+                "parity",
+                match settings.parity {
+                    Parity::ParityNone => "None",
+                    Parity::ParityOdd => "Odd",
+                    Parity::ParityEven => "Even",
+                },
+            )?;
+            result.set(
+                "stop_bits",
+                match settings.stop_bits {
+                    StopBits::Stop1 => 1,
+                    StopBits::Stop2 => 2,
+                },
+            )?;
+            result.set(
+                "flow_control",
+                match settings.flow_control {
+                    FlowControl::FlowNone => "None",
+                    FlowControl::FlowSoftware => "Software",
+                    FlowControl::FlowHardware => "Hardware",
+                },
+            )?;
+            Ok(result)
+        });
+
+        // Changes the settings of the serial port that the pane is
+        // connected to.  Fields that are omitted retain their current
+        // value.
+        methods.add_method("serial_set", |_, this, params: mlua::Table| {
+            let handle = this.serial()?;
+            let settings = serial_settings_from_table(handle.settings(), &params)?;
+            handle.configure(&settings).map_err(luaerr)
+        });
+
+        methods.add_async_method("get_scroll_offset", |_, this, _: ()| async move {
+            let pane_id = this.pane()?.pane_id();
+            let window = this.gui_window()?;
+            let (tx, rx) = smol::channel::bounded(1);
+            window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                if let Some(pane) = Mux::get().unwrap().get_pane(pane_id) {
+                    let dims = pane.get_dimensions();
+                    tx.try_send(term_window.get_scroll_offset(pane_id, &dims))
+                        .ok();
+                }
+            })));
+            rx.recv()
+                .await
+                .map_err(|e| anyhow!("{:#}", e))
+                .map_err(luaerr)
+        });
+        methods.add_method("set_scroll_offset", |_, this, offset: usize| {
+            let pane_id = this.pane()?.pane_id();
+            let window = this.gui_window()?;
+            window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                if let Some(pane) = Mux::get().unwrap().get_pane(pane_id) {
+                    term_window.set_scroll_offset(pane_id, offset, pane.get_dimensions());
+                }
+            })));
+            Ok(())
+        });
+
+        // Overlays `style` on `range` of the pane, returning an id
+        // that can be passed to remove_highlight
+        methods.add_method(
+            "add_highlight",
+            |_, this, (range, style): (HighlightRange, HighlightStyle)| {
+                let pane_id = this.pane()?.pane_id();
+                let window = this.gui_window()?;
+                let highlight = PaneHighlight::new(range, &style).map_err(luaerr)?;
+                let id = highlight.id;
+                window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    term_window.add_pane_highlight(pane_id, highlight);
+                })));
+                Ok(id)
+            },
+        );
+        methods.add_method("remove_highlight", |_, this, id: usize| {
+            let pane_id = this.pane()?.pane_id();
+            let window = this.gui_window()?;
+            window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                term_window.remove_pane_highlight(pane_id, Some(id));
+            })));
+            Ok(())
+        });
+        methods.add_method("clear_highlights", |_, this, _: ()| {
+            let pane_id = this.pane()?.pane_id();
+            let window = this.gui_window()?;
+            window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                term_window.remove_pane_highlight(pane_id, None);
+            })));
+            Ok(())
+        });
 
         // When called with no arguments, returns the lines from the
         // viewport as plain text (no escape sequences).
@@ -79,3 +244,45 @@ impl UserData for PaneObject {
         });
     }
 }
+
+fn serial_settings_from_table(
+    mut settings: PortSettings,
+    params: &mlua::Table,
+) -> mlua::Result<PortSettings> {
+    if let Some(baud) = params.get::<_, Option<usize>>("baud")? {
+        settings.baud_rate = BaudRate::from_speed(baud);
+    }
+    if let Some(char_size) = params.get::<_, Option<u8>>("char_size")? {
+        settings.char_size = match char_size {
+            5 => CharSize::Bits5,
+            6 => CharSize::Bits6,
+            7 => CharSize::Bits7,
+            8 => CharSize::Bits8,
+            _ => return Err(luaerr(anyhow!("invalid char_size {}", char_size))),
+        };
+    }
+    if let Some(parity) = params.get::<_, Option<String>>("parity")? {
+        settings.parity = match parity.as_str() {
+            "None" => Parity::ParityNone,
+            "Odd" => Parity::ParityOdd,
+            "Even" => Parity::ParityEven,
+            _ => return Err(luaerr(anyhow!("invalid parity {}", parity))),
+        };
+    }
+    if let Some(stop_bits) = params.get::<_, Option<u8>>("stop_bits")? {
+        settings.stop_bits = match stop_bits {
+            1 => StopBits::Stop1,
+            2 => StopBits::Stop2,
+            _ => return Err(luaerr(anyhow!("invalid stop_bits {}", stop_bits))),
+        };
+    }
+    if let Some(flow) = params.get::<_, Option<String>>("flow_control")? {
+        settings.flow_control = match flow.as_str() {
+            "None" => FlowControl::FlowNone,
+            "Software" => FlowControl::FlowSoftware,
+            "Hardware" => FlowControl::FlowHardware,
+            _ => return Err(luaerr(anyhow!("invalid flow_control {}", flow))),
+        };
+    }
+    Ok(settings)
+}
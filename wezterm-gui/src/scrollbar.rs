@@ -53,6 +53,15 @@ impl ScrollHit {
         }
     }
 
+    /// Compute the y-coordinate within the scrollbar that corresponds
+    /// to the specified row of the scrollback
+    pub fn row_to_y(pane: &dyn Pane, row: StableRowIndex, dims: &Dimensions) -> usize {
+        let render_dims = pane.get_dimensions();
+        let scroll_size = render_dims.scrollback_rows.max(1) as f32;
+        let offset = row.saturating_sub(render_dims.scrollback_top).max(0) as f32;
+        ((offset / scroll_size) * dims.pixel_height as f32) as usize
+    }
+
     /// Given a new thumb top coordinate (produced by dragging the thumb),
     /// compute the equivalent viewport offset.
     pub fn thumb_top_to_scroll_top(
@@ -1,12 +1,13 @@
 // The range_plus_one lint can't see when the LHS is not compatible with
 // and inclusive range
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::range_plus_one))]
+use config::SelectionWordMode;
 use mux::pane::Pane;
 use std::cmp::Ordering;
 use std::ops::Range;
 use termwiz::surface::line::DoubleClickRange;
 use termwiz::surface::SequenceNo;
-use wezterm_term::{SemanticZone, StableRowIndex};
+use wezterm_term::{SemanticType, SemanticZone, StableRowIndex};
 
 #[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
 pub struct Selection {
@@ -17,6 +18,9 @@ pub struct Selection {
     pub range: Option<SelectionRange>,
     /// When the selection was made wrt. the pane content
     pub seqno: SequenceNo,
+    /// Whether the range selects a rectangular block of cells,
+    /// rather than the text that flows between its start and end
+    pub rectangular: bool,
 }
 
 pub use config::keyassignment::SelectionMode;
@@ -26,11 +30,13 @@ impl Selection {
     pub fn clear(&mut self) {
         self.range = None;
         self.start = None;
+        self.rectangular = false;
     }
 
     pub fn begin(&mut self, start: SelectionCoordinate) {
         self.range = None;
         self.start = Some(start);
+        self.rectangular = false;
     }
 
     #[allow(dead_code)]
@@ -54,14 +60,132 @@ pub struct SelectionRange {
     pub end: SelectionCoordinate,
 }
 
-fn is_double_click_word(s: &str) -> bool {
+/// A program can set this user var to override `selection_word_boundary`
+/// for the pane in which it is running
+pub const WORD_BOUNDARY_USER_VAR: &str = "WEZTERM_SELECTION_WORD_BOUNDARY";
+
+fn is_double_click_word(s: &str, boundary: &str) -> bool {
     match s.chars().count() {
-        1 => !config::configuration().selection_word_boundary.contains(s),
+        1 => !boundary.contains(s),
         0 => false,
         _ => true,
     }
 }
 
+/// Computes the range of cells around `idx` that the semantic word
+/// selection mode selects: a URL, a path or the contents of a quoted
+/// string, in that order of preference.
+/// Returns None if the click doesn't fall within any of those.
+fn semantic_word_range(cells: &[&str], idx: usize) -> Option<Range<usize>> {
+    // Cells that hold combining sequences are treated as word characters,
+    // and empty cells as whitespace
+    let chars: Vec<char> = cells
+        .iter()
+        .map(|s| {
+            let mut iter = s.chars();
+            match (iter.next(), iter.next()) {
+                (Some(c), None) => c,
+                (Some(_), Some(_)) => 'a',
+                (None, _) => ' ',
+            }
+        })
+        .collect();
+    if idx >= chars.len() || chars[idx].is_whitespace() {
+        return None;
+    }
+
+    let expand = |pred: &dyn Fn(char) -> bool| -> Option<Range<usize>> {
+        if !pred(chars[idx]) {
+            return None;
+        }
+        let mut start = idx;
+        while start > 0 && pred(chars[start - 1]) {
+            start -= 1;
+        }
+        let mut end = idx + 1;
+        while end < chars.len() && pred(chars[end]) {
+            end += 1;
+        }
+        Some(start..end)
+    };
+    let text = |range: &Range<usize>| chars[range.clone()].iter().collect::<String>();
+    // Trailing punctuation is more likely to belong to the surrounding
+    // prose than to the url or path
+    let trim = |mut range: Range<usize>, trailing: &str| {
+        while range.end > idx + 1 && trailing.contains(chars[range.end - 1]) {
+            range.end -= 1;
+        }
+        range
+    };
+
+    let is_url_char = |c: char| {
+        !c.is_whitespace() && !matches!(c, '"' | '\'' | '`' | '<' | '>' | '|' | '{' | '}')
+    };
+    if let Some(range) = expand(&is_url_char) {
+        let token = text(&range);
+        if let Some(pos) = token.find("://") {
+            // Skip over anything that precedes the scheme, such as an
+            // opening parenthesis
+            let scheme_len = token[..pos]
+                .chars()
+                .rev()
+                .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+                .count();
+            let prefix = token[..pos].chars().count() - scheme_len;
+            let range = range.start + prefix..range.end;
+            if scheme_len > 0 && range.contains(&idx) {
+                let mut range = trim(range, ".,;:!?)]");
+                // Keep a closing parenthesis that balances one in the url
+                if range.end < chars.len() && chars[range.end] == ')' {
+                    let url = text(&range);
+                    if url.matches('(').count() > url.matches(')').count() {
+                        range.end += 1;
+                    }
+                }
+                return Some(range);
+            }
+        }
+    }
+
+    let is_path_char = |c: char| {
+        c.is_alphanumeric()
+            || matches!(
+                c,
+                '_' | '-' | '.' | '/' | '\\' | '~' | '+' | '@' | '%' | ':' | '=' | ',' | '#'
+            )
+    };
+    if let Some(range) = expand(&is_path_char) {
+        let token = text(&range);
+        if token.contains('/') || token.contains('\\') {
+            let mut range = trim(range, ".,:;");
+            while range.start < idx && matches!(chars[range.start], ':' | ',' | '=') {
+                range.start += 1;
+            }
+            return Some(range);
+        }
+    }
+
+    for &quote in &['"', '\'', '`'] {
+        if chars[idx] == quote {
+            continue;
+        }
+        let open = match chars[..idx].iter().rposition(|&c| c == quote) {
+            Some(open) => open,
+            None => continue,
+        };
+        // An odd number of quotes before this one means that it closes
+        // an earlier string, rather than opening one
+        if chars[..open].iter().filter(|&&c| c == quote).count() % 2 != 0 {
+            continue;
+        }
+        if let Some(close) = chars[idx + 1..].iter().position(|&c| c == quote) {
+            return Some(open + 1..idx + 1 + close);
+        }
+    }
+
+    None
+}
+
 impl SelectionRange {
     /// Create a new range that starts at the specified location
     pub fn start(start: SelectionCoordinate) -> Self {
@@ -135,30 +259,66 @@ impl SelectionRange {
         }
     }
 
+    /// Computes the selection range for the output of the most
+    /// recently completed command
+    pub fn last_command_output(pane: &dyn Pane) -> Option<Self> {
+        let zones = pane.get_semantic_zones().ok()?;
+        let zone = last_output_zone(&zones)?;
+        Some(Self {
+            start: SelectionCoordinate {
+                x: zone.start_x,
+                y: zone.start_y,
+            },
+            end: SelectionCoordinate {
+                x: zone.end_x,
+                y: zone.end_y,
+            },
+        })
+    }
+
     /// Computes the selection range for the word around the specified coords
     pub fn word_around(start: SelectionCoordinate, pane: &dyn Pane) -> Self {
+        let config = config::configuration();
+        let boundary = pane
+            .copy_user_vars()
+            .remove(WORD_BOUNDARY_USER_VAR)
+            .unwrap_or_else(|| config.selection_word_boundary.clone());
+
         for logical in pane.get_logical_lines(start.y..start.y + 1) {
             if !logical.contains_y(start.y) {
                 continue;
             }
 
             let start_idx = logical.xy_to_logical_x(start.x, start.y);
-            return match logical
-                .logical
-                .compute_double_click_range(start_idx, is_double_click_word)
-            {
-                DoubleClickRange::RangeWithWrap(click_range)
-                | DoubleClickRange::Range(click_range) => {
-                    let (start_y, start_x) = logical.logical_x_to_physical_coord(click_range.start);
-                    let (end_y, end_x) = logical.logical_x_to_physical_coord(click_range.end - 1);
-                    Self {
-                        start: SelectionCoordinate {
-                            x: start_x,
-                            y: start_y,
-                        },
-                        end: SelectionCoordinate { x: end_x, y: end_y },
-                    }
+            let semantic = match config.selection_word_mode {
+                SelectionWordMode::Semantic => {
+                    // The cells that are hidden by a wide grapheme are
+                    // treated as being part of it
+                    let line = &logical.logical;
+                    let cells: Vec<&str> = (0..line.cells().len())
+                        .map(|idx| line.cells()[line.grapheme_range(idx).start].str())
+                        .collect();
+                    semantic_word_range(&cells, start_idx)
                 }
+                SelectionWordMode::Boundary => None,
+            };
+            let click_range = semantic.unwrap_or_else(|| {
+                match logical
+                    .logical
+                    .compute_double_click_range(start_idx, |s| is_double_click_word(s, &boundary))
+                {
+                    DoubleClickRange::RangeWithWrap(click_range)
+                    | DoubleClickRange::Range(click_range) => click_range,
+                }
+            });
+            let (start_y, start_x) = logical.logical_x_to_physical_coord(click_range.start);
+            let (end_y, end_x) = logical.logical_x_to_physical_coord(click_range.end - 1);
+            return Self {
+                start: SelectionCoordinate {
+                    x: start_x,
+                    y: start_y,
+                },
+                end: SelectionCoordinate { x: end_x, y: end_y },
             };
         }
 
@@ -216,13 +376,15 @@ impl SelectionRange {
     /// indicates that the selection extends to the end of that row.
     /// Since this struct has no knowledge of line length, it cannot be
     /// more precise than that.
+    /// If `rectangular` is true, the same columns are selected
+    /// in every row.
     /// Must be called on a normalized range!
-    pub fn cols_for_row(&self, row: StableRowIndex) -> Range<usize> {
+    pub fn cols_for_row(&self, row: StableRowIndex, rectangular: bool) -> Range<usize> {
         let norm = self.normalize();
         if row < norm.start.y || row > norm.end.y {
             0..0
-        } else if norm.start.y == norm.end.y {
-            // A single line selection
+        } else if rectangular || norm.start.y == norm.end.y {
+            // A single line selection, or a block
             if norm.start.x <= norm.end.x {
                 norm.start.x..norm.end.x.saturating_add(1)
             } else {
@@ -240,3 +402,97 @@ impl SelectionRange {
         }
     }
 }
+
+/// Returns the output zone of the most recently completed command.
+/// That is the last Output zone that is followed by a Prompt; an
+/// Output zone at the very end belongs to a command that is still
+/// running.
+fn last_output_zone(zones: &[SemanticZone]) -> Option<&SemanticZone> {
+    let last_prompt = zones
+        .iter()
+        .rposition(|zone| zone.semantic_type == SemanticType::Prompt)?;
+    zones[..last_prompt]
+        .iter()
+        .rev()
+        .find(|zone| zone.semantic_type == SemanticType::Output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn zone(
+        start_y: StableRowIndex,
+        end_y: StableRowIndex,
+        semantic_type: SemanticType,
+    ) -> SemanticZone {
+        SemanticZone {
+            start_y,
+            start_x: 0,
+            end_y,
+            end_x: 10,
+            semantic_type,
+        }
+    }
+
+    #[test]
+    fn rectangular_cols() {
+        let range = SelectionRange::start(SelectionCoordinate { x: 8, y: 1 })
+            .extend(SelectionCoordinate { x: 3, y: 4 });
+        assert_eq!(range.cols_for_row(0, true), 0..0);
+        assert_eq!(range.cols_for_row(1, true), 3..9);
+        assert_eq!(range.cols_for_row(2, true), 3..9);
+        assert_eq!(range.cols_for_row(4, true), 3..9);
+        assert_eq!(range.cols_for_row(2, false), 0..usize::max_value());
+        assert_eq!(range.cols_for_row(4, false), 0..4);
+    }
+
+    fn semantic(line: &str, idx: usize) -> Option<String> {
+        let cells: Vec<String> = line.chars().map(|c| c.to_string()).collect();
+        let cells: Vec<&str> = cells.iter().map(|s| s.as_str()).collect();
+        let chars: Vec<char> = line.chars().collect();
+        semantic_word_range(&cells, idx).map(|range| chars[range].iter().collect())
+    }
+
+    #[test]
+    fn semantic_words() {
+        let line = "see (https://example.com/a_(b)?q=1), then";
+        assert_eq!(
+            semantic(line, 10).as_deref(),
+            Some("https://example.com/a_(b)?q=1")
+        );
+
+        let line = "error at src/main.rs:12:5: oops";
+        assert_eq!(semantic(line, 12).as_deref(), Some("src/main.rs:12:5"));
+        assert_eq!(semantic(line, 28), None);
+
+        let line = r#"echo "hello there" 'it''s'"#;
+        assert_eq!(semantic(line, 8).as_deref(), Some("hello there"));
+        // Clicking on the quote itself doesn't select the string
+        assert_eq!(semantic(line, 5), None);
+
+        assert_eq!(semantic("two words", 3), None);
+        assert_eq!(semantic("", 0), None);
+    }
+
+    #[test]
+    fn last_output() {
+        let zones = vec![
+            zone(0, 0, SemanticType::Prompt),
+            zone(0, 0, SemanticType::Input),
+            zone(1, 3, SemanticType::Output),
+            zone(4, 4, SemanticType::Prompt),
+            zone(4, 4, SemanticType::Input),
+            zone(5, 9, SemanticType::Output),
+        ];
+        // The command whose output begins on row 5 is still running
+        assert_eq!(last_output_zone(&zones), Some(&zones[2]));
+
+        let mut zones = zones;
+        zones.push(zone(10, 10, SemanticType::Prompt));
+        assert_eq!(last_output_zone(&zones), Some(&zones[5]));
+
+        assert_eq!(last_output_zone(&zones[..2]), None);
+        assert_eq!(last_output_zone(&[]), None);
+    }
+}
@@ -11,6 +11,8 @@ use wezterm_font::units::*;
 pub struct ShapeCacheKey {
     pub style: TextStyle,
     pub text: String,
+    /// Whether the text was shaped with ligatures turned off
+    pub without_ligatures: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -188,6 +190,7 @@ where
 pub struct BorrowedShapeCacheKey<'a> {
     pub style: &'a TextStyle,
     pub text: &'a str,
+    pub without_ligatures: bool,
 }
 
 impl<'a> BorrowedShapeCacheKey<'a> {
@@ -195,6 +198,7 @@ impl<'a> BorrowedShapeCacheKey<'a> {
         ShapeCacheKey {
             style: self.style.clone(),
             text: self.text.to_owned(),
+            without_ligatures: self.without_ligatures,
         }
     }
 }
@@ -208,6 +212,7 @@ impl ShapeCacheKeyTrait for ShapeCacheKey {
         BorrowedShapeCacheKey {
             style: &self.style,
             text: &self.text,
+            without_ligatures: self.without_ligatures,
         }
     }
 }
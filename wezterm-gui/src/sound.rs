@@ -0,0 +1,63 @@
+//! Makes the sound of the audible bell
+use config::{AudibleBell, ConfigHandle};
+use ratelim::RateLimiter;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Mutex;
+use window::{Connection, ConnectionOps};
+
+lazy_static::lazy_static! {
+    static ref LIMITER: Mutex<RateLimiter> = Mutex::new(RateLimiter::new(|config| {
+        config.ratelimit_audible_bell_per_second.max(1)
+    }));
+}
+
+/// Makes the configured sound for a bell that rang in a pane
+/// that belongs to the domain named `domain_name`
+pub fn ring_audible_bell(config: &ConfigHandle, domain_name: &str) {
+    if let AudibleBell::Disabled = config.audible_bell {
+        return;
+    }
+    if config
+        .audible_bell_muted_domains
+        .iter()
+        .any(|name| name == domain_name)
+    {
+        log::trace!("audible bell is muted for domain {}", domain_name);
+        return;
+    }
+    if !LIMITER.lock().unwrap().non_blocking_admittance_check(1) {
+        log::trace!("audible bell is rate limited");
+        return;
+    }
+
+    match &config.audible_bell {
+        AudibleBell::Disabled => {}
+        AudibleBell::SystemBeep => {
+            Connection::get().expect("on main thread").beep();
+        }
+        AudibleBell::SoundFile(path) => {
+            let path = path.clone();
+            let volume = config.audible_bell_volume.max(0.).min(1.);
+            // Playback blocks until the sound has finished, so
+            // it is performed away from the gui thread
+            std::thread::spawn(move || {
+                if let Err(err) = play_sound_file(&path, volume) {
+                    log::error!("Failed to play {}: {:#}", path.display(), err);
+                }
+            });
+        }
+    }
+}
+
+fn play_sound_file(path: &Path, volume: f32) -> anyhow::Result<()> {
+    let file = BufReader::new(File::open(path)?);
+    let source = rodio::Decoder::new(file)?;
+    let (_stream, handle) = rodio::OutputStream::try_default()?;
+    let sink = rodio::Sink::try_new(&handle)?;
+    sink.set_volume(volume);
+    sink.append(source);
+    sink.sleep_until_end();
+    Ok(())
+}
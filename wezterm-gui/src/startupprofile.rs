@@ -0,0 +1,90 @@
+//! `wezterm --profile-startup`, which records how long the phases of
+//! starting up take and prints a breakdown once the first window has
+//! painted its first frame.
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+lazy_static::lazy_static! {
+    static ref PROFILE: Mutex<Option<StartupProfile>> = Mutex::new(None);
+}
+
+struct StartupProfile {
+    start: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl StartupProfile {
+    fn report(&self) -> String {
+        fn line(phase: &str, elapsed: Duration) -> String {
+            format!("  {:<24}{:>8.1}ms\n", phase, elapsed.as_secs_f64() * 1000.)
+        }
+        let mut report = "startup profile:\n".to_string();
+        for (phase, elapsed) in &self.phases {
+            report.push_str(&line(phase, *elapsed));
+        }
+        report.push_str(&line("total", self.start.elapsed()));
+        report
+    }
+}
+
+/// Starts recording the phases of startup
+pub fn enable() {
+    PROFILE.lock().unwrap().replace(StartupProfile {
+        start: Instant::now(),
+        phases: vec![],
+    });
+}
+
+/// Records how long `func` takes as `phase`, if startup is being profiled
+pub fn time<T>(phase: &'static str, func: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = func();
+    record(phase, start.elapsed());
+    result
+}
+
+/// Records that `phase` took `elapsed`, if startup is being profiled.
+/// Phases that happen more than once, such as creating a second window,
+/// accumulate.
+pub fn record(phase: &'static str, elapsed: Duration) {
+    if let Some(profile) = PROFILE.lock().unwrap().as_mut() {
+        match profile.phases.iter_mut().find(|(p, _)| *p == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => profile.phases.push((phase, elapsed)),
+        }
+    }
+}
+
+/// Prints the breakdown and stops recording.  Called when the first
+/// frame has been painted.
+pub fn finish() {
+    if let Some(profile) = PROFILE.lock().unwrap().take() {
+        eprint!("{}", profile.report());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn phases_accumulate() {
+        enable();
+        record("font initialization", Duration::from_millis(5));
+        record("window creation", Duration::from_millis(2));
+        record("font initialization", Duration::from_millis(1));
+
+        let profile = PROFILE.lock().unwrap().take().unwrap();
+        assert_eq!(
+            profile.phases,
+            vec![
+                ("font initialization", Duration::from_millis(6)),
+                ("window creation", Duration::from_millis(2)),
+            ]
+        );
+        assert_eq!(
+            profile.report().lines().nth(1),
+            Some("  font initialization          6.0ms")
+        );
+    }
+}
@@ -4,7 +4,7 @@ use config::{ConfigHandle, TabBarColors};
 use mlua::FromLua;
 use termwiz::cell::unicode_column_width;
 use termwiz::cell::{Cell, CellAttributes};
-use termwiz::color::ColorSpec;
+use termwiz::color::{ColorSpec, RgbColor};
 use termwiz::escape::csi::Sgr;
 use termwiz::escape::parser::Parser;
 use termwiz::escape::{Action, ControlCode, CSI};
@@ -124,6 +124,14 @@ fn compute_tab_title(
                         pane.title
                     );
                 }
+                // Flag tabs with panes that alerted, using the same
+                // markers as tmux where it has an equivalent
+                match tab.watch_alert.as_deref() {
+                    Some("Activity") => title.insert(0, '#'),
+                    Some("Silence") => title.insert(0, '~'),
+                    Some("CommandCompleted") => title.insert(0, '+'),
+                    _ => {}
+                }
                 // We have a preferred soft minimum on tab width to make it
                 // easier to click on tab titles, but we'll still go below
                 // this if there are too many tabs to fit the window at
@@ -162,6 +170,15 @@ impl TabBarState {
         &self.line
     }
 
+    /// Returns the first column and the width, in cells, of the
+    /// tab with the specified index, if it is shown
+    pub fn tab_extent(&self, tab_idx: usize) -> Option<(usize, usize)> {
+        self.items.iter().find_map(|entry| match entry.item {
+            TabBarItem::Tab(idx) if idx == tab_idx => Some((entry.x, entry.width)),
+            _ => None,
+        })
+    }
+
     /// Build a new tab bar from the current state
     /// mouse_x is some if the mouse is on the same row as the tab bar.
     /// title_width is the total number of cell columns in the window.
@@ -248,18 +265,25 @@ impl TabBarState {
                 tab_title_len,
             );
 
-            let cell_attrs = if active {
-                &active_cell_attrs
+            let mut cell_attrs = if active {
+                active_cell_attrs.clone()
             } else if hover {
-                &inactive_hover_attrs
+                inactive_hover_attrs.clone()
             } else {
-                &inactive_cell_attrs
+                inactive_cell_attrs.clone()
             };
+            if let Some(color) = tab_info[tab_idx]
+                .tab_color
+                .as_deref()
+                .and_then(RgbColor::from_rgb_str)
+            {
+                cell_attrs.set_background(ColorSpec::TrueColor(color));
+            }
 
             let tab_start_idx = x;
 
             let esc = format_as_escapes(tab_title.items.clone()).expect("already parsed ok above");
-            let cells = parse_status_text(&esc, cell_attrs.clone());
+            let cells = parse_status_text(&esc, cell_attrs);
             let mut n = 0;
             for cell in cells {
                 let len = cell.width();
@@ -331,7 +355,7 @@ impl TabBarState {
     }
 }
 
-fn parse_status_text(text: &str, default_cell: CellAttributes) -> Vec<Cell> {
+pub fn parse_status_text(text: &str, default_cell: CellAttributes) -> Vec<Cell> {
     let mut pen = default_cell.clone();
     let mut cells = vec![];
     let mut ignoring = false;
@@ -351,6 +375,7 @@ fn parse_status_text(text: &str, default_cell: CellAttributes) -> Vec<Cell> {
         }
         match action {
             Action::Print(c) => print_buffer.push(c),
+            Action::PrintString(s) => print_buffer.push_str(&s),
             Action::Control(c) => {
                 flush_print(&mut print_buffer, &mut cells, &pen);
                 match c {
@@ -416,7 +441,8 @@ fn parse_status_text(text: &str, default_cell: CellAttributes) -> Vec<Cell> {
             | Action::Esc(_)
             | Action::KittyImage(_)
             | Action::XtGetTcap(_)
-            | Action::Sixel(_) => {
+            | Action::Sixel(_)
+            | Action::SoftFont(_) => {
                 flush_print(&mut print_buffer, &mut cells, &pen);
             }
         }
@@ -0,0 +1,553 @@
+//! Describes the window to screen readers: the tab bar and the panes
+//! of the active tab, or the overlay that is shown in their place, and
+//! announces changes to them as configured by `screen_reader_verbosity`.
+use super::{TabInformation, TermWindowNotif, UIItemType};
+use ::window::{
+    AccessibilityNode, AccessibilityRole, Connection, ConnectionOps, Point, Rect, Size, Window,
+    WindowOps,
+};
+use config::ScreenReaderVerbosity;
+use mux::pane::{Pane, PaneId};
+use mux::tab::{PositionedPane, TabId};
+use mux::Mux;
+use smol::Timer;
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use termwiz::surface::SequenceNo;
+use wezterm_term::StableRowIndex;
+
+/// The ids of the elements of the tree; the ids of tabs and panes
+/// combine the kind of element with the id of the tab or pane
+const WINDOW_NODE: u64 = 0;
+const TAB_LIST_NODE: u64 = 1;
+const TAB_NODE: u64 = 1 << 56;
+const PANE_NODE: u64 = 2 << 56;
+const NODE_KIND_MASK: u64 = 0xff << 56;
+
+/// The minimum time between announcements; anything that happens
+/// in the meantime is announced together, so that a screen reader
+/// isn't interrupted by every small update of the output
+const ANNOUNCE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The tree that was most recently given to the window, and how
+/// much of the output of the active pane has been announced
+#[derive(Default)]
+pub struct AccessibilityState {
+    tree: Option<AccessibilityNode>,
+    inputs: Option<TreeInputs>,
+    output: Option<AnnouncedOutput>,
+    pending: PendingAnnouncements,
+    last_announcement: Option<Instant>,
+    announcement_scheduled: bool,
+}
+
+/// The state of the window that the tree is computed from; the
+/// tree is only rebuilt, and the output of the active pane only
+/// examined, when this changes
+#[derive(PartialEq)]
+struct TreeInputs {
+    size: (usize, usize),
+    tab_bar: Option<Rect>,
+    active_tab: usize,
+    /// The id of each tab along with the title of its active pane
+    tabs: Vec<(TabId, String)>,
+    panes: Vec<PaneInputs>,
+}
+
+#[derive(PartialEq)]
+struct PaneInputs {
+    pane_id: PaneId,
+    seqno: SequenceNo,
+    is_active: bool,
+    /// The position and size of the pane, in cells
+    position: (usize, usize, usize, usize),
+    viewport: Option<StableRowIndex>,
+    title: String,
+    select_label: Option<String>,
+}
+
+/// What will be announced once `ANNOUNCE_INTERVAL` has elapsed
+/// since the last announcement
+#[derive(Default)]
+struct PendingAnnouncements {
+    /// Only the latest change of focus is of interest
+    focus: Option<String>,
+    output: VecDeque<String>,
+}
+
+impl PendingAnnouncements {
+    fn is_empty(&self) -> bool {
+        self.focus.is_none() && self.output.is_empty()
+    }
+
+    /// Adds `output`, keeping at most `max_lines` of the output
+    /// that is pending
+    fn push_output(&mut self, output: &str, max_lines: usize) {
+        self.output.extend(output.lines().map(String::from));
+        while self.output.len() > max_lines {
+            self.output.pop_front();
+        }
+    }
+
+    fn take(&mut self) -> String {
+        let lines: Vec<String> = self
+            .focus
+            .take()
+            .into_iter()
+            .chain(self.output.drain(..))
+            .collect();
+        lines.join("\n")
+    }
+}
+
+struct AnnouncedOutput {
+    pane_id: PaneId,
+    /// The row that the cursor was on
+    row: StableRowIndex,
+    /// The part of that row that has been announced
+    text: String,
+}
+
+/// Returns the text of `rows` of `pane`, without trailing whitespace
+fn row_text(pane: &Rc<dyn Pane>, rows: Range<StableRowIndex>) -> Vec<String> {
+    let (_, lines) = pane.get_lines(rows);
+    lines
+        .iter()
+        .map(|line| line.as_str().trim_end().to_string())
+        .collect()
+}
+
+/// Returns the output to announce, given the text of the rows from
+/// the one that was announced last up to the one that the cursor is
+/// on, and the part of the first of them that was already announced.
+/// The row that the cursor is on is only included if
+/// `include_cursor_row` is true, as it may not be complete yet.
+fn output_to_announce(rows: &[String], announced: &str, include_cursor_row: bool) -> String {
+    let complete = if include_cursor_row {
+        rows.len()
+    } else {
+        rows.len().saturating_sub(1)
+    };
+    let mut lines = vec![];
+    for (idx, row) in rows[..complete].iter().enumerate() {
+        let row = match row.strip_prefix(announced) {
+            Some(remainder) if idx == 0 => remainder,
+            _ => row.as_str(),
+        };
+        let row = row.trim();
+        if !row.is_empty() {
+            lines.push(row);
+        }
+    }
+    lines.join("\n")
+}
+
+/// Returns the active tab of `tree`
+fn selected_tab(tree: &AccessibilityNode) -> Option<&AccessibilityNode> {
+    let mut selected = None;
+    tree.walk(&mut |node| {
+        if node.role == AccessibilityRole::Tab && node.selected {
+            selected = Some(node);
+        }
+    });
+    selected
+}
+
+impl super::TermWindow {
+    /// Gives the window a description of its contents, and announces
+    /// what changed since the last time, if a screen reader is running.
+    /// Called after each frame is painted; the work is only done when
+    /// the tabs, or the panes that are shown, have changed.
+    pub fn update_accessibility(&mut self, window: &Window) {
+        if !Connection::get().unwrap().screen_reader_active() {
+            // Start afresh if a screen reader is started later on
+            self.accessibility = AccessibilityState::default();
+            return;
+        }
+
+        let mux = Mux::get().unwrap();
+        let tabs = self.get_tab_information();
+        let panes = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => self.get_pos_panes_for_tab(&tab),
+            None => vec![],
+        };
+        let inputs = self.accessibility_inputs(&tabs, &panes);
+        if self.accessibility.inputs.as_ref() == Some(&inputs) {
+            return;
+        }
+        self.accessibility.inputs.replace(inputs);
+
+        let tree = self.accessibility_tree(&tabs, &panes);
+        let verbosity = self.config.screen_reader_verbosity;
+        if verbosity != ScreenReaderVerbosity::None {
+            if let Some(focus) = self.focus_announcement(&tree) {
+                self.accessibility.pending.focus.replace(focus);
+            }
+        }
+        if verbosity == ScreenReaderVerbosity::Normal || verbosity == ScreenReaderVerbosity::Full {
+            let include_cursor_row = verbosity == ScreenReaderVerbosity::Full;
+            if let Some((output, max_lines)) = self.output_announcement(include_cursor_row) {
+                self.accessibility.pending.push_output(&output, max_lines);
+            }
+        }
+        self.announce_pending(window);
+
+        if self.accessibility.tree.as_ref() != Some(&tree) {
+            self.accessibility.tree.replace(tree.clone());
+            window.set_accessibility_tree(tree);
+        }
+    }
+
+    /// Announces what is pending, unless something was announced
+    /// less than `ANNOUNCE_INTERVAL` ago, in which case it is
+    /// announced once that has elapsed
+    fn announce_pending(&mut self, window: &Window) {
+        let state = &mut self.accessibility;
+        if state.pending.is_empty() {
+            return;
+        }
+        // The output is followed even while the window doesn't have
+        // the focus, so that it isn't all read out when it regains the
+        // focus, but only the window with the focus speaks
+        if self.focused.is_none() {
+            state.pending = PendingAnnouncements::default();
+            return;
+        }
+
+        let now = Instant::now();
+        if let Some(last) = state.last_announcement {
+            let due = last + ANNOUNCE_INTERVAL;
+            if now < due {
+                if !state.announcement_scheduled {
+                    state.announcement_scheduled = true;
+                    let window = window.clone();
+                    promise::spawn::spawn(async move {
+                        Timer::at(due).await;
+                        let win = window.clone();
+                        window.notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                            tw.accessibility.announcement_scheduled = false;
+                            tw.announce_pending(&win);
+                        })));
+                    })
+                    .detach();
+                }
+                return;
+            }
+        }
+
+        window.announce(&state.pending.take());
+        state.last_announcement.replace(now);
+    }
+
+    /// Returns the state of the window that the tree is built from
+    fn accessibility_inputs(
+        &self,
+        tabs: &[TabInformation],
+        panes: &[PositionedPane],
+    ) -> TreeInputs {
+        TreeInputs {
+            size: (self.dimensions.pixel_width, self.dimensions.pixel_height),
+            tab_bar: self.tab_bar_rect(),
+            active_tab: tabs
+                .iter()
+                .position(|tab| tab.is_active)
+                .unwrap_or(usize::MAX),
+            tabs: tabs
+                .iter()
+                .map(|tab| {
+                    let title = tab
+                        .active_pane
+                        .as_ref()
+                        .map(|pane| pane.title.clone())
+                        .unwrap_or_default();
+                    (tab.tab_id, title)
+                })
+                .collect(),
+            panes: panes
+                .iter()
+                .map(|pos| PaneInputs {
+                    pane_id: pos.pane.pane_id(),
+                    seqno: pos.pane.get_current_seqno(),
+                    is_active: pos.is_active,
+                    position: (pos.left, pos.top, pos.width, pos.height),
+                    viewport: self.get_viewport(pos.pane.pane_id()),
+                    title: pos.pane.get_title(),
+                    select_label: self.pane_select_label(pos).map(|label| label.text),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the area of the window that the tab bar occupies,
+    /// if it is shown
+    fn tab_bar_rect(&self) -> Option<Rect> {
+        self.ui_items
+            .iter()
+            .filter(|_| self.show_tab_bar)
+            .find(|item| item.item_type == UIItemType::TabBar)
+            .map(|item| {
+                Rect::new(
+                    Point::new(item.x as isize, item.y as isize),
+                    Size::new(item.width as isize, item.height as isize),
+                )
+            })
+    }
+
+    /// Returns the announcement for a change of the active tab or
+    /// pane in `tree` relative to the tree that was given last
+    fn focus_announcement(&self, tree: &AccessibilityNode) -> Option<String> {
+        let prior = self.accessibility.tree.as_ref()?;
+        let focused = tree.focused_node()?;
+        if prior.focused_node().map(|node| node.id) == Some(focused.id) {
+            return None;
+        }
+        let tab = selected_tab(tree);
+        if tab.map(|tab| tab.id) != selected_tab(prior).map(|tab| tab.id) {
+            if let Some(tab) = tab {
+                return Some(format!("{}, {}", tab.label, focused.label));
+            }
+        }
+        Some(focused.label.clone())
+    }
+
+    /// Returns the output of the active pane, or overlay, that
+    /// appeared since the last time that this was called, along
+    /// with the number of rows in its viewport
+    fn output_announcement(&mut self, include_cursor_row: bool) -> Option<(String, usize)> {
+        let pane = self.get_active_pane_or_overlay()?;
+        let pane_id = pane.pane_id();
+        let cursor = pane.get_cursor_position();
+        let dims = pane.get_dimensions();
+
+        let output = match self.accessibility.output.take() {
+            // The screen was cleared or redrawn if the cursor moved up,
+            // so don't try to find the output in that case
+            Some(prior) if prior.pane_id == pane_id && prior.row <= cursor.y => {
+                // Don't read more than a screenful when a lot
+                // of output arrives at once
+                let first = prior
+                    .row
+                    .max(cursor.y + 1 - dims.viewport_rows as StableRowIndex);
+                let announced = if first == prior.row {
+                    prior.text
+                } else {
+                    String::new()
+                };
+                let rows = row_text(&pane, first..cursor.y + 1);
+                Some(output_to_announce(&rows, &announced, include_cursor_row))
+            }
+            _ => None,
+        };
+
+        let text = if include_cursor_row {
+            row_text(&pane, cursor.y..cursor.y + 1)
+                .pop()
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        self.accessibility.output.replace(AnnouncedOutput {
+            pane_id,
+            row: cursor.y,
+            text,
+        });
+
+        output
+            .filter(|output| !output.is_empty())
+            .map(|output| (output, dims.viewport_rows))
+    }
+
+    /// Describes the tab bar, and `panes`, which are those of the
+    /// active tab, or the overlay that is shown in their place
+    fn accessibility_tree(
+        &self,
+        tabs: &[TabInformation],
+        panes: &[PositionedPane],
+    ) -> AccessibilityNode {
+        let cell_width = self.render_metrics.cell_size.width as isize;
+        let cell_height = self.render_metrics.cell_size.height as isize;
+        let padding_left = self.config.window_padding.left as isize;
+        let tab_bar = self.tab_bar_rect();
+        // The panes are laid out below the tab bar, unless it is
+        // at the bottom of the window
+        let panes_top = self.config.window_padding.top as isize
+            + match tab_bar {
+                Some(tab_bar) if !self.config.tab_bar_at_bottom => tab_bar.size.height,
+                _ => 0,
+            };
+
+        let active_tab = tabs.iter().find(|tab| tab.is_active);
+        let title = active_tab
+            .and_then(|tab| tab.active_pane.as_ref())
+            .map(|pane| pane.title.clone())
+            .unwrap_or_default();
+        let mut root = AccessibilityNode::new(
+            WINDOW_NODE,
+            AccessibilityRole::Window,
+            title,
+            Rect::new(
+                Point::new(0, 0),
+                Size::new(
+                    self.dimensions.pixel_width as isize,
+                    self.dimensions.pixel_height as isize,
+                ),
+            ),
+        );
+
+        if let Some(tab_bar) = tab_bar {
+            let mut tab_list = AccessibilityNode::new(
+                TAB_LIST_NODE,
+                AccessibilityRole::TabList,
+                "Tabs".to_string(),
+                tab_bar,
+            );
+            for info in tabs {
+                let rect = match self.tab_bar.tab_extent(info.tab_index) {
+                    Some((x, width)) => Rect::new(
+                        Point::new(padding_left + x as isize * cell_width, tab_bar.origin.y),
+                        Size::new(width as isize * cell_width, tab_bar.size.height),
+                    ),
+                    // The tab bar doesn't have room for every tab
+                    None => Rect::new(tab_bar.origin, Size::new(0, 0)),
+                };
+                let title = info
+                    .active_pane
+                    .as_ref()
+                    .map(|pane| pane.title.as_str())
+                    .unwrap_or("");
+                let mut tab = AccessibilityNode::new(
+                    TAB_NODE | info.tab_id as u64,
+                    AccessibilityRole::Tab,
+                    format!("Tab {} of {}: {}", info.tab_index + 1, tabs.len(), title),
+                    rect,
+                );
+                tab.selected = info.is_active;
+                tab_list.children.push(tab);
+            }
+            root.children.push(tab_list);
+        }
+
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return root,
+        };
+        // Overlays are shown in place of the panes of the tab
+        let tab_panes: HashSet<PaneId> = tab
+            .iter_panes()
+            .iter()
+            .map(|pos| pos.pane.pane_id())
+            .collect();
+        let num_panes = panes.len();
+        for pos in panes {
+            let pane_id = pos.pane.pane_id();
+            let is_overlay = !tab_panes.contains(&pane_id);
+            let dims = pos.pane.get_dimensions();
+            let top = self.get_viewport(pane_id).unwrap_or(dims.physical_top);
+
+            let mut label = pos.pane.get_title();
+            if num_panes > 1 && !is_overlay {
+                label = format!("Pane {} of {}: {}", pos.index + 1, num_panes, label);
+            }
+            if let Some(select) = self.pane_select_label(pos) {
+                label = format!("{}, select with {}", label, select.text);
+            }
+            let mut node = AccessibilityNode::new(
+                PANE_NODE | pane_id as u64,
+                if is_overlay {
+                    AccessibilityRole::Dialog
+                } else {
+                    AccessibilityRole::Terminal
+                },
+                label,
+                Rect::new(
+                    Point::new(
+                        padding_left + pos.left as isize * cell_width,
+                        panes_top + pos.top as isize * cell_height,
+                    ),
+                    Size::new(
+                        pos.width as isize * cell_width,
+                        pos.height as isize * cell_height,
+                    ),
+                ),
+            );
+            node.value = row_text(&pos.pane, top..top + dims.viewport_rows as StableRowIndex)
+                .join("\n")
+                .trim_end()
+                .to_string();
+            node.focused = pos.is_active;
+            root.children.push(node);
+        }
+
+        root
+    }
+
+    /// Activates the tab or pane that assistive technology selected
+    pub fn accessibility_activate(&mut self, node_id: u64) -> anyhow::Result<()> {
+        let id = (node_id & !NODE_KIND_MASK) as usize;
+        match node_id & NODE_KIND_MASK {
+            TAB_NODE => {
+                let tab_idx = self
+                    .get_tab_information()
+                    .iter()
+                    .position(|info| info.tab_id == id);
+                if let Some(tab_idx) = tab_idx {
+                    self.activate_tab(tab_idx as isize)?;
+                }
+            }
+            PANE_NODE => {
+                let mux = Mux::get().unwrap();
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    if let Some(pos) = tab.iter_panes().iter().find(|p| p.pane.pane_id() == id) {
+                        tab.set_active_idx(pos.index);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rows(rows: &[&str]) -> Vec<String> {
+        rows.iter().map(|row| row.to_string()).collect()
+    }
+
+    #[test]
+    fn output() {
+        let screen = rows(&["$ ls", "Cargo.toml  src", "$"]);
+        assert_eq!(
+            output_to_announce(&screen, "$", false),
+            "ls\nCargo.toml  src"
+        );
+        assert_eq!(
+            output_to_announce(&screen, "$ ls", true),
+            "Cargo.toml  src\n$"
+        );
+        // The row that was announced last was overwritten
+        assert_eq!(
+            output_to_announce(&screen, "$ cd", false),
+            "$ ls\nCargo.toml  src"
+        );
+        // Only the row that the cursor is on changed
+        assert_eq!(output_to_announce(&rows(&["$ l"]), "$", false), "");
+        assert_eq!(output_to_announce(&rows(&["$ l"]), "$", true), "l");
+    }
+
+    #[test]
+    fn pending() {
+        let mut pending = PendingAnnouncements::default();
+        assert!(pending.is_empty());
+        pending.push_output("one\ntwo", 3);
+        pending.focus.replace("Tab 2 of 2: vim".to_string());
+        pending.push_output("three\nfour", 3);
+        assert_eq!(pending.take(), "Tab 2 of 2: vim\ntwo\nthree\nfour");
+        assert!(pending.is_empty());
+    }
+}
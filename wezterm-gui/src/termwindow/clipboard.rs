@@ -1,11 +1,20 @@
+use crate::overlay::{confirm_upload_files, start_overlay_pane};
+use crate::scripting::guiwin::GuiWin;
+use crate::scripting::pane::PaneObject;
 use crate::termwindow::TermWindowNotif;
 use crate::TermWindow;
 use config::keyassignment::{ClipboardCopyDestination, ClipboardPasteSource};
+use config::{dropped_files_text, RemoteDropPolicy};
+use mlua::FromLua;
+use mux::domain::LocalDomain;
 use mux::pane::Pane;
+use mux::procinfo::process_name_matches;
 use mux::window::WindowId as MuxWindowId;
 use mux::Mux;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
+use wezterm_client::pane::ClientPane;
 use wezterm_term::ClipboardSelection;
 use window::{Clipboard, Window, WindowOps};
 
@@ -66,6 +75,34 @@ impl TermWindow {
         }
     }
 
+    /// Gives the `format-selection` event a chance to transform
+    /// `text`, which was selected in `pane`, before it is copied
+    pub fn format_selection(&self, pane: &Rc<dyn Pane>, text: String) -> String {
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+        match config::run_immediate_with_lua_config(|lua| {
+            let lua = match lua {
+                Some(lua) => lua,
+                None => return Ok(None),
+            };
+            let v = config::lua::emit_sync_callback(
+                &*lua,
+                ("format-selection".to_string(), (window, pane, text.clone())),
+            )?;
+            match v {
+                mlua::Value::Nil => Ok(None),
+                v => Ok(Some(String::from_lua(v, &*lua)?)),
+            }
+        }) {
+            Ok(Some(formatted)) => formatted,
+            Ok(None) => text,
+            Err(err) => {
+                log::warn!("format-selection: {:#}", err);
+                text
+            }
+        }
+    }
+
     pub fn paste_from_clipboard(&mut self, pane: &Rc<dyn Pane>, clipboard: ClipboardPasteSource) {
         let pane_id = pane.pane_id();
         let window = self.window.as_ref().unwrap().clone();
@@ -81,7 +118,8 @@ impl TermWindow {
                         let mux = Mux::get().unwrap();
                         mux.get_pane(pane_id)
                     }) {
-                        pane.trickle_paste(clip).ok();
+                        pane.trickle_paste(myself.normalize_input(&pane, &clip))
+                            .ok();
                     }
                 })));
             }
@@ -89,4 +127,112 @@ impl TermWindow {
         .detach();
         self.maybe_scroll_to_bottom_for_input(&pane);
     }
+
+    /// Pastes the paths of files that were dropped onto the window
+    /// into the active pane
+    pub fn dropped_files(&mut self, paths: Vec<PathBuf>) {
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+
+        let is_local = Mux::get()
+            .and_then(|mux| mux.get_domain(pane.domain_id()))
+            .map(|domain| domain.downcast_ref::<LocalDomain>().is_some())
+            .unwrap_or(false);
+        let upload = !is_local
+            && self.config.dropped_files_remote_policy == RemoteDropPolicy::Upload
+            && pane.downcast_ref::<ClientPane>().is_some();
+        if !is_local
+            && !upload
+            && self.config.dropped_files_remote_policy != RemoteDropPolicy::PastePaths
+        {
+            log::warn!(
+                "Ignoring files dropped onto pane {} because it is in a remote domain; \
+                 see dropped_files_remote_policy",
+                pane.pane_id()
+            );
+            return;
+        }
+
+        let process = pane
+            .get_foreground_process_tree()
+            .map(|proc| proc.executable.to_string_lossy().to_string());
+        let quoting = self
+            .config
+            .dropped_file_quoting(|pattern| match process.as_ref() {
+                Some(process) => process_name_matches(pattern, process),
+                None => false,
+            });
+
+        if upload {
+            let window = self.window.clone().unwrap();
+            let (overlay, future) = start_overlay_pane(self, &pane, move |pane_id, term| {
+                confirm_upload_files(pane_id, term, window, paths, quoting)
+            });
+            self.assign_overlay_for_pane(pane.pane_id(), overlay);
+            promise::spawn::spawn(future).detach();
+            return;
+        }
+
+        // A pane that runs inside WSL can't use Windows paths
+        let in_wsl = cfg!(windows)
+            && is_local
+            && process
+                .as_deref()
+                .map(config::is_wsl_launcher)
+                .unwrap_or(false);
+        let paths = dropped_paths(&paths, in_wsl);
+        let text = self.normalize_input(&pane, &dropped_files_text(quoting, &paths));
+        pane.trickle_paste(text).ok();
+        self.maybe_scroll_to_bottom_for_input(&pane);
+    }
+}
+
+/// Returns the text of the dropped `paths`.  When the pane runs
+/// `in_wsl`, they are translated into the paths by which they are known
+/// inside WSL, such as `/mnt/c/...`; paths that can't be translated are
+/// passed through unchanged.
+fn dropped_paths(paths: &[PathBuf], in_wsl: bool) -> Vec<String> {
+    paths
+        .iter()
+        .map(|path| {
+            let path = path.to_string_lossy().to_string();
+            if in_wsl {
+                config::windows_path_to_wsl(&path).unwrap_or(path)
+            } else {
+                path
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wsl_paths() {
+        let paths = vec![
+            PathBuf::from(r"C:\Users\wez\My Documents\notes.txt"),
+            PathBuf::from(r"\\wsl$\Ubuntu\home\wez\file"),
+            PathBuf::from("relative"),
+        ];
+        assert_eq!(
+            dropped_paths(&paths, true),
+            vec![
+                "/mnt/c/Users/wez/My Documents/notes.txt".to_string(),
+                "/home/wez/file".to_string(),
+                "relative".to_string(),
+            ]
+        );
+        assert_eq!(
+            dropped_paths(&paths, false),
+            vec![
+                r"C:\Users\wez\My Documents\notes.txt".to_string(),
+                r"\\wsl$\Ubuntu\home\wez\file".to_string(),
+                "relative".to_string(),
+            ]
+        );
+    }
 }
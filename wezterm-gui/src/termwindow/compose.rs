@@ -0,0 +1,570 @@
+//! The ComposeCharacter action: the keys typed after it are looked up
+//! in the `compose_sequences` table and the RFC 1345 digraphs, and the
+//! resulting text is sent to the pane.  The InputCodepoint action
+//! instead reads the hex digits of a codepoint.  These work the same
+//! way on every system, regardless of the input method that it provides.
+use super::keymacro::MacroInput;
+use ::window::{KeyCode, KeyEvent, Modifiers, WindowOps};
+use mux::pane::Pane;
+use std::collections::HashMap;
+use std::rc::Rc;
+use termwiz::cell::{unicode_column_width, CellAttributes};
+use wezterm_term::Line;
+
+/// The two character mnemonics from RFC 1345 for the characters
+/// that are most commonly needed; these are also the digraphs used
+/// by vim.
+static DIGRAPHS: &[(&str, char)] = &[
+    // Latin-1 punctuation and symbols
+    ("!I", '¡'),
+    ("Ct", '¢'),
+    ("Pd", '£'),
+    ("Cu", '¤'),
+    ("Ye", '¥'),
+    ("BB", '¦'),
+    ("SE", '§'),
+    ("':", '¨'),
+    ("Co", '©'),
+    ("-a", 'ª'),
+    ("<<", '«'),
+    ("NO", '¬'),
+    ("Rg", '®'),
+    ("'m", '¯'),
+    ("DG", '°'),
+    ("+-", '±'),
+    ("2S", '²'),
+    ("3S", '³'),
+    ("''", '´'),
+    ("My", 'µ'),
+    ("PI", '¶'),
+    (".M", '·'),
+    ("',", '¸'),
+    ("1S", '¹'),
+    ("-o", 'º'),
+    (">>", '»'),
+    ("14", '¼'),
+    ("12", '½'),
+    ("34", '¾'),
+    ("?I", '¿'),
+    ("*X", '×'),
+    ("-:", '÷'),
+    // Latin-1 letters
+    ("A!", 'À'),
+    ("A'", 'Á'),
+    ("A>", 'Â'),
+    ("A?", 'Ã'),
+    ("A:", 'Ä'),
+    ("AA", 'Å'),
+    ("AE", 'Æ'),
+    ("C,", 'Ç'),
+    ("E!", 'È'),
+    ("E'", 'É'),
+    ("E>", 'Ê'),
+    ("E:", 'Ë'),
+    ("I!", 'Ì'),
+    ("I'", 'Í'),
+    ("I>", 'Î'),
+    ("I:", 'Ï'),
+    ("D-", 'Ð'),
+    ("N?", 'Ñ'),
+    ("O!", 'Ò'),
+    ("O'", 'Ó'),
+    ("O>", 'Ô'),
+    ("O?", 'Õ'),
+    ("O:", 'Ö'),
+    ("O/", 'Ø'),
+    ("U!", 'Ù'),
+    ("U'", 'Ú'),
+    ("U>", 'Û'),
+    ("U:", 'Ü'),
+    ("Y'", 'Ý'),
+    ("TH", 'Þ'),
+    ("ss", 'ß'),
+    ("a!", 'à'),
+    ("a'", 'á'),
+    ("a>", 'â'),
+    ("a?", 'ã'),
+    ("a:", 'ä'),
+    ("aa", 'å'),
+    ("ae", 'æ'),
+    ("c,", 'ç'),
+    ("e!", 'è'),
+    ("e'", 'é'),
+    ("e>", 'ê'),
+    ("e:", 'ë'),
+    ("i!", 'ì'),
+    ("i'", 'í'),
+    ("i>", 'î'),
+    ("i:", 'ï'),
+    ("d-", 'ð'),
+    ("n?", 'ñ'),
+    ("o!", 'ò'),
+    ("o'", 'ó'),
+    ("o>", 'ô'),
+    ("o?", 'õ'),
+    ("o:", 'ö'),
+    ("o/", 'ø'),
+    ("u!", 'ù'),
+    ("u'", 'ú'),
+    ("u>", 'û'),
+    ("u:", 'ü'),
+    ("y'", 'ý'),
+    ("th", 'þ'),
+    ("y:", 'ÿ'),
+    // Latin Extended-A
+    ("A-", 'Ā'),
+    ("a-", 'ā'),
+    ("A(", 'Ă'),
+    ("a(", 'ă'),
+    ("A;", 'Ą'),
+    ("a;", 'ą'),
+    ("C'", 'Ć'),
+    ("c'", 'ć'),
+    ("C<", 'Č'),
+    ("c<", 'č'),
+    ("D<", 'Ď'),
+    ("d<", 'ď'),
+    ("D/", 'Đ'),
+    ("d/", 'đ'),
+    ("E-", 'Ē'),
+    ("e-", 'ē'),
+    ("E.", 'Ė'),
+    ("e.", 'ė'),
+    ("E;", 'Ę'),
+    ("e;", 'ę'),
+    ("E<", 'Ě'),
+    ("e<", 'ě'),
+    ("G(", 'Ğ'),
+    ("g(", 'ğ'),
+    ("I-", 'Ī'),
+    ("i-", 'ī'),
+    ("I.", 'İ'),
+    ("i.", 'ı'),
+    ("L/", 'Ł'),
+    ("l/", 'ł'),
+    ("N'", 'Ń'),
+    ("n'", 'ń'),
+    ("N<", 'Ň'),
+    ("n<", 'ň'),
+    ("O\"", 'Ő'),
+    ("o\"", 'ő'),
+    ("OE", 'Œ'),
+    ("oe", 'œ'),
+    ("R<", 'Ř'),
+    ("r<", 'ř'),
+    ("S'", 'Ś'),
+    ("s'", 'ś'),
+    ("S,", 'Ş'),
+    ("s,", 'ş'),
+    ("S<", 'Š'),
+    ("s<", 'š'),
+    ("T<", 'Ť'),
+    ("t<", 'ť'),
+    ("U-", 'Ū'),
+    ("u-", 'ū'),
+    ("U0", 'Ů'),
+    ("u0", 'ů'),
+    ("U\"", 'Ű'),
+    ("u\"", 'ű'),
+    ("Z'", 'Ź'),
+    ("z'", 'ź'),
+    ("Z.", 'Ż'),
+    ("z.", 'ż'),
+    ("Z<", 'Ž'),
+    ("z<", 'ž'),
+    // Greek
+    ("A*", 'Α'),
+    ("B*", 'Β'),
+    ("G*", 'Γ'),
+    ("D*", 'Δ'),
+    ("E*", 'Ε'),
+    ("Z*", 'Ζ'),
+    ("Y*", 'Η'),
+    ("H*", 'Θ'),
+    ("I*", 'Ι'),
+    ("K*", 'Κ'),
+    ("L*", 'Λ'),
+    ("M*", 'Μ'),
+    ("N*", 'Ν'),
+    ("C*", 'Ξ'),
+    ("O*", 'Ο'),
+    ("P*", 'Π'),
+    ("R*", 'Ρ'),
+    ("S*", 'Σ'),
+    ("T*", 'Τ'),
+    ("U*", 'Υ'),
+    ("F*", 'Φ'),
+    ("X*", 'Χ'),
+    ("Q*", 'Ψ'),
+    ("W*", 'Ω'),
+    ("a*", 'α'),
+    ("b*", 'β'),
+    ("g*", 'γ'),
+    ("d*", 'δ'),
+    ("e*", 'ε'),
+    ("z*", 'ζ'),
+    ("y*", 'η'),
+    ("h*", 'θ'),
+    ("i*", 'ι'),
+    ("k*", 'κ'),
+    ("l*", 'λ'),
+    ("m*", 'μ'),
+    ("n*", 'ν'),
+    ("c*", 'ξ'),
+    ("o*", 'ο'),
+    ("p*", 'π'),
+    ("r*", 'ρ'),
+    ("*s", 'ς'),
+    ("s*", 'σ'),
+    ("t*", 'τ'),
+    ("u*", 'υ'),
+    ("f*", 'φ'),
+    ("x*", 'χ'),
+    ("q*", 'ψ'),
+    ("w*", 'ω'),
+    // Punctuation
+    ("-N", '–'),
+    ("-M", '—'),
+    ("'6", '‘'),
+    ("'9", '’'),
+    (".9", '‚'),
+    ("\"6", '“'),
+    ("\"9", '”'),
+    (":9", '„'),
+    ("/-", '†'),
+    ("/=", '‡'),
+    ("..", '‥'),
+    (",.", '…'),
+    ("%0", '‰'),
+    ("Eu", '€'),
+    ("oC", '℃'),
+    ("TM", '™'),
+    // Arrows
+    ("<-", '←'),
+    ("-!", '↑'),
+    ("->", '→'),
+    ("-v", '↓'),
+    ("<>", '↔'),
+    ("UD", '↕'),
+    ("=>", '⇒'),
+    ("==", '⇔'),
+    // Mathematical operators
+    ("FA", '∀'),
+    ("dP", '∂'),
+    ("TE", '∃'),
+    ("/0", '∅'),
+    ("DE", '∆'),
+    ("NB", '∇'),
+    ("(-", '∈'),
+    ("-)", '∋'),
+    ("*P", '∏'),
+    ("+Z", '∑'),
+    ("-2", '−'),
+    ("RT", '√'),
+    ("00", '∞'),
+    ("AN", '∧'),
+    ("OR", '∨'),
+    ("(U", '∩'),
+    (")U", '∪'),
+    ("In", '∫'),
+    (".:", '∴'),
+    ("?1", '∼'),
+    ("?=", '≅'),
+    ("?2", '≈'),
+    ("!=", '≠'),
+    ("=3", '≡'),
+    ("=<", '≤'),
+    (">=", '≥'),
+    ("(C", '⊂'),
+    (")C", '⊃'),
+    ("(_", '⊆'),
+    (")_", '⊇'),
+    // Dingbats
+    ("OK", '✓'),
+    ("XX", '✗'),
+];
+
+fn digraph(typed: &str) -> Option<char> {
+    DIGRAPHS
+        .iter()
+        .find(|(mnemonic, _)| *mnemonic == typed)
+        .map(|(_, c)| *c)
+}
+
+/// The outcome of looking up the keys typed so far
+#[derive(Debug, PartialEq)]
+pub enum ComposeLookup {
+    /// The sequence is complete and produces this text
+    Complete(String),
+    /// More keys may follow.  If the sequence is ended early, by
+    /// pressing Enter, it produces the text that it matches, if any.
+    Pending(Option<String>),
+    /// No sequence starts with the keys typed so far
+    Invalid,
+}
+
+pub fn lookup_compose(
+    typed: &str,
+    sequences: &HashMap<String, String>,
+    digraphs: bool,
+) -> ComposeLookup {
+    let exact = sequences
+        .get(typed)
+        .cloned()
+        .or_else(|| digraph(typed).filter(|_| digraphs).map(String::from));
+    let longer = sequences
+        .keys()
+        .any(|seq| seq.len() > typed.len() && seq.starts_with(typed));
+
+    let num_chars = typed.chars().count();
+    if digraphs && num_chars == 1 && exact.is_none() && !longer {
+        // Either order of the two characters of a digraph is accepted,
+        // so wait for the second if this appears in any position
+        let c = typed.chars().next().unwrap();
+        if DIGRAPHS.iter().any(|(mnemonic, _)| mnemonic.contains(c)) {
+            return ComposeLookup::Pending(None);
+        }
+    }
+
+    match exact {
+        Some(text) if !longer => ComposeLookup::Complete(text),
+        exact if longer => ComposeLookup::Pending(exact),
+        _ if digraphs && num_chars == 2 => {
+            let reversed: String = typed.chars().rev().collect();
+            match digraph(&reversed) {
+                Some(c) => ComposeLookup::Complete(c.to_string()),
+                None => ComposeLookup::Invalid,
+            }
+        }
+        _ => ComposeLookup::Invalid,
+    }
+}
+
+/// Returns the character whose codepoint is the hexadecimal `typed`
+pub fn parse_codepoint(typed: &str) -> Option<char> {
+    u32::from_str_radix(typed, 16)
+        .ok()
+        .and_then(std::char::from_u32)
+}
+
+/// The most hex digits that a codepoint can have
+const MAX_CODEPOINT_DIGITS: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComposeMode {
+    /// Started by ComposeCharacter; the keys are looked up in the
+    /// compose sequences and digraphs
+    Sequence,
+    /// Started by InputCodepoint; the keys are the hex digits of a
+    /// codepoint, which is sent when Space or Enter is pressed
+    Codepoint,
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingCompose {
+    pub mode: ComposeMode,
+    pub typed: String,
+}
+
+/// Returns a copy of `line` with the pending compose input drawn
+/// over it, starting at the cursor position `x`
+pub fn overlay_compose_text(line: &Line, x: usize, pending: &PendingCompose, cols: usize) -> Line {
+    let text = match pending.mode {
+        ComposeMode::Sequence => format!("⎄{}", pending.typed),
+        ComposeMode::Codepoint => format!("u{}", pending.typed),
+    };
+    let mut line = line.clone();
+    line.resize(cols, line.current_seqno());
+    let start = x.min(cols.saturating_sub(unicode_column_width(&text)));
+    let mut attrs = CellAttributes::default();
+    attrs.set_reverse(true);
+    line.overlay_text_with_attribute(start, &text, attrs, line.current_seqno());
+    line
+}
+
+impl super::TermWindow {
+    pub fn start_compose(&mut self, mode: ComposeMode) {
+        self.compose.replace(PendingCompose {
+            mode,
+            typed: String::new(),
+        });
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Returns the keys typed since ComposeCharacter or InputCodepoint,
+    /// if a character is being composed
+    pub fn pending_compose(&self) -> Option<&PendingCompose> {
+        self.compose.as_ref()
+    }
+
+    /// Handles `key` while a character is being composed, sending the
+    /// resulting text to `pane` once it is complete.  Returns false
+    /// if no character is being composed.
+    pub fn compose_key(&mut self, pane: &Rc<dyn Pane>, key: &KeyEvent) -> bool {
+        let mut pending = match self.compose.take() {
+            Some(pending) => pending,
+            None => return false,
+        };
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+
+        match &key.key {
+            key if key.is_modifier() => {}
+            KeyCode::Char('\u{8}') | KeyCode::Char('\u{7f}') => {
+                pending.typed.pop();
+            }
+            KeyCode::Char('\r') => {
+                self.finish_compose(pane, &pending);
+                return true;
+            }
+            KeyCode::Char(' ') if pending.mode == ComposeMode::Codepoint => {
+                self.finish_compose(pane, &pending);
+                return true;
+            }
+            KeyCode::Char(c)
+                if !c.is_control()
+                    && !key.modifiers.intersects(Modifiers::CTRL | Modifiers::SUPER) =>
+            {
+                match pending.mode {
+                    ComposeMode::Sequence => {
+                        pending.typed.push(*c);
+                        match self.lookup_compose(&pending.typed) {
+                            ComposeLookup::Complete(text) => {
+                                self.send_composed(pane, &text);
+                                return true;
+                            }
+                            ComposeLookup::Pending(_) => {}
+                            ComposeLookup::Invalid => {
+                                log::debug!("compose: no sequence starts with {:?}", pending.typed);
+                                return true;
+                            }
+                        }
+                    }
+                    ComposeMode::Codepoint => {
+                        if !c.is_ascii_hexdigit() {
+                            return true;
+                        }
+                        if pending.typed.len() < MAX_CODEPOINT_DIGITS {
+                            pending.typed.push(*c);
+                        }
+                    }
+                }
+            }
+            // Escape, and any other key, cancels
+            _ => return true,
+        }
+
+        self.compose.replace(pending);
+        true
+    }
+
+    /// Sends the text for the keys typed so far, if they are valid
+    fn finish_compose(&mut self, pane: &Rc<dyn Pane>, pending: &PendingCompose) {
+        let text = match pending.mode {
+            ComposeMode::Sequence => match self.lookup_compose(&pending.typed) {
+                ComposeLookup::Pending(text) => text,
+                _ => None,
+            },
+            ComposeMode::Codepoint => parse_codepoint(&pending.typed).map(String::from),
+        };
+        match text {
+            Some(text) => self.send_composed(pane, &text),
+            None => log::debug!("compose: {:?} is not valid", pending.typed),
+        }
+    }
+
+    fn lookup_compose(&self, typed: &str) -> ComposeLookup {
+        lookup_compose(
+            typed,
+            &self.config.compose_sequences,
+            self.config.enable_rfc1345_digraphs,
+        )
+    }
+
+    fn send_composed(&mut self, pane: &Rc<dyn Pane>, text: &str) {
+        let text = self.normalize_input(pane, text);
+        pane.writer().write_all(text.as_bytes()).ok();
+        self.record_key_macro_input(MacroInput::Text(text));
+        self.maybe_scroll_to_bottom_for_input(pane);
+        self.expect_key_echo(pane);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn digraphs() {
+        let sequences = HashMap::new();
+        assert_eq!(
+            lookup_compose("e", &sequences, true),
+            ComposeLookup::Pending(None)
+        );
+        assert_eq!(
+            lookup_compose("e'", &sequences, true),
+            ComposeLookup::Complete("é".to_string())
+        );
+        // Either order is accepted
+        assert_eq!(
+            lookup_compose(":o", &sequences, true),
+            ComposeLookup::Complete("ö".to_string())
+        );
+        assert_eq!(
+            lookup_compose("a*", &sequences, true),
+            ComposeLookup::Complete("α".to_string())
+        );
+        assert_eq!(
+            lookup_compose("e'", &sequences, false),
+            ComposeLookup::Invalid
+        );
+        assert_eq!(
+            lookup_compose("#", &sequences, true),
+            ComposeLookup::Invalid
+        );
+        assert_eq!(
+            lookup_compose("qq", &sequences, true),
+            ComposeLookup::Invalid
+        );
+    }
+
+    #[test]
+    fn custom_sequences() {
+        let mut sequences = HashMap::new();
+        sequences.insert("sh".to_string(), "🤷".to_string());
+        sequences.insert("shrug".to_string(), "¯\\_(ツ)_/¯".to_string());
+        sequences.insert("e'".to_string(), "ė".to_string());
+
+        assert_eq!(
+            lookup_compose("shr", &sequences, true),
+            ComposeLookup::Pending(None)
+        );
+        assert_eq!(
+            lookup_compose("sh", &sequences, true),
+            ComposeLookup::Pending(Some("🤷".to_string()))
+        );
+        assert_eq!(
+            lookup_compose("shrug", &sequences, true),
+            ComposeLookup::Complete("¯\\_(ツ)_/¯".to_string())
+        );
+        // These take precedence over the digraphs
+        assert_eq!(
+            lookup_compose("e'", &sequences, true),
+            ComposeLookup::Complete("ė".to_string())
+        );
+    }
+
+    #[test]
+    fn codepoints() {
+        assert_eq!(parse_codepoint("e9"), Some('é'));
+        assert_eq!(parse_codepoint("1F600"), Some('😀'));
+        assert_eq!(parse_codepoint("10ffff"), Some('\u{10ffff}'));
+        // Surrogates and values beyond the unicode range are invalid
+        assert_eq!(parse_codepoint("d800"), None);
+        assert_eq!(parse_codepoint("110000"), None);
+        assert_eq!(parse_codepoint(""), None);
+    }
+}
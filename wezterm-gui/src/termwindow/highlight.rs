@@ -0,0 +1,544 @@
+use crate::overlay::SearchOverlay;
+use crate::selection::{SelectionCoordinate, SelectionRange};
+use crate::termwindow::TermWindowNotif;
+use ::window::WindowOps;
+use luahelper::impl_lua_conversion;
+use mux::pane::{Pane, PaneId, Pattern, SearchResult};
+use serde::{Deserialize, Serialize};
+use smol::Timer;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use termwiz::cell::Underline;
+use termwiz::color::{AnsiColor, ColorSpec, RgbColor};
+use termwiz::surface::SequenceNo;
+use wezterm_term::{Line, StableRowIndex};
+
+/// While a pane produces output, its highlighted search is run again
+/// at most this often
+const SEARCH_HIGHLIGHT_REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The matches of a search that remain highlighted in a pane
+/// after the search overlay has been dismissed, until they are
+/// cleared by the ClearSearchHighlight action
+#[derive(Clone)]
+pub struct SearchHighlight {
+    pattern: Pattern,
+    /// The matches, in the order in which they appear in the pane
+    results: Vec<SearchResult>,
+    /// The index into `results` of the current match
+    active: Option<usize>,
+    /// The seqno of the pane at the time that `results` were computed
+    seqno: SequenceNo,
+    /// When the most recent search was started
+    searched_at: Instant,
+    /// Whether a search is in progress
+    searching: bool,
+    /// Whether a repaint has been scheduled to run the search again
+    refresh_scheduled: bool,
+}
+
+/// When the matches of a search highlight should be searched for again
+enum Refresh {
+    Now,
+    At(Instant),
+}
+
+/// A region of a pane, in the same terms as a search result:
+/// `end_x` is exclusive, while `end_y` is inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct HighlightRange {
+    pub start_y: StableRowIndex,
+    #[serde(default)]
+    pub start_x: usize,
+    pub end_y: StableRowIndex,
+    #[serde(default = "default_end_x")]
+    pub end_x: usize,
+}
+impl_lua_conversion!(HighlightRange);
+
+fn default_end_x() -> usize {
+    usize::max_value()
+}
+
+/// How a highlight is styled, as specified by lua code
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct HighlightStyle {
+    #[serde(default)]
+    pub foreground: Option<String>,
+    #[serde(default)]
+    pub background: Option<String>,
+    #[serde(default)]
+    pub underline: Option<Underline>,
+    #[serde(default)]
+    pub underline_color: Option<String>,
+}
+impl_lua_conversion!(HighlightStyle);
+
+/// Styling that lua code has overlaid on a region of a pane,
+/// without changing the cells themselves
+#[derive(Debug, Clone)]
+pub struct PaneHighlight {
+    pub id: usize,
+    range: HighlightRange,
+    foreground: Option<RgbColor>,
+    background: Option<RgbColor>,
+    underline: Option<Underline>,
+    underline_color: Option<RgbColor>,
+}
+
+impl PaneHighlight {
+    /// Resolves the colors of `style`, allocating a new id
+    pub fn new(range: HighlightRange, style: &HighlightStyle) -> anyhow::Result<Self> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+        fn color(color: &Option<String>) -> anyhow::Result<Option<RgbColor>> {
+            match color {
+                Some(color) => Ok(Some(
+                    RgbColor::from_named_or_rgb_string(color)
+                        .ok_or_else(|| anyhow::anyhow!("invalid color {}", color))?,
+                )),
+                None => Ok(None),
+            }
+        }
+        Ok(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            range,
+            foreground: color(&style.foreground)?,
+            background: color(&style.background)?,
+            underline: style.underline,
+            underline_color: color(&style.underline_color)?,
+        })
+    }
+
+    pub fn range(&self) -> &HighlightRange {
+        &self.range
+    }
+}
+
+/// Applies `highlights`, which are ordered by their first row,
+/// to `lines`, the first of which is the row `top` of the pane
+fn apply_pane_highlights(highlights: &[PaneHighlight], top: StableRowIndex, lines: &mut [Line]) {
+    let bottom = top + lines.len() as StableRowIndex;
+    for highlight in highlights {
+        let range = &highlight.range;
+        if range.start_y >= bottom {
+            break;
+        }
+        if range.end_y < top {
+            continue;
+        }
+        for row in range.start_y.max(top)..=range.end_y.min(bottom - 1) {
+            let line = &mut lines[(row - top) as usize];
+            let start = if row == range.start_y {
+                range.start_x
+            } else {
+                0
+            };
+            let end = if row == range.end_y {
+                range.end_x
+            } else {
+                usize::max_value()
+            };
+            for cell in line
+                .cells_mut_for_attr_changes_only()
+                .iter_mut()
+                .take(end)
+                .skip(start)
+            {
+                let attrs = cell.attrs_mut();
+                if let Some(color) = highlight.foreground {
+                    attrs.set_foreground(ColorSpec::TrueColor(color));
+                }
+                if let Some(color) = highlight.background {
+                    attrs.set_background(ColorSpec::TrueColor(color));
+                }
+                if let Some(underline) = highlight.underline {
+                    attrs.set_underline(underline);
+                }
+                if let Some(color) = highlight.underline_color {
+                    attrs.set_underline_color(ColorSpec::TrueColor(color));
+                }
+            }
+        }
+    }
+}
+
+impl super::TermWindow {
+    pub fn add_pane_highlight(&mut self, pane_id: PaneId, highlight: PaneHighlight) {
+        {
+            let mut state = self.pane_state(pane_id);
+            let idx = state
+                .highlights
+                .iter()
+                .position(|h| h.range.start_y > highlight.range.start_y)
+                .unwrap_or(state.highlights.len());
+            state.highlights.insert(idx, highlight);
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Removes the highlight `id` from `pane_id`, or all of its
+    /// highlights if `id` is None
+    pub fn remove_pane_highlight(&mut self, pane_id: PaneId, id: Option<usize>) {
+        match id {
+            Some(id) => self.pane_state(pane_id).highlights.retain(|h| h.id != id),
+            None => self.pane_state(pane_id).highlights.clear(),
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Applies the highlights that lua code added to `pane` to `lines`,
+    /// the first of which is the row `top` of the pane
+    pub fn apply_pane_highlights(
+        &self,
+        pane: &Rc<dyn Pane>,
+        top: StableRowIndex,
+        lines: &mut [Line],
+    ) {
+        let state = self.pane_state(pane.pane_id());
+        // Overlays render their own content
+        if state.overlay.is_none() {
+            apply_pane_highlights(&state.highlights, top, lines);
+            if let Some(input) = state.prompt_input.as_ref() {
+                apply_pane_highlights(&input.highlights, top, lines);
+            }
+        }
+    }
+
+    /// Highlights the matches for `pattern` in `pane`.
+    /// If `active` is one of the matches, it is made the current match.
+    pub fn highlight_search_matches(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        pattern: Pattern,
+        active: Option<SearchResult>,
+    ) {
+        self.pane_state(pane.pane_id()).search_highlight = Some(SearchHighlight {
+            pattern,
+            results: vec![],
+            active: None,
+            seqno: pane.get_current_seqno(),
+            searched_at: Instant::now(),
+            searching: false,
+            refresh_scheduled: false,
+        });
+        self.update_search_highlight(pane, active);
+    }
+
+    pub fn clear_search_highlight(&mut self, pane_id: PaneId) {
+        if self.pane_state(pane_id).search_highlight.take().is_some() {
+            if let Some(window) = self.window.as_ref() {
+                window.invalidate();
+            }
+        }
+    }
+
+    /// Remembers `pattern` as the most recent search in `pane_id`, so
+    /// that its matches can be highlighted after the overlay is closed
+    pub fn set_last_search(&mut self, pane_id: PaneId, pattern: Pattern) {
+        self.pane_state(pane_id).last_search = Some(pattern);
+    }
+
+    pub fn last_search(&self, pane_id: PaneId) -> Option<Pattern> {
+        self.pane_state(pane_id).last_search.clone()
+    }
+
+    /// Remembers the type of `pattern` and the whole word option so
+    /// that they can be used for the next search
+    pub fn remember_search_mode(&mut self, mut pattern: Pattern, whole_word: bool) {
+        pattern.clear();
+        self.search_mode = Some((pattern, whole_word));
+    }
+
+    /// Opens the search overlay for the active pane.  If `pattern` is
+    /// empty, the type of pattern from the most recent search is used,
+    /// along with its whole word option unless `whole_word` is specified.
+    pub fn show_search(&mut self, pattern: &Pattern, whole_word: Option<bool>) {
+        let pane = match self.get_active_pane_no_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        self.clear_search_highlight(pane.pane_id());
+
+        let (pattern, whole_word) = match &self.search_mode {
+            Some((mode, remembered)) if pattern.is_empty() => {
+                (mode.clone(), whole_word.unwrap_or(*remembered))
+            }
+            _ => (pattern.clone(), whole_word.unwrap_or(false)),
+        };
+        let search = SearchOverlay::with_pane(self, &pane, pattern, whole_word);
+        self.assign_overlay_for_pane(pane.pane_id(), search);
+    }
+
+    /// Re-runs the highlighted search in the background, so that
+    /// the highlight follows changes to the content of the pane
+    fn update_search_highlight(&self, pane: &Rc<dyn Pane>, active: Option<SearchResult>) {
+        let pane_id = pane.pane_id();
+        let pattern = {
+            let mut state = self.pane_state(pane_id);
+            let highlight = match state.search_highlight.as_mut() {
+                Some(highlight) if !highlight.searching => highlight,
+                _ => return,
+            };
+            highlight.searching = true;
+            highlight.refresh_scheduled = false;
+            highlight.searched_at = Instant::now();
+            highlight.seqno = pane.get_current_seqno();
+            highlight.pattern.clone()
+        };
+
+        let pane = Rc::clone(pane);
+        let window = self.window.clone().unwrap();
+        promise::spawn::spawn(async move {
+            let mut results = match pane.search(pattern.clone()).await {
+                Ok(results) => results,
+                Err(err) => {
+                    log::error!("search highlight in pane {}: {:#}", pane_id, err);
+                    // Allow the search to be tried again later
+                    window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                        let mut state = term_window.pane_state(pane_id);
+                        if let Some(highlight) = state.search_highlight.as_mut() {
+                            if highlight.pattern == pattern {
+                                highlight.searching = false;
+                            }
+                        }
+                    })));
+                    return;
+                }
+            };
+            results.sort();
+
+            window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                {
+                    let mut state = term_window.pane_state(pane_id);
+                    let highlight = match state.search_highlight.as_mut() {
+                        // The highlight may have been replaced while
+                        // we were searching
+                        Some(highlight) if highlight.pattern == pattern => highlight,
+                        _ => return,
+                    };
+                    let active = active.or_else(|| {
+                        highlight
+                            .active
+                            .and_then(|idx| highlight.results.get(idx).cloned())
+                    });
+                    highlight.active = active.and_then(|r| results.binary_search(&r).ok());
+                    highlight.results = results;
+                    highlight.searching = false;
+                }
+                if let Some(window) = term_window.window.as_ref() {
+                    window.invalidate();
+                }
+            })));
+        })
+        .detach();
+    }
+
+    /// Moves to the match `amount` matches away from the current one,
+    /// wrapping around at either end.  Negative values move towards
+    /// the top of the scrollback.  Without a current match, moving
+    /// backwards starts from the bottom and forwards from the top.
+    pub fn scroll_to_search_match(&mut self, pane: &Rc<dyn Pane>, amount: isize) {
+        let pane_id = pane.pane_id();
+        let result = {
+            let mut state = self.pane_state(pane_id);
+            let highlight = match state.search_highlight.as_mut() {
+                Some(highlight) if !highlight.results.is_empty() => highlight,
+                _ => return,
+            };
+            let len = highlight.results.len() as isize;
+            let idx = match highlight.active {
+                Some(idx) => (idx as isize + amount).rem_euclid(len),
+                None if amount < 0 => len - 1,
+                None => 0,
+            } as usize;
+            highlight.active = Some(idx);
+            highlight.results[idx]
+        };
+
+        {
+            let mut selection = self.selection(pane_id);
+            let start = SelectionCoordinate {
+                x: result.start_x,
+                y: result.start_y,
+            };
+            selection.start = Some(start);
+            selection.rectangular = false;
+            selection.range = Some(SelectionRange {
+                start,
+                end: SelectionCoordinate {
+                    // inclusive range for selection, but the result
+                    // range is exclusive
+                    x: result.end_x.saturating_sub(1),
+                    y: result.end_y,
+                },
+            });
+        }
+
+        let dims = pane.get_dimensions();
+        self.set_viewport(pane_id, Some(result.start_y), dims);
+    }
+
+    /// Colors the highlighted matches in `lines`, the first of which
+    /// is the row `top` of `pane`
+    pub fn apply_search_highlight(
+        &self,
+        pane: &Rc<dyn Pane>,
+        top: StableRowIndex,
+        lines: &mut [Line],
+    ) {
+        let refresh = {
+            let mut state = self.pane_state(pane.pane_id());
+            // Overlays render their own content
+            if state.overlay.is_some() {
+                return;
+            }
+            let highlight = match state.search_highlight.as_mut() {
+                Some(highlight) => highlight,
+                None => return,
+            };
+
+            let bottom = top + lines.len() as StableRowIndex;
+            for (idx, result) in highlight.results.iter().enumerate() {
+                if result.end_y < top || result.start_y >= bottom {
+                    continue;
+                }
+                let (bg, fg) = if Some(idx) == highlight.active {
+                    (AnsiColor::Yellow, AnsiColor::Black)
+                } else {
+                    (AnsiColor::Fuschia, AnsiColor::Black)
+                };
+                for row in result.start_y.max(top)..=result.end_y.min(bottom - 1) {
+                    let line = &mut lines[(row - top) as usize];
+                    let start = if row == result.start_y {
+                        result.start_x
+                    } else {
+                        0
+                    };
+                    let end = if row == result.end_y {
+                        result.end_x
+                    } else {
+                        usize::max_value()
+                    };
+                    for cell in line
+                        .cells_mut_for_attr_changes_only()
+                        .iter_mut()
+                        .take(end)
+                        .skip(start)
+                    {
+                        cell.attrs_mut()
+                            .set_background(bg)
+                            .set_foreground(fg)
+                            .set_reverse(false);
+                    }
+                }
+            }
+
+            // The results are kept until the pane has changed, and
+            // are then refreshed no more often than the interval allows
+            if highlight.searching || pane.get_current_seqno() <= highlight.seqno {
+                None
+            } else {
+                let due = highlight.searched_at + SEARCH_HIGHLIGHT_REFRESH_INTERVAL;
+                if Instant::now() >= due {
+                    Some(Refresh::Now)
+                } else if !highlight.refresh_scheduled {
+                    highlight.refresh_scheduled = true;
+                    Some(Refresh::At(due))
+                } else {
+                    None
+                }
+            }
+        };
+
+        match refresh {
+            Some(Refresh::Now) => self.update_search_highlight(pane, None),
+            Some(Refresh::At(due)) => {
+                // Repaint once the interval has passed, in case the
+                // pane produces no further output before then
+                let window = self.window.clone().unwrap();
+                promise::spawn::spawn(async move {
+                    Timer::at(due).await;
+                    window.invalidate();
+                })
+                .detach();
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pane_highlights() {
+        let style = HighlightStyle {
+            background: Some("#ff0000".to_string()),
+            underline: Some(Underline::Curly),
+            ..Default::default()
+        };
+        let highlights = vec![
+            PaneHighlight::new(
+                HighlightRange {
+                    start_y: 10,
+                    start_x: 2,
+                    end_y: 11,
+                    end_x: 1,
+                },
+                &style,
+            )
+            .unwrap(),
+            PaneHighlight::new(
+                HighlightRange {
+                    start_y: 20,
+                    start_x: 0,
+                    end_y: 20,
+                    end_x: 4,
+                },
+                &style,
+            )
+            .unwrap(),
+        ];
+
+        let mut lines: Vec<Line> = vec!["abcd".into(), "efgh".into(), "ijkl".into()];
+        apply_pane_highlights(&highlights, 10, &mut lines);
+
+        let styled = |line: &Line| -> Vec<bool> {
+            line.cells()
+                .iter()
+                .map(|cell| cell.attrs().underline() == Underline::Curly)
+                .collect()
+        };
+        assert_eq!(styled(&lines[0]), vec![false, false, true, true]);
+        assert_eq!(styled(&lines[1]), vec![true, false, false, false]);
+        // The second highlight is beyond the lines
+        assert_eq!(styled(&lines[2]), vec![false, false, false, false]);
+        assert_eq!(
+            lines[0].cells()[2].attrs().background(),
+            ColorSpec::TrueColor(RgbColor::new_8bpc(0xff, 0, 0)).into()
+        );
+        // The cells themselves are unchanged
+        assert_eq!(lines[0].as_str(), "abcd");
+    }
+
+    #[test]
+    fn invalid_color() {
+        let style = HighlightStyle {
+            foreground: Some("not a color".to_string()),
+            ..Default::default()
+        };
+        let range = HighlightRange {
+            start_y: 0,
+            start_x: 0,
+            end_y: 0,
+            end_x: 1,
+        };
+        assert!(PaneHighlight::new(range, &style).is_err());
+    }
+}
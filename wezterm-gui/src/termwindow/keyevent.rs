@@ -1,4 +1,9 @@
+use super::keymacro::MacroInput;
 use ::window::{KeyCode, KeyEvent, Modifiers, WindowOps};
+use config::keyassignment::KeyAssignment;
+use mux::pane::Pane;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 pub fn window_mods_to_termwiz_mods(modifiers: ::window::Modifiers) -> termwiz::input::Modifiers {
     let mut result = termwiz::input::Modifiers::NONE;
@@ -26,6 +31,20 @@ pub fn window_mods_to_termwiz_mods(modifiers: ::window::Modifiers) -> termwiz::i
     result
 }
 
+/// A leader key, or a key table, that is waiting for the next key press
+#[derive(Debug, Clone)]
+pub struct ActiveLeader {
+    /// The key table to consult for the next key press; None
+    /// means that the LEADER modifier is active
+    pub key_table: Option<String>,
+    pub activated: Instant,
+    pub expiry: Instant,
+}
+
+/// The widest that the description of an action in the which-key
+/// popup is allowed to be
+const WHICH_KEY_ACTION_WIDTH: usize = 40;
+
 #[derive(Debug)]
 pub enum Key {
     Code(::termwiz::input::KeyCode),
@@ -50,25 +69,46 @@ impl super::TermWindow {
             None => return false,
         };
 
+        if self.pane_select_key(&window_key) {
+            return true;
+        }
+
+        if self.compose_key(&pane, &window_key) {
+            return true;
+        }
+
+        if self.pane_widget_key(&pane, &window_key.key, window_key.modifiers) {
+            context.invalidate();
+            return true;
+        }
+
+        if self.suggestion_key(&pane, &window_key) {
+            return true;
+        }
+
         // The leader key is a kind of modal modifier key.
         // It is allowed to be active for up to the leader timeout duration,
         // after which it auto-deactivates.
-        let (leader_active, leader_mod) = match self.leader_is_down.as_ref() {
-            Some(expiry) if *expiry > std::time::Instant::now() => {
-                // Currently active
-                (true, Modifiers::LEADER)
-            }
-            Some(_) => {
-                // Expired; clear out the old expiration time
-                self.leader_is_down.take();
-                (false, Modifiers::NONE)
-            }
-            _ => (false, Modifiers::NONE),
-        };
+        self.expire_leaders();
+        let leader_active = !self.leader_stack.is_empty();
 
         let modifiers = window_mods_to_termwiz_mods(window_key.modifiers);
         let raw_modifiers = window_mods_to_termwiz_mods(window_key.raw_modifiers);
 
+        // Bindings to physical keys take precedence, as they are the
+        // most specific: they only match a key in a particular position
+        // regardless of the keyboard layout
+        if let Some(phys) = window_key.phys_code {
+            if self.process_key_binding(
+                &pane,
+                &KeyCode::Physical(phys),
+                window_key.raw_modifiers,
+                context,
+            ) {
+                return true;
+            }
+        }
+
         // If we know the underlying raw code, let's first try any mappings
         // defined for those.  By their nature, we don't know anything useful
         // about their position or meaning in code here, so we don't have
@@ -76,33 +116,12 @@ impl super::TermWindow {
         // That means that we only need check for user-defined values in
         // this block.
         if let Some(raw_code) = window_key.raw_code {
-            let raw_code_key = KeyCode::RawCode(raw_code);
-
-            if !leader_active {
-                // Check to see if this key-press is the leader activating
-                if let Some(duration) = self
-                    .input_map
-                    .is_leader(&raw_code_key, window_key.raw_modifiers)
-                {
-                    // Yes; record its expiration
-                    self.leader_is_down
-                        .replace(std::time::Instant::now() + duration);
-                    return true;
-                }
-            }
-
-            if let Some(assignment) = self
-                .input_map
-                .lookup_key(&raw_code_key, window_key.raw_modifiers | leader_mod)
-            {
-                self.perform_key_assignment(&pane, &assignment).ok();
-                context.invalidate();
-
-                if leader_active {
-                    // A successful leader key-lookup cancels the leader
-                    // virtual modifier state
-                    self.leader_is_down.take();
-                }
+            if self.process_key_binding(
+                &pane,
+                &KeyCode::RawCode(raw_code),
+                window_key.raw_modifiers,
+                context,
+            ) {
                 return true;
             }
         }
@@ -110,28 +129,7 @@ impl super::TermWindow {
         // We may know the decoded platform key, but prior to any composition
         // defined by the system (eg: prior to dead key expansion).
         if let Some(key) = &window_key.raw_key {
-            if !leader_active {
-                // Check to see if this key-press is the leader activating
-                if let Some(duration) = self.input_map.is_leader(key, window_key.raw_modifiers) {
-                    // Yes; record its expiration
-                    self.leader_is_down
-                        .replace(std::time::Instant::now() + duration);
-                    return true;
-                }
-            }
-
-            if let Some(assignment) = self
-                .input_map
-                .lookup_key(key, window_key.raw_modifiers | leader_mod)
-            {
-                self.perform_key_assignment(&pane, &assignment).ok();
-                context.invalidate();
-
-                if leader_active {
-                    // A successful leader key-lookup cancels the leader
-                    // virtual modifier state
-                    self.leader_is_down.take();
-                }
+            if self.process_key_binding(&pane, key, window_key.raw_modifiers, context) {
                 return true;
             }
 
@@ -161,8 +159,12 @@ impl super::TermWindow {
 
                 if let Key::Code(term_key) = self.win_key_code_to_termwiz_key_code(&key) {
                     if bypass_compose && pane.key_down(term_key, raw_modifiers).is_ok() {
+                        if !key.is_modifier() {
+                            self.record_key_macro_input(MacroInput::Key(term_key, raw_modifiers));
+                        }
                         if !key.is_modifier() && self.pane_state(pane.pane_id()).overlay.is_none() {
                             self.maybe_scroll_to_bottom_for_input(&pane);
+                            self.expect_key_echo(&pane);
                         }
                         context.set_cursor(None);
                         context.invalidate();
@@ -172,46 +174,46 @@ impl super::TermWindow {
             }
         }
 
-        if !leader_active {
-            // Check to see if this key-press is the leader activating
-            if let Some(duration) = self
-                .input_map
-                .is_leader(&window_key.key, window_key.modifiers)
-            {
-                // Yes; record its expiration
-                self.leader_is_down
-                    .replace(std::time::Instant::now() + duration);
-                return true;
-            }
-        }
-
-        if let Some(assignment) = self
-            .input_map
-            .lookup_key(&window_key.key, window_key.modifiers | leader_mod)
-        {
-            self.perform_key_assignment(&pane, &assignment).ok();
-            context.invalidate();
-            if leader_active {
-                // A successful leader key-lookup cancels the leader
-                // virtual modifier state
-                self.leader_is_down.take();
-            }
+        if self.process_key_binding(&pane, &window_key.key, window_key.modifiers, context) {
             true
         } else if leader_active {
             if !window_key.key.is_modifier() {
                 // Leader was pressed and this non-modifier keypress isn't
                 // a registered key binding; swallow this event and cancel
                 // the leader modifier
-                self.leader_is_down.take();
+                self.cancel_leaders();
+                context.invalidate();
             }
             true
         } else {
-            let key = self.win_key_code_to_termwiz_key_code(&window_key.key);
+            let key = match self.win_key_code_to_termwiz_key_code(&window_key.key) {
+                // Normalization may turn a character into several
+                Key::Code(::termwiz::input::KeyCode::Char(c))
+                    if !c.is_ascii()
+                        && !modifiers.intersects(
+                            ::termwiz::input::Modifiers::CTRL
+                                | ::termwiz::input::Modifiers::ALT
+                                | ::termwiz::input::Modifiers::SUPER,
+                        ) =>
+                {
+                    let text = self.normalize_input(&pane, &c.to_string());
+                    let mut chars = text.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => Key::Code(::termwiz::input::KeyCode::Char(c)),
+                        _ => Key::Composed(text),
+                    }
+                }
+                key => key,
+            };
             match key {
                 Key::Code(key) => {
                     if pane.key_down(key, modifiers).is_ok() {
+                        if !key.is_modifier() {
+                            self.record_key_macro_input(MacroInput::Key(key, modifiers));
+                        }
                         if !key.is_modifier() && self.pane_state(pane.pane_id()).overlay.is_none() {
                             self.maybe_scroll_to_bottom_for_input(&pane);
+                            self.expect_key_echo(&pane);
                         }
                         context.set_cursor(None);
                         context.invalidate();
@@ -221,16 +223,12 @@ impl super::TermWindow {
                     }
                 }
                 Key::Composed(s) => {
-                    if leader_active {
-                        // Leader was pressed and this non-modifier keypress isn't
-                        // a registered key binding; swallow this event and cancel
-                        // the leader modifier.
-                        self.leader_is_down.take();
-                    } else {
-                        pane.writer().write_all(s.as_bytes()).ok();
-                        self.maybe_scroll_to_bottom_for_input(&pane);
-                        context.invalidate();
-                    }
+                    let s = self.normalize_input(&pane, &s);
+                    pane.writer().write_all(s.as_bytes()).ok();
+                    self.record_key_macro_input(MacroInput::Text(s));
+                    self.maybe_scroll_to_bottom_for_input(&pane);
+                    self.expect_key_echo(&pane);
+                    context.invalidate();
                     true
                 }
                 Key::None => false,
@@ -238,6 +236,165 @@ impl super::TermWindow {
         }
     }
 
+    /// Checks whether `key` activates a leader, or is bound to an
+    /// assignment in the currently active leader mode or key table,
+    /// and if so, acts upon it.  Returns true if the key was consumed.
+    fn process_key_binding(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        key: &KeyCode,
+        mods: Modifiers,
+        context: &dyn WindowOps,
+    ) -> bool {
+        if self.leader_stack.is_empty() {
+            // Check to see if this key-press is the leader activating
+            if let Some((key_table, duration)) = self.input_map.is_leader(key, mods) {
+                // Yes; record its expiration
+                let now = Instant::now();
+                self.leader_stack.push(ActiveLeader {
+                    key_table,
+                    activated: now,
+                    expiry: now + duration,
+                });
+                context.invalidate();
+                return true;
+            }
+        }
+
+        let assignment = match self.leader_stack.last() {
+            Some(ActiveLeader {
+                key_table: Some(name),
+                ..
+            }) => self.input_map.lookup_key_table(name, key, mods),
+            Some(ActiveLeader {
+                key_table: None, ..
+            }) => self.input_map.lookup_key(key, mods | Modifiers::LEADER),
+            None => self.input_map.lookup_key(key, mods),
+        };
+
+        match assignment {
+            Some(assignment) => {
+                // A successful leader key-lookup cancels the leader
+                // virtual modifier state, unless it activates another
+                // key table, which is then nested within it
+                if !matches!(assignment, KeyAssignment::ActivateKeyTable { .. }) {
+                    self.cancel_leaders();
+                }
+                self.perform_key_assignment(pane, &assignment).ok();
+                context.invalidate();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Pushes the key table `name` on top of any active leader,
+    /// or, if `replace_current` is true, in place of the most
+    /// recently activated one
+    pub fn activate_key_table(&mut self, name: &str, timeout: Duration, replace_current: bool) {
+        if !self.input_map.has_key_table(name) {
+            log::error!("ActivateKeyTable: there is no key table named {}", name);
+            return;
+        }
+        if replace_current {
+            self.leader_stack.pop();
+        }
+        let now = Instant::now();
+        self.leader_stack.push(ActiveLeader {
+            key_table: Some(name.to_string()),
+            activated: now,
+            expiry: now + timeout,
+        });
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    pub fn cancel_leaders(&mut self) {
+        self.leader_stack.clear();
+    }
+
+    /// Cancels the active leaders if the most recently activated
+    /// one has timed out
+    pub fn expire_leaders(&mut self) {
+        if let Some(leader) = self.leader_stack.last() {
+            if leader.expiry <= Instant::now() {
+                self.cancel_leaders();
+            }
+        }
+    }
+
+    /// Describes the active leader and key tables, along with the
+    /// time remaining before they are cancelled, eg: `LEADER › resize 0.6s`
+    pub fn leader_indicator_text(&self) -> Option<String> {
+        let leader = self.leader_stack.last()?;
+        let breadcrumb = self
+            .leader_stack
+            .iter()
+            .map(|leader| leader.key_table.as_deref().unwrap_or("LEADER"))
+            .collect::<Vec<_>>()
+            .join(" › ");
+        let remaining = leader.expiry.saturating_duration_since(Instant::now());
+        Some(format!("{} {:.1}s", breadcrumb, remaining.as_secs_f32()))
+    }
+
+    /// Once the most recently activated leader or key table has been
+    /// active for `which_key_delay_milliseconds`, returns a line for
+    /// each of the key bindings that can be used next, padded to
+    /// the same width.
+    pub fn which_key_lines(&self) -> Vec<String> {
+        let leader = match self.leader_stack.last() {
+            Some(leader) if self.config.show_which_key => leader,
+            _ => return vec![],
+        };
+        let delay = Duration::from_millis(self.config.which_key_delay_milliseconds);
+        if leader.activated.elapsed() < delay {
+            return vec![];
+        }
+
+        let mut entries: Vec<(String, String)> = self
+            .input_map
+            .key_table_entries(leader.key_table.as_deref())
+            .into_iter()
+            .map(|(key, mods, action)| {
+                let action = match action {
+                    KeyAssignment::ActivateKeyTable { name, .. } => format!("› {}", name),
+                    action => {
+                        let desc = action.description();
+                        if desc.chars().count() > WHICH_KEY_ACTION_WIDTH {
+                            let truncated: String =
+                                desc.chars().take(WHICH_KEY_ACTION_WIDTH - 1).collect();
+                            format!("{}…", truncated)
+                        } else {
+                            desc
+                        }
+                    }
+                };
+                (config::describe_key(&key, mods), action)
+            })
+            .collect();
+        entries.sort();
+
+        let key_width = entries
+            .iter()
+            .map(|(key, _)| key.chars().count())
+            .max()
+            .unwrap_or(0);
+        let lines: Vec<String> = entries
+            .into_iter()
+            .map(|(key, action)| format!("{:<width$}  {}", key, action, width = key_width))
+            .collect();
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        lines
+            .into_iter()
+            .map(|line| format!("{:<width$}", line, width = width))
+            .collect()
+    }
+
     pub fn win_key_code_to_termwiz_key_code(&self, key: &::window::KeyCode) -> Key {
         use ::termwiz::input::KeyCode as KC;
         use ::window::KeyCode as WK;
@@ -261,7 +418,7 @@ impl super::TermWindow {
                 }
             }
             WK::Char('\u{1b}') => KC::Escape,
-            WK::RawCode(_) => return Key::None,
+            WK::RawCode(_) | WK::Physical(_) => return Key::None,
 
             WK::Char(c) => KC::Char(*c),
             WK::Composed(ref s) => {
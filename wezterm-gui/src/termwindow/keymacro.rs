@@ -0,0 +1,193 @@
+//! Keyboard macros: StartKeyMacro records the input that is sent to
+//! panes by the keyboard until StopKeyMacro, and PlayKeyMacro sends
+//! it to the active pane again.  Macros are shared by all windows and
+//! are kept until wezterm exits.
+use mux::pane::Pane;
+use mux::Mux;
+use smol::Timer;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use termwiz::input::{KeyCode, Modifiers};
+use window::WindowOps;
+
+lazy_static::lazy_static! {
+    static ref KEY_MACROS: Mutex<HashMap<String, Vec<MacroStep>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MacroInput {
+    Key(KeyCode, Modifiers),
+    /// Text from an input method or a compose sequence
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MacroStep {
+    /// How long to wait before sending `input`; zero unless the
+    /// macro was recorded with its timing
+    delay: Duration,
+    input: MacroInput,
+}
+
+pub struct KeyMacroRecorder {
+    name: String,
+    record_timing: bool,
+    last_input: Instant,
+    steps: Vec<MacroStep>,
+}
+
+impl KeyMacroRecorder {
+    fn record(&mut self, input: MacroInput) {
+        let now = Instant::now();
+        let delay = if self.record_timing {
+            now - self.last_input
+        } else {
+            Duration::from_secs(0)
+        };
+        self.last_input = now;
+        self.steps.push(MacroStep { delay, input });
+    }
+}
+
+fn send_input(pane: &dyn Pane, input: &MacroInput) -> anyhow::Result<()> {
+    match input {
+        MacroInput::Key(key, mods) => pane.key_down(*key, *mods),
+        MacroInput::Text(text) => {
+            pane.writer().write_all(text.as_bytes())?;
+            Ok(())
+        }
+    }
+}
+
+impl super::TermWindow {
+    pub fn start_key_macro(&mut self, name: &str, record_timing: bool) {
+        if let Some(previous) = self.key_macro.as_ref() {
+            log::warn!(
+                "StartKeyMacro: discarding the unfinished recording of {}",
+                previous.name
+            );
+        }
+        self.key_macro.replace(KeyMacroRecorder {
+            name: name.to_string(),
+            record_timing,
+            last_input: Instant::now(),
+            steps: vec![],
+        });
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    pub fn stop_key_macro(&mut self) {
+        match self.key_macro.take() {
+            Some(recorder) => {
+                log::info!(
+                    "recorded key macro {} with {} steps",
+                    recorder.name,
+                    recorder.steps.len()
+                );
+                KEY_MACROS
+                    .lock()
+                    .unwrap()
+                    .insert(recorder.name, recorder.steps);
+            }
+            None => log::error!("StopKeyMacro: no key macro is being recorded"),
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Returns the name of the key macro that is being recorded
+    pub fn recording_key_macro(&self) -> Option<&str> {
+        self.key_macro
+            .as_ref()
+            .map(|recorder| recorder.name.as_str())
+    }
+
+    /// Adds `input` to the key macro that is being recorded, if any
+    pub fn record_key_macro_input(&mut self, input: MacroInput) {
+        if let Some(recorder) = self.key_macro.as_mut() {
+            recorder.record(input);
+        }
+    }
+
+    pub fn play_key_macro(&mut self, pane: &Rc<dyn Pane>, name: &str) {
+        let steps = match KEY_MACROS.lock().unwrap().get(name) {
+            Some(steps) => steps.clone(),
+            None => {
+                log::error!("PlayKeyMacro: there is no key macro named {}", name);
+                return;
+            }
+        };
+        self.maybe_scroll_to_bottom_for_input(pane);
+
+        if steps
+            .iter()
+            .all(|step| step.delay == Duration::from_secs(0))
+        {
+            for step in &steps {
+                if let Err(err) = send_input(&**pane, &step.input) {
+                    log::error!("PlayKeyMacro {}: {:#}", name, err);
+                    return;
+                }
+            }
+            return;
+        }
+
+        // Replay the recorded timing without blocking the gui
+        let pane_id = pane.pane_id();
+        let name = name.to_string();
+        promise::spawn::spawn(async move {
+            for step in steps {
+                Timer::after(step.delay).await;
+                let pane = match Mux::get().and_then(|mux| mux.get_pane(pane_id)) {
+                    Some(pane) => pane,
+                    None => return,
+                };
+                if let Err(err) = send_input(&*pane, &step.input) {
+                    log::error!("PlayKeyMacro {}: {:#}", name, err);
+                    return;
+                }
+            }
+        })
+        .detach();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record() {
+        let mut recorder = KeyMacroRecorder {
+            name: "test".to_string(),
+            record_timing: false,
+            last_input: Instant::now(),
+            steps: vec![],
+        };
+        recorder.record(MacroInput::Key(KeyCode::Char('a'), Modifiers::NONE));
+        recorder.record(MacroInput::Text("é".to_string()));
+        assert_eq!(
+            recorder.steps,
+            vec![
+                MacroStep {
+                    delay: Duration::from_secs(0),
+                    input: MacroInput::Key(KeyCode::Char('a'), Modifiers::NONE),
+                },
+                MacroStep {
+                    delay: Duration::from_secs(0),
+                    input: MacroInput::Text("é".to_string()),
+                },
+            ]
+        );
+
+        recorder.record_timing = true;
+        recorder.last_input = Instant::now() - Duration::from_millis(500);
+        recorder.record(MacroInput::Key(KeyCode::Enter, Modifiers::NONE));
+        assert!(recorder.steps[2].delay >= Duration::from_millis(500));
+    }
+}
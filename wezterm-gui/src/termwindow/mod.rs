@@ -4,14 +4,16 @@ use super::utilsprites::RenderMetrics;
 use crate::cache::LruCache;
 use crate::glium::texture::SrgbTexture2d;
 use crate::overlay::{
-    confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program, launcher,
-    start_overlay, start_overlay_pane, tab_navigator, CopyOverlay, QuickSelectOverlay,
-    SearchOverlay,
+    collect_links, command_history, confirm_close_pane, confirm_close_tab, confirm_close_window,
+    confirm_close_workspace, confirm_quit_program, launcher, lua_modal, rename_workspace_prompt,
+    send_snippet_text, snippet_cwd, snippet_picker, snippet_prompt, start_overlay,
+    start_overlay_pane, tab_navigator, tab_overview, tab_preview, text_viewer, url_picker,
+    CopyOverlay, ModalSpec, QuickSelectOverlay, SearchOverlay, ThumbnailLayout,
 };
 use crate::scripting::guiwin::GuiWin;
 use crate::scripting::pane::PaneObject;
 use crate::scrollbar::*;
-use crate::selection::Selection;
+use crate::selection::{Selection, SelectionRange};
 use crate::shapecache::*;
 use crate::tabbar::TabBarState;
 use ::wezterm_term::input::MouseButton as TMB;
@@ -20,16 +22,19 @@ use anyhow::Context;
 use anyhow::{anyhow, ensure};
 use config::keyassignment::{
     ClipboardCopyDestination, ClipboardPasteSource, InputMap, KeyAssignment, SpawnCommand,
+    SpawnTabDomain,
 };
 use config::{
-    configuration, AudibleBell, ConfigHandle, GradientOrientation, TermConfig,
+    configuration, ConfigChanges, ConfigHandle, CopyLineWrap, GradientOrientation, TermConfig,
     WindowCloseConfirmation,
 };
 use luahelper::impl_lua_conversion;
 use mlua::FromLua;
-use mux::domain::{DomainId, DomainState};
-use mux::pane::{Pane, PaneId};
-use mux::renderable::RenderableDimensions;
+use mux::domain::{DomainId, DomainState, LocalDomain};
+use mux::localpane::{is_respawn_pane_uri, is_trusted_respawn_pane_uri, LocalPane};
+use mux::memory::MemoryStat;
+use mux::pane::{Pane, PaneId, Pattern};
+use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::{PositionedPane, PositionedSplit, SplitDirection, Tab, TabId};
 use mux::window::WindowId as MuxWindowId;
 use mux::{Mux, MuxNotification};
@@ -38,33 +43,75 @@ use serde::*;
 use smol::channel::Sender;
 use smol::Timer;
 use std::cell::{RefCell, RefMut};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Add;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use termwiz::hyperlink::Hyperlink;
+use termwiz::hyperlink::{Hyperlink, RULE_EVENT_PARAM, RULE_MODS_PARAM, RULE_OPEN_WITH_PARAM};
 use termwiz::image::{ImageData, ImageDataType};
 use termwiz::surface::SequenceNo;
+use wezterm_client::pane::ClientPane;
 use wezterm_font::FontConfiguration;
-use wezterm_term::color::ColorPalette;
+use wezterm_term::color::{ColorPalette, RgbColor};
 use wezterm_term::input::LastMouseClick;
 use wezterm_term::{Alert, SemanticZone, StableRowIndex, TerminalConfiguration};
 
+mod accessibility;
 pub mod clipboard;
+mod compose;
+pub mod highlight;
 mod keyevent;
+mod keymacro;
 mod mouseevent;
+mod paneselect;
+mod perfhud;
+mod prerender;
 mod prevcursor;
+mod profile;
+mod promptinput;
 mod render;
 pub mod resize;
 mod selection;
 pub mod spawn;
+mod suggest;
+mod thumbnail;
+mod transition;
+mod watch;
+pub mod widget;
 use clipboard::ClipboardHelper;
 use prevcursor::PrevCursorPos;
 use spawn::SpawnWhere;
 
 const ATLAS_SIZE: usize = 128;
+/// How to handle a request to close a pane, tab or window
+enum CloseDecision {
+    /// Close without asking
+    Close,
+    /// Leave it open
+    Cancel,
+    /// Ask for confirmation, optionally with a custom message
+    Prompt(Option<String>),
+}
+
+/// Output that arrives within this long of a key press is
+/// treated as the echo of that key press
+const KEY_ECHO_WINDOW: Duration = Duration::from_millis(100);
+
+/// How many rows of scrollback above the viewport are searched
+/// for links by the url picker
+const URL_PICKER_SCROLLBACK_ROWS: StableRowIndex = 1000;
+
+/// The files written by ViewLastCommandOutputInEditor are named with
+/// this prefix in the runtime directory
+const COMMAND_OUTPUT_FILE_PREFIX: &str = "command-output-";
+/// An editor that exits sooner than this after opening the command
+/// output is assumed to have handed the file to another process
+const EDITOR_HANDOFF_TIME: Duration = Duration::from_secs(5);
+/// Command output files that were left behind for such an editor
+/// are removed once they are this old
+const STALE_COMMAND_OUTPUT_AGE: Duration = Duration::from_secs(24 * 60 * 60);
 
 lazy_static::lazy_static! {
     static ref WINDOW_CLASS: Mutex<String> = Mutex::new("org.wezfurlong.wezterm".to_owned());
@@ -103,6 +150,10 @@ pub enum TermWindowNotif {
         pane_id: Option<PaneId>,
     },
     MuxNotification(MuxNotification),
+    FlashVisualBell {
+        pane_id: PaneId,
+        color: Option<RgbColor>,
+    },
     EmitStatusUpdate,
     Apply(Box<dyn FnOnce(&mut TermWindow) + Send + Sync>),
 }
@@ -131,19 +182,58 @@ pub struct SemanticZoneCache {
     zones: Vec<SemanticZone>,
 }
 
+#[derive(Clone)]
+struct RingingBell {
+    start: Instant,
+    /// The color in which to show the bell; if None, the
+    /// visual_bell color from the palette is used
+    color: Option<RgbColor>,
+}
+
 #[derive(Default, Clone)]
 pub struct PaneState {
     /// If is_some(), the top row of the visible screen.
     /// Otherwise, the viewport is at the bottom of the
     /// scrollback.
     viewport: Option<StableRowIndex>,
+    /// The dimensions of the pane when the viewport was last
+    /// positioned, used to keep the same scroll offset when
+    /// the pane is resized
+    viewport_dims: Option<RenderableDimensions>,
     selection: Selection,
     /// If is_some(), rather than display the actual tab
     /// contents, we're overlaying a little internal application
     /// tab.  We'll also route input to it.
     pub overlay: Option<Rc<dyn Pane>>,
 
-    bell_start: Option<Instant>,
+    /// The visual bell that is currently being shown in this pane
+    bell: Option<RingingBell>,
+
+    /// Overrides the show_command_badges configuration
+    /// for this pane when is_some()
+    show_command_badges: Option<bool>,
+
+    /// The pattern most recently entered in the search overlay
+    last_search: Option<Pattern>,
+    search_highlight: Option<highlight::SearchHighlight>,
+    /// Highlights added by lua code, ordered by their first row
+    highlights: Vec<highlight::PaneHighlight>,
+
+    /// Set by the ToggleLigatures action
+    disable_ligatures: bool,
+
+    /// Widgets placed over the pane by lua code, in the
+    /// order in which they are drawn
+    widgets: Vec<widget::PaneWidget>,
+
+    /// The suggestion from enable_scrollback_suggestions
+    suggestion: Option<suggest::SuggestionCache>,
+
+    /// The input at the prompt, and the highlights that the
+    /// highlight-prompt-input event returned for it
+    prompt_input: Option<promptinput::PromptInput>,
+    /// The seqno of the pane when its prompt input was last examined
+    prompt_input_seqno: Option<SequenceNo>,
 }
 
 /// Data used when synchronously formatting pane and window titles
@@ -153,6 +243,14 @@ pub struct TabInformation {
     pub tab_index: usize,
     pub is_active: bool,
     pub active_pane: Option<PaneInformation>,
+    /// "Activity" or "Silence" if a watch has alerted for one
+    /// of the panes in the tab, or "CommandCompleted" if a long
+    /// running command finished in one of them, and it hasn't
+    /// yet been seen
+    pub watch_alert: Option<String>,
+    /// The color that was assigned to the tab via remote control,
+    /// as a "#rrggbb" string
+    pub tab_color: Option<String>,
 }
 impl_lua_conversion!(TabInformation);
 
@@ -180,6 +278,10 @@ pub struct TabState {
     /// contents, we're overlaying a little internal application
     /// tab.  We'll also route input to it.
     pub overlay: Option<Rc<dyn Pane>>,
+    /// The profile selected for this tab by `SwitchProfile`, which
+    /// takes precedence over that of the window while the tab is active.
+    /// `Some(None)` if the tab was switched back to the default profile.
+    pub profile: Option<Option<String>>,
 }
 
 /// Manages the state/queue of lua based event handlers.
@@ -201,6 +303,13 @@ pub struct TermWindow {
     pub window: Option<Window>,
     pub config: ConfigHandle,
     pub config_overrides: serde_json::Value,
+    /// The profile selected for the window by `SwitchProfile`
+    window_profile: Option<String>,
+    /// The profile whose overrides are applied to `config`
+    applied_profile: Option<String>,
+    palette_transition: Option<transition::PaletteTransition>,
+    font_scale_transition: Option<transition::FontScaleTransition>,
+    accessibility: accessibility::AccessibilityState,
     /// When we most recently received keyboard focus
     focused: Option<Instant>,
     fonts: Rc<FontConfiguration>,
@@ -213,8 +322,9 @@ pub struct TermWindow {
     pub render_metrics: RenderMetrics,
     render_state: Option<RenderState>,
     input_map: InputMap,
-    /// If is_some, the LEADER modifier is active until the specified instant.
-    leader_is_down: Option<std::time::Instant>,
+    /// The active leader and any key tables nested within it;
+    /// the last entry determines how the next key press is handled.
+    leader_stack: Vec<keyevent::ActiveLeader>,
     show_tab_bar: bool,
     show_scroll_bar: bool,
     tab_bar: TabBarState,
@@ -229,11 +339,31 @@ pub struct TermWindow {
 
     tab_state: RefCell<HashMap<TabId, TabState>>,
     pane_state: RefCell<HashMap<PaneId, PaneState>>,
+    /// Panes that are being watched for activity or silence
+    pane_watches: HashMap<PaneId, watch::ActiveWatch>,
+    /// Panes in which a long running command completed while
+    /// they weren't being looked at
+    completed_commands: HashSet<PaneId>,
     semantic_zones: HashMap<PaneId, SemanticZoneCache>,
 
     window_background: Option<Arc<ImageData>>,
 
     current_mouse_buttons: Vec<MousePress>,
+    mouse_gesture: Option<mouseevent::MouseGesture>,
+    /// The pane under the mouse and the time at which it started to
+    /// wait for pane_focus_follows_mouse_delay to elapse
+    pending_pane_focus: Option<(PaneId, Instant)>,
+    /// A motion report held back by mouse_motion_report_interval,
+    /// to be sent when the interval has elapsed
+    pending_mouse_motion: Option<(Rc<dyn Pane>, wezterm_term::MouseEvent)>,
+    /// When the most recent motion report was sent to a pane
+    last_mouse_motion_report: Instant,
+    /// The pane and time of the most recent key press whose echo
+    /// should be painted immediately when low_latency_input is enabled
+    key_echo_pending: Option<(PaneId, Instant)>,
+    /// When the output for a key press was painted immediately, the
+    /// time of that key press, so that the latency can be recorded
+    key_echo_paint: Option<Instant>,
 
     /// Keeps track of double and triple clicks
     last_mouse_click: Option<LastMouseClick>,
@@ -243,6 +373,19 @@ pub struct TermWindow {
 
     shape_cache:
         RefCell<LruCache<ShapeCacheKey, anyhow::Result<Rc<Vec<ShapedInfo<SrgbTexture2d>>>>>>,
+    /// Foreground colors that were adjusted to satisfy the
+    /// minimum_contrast_ratio, keyed by the (fg, bg) colors
+    contrast_cache: RefCell<LruCache<(RgbColor, RgbColor), RgbColor>>,
+    /// The most recently active tabs, most recent first, whose
+    /// text is shaped in advance when prerender_recent_tabs is set
+    recent_tabs: Vec<TabId>,
+    /// The shapes that were prerendered for the panes of recent tabs
+    prerendered_panes: HashMap<PaneId, prerender::PrerenderedPane>,
+    /// The overlay pane of the tab overview, if it is shown, and the
+    /// layout of the thumbnails that it wants painted
+    tab_overview: Option<(PaneId, ThumbnailLayout)>,
+    /// The offscreen renderings of the tabs shown by the tab overview
+    tab_thumbnails: HashMap<TabId, thumbnail::TabThumbnail>,
 
     next_blink_paint: RefCell<Instant>,
     last_status_call: Instant,
@@ -261,6 +404,23 @@ pub struct TermWindow {
     allow_images: bool,
     scheduled_animation: RefCell<Option<Instant>>,
 
+    /// Whether the performance HUD is shown
+    show_perf_hud: bool,
+    /// The type of pattern and whether whole word matching was
+    /// enabled in the most recent search, which are reused when a
+    /// search starts with an empty pattern
+    search_mode: Option<(Pattern, bool)>,
+    /// The labels shown over the panes by ShowPaneNumbers and PaneSelect
+    pane_select: Option<paneselect::PaneSelect>,
+    /// The panes that RotatePanes moves, chosen using the
+    /// ToggleRotation mode of PaneSelect
+    rotation_panes: Vec<PaneId>,
+    /// The keys typed since the ComposeCharacter or InputCodepoint action
+    compose: Option<compose::PendingCompose>,
+    /// The key macro that is being recorded
+    key_macro: Option<keymacro::KeyMacroRecorder>,
+    frame_stats: RefCell<perfhud::FrameStats>,
+
     gl: Option<Rc<glium::backend::Context>>,
     config_subscription: Option<config::ConfigSubscription>,
 }
@@ -268,6 +428,15 @@ pub struct TermWindow {
 impl TermWindow {
     fn close_requested(&mut self, window: &Window) {
         let mux = Mux::get().unwrap();
+        if !self.config.quit_when_all_windows_are_closed
+            && crate::frontend::front_end().map_or(false, |fe| fe.known_window_count() == 1)
+        {
+            // Close the window but leave its panes running, so that
+            // the next `wezterm start` can show them again
+            crate::background::enter_background();
+            window.close();
+            return;
+        }
         match self.config.window_close_confirmation {
             WindowCloseConfirmation::NeverPrompt => {
                 // Immediately kill the tabs and allow the window to close
@@ -286,17 +455,26 @@ impl TermWindow {
 
                 let mux_window_id = self.mux_window_id;
 
-                let can_close = mux
+                let needs_prompt = !mux
                     .get_window(mux_window_id)
                     .map_or(false, |w| w.can_close_without_prompting());
-                if can_close {
-                    mux.kill_window(self.mux_window_id);
-                    window.close();
-                    return;
-                }
+                let decision = match tab.get_active_pane() {
+                    Some(pane) => self.close_decision("window", &pane, needs_prompt),
+                    None if needs_prompt => CloseDecision::Prompt(None),
+                    None => CloseDecision::Close,
+                };
+                let message = match decision {
+                    CloseDecision::Close => {
+                        mux.kill_window(self.mux_window_id);
+                        window.close();
+                        return;
+                    }
+                    CloseDecision::Cancel => return,
+                    CloseDecision::Prompt(message) => message,
+                };
                 let window = self.window.clone().unwrap();
                 let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
-                    confirm_close_window(term, mux_window_id, window, tab_id)
+                    confirm_close_window(term, mux_window_id, window, tab_id, message)
                 });
                 self.assign_overlay(tab.tab_id(), overlay);
                 promise::spawn::spawn(future).detach();
@@ -325,6 +503,27 @@ impl TermWindow {
         if let Some(pane) = self.get_active_pane_or_overlay() {
             pane.focus_changed(focused);
         }
+
+        crate::tray::window_focus_changed(self.mux_window_id, focused);
+        if focused {
+            Mux::get()
+                .unwrap()
+                .record_focused_window(self.mux_window_id);
+        }
+
+        if focused && self.config.window_per_workspace {
+            // The active workspace follows the focused window, so that
+            // new windows and tabs are created alongside it
+            let mux = Mux::get().unwrap();
+            let workspace = mux
+                .get_window(self.mux_window_id)
+                .map(|w| w.get_workspace().to_string());
+            if let Some(workspace) = workspace {
+                if workspace != mux.active_workspace() {
+                    mux.set_active_workspace(&workspace);
+                }
+            }
+        }
     }
 
     fn created(
@@ -343,6 +542,14 @@ impl TermWindow {
                     gl.context.is_context_loss_possible(),
                     config::wezterm_version(),
                 );
+                env_bootstrap::crash::annotate(
+                    "renderer",
+                    format!(
+                        "OpenGL {} {}",
+                        gl.context.get_opengl_renderer_string(),
+                        gl.context.get_opengl_version_string()
+                    ),
+                );
                 self.render_state.replace(gl);
             }
             Err(err) => {
@@ -350,7 +557,9 @@ impl TermWindow {
             }
         }
 
-        window.show();
+        if self.config.window_per_workspace || self.is_in_active_workspace() {
+            window.show();
+        }
 
         if self.render_state.is_none() {
             panic!("No OpenGL");
@@ -507,9 +716,30 @@ fn reload_background_image(
 impl TermWindow {
     pub async fn new_window(mux_window_id: MuxWindowId) -> anyhow::Result<()> {
         let config = configuration();
+        // The first tab of the window may have been spawned with a
+        // profile of its own, such as by `wezterm start --profile`
+        let applied_profile = Mux::get()
+            .and_then(|mux| mux.get_active_tab_for_window(mux_window_id))
+            .and_then(|tab| tab.get_profile())
+            .or_else(|| config.default_profile.clone());
+        let config = match &applied_profile {
+            Some(profile) => {
+                let overrides =
+                    config.profile_overrides(Some(profile), &serde_json::Value::default());
+                match config::overridden_config(&overrides) {
+                    Ok(config) => config,
+                    Err(err) => {
+                        log::error!("Failed to apply profile {}: {:#}", profile, err);
+                        config
+                    }
+                }
+            }
+            None => config,
+        };
 
         let dpi = config.dpi.unwrap_or_else(|| ::window::default_dpi()) as usize;
 
+        let font_start = Instant::now();
         let fontconfig = Rc::new(FontConfiguration::new(Some(config.clone()), dpi)?);
         let mux = Mux::get().expect("to be main thread with mux running");
         let size = match mux.get_active_tab_for_window(mux_window_id) {
@@ -524,6 +754,7 @@ impl TermWindow {
 
         let render_metrics = RenderMetrics::new(&fontconfig)?;
         log::trace!("using render_metrics {:#?}", render_metrics);
+        crate::startupprofile::record("font initialization", font_start.elapsed());
 
         let terminal_size = PtySize {
             rows: physical_rows as u16,
@@ -567,6 +798,11 @@ impl TermWindow {
             window_background,
             config: config.clone(),
             config_overrides: serde_json::Value::default(),
+            window_profile: None,
+            applied_profile,
+            palette_transition: None,
+            font_scale_transition: None,
+            accessibility: Default::default(),
             palette: None,
             focused: None,
             mux_window_id,
@@ -577,7 +813,7 @@ impl TermWindow {
             terminal_size,
             render_state,
             input_map: InputMap::new(&config),
-            leader_is_down: None,
+            leader_stack: vec![],
             show_tab_bar,
             show_scroll_bar: config.enable_scroll_bar,
             tab_bar: TabBarState::default(),
@@ -590,14 +826,31 @@ impl TermWindow {
             last_scroll_info: RenderableDimensions::default(),
             tab_state: RefCell::new(HashMap::new()),
             pane_state: RefCell::new(HashMap::new()),
+            pane_watches: HashMap::new(),
+            completed_commands: HashSet::new(),
             current_mouse_buttons: vec![],
+            mouse_gesture: None,
+            pending_pane_focus: None,
+            pending_mouse_motion: None,
+            last_mouse_motion_report: Instant::now(),
+            key_echo_pending: None,
+            key_echo_paint: None,
             last_mouse_click: None,
             current_highlight: None,
             shape_cache: RefCell::new(LruCache::new(
                 "shape_cache.hit.rate",
                 "shape_cache.miss.rate",
-                65536,
+                config.shape_cache_size,
             )),
+            contrast_cache: RefCell::new(LruCache::new(
+                "contrast_cache.hit.rate",
+                "contrast_cache.miss.rate",
+                1024,
+            )),
+            recent_tabs: vec![],
+            prerendered_panes: HashMap::new(),
+            tab_overview: None,
+            tab_thumbnails: HashMap::new(),
             next_blink_paint: RefCell::new(Instant::now()),
             last_status_call: Instant::now(),
             last_text_blink_paint: RefCell::new(Instant::now()),
@@ -605,6 +858,13 @@ impl TermWindow {
             event_states: HashMap::new(),
             has_animation: RefCell::new(None),
             scheduled_animation: RefCell::new(None),
+            show_perf_hud: false,
+            search_mode: None,
+            pane_select: None,
+            rotation_panes: vec![],
+            compose: None,
+            key_macro: None,
+            frame_stats: RefCell::new(perfhud::FrameStats::default()),
             allow_images: true,
             semantic_zones: HashMap::new(),
             ui_items: vec![],
@@ -615,6 +875,7 @@ impl TermWindow {
         let tw = Rc::new(RefCell::new(myself));
         let tw_event = Rc::clone(&tw);
 
+        let window_start = Instant::now();
         let window = Window::new_window(
             &*WINDOW_CLASS.lock().unwrap(),
             "wezterm",
@@ -630,7 +891,11 @@ impl TermWindow {
             },
         )
         .await?;
+        crate::startupprofile::record("window creation", window_start.elapsed());
         tw.borrow_mut().window.replace(window.clone());
+        if let Some(fe) = crate::frontend::front_end() {
+            fe.record_known_window(window.clone(), mux_window_id);
+        }
 
         Self::apply_icon(&window)?;
         Self::setup_clipboard(&window, mux_window_id);
@@ -645,6 +910,7 @@ impl TermWindow {
             }
         });
 
+        let gpu_start = Instant::now();
         let gl = window.enable_opengl().await?;
         {
             let mut myself = tw.borrow_mut();
@@ -656,7 +922,9 @@ impl TermWindow {
             );
 
             myself.created(&window, Rc::clone(&gl))?;
+            crate::startupprofile::record("gpu initialization", gpu_start.elapsed());
             myself.subscribe_to_pane_updates();
+            myself.register_memory_reporter(&tw);
             myself.emit_window_event("window-config-reloaded", None);
             myself.emit_status_event();
         }
@@ -671,7 +939,12 @@ impl TermWindow {
         window: &Window,
     ) -> anyhow::Result<bool> {
         match event {
-            WindowEvent::Destroyed => Ok(false),
+            WindowEvent::Destroyed => {
+                if let Some(fe) = crate::frontend::front_end() {
+                    fe.forget_known_window(self.mux_window_id);
+                }
+                Ok(false)
+            }
             WindowEvent::CloseRequested => {
                 self.close_requested(window);
                 Ok(true)
@@ -685,10 +958,18 @@ impl TermWindow {
                 self.focus_changed(focused, window);
                 Ok(true)
             }
+            WindowEvent::DroppedFile(paths) => {
+                self.dropped_files(paths);
+                Ok(true)
+            }
             WindowEvent::MouseEvent(event) => {
                 self.mouse_event_impl(event, window);
                 Ok(true)
             }
+            WindowEvent::MouseLeave => {
+                self.mouse_leave_impl();
+                Ok(true)
+            }
             WindowEvent::Resized {
                 dimensions,
                 window_state,
@@ -701,6 +982,10 @@ impl TermWindow {
                 Ok(true)
             }
             WindowEvent::NeedRepaint => Ok(self.do_paint(window)),
+            WindowEvent::AccessibilityActivate(node_id) => {
+                self.accessibility_activate(node_id)?;
+                Ok(true)
+            }
             WindowEvent::Notification(item) => {
                 if let Ok(notif) = item.downcast::<TermWindowNotif>() {
                     self.dispatch_notif(*notif, window)
@@ -712,6 +997,8 @@ impl TermWindow {
     }
 
     fn do_paint(&mut self, window: &Window) -> bool {
+        self.step_appearance_transitions(window);
+
         let gl = match self.gl.as_ref() {
             Some(gl) => gl,
             None => return false,
@@ -731,8 +1018,13 @@ impl TermWindow {
             ),
         );
 
+        let start = Instant::now();
         self.paint_impl(&mut frame);
-        window.finish_frame(frame).is_ok()
+        let painted = window.finish_frame(frame).is_ok();
+        crate::startupprofile::record("first paint", start.elapsed());
+        crate::startupprofile::finish();
+        self.update_accessibility(window);
+        painted
     }
 
     fn dispatch_notif(&mut self, notif: TermWindowNotif, window: &Window) -> anyhow::Result<()> {
@@ -792,6 +1084,9 @@ impl TermWindow {
             TermWindowNotif::CancelOverlayForTab { tab_id, pane_id } => {
                 self.cancel_overlay_for_tab(tab_id, pane_id);
             }
+            TermWindowNotif::FlashVisualBell { pane_id, color } => {
+                self.flash_visual_bell(pane_id, color);
+            }
             TermWindowNotif::MuxNotification(n) => match n {
                 MuxNotification::Alert {
                     alert: Alert::TitleMaybeChanged,
@@ -809,24 +1104,60 @@ impl TermWindow {
                     alert: Alert::Bell,
                     pane_id,
                 } => {
-                    match self.config.audible_bell {
-                        AudibleBell::SystemBeep => {
-                            Connection::get().expect("on main thread").beep();
-                        }
-                        AudibleBell::Disabled => {}
+                    let mux = Mux::get().expect("on main thread");
+                    if let Some(domain) = mux
+                        .get_pane(pane_id)
+                        .and_then(|pane| mux.get_domain(pane.domain_id()))
+                    {
+                        crate::sound::ring_audible_bell(&self.config, domain.domain_name());
                     }
 
                     log::info!("Ding! (this is the bell) in pane {}", pane_id);
                     self.emit_window_event("bell", Some(pane_id));
 
-                    let mut per_pane = self.pane_state(pane_id);
-                    per_pane.bell_start.replace(Instant::now());
-                    window.invalidate();
+                    if self.config.visual_bell.flash_on_bell {
+                        // The bell is urgent if it rang somewhere that
+                        // the user probably isn't looking
+                        let is_urgent = self.focused.is_none()
+                            || self
+                                .get_active_pane_no_overlay()
+                                .map_or(true, |pane| pane.pane_id() != pane_id);
+                        let palette = &self.config.resolved_palette;
+                        let color = if is_urgent {
+                            palette.visual_bell_urgent.or(palette.visual_bell)
+                        } else {
+                            palette.visual_bell
+                        };
+                        self.flash_visual_bell(pane_id, color);
+                    }
+                }
+                MuxNotification::Alert {
+                    alert:
+                        Alert::CommandCompleted {
+                            command,
+                            exit_status,
+                            duration,
+                        },
+                    pane_id,
+                } => {
+                    self.pane_command_completed(
+                        pane_id,
+                        watch::CommandInformation {
+                            command,
+                            exit_status,
+                            duration_seconds: duration.as_secs_f64(),
+                        },
+                    );
                 }
                 MuxNotification::PaneOutput(pane_id) => {
                     self.mux_pane_output_event(pane_id);
                 }
+                MuxNotification::PaneRemoved(pane_id) => {
+                    self.pane_watch_removed(pane_id);
+                }
                 MuxNotification::WindowInvalidated(_) => {
+                    // The active tab may have changed
+                    self.update_profile();
                     window.invalidate();
                 }
                 MuxNotification::WindowRemoved(window_id) => {
@@ -834,6 +1165,17 @@ impl TermWindow {
                         window.close();
                     }
                 }
+                MuxNotification::ActiveWorkspaceChanged(_) => {
+                    // In window_per_workspace mode, windows stay visible
+                    // and switch_to_workspace raises the relevant one
+                    if !self.config.window_per_workspace {
+                        if self.is_in_active_workspace() {
+                            window.show();
+                        } else {
+                            window.hide();
+                        }
+                    }
+                }
                 _ => {}
             },
             TermWindowNotif::EmitStatusUpdate => {
@@ -884,7 +1226,7 @@ impl TermWindow {
 
         let tab_id = tab.tab_id();
         if let Some(tab_overlay) = self.tab_state(tab_id).overlay.clone() {
-            return tab_overlay.pane_id() == pane_id;
+            return tab_overlay.pane_id() == pane_id || self.is_pane_in_tab_thumbnail(pane_id);
         }
 
         tab.contains_pane(pane_id)
@@ -892,13 +1234,33 @@ impl TermWindow {
 
     fn mux_pane_output_event(&mut self, pane_id: PaneId) {
         metrics::histogram!("mux.pane_output_event.rate", 1.);
+        self.pane_watch_output(pane_id);
+        self.update_suggestion(pane_id);
         if self.is_pane_visible(pane_id) {
             if let Some(ref win) = self.window {
-                win.invalidate();
+                match self.key_echo_pending.take() {
+                    Some((echo_pane, pressed))
+                        if echo_pane == pane_id && pressed.elapsed() <= KEY_ECHO_WINDOW =>
+                    {
+                        self.key_echo_paint.replace(pressed);
+                        win.invalidate_immediately();
+                    }
+                    _ => win.invalidate(),
+                }
             }
         }
     }
 
+    /// Called after a key press has been sent to `pane`.  If the pane
+    /// is local, remember it so that its echo can be painted without
+    /// waiting for the next frame.
+    fn expect_key_echo(&mut self, pane: &Rc<dyn Pane>) {
+        if self.config.low_latency_input && pane.downcast_ref::<LocalPane>().is_some() {
+            self.key_echo_pending
+                .replace((pane.pane_id(), Instant::now()));
+        }
+    }
+
     fn mux_pane_output_event_callback(
         n: MuxNotification,
         window: &Window,
@@ -913,7 +1275,7 @@ impl TermWindow {
         match n {
             MuxNotification::Alert {
                 pane_id,
-                alert: Alert::TitleMaybeChanged | Alert::Bell,
+                alert: Alert::TitleMaybeChanged | Alert::Bell | Alert::CommandCompleted { .. },
             }
             | MuxNotification::PaneOutput(pane_id) => {
                 // Ideally we'd check to see if pane_id is part of this window,
@@ -958,6 +1320,7 @@ impl TermWindow {
                     return true;
                 }
             }
+            MuxNotification::ActiveWorkspaceChanged(_) | MuxNotification::PaneRemoved(_) => {}
             _ => return true,
         }
 
@@ -966,6 +1329,44 @@ impl TermWindow {
         true
     }
 
+    fn register_memory_reporter(&self, tw: &Rc<RefCell<Self>>) {
+        let mux = Mux::get().expect("mux started and running on main thread");
+        let tw = Rc::downgrade(tw);
+        mux.register_memory_reporter(move |stats| match tw.upgrade() {
+            Some(tw) => {
+                // The window is busy if it is the one asking for the
+                // stats, in which case it adds its own
+                if let Ok(tw) = tw.try_borrow() {
+                    tw.memory_stats(stats);
+                }
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Adds the memory used by the glyph atlas and decoded images
+    /// of this window to `stats`
+    fn memory_stats(&self, stats: &mut Vec<MemoryStat>) {
+        if let Some(render_state) = self.render_state.as_ref() {
+            if let Ok(glyph_cache) = render_state.glyph_cache.try_borrow() {
+                let item = Some(format!("window {}", self.mux_window_id));
+                // The atlas texture has 4 bytes per pixel
+                let side = glyph_cache.atlas.size();
+                stats.push(MemoryStat::new(
+                    "glyph atlas",
+                    item.clone(),
+                    side * side * 4,
+                ));
+                stats.push(MemoryStat::new(
+                    "decoded images",
+                    item,
+                    glyph_cache.image_cache_memory_usage(),
+                ));
+            }
+        }
+    }
+
     fn subscribe_to_pane_updates(&self) {
         let window = self.window.clone().expect("window to be valid on startup");
         let mux_window_id = self.mux_window_id;
@@ -1141,23 +1542,46 @@ impl TermWindow {
     }
 
     pub fn config_was_reloaded(&mut self) {
+        let (profile, overrides) = self.profile_config_overrides();
         log::debug!(
-            "config was reloaded, overrides: {:?}",
-            self.config_overrides
+            "config was reloaded, profile: {:?}, overrides: {:?}",
+            profile,
+            overrides
         );
-        let config = match config::overridden_config(&self.config_overrides) {
+        let config = match config::overridden_config(&overrides) {
             Ok(config) => config,
             Err(err) => {
                 log::error!(
                     "Failed to apply config overrides to window: {:#}: {:?}",
                     err,
-                    self.config_overrides
+                    overrides
                 );
                 configuration()
             }
         };
-        self.config = config.clone();
-        self.palette.take();
+        self.applied_profile = profile;
+        if config.window_per_workspace != self.config.window_per_workspace {
+            if let Some(window) = self.window.as_ref() {
+                if config.window_per_workspace {
+                    window.show();
+                } else if !self.is_in_active_workspace() {
+                    window.hide();
+                }
+            }
+        }
+        // Only rebuild the state that depends on the options that
+        // changed; reloading the fonts and recreating the glyph atlas
+        // causes the window to flash
+        let changes = ConfigChanges::between(&self.config, &config);
+        log::debug!("config changes: {:?}", changes);
+        let prior_config = std::mem::replace(&mut self.config, config.clone());
+        if changes.affects_colors() {
+            self.palette.take();
+            self.contrast_cache.borrow_mut().clear();
+        }
+        self.shape_cache
+            .borrow_mut()
+            .resize(config.shape_cache_size);
 
         self.window_background =
             reload_background_image(&config, &self.window_background, &self.dimensions);
@@ -1174,28 +1598,44 @@ impl TermWindow {
         }
 
         self.show_scroll_bar = config.enable_scroll_bar;
-        self.shape_cache.borrow_mut().clear();
         self.input_map = InputMap::new(&config);
-        self.leader_is_down = None;
+        if changes.affects_input() {
+            self.cancel_leaders();
+        }
         let dimensions = self.dimensions;
 
-        if let Err(err) = self.fonts.config_changed(&config) {
-            log::error!("Failed to load font configuration: {:#}", err);
+        if changes.affects_fonts() {
+            self.shape_cache.borrow_mut().clear();
+            if let Err(err) = self.fonts.config_changed(&config) {
+                log::error!("Failed to load font configuration: {:#}", err);
+            }
+        }
+
+        if changes.affects_colors() {
+            self.begin_palette_transition(&prior_config);
         }
 
         if let Some(window) = mux.get_window(self.mux_window_id) {
-            let term_config: Arc<dyn TerminalConfiguration> =
-                Arc::new(TermConfig::with_config(config.clone()));
+            let term_config = TermConfig::with_config(config.clone());
             for tab in window.iter() {
                 for pane in tab.iter_panes() {
-                    pane.pane.set_config(Arc::clone(&term_config));
+                    let term_config: Arc<dyn TerminalConfiguration> =
+                        match mux.get_domain(pane.pane.domain_id()) {
+                            Some(domain) => Arc::new(term_config.for_domain(domain.domain_name())),
+                            None => Arc::new(TermConfig::with_config(config.clone())),
+                        };
+                    pane.pane.set_config(term_config);
                 }
             }
         };
 
         if let Some(window) = self.window.as_ref().map(|w| w.clone()) {
-            self.apply_scale_change(&dimensions, self.fonts.get_font_scale(), &window);
-            self.apply_dimensions(&dimensions, None, &window);
+            if changes.affects_fonts() {
+                self.apply_scale_change(&dimensions, self.fonts.get_font_scale(), &window);
+            }
+            if changes.affects_layout() {
+                self.apply_dimensions(&dimensions, None, &window);
+            }
             window.config_did_change(&config);
             window.invalidate();
         }
@@ -1421,10 +1861,15 @@ impl TermWindow {
 
             drop(window);
 
-            if let Some(tab) = self.get_active_pane_or_overlay() {
-                tab.focus_changed(true);
+            // Only report focus-in if the window itself has the focus;
+            // focus_changed will do so when the window is focused later
+            if self.focused.is_some() {
+                if let Some(tab) = self.get_active_pane_or_overlay() {
+                    tab.focus_changed(true);
+                }
             }
 
+            self.update_profile();
             self.update_title();
             self.update_scrollbar();
         }
@@ -1493,9 +1938,12 @@ impl TermWindow {
         };
 
         let gui_win = GuiWin::new(self);
+        let mut stats = mux.memory_stats();
+        self.memory_stats(&mut stats);
+        let memory = mux::memory::format_stats(&stats);
 
         let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
-            crate::overlay::show_debug_overlay(term, gui_win)
+            crate::overlay::show_debug_overlay(term, gui_win, memory)
         });
         self.assign_overlay(tab.tab_id(), overlay);
         promise::spawn::spawn(future).detach();
@@ -1537,6 +1985,195 @@ impl TermWindow {
         promise::spawn::spawn(future).detach();
     }
 
+    fn show_tab_overview(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        // The titles are captured up front, as with the tab navigator,
+        // but the thumbnails are rendered live by paint_tab_thumbnails
+        let (previews, active_tab_idx) = {
+            let window = mux
+                .get_window(self.mux_window_id)
+                .expect("to resolve my own window_id");
+            let previews: Vec<_> = window
+                .iter()
+                .enumerate()
+                .map(|(idx, tab)| tab_preview(tab, idx))
+                .collect();
+            (previews, window.get_active_idx())
+        };
+
+        let mux_window_id = self.mux_window_id;
+        let layout = ThumbnailLayout::default();
+        let overview_layout = Arc::clone(&layout);
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            tab_overview(
+                term,
+                previews,
+                active_tab_idx,
+                mux_window_id,
+                overview_layout,
+            )
+        });
+        self.tab_overview = Some((overlay.pane_id(), layout));
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Shows the modal described by lua code over the tab that
+    /// contains `pane_id`
+    pub fn show_lua_modal(&mut self, pane_id: PaneId, spec: ModalSpec) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux
+            .resolve_pane_id(pane_id)
+            .and_then(|(_, _, tab_id)| mux.get_tab(tab_id))
+        {
+            Some(tab) => tab,
+            None => return,
+        };
+        let window = GuiWin::new(self);
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            lua_modal(tab_id, term, spec, window, pane_id)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_command_history(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let pane = match tab.get_active_pane() {
+            Some(pane) => pane,
+            None => return,
+        };
+
+        let pane_id = pane.pane_id();
+        let clipboard = ClipboardHelper {
+            window: self.window.as_ref().unwrap().clone(),
+        };
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            command_history(tab_id, term, pane_id, clipboard)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_snippets(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let pane = match tab.get_active_pane() {
+            Some(pane) => pane,
+            None => return,
+        };
+        if self.config.snippets.is_empty() {
+            log::error!("ShowSnippets: no snippets are configured");
+            return;
+        }
+
+        let snippets = self.config.snippets.clone();
+        let pane_id = pane.pane_id();
+        let cwd = snippet_cwd(&*pane);
+        let window = self.window.as_ref().unwrap().clone();
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            snippet_picker(tab_id, term, snippets, pane_id, cwd, window)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Sends the snippet named `name` to `pane`, first prompting
+    /// for the values of its fields, if it has any
+    fn send_snippet(&mut self, pane: &Rc<dyn Pane>, name: &str) {
+        let snippet = match self.config.snippet_by_name(name) {
+            Some(snippet) => snippet.clone(),
+            None => {
+                log::error!("SendSnippet: there is no snippet named {}", name);
+                return;
+            }
+        };
+        let pane_id = pane.pane_id();
+        let cwd = snippet_cwd(&**pane);
+        let window = self.window.as_ref().unwrap().clone();
+
+        if snippet.fields().is_empty() {
+            let clipboard = if snippet.uses_clipboard() {
+                Some(window.get_clipboard(Clipboard::Clipboard))
+            } else {
+                None
+            };
+            promise::spawn::spawn(async move {
+                let clipboard = match clipboard {
+                    Some(clipboard) => clipboard.await.unwrap_or_default(),
+                    None => String::new(),
+                };
+                send_snippet_text(pane_id, snippet.expand(&cwd, &clipboard, &HashMap::new()));
+            })
+            .detach();
+            self.maybe_scroll_to_bottom_for_input(pane);
+            return;
+        }
+
+        let tab = match Mux::get()
+            .unwrap()
+            .get_active_tab_for_window(self.mux_window_id)
+        {
+            Some(tab) => tab,
+            None => return,
+        };
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            snippet_prompt(tab_id, term, snippet, pane_id, cwd, window)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_url_picker(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let pane = match tab.get_active_pane() {
+            Some(pane) => pane,
+            None => return,
+        };
+
+        // Consider the viewport and the recent scrollback above it
+        let dims = pane.get_dimensions();
+        let bottom = self
+            .get_viewport(pane.pane_id())
+            .unwrap_or(dims.physical_top)
+            + dims.viewport_rows as StableRowIndex;
+        let top = bottom
+            .saturating_sub(URL_PICKER_SCROLLBACK_ROWS)
+            .max(dims.scrollback_top);
+        let (_first, lines) =
+            pane.get_lines_with_hyperlinks_applied(top..bottom, &self.config.hyperlink_rules);
+        let entries: Vec<_> = collect_links(&lines)
+            .into_iter()
+            .filter(|entry| !is_respawn_pane_uri(entry.link.uri()))
+            .collect();
+
+        let pane_id = pane.pane_id();
+        let clipboard = ClipboardHelper {
+            window: self.window.as_ref().unwrap().clone(),
+        };
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            url_picker(tab_id, term, entries, pane_id, clipboard)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
     fn show_launcher(&mut self) {
         let mux = Mux::get().unwrap();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -1619,6 +2256,26 @@ impl TermWindow {
         &cache.zones
     }
 
+    /// Returns the column at which the input begins, if shell
+    /// integration shows that `cursor` follows a prompt on its row.
+    /// Only the row of the cursor is examined.
+    fn prompt_input_start(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        cursor: &StableCursorPosition,
+    ) -> Option<usize> {
+        let (_, lines) = pane.get_lines(cursor.y..cursor.y + 1);
+        let line = lines.first()?;
+        line.visible_cells()
+            .take_while(|(x, _)| *x < cursor.x)
+            .filter(|(_, cell)| {
+                cell.attrs().semantic_type() == wezterm_term::SemanticType::Prompt
+            })
+            .map(|(x, cell)| x + cell.width())
+            .last()
+            .filter(|&end| end <= cursor.x)
+    }
+
     fn scroll_to_prompt(&mut self, amount: isize) -> anyhow::Result<()> {
         let pane = match self.get_active_pane_or_overlay() {
             Some(pane) => pane,
@@ -1646,46 +2303,281 @@ impl TermWindow {
         Ok(())
     }
 
-    fn scroll_by_page(&mut self, amount: isize) -> anyhow::Result<()> {
-        let pane = match self.get_active_pane_or_overlay() {
-            Some(pane) => pane,
-            None => return Ok(()),
+    /// Selects the output of the most recently completed command,
+    /// scrolling it into view if necessary.
+    /// Returns false if there is no such output.
+    fn select_last_command_output(&mut self, pane: &Rc<dyn Pane>) -> bool {
+        let range = match SelectionRange::last_command_output(&**pane) {
+            Some(range) => range,
+            None => return false,
         };
-        let dims = pane.get_dimensions();
-        let position = self
-            .get_viewport(pane.pane_id())
-            .unwrap_or(dims.physical_top)
-            .saturating_add(amount * dims.viewport_rows as isize);
-        self.set_viewport(pane.pane_id(), Some(position), dims);
-        if let Some(win) = self.window.as_ref() {
-            win.invalidate();
+
+        {
+            let mut selection = self.selection(pane.pane_id());
+            selection.start = Some(range.start);
+            selection.range = Some(range);
+            selection.rectangular = false;
+            selection.seqno = pane.get_current_seqno();
         }
-        Ok(())
-    }
 
-    fn scroll_by_line(&mut self, amount: isize) -> anyhow::Result<()> {
-        let pane = match self.get_active_pane_or_overlay() {
-            Some(pane) => pane,
-            None => return Ok(()),
-        };
         let dims = pane.get_dimensions();
-        let position = self
+        let top = self
             .get_viewport(pane.pane_id())
-            .unwrap_or(dims.physical_top)
-            .saturating_add(amount);
-        self.set_viewport(pane.pane_id(), Some(position), dims);
+            .unwrap_or(dims.physical_top);
+        if range.start.y < top || range.start.y >= top + dims.viewport_rows as StableRowIndex {
+            self.set_viewport(pane.pane_id(), Some(range.start.y), dims);
+        }
+
         if let Some(win) = self.window.as_ref() {
             win.invalidate();
         }
-        Ok(())
+        true
     }
 
-    fn move_tab_relative(&mut self, delta: isize) -> anyhow::Result<()> {
+    /// Presents `text` in a read-only viewer overlay in the active tab
+    pub fn show_text_viewer(&mut self, title: String, text: String) {
         let mux = Mux::get().unwrap();
-        let window = mux
-            .get_window(self.mux_window_id)
-            .ok_or_else(|| anyhow!("no such window"))?;
-
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            text_viewer(tab_id, term, title, text)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Shows the recently captured log entries in the text viewer
+    fn show_log_viewer(&mut self) {
+        let mut text = String::new();
+        for entry in env_bootstrap::ringlog::get_entries() {
+            let (color, label) = match entry.level {
+                log::Level::Error => (31, "ERROR"),
+                log::Level::Warn => (91, "WARNING"),
+                log::Level::Info => (32, "INFO"),
+                log::Level::Debug => (94, "DEBUG"),
+                log::Level::Trace => (95, "TRACE"),
+            };
+            text.push_str(&format!(
+                "{} \x1b[{}m{}\x1b[0m \x1b[1m{}\x1b[0m > {}\n",
+                entry.then.format("%H:%M:%S%.3f"),
+                color,
+                label,
+                entry.target,
+                entry.msg
+            ));
+        }
+        self.show_text_viewer("Log".to_string(), text);
+    }
+
+    /// Starts recording the output of `pane` to a new asciicast file
+    /// in the `recording_directory`
+    fn start_recording(&self, pane: &Rc<dyn Pane>) -> anyhow::Result<()> {
+        ensure!(
+            pane.downcast_ref::<LocalPane>().is_some(),
+            "only panes in the local domain can be recorded"
+        );
+        let dir = self
+            .config
+            .recording_directory
+            .clone()
+            .unwrap_or_else(|| config::HOME_DIR.clone());
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        let path = dir.join(format!(
+            "wezterm-{}-{}.cast",
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            pane.pane_id()
+        ));
+        let dims = pane.get_dimensions();
+        mux::recording::start_recording(
+            pane.pane_id(),
+            &path,
+            dims.cols,
+            dims.viewport_rows,
+            Some(pane.get_title()),
+        )?;
+        wezterm_toast_notification::persistent_toast_notification(
+            "Recording started",
+            &path.display().to_string(),
+        );
+        Ok(())
+    }
+
+    fn stop_recording(&self, pane: &Rc<dyn Pane>) {
+        match mux::recording::stop_recording(pane.pane_id()) {
+            Some(path) => wezterm_toast_notification::persistent_toast_notification(
+                "Recording saved",
+                &path.display().to_string(),
+            ),
+            None => log::error!(
+                "StopRecording: pane {} is not being recorded",
+                pane.pane_id()
+            ),
+        }
+    }
+
+    /// Writes the output of the most recently completed command to
+    /// a file and opens it in the editor in a new tab, in the same
+    /// domain as `pane`.  The file is removed when the editor exits.
+    /// If no editor is configured, or the domain cannot see local
+    /// files, the output is shown in the internal viewer instead.
+    fn view_last_command_output_in_editor(&mut self, pane: &Rc<dyn Pane>) -> anyhow::Result<()> {
+        let range = SelectionRange::last_command_output(&**pane)
+            .ok_or_else(|| anyhow!("there is no completed command output in this pane"))?;
+        let text = Self::text_for_range(pane, &range);
+
+        let mux = Mux::get().unwrap();
+        let domain = mux
+            .get_domain(pane.domain_id())
+            .ok_or_else(|| anyhow!("pane has unresolvable domain id"))?;
+        let editor = if domain.downcast_ref::<LocalDomain>().is_some() {
+            self.config.resolve_editor()
+        } else {
+            // The file is written to the local filesystem,
+            // which a remote domain cannot see
+            None
+        };
+        let mut editor = match editor {
+            Some(editor) => editor,
+            None => {
+                self.show_text_viewer("Command Output".to_string(), text);
+                return Ok(());
+            }
+        };
+
+        let path = config::RUNTIME_DIR.join(format!(
+            "{}{}-{}.txt",
+            COMMAND_OUTPUT_FILE_PREFIX,
+            std::process::id(),
+            pane.pane_id()
+        ));
+
+        // An editor that runs inside WSL can't use Windows paths
+        let in_wsl = cfg!(windows)
+            && editor
+                .first()
+                .map(|program| config::is_wsl_launcher(program))
+                .unwrap_or(false);
+        let file_arg = path.to_string_lossy().to_string();
+        editor.push(if in_wsl {
+            config::windows_path_to_wsl(&file_arg).unwrap_or(file_arg)
+        } else {
+            file_arg
+        });
+
+        let spawn = SpawnCommand {
+            args: Some(editor),
+            domain: SpawnTabDomain::DomainName(domain.domain_name().to_string()),
+            ..Default::default()
+        };
+        let size = self.terminal_size;
+        let mux_window_id = self.mux_window_id;
+        let clipboard = ClipboardHelper {
+            window: self.window.as_ref().unwrap().clone(),
+        };
+        let term_config = Arc::new(TermConfig::with_config(self.config.clone()));
+
+        promise::spawn::spawn(async move {
+            let output_path = path.clone();
+            if let Err(err) = smol::unblock(move || write_command_output(&output_path, &text)).await
+            {
+                log::error!("ViewLastCommandOutputInEditor: {:#}", err);
+                return;
+            }
+
+            let editor_pane = match Self::spawn_command_internal(
+                spawn,
+                SpawnWhere::NewTab,
+                size,
+                mux_window_id,
+                clipboard,
+                term_config,
+                false,
+            )
+            .await
+            {
+                Ok(editor_pane) => editor_pane,
+                Err(err) => {
+                    log::error!("Failed to spawn editor: {:#}", err);
+                    smol::unblock(move || std::fs::remove_file(&path))
+                        .await
+                        .ok();
+                    return;
+                }
+            };
+
+            let started = Instant::now();
+            let editor_pane_id = editor_pane.pane_id();
+            drop(editor_pane);
+            loop {
+                Timer::after(Duration::from_secs(1)).await;
+                let mux = Mux::get().unwrap();
+                match mux.get_pane(editor_pane_id) {
+                    Some(pane) if !pane.is_dead() => {}
+                    _ => break,
+                }
+            }
+
+            if started.elapsed() < EDITOR_HANDOFF_TIME {
+                // The editor most likely passed the file on to an instance
+                // of itself that was already running and that opens it
+                // later, so the file is left for remove_stale_command_output
+                log::debug!(
+                    "editor exited right away; keeping {} for now",
+                    path.display()
+                );
+                return;
+            }
+            smol::unblock(move || std::fs::remove_file(&path))
+                .await
+                .ok();
+        })
+        .detach();
+        Ok(())
+    }
+
+    fn scroll_by_page(&mut self, amount: isize) -> anyhow::Result<()> {
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return Ok(()),
+        };
+        let dims = pane.get_dimensions();
+        let position = self
+            .get_viewport(pane.pane_id())
+            .unwrap_or(dims.physical_top)
+            .saturating_add(amount * dims.viewport_rows as isize);
+        self.set_viewport(pane.pane_id(), Some(position), dims);
+        if let Some(win) = self.window.as_ref() {
+            win.invalidate();
+        }
+        Ok(())
+    }
+
+    fn scroll_by_line(&mut self, amount: isize) -> anyhow::Result<()> {
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return Ok(()),
+        };
+        let dims = pane.get_dimensions();
+        let position = self
+            .get_viewport(pane.pane_id())
+            .unwrap_or(dims.physical_top)
+            .saturating_add(amount);
+        self.set_viewport(pane.pane_id(), Some(position), dims);
+        if let Some(win) = self.window.as_ref() {
+            win.invalidate();
+        }
+        Ok(())
+    }
+
+    fn move_tab_relative(&mut self, delta: isize) -> anyhow::Result<()> {
+        let mux = Mux::get().unwrap();
+        let window = mux
+            .get_window(self.mux_window_id)
+            .ok_or_else(|| anyhow!("no such window"))?;
+
         let max = window.len();
         ensure!(max > 0, "no more tabs");
 
@@ -1742,14 +2634,24 @@ impl TermWindow {
                 self.window.as_ref().unwrap().toggle_fullscreen();
             }
             Copy => {
-                let text = self.selection_text(pane);
+                let text = self.format_selection(pane, self.selection_text(pane));
                 self.copy_to_clipboard(
                     ClipboardCopyDestination::ClipboardAndPrimarySelection,
                     text,
                 );
             }
             CopyTo(dest) => {
-                let text = self.selection_text(pane);
+                let text = self.format_selection(pane, self.selection_text(pane));
+                self.copy_to_clipboard(*dest, text);
+            }
+            CopyAsDisplayed(dest) => {
+                let text = self.selection_text_with_wrap(pane, CopyLineWrap::Physical);
+                let text = self.format_selection(pane, text);
+                self.copy_to_clipboard(*dest, text);
+            }
+            CopyAsLogicalLines(dest) => {
+                let text = self.selection_text_with_wrap(pane, CopyLineWrap::Logical);
+                let text = self.format_selection(pane, text);
                 self.copy_to_clipboard(*dest, text);
             }
             Paste => {
@@ -1816,8 +2718,14 @@ impl TermWindow {
             ScrollByLine(n) => self.scroll_by_line(*n)?,
             ScrollToPrompt(n) => self.scroll_to_prompt(*n)?,
             ShowTabNavigator => self.show_tab_navigator(),
+            ShowTabOverview => self.show_tab_overview(),
             ShowDebugOverlay => self.show_debug_overlay(),
             ShowLauncher => self.show_launcher(),
+            ShowCommandHistory => self.show_command_history(),
+            ShowSnippets => self.show_snippets(),
+            SendSnippet(name) => self.send_snippet(pane, name),
+            ShowUrlPicker => self.show_url_picker(),
+            ShowLogViewer => self.show_log_viewer(),
             HideApplication => {
                 let con = Connection::get().expect("call on gui thread");
                 con.hide_application();
@@ -1837,9 +2745,19 @@ impl TermWindow {
                             None => anyhow::bail!("no active tab!?"),
                         };
 
+                        let message = match self.close_decision("quit", pane, true) {
+                            CloseDecision::Close => {
+                                let con = Connection::get().expect("call on gui thread");
+                                con.terminate_message_loop();
+                                return Ok(());
+                            }
+                            CloseDecision::Cancel => return Ok(()),
+                            CloseDecision::Prompt(message) => message,
+                        };
+
                         let window = self.window.clone().unwrap();
                         let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
-                            confirm_quit_program(term, window, tab_id)
+                            confirm_quit_program(term, window, tab_id, message)
                         });
                         self.assign_overlay(tab.tab_id(), overlay);
                         promise::spawn::spawn(future).detach();
@@ -1862,6 +2780,7 @@ impl TermWindow {
             CompleteSelectionOrOpenLinkAtMouseCursor(dest) => {
                 let text = self.selection_text(pane);
                 if !text.is_empty() {
+                    let text = self.format_selection(pane, text);
                     self.copy_to_clipboard(*dest, text);
                     let window = self.window.as_ref().unwrap();
                     window.invalidate();
@@ -1872,20 +2791,92 @@ impl TermWindow {
             CompleteSelection(dest) => {
                 let text = self.selection_text(pane);
                 if !text.is_empty() {
+                    let text = self.format_selection(pane, text);
                     self.copy_to_clipboard(*dest, text);
                     let window = self.window.as_ref().unwrap();
                     window.invalidate();
                 }
             }
+            SelectLastCommandOutput => {
+                self.select_last_command_output(pane);
+            }
+            CopyLastCommandOutput(dest) => {
+                if self.select_last_command_output(pane) {
+                    let text = self.selection_text(pane);
+                    if !text.is_empty() {
+                        let text = self.format_selection(pane, text);
+                        self.copy_to_clipboard(*dest, text);
+                    }
+                }
+            }
+            StartRecording => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    if let Err(err) = self.start_recording(&pane) {
+                        log::error!("StartRecording: {:#}", err);
+                    }
+                }
+            }
+            StopRecording => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    self.stop_recording(&pane);
+                }
+            }
+            ShowPaneNumbers => self.show_pane_numbers(),
+            PaneSelect { alphabet, mode } => self.show_pane_select(alphabet, *mode),
+            RotatePanes(direction) => self.rotate_panes(*direction),
+            ViewLastCommandOutputInEditor => {
+                if let Err(err) = self.view_last_command_output_in_editor(pane) {
+                    log::error!("ViewLastCommandOutputInEditor: {:#}", err);
+                }
+            }
             ClearScrollback(erase_mode) => {
                 pane.erase_scrollback(*erase_mode);
                 let window = self.window.as_ref().unwrap();
                 window.invalidate();
             }
-            Search(pattern) => {
+            Search(pattern) => self.show_search(pattern, None),
+            SearchWithOptions {
+                pattern,
+                whole_word,
+            } => self.show_search(pattern, Some(*whole_word)),
+            HighlightSearchMatches => {
                 if let Some(pane) = self.get_active_pane_no_overlay() {
-                    let search = SearchOverlay::with_pane(self, &pane, pattern.clone());
-                    self.assign_overlay_for_pane(pane.pane_id(), search);
+                    let pane_id = pane.pane_id();
+                    let search = self
+                        .pane_state(pane_id)
+                        .overlay
+                        .as_ref()
+                        .and_then(|overlay| {
+                            overlay
+                                .downcast_ref::<SearchOverlay>()
+                                .map(|search| (search.pattern(), search.active_result()))
+                        });
+                    let (pattern, active) = match search {
+                        Some((pattern, active)) => {
+                            self.cancel_overlay_for_pane(pane_id);
+                            self.set_last_search(pane_id, pattern.clone());
+                            (pattern, active)
+                        }
+                        None => match self.last_search(pane_id) {
+                            Some(pattern) => (pattern, None),
+                            None => return Ok(()),
+                        },
+                    };
+                    if pattern.is_empty() {
+                        self.clear_search_highlight(pane_id);
+                    } else {
+                        self.highlight_search_matches(&pane, pattern, active);
+                    }
+                }
+            }
+            ScrollToSearchMatch(amount) => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    self.scroll_to_search_match(&pane, *amount);
+                }
+            }
+            ClearSearchHighlight => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    self.clear_search_highlight(pane.pane_id());
                 }
             }
             QuickSelect => {
@@ -1894,6 +2885,37 @@ impl TermWindow {
                     self.assign_overlay_for_pane(pane.pane_id(), qa);
                 }
             }
+            ActivateWatch { watch, toast } => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    self.activate_watch(pane.pane_id(), *watch, *toast);
+                }
+            }
+            TogglePerformanceHud => {
+                self.show_perf_hud = !self.show_perf_hud;
+                if let Some(window) = self.window.as_ref() {
+                    window.invalidate();
+                }
+            }
+            ToggleLigatures => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    let mut state = self.pane_state(pane.pane_id());
+                    state.disable_ligatures = !state.disable_ligatures;
+                }
+                if let Some(window) = self.window.as_ref() {
+                    window.invalidate();
+                }
+            }
+            ToggleCommandBadges => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    let show = !self.show_command_badges(pane.pane_id());
+                    self.pane_state(pane.pane_id())
+                        .show_command_badges
+                        .replace(show);
+                    if let Some(window) = self.window.as_ref() {
+                        window.invalidate();
+                    }
+                }
+            }
             ActivateCopyMode => {
                 if let Some(pane) = self.get_active_pane_no_overlay() {
                     let copy = CopyOverlay::with_pane(self, &pane);
@@ -1934,59 +2956,243 @@ impl TermWindow {
                 };
                 tab.toggle_zoom();
             }
+            MovePaneToNewWindow => self.move_active_pane(None, None),
+            MovePaneToWindow(window_id) => self.move_active_pane(Some(*window_id), None),
+            MovePaneToWorkspace(name) => self.move_active_pane(None, Some(name.to_string())),
+            RespawnPane => self.respawn_pane(pane),
+            SwitchToWorkspace { name } => self.switch_to_workspace(name),
+            RenameWorkspace => self.rename_workspace(),
+            CloseWorkspace { confirm } => self.close_workspace(*confirm),
+            ActivateKeyTable {
+                name,
+                timeout_milliseconds,
+                replace_current,
+            } => self.activate_key_table(
+                name,
+                std::time::Duration::from_millis(
+                    timeout_milliseconds.unwrap_or_else(config::default_leader_timeout),
+                ),
+                *replace_current,
+            ),
+            ComposeCharacter => self.start_compose(compose::ComposeMode::Sequence),
+            InputCodepoint => self.start_compose(compose::ComposeMode::Codepoint),
+            StartKeyMacro {
+                name,
+                record_timing,
+            } => self.start_key_macro(name, *record_timing),
+            StopKeyMacro => self.stop_key_macro(),
+            PlayKeyMacro(name) => self.play_key_macro(pane, name),
+            AcceptSuggestion => self.accept_suggestion(pane, true),
+            AcceptSuggestionWord => self.accept_suggestion(pane, false),
+            SwitchProfile { name, tab } => self.switch_profile(name.as_deref(), *tab),
         };
         Ok(())
     }
 
+    /// Returns true if the mux window associated with this TermWindow
+    /// belongs to the workspace that is currently being displayed
+    fn is_in_active_workspace(&self) -> bool {
+        let mux = Mux::get().unwrap();
+        match mux.get_window(self.mux_window_id) {
+            Some(window) => window.get_workspace() == mux.active_workspace(),
+            None => true,
+        }
+    }
+
+    pub fn switch_to_workspace(&mut self, name: &str) {
+        let mux = Mux::get().unwrap();
+        if name == mux.active_workspace() {
+            return;
+        }
+        let windows = mux.iter_windows_in_workspace(name);
+        let is_new = windows.is_empty();
+        mux.set_active_workspace(name);
+        if self.config.window_per_workspace {
+            if let Some(window) = windows.first().and_then(|&id| {
+                crate::frontend::front_end().and_then(|fe| fe.gui_window_for_mux_window(id))
+            }) {
+                window.focus();
+            }
+        }
+        if is_new {
+            // Populate the new workspace with a window so that there is
+            // something to look at.  Domains that are attached to a remote
+            // mux will add any windows that the server has for this
+            // workspace once they have re-synchronized.
+            if let Some(def) = crate::workspace::workspace_definition(name) {
+                promise::spawn::spawn(async move {
+                    if let Err(err) = crate::workspace::spawn_workspace(&def).await {
+                        log::error!("Failed to open workspace {}: {:#}", def.name, err);
+                    }
+                })
+                .detach();
+            } else {
+                self.spawn_command(&SpawnCommand::default(), SpawnWhere::NewWindow);
+            }
+        }
+    }
+
+    /// Moves the active pane into a new tab in `window_id`, or into a
+    /// new window in `workspace` (or the active workspace) if no window
+    /// is specified.
+    fn move_active_pane(&mut self, window_id: Option<MuxWindowId>, workspace: Option<String>) {
+        let pane = match self.get_active_pane_no_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        if pane.downcast_ref::<ClientPane>().is_some() {
+            // The topology of a remote mux is owned by the server;
+            // moving the pane only on this side would leave us out
+            // of sync with it.
+            log::error!("panes in a remote mux must be moved using `wezterm cli move-pane`");
+            return;
+        }
+
+        let mux = Mux::get().unwrap();
+        let pane_id = pane.pane_id();
+        let result = match window_id {
+            Some(window_id) => mux.move_pane_to_window(pane_id, window_id).map(|_| ()),
+            None => mux.move_pane_to_new_window(pane_id, workspace).map(|_| ()),
+        };
+        if let Err(err) = result {
+            log::error!("failed to move pane {}: {:#}", pane_id, err);
+        }
+    }
+
+    /// Relaunches the command in `pane` after it has exited
+    fn respawn_pane(&self, pane: &Rc<dyn Pane>) {
+        let mux = Mux::get().unwrap();
+        let pane_id = pane.pane_id();
+        let domain = match mux.get_domain(pane.domain_id()) {
+            Some(domain) => domain,
+            None => return,
+        };
+        promise::spawn::spawn(async move {
+            if let Err(err) = domain.respawn_pane(pane_id).await {
+                log::error!("failed to respawn pane {}: {:#}", pane_id, err);
+            }
+        })
+        .detach();
+    }
+
     fn do_open_link_at_mouse_cursor(&self, pane: &Rc<dyn Pane>) {
         // They clicked on a link, so let's open it!
-        // We need to ensure that we spawn the `open` call outside of the context
-        // of our window loop; on Windows it can cause a panic due to
-        // triggering our WndProc recursively.
-        // We get that assurance for free as part of the async dispatch that we
-        // perform below; here we allow the user to define an `open-uri` event
-        // handler that can bypass the normal `open::that` functionality.
         if let Some(link) = self.current_highlight.as_ref().cloned() {
-            let window = GuiWin::new(self);
-            let pane = PaneObject::new(pane);
-
-            async fn open_uri(
-                lua: Option<Rc<mlua::Lua>>,
-                window: GuiWin,
-                pane: PaneObject,
-                link: String,
-            ) -> anyhow::Result<()> {
-                let default_click = match lua {
-                    Some(lua) => {
-                        let args = lua.pack_multi((window, pane, link.clone()))?;
-                        config::lua::emit_event(&lua, ("open-uri".to_string(), args))
-                            .await
-                            .map_err(|e| {
-                                log::error!("while processing open-uri event: {:#}", e);
-                                e
-                            })?
+            // Rules may require modifiers to be held while clicking
+            if let Some(mods) = link
+                .params()
+                .get(RULE_MODS_PARAM)
+                .filter(|_| link.is_implicit())
+            {
+                let required = match config::parse_modifiers(mods) {
+                    Ok(mods) => mods,
+                    Err(err) => {
+                        log::error!("hyperlink rule: {}", err);
+                        return;
                     }
-                    None => true,
                 };
-                if default_click {
-                    log::info!("clicking {}", link);
-                    if let Err(err) = open::that(&link) {
-                        log::error!("failed to open {}: {:?}", link, err);
-                    }
+                let held = self
+                    .current_mouse_event
+                    .as_ref()
+                    .map(|event| event.modifiers - (Modifiers::LEFT_ALT | Modifiers::RIGHT_ALT))
+                    .unwrap_or(Modifiers::NONE);
+                if held != required {
+                    return;
                 }
-                Ok(())
             }
+            self.open_hyperlink(pane, &link);
+        }
+    }
 
-            promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
-                open_uri(lua, window, pane, link.uri().to_string())
-            }))
-            .detach();
+    /// Opens `link`, which was displayed in `pane`.
+    /// We need to ensure that we spawn the `open` call outside of the context
+    /// of our window loop; on Windows it can cause a panic due to
+    /// triggering our WndProc recursively.
+    /// We get that assurance for free as part of the async dispatch that we
+    /// perform below; here we allow the user to define an `open-uri` event
+    /// handler that can bypass the normal `open::that` functionality.
+    pub fn open_hyperlink(&self, pane: &Rc<dyn Pane>, link: &Hyperlink) {
+        if is_respawn_pane_uri(link.uri()) {
+            if is_trusted_respawn_pane_uri(link.uri()) {
+                self.respawn_pane(pane);
+            } else {
+                log::warn!("ignoring a respawn link that was not generated by wezterm");
+            }
+            return;
         }
+        // Links produced by hyperlink_rules may carry routing
+        // options from the rule that matched them
+        let params = if link.is_implicit() {
+            link.params().clone()
+        } else {
+            HashMap::new()
+        };
+
+        let event = params
+            .get(RULE_EVENT_PARAM)
+            .cloned()
+            .unwrap_or_else(|| "open-uri".to_string());
+        let open_with = params.get(RULE_OPEN_WITH_PARAM).cloned();
+        let editor = if event == config::OPEN_IN_EDITOR_EVENT {
+            spawn::editor_spawn_command(pane, link.uri()).map(|spawn| (pane.pane_id(), spawn))
+        } else {
+            None
+        };
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+        let uri = link.uri().to_string();
+
+        async fn open_uri(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: PaneObject,
+            link: String,
+            event: String,
+            open_with: Option<String>,
+            editor: Option<(PaneId, SpawnCommand)>,
+        ) -> anyhow::Result<()> {
+            let notify_window = window.window.clone();
+            let default_click = match lua {
+                Some(lua) => {
+                    let args = lua.pack_multi((window, pane, link.clone()))?;
+                    config::lua::emit_event(&lua, (event.clone(), args))
+                        .await
+                        .map_err(|e| {
+                            log::error!("while processing {} event: {:#}", event, e);
+                            e
+                        })?
+                }
+                None => true,
+            };
+            if default_click {
+                if let Some((pane_id, spawn)) = editor {
+                    notify_window.notify(TermWindowNotif::PerformAssignment {
+                        pane_id,
+                        assignment: KeyAssignment::SpawnCommandInNewTab(spawn),
+                    });
+                    return Ok(());
+                }
+                let link = wsl_link_to_windows(link);
+                log::info!("clicking {}", link);
+                let result = match &open_with {
+                    Some(program) => open::with(&link, program),
+                    None => open::that(&link),
+                };
+                if let Err(err) = result {
+                    log::error!("failed to open {}: {:?}", link, err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            open_uri(lua, window, pane, uri, event, open_with, editor)
+        }))
+        .detach();
     }
     fn close_current_pane(&mut self, confirm: bool) {
-        let mux_window_id = self.mux_window_id;
         let mux = Mux::get().unwrap();
-        let tab = match mux.get_active_tab_for_window(mux_window_id) {
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
             Some(tab) => tab,
             None => return,
         };
@@ -1994,17 +3200,29 @@ impl TermWindow {
             Some(p) => p,
             None => return,
         };
+        self.close_pane(&pane, confirm);
+    }
 
+    fn close_pane(&mut self, pane: &Rc<dyn Pane>, confirm: bool) {
+        let mux_window_id = self.mux_window_id;
+        let mux = Mux::get().unwrap();
         let pane_id = pane.pane_id();
-        if confirm && !pane.can_close_without_prompting() {
-            let window = self.window.clone().unwrap();
-            let (overlay, future) = start_overlay_pane(self, &pane, move |pane_id, term| {
-                confirm_close_pane(pane_id, term, mux_window_id, window)
-            });
-            self.assign_overlay_for_pane(pane_id, overlay);
-            promise::spawn::spawn(future).detach();
+        let decision = if confirm {
+            self.close_decision("pane", pane, !pane.can_close_without_prompting())
         } else {
-            mux.remove_pane(pane_id);
+            CloseDecision::Close
+        };
+        match decision {
+            CloseDecision::Close => mux.remove_pane(pane_id),
+            CloseDecision::Cancel => {}
+            CloseDecision::Prompt(message) => {
+                let window = self.window.clone().unwrap();
+                let (overlay, future) = start_overlay_pane(self, pane, move |pane_id, term| {
+                    confirm_close_pane(pane_id, term, mux_window_id, window, message)
+                });
+                self.assign_overlay_for_pane(pane_id, overlay);
+                promise::spawn::spawn(future).detach();
+            }
         }
     }
 
@@ -2016,15 +3234,120 @@ impl TermWindow {
         };
         let tab_id = tab.tab_id();
         let mux_window_id = self.mux_window_id;
-        if confirm && !tab.can_close_without_prompting() {
-            let window = self.window.clone().unwrap();
-            let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
-                confirm_close_tab(tab_id, term, mux_window_id, window)
-            });
-            self.assign_overlay(tab_id, overlay);
-            promise::spawn::spawn(future).detach();
-        } else {
-            mux.remove_tab(tab_id);
+        let needs_prompt = !tab.can_close_without_prompting();
+        let decision = match tab.get_active_pane() {
+            Some(pane) if confirm => self.close_decision("tab", &pane, needs_prompt),
+            None if confirm && needs_prompt => CloseDecision::Prompt(None),
+            _ => CloseDecision::Close,
+        };
+        match decision {
+            CloseDecision::Close => mux.remove_tab(tab_id),
+            CloseDecision::Cancel => {}
+            CloseDecision::Prompt(message) => {
+                let window = self.window.clone().unwrap();
+                let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+                    confirm_close_tab(tab_id, term, mux_window_id, window, message)
+                });
+                self.assign_overlay(tab_id, overlay);
+                promise::spawn::spawn(future).detach();
+            }
+        }
+    }
+
+    /// Returns the name of the workspace that this window belongs to
+    fn workspace_name(&self) -> Option<String> {
+        Mux::get()
+            .unwrap()
+            .get_window(self.mux_window_id)
+            .map(|window| window.get_workspace().to_string())
+    }
+
+    fn rename_workspace(&mut self) {
+        let workspace = match self.workspace_name() {
+            Some(workspace) => workspace,
+            None => return,
+        };
+        let tab = match Mux::get()
+            .unwrap()
+            .get_active_tab_for_window(self.mux_window_id)
+        {
+            Some(tab) => tab,
+            None => return,
+        };
+        let window = self.window.clone().unwrap();
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            rename_workspace_prompt(term, workspace, window, tab_id)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn close_workspace(&mut self, confirm: bool) {
+        let mux = Mux::get().unwrap();
+        let workspace = match self.workspace_name() {
+            Some(workspace) => workspace,
+            None => return,
+        };
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let needs_prompt = !mux.can_close_workspace_without_prompting(&workspace);
+        let decision = match tab.get_active_pane() {
+            Some(pane) if confirm => self.close_decision("workspace", &pane, needs_prompt),
+            None if confirm && needs_prompt => CloseDecision::Prompt(None),
+            _ => CloseDecision::Close,
+        };
+        match decision {
+            CloseDecision::Close => crate::workspace::close_workspace(&workspace),
+            CloseDecision::Cancel => {}
+            CloseDecision::Prompt(message) => {
+                let window = self.window.clone().unwrap();
+                let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+                    confirm_close_workspace(term, workspace, window, tab_id, message)
+                });
+                self.assign_overlay(tab.tab_id(), overlay);
+                promise::spawn::spawn(future).detach();
+            }
+        }
+    }
+
+    /// Decides how to handle a request to close the `kind` of thing
+    /// ("pane", "tab", "window", "workspace" or "quit") that contains `pane`.
+    /// `needs_prompt` is the default decision, which the `confirm-close`
+    /// event can override.
+    fn close_decision(&self, kind: &str, pane: &Rc<dyn Pane>, needs_prompt: bool) -> CloseDecision {
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+        let decision = match config::run_immediate_with_lua_config(|lua| {
+            if let Some(lua) = lua {
+                let v = config::lua::emit_sync_callback(
+                    &*lua,
+                    (
+                        "confirm-close".to_string(),
+                        (window, pane, kind.to_string(), needs_prompt),
+                    ),
+                )?;
+                Ok(match v {
+                    mlua::Value::Nil => None,
+                    mlua::Value::Boolean(true) => Some(CloseDecision::Close),
+                    mlua::Value::Boolean(false) => Some(CloseDecision::Cancel),
+                    v => Some(CloseDecision::Prompt(Some(String::from_lua(v, &*lua)?))),
+                })
+            } else {
+                Ok(None)
+            }
+        }) {
+            Ok(decision) => decision,
+            Err(err) => {
+                log::warn!("confirm-close: {:#}", err);
+                None
+            }
+        };
+        match decision {
+            Some(decision) => decision,
+            None if needs_prompt => CloseDecision::Prompt(None),
+            None => CloseDecision::Close,
         }
     }
 
@@ -2038,12 +3361,30 @@ impl TermWindow {
         self.activate_tab_relative(0)
     }
 
+    /// Starts showing the visual bell in the specified pane
+    fn flash_visual_bell(&mut self, pane_id: PaneId, color: Option<RgbColor>) {
+        self.pane_state(pane_id).bell.replace(RingingBell {
+            start: Instant::now(),
+            color,
+        });
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
     pub fn pane_state(&self, pane_id: PaneId) -> RefMut<PaneState> {
         RefMut::map(self.pane_state.borrow_mut(), |state| {
             state.entry(pane_id).or_insert_with(PaneState::default)
         })
     }
 
+    /// Returns true if command badges should be rendered for `pane_id`
+    pub fn show_command_badges(&self, pane_id: PaneId) -> bool {
+        self.pane_state(pane_id)
+            .show_command_badges
+            .unwrap_or(self.config.show_command_badges)
+    }
+
     pub fn tab_state(&self, tab_id: TabId) -> RefMut<TabState> {
         RefMut::map(self.tab_state.borrow_mut(), |state| {
             state.entry(tab_id).or_insert_with(TabState::default)
@@ -2077,6 +3418,7 @@ impl TermWindow {
         };
 
         let mut state = self.pane_state(pane_id);
+        state.viewport_dims = Some(dims);
         if pos != state.viewport {
             state.viewport = pos;
 
@@ -2095,12 +3437,73 @@ impl TermWindow {
         self.window.as_ref().unwrap().invalidate();
     }
 
+    /// Returns the number of rows that the viewport of `pane_id` is
+    /// scrolled back from the bottom of the scrollback
+    pub fn get_scroll_offset(&self, pane_id: PaneId, dims: &RenderableDimensions) -> usize {
+        match self.get_viewport(pane_id) {
+            Some(top) => dims.physical_top.saturating_sub(top).max(0) as usize,
+            None => 0,
+        }
+    }
+
+    /// Scrolls the viewport of `pane_id` back `offset` rows from the
+    /// bottom of the scrollback
+    pub fn set_scroll_offset(
+        &mut self,
+        pane_id: PaneId,
+        offset: usize,
+        dims: RenderableDimensions,
+    ) {
+        let position = if offset == 0 {
+            None
+        } else {
+            Some(dims.physical_top.saturating_sub(offset as StableRowIndex))
+        };
+        self.set_viewport(pane_id, position, dims);
+    }
+
+    /// When `pane` has been resized since its viewport was positioned,
+    /// for example by zooming it or resizing the window, moves the
+    /// viewport so that it is scrolled back from the bottom by the
+    /// same number of rows as before
+    fn restore_scroll_offset(&mut self, pane: &Rc<dyn Pane>) {
+        let dims = pane.get_dimensions();
+        let prior = {
+            let mut state = self.pane_state(pane.pane_id());
+            match (state.viewport, state.viewport_dims.replace(dims)) {
+                (Some(top), Some(prior))
+                    if prior.cols != dims.cols || prior.viewport_rows != dims.viewport_rows =>
+                {
+                    Some((top, prior))
+                }
+                _ => None,
+            }
+        };
+        if let Some((top, prior)) = prior {
+            let offset = prior.physical_top.saturating_sub(top).max(0) as usize;
+            self.set_scroll_offset(pane.pane_id(), offset, dims);
+        }
+    }
+
     fn maybe_scroll_to_bottom_for_input(&mut self, pane: &Rc<dyn Pane>) {
         if self.config.scroll_to_bottom_on_input {
             self.scroll_to_bottom(pane);
         }
     }
 
+    /// Applies the `input_normalization` for the domain of `pane`
+    /// to text that is about to be sent to it
+    fn normalize_input(&self, pane: &Rc<dyn Pane>, text: &str) -> String {
+        let domain_name = Mux::get()
+            .and_then(|mux| mux.get_domain(pane.domain_id()))
+            .map(|domain| domain.domain_name().to_string())
+            .unwrap_or_default();
+        self.config
+            .input_normalization_for_domain(&domain_name)
+            .apply(text)
+            .into_owned()
+    }
+
     fn scroll_to_bottom(&mut self, pane: &Rc<dyn Pane>) {
         self.pane_state(pane.pane_id()).viewport = None;
     }
@@ -2193,6 +3596,12 @@ impl TermWindow {
                         .iter()
                         .find(|p| p.is_active)
                         .map(|p| self.pos_pane_to_pane_info(p)),
+                    watch_alert: tab
+                        .iter_panes()
+                        .iter()
+                        .find_map(|p| self.pane_watch_alert(p.pane.pane_id()))
+                        .map(String::from),
+                    tab_color: tab.get_color().map(|color| color.to_rgb_string()),
                 }
             })
             .collect()
@@ -2302,3 +3711,59 @@ impl TermWindow {
         self.update_title();
     }
 }
+
+/// On Windows, a `file://` link that was produced by a program running
+/// inside WSL refers to a path inside WSL; translate it to the equivalent
+/// windows path so that it can be opened.
+fn wsl_link_to_windows(link: String) -> String {
+    if !cfg!(windows) {
+        return link;
+    }
+    let url = match url::Url::parse(&link) {
+        Ok(url) if url.scheme() == "file" => url,
+        _ => return link,
+    };
+    let path = match percent_encoding::percent_decode_str(url.path()).decode_utf8() {
+        Ok(path) => path,
+        Err(_) => return link,
+    };
+    match config::wsl_path_to_windows(&path, None) {
+        Some(path) => path,
+        None => link,
+    }
+}
+
+/// Writes the output of a command to `path` for an editor to open,
+/// first removing any stale files that earlier editors left behind
+fn write_command_output(path: &std::path::Path, text: &str) -> anyhow::Result<()> {
+    std::fs::create_dir_all(&*config::RUNTIME_DIR)?;
+    remove_stale_command_output();
+    std::fs::write(path, text)
+        .with_context(|| format!("writing command output to {}", path.display()))
+}
+
+/// Removes the command output files that are older than
+/// STALE_COMMAND_OUTPUT_AGE
+fn remove_stale_command_output() {
+    let entries = match std::fs::read_dir(&*config::RUNTIME_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let is_command_output = entry
+            .file_name()
+            .to_str()
+            .map(|name| name.starts_with(COMMAND_OUTPUT_FILE_PREFIX))
+            .unwrap_or(false);
+        let is_stale = entry
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map(|age| age >= STALE_COMMAND_OUTPUT_AGE)
+            .unwrap_or(false);
+        if is_command_output && is_stale {
+            std::fs::remove_file(entry.path()).ok();
+        }
+    }
+}
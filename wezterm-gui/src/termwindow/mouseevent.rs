@@ -1,18 +1,19 @@
 use crate::tabbar::TabBarItem;
 use crate::termwindow::keyevent::window_mods_to_termwiz_mods;
-use crate::termwindow::{PositionedSplit, ScrollHit, UIItem, UIItemType, TMB};
+use crate::termwindow::{PositionedSplit, ScrollHit, TermWindowNotif, UIItem, UIItemType, TMB};
 use ::window::{
     MouseButtons as WMB, MouseCursor, MouseEvent, MouseEventKind as WMEK, MousePress, WindowOps,
 };
-use config::keyassignment::{MouseEventTrigger, SpawnTabDomain};
-use mux::pane::Pane;
+use config::keyassignment::{GestureDirection, MouseEventTrigger, SpawnTabDomain};
+use mux::pane::{Pane, PaneId};
 use mux::tab::SplitDirection;
 use mux::Mux;
+use smol::Timer;
 use std::convert::TryInto;
 use std::ops::Sub;
 use std::rc::Rc;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use wezterm_term::input::MouseEventKind as TMEK;
 use wezterm_term::{LastMouseClick, StableRowIndex};
 
@@ -134,7 +135,11 @@ impl super::TermWindow {
                 self.current_mouse_buttons.push(*press);
             }
 
-            WMEK::VertWheel(amount) if !pane.is_mouse_grabbed() && !pane.is_alt_screen_active() => {
+            WMEK::VertWheel(amount)
+                if !pane.is_mouse_grabbed()
+                    && !pane.is_alt_screen_active()
+                    && !self.has_wheel_binding(&event) =>
+            {
                 // adjust viewport
                 let dims = pane.get_dimensions();
                 let position = self
@@ -177,6 +182,10 @@ impl super::TermWindow {
         }
 
         let ui_item = self.resolve_ui_item(&event);
+        if ui_item.is_some() {
+            // Over the tab bar, scroll bar or a split rather than a pane
+            self.pending_pane_focus.take();
+        }
 
         match (self.last_ui_item.take(), &ui_item) {
             (Some(prior), Some(item)) => {
@@ -199,6 +208,117 @@ impl super::TermWindow {
         }
     }
 
+    /// Called when the mouse leaves the window
+    pub fn mouse_leave_impl(&mut self) {
+        self.pending_pane_focus.take();
+    }
+
+    /// Sends a mouse event to the pane, coalescing motion reports so
+    /// that they are sent no more often than mouse_motion_report_interval.
+    fn send_mouse_event_to_pane(&mut self, pane: &Rc<dyn Pane>, event: wezterm_term::MouseEvent) {
+        let interval = Duration::from_millis(self.config.mouse_motion_report_interval);
+
+        if event.kind != TMEK::Move || self.config.mouse_motion_report_interval == 0 {
+            // Deliver any held back motion first so that the
+            // application sees the events in order
+            self.flush_pending_mouse_motion();
+            pane.mouse_event(event).ok();
+            return;
+        }
+
+        let now = Instant::now();
+        let due = self.last_mouse_motion_report + interval;
+        if now >= due && self.pending_mouse_motion.is_none() {
+            self.last_mouse_motion_report = now;
+            pane.mouse_event(event).ok();
+            return;
+        }
+
+        let already_scheduled = self.pending_mouse_motion.is_some();
+        if let Some((pending, _)) = self.pending_mouse_motion.as_ref() {
+            if pending.pane_id() != pane.pane_id() {
+                self.flush_pending_mouse_motion();
+            }
+        }
+        self.pending_mouse_motion = Some((Rc::clone(pane), event));
+
+        if !already_scheduled {
+            let window = self.window.clone().unwrap();
+            promise::spawn::spawn(async move {
+                Timer::at(due).await;
+                window.notify(TermWindowNotif::Apply(Box::new(|tw| {
+                    tw.flush_pending_mouse_motion();
+                })));
+            })
+            .detach();
+        }
+    }
+
+    /// Sends the motion report held back by send_mouse_event_to_pane, if any
+    pub fn flush_pending_mouse_motion(&mut self) {
+        if let Some((pane, event)) = self.pending_mouse_motion.take() {
+            self.last_mouse_motion_report = Instant::now();
+            pane.mouse_event(event).ok();
+        }
+    }
+
+    /// Activates the pane after pane_focus_follows_mouse_delay, provided
+    /// that the mouse is still over it at that time.
+    fn schedule_pane_focus(&mut self, pane_id: PaneId) {
+        if let Some((pending, _)) = self.pending_pane_focus {
+            if pending == pane_id {
+                // Already counting down for this pane
+                return;
+            }
+        }
+
+        let started = Instant::now();
+        self.pending_pane_focus.replace((pane_id, started));
+
+        let due = started + Duration::from_millis(self.config.pane_focus_follows_mouse_delay);
+        let window = self.window.clone().unwrap();
+        promise::spawn::spawn(async move {
+            Timer::at(due).await;
+            window.notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                if tw.pending_pane_focus != Some((pane_id, started)) {
+                    // The mouse moved elsewhere in the meantime
+                    return;
+                }
+                tw.pending_pane_focus.take();
+
+                let mux = Mux::get().unwrap();
+                if let Some(tab) = mux.get_active_tab_for_window(tw.mux_window_id) {
+                    if let Some(pos) = tab
+                        .iter_panes()
+                        .into_iter()
+                        .find(|pos| pos.pane.pane_id() == pane_id)
+                    {
+                        tab.set_active_idx(pos.index);
+                        if let Some(window) = tw.window.as_ref() {
+                            window.invalidate();
+                        }
+                    }
+                }
+            })));
+        })
+        .detach();
+    }
+
+    /// Returns true if the user has bound an action to this wheel
+    /// event, in which case it should not scroll the viewport.
+    fn has_wheel_binding(&self, event: &MouseEvent) -> bool {
+        match wheel_to_tmb(&event.kind) {
+            Some(button) => self
+                .input_map
+                .lookup_mouse(
+                    MouseEventTrigger::Down { streak: 1, button },
+                    event.modifiers,
+                )
+                .is_some(),
+            None => false,
+        }
+    }
+
     fn drag_split(
         &mut self,
         mut item: UIItem,
@@ -457,18 +577,25 @@ impl super::TermWindow {
                         }
                         WMEK::Move => {
                             if self.config.pane_focus_follows_mouse {
-                                let mux = Mux::get().unwrap();
-                                mux.get_active_tab_for_window(self.mux_window_id)
-                                    .map(|tab| tab.set_active_idx(pos.index));
-
-                                pane = Rc::clone(&pos.pane);
-                                context.invalidate();
+                                if self.config.pane_focus_follows_mouse_delay == 0 {
+                                    let mux = Mux::get().unwrap();
+                                    mux.get_active_tab_for_window(self.mux_window_id)
+                                        .map(|tab| tab.set_active_idx(pos.index));
+
+                                    pane = Rc::clone(&pos.pane);
+                                    context.invalidate();
+                                } else {
+                                    self.schedule_pane_focus(pos.pane.pane_id());
+                                }
                             }
                         }
                         WMEK::Release(_) => {}
                         WMEK::VertWheel(_) => {}
                         WMEK::HorzWheel(_) => {}
                     }
+                } else {
+                    // Back over the active pane before the delay elapsed
+                    self.pending_pane_focus.take();
                 }
                 x = x.saturating_sub(pos.left);
                 y = y.saturating_sub(pos.top as i64);
@@ -484,6 +611,12 @@ impl super::TermWindow {
             }
         }
 
+        if let WMEK::Press(MousePress::Left) = &event.kind {
+            if y >= 0 && self.pane_widget_click(&pane, x, y as usize) {
+                return;
+            }
+        }
+
         let dims = pane.get_dimensions();
         let stable_row = self
             .get_viewport(pane.pane_id())
@@ -498,6 +631,8 @@ impl super::TermWindow {
         );
         let new_highlight = if top == stable_row {
             if let Some(line) = lines.get_mut(0) {
+                // The cells hidden by a wide grapheme are part of it
+                let x = line.grapheme_range(x).start;
                 if let Some(cell) = line.cells().get(x) {
                     cell.attrs().hyperlink().cloned()
                 } else {
@@ -542,6 +677,24 @@ impl super::TermWindow {
             MouseCursor::Text
         }));
 
+        match &event.kind {
+            WMEK::Press(press) => {
+                let button = mouse_press_to_tmb(press);
+                self.mouse_gesture = if self.input_map.has_gestures(button) {
+                    Some(MouseGesture::new(button, event.coords.x, event.coords.y))
+                } else {
+                    None
+                };
+            }
+            WMEK::Move => {
+                let threshold = 2 * self.render_metrics.cell_size.height;
+                if let Some(gesture) = self.mouse_gesture.as_mut() {
+                    gesture.update(event.coords.x, event.coords.y, threshold);
+                }
+            }
+            _ => {}
+        }
+
         let event_trigger_type = match &event.kind {
             WMEK::Press(press) => {
                 let press = mouse_press_to_tmb(press);
@@ -557,8 +710,16 @@ impl super::TermWindow {
             }
             WMEK::Release(press) => {
                 let press = mouse_press_to_tmb(press);
-                match self.last_mouse_click.as_ref() {
-                    Some(LastMouseClick { streak, button, .. }) if *button == press => {
+                match (self.mouse_gesture.take(), self.last_mouse_click.as_ref()) {
+                    (Some(gesture), _)
+                        if gesture.button == press && !gesture.directions.is_empty() =>
+                    {
+                        Some(MouseEventTrigger::Gesture {
+                            button: gesture.button,
+                            directions: gesture.directions,
+                        })
+                    }
+                    (_, Some(LastMouseClick { streak, button, .. })) if *button == press => {
                         Some(MouseEventTrigger::Up {
                             streak: *streak,
                             button: press,
@@ -589,7 +750,8 @@ impl super::TermWindow {
                     None
                 }
             }
-            WMEK::VertWheel(_) | WMEK::HorzWheel(_) => None,
+            WMEK::VertWheel(_) | WMEK::HorzWheel(_) => wheel_to_tmb(&event.kind)
+                .map(|button| MouseEventTrigger::Down { streak: 1, button }),
         };
 
         if !pane.is_mouse_grabbed()
@@ -637,22 +799,29 @@ impl super::TermWindow {
                         TMB::None
                     }
                 }
-                WMEK::VertWheel(amount) => {
-                    if amount > 0 {
-                        TMB::WheelUp(amount as usize)
-                    } else {
-                        TMB::WheelDown((-amount) as usize)
-                    }
+                WMEK::VertWheel(_) | WMEK::HorzWheel(_) => {
+                    wheel_to_tmb(&event.kind).unwrap_or(TMB::None)
                 }
-                WMEK::HorzWheel(_) => TMB::None,
             },
             x,
             y,
+            x_pixel_offset: event
+                .coords
+                .x
+                .sub(self.config.window_padding.left as isize)
+                .max(0)
+                % self.render_metrics.cell_size.width,
+            y_pixel_offset: event
+                .coords
+                .y
+                .sub(self.config.window_padding.top as isize)
+                .max(0)
+                % self.render_metrics.cell_size.height,
             modifiers: window_mods_to_termwiz_mods(event.modifiers),
         };
 
         if !(self.config.swallow_mouse_click_on_pane_focus && is_click_to_focus) {
-            pane.mouse_event(mouse_event).ok();
+            self.send_mouse_event_to_pane(&pane, mouse_event);
         }
 
         match event.kind {
@@ -669,5 +838,80 @@ fn mouse_press_to_tmb(press: &MousePress) -> TMB {
         MousePress::Left => TMB::Left,
         MousePress::Right => TMB::Right,
         MousePress::Middle => TMB::Middle,
+        MousePress::Back => TMB::Back,
+        MousePress::Forward => TMB::Forward,
+    }
+}
+
+fn wheel_to_tmb(kind: &WMEK) -> Option<TMB> {
+    match *kind {
+        WMEK::VertWheel(amount) if amount > 0 => Some(TMB::WheelUp(amount as usize)),
+        WMEK::VertWheel(amount) if amount < 0 => Some(TMB::WheelDown((-amount) as usize)),
+        WMEK::HorzWheel(amount) if amount > 0 => Some(TMB::WheelLeft(amount as usize)),
+        WMEK::HorzWheel(amount) if amount < 0 => Some(TMB::WheelRight((-amount) as usize)),
+        _ => None,
+    }
+}
+
+/// Tracks the strokes made while a button with gesture bindings
+/// is held down.
+pub struct MouseGesture {
+    button: TMB,
+    anchor: (isize, isize),
+    directions: Vec<GestureDirection>,
+}
+
+impl MouseGesture {
+    fn new(button: TMB, x: isize, y: isize) -> Self {
+        Self {
+            button,
+            anchor: (x, y),
+            directions: vec![],
+        }
+    }
+
+    /// Once the pointer has travelled at least `threshold` pixels from
+    /// the end of the previous stroke, record the dominant direction of
+    /// travel, collapsing repeated strokes in the same direction.
+    fn update(&mut self, x: isize, y: isize, threshold: isize) {
+        let dx = x - self.anchor.0;
+        let dy = y - self.anchor.1;
+        if dx.abs().max(dy.abs()) < threshold {
+            return;
+        }
+        let direction = if dx.abs() > dy.abs() {
+            if dx > 0 {
+                GestureDirection::Right
+            } else {
+                GestureDirection::Left
+            }
+        } else if dy > 0 {
+            GestureDirection::Down
+        } else {
+            GestureDirection::Up
+        };
+        if self.directions.last() != Some(&direction) {
+            self.directions.push(direction);
+        }
+        self.anchor = (x, y);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gesture_directions() {
+        let mut g = MouseGesture::new(TMB::Right, 0, 0);
+        g.update(5, 3, 20);
+        assert!(g.directions.is_empty());
+        g.update(0, 40, 20);
+        g.update(2, 80, 20);
+        g.update(60, 85, 20);
+        assert_eq!(
+            g.directions,
+            vec![GestureDirection::Down, GestureDirection::Right]
+        );
     }
 }
@@ -0,0 +1,503 @@
+use crate::overlay::compute_labels_for_alphabet;
+use crate::termwindow::keyevent::window_mods_to_termwiz_mods;
+use ::window::{KeyCode, KeyEvent, WindowOps};
+use config::keyassignment::{PaneSelectMode, RotationDirection};
+use mux::pane::{Pane, PaneId};
+use mux::tab::PositionedPane;
+use mux::Mux;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use termwiz::cell::{unicode_column_width, CellAttributes, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::Modifiers;
+use wezterm_client::pane::ClientPane;
+use wezterm_term::Line;
+
+/// The labels shown over the panes of the active tab by the
+/// ShowPaneNumbers and PaneSelect actions, and the keys typed so far
+pub struct PaneSelect {
+    /// The label of each pane, indexed by its topological pane index
+    labels: Vec<String>,
+    /// The characters of a label that have been typed so far
+    typed: String,
+    mode: PaneSelectMode,
+    /// The characters that the labels are made from, or None for
+    /// the numeric labels of ShowPaneNumbers
+    alphabet: Option<String>,
+    /// The pane that was active when PaneSelect started, which is
+    /// the one that SwapWithActive exchanges with the chosen pane,
+    /// even after switching to another tab
+    origin: Option<PaneId>,
+    /// ShowPaneNumbers removes its labels after a timeout, while
+    /// PaneSelect waits until a label has been typed or it is cancelled
+    expiry: Option<Instant>,
+}
+
+impl PaneSelect {
+    fn is_expired(&self) -> bool {
+        matches!(self.expiry, Some(expiry) if expiry <= Instant::now())
+    }
+}
+
+/// How a PaneSelect label is drawn over its pane
+pub struct PaneLabel {
+    pub text: String,
+    pub fg: Option<config::RgbColor>,
+    pub bg: Option<config::RgbColor>,
+    /// The minimum size of the label box, as a fraction
+    /// of the size of the pane
+    pub scale: f64,
+}
+
+impl super::TermWindow {
+    /// Labels each pane in the active tab with its index for
+    /// `pane_numbers_timeout_milliseconds`, during which typing
+    /// the index activates the pane with that index
+    pub fn show_pane_numbers(&mut self) {
+        let num_panes = self.num_panes_in_active_tab();
+        self.start_pane_select(PaneSelect {
+            labels: pane_number_labels(num_panes),
+            typed: String::new(),
+            mode: PaneSelectMode::Activate,
+            alphabet: None,
+            origin: None,
+            expiry: Some(
+                Instant::now()
+                    + Duration::from_millis(self.config.pane_numbers_timeout_milliseconds),
+            ),
+        });
+    }
+
+    /// Labels each pane in the active tab using `alphabet`, or
+    /// `pane_select_alphabet` if it is empty, and waits for a label
+    /// to be typed.  The operation performed on the chosen pane is
+    /// `mode`, unless a modifier is held while typing the last
+    /// character of the label: CTRL swaps it with the active pane,
+    /// ALT moves it to a new tab and CTRL+ALT closes it.
+    /// Tab and Shift-Tab label the panes of the next and previous tabs
+    /// instead, so that panes in other tabs can be chosen too.
+    pub fn show_pane_select(&mut self, alphabet: &str, mode: PaneSelectMode) {
+        let alphabet = if alphabet.is_empty() {
+            self.config.pane_select_alphabet.clone()
+        } else {
+            alphabet.to_string()
+        };
+        let num_panes = self.num_panes_in_active_tab();
+        let origin = self.get_active_pane_no_overlay().map(|pane| pane.pane_id());
+        self.start_pane_select(PaneSelect {
+            labels: compute_labels_for_alphabet(&alphabet, num_panes),
+            typed: String::new(),
+            mode,
+            alphabet: Some(alphabet),
+            origin,
+            expiry: None,
+        });
+    }
+
+    fn num_panes_in_active_tab(&self) -> usize {
+        let mux = Mux::get().unwrap();
+        mux.get_active_tab_for_window(self.mux_window_id)
+            .map(|tab| tab.count_panes())
+            .unwrap_or(0)
+    }
+
+    fn start_pane_select(&mut self, pane_select: PaneSelect) {
+        if pane_select.labels.is_empty() {
+            return;
+        }
+        self.pane_select = Some(pane_select);
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Returns the time at which the pane numbers are removed,
+    /// if they are being shown
+    pub fn pane_numbers_expiry(&self) -> Option<Instant> {
+        self.pane_select
+            .as_ref()
+            .filter(|pane_select| !pane_select.is_expired())
+            .and_then(|pane_select| pane_select.expiry)
+    }
+
+    /// Whether the panes should be dimmed while their labels are shown
+    pub fn pane_select_dims_panes(&self) -> bool {
+        self.config.pane_select_dim_background
+            && matches!(&self.pane_select, Some(pane_select) if pane_select.expiry.is_none())
+    }
+
+    /// Returns the label to draw over the pane at `pane_index`, or
+    /// None if it doesn't have one, or if it can no longer be chosen
+    /// because of the keys that have been typed
+    /// Panes that have been added to the rotation are marked with `*`.
+    pub fn pane_select_label(&self, pos: &PositionedPane) -> Option<PaneLabel> {
+        let pane_select = self.pane_select.as_ref()?;
+        if pane_select.is_expired() {
+            return None;
+        }
+        let text = pane_select.labels.get(pos.index)?;
+        if !text.starts_with(&pane_select.typed) {
+            return None;
+        }
+        let text = if self.rotation_panes.contains(&pos.pane.pane_id()) {
+            format!("{}*", text)
+        } else {
+            text.to_string()
+        };
+
+        let palette = &self.config.resolved_palette;
+        let bg = if pos.is_active {
+            palette.pane_numbers_active_bg.or(palette.pane_numbers_bg)
+        } else {
+            palette.pane_numbers_bg
+        };
+        Some(PaneLabel {
+            text,
+            fg: palette.pane_numbers_fg,
+            bg,
+            scale: self.config.pane_select_label_scale,
+        })
+    }
+
+    /// Called with each key press.  While the pane labels are shown,
+    /// typing a label performs the selected operation on its pane and
+    /// Escape removes the labels.  Returns true if the key has been
+    /// consumed.  Any other key also removes the labels; it is consumed
+    /// by PaneSelect, but is processed as normal by ShowPaneNumbers.
+    pub fn pane_select_key(&mut self, key: &KeyEvent) -> bool {
+        let mut pane_select = match self.pane_select.take() {
+            Some(pane_select) if !pane_select.is_expired() => pane_select,
+            _ => return false,
+        };
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+        let consume_other_keys = pane_select.expiry.is_none();
+
+        // The unmodified key is preferred, so that labels can be
+        // typed while holding CTRL or ALT
+        let c = match key.raw_key.as_ref().unwrap_or(&key.key) {
+            KeyCode::Char(c) => *c,
+            KeyCode::Escape => return true,
+            _ => return consume_other_keys,
+        };
+        if c == '\t' && consume_other_keys && pane_select.typed.is_empty() {
+            let delta = if window_mods_to_termwiz_mods(key.modifiers).contains(Modifiers::SHIFT) {
+                -1
+            } else {
+                1
+            };
+            self.pane_select_other_tab(pane_select, delta);
+            return true;
+        }
+        pane_select.typed.extend(c.to_lowercase());
+
+        let typed = &pane_select.typed;
+        let pane_index = match pane_select.labels.iter().position(|label| label == typed) {
+            Some(pane_index) => pane_index,
+            None if pane_select
+                .labels
+                .iter()
+                .any(|label| label.starts_with(typed.as_str())) =>
+            {
+                // Wait for the rest of the label
+                self.pane_select.replace(pane_select);
+                return true;
+            }
+            None => return consume_other_keys,
+        };
+
+        let modifiers = window_mods_to_termwiz_mods(key.modifiers) - Modifiers::SHIFT;
+        let mode = if modifiers == Modifiers::CTRL {
+            PaneSelectMode::SwapWithActive
+        } else if modifiers == Modifiers::ALT {
+            PaneSelectMode::MoveToNewTab
+        } else if modifiers == Modifiers::CTRL | Modifiers::ALT {
+            PaneSelectMode::Close
+        } else {
+            pane_select.mode
+        };
+        self.perform_pane_select(pane_index, mode, pane_select.origin);
+        true
+    }
+
+    /// Activates the tab that is `delta` tabs away from the active one
+    /// and labels its panes, keeping the rest of `pane_select`
+    fn pane_select_other_tab(&mut self, mut pane_select: PaneSelect, delta: isize) {
+        if let Err(err) = self.activate_tab_relative(delta) {
+            log::error!("PaneSelect: {:#}", err);
+        }
+        let num_panes = self.num_panes_in_active_tab();
+        if let Some(alphabet) = &pane_select.alphabet {
+            pane_select.labels = compute_labels_for_alphabet(alphabet, num_panes);
+        }
+        self.start_pane_select(pane_select);
+    }
+
+    fn perform_pane_select(
+        &mut self,
+        pane_index: usize,
+        mode: PaneSelectMode,
+        origin: Option<PaneId>,
+    ) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        if self.tab_state(tab.tab_id()).overlay.is_some() {
+            return;
+        }
+        let panes = tab.iter_panes();
+        let pane = match panes.iter().find(|pos| pos.index == pane_index) {
+            Some(pos) => Rc::clone(&pos.pane),
+            None => return,
+        };
+
+        match mode {
+            PaneSelectMode::Activate => tab.set_active_idx(pane_index),
+            PaneSelectMode::SwapWithActive => {
+                let active = match origin
+                    .and_then(|pane_id| mux.get_pane(pane_id))
+                    .or_else(|| tab.get_active_pane())
+                {
+                    Some(active) => active,
+                    None => return,
+                };
+                if !Self::can_rearrange(&pane) || !Self::can_rearrange(&active) {
+                    return;
+                }
+                if let Err(err) = mux.swap_panes(active.pane_id(), pane.pane_id()) {
+                    log::error!("PaneSelect: {:#}", err);
+                }
+            }
+            PaneSelectMode::MoveToNewTab => {
+                if !Self::can_rearrange(&pane) {
+                    return;
+                }
+                if let Err(err) = mux.move_pane_to_window(pane.pane_id(), self.mux_window_id) {
+                    log::error!("PaneSelect: {:#}", err);
+                }
+            }
+            PaneSelectMode::Close => self.close_pane(&pane, true),
+            PaneSelectMode::ToggleRotation => {
+                let pane_id = pane.pane_id();
+                if let Some(idx) = self.rotation_panes.iter().position(|&p| p == pane_id) {
+                    self.rotation_panes.remove(idx);
+                } else {
+                    self.rotation_panes.push(pane_id);
+                }
+            }
+        }
+    }
+
+    /// Rotates the panes that were added by the ToggleRotation mode
+    /// of PaneSelect, which may be in different tabs, or the panes of
+    /// the active tab if fewer than two of those remain
+    pub fn rotate_panes(&mut self, direction: RotationDirection) {
+        let mux = Mux::get().unwrap();
+        self.rotation_panes
+            .retain(|&pane_id| mux.get_pane(pane_id).is_some());
+
+        let panes: Vec<Rc<dyn Pane>> = if self.rotation_panes.len() > 1 {
+            self.rotation_panes
+                .iter()
+                .filter_map(|&pane_id| mux.get_pane(pane_id))
+                .collect()
+        } else {
+            match mux.get_active_tab_for_window(self.mux_window_id) {
+                Some(tab) => tab.iter_panes().into_iter().map(|pos| pos.pane).collect(),
+                None => return,
+            }
+        };
+        if !panes.iter().all(Self::can_rearrange) {
+            return;
+        }
+
+        let pane_ids: Vec<PaneId> = panes.iter().map(|pane| pane.pane_id()).collect();
+        let forward = direction == RotationDirection::Clockwise;
+        if let Err(err) = mux.rotate_panes(&pane_ids, forward) {
+            log::error!("RotatePanes: {:#}", err);
+        }
+    }
+
+    /// The topology of a remote mux is owned by the server;
+    /// moving its panes only on this side would leave us out
+    /// of sync with it.
+    fn can_rearrange(pane: &Rc<dyn Pane>) -> bool {
+        if pane.downcast_ref::<ClientPane>().is_some() {
+            log::error!("panes in a remote mux must be moved using `wezterm cli move-pane`");
+            false
+        } else {
+            true
+        }
+    }
+}
+
+/// Returns the labels used by ShowPaneNumbers for `num_panes` panes.
+/// The indices are padded with zeros to the same number of digits,
+/// so that no label is the prefix of another and every pane can be
+/// chosen by typing its label.
+fn pane_number_labels(num_panes: usize) -> Vec<String> {
+    let digits = num_panes.saturating_sub(1).to_string().len();
+    (0..num_panes)
+        .map(|idx| format!("{:0width$}", idx, width = digits))
+        .collect()
+}
+
+/// The box in which a label is drawn over a pane, in cells
+struct LabelBox {
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+    /// The factor by which the text of the label is enlarged to
+    /// fill the box
+    text_scale: usize,
+}
+
+fn label_box(cols: usize, rows: usize, label: &PaneLabel) -> LabelBox {
+    let text_width = unicode_column_width(&label.text).max(1);
+    let width = ((cols as f64 * label.scale) as usize)
+        .max(text_width + 4)
+        .min(cols);
+    // Keep the label on the middle row of the box
+    let height = (((rows as f64 * label.scale) as usize) | 1)
+        .max(3)
+        .min(rows);
+    let middle = rows / 2;
+    LabelBox {
+        left: cols.saturating_sub(width) / 2,
+        top: middle.saturating_sub(height / 2),
+        width,
+        height,
+        text_scale: (width.saturating_sub(4) / text_width)
+            .min(height.saturating_sub(2))
+            .max(1),
+    }
+}
+
+/// When the box of `label` is large enough for its text to be drawn
+/// larger than the cells of the pane, returns the column and row at
+/// which the text starts and the factor by which it is enlarged.
+/// The text is then left out of the lines returned by
+/// `overlay_pane_label` so that it can be painted at that size.
+pub fn scaled_label_text(
+    cols: usize,
+    rows: usize,
+    label: &PaneLabel,
+) -> Option<(usize, usize, usize)> {
+    let label_box = label_box(cols, rows, label);
+    let scale = label_box.text_scale;
+    if scale == 1 {
+        return None;
+    }
+    let text_width = unicode_column_width(&label.text) * scale;
+    Some((
+        label_box.left + label_box.width.saturating_sub(text_width) / 2,
+        label_box.top + label_box.height.saturating_sub(scale) / 2,
+        scale,
+    ))
+}
+
+/// Returns the line to render in place of `line`, which is row `row`
+/// of a pane that is `cols` by `rows` cells, when the pane is labelled
+/// with `label`.  The label is drawn in a box in the middle of the
+/// pane, sized relative to the pane; None is returned for rows that
+/// don't intersect it.
+pub fn overlay_pane_label(
+    line: &Line,
+    row: usize,
+    cols: usize,
+    rows: usize,
+    label: &PaneLabel,
+) -> Option<Line> {
+    let LabelBox {
+        left: start,
+        top,
+        width,
+        height,
+        text_scale,
+    } = label_box(cols, rows, label);
+    if row < top || row >= top + height {
+        return None;
+    }
+
+    let text_width = unicode_column_width(&label.text);
+    let text = if row == rows / 2 && text_scale == 1 {
+        let left = width.saturating_sub(text_width) / 2;
+        let right = width.saturating_sub(text_width + left);
+        format!("{}{}{}", " ".repeat(left), label.text, " ".repeat(right))
+    } else {
+        " ".repeat(width)
+    };
+
+    let mut attrs = CellAttributes::default();
+    attrs.set_intensity(Intensity::Bold);
+    if label.fg.is_none() && label.bg.is_none() {
+        attrs.set_reverse(true);
+    }
+    if let Some(fg) = label.fg {
+        attrs.set_foreground(ColorAttribute::TrueColorWithDefaultFallback(fg));
+    }
+    if let Some(bg) = label.bg {
+        attrs.set_background(ColorAttribute::TrueColorWithDefaultFallback(bg));
+    }
+
+    let mut line = line.clone();
+    line.resize(cols, line.current_seqno());
+    line.overlay_text_with_attribute(start, &text, attrs, line.current_seqno());
+    Some(line)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn label(scale: f64) -> PaneLabel {
+        PaneLabel {
+            text: "a".to_string(),
+            fg: None,
+            bg: None,
+            scale,
+        }
+    }
+
+    fn label_rows(cols: usize, rows: usize, scale: f64) -> Vec<String> {
+        let line = Line::with_width(cols);
+        (0..rows)
+            .filter_map(|row| overlay_pane_label(&line, row, cols, rows, &label(scale)))
+            .map(|line| line.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn label_box() {
+        assert_eq!(
+            label_rows(10, 5, 0.),
+            vec!["          ", "    a     ", "          "]
+        );
+        assert_eq!(label_rows(20, 20, 0.5).len(), 11);
+        assert_eq!(label_rows(4, 1, 1.), vec![" a  "]);
+    }
+
+    #[test]
+    fn scaled_text() {
+        assert_eq!(scaled_label_text(10, 5, &label(0.)), None);
+        // The box is 10x11 cells, leaving room for the text
+        // to be drawn 6 times larger
+        assert_eq!(scaled_label_text(20, 20, &label(0.5)), Some((7, 7, 6)));
+        assert!(label_rows(20, 20, 0.5)
+            .iter()
+            .all(|row| row.trim().is_empty()));
+    }
+
+    #[test]
+    fn pane_numbers() {
+        assert_eq!(pane_number_labels(3), vec!["0", "1", "2"]);
+        assert_eq!(pane_number_labels(10).last().unwrap(), "9");
+        let labels = pane_number_labels(12);
+        assert_eq!(labels[1], "01");
+        assert_eq!(labels[11], "11");
+    }
+}
@@ -0,0 +1,104 @@
+//! Collects the timing of each frame so that it can be shown
+//! in the performance HUD
+use mux::pane::PaneId;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// The period over which the frame rate is computed
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+
+/// The breakdown of the time taken to paint a frame
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FrameTimes {
+    /// Shaping text and rasterizing glyphs
+    pub shape: Duration,
+    /// Computing the quads, excluding shaping
+    pub quads: Duration,
+    /// Unmapping the vertex buffers, which uploads them to the GPU
+    pub upload: Duration,
+    /// Issuing the draw calls
+    pub draw: Duration,
+    pub total: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct FrameStats {
+    /// Accumulates the shaping time of the frame that is being painted
+    pub shape: Duration,
+    /// Accumulates the upload time of the frame that is being painted
+    pub upload: Duration,
+    /// The times of the most recently completed frame
+    pub last: FrameTimes,
+    frames: VecDeque<Instant>,
+}
+
+impl FrameStats {
+    pub fn begin_frame(&mut self) {
+        self.shape = Duration::default();
+        self.upload = Duration::default();
+    }
+
+    /// Records the completion of a frame.
+    /// `build` is the time taken to compute the quads, which
+    /// includes shaping and uploading.
+    pub fn end_frame(&mut self, build: Duration, draw: Duration, total: Duration) {
+        self.last = FrameTimes {
+            shape: self.shape,
+            quads: build
+                .checked_sub(self.shape + self.upload)
+                .unwrap_or_default(),
+            upload: self.upload,
+            draw,
+            total,
+        };
+
+        let now = Instant::now();
+        self.frames.push_back(now);
+        while let Some(first) = self.frames.front() {
+            if now.duration_since(*first) > FPS_WINDOW {
+                self.frames.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn fps(&self) -> f32 {
+        self.frames.len() as f32 / FPS_WINDOW.as_secs_f32()
+    }
+
+    /// Produces the text of the HUD, one entry per line
+    pub fn hud_lines(
+        &self,
+        atlas_utilization: f32,
+        atlas_size: usize,
+        latencies: &[(PaneId, Duration)],
+    ) -> Vec<String> {
+        fn ms(d: Duration) -> String {
+            format!("{:.2}ms", d.as_secs_f64() * 1000.)
+        }
+
+        let mut lines = vec![
+            format!("{:.1} fps  frame {}", self.fps(), ms(self.last.total)),
+            format!(
+                "shape {}  quads {}",
+                ms(self.last.shape),
+                ms(self.last.quads)
+            ),
+            format!(
+                "upload {}  draw {}",
+                ms(self.last.upload),
+                ms(self.last.draw)
+            ),
+            format!(
+                "glyph atlas {:.0}% of {}px",
+                atlas_utilization * 100.,
+                atlas_size
+            ),
+        ];
+        for (pane_id, latency) in latencies {
+            lines.push(format!("pane {} rtt {}ms", pane_id, latency.as_millis()));
+        }
+        lines
+    }
+}
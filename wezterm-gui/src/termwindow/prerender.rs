@@ -0,0 +1,240 @@
+use crate::glium::texture::SrgbTexture2d;
+use crate::shapecache::{ShapeCacheKey, ShapedInfo};
+use mux::pane::{Pane, PaneId};
+use mux::tab::TabId;
+use mux::Mux;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use termwiz::cellcluster::CellCluster;
+use termwiz::surface::SequenceNo;
+use wezterm_term::StableRowIndex;
+
+/// How long each frame may spend shaping the text of inactive tabs
+const PRERENDER_TIME_BUDGET: Duration = Duration::from_millis(4);
+
+/// The shapes that were placed in the shape cache in advance for a pane
+pub struct PrerenderedPane {
+    tab_id: TabId,
+    seqno: SequenceNo,
+    keys: HashSet<ShapeCacheKey>,
+    /// The estimated memory used by the shapes of `keys`
+    bytes: usize,
+}
+
+/// Estimates the memory used by the shape cache entry for `cluster`
+fn shape_memory_estimate(cluster: &CellCluster) -> usize {
+    std::mem::size_of::<ShapeCacheKey>()
+        + cluster.text.len()
+        + cluster.text.chars().count() * std::mem::size_of::<ShapedInfo<SrgbTexture2d>>()
+}
+
+/// Given the memory used by each prerendered tab, most recently used
+/// first, returns the tabs that must be evicted so that the rest fit
+/// within `budget`.  Once a tab doesn't fit, neither do any of the less
+/// recently used tabs, so that the most recent ones are kept.
+fn tabs_over_budget(usage: &[(TabId, usize)], budget: usize) -> Vec<TabId> {
+    let mut used = 0;
+    usage
+        .iter()
+        .skip_while(|(_, bytes)| {
+            used += bytes;
+            used <= budget
+        })
+        .map(|(tab_id, _)| *tab_id)
+        .collect()
+}
+
+impl super::TermWindow {
+    /// Shapes the visible text of the `prerender_recent_tabs` most
+    /// recently used tabs, other than the active one, so that switching
+    /// back to one of them doesn't have to wait for its text to be shaped.
+    /// Only a few milliseconds are spent on this per frame, and the
+    /// shapes are kept within `prerender_memory_budget_kb`, evicting
+    /// those of the least recently used tabs first.
+    pub fn prerender_recent_tabs(&mut self) {
+        let limit = self.config.prerender_recent_tabs;
+        if limit == 0 {
+            self.recent_tabs.clear();
+            self.release_prerendered_panes(|_, _| true);
+            return;
+        }
+
+        let mux = Mux::get().unwrap();
+        let active_tab_id = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab.tab_id(),
+            None => return,
+        };
+        self.recent_tabs.retain(|&tab_id| tab_id != active_tab_id);
+        self.recent_tabs.insert(0, active_tab_id);
+        self.recent_tabs.truncate(limit + 1);
+
+        // The shapes of the active tab are in use, so they are no longer
+        // accounted for, but they are left in the cache
+        self.prerendered_panes
+            .retain(|_, prerendered| prerendered.tab_id != active_tab_id);
+        let recent_tabs = self.recent_tabs.clone();
+        self.release_prerendered_panes(|pane_id, prerendered| {
+            mux.get_pane(pane_id).is_none() || !recent_tabs.contains(&prerendered.tab_id)
+        });
+
+        let budget = self.config.prerender_memory_budget_kb * 1024;
+        let usage: Vec<(TabId, usize)> = recent_tabs
+            .iter()
+            .skip(1)
+            .map(|&tab_id| (tab_id, self.prerendered_tab_bytes(tab_id)))
+            .collect();
+        let evict = tabs_over_budget(&usage, budget);
+        if !evict.is_empty() {
+            self.release_prerendered_panes(|_, prerendered| evict.contains(&prerendered.tab_id));
+        }
+
+        let deadline = Instant::now() + PRERENDER_TIME_BUDGET;
+        let mut pending = false;
+        'tabs: for &tab_id in recent_tabs.iter().skip(1) {
+            let tab = match mux.get_tab(tab_id) {
+                Some(tab) => tab,
+                None => continue,
+            };
+            for pos in tab.iter_panes() {
+                let pane_id = pos.pane.pane_id();
+                let seqno = pos.pane.get_current_seqno();
+                let previous_bytes = match self.prerendered_panes.get(&pane_id) {
+                    Some(prerendered) if prerendered.seqno == seqno => continue,
+                    Some(prerendered) => prerendered.bytes,
+                    None => 0,
+                };
+                if Instant::now() >= deadline {
+                    pending = true;
+                    break 'tabs;
+                }
+
+                let used: usize = self.prerendered_panes.values().map(|p| p.bytes).sum();
+                let available = budget.saturating_sub(used - previous_bytes);
+                match self.prerender_pane(&pos.pane, available) {
+                    Ok(Some((keys, bytes))) => {
+                        if let Some(previous) = self.prerendered_panes.remove(&pane_id) {
+                            // Release the shapes of text that is no longer visible
+                            let mut cache = self.shape_cache.borrow_mut();
+                            for key in previous.keys.difference(&keys) {
+                                cache.pop(key);
+                            }
+                        }
+                        self.prerendered_panes.insert(
+                            pane_id,
+                            PrerenderedPane {
+                                tab_id,
+                                seqno,
+                                keys,
+                                bytes,
+                            },
+                        );
+                    }
+                    Ok(None) => {
+                        // The budget is used up by more recently used tabs
+                        break 'tabs;
+                    }
+                    Err(err) => {
+                        // Most likely the glyph atlas is full; leave it to
+                        // the next real paint to grow it
+                        log::trace!("prerendering pane {}: {:#}", pane_id, err);
+                        break 'tabs;
+                    }
+                }
+            }
+        }
+
+        if pending {
+            self.update_next_frame_time(Some(Instant::now() + Duration::from_millis(50)));
+        }
+    }
+
+    fn prerendered_tab_bytes(&self, tab_id: TabId) -> usize {
+        self.prerendered_panes
+            .values()
+            .filter(|prerendered| prerendered.tab_id == tab_id)
+            .map(|prerendered| prerendered.bytes)
+            .sum()
+    }
+
+    /// Removes the shapes of the prerendered panes that match `release`
+    /// from the shape cache
+    fn release_prerendered_panes<F: Fn(PaneId, &PrerenderedPane) -> bool>(&mut self, release: F) {
+        let pane_ids: Vec<PaneId> = self
+            .prerendered_panes
+            .iter()
+            .filter(|(&pane_id, prerendered)| release(pane_id, prerendered))
+            .map(|(&pane_id, _)| pane_id)
+            .collect();
+        let mut cache = self.shape_cache.borrow_mut();
+        for pane_id in pane_ids {
+            if let Some(prerendered) = self.prerendered_panes.remove(&pane_id) {
+                for key in &prerendered.keys {
+                    cache.pop(key);
+                }
+            }
+        }
+    }
+
+    /// Shapes the lines that are visible in `pane`, placing them
+    /// in the shape cache, provided that their estimated memory
+    /// use doesn't exceed `available` bytes.  Returns the keys
+    /// of the shapes and their estimated memory use.
+    fn prerender_pane(
+        &self,
+        pane: &Rc<dyn Pane>,
+        available: usize,
+    ) -> anyhow::Result<Option<(HashSet<ShapeCacheKey>, usize)>> {
+        let gl_state = match self.render_state.as_ref() {
+            Some(gl_state) => gl_state,
+            None => return Ok(None),
+        };
+        let dims = pane.get_dimensions();
+        let top = self
+            .get_viewport(pane.pane_id())
+            .unwrap_or(dims.physical_top);
+        let (_first, lines) = pane.get_lines(top..top + dims.viewport_rows as StableRowIndex);
+        let without_ligatures = self.pane_state(pane.pane_id()).disable_ligatures;
+
+        let mut keys = HashSet::new();
+        let mut bytes = 0;
+        let mut clusters = vec![];
+        for line in &lines {
+            for cluster in line.cluster() {
+                let style = self.fonts.match_style(&self.config, &cluster.attrs);
+                let key = ShapeCacheKey {
+                    style: style.clone(),
+                    text: cluster.text.clone(),
+                    without_ligatures,
+                };
+                if keys.insert(key) {
+                    bytes += shape_memory_estimate(&cluster);
+                    clusters.push((style, cluster, line));
+                }
+            }
+        }
+        if bytes > available {
+            return Ok(None);
+        }
+
+        for (style, cluster, line) in clusters {
+            self.cached_cluster_shape(style, &cluster, gl_state, line, without_ligatures)?;
+        }
+        Ok(Some((keys, bytes)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn budget() {
+        let usage = [(1, 100), (2, 200), (3, 50), (4, 10)];
+        assert_eq!(tabs_over_budget(&usage, 400), Vec::<TabId>::new());
+        assert_eq!(tabs_over_budget(&usage, 350), vec![4]);
+        // Tab 3 would fit on its own, but tab 2 is more recent
+        assert_eq!(tabs_over_budget(&usage, 250), vec![2, 3, 4]);
+        assert_eq!(tabs_over_budget(&usage, 0), vec![1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,139 @@
+//! Switching windows and tabs between the `profiles` in the config.
+//! A profile is applied as config overrides beneath those set by
+//! `window:set_config_overrides`; the profile of the active tab, if
+//! it has one, takes precedence over that of the window.
+use config::keyassignment::{SpawnCommand, SpawnTabDomain};
+use mux::Mux;
+
+impl super::TermWindow {
+    /// Returns the name of the profile that applies to the active tab
+    fn active_profile(&self) -> Option<String> {
+        let mux = Mux::get().unwrap();
+        let tab_profile = mux
+            .get_active_tab_for_window(self.mux_window_id)
+            .and_then(|tab| {
+                self.tab_state(tab.tab_id())
+                    .profile
+                    .clone()
+                    .or_else(|| tab.get_profile().map(Some))
+            });
+        resolve_profile(
+            tab_profile,
+            self.window_profile.as_deref(),
+            config::configuration().default_profile.as_deref(),
+        )
+    }
+
+    /// Returns the config overrides that apply the active profile,
+    /// along with the name of that profile
+    pub fn profile_config_overrides(&self) -> (Option<String>, serde_json::Value) {
+        let profile = self.active_profile();
+        let overrides =
+            config::configuration().profile_overrides(profile.as_deref(), &self.config_overrides);
+        (profile, overrides)
+    }
+
+    /// Implements `SwitchProfile`
+    pub fn switch_profile(&mut self, name: Option<&str>, tab: bool) {
+        if let Some(name) = name {
+            if !self.config.profiles.contains_key(name) {
+                log::error!("SwitchProfile: there is no profile named {}", name);
+                return;
+            }
+        }
+        let name = name.map(|name| name.to_string());
+        if tab {
+            let mux = Mux::get().unwrap();
+            if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                self.tab_state(tab.tab_id()).profile = Some(name);
+            }
+        } else {
+            self.window_profile = name;
+        }
+        self.update_profile();
+    }
+
+    /// Applies the profile of the active tab if it differs from the
+    /// one that is currently applied, such as after activating a tab
+    /// that has a profile of its own
+    pub fn update_profile(&mut self) {
+        if self.active_profile() != self.applied_profile {
+            self.config_was_reloaded();
+        }
+    }
+
+    /// Returns `spawn` with its domain replaced by the domain of the
+    /// active profile, if it would otherwise be spawned into the
+    /// default domain
+    pub fn spawn_with_profile_domain(
+        &self,
+        spawn: &SpawnCommand,
+        new_window: bool,
+    ) -> SpawnCommand {
+        let mut spawn = spawn.clone();
+        let uses_default_domain = match spawn.domain {
+            SpawnTabDomain::DefaultDomain => true,
+            // This is treated as the default domain for new windows
+            SpawnTabDomain::CurrentPaneDomain => new_window,
+            SpawnTabDomain::DomainName(_) => false,
+        };
+        if uses_default_domain {
+            if let Some(domain) = self
+                .config
+                .profile(self.applied_profile.as_deref())
+                .and_then(|profile| profile.domain.clone())
+            {
+                spawn.domain = SpawnTabDomain::DomainName(domain);
+            }
+        }
+        spawn
+    }
+}
+
+/// Returns the name of the profile that applies given the selection
+/// made for the active tab, that of the window and the `default_profile`.
+/// A tab that was switched with no name uses the default profile,
+/// even while the window uses another one.
+fn resolve_profile(
+    tab: Option<Option<String>>,
+    window: Option<&str>,
+    default: Option<&str>,
+) -> Option<String> {
+    match tab {
+        Some(Some(name)) => Some(name),
+        Some(None) => default.map(|name| name.to_string()),
+        None => window.or(default).map(|name| name.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolve() {
+        // Nothing selected uses the default profile, if any
+        assert_eq!(resolve_profile(None, None, None), None);
+        assert_eq!(
+            resolve_profile(None, None, Some("base")).as_deref(),
+            Some("base")
+        );
+        // A tab without a selection follows the window
+        assert_eq!(
+            resolve_profile(None, Some("work"), Some("base")).as_deref(),
+            Some("work")
+        );
+        // A tab with a profile of its own takes precedence
+        assert_eq!(
+            resolve_profile(Some(Some("demo".to_string())), Some("work"), None).as_deref(),
+            Some("demo")
+        );
+        // Switching a tab with no name resets it to the default
+        // profile rather than to that of the window
+        assert_eq!(
+            resolve_profile(Some(None), Some("work"), Some("base")).as_deref(),
+            Some("base")
+        );
+        assert_eq!(resolve_profile(Some(None), Some("work"), None), None);
+    }
+}
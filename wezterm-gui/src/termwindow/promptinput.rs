@@ -0,0 +1,226 @@
+//! The `highlight-prompt-input` event, which lets lua code highlight
+//! parts of the command line that is being typed at a shell prompt,
+//! such as an unknown command or a misspelled word.  The prompt is
+//! found using OSC 133 shell integration.
+use super::highlight::{HighlightRange, HighlightStyle, PaneHighlight};
+use crate::scripting::guiwin::GuiWin;
+use crate::scripting::pane::PaneObject;
+use luahelper::impl_lua_conversion;
+use mlua::FromLua;
+use mux::pane::Pane;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use termwiz::cell::Underline;
+use wezterm_term::StableRowIndex;
+use window::WindowOps;
+
+/// A region of the input to highlight, as returned by lua code.
+/// The columns are relative to the start of the input, and `end_x`
+/// is exclusive.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PromptInputHighlight {
+    pub start_x: usize,
+    #[serde(default = "default_end_x")]
+    pub end_x: usize,
+    #[serde(default)]
+    pub foreground: Option<String>,
+    #[serde(default)]
+    pub background: Option<String>,
+    #[serde(default)]
+    pub underline: Option<Underline>,
+    #[serde(default)]
+    pub underline_color: Option<String>,
+}
+impl_lua_conversion!(PromptInputHighlight);
+
+fn default_end_x() -> usize {
+    usize::max_value()
+}
+
+impl PromptInputHighlight {
+    /// Resolves this highlight for input that starts at column
+    /// `start_x` of `row`
+    fn resolve(&self, row: StableRowIndex, start_x: usize) -> anyhow::Result<PaneHighlight> {
+        let range = HighlightRange {
+            start_y: row,
+            start_x: start_x + self.start_x,
+            end_y: row,
+            end_x: start_x.saturating_add(self.end_x),
+        };
+        let style = HighlightStyle {
+            foreground: self.foreground.clone(),
+            background: self.background.clone(),
+            underline: self.underline,
+            underline_color: self.underline_color.clone(),
+        };
+        PaneHighlight::new(range, &style)
+    }
+}
+
+/// The input at the prompt of a pane, and its highlights
+#[derive(Debug, Clone)]
+pub struct PromptInput {
+    row: StableRowIndex,
+    start_x: usize,
+    text: String,
+    pub highlights: Vec<PaneHighlight>,
+}
+
+fn has_highlight_handler() -> bool {
+    config::run_immediate_with_lua_config(|lua| {
+        Ok(lua
+            .map(|lua| config::lua::has_event_handler(&lua, "highlight-prompt-input"))
+            .unwrap_or(false))
+    })
+    .unwrap_or(false)
+}
+
+/// Splits the text that follows a prompt at column `start_x` into
+/// the column at which the input begins, and the input itself
+fn split_input(start_x: usize, text: &str) -> (usize, &str) {
+    let input = text.trim_start();
+    (start_x + text.len() - input.len(), input.trim_end())
+}
+
+impl super::TermWindow {
+    /// Emits `highlight-prompt-input` if the input at the prompt of
+    /// `pane` has changed, and remembers the highlights that it returns.
+    /// Nothing is done unless there is a handler for the event, and
+    /// the pane is only examined again once its seqno changes.
+    pub fn update_prompt_input(&mut self, pane: &Rc<dyn Pane>) {
+        let pane_id = pane.pane_id();
+        if !has_highlight_handler() {
+            self.pane_state(pane_id).prompt_input.take();
+            return;
+        }
+        let seqno = pane.get_current_seqno();
+        if self.pane_state(pane_id).prompt_input_seqno.replace(seqno) == Some(seqno) {
+            return;
+        }
+
+        let cursor = pane.get_cursor_position();
+        let found = match self.prompt_input_start(pane, &cursor) {
+            Some(start_x) => {
+                let cols = pane.get_dimensions().cols;
+                let (_, lines) = pane.get_lines(cursor.y..cursor.y + 1);
+                lines.first().map(|line| {
+                    let text = line.columns_as_str(start_x..cols);
+                    let (start_x, input) = split_input(start_x, &text);
+                    (start_x, input.to_string())
+                })
+            }
+            None => None,
+        };
+        let (start_x, text) = match found {
+            Some((start_x, text)) if !text.is_empty() => (start_x, text),
+            _ => {
+                if self.pane_state(pane_id).prompt_input.take().is_some() {
+                    if let Some(window) = self.window.as_ref() {
+                        window.invalidate();
+                    }
+                }
+                return;
+            }
+        };
+
+        if let Some(input) = self.pane_state(pane_id).prompt_input.as_ref() {
+            if input.row == cursor.y && input.start_x == start_x && input.text == text {
+                return;
+            }
+        }
+
+        let highlights = self
+            .highlight_prompt_input(pane, &text)
+            .into_iter()
+            .filter_map(|highlight| match highlight.resolve(cursor.y, start_x) {
+                Ok(highlight) => Some(highlight),
+                Err(err) => {
+                    log::warn!("highlight-prompt-input: {:#}", err);
+                    None
+                }
+            })
+            .collect();
+        self.pane_state(pane_id).prompt_input.replace(PromptInput {
+            row: cursor.y,
+            start_x,
+            text,
+            highlights,
+        });
+    }
+
+    fn highlight_prompt_input(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        text: &str,
+    ) -> Vec<PromptInputHighlight> {
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+        match config::run_immediate_with_lua_config(|lua| {
+            if let Some(lua) = lua {
+                let v = config::lua::emit_sync_callback(
+                    &*lua,
+                    (
+                        "highlight-prompt-input".to_string(),
+                        (window, pane, text.to_string()),
+                    ),
+                )?;
+                match &v {
+                    mlua::Value::Nil => Ok(vec![]),
+                    _ => Ok(Vec::<PromptInputHighlight>::from_lua(v, &*lua)?),
+                }
+            } else {
+                Ok(vec![])
+            }
+        }) {
+            Ok(highlights) => highlights,
+            Err(err) => {
+                log::warn!("highlight-prompt-input: {}", err);
+                vec![]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn input() {
+        assert_eq!(split_input(2, " gti status   "), (3, "gti status"));
+        assert_eq!(split_input(2, "ls"), (2, "ls"));
+        assert_eq!(split_input(2, "   "), (5, ""));
+    }
+
+    #[test]
+    fn resolve() {
+        let highlight = PromptInputHighlight {
+            start_x: 0,
+            end_x: 3,
+            foreground: Some("red".to_string()),
+            ..Default::default()
+        };
+        let resolved = highlight.resolve(10, 2).unwrap();
+        assert_eq!(
+            resolved.range(),
+            &HighlightRange {
+                start_y: 10,
+                start_x: 2,
+                end_y: 10,
+                end_x: 5,
+            }
+        );
+
+        // The end defaults to the end of the line
+        let highlight = PromptInputHighlight {
+            start_x: 4,
+            end_x: default_end_x(),
+            underline: Some(Underline::Curly),
+            ..Default::default()
+        };
+        assert_eq!(
+            highlight.resolve(10, 2).unwrap().range().end_x,
+            usize::max_value()
+        );
+    }
+}
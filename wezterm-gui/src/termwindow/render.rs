@@ -2,6 +2,10 @@ use crate::customglyph::BlockKey;
 use crate::glium::texture::SrgbTexture2d;
 use crate::glyphcache::{CachedGlyph, GlyphCache};
 use crate::shapecache::*;
+use crate::termwindow::compose::overlay_compose_text;
+use crate::termwindow::paneselect::{overlay_pane_label, scaled_label_text, PaneLabel};
+use crate::termwindow::suggest::overlay_suggestion_text;
+use crate::termwindow::widget::overlay_pane_widgets;
 use crate::termwindow::{
     BorrowedShapeCacheKey, MappedQuads, RenderState, ScrollHit, ShapedInfo, TermWindowNotif,
     UIItem, UIItemType,
@@ -23,13 +27,13 @@ use smol::Timer;
 use std::ops::Range;
 use std::rc::Rc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-use termwiz::cell::{unicode_column_width, Blink};
+use termwiz::cell::{unicode_column_width, Blink, Intensity};
 use termwiz::cellcluster::CellCluster;
 use termwiz::surface::{CursorShape, CursorVisibility};
 use wezterm_font::units::PixelLength;
 use wezterm_font::{ClearShapeCache, GlyphInfo};
 use wezterm_term::color::{ColorAttribute, ColorPalette, RgbColor};
-use wezterm_term::{CellAttributes, Line, StableRowIndex};
+use wezterm_term::{CellAttributes, CommandResult, Line, StableRowIndex};
 use window::bitmaps::atlas::SpriteSlice;
 use window::bitmaps::Texture2d;
 use window::color::LinearRgba;
@@ -59,6 +63,8 @@ pub struct RenderScreenLineOpenGLParams<'a> {
 
     pub window_is_transparent: bool,
     pub default_bg: LinearRgba,
+    /// Shape each cell on its own, so that no ligatures are formed
+    pub disable_ligatures: bool,
 }
 
 pub struct ComputeCellFgBgParams<'a> {
@@ -68,6 +74,9 @@ pub struct ComputeCellFgBgParams<'a> {
     pub selection: &'a Range<usize>,
     pub fg_color: LinearRgba,
     pub bg_color: LinearRgba,
+    /// Whether the cell is shown in reverse video, either because
+    /// of its attributes or because the line is reversed
+    pub reverse: bool,
     pub palette: &'a ColorPalette,
     pub is_active_pane: bool,
     pub config: &'a ConfigHandle,
@@ -91,10 +100,12 @@ impl super::TermWindow {
         // If nothing on screen needs animating, then we can avoid
         // invalidating as frequently
         *self.has_animation.borrow_mut() = None;
+        self.expire_leaders();
         // Start with the assumption that we should allow images to render
         self.allow_images = true;
 
         let start = Instant::now();
+        self.frame_stats.borrow_mut().begin_frame();
 
         frame.clear_color(0., 0., 0., 0.);
 
@@ -168,11 +179,38 @@ impl super::TermWindow {
             }
         }
         log::debug!("paint_impl before call_draw elapsed={:?}", start.elapsed());
+        let build_elapsed = start.elapsed();
 
+        let draw_start = Instant::now();
         self.call_draw(frame).ok();
+        self.paint_tab_thumbnails(frame);
+        self.prerender_recent_tabs();
         log::debug!("paint_impl elapsed={:?}", start.elapsed());
+        self.frame_stats.borrow_mut().end_frame(
+            build_elapsed,
+            draw_start.elapsed(),
+            start.elapsed(),
+        );
+        if self.show_perf_hud {
+            // Keep the frame rate and timings up to date
+            self.update_next_frame_time(Some(Instant::now() + Duration::from_secs(1)));
+        }
+        if (self.config.show_leader_indicator || self.config.show_which_key)
+            && !self.leader_stack.is_empty()
+        {
+            // Count down the time remaining, show the which-key popup
+            // when it is due, and remove both once the leader expires
+            self.update_next_frame_time(Some(Instant::now() + Duration::from_millis(100)));
+        }
+        if let Some(expiry) = self.pane_numbers_expiry() {
+            // Remove the pane numbers once they time out
+            self.update_next_frame_time(Some(expiry));
+        }
         metrics::histogram!("gui.paint.opengl", start.elapsed());
         metrics::histogram!("gui.paint.opengl.rate", 1.);
+        if let Some(pressed) = self.key_echo_paint.take() {
+            metrics::histogram!("gui.paint.key_echo.latency", pressed.elapsed());
+        }
         self.update_title_post_status();
 
         // If self.has_animation is some, then the last render detected
@@ -197,7 +235,7 @@ impl super::TermWindow {
         }
     }
 
-    fn update_next_frame_time(&self, next_due: Option<Instant>) {
+    pub fn update_next_frame_time(&self, next_due: Option<Instant>) {
         if let Some(next_due) = next_due {
             let mut has_anim = self.has_animation.borrow_mut();
             match *has_anim {
@@ -212,14 +250,18 @@ impl super::TermWindow {
         }
     }
 
+    /// If the visual bell is ringing in `pane` and is configured to
+    /// use `target`, returns its current intensity, along with the
+    /// color that was requested for it, if any
     fn get_intensity_if_bell_target_ringing(
         &self,
         pane: &Rc<dyn Pane>,
         config: &ConfigHandle,
         target: VisualBellTarget,
-    ) -> Option<f32> {
+    ) -> Option<(f32, Option<RgbColor>)> {
         let mut per_pane = self.pane_state(pane.pane_id());
-        if let Some(ringing) = per_pane.bell_start {
+        if let Some((ringing, color)) = per_pane.bell.as_ref().map(|bell| (bell.start, bell.color))
+        {
             if config.visual_bell.target == target {
                 let elapsed = ringing.elapsed().as_secs_f32();
 
@@ -228,7 +270,15 @@ impl super::TermWindow {
                 let out_duration =
                     Duration::from_millis(config.visual_bell.fade_out_duration_ms).as_secs_f32();
 
-                let intensity = if elapsed < in_duration {
+                let intensity = if config.reduce_motion {
+                    // Show the bell for as long as it would have
+                    // taken to fade in and out, without fading
+                    if elapsed < in_duration + out_duration {
+                        Some(1.0)
+                    } else {
+                        None
+                    }
+                } else if elapsed < in_duration {
                     Some(
                         config
                             .visual_bell
@@ -251,13 +301,13 @@ impl super::TermWindow {
 
                 match intensity {
                     None => {
-                        per_pane.bell_start.take();
+                        per_pane.bell.take();
                     }
                     Some(intensity) => {
                         self.update_next_frame_time(Some(
                             Instant::now() + Duration::from_millis(1000 / config.max_fps as u64),
                         ));
-                        return Some(intensity);
+                        return Some((intensity, color));
                     }
                 }
             }
@@ -271,6 +321,12 @@ impl super::TermWindow {
         num_panes: usize,
     ) -> anyhow::Result<()> {
         self.check_for_dirty_lines_and_invalidate_selection(&pos.pane);
+        self.restore_scroll_offset(&pos.pane);
+        if pos.is_active && self.focused.is_some() {
+            self.acknowledge_pane_watch(pos.pane.pane_id());
+        }
+        // PaneSelect dims all of the panes, rather than only the inactive ones
+        let dim_pane = !pos.is_active || self.pane_select_dims_panes();
         /*
         let zone = {
             let dims = pos.pane.get_dimensions();
@@ -287,9 +343,31 @@ impl super::TermWindow {
         };
         */
 
+        let prompt_rows: Vec<StableRowIndex> = if pos.is_active && self.show_scroll_bar {
+            self.get_semantic_zones(&pos.pane)
+                .iter()
+                .map(|zone| zone.start_y)
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let latencies: Vec<_> = if self.show_perf_hud && pos.is_active {
+            self.get_panes_to_render()
+                .iter()
+                .filter_map(|p| {
+                    p.pane
+                        .get_round_trip_latency()
+                        .map(|latency| (p.pane.pane_id(), latency))
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
         let global_bg_color = self.palette().background;
+        let palette = self.pane_palette(&pos.pane);
         let config = &self.config;
-        let palette = pos.pane.palette();
 
         let first_line_offset = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
             1
@@ -301,6 +379,7 @@ impl super::TermWindow {
         if pos.is_active {
             self.prev_cursor.update(&cursor);
         }
+        self.update_prompt_input(&pos.pane);
 
         let current_viewport = self.get_viewport(pos.pane.pane_id());
         let (stable_top, lines);
@@ -313,7 +392,7 @@ impl super::TermWindow {
             };
 
             let start = Instant::now();
-            let (top, vp_lines) = pos
+            let (top, mut vp_lines) = pos
                 .pane
                 .get_lines_with_hyperlinks_applied(stable_range, &self.config.hyperlink_rules);
             metrics::histogram!("get_lines_with_hyperlinks_applied.latency", start.elapsed());
@@ -321,6 +400,8 @@ impl super::TermWindow {
                 "get_lines_with_hyperlinks_applied took {:?}",
                 start.elapsed()
             );
+            self.apply_pane_highlights(&pos.pane, top, &mut vp_lines);
+            self.apply_search_highlight(&pos.pane, top, &mut vp_lines);
             stable_top = top;
             lines = vp_lines;
         }
@@ -432,7 +513,7 @@ impl super::TermWindow {
             quad.set_texture(filled_box);
             quad.set_is_background();
             quad.set_fg_color(background);
-            quad.set_hsv(if pos.is_active {
+            quad.set_hsv(if !dim_pane {
                 None
             } else {
                 Some(config.inactive_pane_hsb)
@@ -442,15 +523,14 @@ impl super::TermWindow {
         {
             // If the bell is ringing, we draw another background layer over the
             // top of this in the configured bell color
-            if let Some(intensity) = self.get_intensity_if_bell_target_ringing(
+            if let Some((intensity, color)) = self.get_intensity_if_bell_target_ringing(
                 &pos.pane,
                 config,
                 VisualBellTarget::BackgroundColor,
             ) {
                 // target background color
-                let (r, g, b, _) = config
-                    .resolved_palette
-                    .visual_bell
+                let (r, g, b, _) = color
+                    .or(config.resolved_palette.visual_bell)
                     .unwrap_or(palette.foreground)
                     .to_linear_tuple_rgba();
 
@@ -502,6 +582,49 @@ impl super::TermWindow {
                     Some(config.inactive_pane_hsb)
                 });
             }
+
+            // Or around the edges of the pane, fading the border
+            // in and out by adjusting its alpha
+            if let Some((intensity, color)) = self.get_intensity_if_bell_target_ringing(
+                &pos.pane,
+                config,
+                VisualBellTarget::PaneBorder,
+            ) {
+                let (r, g, b, _) = color
+                    .or(config.resolved_palette.visual_bell)
+                    .unwrap_or(palette.foreground)
+                    .to_linear_tuple_rgba();
+                let color = LinearRgba::with_components(r, g, b, intensity);
+
+                let cell_width = self.render_metrics.cell_size.width as f32;
+                let cell_height = self.render_metrics.cell_size.height as f32;
+                let thickness = (cell_width / 4.).max(1.);
+                let left = (self.dimensions.pixel_width as f32 / -2.)
+                    + (pos.left as f32 * cell_width)
+                    + self.config.window_padding.left as f32;
+                let top = (self.dimensions.pixel_height as f32 / -2.)
+                    + ((first_line_offset + pos.top) as f32 * cell_height)
+                    + self.config.window_padding.top as f32;
+                let right = left + pos.width as f32 * cell_width;
+                let bottom = top + pos.height as f32 * cell_height;
+
+                for (x0, y0, x1, y1) in [
+                    (left, top, right, top + thickness),
+                    (left, bottom - thickness, right, bottom),
+                    (left, top, left + thickness, bottom),
+                    (right - thickness, top, right, bottom),
+                ]
+                .iter()
+                {
+                    let mut quad = layers[2].allocate()?;
+                    quad.set_position(*x0, *y0, *x1, *y1);
+                    quad.set_texture_adjust(0., 0., 0., 0.);
+                    quad.set_texture(white_space);
+                    quad.set_is_background();
+                    quad.set_fg_color(color);
+                    quad.set_hsv(None);
+                }
+            }
         }
         if self.show_tab_bar && pos.index == 0 {
             let tab_dims = RenderableDimensions {
@@ -556,6 +679,7 @@ impl super::TermWindow {
                     filled_box,
                     window_is_transparent,
                     default_bg,
+                    disable_ligatures: false,
                 },
                 &mut layers,
             )?;
@@ -618,17 +742,166 @@ impl super::TermWindow {
             quad.set_is_background();
         }
 
-        let selrange = self.selection(pos.pane.pane_id()).range.clone();
+        if !prompt_rows.is_empty() {
+            // Mark the position of each shell prompt in the scrollbar
+            let color = rgbcolor_to_window_color(palette.cursor_bg);
+            let padding = self.effective_right_padding(&self.config) as f32;
+            let right = self.dimensions.pixel_width as f32 / 2.;
+            let left = right - padding;
+            const MARK_HEIGHT: f32 = 2.;
+
+            for row in prompt_rows {
+                let y = ScrollHit::row_to_y(&*pos.pane, row, &self.dimensions) as f32;
+                let top = (self.dimensions.pixel_height as f32 / -2.0) + y;
+
+                let mut quad = layers[2].allocate()?;
+                quad.set_fg_color(color);
+                quad.set_position(left, top, right, top + MARK_HEIGHT);
+                quad.set_texture(white_space);
+                quad.set_texture_adjust(0., 0., 0., 0.);
+                quad.set_hsv(None);
+                quad.set_is_background();
+            }
+        }
+
+        let (selrange, rectangular) = {
+            let sel = self.selection(pos.pane.pane_id());
+            (sel.range.clone(), sel.rectangular)
+        };
 
         let start = Instant::now();
         let selection_fg = rgbcolor_to_window_color(palette.selection_fg);
         let selection_bg = rgbcolor_to_window_color(palette.selection_bg);
         let cursor_fg = rgbcolor_to_window_color(palette.cursor_fg);
         let cursor_bg = rgbcolor_to_window_color(palette.cursor_bg);
+        let hud_lines = if self.show_perf_hud && pos.is_active {
+            let glyph_cache = gl_state.glyph_cache.borrow();
+            self.frame_stats.borrow().hud_lines(
+                glyph_cache.atlas.utilization(),
+                glyph_cache.atlas.size(),
+                &latencies,
+            )
+        } else {
+            vec![]
+        };
+        // The which-key popup and the leader indicator are stacked
+        // in the bottom right corner of the active pane
+        let leader_lines = if pos.is_active {
+            let mut lines = self.which_key_lines();
+            if config.show_leader_indicator {
+                lines.extend(self.leader_indicator_text());
+            }
+            lines.extend(
+                self.recording_key_macro()
+                    .map(|name| format!("● REC {}", name)),
+            );
+            if lines.len() > dims.viewport_rows {
+                lines.drain(0..lines.len() - dims.viewport_rows);
+            }
+            lines
+        } else {
+            vec![]
+        };
+        let first_leader_line = dims.viewport_rows - leader_lines.len();
+        let pane_label = self.pane_select_label(pos);
+        let widget_placements = self.pane_widget_placements(&pos.pane);
+        let pending_compose = if pos.is_active {
+            self.pending_compose().cloned()
+        } else {
+            None
+        };
+        let suggestion = if pos.is_active && pending_compose.is_none() {
+            self.pane_suggestion(&pos.pane)
+        } else {
+            None
+        };
+        let disable_ligatures = self.pane_state(pos.pane.pane_id()).disable_ligatures;
+        let command_results = if self.show_command_badges(pos.pane.pane_id()) {
+            pos.pane.get_command_results()
+        } else {
+            vec![]
+        };
         for (line_idx, line) in lines.iter().enumerate() {
             let stable_row = stable_top + line_idx as StableRowIndex;
 
-            let selrange = selrange.map_or(0..0, |sel| sel.cols_for_row(stable_row));
+            // Selecting part of a wide grapheme selects all of it
+            let selrange = selrange.map_or(0..0, |sel| {
+                line.grapheme_columns(sel.cols_for_row(stable_row, rectangular))
+            });
+
+            let badged_line;
+            let line = match command_results
+                .binary_search_by(|result| result.prompt_row.cmp(&stable_row))
+            {
+                Ok(idx) => match apply_command_badge(line, &command_results[idx], dims.cols) {
+                    Some(badged) => {
+                        badged_line = badged;
+                        &badged_line
+                    }
+                    None => line,
+                },
+                Err(_) => line,
+            };
+
+            let widget_line;
+            let line = match overlay_pane_widgets(line, line_idx, dims.cols, &widget_placements) {
+                Some(overlaid) => {
+                    widget_line = overlaid;
+                    &widget_line
+                }
+                None => line,
+            };
+
+            let suggestion_line;
+            let line = match suggestion.as_ref() {
+                Some(suggestion) if stable_row == cursor.y => {
+                    suggestion_line =
+                        overlay_suggestion_text(line, cursor.x, suggestion, dims.cols);
+                    &suggestion_line
+                }
+                _ => line,
+            };
+
+            let compose_line;
+            let line = match pending_compose.as_ref() {
+                Some(pending) if stable_row == cursor.y => {
+                    compose_line = overlay_compose_text(line, cursor.x, pending, dims.cols);
+                    &compose_line
+                }
+                _ => line,
+            };
+
+            let hud_line;
+            let line = match hud_lines.get(line_idx) {
+                Some(text) => {
+                    hud_line = overlay_hud_text(line, text, dims.cols);
+                    &hud_line
+                }
+                None => line,
+            };
+
+            let leader_line;
+            let line = match line_idx
+                .checked_sub(first_leader_line)
+                .and_then(|idx| leader_lines.get(idx))
+            {
+                Some(text) => {
+                    leader_line = overlay_hud_text(line, text, dims.cols);
+                    &leader_line
+                }
+                None => line,
+            };
+
+            let label_line;
+            let line = match pane_label.as_ref().and_then(|label| {
+                overlay_pane_label(line, line_idx, dims.cols, dims.viewport_rows, label)
+            }) {
+                Some(labelled) => {
+                    label_line = labelled;
+                    &label_line
+                }
+                None => line,
+            };
 
             self.render_screen_line_opengl(
                 RenderScreenLineOpenGLParams {
@@ -643,7 +916,7 @@ impl super::TermWindow {
                     cursor_border_color,
                     foreground,
                     pos,
-                    is_active: pos.is_active,
+                    is_active: !dim_pane,
                     selection_fg,
                     selection_bg,
                     cursor_fg,
@@ -652,10 +925,27 @@ impl super::TermWindow {
                     filled_box,
                     window_is_transparent,
                     default_bg,
+                    disable_ligatures,
                 },
                 &mut layers,
             )?;
         }
+
+        if let Some(label) = pane_label.as_ref() {
+            if let Some(placement) = scaled_label_text(dims.cols, dims.viewport_rows, label) {
+                self.render_scaled_label_text(
+                    pos,
+                    label,
+                    placement,
+                    if dim_pane {
+                        Some(config.inactive_pane_hsb)
+                    } else {
+                        None
+                    },
+                    &mut layers,
+                )?;
+            }
+        }
         /*
         if let Some(zone) = zone {
             // TODO: render a thingy to jump to prior prompt
@@ -666,20 +956,98 @@ impl super::TermWindow {
 
         let start = Instant::now();
         drop(layers);
+        self.frame_stats.borrow_mut().upload += start.elapsed();
         metrics::histogram!("paint_pane_opengl.drop.quads", start.elapsed());
         log::trace!("quad drop elapsed {:?}", start.elapsed());
 
         Ok(())
     }
 
-    pub fn call_draw(&mut self, frame: &mut glium::Frame) -> anyhow::Result<()> {
+    /// Paints the text of a PaneSelect label at the column, row and
+    /// scale given by `placement`, so that the text is enlarged along
+    /// with the label box
+    fn render_scaled_label_text(
+        &self,
+        pos: &PositionedPane,
+        label: &PaneLabel,
+        (cell_idx, line_idx, scale): (usize, usize, usize),
+        hsv: Option<HsbTransform>,
+        layers: &mut [MappedQuads; 3],
+    ) -> anyhow::Result<()> {
+        let gl_state = self.render_state.as_ref().unwrap();
+        let palette = self.pane_palette(&pos.pane);
+        let first_line_offset = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
+            1
+        } else {
+            0
+        };
+        let line_idx = line_idx + first_line_offset;
+        let scale = scale as f32;
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let pos_y = (self.dimensions.pixel_height as f32 / -2.)
+            + (line_idx + pos.top) as f32 * cell_height
+            + self.config.window_padding.top as f32;
+
+        let fg_color = match (label.fg, label.bg) {
+            (Some(fg), _) => fg,
+            // The label box is drawn in reverse video
+            (None, None) => palette.background,
+            (None, Some(_)) => palette.foreground,
+        };
+        let fg_color = rgbcolor_to_window_color(fg_color);
+
+        let mut attrs = CellAttributes::default();
+        attrs.set_intensity(Intensity::Bold);
+        let line = Line::from_text(&label.text, &attrs);
+        for cluster in line.cluster() {
+            let style = self.fonts.match_style(&self.config, &cluster.attrs);
+            let glyph_info = self.cached_cluster_shape(style, &cluster, gl_state, &line, false)?;
+            let mut current_idx = cluster.first_cell_idx;
+            for info in glyph_info.iter() {
+                let glyph = &info.glyph;
+                if let Some(texture) = glyph.texture.as_ref() {
+                    let pos_x = (self.dimensions.pixel_width as f32 / -2.)
+                        + (cell_idx + pos.left) as f32 * cell_width
+                        + current_idx as f32 * cell_width * scale
+                        + self.config.window_padding.left as f32;
+                    let left = info.pos.x_offset.get() as f32 + info.pos.bearing_x;
+                    let top = ((PixelLength::new(self.render_metrics.cell_size.height as f64)
+                        + self.render_metrics.descender)
+                        - (glyph.y_offset + glyph.bearing_y))
+                        .get() as f32;
+                    let width = texture.coords.size.width as f32 * glyph.scale as f32;
+                    let height = texture.coords.size.height as f32 * glyph.scale as f32;
+
+                    let x0 = pos_x + left * scale;
+                    let y0 = pos_y + top * scale;
+                    let mut quad = layers[1].allocate()?;
+                    quad.set_position(x0, y0, x0 + width * scale, y0 + height * scale);
+                    quad.set_fg_color(fg_color);
+                    quad.set_texture(texture.texture_coords());
+                    quad.set_texture_adjust(0., 0., 0., 0.);
+                    quad.set_hsv(hsv);
+                    quad.set_has_color(glyph.has_color);
+                }
+                current_idx += info.pos.num_cells as usize;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn call_draw<S: Surface>(&mut self, frame: &mut S) -> anyhow::Result<()> {
         let gl_state = self.render_state.as_ref().unwrap();
         let tex = gl_state.glyph_cache.borrow().atlas.texture();
+        // While the font size transitions, the frame is scaled about
+        // its top left corner rather than laid out again
+        let render_scale = self.transition_render_scale();
+        let width = self.dimensions.pixel_width as f32;
+        let height = self.dimensions.pixel_height as f32;
         let projection = euclid::Transform3D::<f32, f32, f32>::ortho(
-            -(self.dimensions.pixel_width as f32) / 2.0,
-            self.dimensions.pixel_width as f32 / 2.0,
-            self.dimensions.pixel_height as f32 / 2.0,
-            -(self.dimensions.pixel_height as f32) / 2.0,
+            -width / 2.0,
+            -width / 2.0 + width / render_scale,
+            -height / 2.0 + height / render_scale,
+            -height / 2.0,
             -1.0,
             1.0,
         )
@@ -777,7 +1145,7 @@ impl super::TermWindow {
         let vb = &gl_state.vb[2];
         let mut vb_mut = vb.current_vb_mut();
         let mut quads = vb.map(&mut vb_mut);
-        let palette = pane.palette();
+        let palette = self.pane_palette(pane);
         let foreground = rgbcolor_to_window_color(palette.split);
         let cell_width = self.render_metrics.cell_size.width as f32;
         let cell_height = self.render_metrics.cell_size.height as f32;
@@ -912,7 +1280,29 @@ impl super::TermWindow {
 
         // Break the line into clusters of cells with the same attributes
         let start = Instant::now();
-        let cell_clusters = params.line.cluster();
+        let cursor_cells = if params.config.disable_ligatures_at_cursor
+            && params.stable_line_idx == Some(params.cursor.y)
+            && params.cursor.visibility == CursorVisibility::Visible
+        {
+            let width = params
+                .line
+                .cells()
+                .get(params.cursor.x)
+                .map_or(1, |cell| cell.width().max(1));
+            Some(params.cursor.x..params.cursor.x + width)
+        } else {
+            None
+        };
+        let cell_clusters = match cursor_cells {
+            // Shape the text under the cursor separately from the text
+            // either side of it, so that the characters that make up a
+            // ligature are shown while it is being edited.  That isn't
+            // needed when the ligatures of the pane are turned off.
+            Some(cursor_cells) if !params.disable_ligatures => params
+                .line
+                .cluster_with_breaks(|idx| idx == cursor_cells.start || idx == cursor_cells.end),
+            _ => params.line.cluster(),
+        };
         metrics::histogram!("render_screen_line_opengl.line.cluster", start.elapsed());
         log::trace!(
             "cluster -> {} clusters, elapsed {:?}",
@@ -1044,6 +1434,8 @@ impl super::TermWindow {
                         bg_default = false;
                     }
 
+                    fg = self.ensure_min_contrast(fg, bg, params.config);
+
                     // Check for blink, and if this is the "not-visible"
                     // part of blinking then set fg = bg.  This is a cheap
                     // means of getting it done without impacting other
@@ -1060,7 +1452,7 @@ impl super::TermWindow {
                         )),
                     };
                     if let Some((blink_rate, mut last_time)) = blink_rate {
-                        if blink_rate != 0 {
+                        if blink_rate != 0 && !params.config.reduce_motion {
                             let ticks = milli_uptime / blink_rate as u128;
                             if (ticks & 1) == 0 {
                                 fg = bg;
@@ -1108,8 +1500,13 @@ impl super::TermWindow {
             let style_params = last_style.as_ref().expect("we literally just assigned it");
 
             // Shape the printable text from this cluster
-            let glyph_info =
-                self.cached_cluster_shape(style_params.style, &cluster, &gl_state, params.line)?;
+            let glyph_info = self.cached_cluster_shape(
+                style_params.style,
+                &cluster,
+                &gl_state,
+                params.line,
+                params.disable_ligatures,
+            )?;
 
             let mut current_idx = cluster.first_cell_idx;
 
@@ -1151,6 +1548,7 @@ impl super::TermWindow {
                         selection: &params.selection,
                         fg_color: style_params.fg_color,
                         bg_color: style_params.bg_color,
+                        reverse: style_params.attrs.reverse() != params.line.is_reverse(),
                         palette: params.palette,
                         is_active_pane: params.pos.is_active,
                         config: params.config,
@@ -1387,6 +1785,7 @@ impl super::TermWindow {
                     selection: &params.selection,
                     fg_color: params.foreground,
                     bg_color: params.default_bg,
+                    reverse: params.line.is_reverse(),
                     palette: params.palette,
                     is_active_pane: params.pos.is_active,
                     config: params.config,
@@ -1564,13 +1963,29 @@ impl super::TermWindow {
         Ok(())
     }
 
+    /// Returns `fg`, adjusted if necessary so that its contrast
+    /// with `bg` satisfies the `minimum_contrast_ratio` option
+    fn ensure_min_contrast(&self, fg: RgbColor, bg: RgbColor, config: &ConfigHandle) -> RgbColor {
+        if config.minimum_contrast_ratio <= 1.0 {
+            return fg;
+        }
+
+        let mut cache = self.contrast_cache.borrow_mut();
+        if let Some(&adjusted) = cache.get(&(fg, bg)) {
+            return adjusted;
+        }
+        let adjusted = fg.ensure_contrast_ratio(bg, config.minimum_contrast_ratio.min(21.0));
+        cache.put((fg, bg), adjusted);
+        adjusted
+    }
+
     pub fn compute_cell_fg_bg(&self, params: ComputeCellFgBgParams) -> ComputeCellFgBgResult {
         let selected = params.selection.contains(&params.cell_idx);
         let is_cursor =
             params.stable_line_idx == Some(params.cursor.y) && params.cursor.x == params.cell_idx;
 
         if is_cursor {
-            if let Some(intensity) = self.get_intensity_if_bell_target_ringing(
+            if let Some((intensity, color)) = self.get_intensity_if_bell_target_ringing(
                 params.pane,
                 params.config,
                 VisualBellTarget::CursorColor,
@@ -1584,10 +1999,8 @@ impl super::TermWindow {
                 // interpolate between the background color
                 // and the the target color
                 let (r1, g1, b1, a) = bg_color.tuple();
-                let (r, g, b, _) = params
-                    .config
-                    .resolved_palette
-                    .visual_bell
+                let (r, g, b, _) = color
+                    .or(params.config.resolved_palette.visual_bell)
                     .map(|c| c.to_linear_tuple_rgba())
                     .unwrap_or_else(|| fg_color.tuple());
 
@@ -1614,15 +2027,14 @@ impl super::TermWindow {
                 // visible.
                 // If the cursor is set to a blinking mode then we are visible
                 // depending on the current time.
-                let shape = params
-                    .config
-                    .default_cursor_style
-                    .effective_shape(params.cursor.shape);
+                let shape = params.config.cursor_shape(params.cursor.shape);
+                let blink_rate = params.config.cursor_blink_rate_for(shape);
                 // Work out the blinking shape if its a blinking cursor and it hasn't been disabled
                 // and the window is focused.
                 let blinking = params.is_active_pane
                     && shape.is_blinking()
-                    && params.config.cursor_blink_rate != 0
+                    && blink_rate != 0
+                    && !params.config.reduce_motion
                     && self.focused.is_some();
                 if blinking {
                     let now = std::time::Instant::now();
@@ -1630,7 +2042,7 @@ impl super::TermWindow {
                     // schedule an invalidation so that we can paint the next
                     // cycle at the right time.
                     if let Some(window) = self.window.clone() {
-                        let interval = Duration::from_millis(params.config.cursor_blink_rate);
+                        let interval = Duration::from_millis(blink_rate);
                         let next = *self.next_blink_paint.borrow();
                         if next < now {
                             let target = next + interval;
@@ -1656,7 +2068,7 @@ impl super::TermWindow {
                     let milli_uptime = now
                         .duration_since(self.prev_cursor.last_cursor_movement())
                         .as_millis();
-                    let ticks = milli_uptime / params.config.cursor_blink_rate as u128;
+                    let ticks = milli_uptime / blink_rate as u128;
                     (
                         shape,
                         if (ticks & 1) == 0 {
@@ -1675,7 +2087,12 @@ impl super::TermWindow {
         let focused_and_active = self.focused.is_some() && params.is_active_pane;
 
         let (fg_color, bg_color) = match (selected, focused_and_active, cursor_shape, visibility) {
-            // Selected text overrides colors
+            // Selected text overrides colors.  As in xterm, the selection
+            // colors are swapped for text that is shown in reverse video,
+            // so that it can be distinguished from the rest of the selection
+            (true, _, _, CursorVisibility::Hidden) if params.reverse => {
+                (params.selection_bg, params.selection_fg)
+            }
             (true, _, _, CursorVisibility::Hidden) => (params.selection_fg, params.selection_bg),
             // Cursor cell overrides colors
             (_, true, CursorShape::BlinkingBlock, CursorVisibility::Visible)
@@ -1729,17 +2146,19 @@ impl super::TermWindow {
     }
 
     /// Shape the printable text from a cluster
-    fn cached_cluster_shape(
+    pub fn cached_cluster_shape(
         &self,
         style: &TextStyle,
         cluster: &CellCluster,
         gl_state: &RenderState,
         line: &Line,
+        without_ligatures: bool,
     ) -> anyhow::Result<Rc<Vec<ShapedInfo<SrgbTexture2d>>>> {
         let shape_resolve_start = Instant::now();
         let key = BorrowedShapeCacheKey {
             style,
             text: &cluster.text,
+            without_ligatures,
         };
         let glyph_info = match self.lookup_cached_shape(&key) {
             Some(Ok(info)) => info,
@@ -1747,12 +2166,23 @@ impl super::TermWindow {
             None => {
                 let font = self.fonts.resolve_font(style)?;
                 let window = self.window.as_ref().unwrap().clone();
-                match font.shape(
-                    &cluster.text,
-                    move || window.notify(TermWindowNotif::InvalidateShapeCache),
-                    BlockKey::filter_out_synthetic,
-                    Some(cluster.presentation),
-                ) {
+                let completion = move || window.notify(TermWindowNotif::InvalidateShapeCache);
+                let shaped = if without_ligatures {
+                    font.shape_without_ligatures(
+                        &cluster.text,
+                        completion,
+                        BlockKey::filter_out_synthetic,
+                        Some(cluster.presentation),
+                    )
+                } else {
+                    font.shape(
+                        &cluster.text,
+                        completion,
+                        BlockKey::filter_out_synthetic,
+                        Some(cluster.presentation),
+                    )
+                };
+                match shaped {
                     Ok(info) => {
                         let glyphs = self.glyph_infos_to_glyphs(
                             cluster,
@@ -1786,6 +2216,7 @@ impl super::TermWindow {
             }
         };
         metrics::histogram!("cached_cluster_shape", shape_resolve_start.elapsed());
+        self.frame_stats.borrow_mut().shape += shape_resolve_start.elapsed();
         log::trace!(
             "shape_resolve for cluster len {} -> elapsed {:?}",
             cluster.text.len(),
@@ -1855,3 +2286,80 @@ fn resolve_fg_color_attr(
         _ => params.palette.resolve_fg(fg),
     }
 }
+
+/// Returns a copy of `line` with `text` drawn over its right hand side
+fn overlay_hud_text(line: &Line, text: &str, cols: usize) -> Line {
+    let text = format!(" {} ", text);
+    let mut line = line.clone();
+    line.resize(cols, line.current_seqno());
+    let start = cols.saturating_sub(unicode_column_width(&text));
+    let mut attrs = CellAttributes::default();
+    attrs.set_reverse(true);
+    line.overlay_text_with_attribute(start, &text, attrs, line.current_seqno());
+    line
+}
+
+/// Formats a command duration compactly, eg: `350ms`, `4.2s`, `3m07s`
+fn format_command_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else if secs >= 1 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+/// Returns a copy of `line` with a badge describing `result` placed
+/// at the right hand side of a pane that is `cols` wide.  Returns None
+/// if the badge would overwrite any text on the line.
+fn apply_command_badge(line: &Line, result: &CommandResult, cols: usize) -> Option<Line> {
+    let (indicator, color) = match result.exit_status {
+        Some(0) => ("✓ ".to_string(), ColorAttribute::PaletteIndex(2)),
+        Some(status) => (format!("✗ {} ", status), ColorAttribute::PaletteIndex(1)),
+        None => (String::new(), ColorAttribute::Default),
+    };
+    let badge = format!(
+        " {}{} ",
+        indicator,
+        format_command_duration(result.duration)
+    );
+
+    let width = unicode_column_width(&badge);
+    let start = cols.checked_sub(width)?;
+    if line
+        .cells()
+        .get(start..)
+        .unwrap_or(&[])
+        .iter()
+        .any(|cell| cell.str() != " " || cell.attrs().hyperlink().is_some())
+    {
+        return None;
+    }
+
+    let mut attrs = CellAttributes::default();
+    attrs
+        .set_foreground(color)
+        .set_intensity(termwiz::cell::Intensity::Half)
+        .set_reverse(true);
+    let mut line = line.clone();
+    line.resize(cols, line.current_seqno());
+    line.overlay_text_with_attribute(start, &badge, attrs, line.current_seqno());
+    Some(line)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn command_duration() {
+        assert_eq!(format_command_duration(Duration::from_millis(350)), "350ms");
+        assert_eq!(format_command_duration(Duration::from_millis(4300)), "4.3s");
+        assert_eq!(format_command_duration(Duration::from_secs(187)), "3m07s");
+        assert_eq!(format_command_duration(Duration::from_secs(7260)), "2h01m");
+    }
+}
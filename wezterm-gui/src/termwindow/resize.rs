@@ -248,10 +248,11 @@ impl super::TermWindow {
         self.apply_dimensions(&dimensions, scale_changed_cells, window);
     }
 
-    /// Used for applying font size changes only; this takes into account
-    /// the `adjust_window_size_when_changing_font_size` configuration and
-    /// revises the scaling/resize change accordingly
-    pub fn adjust_font_scale(&mut self, font_scale: f64, window: &Window) {
+    /// Changes the font scale immediately, without the transition that
+    /// `adjust_font_scale` may animate.  This reloads the fonts and takes
+    /// into account the `adjust_window_size_when_changing_font_size`
+    /// configuration to either resize the window or the terminal.
+    pub fn apply_font_scale(&mut self, font_scale: f64, window: &Window) {
         if self.window_state.can_resize() && self.config.adjust_window_size_when_changing_font_size
         {
             self.scaling_changed(self.dimensions, font_scale, window);
@@ -265,11 +266,11 @@ impl super::TermWindow {
     }
 
     pub fn decrease_font_size(&mut self, window: &Window) {
-        self.adjust_font_scale(self.fonts.get_font_scale() * 0.9, window);
+        self.adjust_font_scale(self.target_font_scale() * 0.9, window);
     }
 
     pub fn increase_font_size(&mut self, window: &Window) {
-        self.adjust_font_scale(self.fonts.get_font_scale() * 1.1, window);
+        self.adjust_font_scale(self.target_font_scale() * 1.1, window);
     }
 
     pub fn reset_font_size(&mut self, window: &Window) {
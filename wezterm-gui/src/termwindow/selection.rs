@@ -1,40 +1,109 @@
 use crate::selection::{SelectionCoordinate, SelectionMode, SelectionRange};
 use ::window::WindowOps;
+use config::{configuration, CopyLineWrap};
 use mux::pane::Pane;
 use std::rc::Rc;
 use wezterm_term::StableRowIndex;
 
 impl super::TermWindow {
     pub fn selection_text(&self, pane: &Rc<dyn Pane>) -> String {
-        let mut s = String::new();
-        if let Some(sel) = self
-            .selection(pane.pane_id())
-            .range
-            .as_ref()
-            .map(|r| r.normalize())
-        {
-            let mut last_was_wrapped = false;
-            let first_row = sel.rows().start;
-            let last_row = sel.rows().end;
-
-            for line in pane.get_logical_lines(sel.rows()) {
-                if !s.is_empty() && !last_was_wrapped {
-                    s.push('\n');
+        self.selection_text_with_wrap(pane, self.config.copy_line_wrap)
+    }
+
+    /// Returns the selected text, treating wrapped lines according
+    /// to `wrap` rather than the `copy_line_wrap` configuration
+    pub fn selection_text_with_wrap(&self, pane: &Rc<dyn Pane>, wrap: CopyLineWrap) -> String {
+        let (range, rectangular) = {
+            let selection = self.selection(pane.pane_id());
+            (selection.range, selection.rectangular)
+        };
+        let trim = self.config.copy_trim_trailing_whitespace;
+        match range {
+            Some(range) if rectangular => Self::text_for_rectangle_with(pane, &range, trim),
+            Some(range) => Self::text_for_range_with(pane, &range, wrap, trim),
+            None => String::new(),
+        }
+    }
+
+    /// Returns the text from `pane` in the block of cells with
+    /// corners at the start and end of `range`, one line per row
+    pub fn text_for_rectangle(pane: &Rc<dyn Pane>, range: &SelectionRange) -> String {
+        Self::text_for_rectangle_with(pane, range, configuration().copy_trim_trailing_whitespace)
+    }
+
+    fn text_for_rectangle_with(pane: &Rc<dyn Pane>, range: &SelectionRange, trim: bool) -> String {
+        let sel = range.normalize();
+        let (first_row, lines) = pane.get_lines(sel.rows());
+        lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| {
+                let cols = line
+                    .grapheme_columns(sel.cols_for_row(first_row + idx as StableRowIndex, true));
+                let text = line.columns_as_str(cols);
+                if trim {
+                    text.trim_end().to_string()
+                } else {
+                    text
                 }
-                for (idx, phys) in line.physical_lines.iter().enumerate() {
-                    let this_row = line.first_row + idx as StableRowIndex;
-                    if this_row >= first_row && this_row < last_row {
-                        let last_phys_idx = phys.cells().len().saturating_sub(1);
-                        let cols = sel.cols_for_row(this_row);
-                        let last_col_idx = cols.end.saturating_sub(1).min(last_phys_idx);
-                        s.push_str(phys.columns_as_str(cols).trim_end());
-
-                        last_was_wrapped = last_col_idx == last_phys_idx
-                            && phys
-                                .cells()
-                                .get(last_col_idx)
-                                .map(|c| c.attrs().wrapped())
-                                .unwrap_or(false);
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Returns the text from `pane` that is covered by `range`
+    pub fn text_for_range(pane: &Rc<dyn Pane>, range: &SelectionRange) -> String {
+        let config = configuration();
+        Self::text_for_range_with(
+            pane,
+            range,
+            config.copy_line_wrap,
+            config.copy_trim_trailing_whitespace,
+        )
+    }
+
+    /// Returns the text from `pane` that is covered by `range`.
+    /// With `CopyLineWrap::Logical`, rows that were wrapped are joined
+    /// without a line break and only the end of each logical line is
+    /// trimmed, so that whitespace at a wrap point is preserved.
+    pub fn text_for_range_with(
+        pane: &Rc<dyn Pane>,
+        range: &SelectionRange,
+        wrap: CopyLineWrap,
+        trim: bool,
+    ) -> String {
+        let sel = range.normalize();
+        let mut s = String::new();
+        let mut first = true;
+        let mut last_was_wrapped = false;
+        let first_row = sel.rows().start;
+        let last_row = sel.rows().end;
+        let join_wrapped = wrap == CopyLineWrap::Logical;
+
+        for line in pane.get_logical_lines(sel.rows()) {
+            for (idx, phys) in line.physical_lines.iter().enumerate() {
+                let this_row = line.first_row + idx as StableRowIndex;
+                if this_row >= first_row && this_row < last_row {
+                    if !first && !(join_wrapped && last_was_wrapped) {
+                        s.push('\n');
+                    }
+                    first = false;
+
+                    let last_phys_idx = phys.cells().len().saturating_sub(1);
+                    let cols = phys.grapheme_columns(sel.cols_for_row(this_row, false));
+                    let last_col_idx = cols.end.saturating_sub(1).min(last_phys_idx);
+                    last_was_wrapped = last_col_idx == last_phys_idx
+                        && phys
+                            .cells()
+                            .get(last_col_idx)
+                            .map(|c| c.attrs().wrapped())
+                            .unwrap_or(false);
+
+                    let text = phys.columns_as_str(cols);
+                    if trim && !(join_wrapped && last_was_wrapped) {
+                        s.push_str(text.trim_end());
+                    } else {
+                        s.push_str(&text);
                     }
                 }
             }
@@ -51,8 +120,9 @@ impl super::TermWindow {
         self.selection(pane.pane_id()).seqno = pane.get_current_seqno();
         let mode = mode.unwrap_or(SelectionMode::Cell);
         let (x, y) = self.last_mouse_terminal_coords;
+        self.selection(pane.pane_id()).rectangular = mode == SelectionMode::Block;
         match mode {
-            SelectionMode::Cell => {
+            SelectionMode::Cell | SelectionMode::Block => {
                 let end = SelectionCoordinate { x, y };
                 let selection_range = self.selection(pane.pane_id()).range.take();
                 let sel = match selection_range {
@@ -138,6 +208,7 @@ impl super::TermWindow {
 
     pub fn select_text_at_mouse_cursor(&mut self, mode: SelectionMode, pane: &Rc<dyn Pane>) {
         let (x, y) = self.last_mouse_terminal_coords;
+        self.selection(pane.pane_id()).rectangular = false;
         match mode {
             SelectionMode::Line => {
                 let start = SelectionCoordinate { x, y };
@@ -164,6 +235,11 @@ impl super::TermWindow {
                 self.selection(pane.pane_id())
                     .begin(SelectionCoordinate { x, y });
             }
+            SelectionMode::Block => {
+                let mut selection = self.selection(pane.pane_id());
+                selection.begin(SelectionCoordinate { x, y });
+                selection.rectangular = true;
+            }
         }
 
         self.selection(pane.pane_id()).seqno = pane.get_current_seqno();
@@ -1,14 +1,24 @@
+use crate::overlay::{confirm_load_env_file, start_overlay};
+use crate::scripting::pane::PaneObject;
 use crate::termwindow::{ClipboardHelper, MuxWindowId};
 use anyhow::{anyhow, bail};
-use config::keyassignment::{SpawnCommand, SpawnTabDomain};
+use config::keyassignment::{SpawnCommand, SpawnInheritance, SpawnTabDomain};
 use config::TermConfig;
+use mlua::FromLua;
 use mux::activity::Activity;
-use mux::domain::DomainState;
+use mux::domain::{DomainState, LocalDomain};
+use mux::encoding::CharacterEncoding;
+use mux::localpane::LocalPane;
+use mux::pane::Pane;
 use mux::tab::SplitDirection;
 use mux::Mux;
 use percent_encoding::percent_decode_str;
 use portable_pty::{CommandBuilder, PtySize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::Arc;
+use termwiz::escape::{Action, OperatingSystemCommand};
 
 #[derive(Copy, Debug, Clone, Eq, PartialEq)]
 pub enum SpawnWhere {
@@ -19,25 +29,120 @@ pub enum SpawnWhere {
 
 impl super::TermWindow {
     pub fn spawn_command(&mut self, spawn: &SpawnCommand, spawn_where: SpawnWhere) {
+        let spawn = &self.spawn_with_profile_domain(spawn, spawn_where == SpawnWhere::NewWindow);
         let size = if spawn_where == SpawnWhere::NewWindow {
             self.config.initial_size()
         } else {
             self.terminal_size
         };
         let term_config = Arc::new(TermConfig::with_config(self.config.clone()));
+        let clipboard = ClipboardHelper {
+            window: self.window.as_ref().unwrap().clone(),
+        };
+
+        if self.config.load_env_files {
+            if let Some(env_file) = self.env_file_for_spawn(spawn) {
+                let mut spawn = spawn.clone();
+                if config::is_env_file_trusted(&env_file) {
+                    let mux_window_id = self.mux_window_id;
+                    promise::spawn::spawn(async move {
+                        let env_file_loaded = apply_env_file(&mut spawn, env_file).await;
+                        Self::spawn_command_impl(
+                            &spawn,
+                            spawn_where,
+                            size,
+                            mux_window_id,
+                            clipboard,
+                            term_config,
+                            env_file_loaded,
+                        );
+                    })
+                    .detach();
+                    return;
+                }
+
+                let mux = Mux::get().unwrap();
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    // Ask the user before loading it for the first time
+                    let mux_window_id = self.mux_window_id;
+                    let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+                        confirm_load_env_file(
+                            term,
+                            env_file,
+                            spawn,
+                            spawn_where,
+                            size,
+                            mux_window_id,
+                            clipboard,
+                            term_config,
+                        )
+                    });
+                    self.assign_overlay(tab.tab_id(), overlay);
+                    promise::spawn::spawn(future).detach();
+                    return;
+                }
+                return Self::spawn_command_impl(
+                    &spawn,
+                    spawn_where,
+                    size,
+                    self.mux_window_id,
+                    clipboard,
+                    term_config,
+                    false,
+                );
+            }
+        }
 
         Self::spawn_command_impl(
             spawn,
             spawn_where,
             size,
             self.mux_window_id,
-            ClipboardHelper {
-                window: self.window.as_ref().unwrap().clone(),
-            },
+            clipboard,
             term_config,
+            false,
         )
     }
 
+    /// Returns the environment file that applies to `spawn`, if it
+    /// will be spawned into a local directory that has one
+    fn env_file_for_spawn(&self, spawn: &SpawnCommand) -> Option<PathBuf> {
+        let mux = Mux::get().unwrap();
+        let pane = mux
+            .get_active_tab_for_window(self.mux_window_id)
+            .and_then(|tab| tab.get_active_pane());
+
+        let domain = match &spawn.domain {
+            SpawnTabDomain::DefaultDomain => mux.default_domain(),
+            SpawnTabDomain::CurrentPaneDomain => match &pane {
+                Some(pane) => mux.get_domain(pane.domain_id())?,
+                None => mux.default_domain(),
+            },
+            SpawnTabDomain::DomainName(name) => {
+                // No working directory is inherited in this case
+                spawn.cwd.as_ref()?;
+                mux.get_domain_by_name(name)?
+            }
+        };
+        if domain.downcast_ref::<LocalDomain>().is_none() {
+            return None;
+        }
+
+        let cwd = match &spawn.cwd {
+            Some(cwd) => cwd.clone(),
+            None => {
+                let url = pane?.get_current_working_dir()?;
+                if url.scheme() != "file" {
+                    return None;
+                }
+                url.to_file_path().ok()?
+            }
+        };
+        config::find_env_file(&cwd)
+    }
+
+    /// `env_file_loaded` is true if the environment of `spawn` was
+    /// augmented by `apply_env_file`
     pub fn spawn_command_impl(
         spawn: &SpawnCommand,
         spawn_where: SpawnWhere,
@@ -45,6 +150,7 @@ impl super::TermWindow {
         src_window_id: MuxWindowId,
         clipboard: ClipboardHelper,
         term_config: Arc<TermConfig>,
+        env_file_loaded: bool,
     ) {
         let spawn = spawn.clone();
 
@@ -56,6 +162,7 @@ impl super::TermWindow {
                 src_window_id,
                 clipboard,
                 term_config,
+                env_file_loaded,
             )
             .await
             {
@@ -65,20 +172,30 @@ impl super::TermWindow {
         .detach();
     }
 
-    async fn spawn_command_internal(
-        spawn: SpawnCommand,
+    /// Spawns `spawn`, returning the newly created pane
+    pub async fn spawn_command_internal(
+        mut spawn: SpawnCommand,
         spawn_where: SpawnWhere,
         size: PtySize,
         src_window_id: MuxWindowId,
         clipboard: ClipboardHelper,
         term_config: Arc<TermConfig>,
-    ) -> anyhow::Result<()> {
+        env_file_loaded: bool,
+    ) -> anyhow::Result<Rc<dyn Pane>> {
         let mux = Mux::get().unwrap();
         let activity = Activity::new();
         let mux_builder;
+        let config = config::configuration();
+        let inherit = spawn
+            .inherit
+            .clone()
+            .unwrap_or_else(|| config.spawn_inheritance.clone());
+        let src_pane = mux
+            .get_active_tab_for_window(src_window_id)
+            .and_then(|tab| tab.get_active_pane());
 
         let target_window_id = if spawn_where == SpawnWhere::NewWindow {
-            mux_builder = mux.new_empty_window();
+            mux_builder = mux.new_empty_window(None);
             *mux_builder
         } else {
             src_window_id
@@ -129,6 +246,8 @@ impl super::TermWindow {
             bail!("Cannot spawn a tab into a Detached domain");
         }
 
+        let cwd = if inherit.cwd { cwd } else { None };
+
         let cwd = if let Some(cwd) = spawn.cwd.as_ref() {
             Some(cwd.to_str().map(|s| s.to_owned()).ok_or_else(|| {
                 anyhow!(
@@ -147,6 +266,11 @@ impl super::TermWindow {
                         let bytes = path.as_bytes();
                         if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
                             Some(path[1..].to_owned())
+                        } else if cfg!(windows) && domain.downcast_ref::<LocalDomain>().is_some() {
+                            // The working directory was reported by a program
+                            // running inside WSL; map it back to a windows path
+                            // if possible, otherwise let the domain pick
+                            config::wsl_path_to_windows(&path, None)
                         } else {
                             Some(path)
                         }
@@ -158,22 +282,61 @@ impl super::TermWindow {
             }
         };
 
-        let cmd_builder = if let Some(args) = spawn.args {
-            let mut builder = CommandBuilder::from_argv(args.iter().map(Into::into).collect());
-            for (k, v) in spawn.set_environment_variables.iter() {
-                builder.env(k, v);
-            }
-            if let Some(cwd) = spawn.cwd {
-                builder.cwd(cwd);
+        // Project rules consult the local filesystem, so they are
+        // only applied when spawning locally
+        let is_local = domain.downcast_ref::<LocalDomain>().is_some();
+        let project = match cwd.clone() {
+            Some(cwd) if is_local && !config.project_rules.is_empty() => {
+                // Walking up from the cwd may touch slow or unresponsive
+                // filesystems, so it is done away from the gui thread
+                let rules = config.project_rules.clone();
+                smol::unblock(move || {
+                    config::find_project_rule(&rules, Path::new(&cwd))
+                        .map(|(rule, root)| (rule.clone(), root))
+                })
+                .await
             }
-            Some(builder)
-        } else {
-            None
+            _ => None,
         };
+        let tab_title = project
+            .as_ref()
+            .and_then(|(rule, root)| rule.expand_tab_title(root));
+
+        if let Some((rule, _root)) = project.as_ref() {
+            if spawn.args.is_none() && rule.args.is_some() {
+                spawn.args = rule.args.clone();
+            }
+            for (k, v) in rule.set_environment_variables.iter() {
+                spawn
+                    .set_environment_variables
+                    .entry(k.clone())
+                    .or_insert_with(|| v.clone());
+            }
+        }
+
+        if let Some(pane) = src_pane.as_ref() {
+            for (k, v) in inherited_environment(pane, &inherit) {
+                spawn.set_environment_variables.entry(k).or_insert(v);
+            }
+        }
+
+        let mut cwd = cwd;
+        if let Some(mutated) = mutate_spawn_command(src_pane.as_ref(), &spawn, cwd.as_deref()) {
+            spawn = mutated;
+            cwd = spawn
+                .cwd
+                .as_ref()
+                .and_then(|cwd| cwd.to_str().map(|s| s.to_owned()));
+        }
+
+        let encoding = spawn_encoding(&spawn)?;
+        // The environment from a project rule or env file is passed
+        // on to the default program
+        let cmd_builder = command_builder_for_spawn(&spawn, project.is_some() || env_file_loaded)?;
 
         let clipboard: Arc<dyn wezterm_term::Clipboard> = Arc::new(clipboard);
 
-        match spawn_where {
+        let pane = match spawn_where {
             SpawnWhere::SplitPane(direction) => {
                 let mux = Mux::get().unwrap();
                 if let Some(tab) = mux.get_active_tab_for_window(target_window_id) {
@@ -183,23 +346,33 @@ impl super::TermWindow {
 
                     log::trace!("doing split_pane");
                     let pane = domain
-                        .split_pane(cmd_builder, cwd, tab.tab_id(), pane.pane_id(), direction)
+                        .split_pane(
+                            cmd_builder,
+                            cwd,
+                            encoding,
+                            tab.tab_id(),
+                            pane.pane_id(),
+                            direction,
+                        )
                         .await?;
-                    pane.set_config(term_config);
+                    pane.set_config(Arc::new(term_config.for_domain(domain.domain_name())));
                     pane.set_clipboard(&clipboard);
+                    set_pane_title(&*pane, tab_title);
+                    pane
                 } else {
                     bail!("there is no active tab while splitting pane!?");
                 }
             }
             _ => {
                 let tab = domain
-                    .spawn(size, cmd_builder, cwd, target_window_id)
+                    .spawn(size, cmd_builder, cwd, encoding, target_window_id)
                     .await?;
                 let tab_id = tab.tab_id();
                 let pane = tab
                     .get_active_pane()
                     .ok_or_else(|| anyhow!("newly spawned tab to have a pane"))?;
-                pane.set_config(term_config);
+                pane.set_config(Arc::new(term_config.for_domain(domain.domain_name())));
+                set_pane_title(&*pane, tab_title);
 
                 if spawn_where != SpawnWhere::NewWindow {
                     pane.set_clipboard(&clipboard);
@@ -210,12 +383,13 @@ impl super::TermWindow {
                         window.save_and_then_set_active(idx);
                     }
                 }
+                pane
             }
         };
 
         drop(activity);
 
-        Ok(())
+        Ok(pane)
     }
 
     pub fn spawn_tab(&mut self, domain: &SpawnTabDomain) {
@@ -228,3 +402,184 @@ impl super::TermWindow {
         );
     }
 }
+
+/// Returns the environment variables that `inherit` selects from `pane`
+fn inherited_environment(
+    pane: &Rc<dyn Pane>,
+    inherit: &SpawnInheritance,
+) -> HashMap<String, String> {
+    let mut env = HashMap::new();
+    if let Some(cmd) = pane
+        .downcast_ref::<LocalPane>()
+        .and_then(|pane| pane.spawn_command())
+    {
+        for (k, v) in cmd.iter_env_as_str() {
+            if inherit.environment_variables.iter().any(|name| name == k) {
+                env.insert(k.to_string(), v.to_string());
+            }
+        }
+    }
+    if !inherit.user_vars.is_empty() {
+        let user_vars = pane.copy_user_vars();
+        for name in &inherit.user_vars {
+            if let Some(value) = user_vars.get(name) {
+                env.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+    env
+}
+
+/// Gives the `mutate-spawn-command` event the opportunity to adjust
+/// `spawn` before it is launched.  `cwd` is the working directory
+/// that has been resolved for the command.
+/// Returns None if the command should be used as-is.
+fn mutate_spawn_command(
+    pane: Option<&Rc<dyn Pane>>,
+    spawn: &SpawnCommand,
+    cwd: Option<&str>,
+) -> Option<SpawnCommand> {
+    let mut spawn = spawn.clone();
+    if let Some(cwd) = cwd {
+        spawn.cwd.replace(PathBuf::from(cwd));
+    }
+    let pane = pane.map(PaneObject::new);
+    match config::run_immediate_with_lua_config(|lua| {
+        let lua = match lua {
+            Some(lua) => lua,
+            None => return Ok(None),
+        };
+        let v = config::lua::emit_sync_callback(
+            &*lua,
+            ("mutate-spawn-command".to_string(), (pane, spawn)),
+        )?;
+        match v {
+            mlua::Value::Nil => Ok(None),
+            v => Ok(Some(SpawnCommand::from_lua(v, &*lua)?)),
+        }
+    }) {
+        Ok(spawn) => spawn,
+        Err(err) => {
+            log::warn!("mutate-spawn-command: {:#}", err);
+            None
+        }
+    }
+}
+
+/// Builds the command that opens the file location `uri`, as produced
+/// by the `open_in_editor` hyperlink rules, in an editor.
+/// Relative locations are resolved against the working directory of
+/// `pane`.  If that directory is on another host while the pane itself
+/// is local, then we are most likely looking at the output of an ssh
+/// session, so the editor is launched on that host via ssh.
+pub fn editor_spawn_command(pane: &Rc<dyn Pane>, uri: &str) -> Option<SpawnCommand> {
+    let location = config::FileLocation::parse(uri)?;
+    let config = config::configuration();
+
+    let is_local = Mux::get()
+        .and_then(|mux| mux.get_domain(pane.domain_id()))
+        .map(|domain| domain.downcast_ref::<LocalDomain>().is_some())
+        .unwrap_or(false);
+
+    let (cwd, remote_host) = match pane.get_current_working_dir() {
+        Some(url) if url.scheme() == "file" => {
+            let host = url.host_str().unwrap_or("").to_string();
+            let mut path = percent_decode_str(url.path())
+                .decode_utf8()
+                .ok()?
+                .into_owned();
+            // Strip the leading slash from `/C:\Users` style paths
+            let bytes = path.as_bytes();
+            if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
+                path.remove(0);
+            }
+            if is_local && !config::is_local_hostname(&host) {
+                (Some(path), Some(host))
+            } else {
+                (Some(path), None)
+            }
+        }
+        Some(_) | None => (None, None),
+    };
+
+    let args = config
+        .open_in_editor
+        .command_for(&location, cwd.as_deref(), remote_host.as_deref())?;
+
+    Some(SpawnCommand {
+        args: Some(args),
+        // The remote working directory doesn't exist locally
+        cwd: if remote_host.is_some() {
+            Some(config::HOME_DIR.clone())
+        } else {
+            None
+        },
+        domain: SpawnTabDomain::CurrentPaneDomain,
+        ..Default::default()
+    })
+}
+
+/// Assigns the title selected by a project rule to a newly spawned pane
+/// Builds the command to run for `spawn`.  Without `args`, the
+/// domain chooses the default program, which is run without any of
+/// the environment variables of `spawn`; when `pass_environment` is
+/// true and there are variables to set, the default program is
+/// built here so that they are passed on to it.
+pub fn command_builder_for_spawn(
+    spawn: &SpawnCommand,
+    pass_environment: bool,
+) -> anyhow::Result<Option<CommandBuilder>> {
+    let mut builder = match &spawn.args {
+        Some(args) => CommandBuilder::from_argv(args.iter().map(Into::into).collect()),
+        None if pass_environment && !spawn.set_environment_variables.is_empty() => {
+            config::configuration().build_prog(None)?
+        }
+        None => return Ok(None),
+    };
+    for (k, v) in spawn.set_environment_variables.iter() {
+        builder.env(k, v);
+    }
+    if let Some(cwd) = &spawn.cwd {
+        builder.cwd(cwd);
+    }
+    Ok(Some(builder))
+}
+
+fn set_pane_title(pane: &dyn Pane, title: Option<String>) {
+    if let Some(title) = title {
+        pane.perform_actions(vec![Action::OperatingSystemCommand(Box::new(
+            OperatingSystemCommand::SetIconNameAndWindowTitle(title),
+        ))]);
+    }
+}
+
+/// Resolves the character encoding requested by a spawn command
+pub fn spawn_encoding(spawn: &SpawnCommand) -> anyhow::Result<Option<CharacterEncoding>> {
+    spawn
+        .encoding
+        .as_deref()
+        .map(CharacterEncoding::for_label)
+        .transpose()
+}
+
+/// Merges the environment from `env_file` into `spawn`; variables
+/// that were explicitly set by `spawn` take precedence.
+/// Loading an `.envrc` runs direnv, which can take a while, so the
+/// file is loaded on a background thread.
+/// Returns true if any variables were loaded.
+pub async fn apply_env_file(spawn: &mut SpawnCommand, env_file: PathBuf) -> bool {
+    let path = env_file.clone();
+    match smol::unblock(move || config::load_env_file(&path)).await {
+        Ok(env) => {
+            let loaded = !env.is_empty();
+            for (k, v) in env {
+                spawn.set_environment_variables.entry(k).or_insert(v);
+            }
+            loaded
+        }
+        Err(err) => {
+            log::error!("Failed to load {}: {:#}", env_file.display(), err);
+            false
+        }
+    }
+}
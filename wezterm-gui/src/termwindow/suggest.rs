@@ -0,0 +1,350 @@
+//! Suggestions for the text at the prompt, enabled by
+//! `enable_scrollback_suggestions`.  Suggestions are only made while
+//! the cursor is in the input that follows a prompt marked by OSC 133
+//! shell integration.  The input is completed from the command history,
+//! or when no command matches, the word before the cursor is completed
+//! from the words in the scrollback.  The suggestion is computed in the
+//! background when the pane changes, drawn in dim text after the cursor
+//! once it is ready, and accepted via the `suggestion` key table.
+use super::keymacro::MacroInput;
+use crate::termwindow::TermWindowNotif;
+use ::window::{KeyEvent, WindowOps};
+use config::keyassignment::SUGGESTION_KEY_TABLE;
+use mux::pane::{Pane, PaneId};
+use mux::renderable::StableCursorPosition;
+use mux::termwiztermtab::TermWizTerminalPane;
+use mux::Mux;
+use std::rc::Rc;
+use termwiz::cell::{unicode_column_width, CellAttributes, Intensity};
+use wezterm_term::{Line, StableRowIndex};
+
+/// Words shorter than this aren't completed
+const MIN_WORD_LEN: usize = 2;
+/// How many rows above the cursor are searched for words
+const SUGGESTION_SCROLLBACK_ROWS: StableRowIndex = 2000;
+
+/// The suggestion for a pane, as of the content and cursor position
+/// from which it was computed.  `suggestion` is None while it is
+/// still being computed.
+#[derive(Debug, Clone, Default)]
+pub struct SuggestionCache {
+    seqno: usize,
+    cursor: StableCursorPosition,
+    suggestion: Option<String>,
+}
+
+/// Characters that make up words and paths
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || "_-./~".contains(c)
+}
+
+/// Returns the word that ends at the end of `input`
+fn last_word(input: &str) -> &str {
+    let start = input
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word_char(*c))
+        .last()
+        .map(|(idx, _)| idx)
+        .unwrap_or_else(|| input.len());
+    &input[start..]
+}
+
+/// Returns the remainder of the most recent single line command
+/// that starts with `input`
+fn suggest_command(input: &str, commands: &[String]) -> Option<String> {
+    if input.trim().is_empty() {
+        return None;
+    }
+    commands
+        .iter()
+        .find(|command| {
+            command.len() > input.len() && command.starts_with(input) && !command.contains('\n')
+        })
+        .map(|command| command[input.len()..].to_string())
+}
+
+/// Returns the remainder of the word in `lines` that is nearest to
+/// the end and that starts with the last word of `input`
+fn suggest_word<'a>(
+    input: &str,
+    lines: impl DoubleEndedIterator<Item = &'a str>,
+) -> Option<String> {
+    let prefix = last_word(input);
+    if prefix.chars().count() < MIN_WORD_LEN {
+        return None;
+    }
+    for line in lines.rev() {
+        let found = line
+            .split(|c: char| !is_word_char(c))
+            .rev()
+            .find(|word| word.len() > prefix.len() && word.starts_with(prefix));
+        if let Some(word) = found {
+            return Some(word[prefix.len()..].to_string());
+        }
+    }
+    None
+}
+
+/// Returns the part of `suggestion` up to and including its next word
+fn next_word(suggestion: &str) -> &str {
+    let mut seen_word = false;
+    let end = suggestion
+        .char_indices()
+        .find(|(_, c)| {
+            if is_word_char(*c) {
+                seen_word = true;
+                false
+            } else {
+                seen_word
+            }
+        })
+        .map(|(idx, _)| idx)
+        .unwrap_or_else(|| suggestion.len());
+    &suggestion[..end]
+}
+
+/// Draws `suggestion` in dim text after the cursor
+pub fn overlay_suggestion_text(line: &Line, x: usize, suggestion: &str, cols: usize) -> Line {
+    let mut line = line.clone();
+    line.resize(cols, line.current_seqno());
+    let available = cols.saturating_sub(x);
+    let mut text = String::new();
+    for c in suggestion.chars() {
+        let mut candidate = text.clone();
+        candidate.push(c);
+        if unicode_column_width(&candidate) > available {
+            break;
+        }
+        text = candidate;
+    }
+    let mut attrs = CellAttributes::default();
+    attrs.set_intensity(Intensity::Half);
+    line.overlay_text_with_attribute(x, &text, attrs, line.current_seqno());
+    line
+}
+
+impl super::TermWindow {
+    /// Returns the suggestion to show after the cursor in `pane`, if
+    /// one has been computed for its current content and cursor position
+    pub fn pane_suggestion(&self, pane: &Rc<dyn Pane>) -> Option<String> {
+        if !self.config.enable_scrollback_suggestions {
+            return None;
+        }
+        let state = self.pane_state(pane.pane_id());
+        let cache = state.suggestion.as_ref()?;
+        if cache.seqno == pane.get_current_seqno() && cache.cursor == pane.get_cursor_position() {
+            cache.suggestion.clone()
+        } else {
+            None
+        }
+    }
+
+    /// Called when `pane_id` has changed.  If it is the active pane and
+    /// its cursor is in the input after a prompt, the suggestion for that
+    /// input is computed in the background, and the window is invalidated
+    /// once it is ready.
+    pub fn update_suggestion(&mut self, pane_id: PaneId) {
+        if !self.config.enable_scrollback_suggestions {
+            return;
+        }
+        // Overlays, such as the launcher, aren't shells
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) if pane.pane_id() == pane_id => pane,
+            _ => return,
+        };
+        if pane.is_alt_screen_active() || pane.downcast_ref::<TermWizTerminalPane>().is_some() {
+            return;
+        }
+
+        let seqno = pane.get_current_seqno();
+        let cursor = pane.get_cursor_position();
+        if let Some(cache) = self.pane_state(pane_id).suggestion.as_ref() {
+            if cache.seqno == seqno && cache.cursor == cursor {
+                return;
+            }
+        }
+        self.pane_state(pane_id)
+            .suggestion
+            .replace(SuggestionCache {
+                seqno,
+                cursor,
+                suggestion: None,
+            });
+
+        let input = match self.suggestion_input(&pane, &cursor) {
+            Some(input) => input,
+            None => return,
+        };
+        let window = match self.window.clone() {
+            Some(window) => window,
+            None => return,
+        };
+
+        promise::spawn::spawn(async move {
+            let command_input = input.trim_start().to_string();
+            let command = smol::unblock(move || {
+                crate::commandhistory::with_recent_commands(|commands| {
+                    suggest_command(&command_input, commands)
+                })
+            })
+            .await;
+            let suggestion = match command {
+                Some(command) => Some(command),
+                None => {
+                    let lines = scrollback_text(pane_id, &cursor);
+                    smol::unblock(move || suggest_word(&input, lines.iter().map(String::as_str)))
+                        .await
+                }
+            };
+            if suggestion.is_none() {
+                return;
+            }
+            window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                // The pane may have changed again in the meantime
+                let current = {
+                    let mut state = term_window.pane_state(pane_id);
+                    match state.suggestion.as_mut() {
+                        Some(cache) if cache.seqno == seqno && cache.cursor == cursor => {
+                            cache.suggestion = suggestion;
+                            true
+                        }
+                        _ => false,
+                    }
+                };
+                if current {
+                    if let Some(window) = term_window.window.as_ref() {
+                        window.invalidate();
+                    }
+                }
+            })));
+        })
+        .detach();
+    }
+
+    /// Returns the text between the end of the prompt on the cursor
+    /// row and the cursor, provided that nothing follows the cursor
+    fn suggestion_input(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        cursor: &StableCursorPosition,
+    ) -> Option<String> {
+        let cols = pane.get_dimensions().cols;
+        let (_, lines) = pane.get_lines(cursor.y..cursor.y + 1);
+        let line = lines.into_iter().next()?;
+        // Only suggest at the end of the input
+        if !line.columns_as_str(cursor.x..cols).trim().is_empty() {
+            return None;
+        }
+        let input_start = self.prompt_input_start(pane, cursor)?;
+        Some(line.columns_as_str(input_start..cursor.x))
+    }
+
+    /// Handles `key` while a suggestion is shown in `pane`, performing
+    /// its binding in the `suggestion` key table.  Returns false if
+    /// no suggestion is shown or the key isn't bound.
+    pub fn suggestion_key(&mut self, pane: &Rc<dyn Pane>, key: &KeyEvent) -> bool {
+        if !self.config.enable_scrollback_suggestions {
+            return false;
+        }
+        let action = self
+            .input_map
+            .lookup_key_table(SUGGESTION_KEY_TABLE, &key.key, key.modifiers);
+        let action = match action {
+            Some(action) => action,
+            None => return false,
+        };
+        if self.pane_suggestion(pane).is_none() {
+            return false;
+        }
+        if let Err(err) = self.perform_key_assignment(pane, &action) {
+            log::error!("{:?} failed: {:#}", action, err);
+        }
+        true
+    }
+
+    /// Sends the suggestion that is shown in `pane`, or only its
+    /// next word if `whole` is false
+    pub fn accept_suggestion(&mut self, pane: &Rc<dyn Pane>, whole: bool) {
+        let suggestion = match self.pane_suggestion(pane) {
+            Some(suggestion) => suggestion,
+            None => return,
+        };
+        let text = if whole {
+            suggestion.as_str()
+        } else {
+            next_word(&suggestion)
+        };
+        pane.writer().write_all(text.as_bytes()).ok();
+        self.record_key_macro_input(MacroInput::Text(text.to_string()));
+        self.maybe_scroll_to_bottom_for_input(pane);
+        self.expect_key_echo(pane);
+    }
+}
+
+/// Returns the text of the rows above the cursor of `pane_id` that
+/// are searched for words to complete
+fn scrollback_text(pane_id: PaneId, cursor: &StableCursorPosition) -> Vec<String> {
+    let mux = Mux::get().unwrap();
+    let pane = match mux.get_pane(pane_id) {
+        Some(pane) => pane,
+        None => return vec![],
+    };
+    let top = (cursor.y - SUGGESTION_SCROLLBACK_ROWS).max(0);
+    let (_, lines) = pane.get_lines(top..cursor.y);
+    lines.iter().map(Line::as_str).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let commands = vec![
+            "cargo test --all".to_string(),
+            "cargo build".to_string(),
+            "for x in a b\ndo echo $x\ndone".to_string(),
+        ];
+        assert_eq!(
+            suggest_command("cargo ", &commands),
+            Some("test --all".to_string())
+        );
+        assert_eq!(
+            suggest_command("cargo b", &commands),
+            Some("uild".to_string())
+        );
+        assert_eq!(suggest_command("for", &commands), None);
+        assert_eq!(suggest_command("cargo build", &commands), None);
+        assert_eq!(suggest_command("", &commands), None);
+    }
+
+    #[test]
+    fn scrollback_words() {
+        let lines = vec![
+            "src/termwindow/mod.rs",
+            "Compiling wezterm-gui v0.1.0",
+            "error: could not compile wezterm-gui",
+        ];
+        assert_eq!(
+            suggest_word("vim src/t", lines.iter().copied()),
+            Some("ermwindow/mod.rs".to_string())
+        );
+        assert_eq!(
+            suggest_word("cargo build -p wez", lines.iter().copied()),
+            Some("term-gui".to_string())
+        );
+        // Too short to complete
+        assert_eq!(suggest_word("ls s", lines.iter().copied()), None);
+        assert_eq!(suggest_word("ls ", lines.iter().copied()), None);
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(last_word("cd ~/src/wez"), "~/src/wez");
+        assert_eq!(last_word("echo "), "");
+        assert_eq!(next_word("test --all"), "test");
+        assert_eq!(next_word(" --all"), " --all");
+        assert_eq!(next_word("/mod.rs"), "/mod.rs");
+    }
+}
@@ -0,0 +1,249 @@
+use crate::overlay::ThumbnailSlot;
+use ::window::glium;
+use ::window::glium::framebuffer::SimpleFrameBuffer;
+use ::window::glium::texture::{MipmapsOption, SrgbFormat, SrgbTexture2d};
+use ::window::glium::uniforms::MagnifySamplerFilter;
+use ::window::glium::{BlitTarget, Rect, Surface};
+use mux::pane::PaneId;
+use mux::tab::{PositionedPane, Tab};
+use mux::Mux;
+use std::rc::Rc;
+use std::sync::Arc;
+use termwiz::surface::SequenceNo;
+
+/// An offscreen rendering of a tab at the size of the window,
+/// which the tab overview scales down into its cards
+pub struct TabThumbnail {
+    seqno: SequenceNo,
+    width: u32,
+    height: u32,
+    texture: SrgbTexture2d,
+}
+
+impl super::TermWindow {
+    /// Returns the slots in which the tab overview wants the tabs to
+    /// be painted, if the overview is shown in the active tab.
+    /// Once the overview has been closed, its thumbnails are released.
+    fn tab_overview_slots(&mut self) -> Option<Vec<ThumbnailSlot>> {
+        let (overlay_id, layout) = match &self.tab_overview {
+            Some((overlay_id, layout)) => (*overlay_id, Arc::clone(layout)),
+            None => return None,
+        };
+
+        let mux = Mux::get().unwrap();
+        if mux.get_pane(overlay_id).is_none() {
+            self.tab_overview.take();
+            self.tab_thumbnails.clear();
+            return None;
+        }
+
+        let tab = mux.get_active_tab_for_window(self.mux_window_id)?;
+        let overlay = self.tab_state(tab.tab_id()).overlay.as_ref()?.pane_id();
+        if overlay != overlay_id {
+            return None;
+        }
+
+        let slots = layout.lock().unwrap().clone();
+        Some(slots)
+    }
+
+    /// Returns true if `pane_id` is part of a tab whose thumbnail is
+    /// currently shown by the tab overview, so that its output needs
+    /// to be painted
+    pub fn is_pane_in_tab_thumbnail(&mut self, pane_id: PaneId) -> bool {
+        let slots = match self.tab_overview_slots() {
+            Some(slots) => slots,
+            None => return false,
+        };
+        let mux = Mux::get().unwrap();
+        slots.iter().any(|slot| {
+            mux.get_tab(slot.tab_id)
+                .map(|tab| tab.contains_pane(pane_id))
+                .unwrap_or(false)
+        })
+    }
+
+    /// Paints each of the tabs shown by the tab overview, scaled down
+    /// to fit its slot.  This must be called after the window has been
+    /// drawn into `frame`, as it reuses the quad buffers.
+    pub fn paint_tab_thumbnails(&mut self, frame: &mut glium::Frame) {
+        let slots = match self.tab_overview_slots() {
+            Some(slots) => slots,
+            None => return,
+        };
+        self.tab_thumbnails
+            .retain(|tab_id, _| slots.iter().any(|slot| slot.tab_id == *tab_id));
+
+        // Painting the panes records their UI items, which must not be
+        // hit tested as though they were part of the window
+        let ui_items = std::mem::take(&mut self.ui_items);
+
+        let mux = Mux::get().unwrap();
+        for slot in &slots {
+            let tab = match mux.get_tab(slot.tab_id) {
+                Some(tab) => tab,
+                None => continue,
+            };
+            if let Err(err) = self.update_tab_thumbnail(&tab) {
+                log::error!(
+                    "Failed to render thumbnail of tab {}: {:#}",
+                    slot.tab_id,
+                    err
+                );
+                continue;
+            }
+            let target = match self.thumbnail_target(slot) {
+                Some(target) => target,
+                None => continue,
+            };
+            if let Some(thumbnail) = self.tab_thumbnails.get(&slot.tab_id) {
+                let context = &self.render_state.as_ref().unwrap().context;
+                match SimpleFrameBuffer::new(context, &thumbnail.texture) {
+                    Ok(fb) => fb.blit_color(
+                        &Rect {
+                            left: 0,
+                            bottom: 0,
+                            width: thumbnail.width,
+                            height: thumbnail.height,
+                        },
+                        &*frame,
+                        &target,
+                        MagnifySamplerFilter::Linear,
+                    ),
+                    Err(err) => log::error!("Failed to blit thumbnail: {:#}", err),
+                }
+            }
+        }
+
+        self.ui_items = ui_items;
+    }
+
+    /// Renders `tab` into its offscreen texture, unless the texture
+    /// is already up to date
+    fn update_tab_thumbnail(&mut self, tab: &Rc<Tab>) -> anyhow::Result<()> {
+        let width = self.dimensions.pixel_width as u32;
+        let height = self.dimensions.pixel_height as u32;
+        let panes = tab.iter_panes();
+        let seqno = panes
+            .iter()
+            .map(|pos| pos.pane.get_current_seqno())
+            .max()
+            .unwrap_or(0);
+        if let Some(thumbnail) = self.tab_thumbnails.get(&tab.tab_id()) {
+            if thumbnail.seqno == seqno && thumbnail.width == width && thumbnail.height == height {
+                return Ok(());
+            }
+        }
+
+        // As in paint_impl, grow the quad buffers until the tab fits
+        loop {
+            self.paint_thumbnail_pass(tab, &panes)?;
+            let gl_state = self.render_state.as_mut().unwrap();
+            let mut allocated = false;
+            for vb_idx in 0..3 {
+                if let Some(need_quads) = gl_state.vb[vb_idx].need_more_quads() {
+                    let num_quads = (need_quads + 1023) & !1023;
+                    gl_state.reallocate_quads(vb_idx, num_quads)?;
+                    allocated = true;
+                }
+            }
+            if !allocated {
+                break;
+            }
+        }
+
+        let context = Rc::clone(&self.render_state.as_ref().unwrap().context);
+        let texture = SrgbTexture2d::empty_with_format(
+            &context,
+            SrgbFormat::U8U8U8U8,
+            MipmapsOption::NoMipmap,
+            width,
+            height,
+        )?;
+        {
+            let mut fb = SimpleFrameBuffer::new(&context, &texture)?;
+            fb.clear_color(0., 0., 0., 1.);
+            self.call_draw(&mut fb)?;
+        }
+
+        self.tab_thumbnails.insert(
+            tab.tab_id(),
+            TabThumbnail {
+                seqno,
+                width,
+                height,
+                texture,
+            },
+        );
+        Ok(())
+    }
+
+    fn paint_thumbnail_pass(
+        &mut self,
+        tab: &Rc<Tab>,
+        panes: &[PositionedPane],
+    ) -> anyhow::Result<()> {
+        {
+            let gl_state = self.render_state.as_ref().unwrap();
+            for vb in &gl_state.vb {
+                vb.clear_quad_allocation();
+            }
+        }
+
+        for pos in panes {
+            // None of the panes of a thumbnail have the focus; painting
+            // one as active would acknowledge its output and move the
+            // cursor of the window
+            let pos = PositionedPane {
+                is_active: false,
+                ..pos.clone()
+            };
+            self.paint_pane_opengl(&pos, panes.len())?;
+        }
+
+        if let Some(pane) = tab.get_active_pane() {
+            for split in tab.iter_splits() {
+                self.paint_split_opengl(&split, &pane)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Computes where the thumbnail for `slot` is drawn in the window,
+    /// preserving the aspect ratio of the window
+    fn thumbnail_target(&self, slot: &ThumbnailSlot) -> Option<BlitTarget> {
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let window_width = self.dimensions.pixel_width as f32;
+        let window_height = self.dimensions.pixel_height as f32;
+        let first_line_offset = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
+            1
+        } else {
+            0
+        };
+
+        let left = self.config.window_padding.left as f32 + slot.left as f32 * cell_width;
+        let top = self.config.window_padding.top as f32
+            + (first_line_offset + slot.top) as f32 * cell_height;
+        let slot_width = slot.width as f32 * cell_width;
+        let slot_height = slot.height as f32 * cell_height;
+
+        let scale = (slot_width / window_width).min(slot_height / window_height);
+        let width = (window_width * scale).floor();
+        let height = (window_height * scale).floor();
+        if width < 1. || height < 1. {
+            return None;
+        }
+        let left = left + (slot_width - width) / 2.;
+        let top = top + (slot_height - height) / 2.;
+
+        // The origin of the framebuffer is its bottom left corner
+        Some(BlitTarget {
+            left: left as u32,
+            bottom: (window_height - (top + height)).max(0.) as u32,
+            width: width as i32,
+            height: height as i32,
+        })
+    }
+}
@@ -0,0 +1,260 @@
+//! Animated appearance changes.  When the colors change, such as when
+//! switching color schemes, each pane fades from its old palette to its
+//! new one, and when the font size is changed, the rendered frame is
+//! magnified or shrunk towards the new size, over
+//! `appearance_transition_duration_ms`, unless `reduce_motion` is enabled.
+//! The fonts are only reloaded at the new size, and the terminal resized,
+//! once the transition has finished.
+use ::window::{Window, WindowOps};
+use config::ConfigHandle;
+use mux::pane::{Pane, PaneId};
+use mux::Mux;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use termwiz::color::RgbColor;
+use wezterm_term::color::{ColorPalette, Palette256};
+
+/// The palettes of the panes from before the colors changed
+pub struct PaletteTransition {
+    start: Instant,
+    from: HashMap<PaneId, ColorPalette>,
+}
+
+pub struct FontScaleTransition {
+    start: Instant,
+    from: f64,
+    to: f64,
+}
+
+fn interpolate_color(from: RgbColor, to: RgbColor, position: f32) -> RgbColor {
+    let (r0, g0, b0) = from.to_tuple_rgb8();
+    let (r1, g1, b1) = to.to_tuple_rgb8();
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * position).round() as u8;
+    RgbColor::new_8bpc(lerp(r0, r1), lerp(g0, g1), lerp(b0, b1))
+}
+
+/// Returns the palette that is `position` of the way from `from` to `to`
+fn interpolate_palette(from: &ColorPalette, to: &ColorPalette, position: f32) -> ColorPalette {
+    let mut colors = to.colors.0;
+    for (color, from) in colors.iter_mut().zip(from.colors.0.iter()) {
+        *color = interpolate_color(*from, *color, position);
+    }
+    let lerp = |from: RgbColor, to: RgbColor| interpolate_color(from, to, position);
+    ColorPalette {
+        colors: Palette256(colors),
+        foreground: lerp(from.foreground, to.foreground),
+        background: lerp(from.background, to.background),
+        cursor_fg: lerp(from.cursor_fg, to.cursor_fg),
+        cursor_bg: lerp(from.cursor_bg, to.cursor_bg),
+        cursor_border: lerp(from.cursor_border, to.cursor_border),
+        selection_fg: lerp(from.selection_fg, to.selection_fg),
+        selection_bg: lerp(from.selection_bg, to.selection_bg),
+        scrollbar_thumb: lerp(from.scrollbar_thumb, to.scrollbar_thumb),
+        split: lerp(from.split, to.split),
+    }
+}
+
+impl super::TermWindow {
+    /// Returns how long appearance transitions take, or None if
+    /// they are disabled
+    fn appearance_transition_duration(&self) -> Option<Duration> {
+        if self.config.reduce_motion || self.config.appearance_transition_duration_ms == 0 {
+            None
+        } else {
+            Some(Duration::from_millis(
+                self.config.appearance_transition_duration_ms,
+            ))
+        }
+    }
+
+    /// Returns how far along a transition that began at `start` is,
+    /// or None if it has finished.  Schedules the next frame while
+    /// it is in progress.
+    fn transition_position(&self, start: Instant) -> Option<f32> {
+        let duration = self.appearance_transition_duration()?;
+        let completion = start.elapsed().as_secs_f32() / duration.as_secs_f32();
+        if completion >= 1.0 {
+            return None;
+        }
+        self.update_next_frame_time(Some(
+            Instant::now() + Duration::from_millis(1000 / self.config.max_fps.max(1) as u64),
+        ));
+        Some(
+            self.config
+                .appearance_transition_function
+                .evaluate_at_position(completion),
+        )
+    }
+
+    /// Records the palettes of the panes in the window so that they can
+    /// fade to the palettes that they have after the colors change.
+    /// This is called after `self.config` has been replaced, but
+    /// before the panes are given the new configuration; `prior_config`
+    /// is the configuration that the window had before.
+    pub fn begin_palette_transition(&mut self, prior_config: &ConfigHandle) {
+        if self.appearance_transition_duration().is_none() {
+            return;
+        }
+        let mux = Mux::get().unwrap();
+        let window = match mux.get_window(self.mux_window_id) {
+            Some(window) => window,
+            None => return,
+        };
+        let prior_palette: ColorPalette = prior_config.resolved_palette.clone().into();
+        let new_palette: ColorPalette = self.config.resolved_palette.clone().into();
+        let mut from = HashMap::new();
+        for tab in window.iter() {
+            for pos in tab.iter_panes() {
+                // Panes that read the global configuration already
+                // report the new colors, so take their old colors from
+                // the prior configuration
+                let mut palette = pos.pane.palette();
+                if palette == new_palette {
+                    palette = prior_palette.clone();
+                }
+                // Start from the colors currently on screen if the
+                // colors change again mid-transition
+                let pane_id = pos.pane.pane_id();
+                from.insert(pane_id, self.transition_palette(pane_id, palette));
+            }
+        }
+        self.palette_transition.replace(PaletteTransition {
+            start: Instant::now(),
+            from,
+        });
+    }
+
+    /// Returns the palette with which to render `pane`, which is
+    /// part way between its old and new palettes during a transition
+    pub fn pane_palette(&self, pane: &Rc<dyn Pane>) -> ColorPalette {
+        self.transition_palette(pane.pane_id(), pane.palette())
+    }
+
+    /// Returns the palette that is shown for `pane_id` when its
+    /// palette is `palette`
+    fn transition_palette(&self, pane_id: PaneId, palette: ColorPalette) -> ColorPalette {
+        if let Some(transition) = self.palette_transition.as_ref() {
+            if let Some(from) = transition.from.get(&pane_id) {
+                if let Some(position) = self.transition_position(transition.start) {
+                    return interpolate_palette(from, &palette, position);
+                }
+            }
+        }
+        palette
+    }
+
+    /// Returns the font scale that the window has or is transitioning to
+    pub fn target_font_scale(&self) -> f64 {
+        match self.font_scale_transition.as_ref() {
+            Some(transition) => transition.to,
+            None => self.fonts.get_font_scale(),
+        }
+    }
+
+    /// Returns the font scale that is currently shown during a
+    /// font scale transition
+    fn transition_font_scale(&self) -> Option<f64> {
+        let transition = self.font_scale_transition.as_ref()?;
+        let position = self.transition_position(transition.start)?;
+        Some(transition.from + (transition.to - transition.from) * position as f64)
+    }
+
+    /// Returns the factor by which the rendered frame is scaled.  This is
+    /// 1.0 except while the font scale transitions, when the frame is
+    /// scaled from the font scale that the fonts were loaded with to the
+    /// font scale that is currently shown.
+    pub fn transition_render_scale(&self) -> f32 {
+        match self.transition_font_scale() {
+            Some(scale) => (scale / self.fonts.get_font_scale()) as f32,
+            None => 1.0,
+        }
+    }
+
+    /// Changes the font scale, gradually if transitions are enabled,
+    /// but not below `minimum_font_size`
+    pub fn adjust_font_scale(&mut self, font_scale: f64, window: &Window) {
+        let font_scale = font_scale.max(self.config.minimum_font_scale());
+        if self.appearance_transition_duration().is_none() {
+            self.font_scale_transition.take();
+            self.apply_font_scale(font_scale, window);
+            return;
+        }
+        // Continue from the scale that is on screen if the font
+        // size changes again mid-transition
+        let from = self
+            .transition_font_scale()
+            .unwrap_or_else(|| self.fonts.get_font_scale());
+        self.font_scale_transition.replace(FontScaleTransition {
+            start: Instant::now(),
+            from,
+            to: font_scale,
+        });
+        window.invalidate();
+    }
+
+    /// Advances the transitions that are in progress; called before
+    /// each frame is painted
+    pub fn step_appearance_transitions(&mut self, window: &Window) {
+        let palette_done = match self.palette_transition.as_ref() {
+            Some(transition) => self.transition_position(transition.start).is_none(),
+            None => false,
+        };
+        if palette_done {
+            self.palette_transition.take();
+        }
+
+        let (start, to) = match self.font_scale_transition.as_ref() {
+            Some(t) => (t.start, t.to),
+            None => return,
+        };
+        match self.transition_position(start) {
+            Some(_) => {
+                // The frame is scaled as it is drawn; see
+                // `transition_render_scale`.  This runs ahead of painting,
+                // which discards any frame time that was scheduled here,
+                // so request the next frame
+                window.invalidate();
+            }
+            None => {
+                // Only now are the fonts reloaded and the window and
+                // terminal resized, and only once
+                self.font_scale_transition.take();
+                self.apply_font_scale(to, window);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interpolate() {
+        let black = RgbColor::new_8bpc(0, 0, 0);
+        let white = RgbColor::new_8bpc(0xff, 0xff, 0xff);
+        assert_eq!(interpolate_color(black, white, 0.0), black);
+        assert_eq!(interpolate_color(black, white, 1.0), white);
+        assert_eq!(
+            interpolate_color(black, white, 0.5),
+            RgbColor::new_8bpc(0x80, 0x80, 0x80)
+        );
+
+        let from = ColorPalette::default();
+        let mut to = ColorPalette::default();
+        to.background = white;
+        to.colors.0[1] = white;
+        let halfway = interpolate_palette(&from, &to, 0.5);
+        assert_eq!(halfway.foreground, from.foreground);
+        assert_eq!(
+            halfway.background,
+            interpolate_color(from.background, white, 0.5)
+        );
+        assert_eq!(
+            halfway.colors.0[1],
+            interpolate_color(from.colors.0[1], white, 0.5)
+        );
+        assert_eq!(interpolate_palette(&from, &to, 1.0), to);
+    }
+}
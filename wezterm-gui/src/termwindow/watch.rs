@@ -0,0 +1,289 @@
+//! Per-pane watches that alert when a pane produces output, or stops
+//! producing output, similar to the monitor-activity and monitor-silence
+//! options of tmux, along with notifications for long running commands
+//! that complete in panes that aren't being looked at.
+use crate::scripting::guiwin::GuiWin;
+use crate::scripting::pane::PaneObject;
+use crate::termwindow::{TermWindow, TermWindowNotif};
+use config::keyassignment::PaneWatch;
+use luahelper::impl_lua_conversion;
+use mlua::FromLua;
+use mux::pane::PaneId;
+use mux::Mux;
+use serde::{Deserialize, Serialize};
+use smol::Timer;
+use std::time::{Duration, Instant};
+use wezterm_toast_notification::persistent_toast_notification;
+use window::WindowOps;
+
+/// Describes a completed command to the format-command-notification event
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommandInformation {
+    pub command: String,
+    pub exit_status: Option<i32>,
+    pub duration_seconds: f64,
+}
+impl_lua_conversion!(CommandInformation);
+
+pub struct ActiveWatch {
+    watch: PaneWatch,
+    toast: bool,
+    last_output: Instant,
+    /// Whether a silence check is pending for this watch
+    check_scheduled: bool,
+    /// Whether the silence watch has already alerted for the
+    /// current period of silence
+    fired: bool,
+    /// Set when the watch has alerted, and cleared once the
+    /// pane has been looked at
+    alerted: bool,
+}
+
+impl TermWindow {
+    pub fn activate_watch(&mut self, pane_id: PaneId, watch: PaneWatch, toast: bool) {
+        if watch == PaneWatch::Disabled {
+            if self.pane_watches.remove(&pane_id).is_some() {
+                self.update_title();
+            }
+            return;
+        }
+
+        self.pane_watches.insert(
+            pane_id,
+            ActiveWatch {
+                watch,
+                toast,
+                last_output: Instant::now(),
+                check_scheduled: false,
+                fired: false,
+                alerted: false,
+            },
+        );
+        self.schedule_silence_check(pane_id);
+        self.update_title();
+    }
+
+    /// Returns the kind of alert that has been raised for `pane_id`
+    /// and hasn't yet been seen
+    pub fn pane_watch_alert(&self, pane_id: PaneId) -> Option<&'static str> {
+        if let Some(watch) = self.pane_watches.get(&pane_id) {
+            if watch.alerted {
+                return Some(match watch.watch {
+                    PaneWatch::Silence(_) => "Silence",
+                    _ => "Activity",
+                });
+            }
+        }
+        if self.completed_commands.contains(&pane_id) {
+            return Some("CommandCompleted");
+        }
+        None
+    }
+
+    /// Called when `pane_id` has produced output
+    pub fn pane_watch_output(&mut self, pane_id: PaneId) {
+        if !self.pane_watches.contains_key(&pane_id) {
+            return;
+        }
+        let is_viewed = self.is_pane_being_viewed(pane_id);
+        let watch = match self.pane_watches.get_mut(&pane_id) {
+            Some(watch) => watch,
+            None => return,
+        };
+        watch.last_output = Instant::now();
+        match watch.watch {
+            PaneWatch::Activity => {
+                if !watch.alerted && !is_viewed {
+                    self.trigger_watch(pane_id);
+                }
+            }
+            PaneWatch::Silence(_) => {
+                watch.fired = false;
+                self.schedule_silence_check(pane_id);
+            }
+            PaneWatch::Disabled => {}
+        }
+    }
+
+    /// Forgets the watch and alert of a pane that has been removed
+    pub fn pane_watch_removed(&mut self, pane_id: PaneId) {
+        self.pane_watches.remove(&pane_id);
+        self.completed_commands.remove(&pane_id);
+    }
+
+    /// Clears the alert for a pane that is being looked at
+    pub fn acknowledge_pane_watch(&mut self, pane_id: PaneId) {
+        let mut changed = self.completed_commands.remove(&pane_id);
+        if let Some(watch) = self.pane_watches.get_mut(&pane_id) {
+            if watch.alerted {
+                watch.alerted = false;
+                changed = true;
+            }
+        }
+        if changed {
+            self.update_title();
+        }
+    }
+
+    /// Called when a command reported via OSC 133 has completed
+    /// in `pane_id`
+    pub fn pane_command_completed(&mut self, pane_id: PaneId, info: CommandInformation) {
+        let threshold = self.config.command_completion_notification_threshold;
+        if threshold == 0
+            || info.duration_seconds < threshold as f64
+            || self.is_pane_being_viewed(pane_id)
+        {
+            return;
+        }
+        let mux = Mux::get().unwrap();
+        // Every window hears about the command, but only the
+        // one that contains the pane should notify
+        match mux.resolve_pane_id(pane_id) {
+            Some((_, window_id, _)) if window_id == self.mux_window_id => {}
+            _ => return,
+        }
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+
+        if self.completed_commands.insert(pane_id) {
+            self.update_title();
+        }
+
+        let window = GuiWin::new(self);
+        let pane_obj = PaneObject::new(&pane);
+        let message = match config::run_immediate_with_lua_config(|lua| {
+            if let Some(lua) = lua {
+                let v = config::lua::emit_sync_callback(
+                    &*lua,
+                    (
+                        "format-command-notification".to_string(),
+                        (window, pane_obj, info.clone()),
+                    ),
+                )?;
+                match &v {
+                    mlua::Value::Nil => Ok(Some(None)),
+                    mlua::Value::Boolean(false) => Ok(None),
+                    _ => Ok(Some(Some(String::from_lua(v, &*lua)?))),
+                }
+            } else {
+                Ok(Some(None))
+            }
+        }) {
+            Ok(message) => message,
+            Err(err) => {
+                log::warn!("format-command-notification: {}", err);
+                Some(None)
+            }
+        };
+
+        // The event handler returns false to suppress the notification
+        if let Some(message) = message {
+            let message = message.unwrap_or_else(|| {
+                let status = match info.exit_status {
+                    Some(status) if status != 0 => format!("failed with status {}", status),
+                    _ => "completed".to_string(),
+                };
+                format!(
+                    "{} {} after {}s",
+                    info.command.trim(),
+                    status,
+                    info.duration_seconds.round()
+                )
+            });
+            persistent_toast_notification(&pane.get_title(), &message);
+        }
+    }
+
+    fn is_pane_being_viewed(&self, pane_id: PaneId) -> bool {
+        self.focused.is_some()
+            && self
+                .get_active_pane_no_overlay()
+                .map_or(false, |pane| pane.pane_id() == pane_id)
+    }
+
+    fn schedule_silence_check(&mut self, pane_id: PaneId) {
+        let window = match self.window.as_ref() {
+            Some(window) => window.clone(),
+            None => return,
+        };
+        let watch = match self.pane_watches.get_mut(&pane_id) {
+            Some(watch) => watch,
+            None => return,
+        };
+        let seconds = match watch.watch {
+            PaneWatch::Silence(seconds) => seconds,
+            _ => return,
+        };
+        if watch.check_scheduled {
+            // The pending check will reschedule itself based on
+            // the updated last_output time
+            return;
+        }
+        watch.check_scheduled = true;
+
+        let target = watch.last_output + Duration::from_secs(seconds);
+        promise::spawn::spawn(async move {
+            Timer::at(target).await;
+            window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                term_window.check_silence_watch(pane_id);
+            })));
+        })
+        .detach();
+    }
+
+    fn check_silence_watch(&mut self, pane_id: PaneId) {
+        if Mux::get().unwrap().get_pane(pane_id).is_none() {
+            self.pane_watches.remove(&pane_id);
+            return;
+        }
+        let watch = match self.pane_watches.get_mut(&pane_id) {
+            Some(watch) => watch,
+            None => return,
+        };
+        watch.check_scheduled = false;
+        let seconds = match watch.watch {
+            PaneWatch::Silence(seconds) => seconds,
+            _ => return,
+        };
+        if watch.fired {
+            return;
+        }
+        if watch.last_output.elapsed() >= Duration::from_secs(seconds) {
+            watch.fired = true;
+            self.trigger_watch(pane_id);
+        } else {
+            self.schedule_silence_check(pane_id);
+        }
+    }
+
+    fn trigger_watch(&mut self, pane_id: PaneId) {
+        let (watch, toast) = match self.pane_watches.get_mut(&pane_id) {
+            Some(watch) => {
+                watch.alerted = true;
+                (watch.watch, watch.toast)
+            }
+            None => return,
+        };
+
+        let (event, message) = match watch {
+            PaneWatch::Activity => ("pane-activity", "Activity"),
+            PaneWatch::Silence(_) => ("pane-silence", "Silence"),
+            PaneWatch::Disabled => return,
+        };
+        log::trace!("{} in pane {}", message, pane_id);
+
+        if toast {
+            let title = Mux::get()
+                .unwrap()
+                .get_pane(pane_id)
+                .map(|pane| pane.get_title())
+                .unwrap_or_default();
+            persistent_toast_notification(&title, &format!("{} in pane {}", message, pane_id));
+        }
+
+        self.emit_window_event(event, Some(pane_id));
+        self.update_title();
+    }
+}
@@ -0,0 +1,449 @@
+//! Widgets that lua code places over a corner of a pane, such as an
+//! inline popup or a timer, along with their periodic updates and the
+//! routing of clicks and key presses to lua event handlers.
+use super::{TermWindow, TermWindowNotif};
+use crate::scripting::guiwin::GuiWin;
+use crate::scripting::pane::PaneObject;
+use crate::tabbar::parse_status_text;
+use luahelper::impl_lua_conversion;
+use mlua::FromLua;
+use mux::pane::{Pane, PaneId};
+use mux::Mux;
+use serde::{Deserialize, Serialize};
+use smol::Timer;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use termwiz::cell::{Cell, CellAttributes};
+use wezterm_term::Line;
+use window::{KeyCode, Modifiers, WindowOps};
+
+/// The corner of the pane that a widget is placed against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PaneWidgetAnchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Default for PaneWidgetAnchor {
+    fn default() -> Self {
+        Self::TopRight
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PaneWidgetSpec {
+    #[serde(default)]
+    pub anchor: PaneWidgetAnchor,
+    /// Moves the widget away from the top or bottom edge, in cells
+    #[serde(default)]
+    pub row_offset: usize,
+    /// Moves the widget away from the left or right edge, in cells
+    #[serde(default)]
+    pub col_offset: usize,
+    /// The text of each row of the widget, which may be
+    /// formatted using `wezterm.format`
+    #[serde(default)]
+    pub lines: Vec<String>,
+    /// If set, the `update-pane-widget` event is emitted this often
+    /// to recompute the lines of the widget
+    #[serde(default)]
+    pub update_interval_milliseconds: Option<u64>,
+    /// If true, key presses in the pane are offered to the
+    /// `pane-widget-key` event while the widget is shown
+    #[serde(default)]
+    pub capture_input: bool,
+}
+impl_lua_conversion!(PaneWidgetSpec);
+
+#[derive(Clone)]
+pub struct PaneWidget {
+    pub id: String,
+    spec: PaneWidgetSpec,
+    /// Distinguishes this widget from a prior one with the same id,
+    /// so that the update timer of a replaced widget stops
+    serial: usize,
+    rows: Rc<Vec<Vec<Cell>>>,
+    width: usize,
+}
+
+/// Where a widget is drawn in a pane of a particular size
+pub struct PaneWidgetPlacement {
+    left: usize,
+    top: usize,
+    width: usize,
+    rows: Rc<Vec<Vec<Cell>>>,
+}
+
+impl PaneWidget {
+    fn new(id: String, spec: PaneWidgetSpec) -> Self {
+        static SERIAL: AtomicUsize = AtomicUsize::new(0);
+        let mut widget = Self {
+            id,
+            spec,
+            serial: SERIAL.fetch_add(1, Ordering::Relaxed),
+            rows: Rc::new(vec![]),
+            width: 0,
+        };
+        widget.set_lines(widget.spec.lines.clone());
+        widget
+    }
+
+    fn set_lines(&mut self, lines: Vec<String>) {
+        // Widgets are shown in reverse video unless they say otherwise,
+        // in the same way as the other text drawn over panes
+        let mut attrs = CellAttributes::default();
+        attrs.set_reverse(true);
+        let rows: Vec<Vec<Cell>> = lines
+            .iter()
+            .map(|line| parse_status_text(line, attrs.clone()))
+            .collect();
+        self.width = rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.width()).sum())
+            .max()
+            .unwrap_or(0);
+        self.rows = Rc::new(rows);
+        self.spec.lines = lines;
+    }
+
+    pub fn placement(&self, cols: usize, viewport_rows: usize) -> PaneWidgetPlacement {
+        let height = self.rows.len();
+        let left = match self.spec.anchor {
+            PaneWidgetAnchor::TopLeft | PaneWidgetAnchor::BottomLeft => self.spec.col_offset,
+            PaneWidgetAnchor::TopRight | PaneWidgetAnchor::BottomRight => cols
+                .saturating_sub(self.width)
+                .saturating_sub(self.spec.col_offset),
+        };
+        let top = match self.spec.anchor {
+            PaneWidgetAnchor::TopLeft | PaneWidgetAnchor::TopRight => self.spec.row_offset,
+            PaneWidgetAnchor::BottomLeft | PaneWidgetAnchor::BottomRight => viewport_rows
+                .saturating_sub(height)
+                .saturating_sub(self.spec.row_offset),
+        };
+        PaneWidgetPlacement {
+            left,
+            top,
+            width: self.width,
+            rows: Rc::clone(&self.rows),
+        }
+    }
+}
+
+impl PaneWidgetPlacement {
+    /// Returns the position of (x, y) relative to the widget,
+    /// if it falls within it
+    pub fn hit_test(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        if x >= self.left
+            && x < self.left + self.width
+            && y >= self.top
+            && y < self.top + self.rows.len()
+        {
+            Some((x - self.left, y - self.top))
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns a copy of `line` with the rows of `placements` that fall
+/// on `line_idx` drawn over it, or None if none of them do
+pub fn overlay_pane_widgets(
+    line: &Line,
+    line_idx: usize,
+    cols: usize,
+    placements: &[PaneWidgetPlacement],
+) -> Option<Line> {
+    let mut result: Option<Line> = None;
+    for placement in placements {
+        let row = match line_idx
+            .checked_sub(placement.top)
+            .and_then(|idx| placement.rows.get(idx))
+        {
+            Some(row) => row,
+            None => continue,
+        };
+        let line = result.get_or_insert_with(|| {
+            let mut line = line.clone();
+            line.resize(cols, line.current_seqno());
+            line
+        });
+        let seqno = line.current_seqno();
+        let mut x = placement.left;
+        for cell in row {
+            if x + cell.width() > cols {
+                break;
+            }
+            let width = cell.width();
+            line.set_cell(x, cell.clone(), seqno);
+            x += width;
+        }
+        // Pad shorter rows so that the widget is a solid block
+        let mut attrs = CellAttributes::default();
+        attrs.set_reverse(true);
+        while x < (placement.left + placement.width).min(cols) {
+            line.set_cell(x, Cell::new(' ', attrs.clone()), seqno);
+            x += 1;
+        }
+    }
+    result
+}
+
+impl TermWindow {
+    /// Shows the widget `id` over `pane_id`, replacing any prior
+    /// widget with the same id
+    pub fn set_pane_widget(&mut self, pane_id: PaneId, id: String, spec: PaneWidgetSpec) {
+        let interval = spec.update_interval_milliseconds;
+        let widget = PaneWidget::new(id.clone(), spec);
+        let serial = widget.serial;
+        {
+            let mut state = self.pane_state(pane_id);
+            match state.widgets.iter_mut().find(|w| w.id == id) {
+                Some(existing) => *existing = widget,
+                None => state.widgets.push(widget),
+            }
+        }
+        if let Some(interval) = interval {
+            self.schedule_pane_widget_update(pane_id, id, serial, interval);
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    pub fn remove_pane_widget(&mut self, pane_id: PaneId, id: &str) {
+        self.pane_state(pane_id).widgets.retain(|w| w.id != id);
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    fn schedule_pane_widget_update(
+        &self,
+        pane_id: PaneId,
+        id: String,
+        serial: usize,
+        interval: u64,
+    ) {
+        let window = self.window.clone().unwrap();
+        promise::spawn::spawn(async move {
+            Timer::after(Duration::from_millis(interval.max(1))).await;
+            window.notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                if Mux::get().unwrap().get_pane(pane_id).is_none() {
+                    tw.pane_state(pane_id).widgets.clear();
+                    return;
+                }
+                let current = tw
+                    .pane_state(pane_id)
+                    .widgets
+                    .iter()
+                    .any(|w| w.id == id && w.serial == serial);
+                // The widget was removed or replaced in the meantime
+                if !current {
+                    return;
+                }
+                tw.update_pane_widget(pane_id, &id);
+                tw.schedule_pane_widget_update(pane_id, id, serial, interval);
+            })));
+        })
+        .detach();
+    }
+
+    /// Emits `update-pane-widget` so that lua can recompute the lines
+    /// of the widget; returning nil leaves them as they are
+    pub fn update_pane_widget(&mut self, pane_id: PaneId, id: &str) {
+        let pane = match Mux::get().unwrap().get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(&pane);
+        let lines = match config::run_immediate_with_lua_config(|lua| {
+            if let Some(lua) = lua {
+                let v = config::lua::emit_sync_callback(
+                    &*lua,
+                    (
+                        "update-pane-widget".to_string(),
+                        (window, pane, id.to_string()),
+                    ),
+                )?;
+                match &v {
+                    mlua::Value::Nil => Ok(None),
+                    _ => Ok(Some(Vec::<String>::from_lua(v, &*lua)?)),
+                }
+            } else {
+                Ok(None)
+            }
+        }) {
+            Ok(lines) => lines,
+            Err(err) => {
+                log::warn!("update-pane-widget: {}", err);
+                None
+            }
+        };
+
+        if let Some(lines) = lines {
+            if let Some(widget) = self
+                .pane_state(pane_id)
+                .widgets
+                .iter_mut()
+                .find(|w| w.id == id)
+            {
+                widget.set_lines(lines);
+            }
+            if let Some(window) = self.window.as_ref() {
+                window.invalidate();
+            }
+        }
+    }
+
+    pub fn pane_widget_placements(&self, pane: &Rc<dyn Pane>) -> Vec<PaneWidgetPlacement> {
+        let dims = pane.get_dimensions();
+        self.pane_state(pane.pane_id())
+            .widgets
+            .iter()
+            .map(|w| w.placement(dims.cols, dims.viewport_rows))
+            .collect()
+    }
+
+    /// Emits `pane-widget-clicked` if (x, y), which are relative to
+    /// the viewport of `pane`, fall within one of its widgets.
+    /// Returns true if they did, in which case the click is consumed.
+    pub fn pane_widget_click(&mut self, pane: &Rc<dyn Pane>, x: usize, y: usize) -> bool {
+        // The most recently added widget is drawn on top
+        let hit = self
+            .pane_widget_placements(pane)
+            .iter()
+            .zip(self.pane_state(pane.pane_id()).widgets.iter())
+            .rev()
+            .find_map(|(placement, widget)| {
+                placement
+                    .hit_test(x, y)
+                    .map(|(col, row)| (widget.id.clone(), col, row))
+            });
+        let (id, col, row) = match hit {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+
+        async fn do_event(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: PaneObject,
+            id: String,
+            col: usize,
+            row: usize,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, pane, id, col, row))?;
+                if let Err(err) =
+                    config::lua::emit_event(&lua, ("pane-widget-clicked".to_string(), args)).await
+                {
+                    log::error!("while processing pane-widget-clicked event: {:#}", err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            do_event(lua, window, pane, id, col, row)
+        }))
+        .detach();
+        true
+    }
+
+    /// Offers a key press to the most recently added widget of `pane`
+    /// that captures input, via `pane-widget-key`.  Returns true if
+    /// the handler returned true to consume it; otherwise the key
+    /// is processed as usual.
+    pub fn pane_widget_key(&mut self, pane: &Rc<dyn Pane>, key: &KeyCode, mods: Modifiers) -> bool {
+        let id = match self
+            .pane_state(pane.pane_id())
+            .widgets
+            .iter()
+            .rev()
+            .find(|w| w.spec.capture_input)
+        {
+            Some(widget) => widget.id.clone(),
+            None => return false,
+        };
+        if key.is_modifier() {
+            return false;
+        }
+
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+        let key = config::describe_key(key, mods);
+        match config::run_immediate_with_lua_config(|lua| {
+            if let Some(lua) = lua {
+                let v = config::lua::emit_sync_callback(
+                    &*lua,
+                    ("pane-widget-key".to_string(), (window, pane, id, key)),
+                )?;
+                Ok(matches!(v, mlua::Value::Boolean(true)))
+            } else {
+                Ok(false)
+            }
+        }) {
+            Ok(consumed) => consumed,
+            Err(err) => {
+                log::warn!("pane-widget-key: {}", err);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn placement() {
+        let widget = PaneWidget::new(
+            "w".to_string(),
+            PaneWidgetSpec {
+                anchor: PaneWidgetAnchor::BottomRight,
+                row_offset: 1,
+                lines: vec!["hello".to_string(), "hi".to_string()],
+                ..Default::default()
+            },
+        );
+        let placement = widget.placement(80, 24);
+        assert_eq!(
+            (placement.left, placement.top, placement.width),
+            (75, 21, 5)
+        );
+        assert_eq!(placement.hit_test(75, 21), Some((0, 0)));
+        assert_eq!(placement.hit_test(79, 22), Some((4, 1)));
+        assert_eq!(placement.hit_test(74, 21), None);
+        assert_eq!(placement.hit_test(75, 23), None);
+    }
+
+    #[test]
+    fn overlay() {
+        let widget = PaneWidget::new(
+            "w".to_string(),
+            PaneWidgetSpec {
+                anchor: PaneWidgetAnchor::TopLeft,
+                col_offset: 2,
+                lines: vec!["ab".to_string(), "c".to_string()],
+                ..Default::default()
+            },
+        );
+        let placements = vec![widget.placement(6, 4)];
+        let line: Line = "xxxxxx".into();
+
+        let first = overlay_pane_widgets(&line, 0, 6, &placements).unwrap();
+        assert_eq!(first.as_str(), "xxabxx");
+        // Shorter rows are padded to the width of the widget
+        let second = overlay_pane_widgets(&line, 1, 6, &placements).unwrap();
+        assert_eq!(second.as_str(), "xxc xx");
+        assert!(overlay_pane_widgets(&line, 2, 6, &placements).is_none());
+    }
+}
@@ -0,0 +1,263 @@
+//! The optional tray icon, whose menu offers quick access to windows,
+//! workspaces and domains, and which indicates when panes that aren't
+//! being looked at have produced output.
+use crate::frontend::front_end;
+use ::window::WindowOps;
+use config::WorkspaceDefinition;
+use mux::domain::DomainState;
+use mux::pane::PaneId;
+use mux::window::WindowId as MuxWindowId;
+use mux::Mux;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use wezterm_tray::{TrayMenuItem, TrayState};
+
+thread_local! {
+    /// Panes that have produced output while they weren't being looked at
+    static ACTIVITY: RefCell<BTreeSet<PaneId>> = RefCell::new(BTreeSet::new());
+    /// The mux window of the gui window that has the focus
+    static FOCUSED_WINDOW: Cell<Option<MuxWindowId>> = Cell::new(None);
+    /// The mux window that is shown and hidden by the quake window
+    /// toggle, and whether it is currently shown
+    static QUAKE_WINDOW: Cell<Option<(MuxWindowId, bool)>> = Cell::new(None);
+}
+
+fn build_state() -> TrayState {
+    let mux = Mux::get().unwrap();
+
+    let domains: Vec<TrayMenuItem> = mux
+        .iter_domains()
+        .into_iter()
+        .filter(|domain| domain.state() == DomainState::Detached)
+        .map(|domain| {
+            let name = domain.domain_name();
+            TrayMenuItem::new(format!("attach-domain:{}", name), name)
+        })
+        .collect();
+
+    let active_workspace = mux.active_workspace();
+    let workspaces: Vec<TrayMenuItem> = mux
+        .iter_workspaces()
+        .into_iter()
+        .map(|name| {
+            let label = if name == active_workspace {
+                format!("{} (active)", name)
+            } else {
+                name.clone()
+            };
+            TrayMenuItem::new(format!("workspace:{}", name), label)
+        })
+        .collect();
+
+    let active_panes: Vec<TrayMenuItem> = ACTIVITY.with(|activity| {
+        activity
+            .borrow()
+            .iter()
+            .filter_map(|pane_id| {
+                let pane = mux.get_pane(*pane_id)?;
+                Some(TrayMenuItem::new(
+                    format!("pane:{}", pane_id),
+                    format!("{}: {}", pane_id, pane.get_title()),
+                ))
+            })
+            .collect()
+    });
+
+    let tooltip = match active_panes.len() {
+        0 => String::new(),
+        1 => "Activity in 1 pane".to_string(),
+        n => format!("Activity in {} panes", n),
+    };
+
+    TrayState {
+        tooltip,
+        attention: !active_panes.is_empty(),
+        menu: vec![
+            TrayMenuItem::new("new-window", "New Window"),
+            TrayMenuItem::new("show-windows", "Show Windows"),
+            TrayMenuItem::new("toggle-quake-window", "Toggle Quake Window"),
+            TrayMenuItem::submenu("Attach Domain", domains),
+            TrayMenuItem::submenu("Workspaces", workspaces),
+            TrayMenuItem::submenu("Panes with activity", active_panes),
+            TrayMenuItem::separator(),
+            TrayMenuItem::new("quit", "Quit WezTerm"),
+        ],
+    }
+}
+
+/// Shows, updates or hides the tray icon according to the configuration
+/// and the current state of the mux
+pub fn update() {
+    if !config::configuration().enable_tray_icon {
+        wezterm_tray::hide();
+        return;
+    }
+    wezterm_tray::show(
+        build_state(),
+        Arc::new(|id| {
+            promise::spawn::spawn_into_main_thread(async move {
+                if let Err(err) = perform_action(&id).await {
+                    log::error!("tray action {}: {:#}", id, err);
+                }
+            })
+            .detach();
+        }),
+    );
+}
+
+async fn perform_action(id: &str) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    if let Some(name) = id.strip_prefix("attach-domain:") {
+        if let Some(domain) = mux.get_domain_by_name(name) {
+            domain.attach().await?;
+        }
+    } else if let Some(name) = id.strip_prefix("workspace:") {
+        mux.set_active_workspace(name);
+        crate::background::show_windows();
+    } else if let Some(pane_id) = id.strip_prefix("pane:") {
+        activate_pane(pane_id.parse()?);
+    } else {
+        match id {
+            "new-window" => {
+                crate::workspace::spawn_workspace(&WorkspaceDefinition {
+                    name: mux.active_workspace(),
+                    ..Default::default()
+                })
+                .await?;
+            }
+            // Activating the icon itself shows the windows
+            "" | "show-windows" => crate::background::show_windows(),
+            "toggle-quake-window" => toggle_quake_window().await?,
+            "quit" => {
+                ::window::Connection::get()
+                    .expect("call on gui thread")
+                    .terminate_message_loop();
+            }
+            _ => log::warn!("unknown tray action {}", id),
+        }
+    }
+    Ok(())
+}
+
+/// Hides the quake window if it is shown, or else shows it at the top
+/// left of the screen and focuses it.  The quake window is created in
+/// the active workspace the first time that it is toggled, and again
+/// if it has been closed.
+async fn toggle_quake_window() -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    let quake = QUAKE_WINDOW
+        .with(|quake| quake.get())
+        .filter(|(window_id, _)| mux.get_window(*window_id).is_some());
+
+    let (window_id, shown) = match quake {
+        Some(quake) => quake,
+        None => {
+            let domain = mux.default_domain();
+            if domain.state() == DomainState::Detached {
+                domain.attach().await?;
+            }
+            let window_id = mux.new_empty_window(Some(mux.active_workspace()));
+            let size = config::configuration().initial_size();
+            domain.spawn(size, None, None, None, *window_id).await?;
+            // The gui window is created, shown and focused in
+            // response to the new mux window
+            QUAKE_WINDOW.with(|quake| quake.set(Some((*window_id, true))));
+            return Ok(());
+        }
+    };
+
+    let window = match front_end().and_then(|fe| fe.gui_window_for_mux_window(window_id)) {
+        Some(window) => window,
+        None => return Ok(()),
+    };
+    if shown {
+        window.hide();
+    } else {
+        window.show();
+        window.set_window_position(::window::ScreenPoint::new(0, 0));
+        window.focus();
+    }
+    QUAKE_WINDOW.with(|quake| quake.set(Some((window_id, !shown))));
+    Ok(())
+}
+
+/// Brings the window, tab and pane that contain `pane_id` to the front
+fn activate_pane(pane_id: PaneId) {
+    let mux = Mux::get().unwrap();
+    let (_domain_id, window_id, tab_id) = match mux.resolve_pane_id(pane_id) {
+        Some(ids) => ids,
+        None => return,
+    };
+
+    if let Some(tab) = mux.get_tab(tab_id) {
+        if let Some(pane) = mux.get_pane(pane_id) {
+            tab.set_active_pane(&pane);
+        }
+    }
+    let workspace = match mux.get_window_mut(window_id) {
+        Some(mut window) => {
+            if let Some(idx) = window.idx_by_id(tab_id) {
+                window.save_and_then_set_active(idx);
+            }
+            window.get_workspace().to_string()
+        }
+        None => return,
+    };
+
+    mux.set_active_workspace(&workspace);
+    crate::background::show_windows();
+    if let Some(window) = front_end().and_then(|fe| fe.gui_window_for_mux_window(window_id)) {
+        window.focus();
+    }
+}
+
+/// Called when `pane_id` has produced output
+pub fn pane_output(pane_id: PaneId) {
+    if !config::configuration().enable_tray_icon {
+        return;
+    }
+    let mux = Mux::get().unwrap();
+    if let Some(window_id) = FOCUSED_WINDOW.with(|focused| focused.get()) {
+        let is_viewed = mux
+            .get_active_tab_for_window(window_id)
+            .and_then(|tab| tab.get_active_pane())
+            .map_or(false, |pane| pane.pane_id() == pane_id);
+        if is_viewed {
+            return;
+        }
+    }
+    if ACTIVITY.with(|activity| activity.borrow_mut().insert(pane_id)) {
+        update();
+    }
+}
+
+/// Called when `pane_id` has been removed from the mux
+pub fn pane_removed(pane_id: PaneId) {
+    if ACTIVITY.with(|activity| activity.borrow_mut().remove(&pane_id)) {
+        update();
+    }
+}
+
+/// Called when a gui window gains or loses the focus.  Gaining the
+/// focus clears the activity indicator, as the user is back.
+pub fn window_focus_changed(mux_window_id: MuxWindowId, focused: bool) {
+    FOCUSED_WINDOW.with(|focused_window| {
+        if focused {
+            focused_window.set(Some(mux_window_id));
+        } else if focused_window.get() == Some(mux_window_id) {
+            focused_window.set(None);
+        }
+    });
+    if focused {
+        let cleared = ACTIVITY.with(|activity| {
+            let mut activity = activity.borrow_mut();
+            let was_empty = activity.is_empty();
+            activity.clear();
+            !was_empty
+        });
+        if cleared {
+            update();
+        }
+    }
+}
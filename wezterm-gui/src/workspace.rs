@@ -0,0 +1,193 @@
+//! Populates workspaces that are described by the `workspaces`
+//! configuration, or by the `gui-startup` event, with their tabs
+//! and panes when they are opened for the first time, along with
+//! the gui side of closing workspaces and their lifecycle events.
+use crate::termwindow::spawn::{command_builder_for_spawn, spawn_encoding};
+use anyhow::anyhow;
+use config::keyassignment::{SpawnCommand, SpawnTabDomain};
+use config::{TermConfig, WorkspaceDefinition, WorkspaceSplit, WorkspaceTab};
+use mlua::FromLua;
+use mux::domain::{Domain, DomainState, LocalDomain};
+use mux::pane::Pane;
+use mux::tab::SplitDirection;
+use mux::Mux;
+use portable_pty::CommandBuilder;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Returns the configured definition of the workspace named `name`
+pub fn workspace_definition(name: &str) -> Option<WorkspaceDefinition> {
+    config::find_workspace_definition(&config::configuration().workspaces, name).cloned()
+}
+
+/// Gives the `gui-startup` event the opportunity to describe the
+/// workspace that the gui starts up in; if it doesn't, then the
+/// configured definition of that workspace is used.
+pub fn gui_startup_definition(name: &str) -> Option<WorkspaceDefinition> {
+    match config::run_immediate_with_lua_config(|lua| {
+        let lua = match lua {
+            Some(lua) => lua,
+            None => return Ok(None),
+        };
+        let v =
+            config::lua::emit_sync_callback(&*lua, ("gui-startup".to_string(), name.to_string()))?;
+        match v {
+            mlua::Value::Nil => Ok(None),
+            v => Ok(Some(WorkspaceDefinition::from_lua(v, &*lua)?)),
+        }
+    }) {
+        Ok(Some(mut def)) => {
+            if def.name.is_empty() {
+                def.name = name.to_string();
+            }
+            Some(def)
+        }
+        Ok(None) => workspace_definition(name),
+        Err(err) => {
+            log::warn!("gui-startup: {:#}", err);
+            workspace_definition(name)
+        }
+    }
+}
+
+/// Creates a window in the workspace described by `def`, and
+/// spawns its tabs and panes into it
+pub async fn spawn_workspace(def: &WorkspaceDefinition) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    let config = config::configuration();
+    let size = config.initial_size();
+    let window_id = mux.new_empty_window(Some(def.name.clone()));
+
+    let default_tab = [WorkspaceTab::default()];
+    let tabs = if def.tabs.is_empty() {
+        &default_tab[..]
+    } else {
+        &def.tabs[..]
+    };
+
+    for tab_def in tabs {
+        let mut panes = tab_def.panes.iter();
+        let first = panes.next().cloned().unwrap_or_default();
+        let spawn = def.apply_to(&first.command);
+
+        let mut domain = resolve_domain(&spawn, None).await?;
+        let (cmd, cwd) = command_for_spawn(&spawn, &domain)?;
+        let encoding = spawn_encoding(&spawn)?;
+        let tab = domain.spawn(size, cmd, cwd, encoding, *window_id).await?;
+        let mut pane = tab
+            .get_active_pane()
+            .ok_or_else(|| anyhow!("newly spawned tab to have a pane"))?;
+        configure_pane(&pane, &domain);
+
+        for pane_def in panes {
+            let spawn = def.apply_to(&pane_def.command);
+            domain = resolve_domain(&spawn, Some(&domain)).await?;
+            let (cmd, cwd) = command_for_spawn(&spawn, &domain)?;
+            let encoding = spawn_encoding(&spawn)?;
+            let direction = match pane_def.split {
+                WorkspaceSplit::Horizontal => SplitDirection::Horizontal,
+                WorkspaceSplit::Vertical => SplitDirection::Vertical,
+            };
+            pane = domain
+                .split_pane(cmd, cwd, encoding, tab.tab_id(), pane.pane_id(), direction)
+                .await?;
+            configure_pane(&pane, &domain);
+        }
+    }
+
+    if let Some(mut window) = mux.get_window_mut(*window_id) {
+        window.set_active_without_saving(0);
+    }
+
+    Ok(())
+}
+
+/// Resolves the domain for a pane of a workspace.  The "current pane"
+/// is the pane that is being split, or the default domain for the
+/// first pane in a tab.
+async fn resolve_domain(
+    spawn: &SpawnCommand,
+    current: Option<&Arc<dyn Domain>>,
+) -> anyhow::Result<Arc<dyn Domain>> {
+    let mux = Mux::get().unwrap();
+    let domain = match &spawn.domain {
+        SpawnTabDomain::DefaultDomain => mux.default_domain(),
+        SpawnTabDomain::CurrentPaneDomain => match current {
+            Some(domain) => Arc::clone(domain),
+            None => mux.default_domain(),
+        },
+        SpawnTabDomain::DomainName(name) => mux
+            .get_domain_by_name(name)
+            .ok_or_else(|| anyhow!("workspace refers to unknown domain {}", name))?,
+    };
+    if domain.state() == DomainState::Detached {
+        domain.attach().await?;
+    }
+    Ok(domain)
+}
+
+fn command_for_spawn(
+    spawn: &SpawnCommand,
+    domain: &Arc<dyn Domain>,
+) -> anyhow::Result<(Option<CommandBuilder>, Option<String>)> {
+    let cwd = match spawn.cwd.as_ref() {
+        Some(cwd) => Some(
+            cwd.to_str()
+                .map(|s| s.to_owned())
+                .ok_or_else(|| anyhow!("workspace cwd {:?} is not unicode", cwd))?,
+        ),
+        None => None,
+    };
+
+    // The environment of the workspace definition is passed on to
+    // the default program of local domains
+    let is_local = domain.downcast_ref::<LocalDomain>().is_some();
+    let builder = command_builder_for_spawn(spawn, is_local)?;
+    Ok((builder, cwd))
+}
+
+fn configure_pane(pane: &Rc<dyn Pane>, domain: &Arc<dyn Domain>) {
+    pane.set_config(Arc::new(TermConfig::new().for_domain(domain.domain_name())));
+}
+
+/// Closes the windows of `name`.  If `name` is the active workspace,
+/// the gui first switches to the next workspace so that there is
+/// still something to look at.
+pub fn close_workspace(name: &str) {
+    let mux = Mux::get().unwrap();
+    if mux.active_workspace() == name {
+        let workspaces = mux.iter_workspaces();
+        let next = workspaces.iter().position(|w| w == name).and_then(|idx| {
+            workspaces
+                .get(idx + 1)
+                .or_else(|| idx.checked_sub(1).and_then(|idx| workspaces.get(idx)))
+        });
+        if let Some(next) = next {
+            mux.set_active_workspace(next);
+        }
+    }
+    mux.kill_workspace(name);
+}
+
+/// Emits one of the workspace lifecycle events, which are not
+/// associated with any particular window
+pub fn emit_workspace_event(name: &'static str, args: Vec<String>) {
+    async fn do_event(
+        lua: Option<Rc<mlua::Lua>>,
+        name: &'static str,
+        args: Vec<String>,
+    ) -> anyhow::Result<()> {
+        if let Some(lua) = lua {
+            let args = lua.pack_multi(args)?;
+            if let Err(err) = config::lua::emit_event(&lua, (name.to_string(), args)).await {
+                log::error!("while processing {} event: {:#}", name, err);
+            }
+        }
+        Ok(())
+    }
+
+    promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+        do_event(lua, name, args)
+    }))
+    .detach();
+}
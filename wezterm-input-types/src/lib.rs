@@ -16,6 +16,9 @@ pub enum KeyCode {
     Char(char),
     Composed(String),
     RawCode(u32),
+    /// A key identified by its position on the keyboard rather
+    /// than by what it produces in the current layout
+    Physical(PhysKeyCode),
 
     Hyper,
     Super,
@@ -119,6 +122,227 @@ impl KeyCode {
     }
 }
 
+/// Identifies a key by its physical position on the keyboard.
+/// The names are those of the keys found in those positions on
+/// an ANSI US keyboard, regardless of the active layout.
+/// Only the keys whose meaning varies between layouts are
+/// represented here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum PhysKeyCode {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    K0,
+    K1,
+    K2,
+    K3,
+    K4,
+    K5,
+    K6,
+    K7,
+    K8,
+    K9,
+    Minus,
+    Equal,
+    LeftBracket,
+    RightBracket,
+    Backslash,
+    Semicolon,
+    Quote,
+    Grave,
+    Comma,
+    Period,
+    Slash,
+    Space,
+    Return,
+    Tab,
+    Backspace,
+    Escape,
+}
+
+/// The physical key and the character that it produces, without
+/// shift, in the ANSI US layout
+const PHYS_US_LAYOUT: &[(PhysKeyCode, char)] = &[
+    (PhysKeyCode::A, 'a'),
+    (PhysKeyCode::B, 'b'),
+    (PhysKeyCode::C, 'c'),
+    (PhysKeyCode::D, 'd'),
+    (PhysKeyCode::E, 'e'),
+    (PhysKeyCode::F, 'f'),
+    (PhysKeyCode::G, 'g'),
+    (PhysKeyCode::H, 'h'),
+    (PhysKeyCode::I, 'i'),
+    (PhysKeyCode::J, 'j'),
+    (PhysKeyCode::K, 'k'),
+    (PhysKeyCode::L, 'l'),
+    (PhysKeyCode::M, 'm'),
+    (PhysKeyCode::N, 'n'),
+    (PhysKeyCode::O, 'o'),
+    (PhysKeyCode::P, 'p'),
+    (PhysKeyCode::Q, 'q'),
+    (PhysKeyCode::R, 'r'),
+    (PhysKeyCode::S, 's'),
+    (PhysKeyCode::T, 't'),
+    (PhysKeyCode::U, 'u'),
+    (PhysKeyCode::V, 'v'),
+    (PhysKeyCode::W, 'w'),
+    (PhysKeyCode::X, 'x'),
+    (PhysKeyCode::Y, 'y'),
+    (PhysKeyCode::Z, 'z'),
+    (PhysKeyCode::K0, '0'),
+    (PhysKeyCode::K1, '1'),
+    (PhysKeyCode::K2, '2'),
+    (PhysKeyCode::K3, '3'),
+    (PhysKeyCode::K4, '4'),
+    (PhysKeyCode::K5, '5'),
+    (PhysKeyCode::K6, '6'),
+    (PhysKeyCode::K7, '7'),
+    (PhysKeyCode::K8, '8'),
+    (PhysKeyCode::K9, '9'),
+    (PhysKeyCode::Minus, '-'),
+    (PhysKeyCode::Equal, '='),
+    (PhysKeyCode::LeftBracket, '['),
+    (PhysKeyCode::RightBracket, ']'),
+    (PhysKeyCode::Backslash, '\\'),
+    (PhysKeyCode::Semicolon, ';'),
+    (PhysKeyCode::Quote, '\''),
+    (PhysKeyCode::Grave, '`'),
+    (PhysKeyCode::Comma, ','),
+    (PhysKeyCode::Period, '.'),
+    (PhysKeyCode::Slash, '/'),
+    (PhysKeyCode::Space, ' '),
+    (PhysKeyCode::Return, '\r'),
+    (PhysKeyCode::Tab, '\t'),
+    (PhysKeyCode::Backspace, '\u{8}'),
+    (PhysKeyCode::Escape, '\u{1b}'),
+];
+
+/// The PC (set 1) scan codes of the physical keys.  Linux evdev
+/// key codes share these values for these keys.
+const PHYS_SCAN_CODES: &[(PhysKeyCode, u32)] = &[
+    (PhysKeyCode::Escape, 0x01),
+    (PhysKeyCode::K1, 0x02),
+    (PhysKeyCode::K2, 0x03),
+    (PhysKeyCode::K3, 0x04),
+    (PhysKeyCode::K4, 0x05),
+    (PhysKeyCode::K5, 0x06),
+    (PhysKeyCode::K6, 0x07),
+    (PhysKeyCode::K7, 0x08),
+    (PhysKeyCode::K8, 0x09),
+    (PhysKeyCode::K9, 0x0a),
+    (PhysKeyCode::K0, 0x0b),
+    (PhysKeyCode::Minus, 0x0c),
+    (PhysKeyCode::Equal, 0x0d),
+    (PhysKeyCode::Backspace, 0x0e),
+    (PhysKeyCode::Tab, 0x0f),
+    (PhysKeyCode::Q, 0x10),
+    (PhysKeyCode::W, 0x11),
+    (PhysKeyCode::E, 0x12),
+    (PhysKeyCode::R, 0x13),
+    (PhysKeyCode::T, 0x14),
+    (PhysKeyCode::Y, 0x15),
+    (PhysKeyCode::U, 0x16),
+    (PhysKeyCode::I, 0x17),
+    (PhysKeyCode::O, 0x18),
+    (PhysKeyCode::P, 0x19),
+    (PhysKeyCode::LeftBracket, 0x1a),
+    (PhysKeyCode::RightBracket, 0x1b),
+    (PhysKeyCode::Return, 0x1c),
+    (PhysKeyCode::A, 0x1e),
+    (PhysKeyCode::S, 0x1f),
+    (PhysKeyCode::D, 0x20),
+    (PhysKeyCode::F, 0x21),
+    (PhysKeyCode::G, 0x22),
+    (PhysKeyCode::H, 0x23),
+    (PhysKeyCode::J, 0x24),
+    (PhysKeyCode::K, 0x25),
+    (PhysKeyCode::L, 0x26),
+    (PhysKeyCode::Semicolon, 0x27),
+    (PhysKeyCode::Quote, 0x28),
+    (PhysKeyCode::Grave, 0x29),
+    (PhysKeyCode::Backslash, 0x2b),
+    (PhysKeyCode::Z, 0x2c),
+    (PhysKeyCode::X, 0x2d),
+    (PhysKeyCode::C, 0x2e),
+    (PhysKeyCode::V, 0x2f),
+    (PhysKeyCode::B, 0x30),
+    (PhysKeyCode::N, 0x31),
+    (PhysKeyCode::M, 0x32),
+    (PhysKeyCode::Comma, 0x33),
+    (PhysKeyCode::Period, 0x34),
+    (PhysKeyCode::Slash, 0x35),
+    (PhysKeyCode::Space, 0x39),
+];
+
+impl PhysKeyCode {
+    /// Returns the KeyCode produced by this key in the US layout
+    /// when no modifiers are held
+    pub fn to_us_key_code(self) -> KeyCode {
+        PHYS_US_LAYOUT
+            .iter()
+            .find(|(phys, _)| *phys == self)
+            .map(|(_, c)| KeyCode::Char(*c))
+            .expect("all PhysKeyCodes are in PHYS_US_LAYOUT")
+    }
+
+    /// Returns the physical key that produces `c` in the US layout,
+    /// ignoring the effect of shift on letters
+    pub fn from_us_char(c: char) -> Option<Self> {
+        let c = c.to_ascii_lowercase();
+        PHYS_US_LAYOUT
+            .iter()
+            .find(|(_, us)| *us == c)
+            .map(|(phys, _)| *phys)
+    }
+
+    /// Maps a PC (set 1) scan code, or a Linux evdev key code, to
+    /// the physical key
+    pub fn from_scan_code(code: u32) -> Option<Self> {
+        PHYS_SCAN_CODES
+            .iter()
+            .find(|(_, scan)| *scan == code)
+            .map(|(phys, _)| *phys)
+    }
+
+    /// Parses the name used for this key in the configuration;
+    /// the variant name, except that the digits are named `0`-`9`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if c.is_ascii_alphanumeric() {
+                return Self::from_us_char(c);
+            }
+        }
+        PHYS_US_LAYOUT
+            .iter()
+            .map(|(phys, _)| *phys)
+            .find(|phys| format!("{:?}", phys) == name)
+    }
+}
+
 bitflags! {
     #[derive(Default, Deserialize, Serialize)]
     pub struct Modifiers: u8 {
@@ -150,6 +374,10 @@ pub enum MousePress {
     Left,
     Right,
     Middle,
+    /// The "back" side button, also known as X1
+    Back,
+    /// The "forward" side button, also known as X2
+    Forward,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -157,7 +385,9 @@ pub enum MouseEventKind {
     Move,
     Press(MousePress),
     Release(MousePress),
+    /// Positive values scroll up, negative values scroll down
     VertWheel(i16),
+    /// Positive values scroll left, negative values scroll right
     HorzWheel(i16),
 }
 
@@ -186,6 +416,8 @@ pub struct KeyEvent {
     pub raw_key: Option<KeyCode>,
     pub raw_modifiers: Modifiers,
     pub raw_code: Option<u32>,
+    /// The position of the key on the keyboard, if known
+    pub phys_code: Option<PhysKeyCode>,
 
     /// How many times this key repeats
     pub repeat_count: u16,
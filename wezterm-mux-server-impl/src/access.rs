@@ -0,0 +1,527 @@
+use anyhow::anyhow;
+use codec::*;
+use config::keyassignment::SpawnTabDomain;
+use config::{MuxAccessRule, MuxPermission};
+use mux::pane::PaneId;
+use mux::window::WindowId;
+use mux::Mux;
+
+/// Decides which requests a connected client is permitted to make,
+/// based on the `access_rules` configured for the listener that
+/// accepted the connection.
+#[derive(Debug, Clone, Default)]
+pub struct AccessPolicy {
+    user: Option<String>,
+    rules: Vec<MuxAccessRule>,
+}
+
+impl AccessPolicy {
+    /// A policy that permits everything; used when no rules
+    /// have been configured
+    pub fn unrestricted() -> Self {
+        Self::default()
+    }
+
+    pub fn new(user: Option<String>, rules: &[MuxAccessRule]) -> Self {
+        Self {
+            user,
+            rules: rules.to_vec(),
+        }
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    pub fn is_permitted(
+        &self,
+        permission: MuxPermission,
+        workspace: Option<&str>,
+        domain: Option<&str>,
+    ) -> bool {
+        if self.rules.is_empty() {
+            return true;
+        }
+        let user = match self.user.as_ref() {
+            Some(user) => user,
+            None => return false,
+        };
+        self.rules
+            .iter()
+            .any(|rule| rule.permits(user, permission, workspace, domain))
+    }
+
+    pub fn can_view_workspace(&self, workspace: &str) -> bool {
+        self.is_permitted(MuxPermission::View, Some(workspace), None)
+    }
+
+    /// Returns an error if the policy doesn't permit `pdu`.
+    /// `client_workspace` is the workspace that the client is
+    /// currently viewing, which is where any new windows that
+    /// it spawns will be placed.
+    pub fn check(&self, pdu: &Pdu, client_workspace: &str) -> anyhow::Result<()> {
+        if self.rules.is_empty() {
+            return Ok(());
+        }
+        let permission = match required_permission(pdu) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let scope = scope_for_pdu(pdu, client_workspace);
+        let permitted = match &scope {
+            Scope::Resource { workspace, domain } => {
+                // A pane that is being moved must also be permitted
+                // to arrive in its destination
+                let destination = destination_workspace(pdu, client_workspace);
+                std::iter::once(workspace.as_str())
+                    .chain(destination.as_deref())
+                    .all(|workspace| {
+                        self.is_permitted(permission, Some(workspace), domain.as_deref())
+                    })
+            }
+            Scope::Filtered => self.is_permitted(permission, None, None),
+            Scope::Server => self.is_permitted_everywhere(permission),
+            Scope::Unknown => false,
+        };
+        if !permitted {
+            // The scope is deliberately left out of the error, so that
+            // it doesn't reveal whether a pane or workspace exists
+            log::debug!(
+                "denied {} to user {:?}: {:?}",
+                pdu.pdu_name(),
+                self.user,
+                scope
+            );
+            return Err(anyhow!(
+                "user {} is not permitted {:?} access for {}",
+                self.user.as_deref().unwrap_or("<unknown>"),
+                permission,
+                pdu.pdu_name(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns true if a rule that isn't limited to any workspaces
+    /// or domains grants `permission`
+    fn is_permitted_everywhere(&self, permission: MuxPermission) -> bool {
+        if self.rules.is_empty() {
+            return true;
+        }
+        let user = match self.user.as_ref() {
+            Some(user) => user,
+            None => return false,
+        };
+        self.rules.iter().any(|rule| {
+            rule.workspaces.is_empty()
+                && rule.domains.is_empty()
+                && rule.permits(user, permission, None, None)
+        })
+    }
+}
+
+/// Returns the permission required to make the request `pdu`,
+/// or None if the request is always permitted.
+pub fn required_permission(pdu: &Pdu) -> Option<MuxPermission> {
+    match pdu {
+        Pdu::ListPanes(_)
+        | Pdu::GetPaneRenderChanges(_)
+        | Pdu::GetLines(_)
+        | Pdu::SearchScrollbackRequest(_)
+        | Pdu::SetFocusedPane(_)
+        | Pdu::SetActiveWorkspace(_)
+        | Pdu::ListWorkspaces(_)
+        | Pdu::GetFocusedWindow(_)
+        | Pdu::GetMemoryStats(_)
+        | Pdu::SetClientId(_) => Some(MuxPermission::View),
+
+        Pdu::WriteToPane(_)
+        | Pdu::SendKeyDown(_)
+        | Pdu::SendMouseEvent(_)
+        | Pdu::SendPaste(_)
+        | Pdu::Resize(_)
+        | Pdu::SetPaneZoomed(_)
+        | Pdu::SetPaneAmbiguousWidth(_)
+        | Pdu::ShowWindows(_) => Some(MuxPermission::Input),
+
+        // Issuing credentials is equivalent to granting a new connection,
+        // so we treat it as being as privileged as spawning
+        Pdu::Spawn(_) | Pdu::SpawnV2(_) | Pdu::SplitPane(_) | Pdu::GetTlsCreds(_) => {
+            Some(MuxPermission::Spawn)
+        }
+
+        Pdu::KillPane(_) | Pdu::KillWorkspace(_) => Some(MuxPermission::Kill),
+
+        // Moving a pane rearranges windows in much the same way
+        // as spawning into them
+        Pdu::MovePane(_) => Some(MuxPermission::Spawn),
+
+        // Renaming and reordering workspaces rearranges them for
+        // every client
+        Pdu::RenameWorkspace(_) | Pdu::SetWorkspaceOrder(_) => Some(MuxPermission::Spawn),
+
+        // Arbitrary file access is as powerful as running a command
+        Pdu::GetFileInfo(_) | Pdu::ReadFileChunk(_) | Pdu::WriteFileChunk(_) => {
+            Some(MuxPermission::Spawn)
+        }
+
+        // Raising the log level can expose the content of panes
+        // and other sensitive information in the logs
+        Pdu::SetLogLevel(_) => Some(MuxPermission::Spawn),
+
+        Pdu::Ping(_) | Pdu::GetCodecVersion(_) => None,
+
+        // Responses and unilateral notifications are not valid requests
+        // and are rejected elsewhere.
+        // This match is deliberately exhaustive so that adding a new
+        // PDU forces a decision about the permission it requires.
+        Pdu::Invalid { .. }
+        | Pdu::ErrorResponse(_)
+        | Pdu::Pong(_)
+        | Pdu::ListPanesResponse(_)
+        | Pdu::SpawnResponse(_)
+        | Pdu::UnitResponse(_)
+        | Pdu::SetClipboard(_)
+        | Pdu::GetLinesResponse(_)
+        | Pdu::GetPaneRenderChangesResponse(_)
+        | Pdu::GetCodecVersionResponse(_)
+        | Pdu::GetTlsCredsResponse(_)
+        | Pdu::LivenessResponse(_)
+        | Pdu::SearchScrollbackResponse(_)
+        | Pdu::PaneRemoved(_)
+        | Pdu::SetPalette(_)
+        | Pdu::NotifyAlert(_)
+        | Pdu::FileInfoResponse(_)
+        | Pdu::FileChunkResponse(_)
+        | Pdu::MovePaneResponse(_)
+        | Pdu::ListWorkspacesResponse(_)
+        | Pdu::GetFocusedWindowResponse(_)
+        | Pdu::GetMemoryStatsResponse(_) => None,
+    }
+}
+
+fn workspace_for_window(window_id: WindowId) -> Option<String> {
+    let mux = Mux::get().unwrap();
+    let window = mux.get_window(window_id)?;
+    Some(window.get_workspace().to_string())
+}
+
+/// What a request operates upon, which is matched against the
+/// workspaces and domains of the access rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Scope {
+    /// The request operates upon a workspace and, if it involves
+    /// a pane, the domain of that pane
+    Resource {
+        workspace: String,
+        domain: Option<String>,
+    },
+    /// The request isn't tied to a workspace or domain, but its
+    /// response only includes what the client is permitted to view,
+    /// so any rule that grants the permission permits it
+    Filtered,
+    /// The request affects the whole server, so it is only permitted
+    /// by rules that aren't limited to workspaces or domains
+    Server,
+    /// The scope can't be determined, eg: the pane doesn't exist,
+    /// so the request is denied
+    Unknown,
+}
+
+fn scope_for_pane(pane_id: PaneId) -> Scope {
+    let mux = Mux::get().unwrap();
+    let (domain_id, window_id, _tab_id) = match mux.resolve_pane_id(pane_id) {
+        Some(resolved) => resolved,
+        None => return Scope::Unknown,
+    };
+    match workspace_for_window(window_id) {
+        Some(workspace) => Scope::Resource {
+            workspace,
+            domain: mux
+                .get_domain(domain_id)
+                .map(|domain| domain.domain_name().to_string()),
+        },
+        None => Scope::Unknown,
+    }
+}
+
+fn domain_name_for_spawn(domain: &SpawnTabDomain) -> Option<String> {
+    let mux = Mux::get().unwrap();
+    match domain {
+        SpawnTabDomain::DefaultDomain => Some(mux.default_domain().domain_name().to_string()),
+        SpawnTabDomain::DomainName(name) => Some(name.to_string()),
+        SpawnTabDomain::CurrentPaneDomain => None,
+    }
+}
+
+/// Returns the workspace that the request `pdu` moves things into,
+/// if it is different from the one it operates upon
+fn destination_workspace(pdu: &Pdu, client_workspace: &str) -> Option<String> {
+    match pdu {
+        Pdu::MovePane(MovePane {
+            target: MovePaneTarget::Window(window_id),
+            ..
+        }) => workspace_for_window(*window_id),
+        Pdu::RenameWorkspace(RenameWorkspace { new, .. }) => Some(new.to_string()),
+        Pdu::MovePane(MovePane {
+            target: MovePaneTarget::NewWindow { workspace },
+            ..
+        }) => Some(
+            workspace
+                .clone()
+                .unwrap_or_else(|| client_workspace.to_string()),
+        ),
+        _ => None,
+    }
+}
+
+/// Returns the scope of the request `pdu`.
+/// This match is deliberately exhaustive so that adding a new
+/// PDU forces a decision about its scope.
+fn scope_for_pdu(pdu: &Pdu, client_workspace: &str) -> Scope {
+    let mux = Mux::get().unwrap();
+    let spawn_workspace = |window_id: &Option<WindowId>| {
+        window_id
+            .and_then(workspace_for_window)
+            .unwrap_or_else(|| client_workspace.to_string())
+    };
+    match pdu {
+        Pdu::GetPaneRenderChanges(GetPaneRenderChanges { pane_id, .. })
+        | Pdu::GetLines(GetLines { pane_id, .. })
+        | Pdu::SearchScrollbackRequest(SearchScrollbackRequest { pane_id, .. })
+        | Pdu::SetFocusedPane(SetFocusedPane { pane_id })
+        | Pdu::WriteToPane(WriteToPane { pane_id, .. })
+        | Pdu::SendKeyDown(SendKeyDown { pane_id, .. })
+        | Pdu::SendMouseEvent(SendMouseEvent { pane_id, .. })
+        | Pdu::SendPaste(SendPaste { pane_id, .. })
+        | Pdu::Resize(Resize { pane_id, .. })
+        | Pdu::SetPaneZoomed(SetPaneZoomed { pane_id, .. })
+        | Pdu::SetPaneAmbiguousWidth(SetPaneAmbiguousWidth { pane_id, .. })
+        | Pdu::KillPane(KillPane { pane_id })
+        | Pdu::MovePane(MovePane { pane_id, .. })
+        | Pdu::GetFileInfo(GetFileInfo { pane_id, .. })
+        | Pdu::ReadFileChunk(ReadFileChunk { pane_id, .. })
+        | Pdu::WriteFileChunk(WriteFileChunk { pane_id, .. }) => scope_for_pane(*pane_id),
+
+        Pdu::SetActiveWorkspace(SetActiveWorkspace { workspace })
+        | Pdu::SetClientId(SetClientId { workspace, .. })
+        | Pdu::KillWorkspace(KillWorkspace { workspace, .. })
+        | Pdu::RenameWorkspace(RenameWorkspace { old: workspace, .. }) => Scope::Resource {
+            workspace: workspace.to_string(),
+            domain: None,
+        },
+
+        Pdu::Spawn(Spawn {
+            domain_id,
+            window_id,
+            ..
+        }) => match mux.get_domain(*domain_id) {
+            Some(domain) => Scope::Resource {
+                workspace: spawn_workspace(window_id),
+                domain: Some(domain.domain_name().to_string()),
+            },
+            None => Scope::Unknown,
+        },
+
+        Pdu::SpawnV2(SpawnV2 {
+            domain, window_id, ..
+        }) => match domain_name_for_spawn(domain) {
+            Some(domain) => Scope::Resource {
+                workspace: spawn_workspace(window_id),
+                domain: Some(domain),
+            },
+            // There is no current pane from which to take the domain
+            None => Scope::Unknown,
+        },
+
+        Pdu::SplitPane(SplitPane {
+            pane_id, domain, ..
+        }) => match (scope_for_pane(*pane_id), domain_name_for_spawn(domain)) {
+            (Scope::Resource { workspace, .. }, Some(domain)) => Scope::Resource {
+                workspace,
+                domain: Some(domain),
+            },
+            (scope, None) => scope,
+            (_, Some(_)) => Scope::Unknown,
+        },
+
+        Pdu::GetFocusedWindow(GetFocusedWindow {}) => match mux.focused_window() {
+            Some(window_id) => match workspace_for_window(window_id) {
+                Some(workspace) => Scope::Resource {
+                    workspace,
+                    domain: None,
+                },
+                None => Scope::Unknown,
+            },
+            None => Scope::Filtered,
+        },
+
+        // The response is filtered to the viewable workspaces
+        // when it is produced
+        Pdu::ListPanes(_) | Pdu::ListWorkspaces(_) => Scope::Filtered,
+
+        // Always permitted by required_permission
+        Pdu::Ping(_) | Pdu::GetCodecVersion(_) => Scope::Filtered,
+
+        Pdu::GetTlsCreds(_)
+        | Pdu::SetLogLevel(_)
+        | Pdu::SetWorkspaceOrder(_)
+        | Pdu::ShowWindows(_)
+        | Pdu::GetMemoryStats(_) => Scope::Server,
+
+        // Responses and unilateral notifications are not valid requests
+        Pdu::Invalid { .. }
+        | Pdu::ErrorResponse(_)
+        | Pdu::Pong(_)
+        | Pdu::ListPanesResponse(_)
+        | Pdu::SpawnResponse(_)
+        | Pdu::UnitResponse(_)
+        | Pdu::SetClipboard(_)
+        | Pdu::GetLinesResponse(_)
+        | Pdu::GetPaneRenderChangesResponse(_)
+        | Pdu::GetCodecVersionResponse(_)
+        | Pdu::GetTlsCredsResponse(_)
+        | Pdu::LivenessResponse(_)
+        | Pdu::SearchScrollbackResponse(_)
+        | Pdu::PaneRemoved(_)
+        | Pdu::SetPalette(_)
+        | Pdu::NotifyAlert(_)
+        | Pdu::FileInfoResponse(_)
+        | Pdu::FileChunkResponse(_)
+        | Pdu::MovePaneResponse(_)
+        | Pdu::ListWorkspacesResponse(_)
+        | Pdu::GetFocusedWindowResponse(_)
+        | Pdu::GetMemoryStatsResponse(_) => Scope::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use portable_pty::PtySize;
+    use std::rc::Rc;
+
+    #[test]
+    fn permissions() {
+        assert_eq!(required_permission(&Pdu::Ping(Ping {})), None);
+        assert_eq!(
+            required_permission(&Pdu::ListPanes(ListPanes {})),
+            Some(MuxPermission::View)
+        );
+        assert_eq!(
+            required_permission(&Pdu::WriteToPane(WriteToPane {
+                pane_id: 0,
+                data: vec![],
+            })),
+            Some(MuxPermission::Input)
+        );
+        assert_eq!(
+            required_permission(&Pdu::KillPane(KillPane { pane_id: 0 })),
+            Some(MuxPermission::Kill)
+        );
+        assert_eq!(
+            required_permission(&Pdu::RenameWorkspace(RenameWorkspace {
+                old: "a".to_string(),
+                new: "b".to_string(),
+            })),
+            Some(MuxPermission::Spawn)
+        );
+    }
+
+    #[test]
+    fn scope() {
+        let mux = Rc::new(Mux::new(None));
+        Mux::set_mux(&mux);
+        let window_id = *mux.new_empty_window(Some("work".to_string()));
+
+        let spawn = |window_id| {
+            Pdu::SpawnV2(SpawnV2 {
+                domain: SpawnTabDomain::DomainName("remote".to_string()),
+                window_id,
+                command: None,
+                command_dir: None,
+                size: PtySize::default(),
+                encoding: None,
+                profile: None,
+            })
+        };
+        assert_eq!(
+            scope_for_pdu(&spawn(Some(window_id)), "default"),
+            Scope::Resource {
+                workspace: "work".to_string(),
+                domain: Some("remote".to_string())
+            }
+        );
+        assert_eq!(
+            scope_for_pdu(&spawn(None), "default"),
+            Scope::Resource {
+                workspace: "default".to_string(),
+                domain: Some("remote".to_string())
+            }
+        );
+        assert_eq!(
+            scope_for_pdu(
+                &Pdu::SetActiveWorkspace(SetActiveWorkspace {
+                    workspace: "play".to_string(),
+                }),
+                "default"
+            ),
+            Scope::Resource {
+                workspace: "play".to_string(),
+                domain: None
+            }
+        );
+        // An unknown pane has no scope, and is denied
+        let kill = Pdu::KillPane(KillPane { pane_id: 1234 });
+        assert_eq!(scope_for_pdu(&kill, "default"), Scope::Unknown);
+        assert_eq!(
+            scope_for_pdu(&Pdu::ListPanes(ListPanes {}), "default"),
+            Scope::Filtered
+        );
+        let set_log_level = Pdu::SetLogLevel(SetLogLevel {
+            filters: "debug".to_string(),
+        });
+        assert_eq!(scope_for_pdu(&set_log_level, "default"), Scope::Server);
+
+        let rename = Pdu::RenameWorkspace(RenameWorkspace {
+            old: "work".to_string(),
+            new: "play".to_string(),
+        });
+        assert_eq!(
+            destination_workspace(&rename, "default"),
+            Some("play".to_string())
+        );
+
+        let policy = AccessPolicy::new(
+            Some("alice".to_string()),
+            &[MuxAccessRule {
+                user: "alice".to_string(),
+                workspaces: vec!["work".to_string()],
+                domains: vec![],
+                permissions: vec![MuxPermission::Spawn],
+            }],
+        );
+        assert!(policy.check(&spawn(Some(window_id)), "default").is_ok());
+        assert!(policy.check(&spawn(None), "default").is_err());
+        // The destination must be permitted too
+        assert!(policy.check(&rename, "default").is_err());
+        assert!(policy.check(&kill, "default").is_err());
+        // A rule that is limited to a workspace doesn't grant
+        // requests that affect the whole server
+        assert!(policy.check(&set_log_level, "default").is_err());
+        assert!(AccessPolicy::new(
+            Some("alice".to_string()),
+            &[MuxAccessRule {
+                user: "alice".to_string(),
+                workspaces: vec![],
+                domains: vec![],
+                permissions: vec![MuxPermission::Spawn],
+            }],
+        )
+        .check(&set_log_level, "default")
+        .is_ok());
+        assert!(AccessPolicy::unrestricted()
+            .check(&rename, "default")
+            .is_ok());
+    }
+}
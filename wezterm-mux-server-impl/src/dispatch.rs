@@ -1,3 +1,4 @@
+use crate::access::AccessPolicy;
 use crate::sessionhandler::{PduSender, SessionHandler};
 use crate::UnixStream;
 use anyhow::Context;
@@ -7,6 +8,31 @@ use futures::FutureExt;
 use mux::{Mux, MuxNotification};
 use smol::prelude::*;
 use smol::Async;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of client connections that are currently
+/// being processed
+pub fn connection_count() -> usize {
+    CONNECTIONS.load(Ordering::SeqCst)
+}
+
+/// Tracks a live connection in CONNECTIONS for as long as it is held
+pub(crate) struct ConnectionGuard;
+
+impl ConnectionGuard {
+    pub(crate) fn new() -> Self {
+        CONNECTIONS.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        CONNECTIONS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
 
 #[cfg(unix)]
 pub trait AsRawDesc: std::os::unix::io::AsRawFd {}
@@ -23,7 +49,7 @@ enum Item {
     Readable,
 }
 
-pub async fn process<T>(stream: T) -> anyhow::Result<()>
+pub async fn process<T>(stream: T, policy: AccessPolicy) -> anyhow::Result<()>
 where
     T: 'static,
     T: std::io::Read,
@@ -32,10 +58,10 @@ where
     T: std::fmt::Debug,
 {
     let stream = smol::Async::new(stream)?;
-    process_async(stream).await
+    process_async(stream, policy).await
 }
 
-pub async fn process_async<T>(mut stream: Async<T>) -> anyhow::Result<()>
+pub async fn process_async<T>(mut stream: Async<T>, policy: AccessPolicy) -> anyhow::Result<()>
 where
     T: 'static,
     T: std::io::Read,
@@ -43,6 +69,7 @@ where
     T: std::fmt::Debug,
 {
     log::trace!("process_async called");
+    let _connection = ConnectionGuard::new();
 
     let (item_tx, item_rx) = smol::channel::unbounded::<Item>();
 
@@ -54,7 +81,7 @@ where
                 .map_err(|e| anyhow::anyhow!("{:?}", e))
         }
     });
-    let mut handler = SessionHandler::new(pdu_sender);
+    let mut handler = SessionHandler::new(pdu_sender, policy);
 
     {
         let mux = Mux::get().expect("to be running on gui thread");
@@ -62,13 +89,15 @@ where
         mux.subscribe(move |n| tx.try_send(Item::Notif(n)).is_ok());
     }
 
+    let mut decode_buffer = vec![];
     loop {
         let rx_msg = item_rx.recv();
         let wait_for_read = stream.readable().map(|_| Ok(Item::Readable));
 
         match smol::future::or(rx_msg, wait_for_read).await {
             Ok(Item::Readable) => {
-                let decoded = Pdu::decode_async(&mut stream).await?;
+                let decoded =
+                    Pdu::decode_async_with_buffer(&mut stream, &mut decode_buffer).await?;
                 handler.process_one(decoded);
             }
             Ok(Item::WritePdu(decoded)) => {
@@ -99,6 +128,11 @@ where
             Ok(Item::Notif(MuxNotification::WindowRemoved(_window_id))) => {}
             Ok(Item::Notif(MuxNotification::WindowCreated(_window_id))) => {}
             Ok(Item::Notif(MuxNotification::WindowInvalidated(_window_id))) => {}
+            Ok(Item::Notif(MuxNotification::ActiveWorkspaceChanged(_))) => {}
+            Ok(Item::Notif(MuxNotification::WorkspaceCreated(_))) => {}
+            Ok(Item::Notif(MuxNotification::WorkspaceRenamed { .. })) => {}
+            Ok(Item::Notif(MuxNotification::WorkspaceClosed(_))) => {}
+            Ok(Item::Notif(MuxNotification::ShowWindowsRequested)) => {}
             Ok(Item::Notif(MuxNotification::Empty)) => {}
             Err(err) => {
                 log::error!("process_async Err {}", err);
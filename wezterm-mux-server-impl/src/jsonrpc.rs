@@ -0,0 +1,667 @@
+//! A JSON-RPC 2.0 control interface to the mux.
+//!
+//! Requests and responses are newline delimited JSON objects exchanged
+//! over a unix domain socket.  Each method is translated into the
+//! equivalent mux protocol request and handled by a `SessionHandler`,
+//! so the same access rules apply as for a regular mux client.
+//! The method names and parameters are intended to remain stable
+//! even as the underlying mux protocol evolves.
+use crate::access::AccessPolicy;
+use crate::dispatch::{AsRawDesc, ConnectionGuard};
+use crate::local::{policy_for_stream, safely_create_sock_path_at};
+use crate::sessionhandler::{PduSender, SessionHandler};
+use crate::UnixListener;
+use codec::*;
+use config::keyassignment::SpawnTabDomain;
+use config::{configuration, MuxAccessRule, UnixDomain};
+use mux::pane::PaneId;
+use mux::tab::SplitDirection;
+use mux::window::WindowId;
+use mux::Mux;
+use portable_pty::CommandBuilder;
+use promise::spawn::spawn_into_main_thread;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use smol::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use wezterm_term::StableRowIndex;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+pub struct JsonRpcListener {
+    listener: UnixListener,
+    access_rules: Vec<MuxAccessRule>,
+}
+
+impl JsonRpcListener {
+    /// Sets up the control socket for `unix_dom`, returning None
+    /// if it has no `control_socket_path` configured
+    pub fn with_domain(unix_dom: &UnixDomain) -> anyhow::Result<Option<Self>> {
+        let path = match unix_dom.control_socket_path.as_ref() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let listener = safely_create_sock_path_at(path, unix_dom.skip_permissions_check)?;
+        Ok(Some(Self {
+            listener,
+            access_rules: unix_dom.access_rules.clone(),
+        }))
+    }
+
+    pub fn run(&mut self) {
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let policy = policy_for_stream(&stream, &self.access_rules);
+                    spawn_into_main_thread(async move {
+                        process(stream, policy).await.map_err(|e| {
+                            log::error!("json-rpc: {:#}", e);
+                            e
+                        })
+                    })
+                    .detach();
+                }
+                Err(err) => {
+                    log::error!("accept failed: {}", err);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    /// Present (and "2.0") in conforming requests; we don't
+    /// insist upon it
+    #[allow(dead_code)]
+    jsonrpc: Option<String>,
+    /// Requests without an id are notifications and receive
+    /// no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Serialize, Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new<S: Into<String>>(code: i64, message: S) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PaneParams {
+    pane_id: PaneId,
+}
+
+#[derive(Deserialize)]
+struct SpawnParams {
+    domain_name: Option<String>,
+    window_id: Option<WindowId>,
+    args: Option<Vec<String>>,
+    cwd: Option<String>,
+    rows: Option<u16>,
+    cols: Option<u16>,
+    encoding: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SplitPaneParams {
+    pane_id: PaneId,
+    direction: Option<SplitDirection>,
+    domain_name: Option<String>,
+    args: Option<Vec<String>>,
+    cwd: Option<String>,
+    encoding: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SendTextParams {
+    pane_id: PaneId,
+    text: String,
+    /// If true, send the text as a paste, which applies
+    /// bracketed paste if the application has enabled it
+    #[serde(default)]
+    paste: bool,
+}
+
+#[derive(Deserialize)]
+struct GetTextParams {
+    pane_id: PaneId,
+    start_row: Option<StableRowIndex>,
+    end_row: Option<StableRowIndex>,
+}
+
+#[derive(Deserialize)]
+struct SetZoomedParams {
+    pane_id: PaneId,
+    zoomed: bool,
+}
+
+#[derive(Deserialize)]
+struct MovePaneParams {
+    pane_id: PaneId,
+    window_id: Option<WindowId>,
+    workspace: Option<String>,
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    // Allow params to be omitted for methods whose
+    // parameters are all optional
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn command_from_args(args: Option<Vec<String>>) -> Option<CommandBuilder> {
+    args.map(|args| CommandBuilder::from_argv(args.into_iter().map(Into::into).collect()))
+}
+
+fn spawn_domain(domain_name: Option<String>, default: SpawnTabDomain) -> SpawnTabDomain {
+    domain_name
+        .map(SpawnTabDomain::DomainName)
+        .unwrap_or(default)
+}
+
+/// Translates a JSON-RPC method call into the equivalent mux request.
+/// Methods that need to look up a pane to build their request first
+/// pass an equivalent request to `check_access`, so that callers can't
+/// tell whether a pane exists unless they are permitted to access it.
+fn request_to_pdu(
+    method: &str,
+    params: Value,
+    check_access: &dyn Fn(&Pdu) -> anyhow::Result<()>,
+) -> Result<Pdu, RpcError> {
+    let check_access = |pdu: Pdu| {
+        check_access(&pdu).map_err(|err| RpcError::new(SERVER_ERROR, format!("{:#}", err)))
+    };
+    match method {
+        "list_panes" => Ok(Pdu::ListPanes(ListPanes {})),
+        "spawn" => {
+            let params: SpawnParams = parse_params(params)?;
+            let mut size = configuration().initial_size();
+            if let Some(rows) = params.rows {
+                size.rows = rows;
+            }
+            if let Some(cols) = params.cols {
+                size.cols = cols;
+            }
+            Ok(Pdu::SpawnV2(SpawnV2 {
+                domain: spawn_domain(params.domain_name, SpawnTabDomain::DefaultDomain),
+                window_id: params.window_id,
+                command: command_from_args(params.args),
+                command_dir: params.cwd,
+                size,
+                encoding: params.encoding,
+                profile: None,
+            }))
+        }
+        "split_pane" => {
+            let params: SplitPaneParams = parse_params(params)?;
+            Ok(Pdu::SplitPane(SplitPane {
+                pane_id: params.pane_id,
+                direction: params.direction.unwrap_or(SplitDirection::Horizontal),
+                command: command_from_args(params.args),
+                command_dir: params.cwd,
+                domain: spawn_domain(params.domain_name, SpawnTabDomain::CurrentPaneDomain),
+                encoding: params.encoding,
+            }))
+        }
+        "kill_pane" => {
+            let params: PaneParams = parse_params(params)?;
+            Ok(Pdu::KillPane(KillPane {
+                pane_id: params.pane_id,
+            }))
+        }
+        "send_text" => {
+            let params: SendTextParams = parse_params(params)?;
+            if params.paste {
+                Ok(Pdu::SendPaste(SendPaste {
+                    pane_id: params.pane_id,
+                    data: params.text,
+                }))
+            } else {
+                Ok(Pdu::WriteToPane(WriteToPane {
+                    pane_id: params.pane_id,
+                    data: params.text.into_bytes(),
+                }))
+            }
+        }
+        "get_text" => {
+            let params: GetTextParams = parse_params(params)?;
+            check_access(Pdu::GetLines(GetLines {
+                pane_id: params.pane_id,
+                lines: vec![],
+            }))?;
+            let mux = Mux::get().unwrap();
+            let pane = mux.get_pane(params.pane_id).ok_or_else(|| {
+                RpcError::new(INVALID_PARAMS, format!("no such pane {}", params.pane_id))
+            })?;
+            // Default to the visible portion of the screen
+            let dims = pane.get_dimensions();
+            let start_row = params.start_row.unwrap_or(dims.physical_top);
+            let end_row = params
+                .end_row
+                .unwrap_or(dims.physical_top + dims.viewport_rows as StableRowIndex);
+            Ok(Pdu::GetLines(GetLines {
+                pane_id: params.pane_id,
+                lines: vec![start_row..end_row],
+            }))
+        }
+        "set_zoomed" => {
+            let params: SetZoomedParams = parse_params(params)?;
+            check_access(Pdu::SetPaneZoomed(SetPaneZoomed {
+                containing_tab_id: 0,
+                pane_id: params.pane_id,
+                zoomed: params.zoomed,
+            }))?;
+            let mux = Mux::get().unwrap();
+            let (_domain_id, _window_id, tab_id) =
+                mux.resolve_pane_id(params.pane_id).ok_or_else(|| {
+                    RpcError::new(INVALID_PARAMS, format!("no such pane {}", params.pane_id))
+                })?;
+            Ok(Pdu::SetPaneZoomed(SetPaneZoomed {
+                containing_tab_id: tab_id,
+                pane_id: params.pane_id,
+                zoomed: params.zoomed,
+            }))
+        }
+        "move_pane" => {
+            let params: MovePaneParams = parse_params(params)?;
+            Ok(Pdu::MovePane(MovePane {
+                pane_id: params.pane_id,
+                target: match params.window_id {
+                    Some(window_id) => MovePaneTarget::Window(window_id),
+                    None => MovePaneTarget::NewWindow {
+                        workspace: params.workspace,
+                    },
+                },
+            }))
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method {}", method),
+        )),
+    }
+}
+
+/// Translates the response from the mux into the JSON-RPC result
+fn response_to_json(pdu: Pdu) -> Result<Value, RpcError> {
+    match pdu {
+        Pdu::ErrorResponse(ErrorResponse { reason }) => Err(RpcError::new(SERVER_ERROR, reason)),
+        Pdu::UnitResponse(_) => Ok(Value::Null),
+        Pdu::ListPanesResponse(ListPanesResponse { tabs }) => Ok(json!({ "tabs": tabs })),
+        Pdu::MovePaneResponse(MovePaneResponse { tab_id, window_id }) => {
+            Ok(json!({ "tab_id": tab_id, "window_id": window_id }))
+        }
+        Pdu::SpawnResponse(response) => {
+            serde_json::to_value(response).map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))
+        }
+        Pdu::GetLinesResponse(GetLinesResponse { lines, .. }) => {
+            let text = lines
+                .lines()
+                .into_iter()
+                .map(|(_row, line)| line.as_str().trim_end().to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            Ok(json!({ "text": text }))
+        }
+        other => Err(RpcError::new(
+            SERVER_ERROR,
+            format!("unexpected response {}", other.pdu_name()),
+        )),
+    }
+}
+
+async fn process<T>(stream: T, policy: AccessPolicy) -> anyhow::Result<()>
+where
+    T: 'static,
+    T: std::io::Read,
+    T: std::io::Write,
+    T: AsRawDesc,
+    T: std::fmt::Debug,
+{
+    let _connection = ConnectionGuard::new();
+    let mut stream = BufReader::new(smol::Async::new(stream)?);
+
+    let (pdu_tx, pdu_rx) = smol::channel::unbounded::<DecodedPdu>();
+    let mut handler = SessionHandler::new(
+        PduSender::new(move |pdu| pdu_tx.try_send(pdu).map_err(|e| anyhow::anyhow!("{:?}", e))),
+        policy,
+    );
+
+    let mut serial = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if stream.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                let code = if serde_json::from_str::<Value>(&line).is_ok() {
+                    INVALID_REQUEST
+                } else {
+                    PARSE_ERROR
+                };
+                let response = Response {
+                    jsonrpc: "2.0",
+                    id: Value::Null,
+                    result: None,
+                    error: Some(RpcError::new(code, err.to_string())),
+                };
+                write_response(stream.get_mut(), &response).await?;
+                continue;
+            }
+        };
+
+        let pdu = request_to_pdu(&request.method, request.params, &|pdu| {
+            handler.check_access(pdu)
+        });
+        let result = match pdu {
+            Ok(pdu) => {
+                serial += 1;
+                handler.process_one(DecodedPdu { serial, pdu });
+                loop {
+                    let decoded = pdu_rx.recv().await?;
+                    if decoded.serial == serial {
+                        break response_to_json(decoded.pdu);
+                    }
+                }
+            }
+            Err(err) => Err(err),
+        };
+
+        if let Some(id) = request.id {
+            let response = match result {
+                Ok(result) => Response {
+                    jsonrpc: "2.0",
+                    id,
+                    result: Some(result),
+                    error: None,
+                },
+                Err(error) => Response {
+                    jsonrpc: "2.0",
+                    id,
+                    result: None,
+                    error: Some(error),
+                },
+            };
+            write_response(stream.get_mut(), &response).await?;
+        }
+    }
+}
+
+async fn write_response<W: AsyncWriteExt + Unpin>(
+    stream: &mut W,
+    response: &Response,
+) -> anyhow::Result<()> {
+    let mut data = serde_json::to_string(response)?;
+    data.push('\n');
+    stream.write_all(data.as_bytes()).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use portable_pty::PtySize;
+
+    fn permit_all(_pdu: &Pdu) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    #[test]
+    fn requests() {
+        config::use_test_configuration();
+
+        assert_eq!(
+            request_to_pdu("list_panes", Value::Null, &permit_all).unwrap(),
+            Pdu::ListPanes(ListPanes {})
+        );
+
+        let size = configuration().initial_size();
+        assert_eq!(
+            request_to_pdu(
+                "spawn",
+                json!({"domain_name": "remote", "args": ["top"], "rows": 10}),
+                &permit_all
+            )
+            .unwrap(),
+            Pdu::SpawnV2(SpawnV2 {
+                domain: SpawnTabDomain::DomainName("remote".to_string()),
+                window_id: None,
+                command: Some(CommandBuilder::from_argv(vec!["top".into()])),
+                command_dir: None,
+                size: PtySize { rows: 10, ..size },
+                encoding: None,
+                profile: None,
+            })
+        );
+        assert_eq!(
+            request_to_pdu("spawn", Value::Null, &permit_all).unwrap(),
+            Pdu::SpawnV2(SpawnV2 {
+                domain: SpawnTabDomain::DefaultDomain,
+                window_id: None,
+                command: None,
+                command_dir: None,
+                size,
+                encoding: None,
+                profile: None,
+            })
+        );
+
+        assert_eq!(
+            request_to_pdu(
+                "split_pane",
+                json!({"pane_id": 2, "direction": "Vertical"}),
+                &permit_all
+            )
+            .unwrap(),
+            Pdu::SplitPane(SplitPane {
+                pane_id: 2,
+                direction: SplitDirection::Vertical,
+                command: None,
+                command_dir: None,
+                domain: SpawnTabDomain::CurrentPaneDomain,
+                encoding: None,
+            })
+        );
+
+        assert_eq!(
+            request_to_pdu(
+                "send_text",
+                json!({"pane_id": 1, "text": "ls\n"}),
+                &permit_all
+            )
+            .unwrap(),
+            Pdu::WriteToPane(WriteToPane {
+                pane_id: 1,
+                data: b"ls\n".to_vec(),
+            })
+        );
+        assert_eq!(
+            request_to_pdu(
+                "send_text",
+                json!({"pane_id": 1, "text": "ls", "paste": true}),
+                &permit_all
+            )
+            .unwrap(),
+            Pdu::SendPaste(SendPaste {
+                pane_id: 1,
+                data: "ls".to_string(),
+            })
+        );
+
+        assert_eq!(
+            request_to_pdu(
+                "move_pane",
+                json!({"pane_id": 3, "workspace": "work"}),
+                &permit_all
+            )
+            .unwrap(),
+            Pdu::MovePane(MovePane {
+                pane_id: 3,
+                target: MovePaneTarget::NewWindow {
+                    workspace: Some("work".to_string()),
+                },
+            })
+        );
+        assert_eq!(
+            request_to_pdu(
+                "move_pane",
+                json!({"pane_id": 3, "window_id": 4}),
+                &permit_all
+            )
+            .unwrap(),
+            Pdu::MovePane(MovePane {
+                pane_id: 3,
+                target: MovePaneTarget::Window(4),
+            })
+        );
+    }
+
+    #[test]
+    fn request_errors() {
+        assert_eq!(
+            request_to_pdu("reboot", Value::Null, &permit_all)
+                .unwrap_err()
+                .code,
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            request_to_pdu("kill_pane", Value::Null, &permit_all)
+                .unwrap_err()
+                .code,
+            INVALID_PARAMS
+        );
+        assert_eq!(
+            request_to_pdu("kill_pane", json!({"pane_id": "one"}), &permit_all)
+                .unwrap_err()
+                .code,
+            INVALID_PARAMS
+        );
+        assert_eq!(
+            request_to_pdu("send_text", json!({"pane_id": 1}), &permit_all)
+                .unwrap_err()
+                .code,
+            INVALID_PARAMS
+        );
+    }
+
+    #[test]
+    fn access_is_checked_before_pane_lookup() {
+        let deny =
+            |pdu: &Pdu| -> anyhow::Result<()> { anyhow::bail!("not permitted {}", pdu.pdu_name()) };
+        // A denied caller gets the same error whether or not the pane
+        // exists, rather than "no such pane"
+        let err = request_to_pdu("get_text", json!({"pane_id": 1234}), &deny).unwrap_err();
+        assert_eq!(err.code, SERVER_ERROR);
+        assert_eq!(err.message, "not permitted GetLines");
+        let err = request_to_pdu(
+            "set_zoomed",
+            json!({"pane_id": 1234, "zoomed": true}),
+            &deny,
+        )
+        .unwrap_err();
+        assert_eq!(err.code, SERVER_ERROR);
+    }
+
+    #[test]
+    fn responses() {
+        assert_eq!(
+            response_to_json(Pdu::UnitResponse(UnitResponse {})).unwrap(),
+            Value::Null
+        );
+        assert_eq!(
+            response_to_json(Pdu::MovePaneResponse(MovePaneResponse {
+                tab_id: 1,
+                window_id: 2,
+            }))
+            .unwrap(),
+            json!({"tab_id": 1, "window_id": 2})
+        );
+
+        let size = PtySize::default();
+        assert_eq!(
+            response_to_json(Pdu::SpawnResponse(SpawnResponse {
+                tab_id: 1,
+                pane_id: 2,
+                window_id: 3,
+                size,
+            }))
+            .unwrap(),
+            json!({
+                "tab_id": 1,
+                "pane_id": 2,
+                "window_id": 3,
+                "size": serde_json::to_value(size).unwrap(),
+            })
+        );
+
+        let err = response_to_json(Pdu::ErrorResponse(ErrorResponse {
+            reason: "no such pane".to_string(),
+        }))
+        .unwrap_err();
+        assert_eq!(err.code, SERVER_ERROR);
+        assert_eq!(err.message, "no such pane");
+
+        assert_eq!(
+            response_to_json(Pdu::Pong(Pong {})).unwrap_err().code,
+            SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn serialize_response() {
+        let response = Response {
+            jsonrpc: "2.0",
+            id: json!(1),
+            result: Some(Value::Null),
+            error: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({"jsonrpc": "2.0", "id": 1, "result": null})
+        );
+
+        let response = Response {
+            jsonrpc: "2.0",
+            id: Value::Null,
+            result: None,
+            error: Some(RpcError::new(PARSE_ERROR, "oops")),
+        };
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({"jsonrpc": "2.0", "id": null, "error": {"code": PARSE_ERROR, "message": "oops"}})
+        );
+    }
+}
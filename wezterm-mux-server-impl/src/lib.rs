@@ -1,9 +1,11 @@
 #[cfg(unix)]
-use std::os::unix::net::{UnixListener, UnixStream};
+pub use std::os::unix::net::{UnixListener, UnixStream};
 #[cfg(windows)]
-use uds_windows::{UnixListener, UnixStream};
+pub use uds_windows::{UnixListener, UnixStream};
 
+pub mod access;
 pub mod dispatch;
+pub mod jsonrpc;
 pub mod local;
 pub mod pki;
 pub mod sessionhandler;
@@ -1,28 +1,44 @@
-use crate::UnixListener;
+use crate::access::AccessPolicy;
+use crate::{UnixListener, UnixStream};
 use anyhow::{anyhow, Context as _};
-use config::{create_user_owned_dirs, UnixDomain};
+use config::{create_user_owned_dirs, MuxAccessRule, UnixDomain};
 use promise::spawn::spawn_into_main_thread;
+use std::path::Path;
 
 pub struct LocalListener {
     listener: UnixListener,
+    access_rules: Vec<MuxAccessRule>,
 }
 
 impl LocalListener {
     pub fn new(listener: UnixListener) -> Self {
-        Self { listener }
+        Self {
+            listener,
+            access_rules: vec![],
+        }
     }
 
     pub fn with_domain(unix_dom: &UnixDomain) -> anyhow::Result<Self> {
         let listener = safely_create_sock_path(unix_dom)?;
-        Ok(Self::new(listener))
+        Ok(Self::with_listener(listener, unix_dom))
+    }
+
+    /// Serve `unix_dom` using a listener that has already been set up;
+    /// for example, one that was passed to us via socket activation.
+    pub fn with_listener(listener: UnixListener, unix_dom: &UnixDomain) -> Self {
+        Self {
+            listener,
+            access_rules: unix_dom.access_rules.clone(),
+        }
     }
 
     pub fn run(&mut self) {
         for stream in self.listener.incoming() {
             match stream {
                 Ok(stream) => {
+                    let policy = policy_for_stream(&stream, &self.access_rules);
                     spawn_into_main_thread(async move {
-                        crate::dispatch::process(stream).await.map_err(|e| {
+                        crate::dispatch::process(stream, policy).await.map_err(|e| {
                             log::error!("{:#}", e);
                             e
                         })
@@ -38,12 +54,90 @@ impl LocalListener {
     }
 }
 
+/// Computes the access policy for a client connected via `stream`
+pub(crate) fn policy_for_stream(stream: &UnixStream, rules: &[MuxAccessRule]) -> AccessPolicy {
+    if rules.is_empty() {
+        return AccessPolicy::unrestricted();
+    }
+    let user = match peer_user_name(stream) {
+        Ok(user) => Some(user),
+        Err(err) => {
+            log::error!("unable to determine peer user name: {:#}", err);
+            None
+        }
+    };
+    AccessPolicy::new(user, rules)
+}
+
+/// Returns the uid of the process on the other end of `stream`
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> anyhow::Result<libc::uid_t> {
+    use std::os::unix::io::AsRawFd;
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error()).context("getsockopt SO_PEERCRED");
+    }
+    Ok(cred.uid)
+}
+
+/// Returns the uid of the process on the other end of `stream`
+#[cfg(all(unix, not(target_os = "linux")))]
+fn peer_uid(stream: &UnixStream) -> anyhow::Result<libc::uid_t> {
+    use std::os::unix::io::AsRawFd;
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    let res = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error()).context("getpeereid");
+    }
+    Ok(uid)
+}
+
+/// Resolves the name of the user on the other end of `stream`
+#[cfg(unix)]
+fn peer_user_name(stream: &UnixStream) -> anyhow::Result<String> {
+    let uid = peer_uid(stream)?;
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let res = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if res != 0 || result.is_null() {
+        anyhow::bail!("no passwd entry for uid {}", uid);
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(pwd.pw_name) };
+    Ok(name.to_string_lossy().to_string())
+}
+
+/// There is no portable way to obtain the peer credentials for an
+/// AF_UNIX socket on Windows; since the socket directory is only
+/// accessible to the owner, assume that the peer is the same user.
+#[cfg(windows)]
+fn peer_user_name(_stream: &UnixStream) -> anyhow::Result<String> {
+    config::username_from_env()
+}
+
 /// Take care when setting up the listener socket;
 /// we need to be sure that the directory that we create it in
 /// is owned by the user and has appropriate file permissions
 /// that prevent other users from manipulating its contents.
 fn safely_create_sock_path(unix_dom: &UnixDomain) -> anyhow::Result<UnixListener> {
-    let sock_path = &unix_dom.socket_path();
+    safely_create_sock_path_at(&unix_dom.socket_path(), unix_dom.skip_permissions_check)
+}
+
+pub(crate) fn safely_create_sock_path_at(
+    sock_path: &Path,
+    skip_permissions_check: bool,
+) -> anyhow::Result<UnixListener> {
     log::info!("setting up {}", sock_path.display());
 
     let sock_dir = sock_path
@@ -57,7 +151,7 @@ fn safely_create_sock_path(unix_dom: &UnixDomain) -> anyhow::Result<UnixListener
         use config::running_under_wsl;
         use std::os::unix::fs::PermissionsExt;
 
-        if !running_under_wsl() && !unix_dom.skip_permissions_check {
+        if !running_under_wsl() && !skip_permissions_check {
             // Let's be sure that the ownership looks sane
             let meta = sock_dir.symlink_metadata()?;
 
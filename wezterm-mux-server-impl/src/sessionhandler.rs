@@ -1,22 +1,28 @@
+use crate::access::{required_permission, AccessPolicy};
 use crate::PKI;
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use codec::*;
 use config::keyassignment::SpawnTabDomain;
+use config::{MuxPermission, TermConfig};
+use mux::client::ClientId;
+use mux::encoding::CharacterEncoding;
 use mux::pane::{Pane, PaneId};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
-use mux::Mux;
+use mux::{Mux, MuxNotification};
 use percent_encoding::percent_decode_str;
 use portable_pty::PtySize;
 use promise::spawn::spawn_into_main_thread;
 use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use termwiz::surface::SequenceNo;
 use url::Url;
 use wezterm_term::terminal::{Alert, Clipboard, ClipboardSelection};
-use wezterm_term::StableRowIndex;
+use wezterm_term::{CommandResult, StableRowIndex};
 
 #[derive(Clone)]
 pub struct PduSender {
@@ -41,6 +47,7 @@ pub(crate) struct PerPane {
     cursor_position: StableCursorPosition,
     title: String,
     working_dir: Option<Url>,
+    command_results: Vec<CommandResult>,
     dimensions: RenderableDimensions,
     mouse_grabbed: bool,
     sent_initial_palette: bool,
@@ -80,6 +87,11 @@ impl PerPane {
             changed = true;
         }
 
+        let command_results = pane.get_command_results();
+        if command_results != self.command_results {
+            changed = true;
+        }
+
         let mut all_dirty_lines = pane.get_changed_since(
             0..dims.physical_top + dims.viewport_rows as StableRowIndex,
             self.seqno,
@@ -115,6 +127,7 @@ impl PerPane {
         self.cursor_position = cursor_position;
         self.title = title.clone();
         self.working_dir = working_dir.clone();
+        self.command_results = command_results.clone();
         self.dimensions = dims;
         self.mouse_grabbed = mouse_grabbed;
         self.seqno = pane.get_current_seqno();
@@ -129,6 +142,7 @@ impl PerPane {
             title,
             bonus_lines,
             working_dir: working_dir.map(Into::into),
+            command_results,
             input_serial: force_with_input_serial,
             seqno: self.seqno,
         })
@@ -179,10 +193,22 @@ fn maybe_push_pane_changes(
 pub struct SessionHandler {
     to_write_tx: PduSender,
     per_pane: HashMap<TabId, Arc<Mutex<PerPane>>>,
+    client_id: Option<ClientId>,
+    read_only: bool,
+    policy: Arc<AccessPolicy>,
+}
+
+impl Drop for SessionHandler {
+    fn drop(&mut self) {
+        if let Some(client_id) = self.client_id.take() {
+            let mux = Mux::get().expect("to be running on gui thread");
+            mux.unregister_client(&client_id);
+        }
+    }
 }
 
 impl SessionHandler {
-    pub fn new(to_write_tx: PduSender) -> Self {
+    pub fn new(to_write_tx: PduSender, policy: AccessPolicy) -> Self {
         // Fixup the clipboard on the empty initial pane that is
         // spawned into the mux
         let mux = Mux::get().unwrap();
@@ -197,6 +223,9 @@ impl SessionHandler {
         Self {
             to_write_tx,
             per_pane: HashMap::new(),
+            client_id: None,
+            read_only: false,
+            policy: Arc::new(policy),
         }
     }
 
@@ -222,6 +251,23 @@ impl SessionHandler {
         .detach();
     }
 
+    /// Returns an error if this client is not permitted to make
+    /// the request `pdu`
+    pub fn check_access(&self, pdu: &Pdu) -> anyhow::Result<()> {
+        if self.read_only && !permitted_when_read_only(pdu) {
+            anyhow::bail!(
+                "this client is attached in read-only mode and may not send {}",
+                pdu.pdu_name()
+            );
+        }
+        let mux = Mux::get().expect("to be running on gui thread");
+        let workspace = match self.client_id.as_ref() {
+            Some(client_id) => mux.active_workspace_for_client(client_id),
+            None => mux.active_workspace(),
+        };
+        self.policy.check(pdu, &workspace)
+    }
+
     pub fn process_one(&mut self, decoded: DecodedPdu) {
         let start = Instant::now();
         let sender = self.to_write_tx.clone();
@@ -246,18 +292,47 @@ impl SessionHandler {
             send_response(f());
         }
 
+        if let Err(err) = self.check_access(&decoded.pdu) {
+            send_response(Err(err));
+            return;
+        }
+
         match decoded.pdu {
             Pdu::Ping(Ping {}) => send_response(Ok(Pdu::Pong(Pong {}))),
             Pdu::ListPanes(ListPanes {}) => {
+                let client_id = self.client_id.clone();
+                let policy = Arc::clone(&self.policy);
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
                             let mux = Mux::get().unwrap();
+                            // Clients that have identified themselves only get to see
+                            // the windows in their active workspace, and see their own
+                            // idea of the focused pane rather than that of whichever
+                            // client most recently changed it.
+                            let (window_ids, focused) = match client_id.as_ref() {
+                                Some(client_id) => (
+                                    mux.iter_windows_in_workspace(
+                                        &mux.active_workspace_for_client(client_id),
+                                    ),
+                                    mux.focused_pane_for_client(client_id),
+                                ),
+                                None => (mux.iter_windows(), None),
+                            };
                             let mut tabs = vec![];
-                            for window_id in mux.iter_windows().into_iter() {
+                            for window_id in window_ids.into_iter() {
                                 let window = mux.get_window(window_id).unwrap();
+                                if !policy.can_view_workspace(window.get_workspace()) {
+                                    continue;
+                                }
                                 for tab in window.iter() {
-                                    tabs.push(tab.codec_pane_tree());
+                                    let mut tree = tab.codec_pane_tree();
+                                    if let Some(focused) = focused {
+                                        if tree.contains_pane(focused) {
+                                            tree.set_active_pane(focused);
+                                        }
+                                    }
+                                    tabs.push(tree);
                                 }
                             }
                             log::trace!("ListPanes {:#?}", tabs);
@@ -269,6 +344,180 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::SetClientId(SetClientId {
+                client_id,
+                workspace,
+                read_only,
+            }) => {
+                self.client_id.replace(client_id.clone());
+                // Once a connection has asked to be read-only, it
+                // cannot later upgrade itself
+                self.read_only = self.read_only || read_only;
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.register_client(&client_id, &workspace);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::SetActiveWorkspace(SetActiveWorkspace { workspace }) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let client_id =
+                                client_id.ok_or_else(|| anyhow!("no client id was set"))?;
+                            let mux = Mux::get().unwrap();
+                            mux.set_active_workspace_for_client(&client_id, &workspace);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::ListWorkspaces(ListWorkspaces {}) => {
+                let policy = Arc::clone(&self.policy);
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let workspaces = mux
+                                .iter_workspaces()
+                                .into_iter()
+                                .filter(|w| policy.can_view_workspace(w))
+                                .collect();
+                            Ok(Pdu::ListWorkspacesResponse(ListWorkspacesResponse {
+                                workspaces,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::RenameWorkspace(RenameWorkspace { old, new }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.rename_workspace(&old, &new)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::KillWorkspace(KillWorkspace { workspace, force }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            if mux.iter_windows_in_workspace(&workspace).is_empty() {
+                                return Err(anyhow!("there is no workspace named {}", workspace));
+                            }
+                            if !force && !mux.can_close_workspace_without_prompting(&workspace) {
+                                return Err(anyhow!(
+                                    "workspace {} has panes that are running programs",
+                                    workspace
+                                ));
+                            }
+                            mux.kill_workspace(&workspace);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::SetWorkspaceOrder(SetWorkspaceOrder { workspaces }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.set_workspace_order(workspaces);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::ShowWindows(ShowWindows {}) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.notify(MuxNotification::ShowWindowsRequested);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::GetFocusedWindow(GetFocusedWindow {}) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            Ok(Pdu::GetFocusedWindowResponse(GetFocusedWindowResponse {
+                                window_id: mux.focused_window(),
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::GetMemoryStats(GetMemoryStats {}) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            Ok(Pdu::GetMemoryStatsResponse(GetMemoryStatsResponse {
+                                stats: mux.memory_stats(),
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::SetFocusedPane(SetFocusedPane { pane_id }) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            if let Some(client_id) = client_id {
+                                mux.record_focus_for_client(&client_id, pane_id);
+                            }
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::WriteToPane(WriteToPane { pane_id, data }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -377,15 +626,57 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::SetPaneAmbiguousWidth(SetPaneAmbiguousWidth {
+                pane_id,
+                ambiguous_are_wide,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            let mut config = TermConfig::new();
+                            if let Some(domain) = mux.get_domain(pane.domain_id()) {
+                                config = config.for_domain(domain.domain_name());
+                            }
+                            if let Some(wide) = ambiguous_are_wide {
+                                config = config.with_ambiguous_width(wide);
+                            }
+                            pane.set_config(Arc::new(config));
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::Resize(Resize {
                 containing_tab_id,
                 pane_id,
                 size,
             }) => {
+                let client_id = self.client_id.clone();
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
                             let mux = Mux::get().unwrap();
+                            if let Some(client_id) = client_id {
+                                // Size negotiation happens per-workspace: a client
+                                // that is looking at some other workspace doesn't
+                                // get to dictate the size of this tab.
+                                let workspace = mux.active_workspace_for_client(&client_id);
+                                let in_workspace = mux
+                                    .window_containing_tab(containing_tab_id)
+                                    .and_then(|window_id| mux.get_window(window_id))
+                                    .map(|window| window.get_workspace() == workspace)
+                                    .unwrap_or(true);
+                                if !in_workspace {
+                                    return Ok(Pdu::UnitResponse(UnitResponse {}));
+                                }
+                            }
                             let pane = mux
                                 .get_pane(pane_id)
                                 .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
@@ -458,16 +749,18 @@ impl SessionHandler {
 
             Pdu::Spawn(spawn) => {
                 let sender = self.to_write_tx.clone();
+                let client_id = self.client_id.clone();
                 spawn_into_main_thread(async move {
-                    schedule_domain_spawn(spawn, sender, send_response);
+                    schedule_domain_spawn(spawn, sender, client_id, send_response);
                 })
                 .detach();
             }
 
             Pdu::SpawnV2(spawn) => {
                 let sender = self.to_write_tx.clone();
+                let client_id = self.client_id.clone();
                 spawn_into_main_thread(async move {
-                    schedule_domain_spawn_v2(spawn, sender, send_response);
+                    schedule_domain_spawn_v2(spawn, sender, client_id, send_response);
                 })
                 .detach();
             }
@@ -480,6 +773,38 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::MovePane(MovePane { pane_id, target }) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let (tab_id, window_id) = match target {
+                                MovePaneTarget::Window(window_id) => {
+                                    (mux.move_pane_to_window(pane_id, window_id)?, window_id)
+                                }
+                                MovePaneTarget::NewWindow { workspace } => {
+                                    let workspace =
+                                        workspace.unwrap_or_else(|| match client_id.as_ref() {
+                                            Some(client_id) => {
+                                                mux.active_workspace_for_client(client_id)
+                                            }
+                                            None => mux.active_workspace(),
+                                        });
+                                    mux.move_pane_to_new_window(pane_id, Some(workspace))?
+                                }
+                            };
+                            Ok(Pdu::MovePaneResponse(MovePaneResponse {
+                                tab_id,
+                                window_id,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::GetPaneRenderChanges(GetPaneRenderChanges { pane_id, .. }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -540,6 +865,18 @@ impl SessionHandler {
                 })))
             }
 
+            Pdu::SetLogLevel(SetLogLevel { filters }) => {
+                catch(
+                    move || {
+                        env_bootstrap::ringlog::set_log_filters(&filters)
+                            .map_err(|err| anyhow!(err))?;
+                        log::info!("log filters adjusted: {}", filters);
+                        Ok(Pdu::UnitResponse(UnitResponse {}))
+                    },
+                    send_response,
+                );
+            }
+
             Pdu::GetTlsCreds(_) => {
                 catch(
                     move || {
@@ -554,6 +891,37 @@ impl SessionHandler {
                 );
             }
 
+            Pdu::GetFileInfo(GetFileInfo { pane_id, path }) => {
+                spawn_into_main_thread(async move {
+                    send_response(get_file_info(pane_id, path).await)
+                })
+                .detach();
+            }
+
+            Pdu::ReadFileChunk(ReadFileChunk {
+                pane_id,
+                path,
+                offset,
+                len,
+            }) => {
+                spawn_into_main_thread(async move {
+                    send_response(read_pane_file_chunk(pane_id, path, offset, len).await)
+                })
+                .detach();
+            }
+
+            Pdu::WriteFileChunk(WriteFileChunk {
+                pane_id,
+                path,
+                offset,
+                data,
+            }) => {
+                spawn_into_main_thread(async move {
+                    send_response(write_pane_file_chunk(pane_id, path, offset, data).await)
+                })
+                .detach();
+            }
+
             Pdu::Invalid { .. } => send_response(Err(anyhow!("invalid PDU {:?}", decoded.pdu))),
             Pdu::Pong { .. }
             | Pdu::ListPanesResponse { .. }
@@ -561,6 +929,12 @@ impl SessionHandler {
             | Pdu::NotifyAlert { .. }
             | Pdu::SetPalette { .. }
             | Pdu::SpawnResponse { .. }
+            | Pdu::FileInfoResponse { .. }
+            | Pdu::FileChunkResponse { .. }
+            | Pdu::MovePaneResponse { .. }
+            | Pdu::ListWorkspacesResponse { .. }
+            | Pdu::GetFocusedWindowResponse { .. }
+            | Pdu::GetMemoryStatsResponse { .. }
             | Pdu::GetPaneRenderChangesResponse { .. }
             | Pdu::UnitResponse { .. }
             | Pdu::LivenessResponse { .. }
@@ -576,23 +950,52 @@ impl SessionHandler {
     }
 }
 
+/// Returns true if `pdu` is a request that a read-only client is
+/// allowed to make; those are the requests that observe the state
+/// of the mux without sending input or changing the panes.
+fn permitted_when_read_only(pdu: &Pdu) -> bool {
+    matches!(required_permission(pdu), None | Some(MuxPermission::View))
+}
+
 // Dancing around a little bit here; we can't directly spawn_into_main_thread the domain_spawn
 // function below because the compiler thinks that all of its locals then need to be Send.
 // We need to shimmy through this helper to break that aspect of the compiler flow
 // analysis and allow things to compile.
-fn schedule_domain_spawn<SND>(spawn: Spawn, sender: PduSender, send_response: SND)
-where
+fn schedule_domain_spawn<SND>(
+    spawn: Spawn,
+    sender: PduSender,
+    client_id: Option<ClientId>,
+    send_response: SND,
+) where
     SND: Fn(anyhow::Result<Pdu>) + 'static,
 {
-    promise::spawn::spawn(async move { send_response(domain_spawn(spawn, sender).await) }).detach();
+    promise::spawn::spawn(async move {
+        send_response(domain_spawn(spawn, sender, client_id).await)
+    })
+    .detach();
 }
 
-fn schedule_domain_spawn_v2<SND>(spawn: SpawnV2, sender: PduSender, send_response: SND)
-where
+fn schedule_domain_spawn_v2<SND>(
+    spawn: SpawnV2,
+    sender: PduSender,
+    client_id: Option<ClientId>,
+    send_response: SND,
+) where
     SND: Fn(anyhow::Result<Pdu>) + 'static,
 {
-    promise::spawn::spawn(async move { send_response(domain_spawn_v2(spawn, sender).await) })
-        .detach();
+    promise::spawn::spawn(async move {
+        send_response(domain_spawn_v2(spawn, sender, client_id).await)
+    })
+    .detach();
+}
+
+/// New windows created on behalf of a client are placed into
+/// the workspace that the client is currently viewing
+fn workspace_for_client(client_id: &Option<ClientId>) -> Option<String> {
+    let mux = Mux::get().unwrap();
+    client_id
+        .as_ref()
+        .map(|client_id| mux.active_workspace_for_client(client_id))
 }
 
 fn schedule_split_pane<SND>(split: SplitPane, sender: PduSender, send_response: SND)
@@ -647,30 +1050,22 @@ async fn split_pane(split: SplitPane, sender: PduSender) -> anyhow::Result<Pdu>
         .ok_or_else(|| anyhow!("pane_id {} is invalid", pane_id))?;
     let term_config = current_pane.get_config();
 
-    let cwd = split.command_dir.or_else(|| {
-        mux.get_pane(pane_id)
-            .and_then(|pane| pane.get_current_working_dir())
-            .and_then(|url| {
-                percent_decode_str(url.path())
-                    .decode_utf8()
-                    .ok()
-                    .map(|path| path.into_owned())
-            })
-            .map(|path| {
-                // On Windows the file URI can produce a path like:
-                // `/C:\Users` which is valid in a file URI, but the leading slash
-                // is not liked by the windows file APIs, so we strip it off here.
-                let bytes = path.as_bytes();
-                if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
-                    path[1..].to_owned()
-                } else {
-                    path
-                }
-            })
-    });
+    let cwd = split.command_dir.or_else(|| pane_cwd(pane_id));
+    let encoding = split
+        .encoding
+        .as_deref()
+        .map(CharacterEncoding::for_label)
+        .transpose()?;
 
     let pane = domain
-        .split_pane(split.command, cwd, tab_id, split.pane_id, split.direction)
+        .split_pane(
+            split.command,
+            cwd,
+            encoding,
+            tab_id,
+            split.pane_id,
+            split.direction,
+        )
         .await?;
     let dims = pane.get_dimensions();
     let size = PtySize {
@@ -697,11 +1092,20 @@ async fn split_pane(split: SplitPane, sender: PduSender) -> anyhow::Result<Pdu>
     }))
 }
 
-async fn domain_spawn(spawn: Spawn, sender: PduSender) -> anyhow::Result<Pdu> {
+async fn domain_spawn(
+    spawn: Spawn,
+    sender: PduSender,
+    client_id: Option<ClientId>,
+) -> anyhow::Result<Pdu> {
     let mux = Mux::get().unwrap();
     let domain = mux
         .get_domain(spawn.domain_id)
         .ok_or_else(|| anyhow!("domain {} not found on this server", spawn.domain_id))?;
+    let encoding = spawn
+        .encoding
+        .as_deref()
+        .map(CharacterEncoding::for_label)
+        .transpose()?;
     let window_builder;
 
     let window_id = if let Some(window_id) = spawn.window_id {
@@ -709,12 +1113,18 @@ async fn domain_spawn(spawn: Spawn, sender: PduSender) -> anyhow::Result<Pdu> {
             .ok_or_else(|| anyhow!("window_id {} not found on this server", window_id))?;
         window_id
     } else {
-        window_builder = mux.new_empty_window();
+        window_builder = mux.new_empty_window(workspace_for_client(&client_id));
         *window_builder
     };
 
     let tab = domain
-        .spawn(spawn.size, spawn.command, spawn.command_dir, window_id)
+        .spawn(
+            spawn.size,
+            spawn.command,
+            spawn.command_dir,
+            encoding,
+            window_id,
+        )
         .await?;
 
     let pane = tab
@@ -727,6 +1137,16 @@ async fn domain_spawn(spawn: Spawn, sender: PduSender) -> anyhow::Result<Pdu> {
     });
     pane.set_clipboard(&clip);
 
+    if spawn.window_id.is_some() {
+        // Bring the new tab to the front of the existing window,
+        // as happens when spawning a tab from the gui
+        if let Some(mut window) = mux.get_window_mut(window_id) {
+            if let Some(idx) = window.idx_by_id(tab.tab_id()) {
+                window.save_and_then_set_active(idx);
+            }
+        }
+    }
+
     Ok::<Pdu, anyhow::Error>(Pdu::SpawnResponse(SpawnResponse {
         pane_id: pane.pane_id(),
         tab_id: tab.tab_id(),
@@ -735,7 +1155,11 @@ async fn domain_spawn(spawn: Spawn, sender: PduSender) -> anyhow::Result<Pdu> {
     }))
 }
 
-async fn domain_spawn_v2(spawn: SpawnV2, sender: PduSender) -> anyhow::Result<Pdu> {
+async fn domain_spawn_v2(
+    spawn: SpawnV2,
+    sender: PduSender,
+    client_id: Option<ClientId>,
+) -> anyhow::Result<Pdu> {
     let mux = Mux::get().unwrap();
 
     let domain = match spawn.domain {
@@ -745,6 +1169,11 @@ async fn domain_spawn_v2(spawn: SpawnV2, sender: PduSender) -> anyhow::Result<Pd
             .get_domain_by_name(&name)
             .ok_or_else(|| anyhow!("domain name {} is invalid", name))?,
     };
+    let encoding = spawn
+        .encoding
+        .as_deref()
+        .map(CharacterEncoding::for_label)
+        .transpose()?;
 
     let window_builder;
     let term_config;
@@ -766,12 +1195,12 @@ async fn domain_spawn_v2(spawn: SpawnV2, sender: PduSender) -> anyhow::Result<Pd
         (window_id, size)
     } else {
         term_config = None;
-        window_builder = mux.new_empty_window();
+        window_builder = mux.new_empty_window(workspace_for_client(&client_id));
         (*window_builder, spawn.size)
     };
 
     let tab = domain
-        .spawn(size, spawn.command, spawn.command_dir, window_id)
+        .spawn(size, spawn.command, spawn.command_dir, encoding, window_id)
         .await?;
 
     let pane = tab
@@ -782,6 +1211,13 @@ async fn domain_spawn_v2(spawn: SpawnV2, sender: PduSender) -> anyhow::Result<Pd
         pane.set_config(config);
     }
 
+    if spawn.profile.is_some() {
+        tab.set_profile(spawn.profile);
+        // The window was invalidated when the tab was added to it,
+        // before the profile was set
+        mux.notify(MuxNotification::WindowInvalidated(window_id));
+    }
+
     let clip: Arc<dyn Clipboard> = Arc::new(RemoteClipboard {
         pane_id: pane.pane_id(),
         sender,
@@ -795,3 +1231,167 @@ async fn domain_spawn_v2(spawn: SpawnV2, sender: PduSender) -> anyhow::Result<Pd
         size: tab.get_size(),
     }))
 }
+
+/// Returns the current working directory of `pane_id` as a local path
+fn pane_cwd(pane_id: PaneId) -> Option<String> {
+    let mux = Mux::get().unwrap();
+    mux.get_pane(pane_id)
+        .and_then(|pane| pane.get_current_working_dir())
+        .and_then(|url| {
+            percent_decode_str(url.path())
+                .decode_utf8()
+                .ok()
+                .map(|path| path.into_owned())
+        })
+        .map(|path| {
+            // On Windows the file URI can produce a path like:
+            // `/C:\Users` which is valid in a file URI, but the leading slash
+            // is not liked by the windows file APIs, so we strip it off here.
+            let bytes = path.as_bytes();
+            if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
+                path[1..].to_owned()
+            } else {
+                path
+            }
+        })
+}
+
+/// Resolves `path` for a file transfer involving `pane_id`;
+/// relative paths are taken to be relative to the working
+/// directory of that pane.
+fn resolve_pane_path(pane_id: PaneId, path: &str) -> anyhow::Result<PathBuf> {
+    let mux = Mux::get().unwrap();
+    mux.get_pane(pane_id)
+        .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+    let path = PathBuf::from(path);
+    if path.is_absolute() {
+        return Ok(path);
+    }
+    let cwd = pane_cwd(pane_id).ok_or_else(|| {
+        anyhow!(
+            "cannot resolve relative path {} because the working directory \
+             of pane {} is not known",
+            path.display(),
+            pane_id
+        )
+    })?;
+    Ok(PathBuf::from(cwd).join(path))
+}
+
+// The file IO for the requests below happens on a blocking thread
+// pool, so that a large chunk or a slow filesystem doesn't stall
+// the mux for everyone else; only resolving the path needs the mux.
+
+async fn get_file_info(pane_id: PaneId, path: String) -> anyhow::Result<Pdu> {
+    let path = resolve_pane_path(pane_id, &path)?;
+    smol::unblock(move || {
+        let size = match std::fs::metadata(&path) {
+            Ok(meta) => Some(meta.len()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err).with_context(|| format!("stat {}", path.display())),
+        };
+        Ok(Pdu::FileInfoResponse(FileInfoResponse { size }))
+    })
+    .await
+}
+
+async fn read_pane_file_chunk(
+    pane_id: PaneId,
+    path: String,
+    offset: u64,
+    len: u32,
+) -> anyhow::Result<Pdu> {
+    let path = resolve_pane_path(pane_id, &path)?;
+    smol::unblock(move || {
+        let (data, total_size) = read_file_chunk(&path, offset, len)
+            .with_context(|| format!("reading {}", path.display()))?;
+        Ok(Pdu::FileChunkResponse(FileChunkResponse { data, total_size }))
+    })
+    .await
+}
+
+async fn write_pane_file_chunk(
+    pane_id: PaneId,
+    path: String,
+    offset: u64,
+    data: Vec<u8>,
+) -> anyhow::Result<Pdu> {
+    let path = resolve_pane_path(pane_id, &path)?;
+    smol::unblock(move || {
+        write_file_chunk(&path, offset, &data)
+            .with_context(|| format!("writing {}", path.display()))?;
+        Ok(Pdu::UnitResponse(UnitResponse {}))
+    })
+    .await
+}
+
+/// The largest chunk that we'll return from a single ReadFileChunk request
+const MAX_FILE_CHUNK: u32 = 1024 * 1024;
+
+fn read_file_chunk(path: &Path, offset: u64, len: u32) -> anyhow::Result<(Vec<u8>, u64)> {
+    let mut file = std::fs::File::open(path)?;
+    let total_size = file.metadata()?.len();
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = vec![];
+    file.take(len.min(MAX_FILE_CHUNK) as u64)
+        .read_to_end(&mut data)?;
+    Ok((data, total_size))
+}
+
+fn write_file_chunk(path: &Path, offset: u64, data: &[u8]) -> anyhow::Result<()> {
+    let mut file = if offset == 0 {
+        std::fs::File::create(path)?
+    } else {
+        let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+        let size = file.metadata()?.len();
+        if offset > size {
+            anyhow::bail!(
+                "cannot write at offset {} because the file is only {} bytes long",
+                offset,
+                size
+            );
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        file
+    };
+    file.write_all(data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_only_rejects_input() {
+        assert!(!permitted_when_read_only(&Pdu::WriteToPane(WriteToPane {
+            pane_id: 0,
+            data: b"ls\n".to_vec(),
+        })));
+        assert!(!permitted_when_read_only(&Pdu::SendPaste(SendPaste {
+            pane_id: 0,
+            data: "ls".to_string(),
+        })));
+        assert!(!permitted_when_read_only(&Pdu::Resize(Resize {
+            containing_tab_id: 0,
+            pane_id: 0,
+            size: PtySize::default(),
+        })));
+        assert!(!permitted_when_read_only(&Pdu::KillPane(KillPane {
+            pane_id: 0
+        })));
+    }
+
+    #[test]
+    fn read_only_allows_queries() {
+        assert!(permitted_when_read_only(&Pdu::Ping(Ping {})));
+        assert!(permitted_when_read_only(&Pdu::ListPanes(ListPanes {})));
+        assert!(permitted_when_read_only(&Pdu::GetPaneRenderChanges(
+            GetPaneRenderChanges { pane_id: 0 }
+        )));
+        assert!(permitted_when_read_only(&Pdu::GetLines(GetLines {
+            pane_id: 0,
+            lines: vec![0..10],
+        })));
+    }
+}
@@ -0,0 +1,106 @@
+//! Support for the systemd socket activation protocol.
+//! When started by a service manager that has already bound our
+//! listening sockets, `LISTEN_PID` and `LISTEN_FDS` describe a set
+//! of descriptors that begin at fd 3.
+//! See <https://www.freedesktop.org/software/systemd/man/sd_listen_fds.html>
+use std::collections::VecDeque;
+use std::net::TcpListener;
+use wezterm_mux_server_impl::UnixListener;
+
+/// The listeners that were passed to us, partitioned by type.
+/// Unix listeners are assigned to the configured `unix_domains`
+/// and tcp listeners to the configured `tls_servers`, in order.
+#[derive(Default)]
+pub struct ActivatedListeners {
+    pub unix: VecDeque<UnixListener>,
+    pub tcp: VecDeque<TcpListener>,
+}
+
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: i32 = 3;
+
+impl ActivatedListeners {
+    #[cfg(windows)]
+    pub fn take() -> anyhow::Result<Self> {
+        Ok(Self::default())
+    }
+
+    /// Claims the descriptors passed via socket activation, if any.
+    /// The environment variables are removed so that they are not
+    /// inherited by the programs that we spawn.
+    #[cfg(unix)]
+    pub fn take() -> anyhow::Result<Self> {
+        use std::os::unix::io::FromRawFd;
+
+        let mut result = Self::default();
+
+        let pid = std::env::var("LISTEN_PID").ok();
+        let fds = std::env::var("LISTEN_FDS").ok();
+        std::env::remove_var("LISTEN_PID");
+        std::env::remove_var("LISTEN_FDS");
+        std::env::remove_var("LISTEN_FDNAMES");
+
+        let (pid, fds) = match (pid, fds) {
+            (Some(pid), Some(fds)) => (pid, fds),
+            _ => return Ok(result),
+        };
+
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            log::trace!("LISTEN_PID={} is not for us; ignoring LISTEN_FDS", pid);
+            return Ok(result);
+        }
+        let count: i32 = fds
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid LISTEN_FDS={}: {}", fds, e))?;
+
+        for fd in SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count {
+            unsafe {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+            match socket_family(fd)? {
+                libc::AF_UNIX => {
+                    log::info!("using socket activated unix listener fd {}", fd);
+                    result
+                        .unix
+                        .push_back(unsafe { UnixListener::from_raw_fd(fd) });
+                }
+                libc::AF_INET | libc::AF_INET6 => {
+                    log::info!("using socket activated tcp listener fd {}", fd);
+                    result
+                        .tcp
+                        .push_back(unsafe { TcpListener::from_raw_fd(fd) });
+                }
+                family => {
+                    log::error!(
+                        "ignoring socket activated fd {} with unsupported family {}",
+                        fd,
+                        family
+                    );
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(unix)]
+fn socket_family(fd: i32) -> anyhow::Result<libc::c_int> {
+    let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockname(
+            fd,
+            &mut addr as *mut libc::sockaddr_storage as *mut libc::sockaddr,
+            &mut len,
+        )
+    };
+    if res != 0 {
+        return Err(anyhow::anyhow!(
+            "getsockname on socket activated fd {}: {}",
+            fd,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(addr.ss_family as libc::c_int)
+}
@@ -8,9 +8,11 @@ use std::process::Command;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 use structopt::*;
 use wezterm_gui_subcommands::*;
 
+mod activation;
 mod daemonize;
 
 #[derive(Debug, StructOpt)]
@@ -77,6 +79,7 @@ fn run() -> anyhow::Result<()> {
         &opts.config_override,
         opts.skip_config,
     );
+    env_bootstrap::crash::install_crash_handler("wezterm-mux-server");
 
     #[cfg(unix)]
     {
@@ -163,8 +166,10 @@ fn run() -> anyhow::Result<()> {
     let domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
     let mux = Rc::new(mux::Mux::new(Some(domain.clone())));
     Mux::set_mux(&mux);
+    mux::exec::register_exec_domains()?;
 
     let executor = promise::spawn::SimpleExecutor::new();
+    mux::container::register_container_domains();
 
     spawn_listener().map_err(|e| {
         log::error!("problem spawning listeners: {:?}", e);
@@ -181,6 +186,10 @@ fn run() -> anyhow::Result<()> {
     })
     .detach();
 
+    if let Some(idle_secs) = configuration().exit_when_idle_for {
+        promise::spawn::spawn(exit_when_idle(Duration::from_secs(idle_secs))).detach();
+    }
+
     loop {
         executor.tick()?;
     }
@@ -193,14 +202,41 @@ async fn async_run(cmd: Option<CommandBuilder>) -> anyhow::Result<()> {
     domain.attach().await?;
 
     let config = config::configuration();
-    let window_id = mux.new_empty_window();
+    let window_id = mux.new_empty_window(None);
     let _tab = mux
         .default_domain()
-        .spawn(config.initial_size(), cmd, None, *window_id)
+        .spawn(config.initial_size(), cmd, None, None, *window_id)
         .await?;
     Ok(())
 }
 
+/// Terminates the server once there are no panes and no connected
+/// clients, and that has remained true for `idle_for`.
+async fn exit_when_idle(idle_for: Duration) {
+    let mut idle_since: Option<Instant> = None;
+    loop {
+        smol::Timer::after(Duration::from_secs(1)).await;
+
+        let mux = Mux::get().unwrap();
+        let idle = mux.is_empty()
+            && Activity::count() == 0
+            && wezterm_mux_server_impl::dispatch::connection_count() == 0;
+        if !idle {
+            idle_since = None;
+            continue;
+        }
+
+        let since = *idle_since.get_or_insert_with(Instant::now);
+        if since.elapsed() >= idle_for {
+            log::info!(
+                "no panes or clients for {:?}; exiting because exit_when_idle_for is set",
+                idle_for
+            );
+            std::process::exit(0);
+        }
+    }
+}
+
 fn terminate_with_error(err: anyhow::Error) -> ! {
     log::error!("{:#}; terminating", err);
     std::process::exit(1);
@@ -210,15 +246,38 @@ mod ossl;
 
 pub fn spawn_listener() -> anyhow::Result<()> {
     let config = configuration();
+    let mut activated = activation::ActivatedListeners::take()?;
+
     for unix_dom in &config.unix_domains {
-        let mut listener = wezterm_mux_server_impl::local::LocalListener::with_domain(unix_dom)?;
+        let mut listener = match activated.unix.pop_front() {
+            Some(listener) => {
+                wezterm_mux_server_impl::local::LocalListener::with_listener(listener, unix_dom)
+            }
+            None => wezterm_mux_server_impl::local::LocalListener::with_domain(unix_dom)?,
+        };
         thread::spawn(move || {
             listener.run();
         });
+
+        if let Some(mut control) =
+            wezterm_mux_server_impl::jsonrpc::JsonRpcListener::with_domain(unix_dom)?
+        {
+            thread::spawn(move || {
+                control.run();
+            });
+        }
     }
 
     for tls_server in &config.tls_servers {
-        ossl::spawn_tls_listener(tls_server)?;
+        ossl::spawn_tls_listener(tls_server, activated.tcp.pop_front())?;
+    }
+
+    if !activated.unix.is_empty() || !activated.tcp.is_empty() {
+        log::warn!(
+            "{} socket activated listener(s) were not used because there are \
+             more sockets than configured unix_domains and tls_servers",
+            activated.unix.len() + activated.tcp.len()
+        );
     }
 
     Ok(())
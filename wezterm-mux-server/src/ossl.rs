@@ -1,24 +1,31 @@
 use anyhow::{anyhow, Context, Error};
 use async_ossl::AsyncSslStream;
-use config::TlsDomainServer;
+use config::{MuxAccessRule, TlsDomainServer};
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslStream, SslVerifyMode};
 use openssl::x509::X509;
 use promise::spawn::spawn_into_main_thread;
 use std::net::TcpListener;
 use std::path::Path;
 use std::sync::Arc;
+use wezterm_mux_server_impl::access::AccessPolicy;
 use wezterm_mux_server_impl::PKI;
 
 struct OpenSSLNetListener {
     acceptor: Arc<SslAcceptor>,
     listener: TcpListener,
+    access_rules: Vec<MuxAccessRule>,
 }
 
 impl OpenSSLNetListener {
-    pub fn new(listener: TcpListener, acceptor: SslAcceptor) -> Self {
+    pub fn new(
+        listener: TcpListener,
+        acceptor: SslAcceptor,
+        access_rules: Vec<MuxAccessRule>,
+    ) -> Self {
         Self {
             listener,
             acceptor: Arc::new(acceptor),
+            access_rules,
         }
     }
 
@@ -31,7 +38,11 @@ impl OpenSSLNetListener {
     ///   user running this mux server instance, or must match
     ///   a special encoded prefix set up by a proprietary PKI
     ///   infrastructure in an environment used by the author.
-    fn verify_peer_cert<T>(stream: &SslStream<T>) -> anyhow::Result<()> {
+    /// * Alternatively, when `access_rules` are configured, the
+    ///   CN may name any user that is granted access by one of
+    ///   those rules.
+    /// Returns the access policy that applies to the peer.
+    fn verify_peer_cert<T>(&self, stream: &SslStream<T>) -> anyhow::Result<AccessPolicy> {
         let cert = stream
             .ssl()
             .peer_certificate()
@@ -51,22 +62,34 @@ impl OpenSSLNetListener {
                 cn_str,
                 wanted_unix_name
             );
-            Ok(())
-        } else {
-            // Some environments that are used by the author of this
-            // program encode the CN in the form `user:unixname/DATA`
-            let maybe_encoded = format!("user:{}/", wanted_unix_name);
-            if cn_str.starts_with(&maybe_encoded) {
-                log::trace!(
-                    "Peer certificate CN `{}` matches $USER `{}`",
-                    cn_str,
-                    wanted_unix_name
-                );
-                Ok(())
-            } else {
-                anyhow::bail!("CN `{}` did not match $USER `{}`", cn_str, wanted_unix_name);
-            }
+            return Ok(AccessPolicy::new(Some(cn_str), &self.access_rules));
+        }
+
+        // Some environments that are used by the author of this
+        // program encode the CN in the form `user:unixname/DATA`
+        let maybe_encoded = format!("user:{}/", wanted_unix_name);
+        if cn_str.starts_with(&maybe_encoded) {
+            log::trace!(
+                "Peer certificate CN `{}` matches $USER `{}`",
+                cn_str,
+                wanted_unix_name
+            );
+            return Ok(AccessPolicy::new(
+                Some(wanted_unix_name),
+                &self.access_rules,
+            ));
+        }
+
+        if self
+            .access_rules
+            .iter()
+            .any(|rule| rule.matches_user(&cn_str))
+        {
+            log::trace!("Peer certificate CN `{}` matches an access rule", cn_str);
+            return Ok(AccessPolicy::new(Some(cn_str), &self.access_rules));
         }
+
+        anyhow::bail!("CN `{}` did not match $USER `{}`", cn_str, wanted_unix_name);
     }
 
     fn run(&mut self) {
@@ -78,15 +101,19 @@ impl OpenSSLNetListener {
 
                     match acceptor.accept(stream) {
                         Ok(stream) => {
-                            if let Err(err) = Self::verify_peer_cert(&stream) {
-                                log::error!("problem with peer cert: {}", err);
-                                break;
-                            }
+                            let policy = match self.verify_peer_cert(&stream) {
+                                Ok(policy) => policy,
+                                Err(err) => {
+                                    log::error!("problem with peer cert: {}", err);
+                                    break;
+                                }
+                            };
                             spawn_into_main_thread(async move {
                                 log::error!("Making new AsyncSslStream");
-                                wezterm_mux_server_impl::dispatch::process(AsyncSslStream::new(
-                                    stream,
-                                ))
+                                wezterm_mux_server_impl::dispatch::process(
+                                    AsyncSslStream::new(stream),
+                                    policy,
+                                )
                                 .await
                                 .map_err(|e| {
                                     log::error!("process: {:?}", e);
@@ -109,7 +136,13 @@ impl OpenSSLNetListener {
     }
 }
 
-pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
+/// Serves `tls_server`.  If `listener` is provided (for example, it
+/// was passed to us via socket activation) then it is used rather than
+/// binding to the configured `bind_address`.
+pub fn spawn_tls_listener(
+    tls_server: &TlsDomainServer,
+    listener: Option<TcpListener>,
+) -> Result<(), Error> {
     openssl::init();
 
     let mut acceptor = SslAcceptor::mozilla_modern(SslMethod::tls())?;
@@ -172,15 +205,18 @@ pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
 
     log::error!("listening with TLS on {:?}", tls_server.bind_address);
 
-    let mut net_listener = OpenSSLNetListener::new(
-        TcpListener::bind(&tls_server.bind_address).with_context(|| {
+    let listener = match listener {
+        Some(listener) => listener,
+        None => TcpListener::bind(&tls_server.bind_address).with_context(|| {
             format!(
                 "error binding to mux_server_bind_address {}",
                 tls_server.bind_address,
             )
         })?,
-        acceptor,
-    );
+    };
+
+    let mut net_listener =
+        OpenSSLNetListener::new(listener, acceptor, tls_server.access_rules.clone());
     std::thread::spawn(move || {
         net_listener.run();
     });
@@ -0,0 +1,317 @@
+#![cfg(all(not(target_os = "macos"), not(windows), not(target_os = "freebsd")))]
+//! Implements the tray icon as a StatusNotifierItem, whose menu is
+//! exported using the dbusmenu protocol.
+//! See <https://www.freedesktop.org/wiki/Specifications/StatusNotifierItem/>
+
+use crate::{NumberedItem, Shared, TrayMenuItem};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::{Arc, Mutex};
+use zbus::{dbus_interface, dbus_proxy, fdo};
+use zvariant::{ObjectPath, OwnedValue, Structure, Value};
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+const ICON_NAME: &str = "org.wezfurlong.wezterm";
+
+#[dbus_proxy(
+    interface = "org.kde.StatusNotifierWatcher",
+    default_service = "org.kde.StatusNotifierWatcher",
+    default_path = "/StatusNotifierWatcher"
+)]
+trait StatusNotifierWatcher {
+    fn register_status_notifier_item(&self, service: &str) -> zbus::Result<()>;
+}
+
+struct StatusNotifierItem {
+    shared: Arc<Shared>,
+}
+
+#[dbus_interface(name = "org.kde.StatusNotifierItem")]
+impl StatusNotifierItem {
+    fn activate(&self, _x: i32, _y: i32) {
+        (self.shared.callback)(String::new());
+    }
+
+    fn secondary_activate(&self, _x: i32, _y: i32) {}
+
+    fn context_menu(&self, _x: i32, _y: i32) {}
+
+    fn scroll(&self, _delta: i32, _orientation: &str) {}
+
+    #[dbus_interface(property)]
+    fn category(&self) -> &str {
+        "ApplicationStatus"
+    }
+
+    #[dbus_interface(property)]
+    fn id(&self) -> &str {
+        "wezterm"
+    }
+
+    #[dbus_interface(property)]
+    fn title(&self) -> &str {
+        "WezTerm"
+    }
+
+    #[dbus_interface(property)]
+    fn status(&self) -> &str {
+        if self.shared.state.lock().unwrap().attention {
+            "NeedsAttention"
+        } else {
+            "Active"
+        }
+    }
+
+    #[dbus_interface(property)]
+    fn icon_name(&self) -> &str {
+        ICON_NAME
+    }
+
+    #[dbus_interface(property)]
+    fn attention_icon_name(&self) -> &str {
+        ICON_NAME
+    }
+
+    #[dbus_interface(property)]
+    fn tool_tip(&self) -> (String, Vec<(i32, i32, Vec<u8>)>, String, String) {
+        let tooltip = self.shared.state.lock().unwrap().tooltip.clone();
+        (
+            ICON_NAME.to_string(),
+            vec![],
+            "WezTerm".to_string(),
+            tooltip,
+        )
+    }
+
+    #[dbus_interface(property)]
+    fn item_is_menu(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn menu(&self) -> ObjectPath {
+        ObjectPath::from_str_unchecked(MENU_PATH)
+    }
+}
+
+struct DBusMenu {
+    shared: Arc<Shared>,
+    revision: Arc<Mutex<u32>>,
+}
+
+/// Returns the dbusmenu properties of `item`
+fn menu_item_properties(item: &TrayMenuItem) -> HashMap<String, OwnedValue> {
+    let mut props = HashMap::new();
+    if item.is_separator() {
+        props.insert("type".to_string(), Value::from("separator").into());
+        return props;
+    }
+    props.insert("label".to_string(), Value::from(item.label.clone()).into());
+    props.insert("enabled".to_string(), Value::from(item.enabled).into());
+    if !item.children.is_empty() {
+        props.insert(
+            "children-display".to_string(),
+            Value::from("submenu").into(),
+        );
+    }
+    props
+}
+
+/// Builds the (ia{sv}av) layout for `items`.  Their numbers are used
+/// as the dbusmenu ids; these start at 1, as id 0 is the root of the menu.
+fn menu_layout(items: &[NumberedItem]) -> Vec<OwnedValue> {
+    items
+        .iter()
+        .map(|numbered| {
+            let structure = Structure::default()
+                .add_field(numbered.number as i32)
+                .add_field(menu_item_properties(numbered.item))
+                .add_field(menu_layout(&numbered.children));
+            Value::from(structure).into()
+        })
+        .collect()
+}
+
+#[dbus_interface(name = "com.canonical.dbusmenu")]
+impl DBusMenu {
+    /// The whole menu is returned regardless of the requested parent
+    /// and depth, which is permitted by the protocol
+    fn get_layout(
+        &self,
+        _parent_id: i32,
+        _recursion_depth: i32,
+        _property_names: Vec<String>,
+    ) -> (u32, (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>)) {
+        let state = self.shared.state.lock().unwrap();
+        let children = menu_layout(&self.shared.number_menu(&state.menu));
+        let mut props = HashMap::new();
+        props.insert(
+            "children-display".to_string(),
+            Value::from("submenu").into(),
+        );
+        (*self.revision.lock().unwrap(), (0, props, children))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        _property_names: Vec<String>,
+    ) -> Vec<(i32, HashMap<String, OwnedValue>)> {
+        let state = self.shared.state.lock().unwrap();
+        let menu = self.shared.number_menu(&state.menu);
+        ids.into_iter()
+            .filter_map(|id| {
+                NumberedItem::find(&menu, id as u32)
+                    .map(|numbered| (id, menu_item_properties(numbered.item)))
+            })
+            .collect()
+    }
+
+    fn event(&self, id: i32, event_id: &str, _data: Value, _timestamp: u32) {
+        if event_id == "clicked" && id > 0 {
+            self.shared.activate(id as u32);
+        }
+    }
+
+    fn about_to_show(&self, _id: i32) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[dbus_interface(property)]
+    fn text_direction(&self) -> &str {
+        "ltr"
+    }
+
+    #[dbus_interface(property)]
+    fn status(&self) -> &str {
+        "normal"
+    }
+
+    #[dbus_interface(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+struct Server {
+    connection: zbus::Connection,
+    name: String,
+    revision: Arc<Mutex<u32>>,
+}
+
+lazy_static::lazy_static! {
+    static ref SERVER: Mutex<Option<Server>> = Mutex::new(None);
+}
+
+fn serve(shared: Arc<Shared>) -> Result<(), Box<dyn std::error::Error>> {
+    let connection = zbus::Connection::new_session()?;
+    let name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+    fdo::DBusProxy::new(&connection)?
+        .request_name(&name, fdo::RequestNameFlags::DoNotQueue.into())?;
+
+    let revision = Arc::new(Mutex::new(1));
+    let mut object_server = zbus::ObjectServer::new(&connection);
+    object_server.at(
+        &ITEM_PATH.try_into()?,
+        StatusNotifierItem {
+            shared: Arc::clone(&shared),
+        },
+    )?;
+    object_server.at(
+        &MENU_PATH.try_into()?,
+        DBusMenu {
+            shared,
+            revision: Arc::clone(&revision),
+        },
+    )?;
+
+    StatusNotifierWatcherProxy::new(&connection)?.register_status_notifier_item(&name)?;
+
+    SERVER.lock().unwrap().replace(Server {
+        connection: connection.clone(),
+        name,
+        revision,
+    });
+
+    loop {
+        if SERVER.lock().unwrap().is_none() {
+            break;
+        }
+        if let Err(err) = object_server.try_handle_next() {
+            log::error!("tray: {:#}", err);
+        }
+    }
+    Ok(())
+}
+
+pub fn show_tray(shared: &Arc<Shared>) -> Result<(), Box<dyn std::error::Error>> {
+    // Serve in a separate thread, as the StatusNotifierWatcher may
+    // not be running, and the object server blocks while waiting
+    // for requests.
+    let shared = Arc::clone(shared);
+    std::thread::spawn(move || {
+        if let Err(err) = serve(shared) {
+            log::error!("while serving tray icon: {:#}", err);
+        }
+    });
+    Ok(())
+}
+
+fn emit_signal<B>(server: &Server, path: &str, iface: &str, signal: &str, body: &B)
+where
+    B: serde::ser::Serialize + zvariant::Type,
+{
+    if let Err(err) = server
+        .connection
+        .emit_signal(None, path, iface, signal, body)
+    {
+        log::error!("tray: emitting {}.{}: {:#}", iface, signal, err);
+    }
+}
+
+pub fn update_tray(shared: &Arc<Shared>) {
+    let server = SERVER.lock().unwrap();
+    let server = match server.as_ref() {
+        Some(server) => server,
+        None => return,
+    };
+    let revision = {
+        let mut revision = server.revision.lock().unwrap();
+        *revision += 1;
+        *revision
+    };
+    let status = if shared.state.lock().unwrap().attention {
+        "NeedsAttention"
+    } else {
+        "Active"
+    };
+
+    const ITEM_IFACE: &str = "org.kde.StatusNotifierItem";
+    emit_signal(server, ITEM_PATH, ITEM_IFACE, "NewStatus", &(status));
+    emit_signal(server, ITEM_PATH, ITEM_IFACE, "NewToolTip", &());
+    emit_signal(
+        server,
+        MENU_PATH,
+        "com.canonical.dbusmenu",
+        "LayoutUpdated",
+        &(revision, 0i32),
+    );
+}
+
+pub fn hide_tray() {
+    // Releasing our name removes the item from the tray; the serving
+    // thread notices on its next request and stops.
+    if let Some(server) = SERVER.lock().unwrap().take() {
+        if let Err(err) = fdo::DBusProxy::new(&server.connection)
+            .and_then(|proxy| proxy.release_name(&server.name))
+        {
+            log::error!("tray: releasing {}: {:#}", server.name, err);
+        }
+    }
+}
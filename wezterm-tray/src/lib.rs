@@ -0,0 +1,298 @@
+//! A tray icon (a status item in the macOS menu bar, a notification
+//! area icon on Windows and a StatusNotifierItem elsewhere) with a menu
+//! of actions.  Selecting an item calls back into the application with
+//! the `id` of that item.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+mod dbus;
+mod macos;
+mod windows;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrayMenuItem {
+    /// Passed to the callback when the item is selected
+    pub id: String,
+    pub label: String,
+    pub enabled: bool,
+    /// When non-empty, the item opens a submenu rather than
+    /// being selectable itself
+    pub children: Vec<TrayMenuItem>,
+}
+
+impl TrayMenuItem {
+    pub fn new(id: impl Into<String>, label: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            label: label.into(),
+            enabled: true,
+            children: vec![],
+        }
+    }
+
+    pub fn submenu(label: impl Into<String>, children: Vec<TrayMenuItem>) -> Self {
+        Self {
+            id: String::new(),
+            label: label.into(),
+            enabled: !children.is_empty(),
+            children,
+        }
+    }
+
+    pub fn separator() -> Self {
+        Self::default()
+    }
+
+    pub fn is_separator(&self) -> bool {
+        self.label.is_empty()
+    }
+}
+
+/// A menu item along with the number by which the backends identify it
+pub struct NumberedItem<'a> {
+    pub number: u32,
+    pub item: &'a TrayMenuItem,
+    pub children: Vec<NumberedItem<'a>>,
+}
+
+impl<'a> NumberedItem<'a> {
+    /// Returns the item numbered `number` in `items` or their children
+    pub fn find<'b>(items: &'b [NumberedItem<'a>], number: u32) -> Option<&'b NumberedItem<'a>> {
+        for item in items {
+            if item.number == number {
+                return Some(item);
+            }
+            if let Some(found) = Self::find(&item.children, number) {
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
+/// Assigns the numbers by which the backends identify menu items.
+/// An item keeps its number when the menu is rebuilt, even if it moves,
+/// so that a number that a backend reports after the menu has changed
+/// refers to the same action, or to nothing if the item was removed.
+#[derive(Default)]
+struct ItemNumbers {
+    numbers: HashMap<String, u32>,
+    last: u32,
+}
+
+impl ItemNumbers {
+    /// Returns the number for the item identified by `key`, allocating
+    /// a new one if it hasn't been seen before.  Numbering starts at 1,
+    /// as the backends reserve 0.
+    fn number(&mut self, key: String) -> u32 {
+        let last = &mut self.last;
+        *self.numbers.entry(key).or_insert_with(|| {
+            *last += 1;
+            *last
+        })
+    }
+
+    fn number_items<'a>(
+        &mut self,
+        items: &'a [TrayMenuItem],
+        parent: &str,
+    ) -> Vec<NumberedItem<'a>> {
+        items
+            .iter()
+            .enumerate()
+            .map(|(position, item)| {
+                // Submenus and separators have no id; they can't be
+                // selected, so identifying them by position is enough
+                let key = if item.id.is_empty() {
+                    format!("{}/{}:{}", parent, position, item.label)
+                } else {
+                    format!("id:{}", item.id)
+                };
+                let children = self.number_items(&item.children, &key);
+                NumberedItem {
+                    number: self.number(key),
+                    item,
+                    children,
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrayState {
+    pub tooltip: String,
+    /// Requests the user's attention, for example because of activity
+    /// in a pane that isn't being looked at
+    pub attention: bool,
+    pub menu: Vec<TrayMenuItem>,
+}
+
+/// Called with the id of the selected menu item, or with an empty
+/// id when the icon itself is activated
+pub type TrayCallback = Arc<dyn Fn(String) + Send + Sync>;
+
+#[cfg(windows)]
+use crate::windows as backend;
+#[cfg(all(not(target_os = "macos"), not(windows), not(target_os = "freebsd")))]
+use dbus as backend;
+#[cfg(target_os = "macos")]
+use macos as backend;
+
+mod nop {
+    use super::*;
+
+    #[allow(dead_code)]
+    pub fn show_tray(_: &Arc<Shared>) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn update_tray(_: &Arc<Shared>) {}
+
+    #[allow(dead_code)]
+    pub fn hide_tray() {}
+}
+
+#[cfg(target_os = "freebsd")]
+use nop as backend;
+
+/// The state of the tray, which is shared with the backend
+pub struct Shared {
+    pub state: Mutex<TrayState>,
+    pub callback: TrayCallback,
+    numbers: Mutex<ItemNumbers>,
+}
+
+impl Shared {
+    fn new(state: TrayState, callback: TrayCallback) -> Self {
+        Self {
+            state: Mutex::new(state),
+            callback,
+            numbers: Mutex::new(ItemNumbers::default()),
+        }
+    }
+
+    /// Numbers the items of `menu`, which is the menu of the state
+    pub fn number_menu<'a>(&self, menu: &'a [TrayMenuItem]) -> Vec<NumberedItem<'a>> {
+        self.numbers.lock().unwrap().number_items(menu, "")
+    }
+
+    /// Invokes the callback for the item numbered `number`
+    pub fn activate(&self, number: u32) {
+        let id = {
+            let state = self.state.lock().unwrap();
+            let menu = self.number_menu(&state.menu);
+            match NumberedItem::find(&menu, number) {
+                Some(NumberedItem { item, .. }) if item.enabled && item.children.is_empty() => {
+                    item.id.clone()
+                }
+                _ => return,
+            }
+        };
+        (self.callback)(id);
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref TRAY: Mutex<Option<Arc<Shared>>> = Mutex::new(None);
+}
+
+/// Shows the tray icon, or updates it if it is already showing
+pub fn show(state: TrayState, callback: TrayCallback) {
+    let mut tray = TRAY.lock().unwrap();
+    if let Some(shared) = tray.as_ref() {
+        let changed = {
+            let mut current = shared.state.lock().unwrap();
+            if *current == state {
+                false
+            } else {
+                *current = state;
+                true
+            }
+        };
+        if changed {
+            backend::update_tray(shared);
+        }
+        return;
+    }
+
+    let shared = Arc::new(Shared::new(state, callback));
+    match backend::show_tray(&shared) {
+        Ok(()) => {
+            tray.replace(shared);
+        }
+        Err(err) => log::error!("Failed to show tray icon: {}", err),
+    }
+}
+
+/// Removes the tray icon
+pub fn hide() {
+    if TRAY.lock().unwrap().take().is_some() {
+        backend::hide_tray();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        let selected = Arc::new(Mutex::new(vec![]));
+        let shared = {
+            let selected = Arc::clone(&selected);
+            Shared::new(
+                TrayState::default(),
+                Arc::new(move |id| selected.lock().unwrap().push(id)),
+            )
+        };
+        let number_of = |menu: &[TrayMenuItem], id: &str| {
+            fn walk(items: &[NumberedItem], id: &str) -> Option<u32> {
+                items.iter().find_map(|numbered| {
+                    if numbered.item.id == id {
+                        Some(numbered.number)
+                    } else {
+                        walk(&numbered.children, id)
+                    }
+                })
+            }
+            walk(&shared.number_menu(menu), id).unwrap()
+        };
+
+        let before = vec![
+            TrayMenuItem::new("a", "A"),
+            TrayMenuItem::submenu(
+                "B",
+                vec![TrayMenuItem::new("b1", "B1"), TrayMenuItem::new("b2", "B2")],
+            ),
+            TrayMenuItem::separator(),
+            TrayMenuItem::new("c", "C"),
+        ];
+        let a = number_of(&before, "a");
+        let b2 = number_of(&before, "b2");
+        let c = number_of(&before, "c");
+        assert_ne!(a, b2);
+        assert_ne!(b2, c);
+
+        // Removing an item and adding another keeps the numbers
+        // of the items that remain
+        let after = vec![
+            TrayMenuItem::submenu("B", vec![TrayMenuItem::new("b2", "B2")]),
+            TrayMenuItem::separator(),
+            TrayMenuItem::new("c", "C"),
+            TrayMenuItem::new("d", "D"),
+        ];
+        assert_eq!(number_of(&after, "b2"), b2);
+        assert_eq!(number_of(&after, "c"), c);
+        let d = number_of(&after, "d");
+        assert!(d != a && d != b2 && d != c);
+
+        shared.state.lock().unwrap().menu = after;
+        shared.activate(c);
+        // The removed item doesn't select whatever replaced it
+        shared.activate(a);
+        shared.activate(b2);
+        assert_eq!(*selected.lock().unwrap(), vec!["c", "b2"]);
+    }
+}
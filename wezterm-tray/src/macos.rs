@@ -0,0 +1,164 @@
+#![cfg(target_os = "macos")]
+//! Implements the tray icon as a status item in the menu bar.
+//! This must be used from the main thread.
+
+use crate::{NumberedItem, Shared};
+use cocoa::base::*;
+use cocoa::foundation::{NSSize, NSString};
+use objc::declare::ClassDecl;
+use objc::rc::StrongPtr;
+use objc::runtime::{Class, Object, Sel};
+use objc::*;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+const TARGET_CLS_NAME: &str = "WezTermTrayTarget";
+
+/// Corresponds to NSVariableStatusItemLength
+const VARIABLE_LENGTH: f64 = -1.0;
+
+struct MacTray {
+    status_item: StrongPtr,
+    target: StrongPtr,
+    shared: Arc<Shared>,
+}
+
+thread_local! {
+    static TRAY: RefCell<Option<MacTray>> = RefCell::new(None);
+}
+
+struct TrayTarget {}
+
+impl TrayTarget {
+    fn get_class() -> &'static Class {
+        Class::get(TARGET_CLS_NAME).unwrap_or_else(Self::define_class)
+    }
+
+    fn define_class() -> &'static Class {
+        let mut cls = ClassDecl::new(TARGET_CLS_NAME, class!(NSObject))
+            .expect("Unable to register tray target class");
+
+        unsafe {
+            cls.add_method(
+                sel!(wezTrayAction:),
+                Self::action as extern "C" fn(&mut Object, Sel, id),
+            );
+        }
+
+        cls.register()
+    }
+
+    extern "C" fn action(_: &mut Object, _sel: Sel, sender: id) {
+        let number: NSInteger = unsafe { msg_send![sender, tag] };
+        let shared = TRAY.with(|tray| tray.borrow().as_ref().map(|t| Arc::clone(&t.shared)));
+        if let Some(shared) = shared {
+            shared.activate(number as u32);
+        }
+    }
+
+    fn alloc() -> StrongPtr {
+        let cls = Self::get_class();
+        unsafe { StrongPtr::new(msg_send![cls, new]) }
+    }
+}
+
+/// Convert a rust string to a cocoa string
+fn nsstring(s: &str) -> StrongPtr {
+    unsafe { StrongPtr::new(NSString::alloc(nil).init_str(s)) }
+}
+
+/// Builds a menu for `items`, tagging each item with its number
+unsafe fn build_menu(items: &[NumberedItem], target: id) -> id {
+    let menu: id = msg_send![class!(NSMenu), alloc];
+    let menu: id = msg_send![menu, initWithTitle: *nsstring("")];
+    let () = msg_send![menu, setAutoenablesItems: NO];
+
+    for numbered in items {
+        let item = numbered.item;
+        if item.is_separator() {
+            let separator: id = msg_send![class!(NSMenuItem), separatorItem];
+            let () = msg_send![menu, addItem: separator];
+            continue;
+        }
+
+        let menu_item: id = msg_send![class!(NSMenuItem), alloc];
+        let menu_item: id = msg_send![menu_item,
+            initWithTitle: *nsstring(&item.label)
+            action: sel!(wezTrayAction:)
+            keyEquivalent: *nsstring("")];
+        let () = msg_send![menu_item, setTarget: target];
+        let () = msg_send![menu_item, setTag: numbered.number as NSInteger];
+        let () = msg_send![menu_item, setEnabled: if item.enabled { YES } else { NO }];
+
+        if !item.children.is_empty() {
+            let submenu = build_menu(&numbered.children, target);
+            let () = msg_send![menu_item, setSubmenu: submenu];
+            let () = msg_send![submenu, release];
+        }
+
+        let () = msg_send![menu, addItem: menu_item];
+        let () = msg_send![menu_item, release];
+    }
+
+    menu
+}
+
+unsafe fn apply_state(tray: &MacTray) {
+    let state = tray.shared.state.lock().unwrap().clone();
+    let button: id = msg_send![*tray.status_item, button];
+
+    // Show a dot alongside the icon when attention is needed
+    let title = if state.attention { "●" } else { "" };
+    let () = msg_send![button, setTitle: *nsstring(title)];
+    let () = msg_send![button, setToolTip: *nsstring(&state.tooltip)];
+
+    let menu = build_menu(&tray.shared.number_menu(&state.menu), *tray.target);
+    let () = msg_send![*tray.status_item, setMenu: menu];
+    let () = msg_send![menu, release];
+}
+
+pub fn show_tray(shared: &Arc<Shared>) -> Result<(), Box<dyn std::error::Error>> {
+    unsafe {
+        let bar: id = msg_send![class!(NSStatusBar), systemStatusBar];
+        let status_item: id = msg_send![bar, statusItemWithLength: VARIABLE_LENGTH];
+        let status_item = StrongPtr::retain(status_item);
+
+        let app: id = msg_send![class!(NSApplication), sharedApplication];
+        let icon: id = msg_send![app, applicationIconImage];
+        if !icon.is_null() {
+            let icon: id = msg_send![icon, copy];
+            let () = msg_send![icon, setSize: NSSize::new(18., 18.)];
+            let button: id = msg_send![*status_item, button];
+            let () = msg_send![button, setImage: icon];
+            let () = msg_send![icon, release];
+        }
+
+        let tray = MacTray {
+            status_item,
+            target: TrayTarget::alloc(),
+            shared: Arc::clone(shared),
+        };
+        apply_state(&tray);
+        TRAY.with(|t| t.borrow_mut().replace(tray));
+    }
+    Ok(())
+}
+
+pub fn update_tray(_shared: &Arc<Shared>) {
+    TRAY.with(|tray| {
+        if let Some(tray) = tray.borrow().as_ref() {
+            unsafe { apply_state(tray) };
+        }
+    });
+}
+
+pub fn hide_tray() {
+    TRAY.with(|tray| {
+        if let Some(tray) = tray.borrow_mut().take() {
+            unsafe {
+                let bar: id = msg_send![class!(NSStatusBar), systemStatusBar];
+                let () = msg_send![bar, removeStatusItem: *tray.status_item];
+            }
+        }
+    });
+}
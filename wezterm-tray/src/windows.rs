@@ -0,0 +1,247 @@
+#![cfg(windows)]
+//! Implements the tray icon as a notification area icon.  The icon
+//! is owned by a message-only window that runs on its own thread.
+
+use crate::{NumberedItem, Shared};
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::ptr::null_mut;
+use std::sync::{Arc, Mutex};
+use winapi::shared::minwindef::*;
+use winapi::shared::windef::*;
+use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::shellapi::*;
+use winapi::um::winuser::*;
+
+/// Sent by the shell when the icon is clicked
+const WM_TRAY_CALLBACK: UINT = WM_APP + 1;
+/// Posted to the tray window when the state has changed
+const WM_TRAY_UPDATE: UINT = WM_APP + 2;
+const TRAY_ICON_ID: UINT = 1;
+/// The resource id of the application icon, as used by the window crate
+const ICON_RESOURCE: WORD = 0x101;
+
+struct Tray {
+    hwnd: usize,
+    shared: Arc<Shared>,
+}
+
+lazy_static::lazy_static! {
+    static ref TRAY: Mutex<Option<Tray>> = Mutex::new(None);
+}
+
+fn wide_string(s: &str) -> Vec<u16> {
+    OsStr::new(s)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect()
+}
+
+fn shared() -> Option<Arc<Shared>> {
+    TRAY.lock()
+        .unwrap()
+        .as_ref()
+        .map(|tray| Arc::clone(&tray.shared))
+}
+
+unsafe fn notify_icon_data(hwnd: HWND) -> NOTIFYICONDATAW {
+    let mut data: NOTIFYICONDATAW = std::mem::zeroed();
+    data.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+    data.hWnd = hwnd;
+    data.uID = TRAY_ICON_ID;
+    data
+}
+
+/// Adds or modifies the icon to reflect the current state
+unsafe fn apply_state(hwnd: HWND, shared: &Shared, message: DWORD) {
+    let state = shared.state.lock().unwrap().clone();
+    let mut data = notify_icon_data(hwnd);
+    data.uFlags = NIF_MESSAGE | NIF_ICON | NIF_TIP;
+    data.uCallbackMessage = WM_TRAY_CALLBACK;
+    data.hIcon = LoadIconW(
+        GetModuleHandleW(null_mut()),
+        MAKEINTRESOURCEW(ICON_RESOURCE),
+    );
+
+    let mut tooltip = if state.attention {
+        format!("WezTerm ●\n{}", state.tooltip)
+    } else {
+        format!("WezTerm\n{}", state.tooltip)
+    };
+    // Leave room for the terminating NUL
+    while tooltip.encode_utf16().count() >= data.szTip.len() {
+        tooltip.pop();
+    }
+    for (dest, c) in data.szTip.iter_mut().zip(wide_string(tooltip.trim_end())) {
+        *dest = c;
+    }
+
+    if Shell_NotifyIconW(message, &mut data) == 0 {
+        log::error!("tray: Shell_NotifyIconW failed");
+    }
+}
+
+/// Builds a popup menu for `items`, using the number of each item as
+/// its command id; these start at 1, as zero indicates that the menu
+/// was dismissed
+unsafe fn build_menu(items: &[NumberedItem]) -> HMENU {
+    let menu = CreatePopupMenu();
+    for numbered in items {
+        let item = numbered.item;
+        if item.is_separator() {
+            AppendMenuW(menu, MF_SEPARATOR, 0, null_mut());
+            continue;
+        }
+
+        let label = wide_string(&item.label);
+        let mut flags = MF_STRING;
+        if !item.enabled {
+            flags |= MF_GRAYED;
+        }
+        if item.children.is_empty() {
+            AppendMenuW(menu, flags, numbered.number as usize, label.as_ptr());
+        } else {
+            let submenu = build_menu(&numbered.children);
+            AppendMenuW(menu, flags | MF_POPUP, submenu as usize, label.as_ptr());
+        }
+    }
+    menu
+}
+
+unsafe fn show_menu(hwnd: HWND, shared: &Shared) {
+    let menu = {
+        let state = shared.state.lock().unwrap();
+        build_menu(&shared.number_menu(&state.menu))
+    };
+
+    let mut point = POINT { x: 0, y: 0 };
+    GetCursorPos(&mut point);
+    // The menu won't be dismissed when clicking elsewhere unless
+    // our window is in the foreground
+    SetForegroundWindow(hwnd);
+    let command = TrackPopupMenu(
+        menu,
+        TPM_RETURNCMD | TPM_NONOTIFY | TPM_RIGHTBUTTON,
+        point.x,
+        point.y,
+        0,
+        hwnd,
+        null_mut(),
+    );
+    PostMessageW(hwnd, WM_NULL, 0, 0);
+    DestroyMenu(menu);
+
+    if command > 0 {
+        shared.activate(command as u32);
+    }
+}
+
+unsafe extern "system" fn wnd_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match msg {
+        WM_TRAY_CALLBACK => {
+            if let Some(shared) = shared() {
+                match lparam as UINT {
+                    WM_LBUTTONUP => (shared.callback)(String::new()),
+                    WM_RBUTTONUP | WM_CONTEXTMENU => show_menu(hwnd, &shared),
+                    _ => {}
+                }
+            }
+            0
+        }
+        WM_TRAY_UPDATE => {
+            if let Some(shared) = shared() {
+                apply_state(hwnd, &shared, NIM_MODIFY);
+            }
+            0
+        }
+        WM_DESTROY => {
+            let mut data = notify_icon_data(hwnd);
+            Shell_NotifyIconW(NIM_DELETE, &mut data);
+            PostQuitMessage(0);
+            0
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+unsafe fn serve(shared: Arc<Shared>) -> Result<(), Box<dyn std::error::Error>> {
+    let class_name = wide_string("WezTermTray");
+    let h_inst = GetModuleHandleW(null_mut());
+    let class = WNDCLASSW {
+        style: 0,
+        lpfnWndProc: Some(wnd_proc),
+        cbClsExtra: 0,
+        cbWndExtra: 0,
+        hInstance: h_inst,
+        hIcon: null_mut(),
+        hCursor: null_mut(),
+        hbrBackground: null_mut(),
+        lpszMenuName: null_mut(),
+        lpszClassName: class_name.as_ptr(),
+    };
+    if RegisterClassW(&class) == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    let hwnd = CreateWindowExW(
+        0,
+        class_name.as_ptr(),
+        class_name.as_ptr(),
+        0,
+        0,
+        0,
+        0,
+        0,
+        HWND_MESSAGE,
+        null_mut(),
+        h_inst,
+        null_mut(),
+    );
+    if hwnd.is_null() {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    apply_state(hwnd, &shared, NIM_ADD);
+    TRAY.lock().unwrap().replace(Tray {
+        hwnd: hwnd as usize,
+        shared,
+    });
+
+    let mut msg: MSG = std::mem::zeroed();
+    while GetMessageW(&mut msg, null_mut(), 0, 0) > 0 {
+        TranslateMessage(&msg);
+        DispatchMessageW(&msg);
+    }
+    Ok(())
+}
+
+pub fn show_tray(shared: &Arc<Shared>) -> Result<(), Box<dyn std::error::Error>> {
+    let shared = Arc::clone(shared);
+    std::thread::spawn(move || {
+        if let Err(err) = unsafe { serve(shared) } {
+            log::error!("while serving tray icon: {:#}", err);
+        }
+    });
+    Ok(())
+}
+
+pub fn update_tray(_shared: &Arc<Shared>) {
+    if let Some(tray) = TRAY.lock().unwrap().as_ref() {
+        unsafe {
+            PostMessageW(tray.hwnd as HWND, WM_TRAY_UPDATE, 0, 0);
+        }
+    }
+}
+
+pub fn hide_tray() {
+    if let Some(tray) = TRAY.lock().unwrap().take() {
+        unsafe {
+            PostMessageW(tray.hwnd as HWND, WM_CLOSE, 0, 0);
+        }
+    }
+}
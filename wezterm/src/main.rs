@@ -16,6 +16,9 @@ use umask::UmaskSaver;
 use wezterm_client::client::{unix_connect_with_retry, Client};
 use wezterm_gui_subcommands::*;
 
+mod replay;
+mod transfer;
+
 //    let message = "; ❤ 😍🤢\n\x1b[91;mw00t\n\x1b[37;104;m bleet\x1b[0;m.";
 
 #[derive(Debug, StructOpt)]
@@ -52,7 +55,13 @@ struct Opt {
 
 #[derive(Debug, StructOpt, Clone)]
 enum SubCommand {
-    #[structopt(name = "start", about = "Start a front-end")]
+    // Everything that follows the program name is passed to the
+    // program, so that `wezterm start -e prog --flag` works
+    #[structopt(
+        name = "start",
+        about = "Start a front-end",
+        setting = structopt::clap::AppSettings::TrailingVarArg
+    )]
     Start(StartCommand),
 
     #[structopt(name = "ssh", about = "Establish an ssh session")]
@@ -79,6 +88,12 @@ enum SubCommand {
                  emitting an OSC 7 escape sequence"
     )]
     SetCwd(SetCwdCommand),
+
+    #[structopt(
+        name = "replay",
+        about = "Play back an asciicast recording in the terminal"
+    )]
+    Replay(ReplayCommand),
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -123,6 +138,11 @@ Outputs the pane-id for the newly created pane on success"
         #[structopt(long = "cwd", parse(from_os_str))]
         cwd: Option<OsString>,
 
+        /// Specify the character encoding used by the program,
+        /// such as `cp437` or `Shift_JIS`.  The default is UTF-8.
+        #[structopt(long = "encoding")]
+        encoding: Option<String>,
+
         /// Instead of executing your shell, run PROG.
         /// For example: `wezterm start -- bash -l` will spawn bash
         /// as if it were a login shell.
@@ -162,12 +182,122 @@ Outputs the pane-id for the newly created pane on success"
         #[structopt(long = "cwd", parse(from_os_str))]
         cwd: Option<OsString>,
 
+        /// Specify the character encoding used by the program,
+        /// such as `cp437` or `Shift_JIS`.  The default is UTF-8.
+        #[structopt(long = "encoding")]
+        encoding: Option<String>,
+
         /// Instead of executing your shell, run PROG.
         /// For example: `wezterm start -- bash -l` will spawn bash
         /// as if it were a login shell.
         #[structopt(parse(from_os_str))]
         prog: Vec<OsString>,
     },
+
+    #[structopt(
+        name = "move-pane",
+        about = "Move a pane into a new tab in another window, or into a new window
+Outputs the window-id that the pane was moved into on success"
+    )]
+    MovePane {
+        /// Specify the pane to move.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// Move the pane into a new tab in the specified window.
+        /// If neither this nor --to-workspace is specified, the pane
+        /// is moved into a new window in the current workspace.
+        #[structopt(long = "to-window")]
+        to_window: Option<WindowId>,
+
+        /// Move the pane into a new window in the named workspace
+        #[structopt(long = "to-workspace", conflicts_with = "to_window")]
+        to_workspace: Option<String>,
+    },
+
+    #[structopt(
+        name = "list-workspaces",
+        about = "list the workspaces, in their display order"
+    )]
+    ListWorkspaces,
+
+    #[structopt(name = "rename-workspace", about = "rename a workspace")]
+    RenameWorkspace {
+        /// The current name of the workspace
+        old: String,
+        /// The new name for the workspace
+        new: String,
+    },
+
+    #[structopt(
+        name = "close-workspace",
+        about = "close all of the windows in a workspace"
+    )]
+    CloseWorkspace {
+        /// The name of the workspace to close
+        workspace: String,
+
+        /// Close the workspace even if its panes are running programs
+        /// that would ordinarily prompt for confirmation
+        #[structopt(long = "force")]
+        force: bool,
+    },
+
+    #[structopt(
+        name = "set-workspace-order",
+        about = "set the order in which workspaces are listed.
+Workspaces that are not named are listed after those that are"
+    )]
+    SetWorkspaceOrder {
+        /// The names of the workspaces, in the desired order
+        #[structopt(required = true)]
+        workspaces: Vec<String>,
+    },
+
+    #[structopt(
+        name = "transfer",
+        about = "Copy a file to or from the host where a pane is running"
+    )]
+    Transfer {
+        /// The file to copy; either `local:PATH` or `pane:PANEID:PATH`.
+        /// Relative pane paths are resolved against the working
+        /// directory of that pane.
+        #[structopt(long = "from")]
+        from: transfer::Location,
+
+        /// Where to copy the file to; either `local:PATH` or
+        /// `pane:PANEID:PATH`
+        #[structopt(long = "to")]
+        to: transfer::Location,
+
+        /// If the destination already exists, assume that it is the
+        /// result of an interrupted transfer and continue from its
+        /// current size rather than starting over
+        #[structopt(long = "resume")]
+        resume: bool,
+    },
+
+    #[structopt(
+        name = "set-log-level",
+        about = "Adjust the log level of the wezterm instance at runtime"
+    )]
+    SetLogLevel {
+        /// One or more filters in the same syntax as `WEZTERM_LOG`,
+        /// for example `wezterm_font=debug` or `wezterm_client=trace`.
+        /// A bare level such as `info` sets the default level.
+        /// Filters replace any existing filter for the same module.
+        #[structopt(required = true)]
+        filters: Vec<String>,
+    },
+
+    #[structopt(
+        name = "memory-stats",
+        about = "Report the approximate memory used by the scrollback of each pane, \
+the glyph atlas, decoded images and the lua configuration"
+    )]
+    MemoryStats,
 }
 
 use termwiz::escape::osc::{
@@ -262,6 +392,35 @@ impl SetCwdCommand {
     }
 }
 
+#[derive(Debug, StructOpt, Clone)]
+struct ReplayCommand {
+    /// The playback speed; 2 plays twice as fast as the recording.
+    /// The speed can be doubled and halved during playback by
+    /// pressing `+` and `-`.  Press space to pause, `.` to step
+    /// through the output while paused and `q` to quit.
+    #[structopt(long = "speed", default_value = "1")]
+    speed: f64,
+
+    /// Limit pauses between output to this many seconds.
+    /// Defaults to the idle time limit stored in the recording,
+    /// if any.
+    #[structopt(long = "idle-time-limit")]
+    idle_time_limit: Option<f64>,
+
+    /// The asciicast file to play
+    #[structopt(parse(from_os_str))]
+    file_name: std::path::PathBuf,
+}
+
+impl ReplayCommand {
+    fn run(&self) -> anyhow::Result<()> {
+        if self.speed.is_nan() || self.speed <= 0. {
+            anyhow::bail!("--speed must be greater than zero");
+        }
+        replay::replay(&self.file_name, self.speed, self.idle_time_limit)
+    }
+}
+
 fn terminate_with_error_message(err: &str) -> ! {
     log::error!("{}; terminating", err);
     std::process::exit(1);
@@ -306,6 +465,7 @@ fn run() -> anyhow::Result<()> {
         | SubCommand::Connect(_) => delegate_to_gui(saver),
         SubCommand::ImageCat(cmd) => cmd.run(),
         SubCommand::SetCwd(cmd) => cmd.run(),
+        SubCommand::Replay(cmd) => cmd.run(),
         SubCommand::Cli(cli) => run_cli(config, cli),
     }
 }
@@ -383,6 +543,10 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                     name: "CWD".to_string(),
                     alignment: Alignment::Left,
                 },
+                Column {
+                    name: "WORKSPACE".to_string(),
+                    alignment: Alignment::Left,
+                },
             ];
             let mut data = vec![];
             let panes = client.list_panes().await?;
@@ -404,6 +568,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                                 .map(|url| url.url.as_str())
                                 .unwrap_or("")
                                 .to_string(),
+                            entry.workspace.clone(),
                         ]);
                     }
                     match cursor.preorder_next() {
@@ -420,6 +585,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             cwd,
             prog,
             horizontal,
+            encoding,
         } => {
             let pane_id: PaneId = match pane_id {
                 Some(p) => p,
@@ -449,12 +615,46 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                         Some(builder)
                     },
                     command_dir: cwd.and_then(|c| c.to_str().map(|s| s.to_string())),
+                    encoding,
+                    profile: None,
                 })
                 .await?;
 
             log::debug!("{:?}", spawned);
             println!("{}", spawned.pane_id);
         }
+        CliSubCommand::MovePane {
+            pane_id,
+            to_window,
+            to_workspace,
+        } => {
+            let pane_id: PaneId = match pane_id {
+                Some(p) => p,
+                None => std::env::var("WEZTERM_PANE")
+                    .map_err(|_| {
+                        anyhow!(
+                            "--pane-id was not specified and $WEZTERM_PANE
+                                    is not set in the environment"
+                        )
+                    })?
+                    .parse()?,
+            };
+
+            let moved = client
+                .move_pane(codec::MovePane {
+                    pane_id,
+                    target: match to_window {
+                        Some(window_id) => codec::MovePaneTarget::Window(window_id),
+                        None => codec::MovePaneTarget::NewWindow {
+                            workspace: to_workspace,
+                        },
+                    },
+                })
+                .await?;
+
+            log::debug!("{:?}", moved);
+            println!("{}", moved.window_id);
+        }
         CliSubCommand::SpawnCommand {
             cwd,
             prog,
@@ -462,6 +662,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             domain_name,
             window_id,
             new_window,
+            encoding,
         } => {
             let window_id = if new_window {
                 None
@@ -518,6 +719,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                     },
                     command_dir: cwd.and_then(|c| c.to_str().map(|s| s.to_string())),
                     size: config::configuration().initial_size(),
+                    encoding,
                 })
                 .await?;
 
@@ -559,6 +761,40 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             let creds = client.get_tls_creds().await?;
             codec::Pdu::GetTlsCredsResponse(creds).encode(std::io::stdout().lock(), 0)?;
         }
+        CliSubCommand::ListWorkspaces => {
+            let response = client.list_workspaces().await?;
+            for workspace in response.workspaces {
+                println!("{}", workspace);
+            }
+        }
+        CliSubCommand::RenameWorkspace { old, new } => {
+            client
+                .rename_workspace(codec::RenameWorkspace { old, new })
+                .await?;
+        }
+        CliSubCommand::CloseWorkspace { workspace, force } => {
+            client
+                .kill_workspace(codec::KillWorkspace { workspace, force })
+                .await?;
+        }
+        CliSubCommand::SetWorkspaceOrder { workspaces } => {
+            client
+                .set_workspace_order(codec::SetWorkspaceOrder { workspaces })
+                .await?;
+        }
+        CliSubCommand::Transfer { from, to, resume } => {
+            transfer::transfer(&client, &from, &to, resume).await?;
+        }
+        CliSubCommand::SetLogLevel { filters } => {
+            let filters = filters.join(",");
+            // Validate locally so that mistakes are reported clearly
+            env_bootstrap::ringlog::parse_filters(&filters).map_err(|err| anyhow!(err))?;
+            client.set_log_level(codec::SetLogLevel { filters }).await?;
+        }
+        CliSubCommand::MemoryStats => {
+            let response = client.get_memory_stats().await?;
+            print!("{}", mux::memory::format_stats(&response.stats));
+        }
     }
     Ok(())
 }
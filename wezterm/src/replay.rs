@@ -0,0 +1,108 @@
+//! Implements `wezterm replay`, which plays back an asciicast
+//! recording, such as one made by the StartRecording key assignment,
+//! in the current terminal.
+use anyhow::Context;
+use mux::recording::{read_cast, CastEvent};
+use std::io::{BufReader, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use termwiz::caps::Capabilities;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::terminal::{new_terminal, Terminal};
+
+const MIN_SPEED: f64 = 1. / 64.;
+const MAX_SPEED: f64 = 64.;
+
+pub fn replay(path: &Path, speed: f64, idle_time_limit: Option<f64>) -> anyhow::Result<()> {
+    let file = std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let (header, events) =
+        read_cast(BufReader::new(file)).with_context(|| format!("reading {}", path.display()))?;
+    let idle_time_limit = idle_time_limit.or(header.idle_time_limit);
+
+    let caps = Capabilities::new_from_env()?;
+    let mut term = new_terminal(caps)?;
+    let (cols, rows) = {
+        let size = term.get_screen_size()?;
+        (size.cols, size.rows)
+    };
+    if cols < header.width || rows < header.height {
+        eprint!(
+            "The recording is {}x{}, which is larger than this terminal ({}x{})\r\n",
+            header.width, header.height, cols, rows
+        );
+    }
+
+    term.set_raw_mode()?;
+    let result = play(&mut term, &events, speed, idle_time_limit);
+    term.set_cooked_mode()?;
+    result
+}
+
+/// Writes `events` to stdout with their original timing, adjusted
+/// by `speed`, while handling the playback keys:
+/// space pauses and resumes, `.` steps through the output while
+/// paused, `+` and `-` double and halve the speed, and `q` quits.
+fn play<T: Terminal>(
+    term: &mut T,
+    events: &[CastEvent],
+    mut speed: f64,
+    idle_time_limit: Option<f64>,
+) -> anyhow::Result<()> {
+    let mut out = std::io::stdout();
+    let mut prior_time = 0.;
+    let mut paused = false;
+
+    for event in events {
+        let mut delay = (event.time - prior_time).max(0.);
+        if let Some(limit) = idle_time_limit {
+            delay = delay.min(limit);
+        }
+        prior_time = event.time;
+
+        // While paused, this is the time that remained before the
+        // event was due when playback was paused
+        let mut remaining = Duration::from_secs_f64(delay / speed);
+        let mut deadline = Instant::now() + remaining;
+        loop {
+            let wait = if paused {
+                None
+            } else {
+                match deadline.checked_duration_since(Instant::now()) {
+                    Some(wait) if wait > Duration::from_millis(0) => Some(wait),
+                    _ => break,
+                }
+            };
+
+            let (key, modifiers) = match term.poll_input(wait)? {
+                Some(InputEvent::Key(KeyEvent { key, modifiers })) => (key, modifiers),
+                _ => continue,
+            };
+            match (key, modifiers) {
+                (KeyCode::Char('q'), _)
+                | (KeyCode::Escape, _)
+                | (KeyCode::Char('c'), Modifiers::CTRL) => return Ok(()),
+                (KeyCode::Char(' '), _) => {
+                    if paused {
+                        deadline = Instant::now() + remaining;
+                    } else {
+                        remaining = deadline.saturating_duration_since(Instant::now());
+                    }
+                    paused = !paused;
+                }
+                (KeyCode::Char('.'), _) if paused => break,
+                (KeyCode::Char('+'), _) | (KeyCode::Char('='), _) => {
+                    speed = (speed * 2.).min(MAX_SPEED);
+                }
+                (KeyCode::Char('-'), _) => {
+                    speed = (speed / 2.).max(MIN_SPEED);
+                }
+                _ => {}
+            }
+        }
+
+        out.write_all(event.data.as_bytes())?;
+        out.flush()?;
+    }
+
+    Ok(())
+}
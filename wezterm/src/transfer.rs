@@ -0,0 +1,163 @@
+//! Implements `wezterm cli transfer`, which copies files to and from
+//! the host where a mux pane is running, using the mux protocol.
+use anyhow::{anyhow, Context};
+use codec::{GetFileInfo, ReadFileChunk, WriteFileChunk};
+use mux::pane::PaneId;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use wezterm_client::client::Client;
+
+/// How much data to move in a single request
+const CHUNK_SIZE: u32 = 256 * 1024;
+
+/// One end of a transfer
+#[derive(Debug, Clone, PartialEq)]
+pub enum Location {
+    /// `local:PATH`; a file on this host
+    Local(PathBuf),
+    /// `pane:PANEID:PATH`; a file on the host where the pane is running.
+    /// Relative paths are resolved against the pane's working directory.
+    Pane(PaneId, String),
+}
+
+impl FromStr for Location {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if let Some(path) = s.strip_prefix("local:") {
+            return Ok(Self::Local(PathBuf::from(path)));
+        }
+        if let Some(rest) = s.strip_prefix("pane:") {
+            let mut fields = rest.splitn(2, ':');
+            let pane_id = fields
+                .next()
+                .unwrap_or("")
+                .parse()
+                .with_context(|| format!("invalid pane id in {}", s))?;
+            let path = fields
+                .next()
+                .ok_or_else(|| anyhow!("{} is missing a path", s))?;
+            return Ok(Self::Pane(pane_id, path.to_string()));
+        }
+        anyhow::bail!("{} must be of the form local:PATH or pane:PANEID:PATH", s);
+    }
+}
+
+impl Location {
+    async fn size(&self, client: &Client) -> anyhow::Result<Option<u64>> {
+        match self {
+            Self::Local(path) => match std::fs::metadata(path) {
+                Ok(meta) => Ok(Some(meta.len())),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err).with_context(|| format!("stat {}", path.display())),
+            },
+            Self::Pane(pane_id, path) => Ok(client
+                .get_file_info(GetFileInfo {
+                    pane_id: *pane_id,
+                    path: path.clone(),
+                })
+                .await?
+                .size),
+        }
+    }
+
+    async fn read_chunk(&self, client: &Client, offset: u64) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Local(path) => {
+                let mut file =
+                    File::open(path).with_context(|| format!("opening {}", path.display()))?;
+                file.seek(SeekFrom::Start(offset))?;
+                let mut data = vec![];
+                file.take(CHUNK_SIZE as u64).read_to_end(&mut data)?;
+                Ok(data)
+            }
+            Self::Pane(pane_id, path) => Ok(client
+                .read_file_chunk(ReadFileChunk {
+                    pane_id: *pane_id,
+                    path: path.clone(),
+                    offset,
+                    len: CHUNK_SIZE,
+                })
+                .await?
+                .data),
+        }
+    }
+
+    async fn write_chunk(&self, client: &Client, offset: u64, data: Vec<u8>) -> anyhow::Result<()> {
+        match self {
+            Self::Local(path) => {
+                let mut file = if offset == 0 {
+                    File::create(path)
+                } else {
+                    OpenOptions::new().write(true).open(path)
+                }
+                .with_context(|| format!("opening {}", path.display()))?;
+                file.seek(SeekFrom::Start(offset))?;
+                file.write_all(&data)?;
+                Ok(())
+            }
+            Self::Pane(pane_id, path) => {
+                client
+                    .write_file_chunk(WriteFileChunk {
+                        pane_id: *pane_id,
+                        path: path.clone(),
+                        offset,
+                        data,
+                    })
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Copies `from` to `to`.  If `resume` is true and `to` already exists
+/// and is no larger than `from`, the copy continues from the end of `to`
+/// rather than starting over.
+pub async fn transfer(
+    client: &Client,
+    from: &Location,
+    to: &Location,
+    resume: bool,
+) -> anyhow::Result<()> {
+    let total = from
+        .size(client)
+        .await?
+        .ok_or_else(|| anyhow!("{:?} does not exist", from))?;
+
+    let mut offset = 0;
+    if resume {
+        if let Some(existing) = to.size(client).await? {
+            if existing <= total {
+                offset = existing;
+            }
+        }
+    }
+    if offset > 0 {
+        log::info!("resuming transfer at offset {} of {}", offset, total);
+    }
+
+    if offset == 0 {
+        // Ensure that the destination is created (and truncated)
+        // even when the source is empty
+        to.write_chunk(client, 0, vec![]).await?;
+    }
+
+    while offset < total {
+        let data = from.read_chunk(client, offset).await?;
+        if data.is_empty() {
+            anyhow::bail!(
+                "{:?} was truncated to {} bytes during the transfer",
+                from,
+                offset
+            );
+        }
+        let len = data.len() as u64;
+        to.write_chunk(client, offset, data).await?;
+        offset += len;
+    }
+
+    Ok(())
+}
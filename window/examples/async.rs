@@ -72,7 +72,10 @@ impl MyWindow {
             }
             WindowEvent::AppearanceChanged(_)
             | WindowEvent::Notification(_)
-            | WindowEvent::FocusChanged(_) => {}
+            | WindowEvent::DroppedFile(_)
+            | WindowEvent::MouseLeave
+            | WindowEvent::FocusChanged(_)
+            | WindowEvent::AccessibilityActivate(_) => {}
         }
     }
 }
@@ -250,7 +250,10 @@ impl MyWindow {
             }
             WindowEvent::AppearanceChanged(_)
             | WindowEvent::Notification(_)
-            | WindowEvent::FocusChanged(_) => {}
+            | WindowEvent::DroppedFile(_)
+            | WindowEvent::FocusChanged(_)
+            | WindowEvent::MouseLeave
+            | WindowEvent::AccessibilityActivate(_) => {}
         }
     }
 }
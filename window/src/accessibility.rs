@@ -0,0 +1,72 @@
+//! A platform independent description of the contents of a window,
+//! which the platform specific code exposes to screen readers and
+//! other assistive technology.
+use crate::Rect;
+
+/// What an element of the accessibility tree represents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccessibilityRole {
+    /// The contents of a window
+    Window,
+    /// A list of tabs, such as the tab bar
+    TabList,
+    /// A tab in a `TabList`; it can be activated
+    Tab,
+    /// Terminal output; the value of the element is its text
+    Terminal,
+    /// A modal interface that is presented over the other elements,
+    /// such as a launcher or prompt
+    Dialog,
+}
+
+/// An element of the accessibility tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessibilityNode {
+    /// Identifies the element across updates, so that it can be
+    /// tracked by assistive technology while its content changes
+    pub id: u64,
+    pub role: AccessibilityRole,
+    /// A short description of the element, such as its title
+    pub label: String,
+    /// The content of the element, such as the text of a terminal
+    pub value: String,
+    /// The area that the element occupies, in pixels relative
+    /// to the top left of the window
+    pub rect: Rect,
+    /// Whether this is the active tab in a `TabList`
+    pub selected: bool,
+    /// Whether this element has the keyboard focus
+    pub focused: bool,
+    pub children: Vec<AccessibilityNode>,
+}
+
+impl AccessibilityNode {
+    pub fn new(id: u64, role: AccessibilityRole, label: String, rect: Rect) -> Self {
+        Self {
+            id,
+            role,
+            label,
+            value: String::new(),
+            rect,
+            selected: false,
+            focused: false,
+            children: vec![],
+        }
+    }
+
+    /// Returns the element with the keyboard focus, if any
+    pub fn focused_node(&self) -> Option<&Self> {
+        if self.focused {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.focused_node())
+    }
+
+    /// Calls `f` for this element and each of its descendants
+    pub fn walk<'a, F: FnMut(&'a Self)>(&'a self, f: &mut F) {
+        f(self);
+        for child in &self.children {
+            child.walk(f);
+        }
+    }
+}
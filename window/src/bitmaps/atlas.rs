@@ -28,6 +28,9 @@ where
 
     /// Dimensions of the texture
     side: usize,
+
+    /// The number of pixels that have been allocated
+    allocated_pixels: usize,
 }
 
 impl<T> Atlas<T>
@@ -52,6 +55,7 @@ where
             texture: Rc::clone(texture),
             side,
             allocator,
+            allocated_pixels: 0,
         })
     }
 
@@ -102,6 +106,7 @@ where
             );
 
             self.texture.write(rect, im);
+            self.allocated_pixels += (reserve_width * reserve_height) as usize;
 
             metrics::histogram!("window.atlas.allocate.success.rate", 1.);
             Ok(Sprite {
@@ -126,6 +131,11 @@ where
         self.side
     }
 
+    /// Returns the fraction of the texture that has been allocated
+    pub fn utilization(&self) -> f32 {
+        self.allocated_pixels as f32 / (self.side * self.side) as f32
+    }
+
     /// Zero out the texture, and forget all allocated regions
     pub fn clear(&mut self) {
         let iside = self.side as isize;
@@ -133,6 +143,7 @@ where
         let rect = Rect::new(Point::new(0, 0), Size::new(iside, iside));
         self.texture.write(rect, &image);
         self.allocator.clear();
+        self.allocated_pixels = 0;
     }
 }
 
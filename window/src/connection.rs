@@ -48,4 +48,11 @@ pub trait ConnectionOps {
 
     /// Perform the system beep/notification sound
     fn beep(&self) {}
+
+    /// Returns true if a screen reader is running, in which case
+    /// windows should keep their accessibility tree up to date.
+    /// Only implemented on macOS; elsewhere this is always false.
+    fn screen_reader_active(&self) -> bool {
+        false
+    }
 }
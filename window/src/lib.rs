@@ -2,8 +2,10 @@ use async_trait::async_trait;
 use bitflags::bitflags;
 use promise::Future;
 use std::any::Any;
+use std::path::PathBuf;
 use std::rc::Rc;
 use thiserror::Error;
+mod accessibility;
 pub mod bitmaps;
 pub use color_types as color;
 mod configuration;
@@ -25,6 +27,7 @@ pub fn default_dpi() -> f64 {
 
 mod egl;
 
+pub use accessibility::*;
 pub use bitmaps::{BitmapImage, Image};
 pub use connection::*;
 pub use glium;
@@ -148,9 +151,21 @@ pub enum WindowEvent {
 
     MouseEvent(MouseEvent),
 
+    /// Called when the mouse pointer leaves the window
+    MouseLeave,
+
     AppearanceChanged(Appearance),
 
+    /// Called when files are dragged from another application
+    /// and dropped onto the window
+    DroppedFile(Vec<PathBuf>),
+
     Notification(Box<dyn Any + Send + Sync>),
+
+    /// Called when assistive technology activates the element of
+    /// the accessibility tree with the specified id, such as when
+    /// a screen reader user selects a tab
+    AccessibilityActivate(u64),
 }
 
 pub struct WindowEventSender {
@@ -202,6 +217,13 @@ pub trait WindowOps {
     /// Hide a visible window
     fn hide(&self);
 
+    /// Bring the window to the front and give it the keyboard focus,
+    /// showing it if it is hidden.  Backends that don't permit a window
+    /// to raise itself just show it.
+    fn focus(&self) {
+        self.show();
+    }
+
     /// Schedule the window to be closed
     fn close(&self);
 
@@ -212,6 +234,15 @@ pub trait WindowOps {
     /// be repainted shortly
     fn invalidate(&self);
 
+    /// Invalidate the window and repaint it right away, without
+    /// waiting for frame rate throttling.  Only X11 throttles painting
+    /// itself; on Wayland the paint still waits for the compositor to
+    /// request the next frame, and macOS and Windows already repaint as
+    /// soon as possible, so this is the same as `invalidate` there.
+    fn invalidate_immediately(&self) {
+        self.invalidate();
+    }
+
     /// Change the titlebar text for the window
     fn set_title(&self, title: &str);
 
@@ -260,4 +291,12 @@ pub trait WindowOps {
     /// This may not be supported or respected by the desktop
     /// environment.
     fn set_resize_increments(&self, _x: u16, _y: u16) {}
+
+    /// Replaces the description of the window contents that is
+    /// exposed to assistive technology.  This is only implemented
+    /// for NSAccessibility on macOS.
+    fn set_accessibility_tree(&self, _tree: AccessibilityNode) {}
+
+    /// Asks the screen reader, if any, to speak `text`
+    fn announce(&self, _text: &str) {}
 }
@@ -0,0 +1,211 @@
+// let () = msg_send! is a common pattern for objc
+#![allow(clippy::let_unit_value)]
+
+//! Exposes the accessibility tree of a window to VoiceOver via the
+//! NSAccessibility API.  The window view stands for the root of the
+//! tree, and each of its descendants is represented by an instance of
+//! an NSAccessibilityElement subclass that is reused for as long as the
+//! tree contains a node with the same id, so that VoiceOver can keep
+//! track of an element while its content changes.
+
+use super::nsstring;
+use crate::{AccessibilityNode, AccessibilityRole};
+use cocoa::appkit::NSApp;
+use cocoa::base::*;
+use cocoa::foundation::{NSArray, NSPoint, NSRect, NSSize};
+use objc::declare::ClassDecl;
+use objc::rc::StrongPtr;
+use objc::runtime::{Class, Object, Sel};
+use objc::*;
+use std::collections::{HashMap, HashSet};
+
+const ELEMENT_CLS_NAME: &str = "WezTermAccessibilityElement";
+const WINDOW_ID_IVAR: &str = "wezterm_window_id";
+const NODE_ID_IVAR: &str = "wezterm_node_id";
+
+extern "C" {
+    static NSAccessibilityAnnouncementRequestedNotification: id;
+    static NSAccessibilityAnnouncementKey: id;
+    static NSAccessibilityValueChangedNotification: id;
+    static NSAccessibilityFocusedUIElementChangedNotification: id;
+    static NSAccessibilityLayoutChangedNotification: id;
+
+    fn NSAccessibilityPostNotification(element: id, notification: id);
+    fn NSAccessibilityPostNotificationWithUserInfo(element: id, notification: id, user_info: id);
+}
+
+fn role_name(role: AccessibilityRole) -> &'static str {
+    match role {
+        AccessibilityRole::Window => "AXGroup",
+        AccessibilityRole::TabList => "AXTabGroup",
+        AccessibilityRole::Tab => "AXRadioButton",
+        AccessibilityRole::Terminal => "AXTextArea",
+        AccessibilityRole::Dialog => "AXSheet",
+    }
+}
+
+/// Called when VoiceOver activates an element, such as a tab
+extern "C" fn perform_press(this: &mut Object, _sel: Sel) -> BOOL {
+    let (window_id, node_id) = unsafe {
+        (
+            *this.get_ivar::<usize>(WINDOW_ID_IVAR),
+            *this.get_ivar::<u64>(NODE_ID_IVAR),
+        )
+    };
+    super::window::accessibility_activate(window_id, node_id);
+    YES
+}
+
+fn element_class() -> &'static Class {
+    Class::get(ELEMENT_CLS_NAME).unwrap_or_else(|| {
+        let mut cls = ClassDecl::new(ELEMENT_CLS_NAME, class!(NSAccessibilityElement))
+            .expect("Unable to register AccessibilityElement class");
+        cls.add_ivar::<usize>(WINDOW_ID_IVAR);
+        cls.add_ivar::<u64>(NODE_ID_IVAR);
+        unsafe {
+            cls.add_method(
+                sel!(accessibilityPerformPress),
+                perform_press as extern "C" fn(&mut Object, Sel) -> BOOL,
+            );
+        }
+        cls.register()
+    })
+}
+
+/// The NSAccessibility elements that represent the accessibility
+/// tree of a window
+pub(crate) struct AccessibilityElements {
+    window_id: usize,
+    elements: HashMap<u64, StrongPtr>,
+    values: HashMap<u64, String>,
+    focused: Option<u64>,
+}
+
+impl AccessibilityElements {
+    pub fn new(window_id: usize) -> Self {
+        Self {
+            window_id,
+            elements: HashMap::new(),
+            values: HashMap::new(),
+            focused: None,
+        }
+    }
+
+    /// Updates the elements to match `tree`; `scale` is the ratio
+    /// of pixels to points for the view
+    pub fn update(&mut self, tree: &AccessibilityNode, view: id, scale: f64) {
+        let mut seen = HashSet::new();
+        unsafe {
+            let () = msg_send![view, setAccessibilityElement: YES];
+            let () = msg_send![view, setAccessibilityRole: *nsstring(role_name(tree.role))];
+            let () = msg_send![view, setAccessibilityLabel: *nsstring(&tree.label)];
+            let children = self.update_children(&tree.children, view, scale, &mut seen);
+            let () = msg_send![view, setAccessibilityChildren: children];
+        }
+
+        let removed = self.elements.len() != seen.len();
+        self.elements.retain(|node_id, _| seen.contains(node_id));
+        self.values.retain(|node_id, _| seen.contains(node_id));
+        if removed {
+            unsafe {
+                NSAccessibilityPostNotification(view, NSAccessibilityLayoutChangedNotification);
+            }
+        }
+
+        let focused = tree.focused_node().map(|node| node.id);
+        if focused != self.focused {
+            self.focused = focused;
+            if let Some(element) = focused.and_then(|node_id| self.elements.get(&node_id)) {
+                unsafe {
+                    NSAccessibilityPostNotification(
+                        **element,
+                        NSAccessibilityFocusedUIElementChangedNotification,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Updates the elements for `nodes`, returning an NSArray of them
+    unsafe fn update_children(
+        &mut self,
+        nodes: &[AccessibilityNode],
+        parent: id,
+        scale: f64,
+        seen: &mut HashSet<u64>,
+    ) -> id {
+        let mut children = vec![];
+        for node in nodes {
+            seen.insert(node.id);
+            let element = self.element(node.id);
+
+            let () = msg_send![element, setAccessibilityParent: parent];
+            let () = msg_send![element, setAccessibilityRole: *nsstring(role_name(node.role))];
+            if node.role == AccessibilityRole::Tab {
+                let () = msg_send![element, setAccessibilitySubrole: *nsstring("AXTabButton")];
+            }
+            let () = msg_send![element, setAccessibilityLabel: *nsstring(&node.label)];
+            let selected = if node.selected { YES } else { NO };
+            let () = msg_send![element, setAccessibilitySelected: selected];
+            let focused = if node.focused { YES } else { NO };
+            let () = msg_send![element, setAccessibilityFocused: focused];
+            // The view is flipped, so the origin is its top left,
+            // as it is for the tree
+            let frame = NSRect::new(
+                NSPoint::new(
+                    node.rect.origin.x as f64 / scale,
+                    node.rect.origin.y as f64 / scale,
+                ),
+                NSSize::new(
+                    node.rect.size.width as f64 / scale,
+                    node.rect.size.height as f64 / scale,
+                ),
+            );
+            let () = msg_send![element, setAccessibilityFrameInParentSpace: frame];
+
+            if self.values.get(&node.id) != Some(&node.value) {
+                let () = msg_send![element, setAccessibilityValue: *nsstring(&node.value)];
+                if self.values.insert(node.id, node.value.clone()).is_some() {
+                    NSAccessibilityPostNotification(
+                        element,
+                        NSAccessibilityValueChangedNotification,
+                    );
+                }
+            }
+
+            let grandchildren = self.update_children(&node.children, element, scale, seen);
+            let () = msg_send![element, setAccessibilityChildren: grandchildren];
+            children.push(element);
+        }
+        NSArray::arrayWithObjects(nil, &children)
+    }
+
+    /// Returns the element for the node with the specified id,
+    /// creating it if it doesn't exist yet
+    fn element(&mut self, node_id: u64) -> id {
+        let window_id = self.window_id;
+        **self.elements.entry(node_id).or_insert_with(|| unsafe {
+            let element: id = msg_send![element_class(), new];
+            (*element).set_ivar::<usize>(WINDOW_ID_IVAR, window_id);
+            (*element).set_ivar::<u64>(NODE_ID_IVAR, node_id);
+            StrongPtr::new(element)
+        })
+    }
+}
+
+/// Asks VoiceOver to speak `text`
+pub(crate) fn announce(text: &str) {
+    unsafe {
+        let text = nsstring(text);
+        let user_info: id = msg_send![
+            class!(NSDictionary),
+            dictionaryWithObject: *text
+            forKey: NSAccessibilityAnnouncementKey
+        ];
+        NSAccessibilityPostNotificationWithUserInfo(
+            NSApp(),
+            NSAccessibilityAnnouncementRequestedNotification,
+            user_info,
+        );
+    }
+}
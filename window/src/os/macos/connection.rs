@@ -7,7 +7,7 @@ use crate::connection::ConnectionOps;
 use crate::spawn::*;
 use crate::Appearance;
 use cocoa::appkit::{NSApp, NSApplication, NSApplicationActivationPolicyRegular};
-use cocoa::base::{id, nil};
+use cocoa::base::{id, nil, BOOL, NO, YES};
 use objc::*;
 use std::cell::RefCell;
 use std::collections::HashMap;
@@ -118,6 +118,22 @@ impl ConnectionOps for Connection {
             NSBeep();
         }
     }
+
+    fn screen_reader_active(&self) -> bool {
+        unsafe {
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            // isVoiceOverEnabled was added in macOS 10.13
+            let supported: BOOL = msg_send![
+                workspace,
+                respondsToSelector: sel!(isVoiceOverEnabled)
+            ];
+            if supported == NO {
+                return false;
+            }
+            let enabled: BOOL = msg_send![workspace, isVoiceOverEnabled];
+            enabled == YES
+        }
+    }
 }
 
 extern "C" {
@@ -3,6 +3,7 @@ use cocoa::foundation::NSString;
 use objc::rc::StrongPtr;
 use objc::runtime::Object;
 
+mod accessibility;
 pub mod bitmap;
 pub mod connection;
 pub mod window;
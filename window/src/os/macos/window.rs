@@ -1,20 +1,21 @@
 // let () = msg_send! is a common pattern for objc
 #![allow(clippy::let_unit_value)]
 
+use super::accessibility::{self, AccessibilityElements};
 use super::{nsstring, nsstring_to_str};
 use crate::connection::ConnectionOps;
 use crate::{
-    Clipboard, Connection, Dimensions, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseCursor,
-    MouseEvent, MouseEventKind, MousePress, Point, Rect, ScreenPoint, Size, WindowDecorations,
-    WindowEvent, WindowEventSender, WindowOps, WindowState,
+    AccessibilityNode, Clipboard, Connection, Dimensions, KeyCode, KeyEvent, Modifiers,
+    MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, PhysKeyCode, Point, Rect,
+    ScreenPoint, Size, WindowDecorations, WindowEvent, WindowEventSender, WindowOps, WindowState,
 };
 use anyhow::{anyhow, bail, ensure};
 use async_trait::async_trait;
 use cocoa::appkit::{
-    self, NSApplication, NSApplicationActivateIgnoringOtherApps, NSApplicationPresentationOptions,
-    NSBackingStoreBuffered, NSEvent, NSEventModifierFlags, NSOpenGLContext, NSOpenGLPixelFormat,
-    NSRunningApplication, NSScreen, NSView, NSViewHeightSizable, NSViewWidthSizable, NSWindow,
-    NSWindowStyleMask,
+    self, NSApp, NSApplication, NSApplicationActivateIgnoringOtherApps,
+    NSApplicationPresentationOptions, NSBackingStoreBuffered, NSEvent, NSEventModifierFlags,
+    NSOpenGLContext, NSOpenGLPixelFormat, NSRunningApplication, NSScreen, NSView,
+    NSViewHeightSizable, NSViewWidthSizable, NSWindow, NSWindowStyleMask,
 };
 use cocoa::base::*;
 use cocoa::foundation::{
@@ -36,6 +37,7 @@ use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 use std::any::Any;
 use std::cell::RefCell;
 use std::ffi::c_void;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::str::FromStr;
 use std::time::Instant;
@@ -43,6 +45,12 @@ use wezterm_font::FontConfiguration;
 
 #[allow(non_upper_case_globals)]
 const NSViewLayerContentsPlacementTopLeft: NSInteger = 11;
+#[allow(non_upper_case_globals)]
+const NSTrackingMouseEnteredAndExited: NSUInteger = 0x01;
+#[allow(non_upper_case_globals)]
+const NSTrackingActiveAlways: NSUInteger = 0x80;
+#[allow(non_upper_case_globals)]
+const NSTrackingInVisibleRect: NSUInteger = 0x200;
 
 fn round_away_from_zerof(value: f64) -> f64 {
     if value > 0. {
@@ -328,6 +336,7 @@ pub(crate) struct WindowInner {
     view: StrongPtr,
     window: StrongPtr,
     config: ConfigHandle,
+    accessibility: AccessibilityElements,
 }
 
 fn function_key_to_keycode(function_key: char) -> KeyCode {
@@ -466,9 +475,27 @@ impl Window {
                 setLayerContentsPlacement: NSViewLayerContentsPlacementTopLeft
             ];
 
+            // Deliver mouseExited: when the mouse leaves the view; the
+            // visible rect is tracked as the view is resized
+            let tracking_area: id = msg_send![class!(NSTrackingArea), alloc];
+            let tracking_area: id = msg_send![
+                tracking_area,
+                initWithRect: rect
+                options: NSTrackingMouseEnteredAndExited
+                    | NSTrackingActiveAlways
+                    | NSTrackingInVisibleRect
+                owner: *view
+                userInfo: nil
+            ];
+            let () = msg_send![*view, addTrackingArea: tracking_area];
+            let () = msg_send![tracking_area, release];
+
             window.setContentView_(*view);
             window.setDelegate_(*view);
 
+            let types = NSArray::arrayWithObject(nil, appkit::NSFilenamesPboardType);
+            let () = msg_send![*view, registerForDraggedTypes: types];
+
             let frame = NSView::frame(*view);
             let backing_frame = NSView::convertRectToBacking(*view, frame);
             let width = backing_frame.size.width;
@@ -484,6 +511,7 @@ impl Window {
                 window,
                 view,
                 config: config.clone(),
+                accessibility: AccessibilityElements::new(window_id),
             }));
             inner.borrow_mut().window.replace(weak_window);
             conn.windows
@@ -657,6 +685,32 @@ impl WindowOps for Window {
             Ok(())
         });
     }
+
+    fn set_accessibility_tree(&self, tree: AccessibilityNode) {
+        Connection::with_window_inner(self.id, move |inner| {
+            inner.set_accessibility_tree(&tree);
+            Ok(())
+        });
+    }
+
+    fn announce(&self, text: &str) {
+        accessibility::announce(text);
+    }
+}
+
+/// Called when VoiceOver activates an element of the accessibility
+/// tree of the window
+pub(crate) fn accessibility_activate(window_id: usize, node_id: u64) {
+    Connection::with_window_inner(window_id, move |inner| {
+        if let Some(window_view) = WindowView::get_this(unsafe { &**inner.view }) {
+            window_view
+                .inner
+                .borrow_mut()
+                .events
+                .dispatch(WindowEvent::AccessibilityActivate(node_id));
+        }
+        Ok(())
+    });
 }
 
 /// Convert from a macOS screen coordinate with the origin in the bottom left
@@ -923,6 +977,15 @@ impl WindowInner {
         }
     }
 
+    fn set_accessibility_tree(&mut self, tree: &AccessibilityNode) {
+        unsafe {
+            let frame = NSView::frame(*self.view as *mut _);
+            let backing_frame = NSView::convertRectToBacking(*self.view as *mut _, frame);
+            let scale = backing_frame.size.width / frame.size.width;
+            self.accessibility.update(tree, *self.view, scale);
+        }
+    }
+
     fn set_text_cursor_position(&mut self, cursor: Rect) {
         if let Some(window_view) = WindowView::get_this(unsafe { &**self.view }) {
             window_view.inner.borrow_mut().text_cursor_position = cursor;
@@ -967,6 +1030,41 @@ impl WindowInner {
         }
         self.update_window_shadow();
         self.apply_decorations();
+        unsafe {
+            apply_color_space(*self.window, *self.view, &self.config);
+        }
+    }
+}
+
+/// When color management is enabled, tags the content of the window
+/// as sRGB so that the system converts the colors that we render into
+/// the color profile of the display; wide gamut displays would otherwise
+/// show them over-saturated.  When disabled, the colorspace of the screen
+/// is used, which sends the colors to the display without conversion.
+unsafe fn apply_color_space(window: id, view: id, config: &ConfigHandle) {
+    let color_space: id = if config.enable_color_management {
+        msg_send![class!(NSColorSpace), sRGBColorSpace]
+    } else {
+        let screen: id = msg_send![window, screen];
+        if screen.is_null() {
+            return;
+        }
+        msg_send![screen, colorSpace]
+    };
+    if color_space.is_null() {
+        return;
+    }
+    let () = msg_send![window, setColorSpace: color_space];
+
+    // Layer backed views, such as those used when rendering via
+    // Metal, don't inherit the colorspace of the window
+    let layer: id = msg_send![view, layer];
+    if !layer.is_null() {
+        let responds: BOOL = msg_send![layer, respondsToSelector: sel!(setColorspace:)];
+        if responds == YES {
+            let cg_color_space: *mut c_void = msg_send![color_space, CGColorSpace];
+            let () = msg_send![layer, setColorspace: cg_color_space];
+        }
     }
 }
 
@@ -1229,6 +1327,67 @@ fn decode_mouse_buttons(mask: u64) -> MouseButtons {
     buttons
 }
 
+/// Maps a macOS virtual key code, which identifies the position of
+/// the key rather than its meaning, to the physical key
+fn phys_code_from_virtual_key(virtual_key: u16) -> Option<PhysKeyCode> {
+    use super::keycodes::*;
+    Some(match virtual_key {
+        kVK_ANSI_A => PhysKeyCode::A,
+        kVK_ANSI_B => PhysKeyCode::B,
+        kVK_ANSI_C => PhysKeyCode::C,
+        kVK_ANSI_D => PhysKeyCode::D,
+        kVK_ANSI_E => PhysKeyCode::E,
+        kVK_ANSI_F => PhysKeyCode::F,
+        kVK_ANSI_G => PhysKeyCode::G,
+        kVK_ANSI_H => PhysKeyCode::H,
+        kVK_ANSI_I => PhysKeyCode::I,
+        kVK_ANSI_J => PhysKeyCode::J,
+        kVK_ANSI_K => PhysKeyCode::K,
+        kVK_ANSI_L => PhysKeyCode::L,
+        kVK_ANSI_M => PhysKeyCode::M,
+        kVK_ANSI_N => PhysKeyCode::N,
+        kVK_ANSI_O => PhysKeyCode::O,
+        kVK_ANSI_P => PhysKeyCode::P,
+        kVK_ANSI_Q => PhysKeyCode::Q,
+        kVK_ANSI_R => PhysKeyCode::R,
+        kVK_ANSI_S => PhysKeyCode::S,
+        kVK_ANSI_T => PhysKeyCode::T,
+        kVK_ANSI_U => PhysKeyCode::U,
+        kVK_ANSI_V => PhysKeyCode::V,
+        kVK_ANSI_W => PhysKeyCode::W,
+        kVK_ANSI_X => PhysKeyCode::X,
+        kVK_ANSI_Y => PhysKeyCode::Y,
+        kVK_ANSI_Z => PhysKeyCode::Z,
+        kVK_ANSI_0 => PhysKeyCode::K0,
+        kVK_ANSI_1 => PhysKeyCode::K1,
+        kVK_ANSI_2 => PhysKeyCode::K2,
+        kVK_ANSI_3 => PhysKeyCode::K3,
+        kVK_ANSI_4 => PhysKeyCode::K4,
+        kVK_ANSI_5 => PhysKeyCode::K5,
+        kVK_ANSI_6 => PhysKeyCode::K6,
+        kVK_ANSI_7 => PhysKeyCode::K7,
+        kVK_ANSI_8 => PhysKeyCode::K8,
+        kVK_ANSI_9 => PhysKeyCode::K9,
+        kVK_ANSI_Minus => PhysKeyCode::Minus,
+        kVK_ANSI_Equal => PhysKeyCode::Equal,
+        kVK_ANSI_LeftBracket => PhysKeyCode::LeftBracket,
+        kVK_ANSI_RightBracket => PhysKeyCode::RightBracket,
+        kVK_ANSI_Backslash => PhysKeyCode::Backslash,
+        kVK_ANSI_Semicolon => PhysKeyCode::Semicolon,
+        kVK_ANSI_Quote => PhysKeyCode::Quote,
+        kVK_ANSI_Grave => PhysKeyCode::Grave,
+        kVK_ANSI_Comma => PhysKeyCode::Comma,
+        kVK_ANSI_Period => PhysKeyCode::Period,
+        kVK_ANSI_Slash => PhysKeyCode::Slash,
+        kVK_Space => PhysKeyCode::Space,
+        kVK_Return => PhysKeyCode::Return,
+        kVK_Tab => PhysKeyCode::Tab,
+        kVK_Delete => PhysKeyCode::Backspace,
+        kVK_Escape => PhysKeyCode::Escape,
+        _ => return None,
+    })
+}
+
 fn key_modifiers(flags: NSEventModifierFlags) -> Modifiers {
     let mut mods = Modifiers::NONE;
 
@@ -1347,6 +1506,7 @@ impl WindowView {
             modifiers,
             raw_modifiers: Modifiers::NONE,
             raw_code: None,
+            phys_code: None,
             repeat_count: 1,
             key_is_down: true,
         }
@@ -1388,6 +1548,7 @@ impl WindowView {
                 modifiers: Modifiers::NONE,
                 raw_modifiers: Modifiers::NONE,
                 raw_code: None,
+                phys_code: None,
                 repeat_count: 1,
                 key_is_down,
             }
@@ -1486,6 +1647,21 @@ impl WindowView {
         YES
     }
 
+    /// Defers bringing the window to the front when it is clicked,
+    /// so that mouse_down can prevent that from happening at all
+    /// when raise_window_on_click is disabled
+    extern "C" fn should_delay_window_ordering(
+        _this: &mut Object,
+        _sel: Sel,
+        _nsevent: id,
+    ) -> BOOL {
+        if config::configuration().raise_window_on_click {
+            NO
+        } else {
+            YES
+        }
+    }
+
     extern "C" fn accepts_first_responder(_this: &mut Object, _sel: Sel) -> BOOL {
         YES
     }
@@ -1534,6 +1710,34 @@ impl WindowView {
         }
     }
 
+    extern "C" fn dragging_entered(_this: &mut Object, _sel: Sel, _sender: id) -> NSUInteger {
+        // NSDragOperationCopy
+        1
+    }
+
+    extern "C" fn perform_drag_operation(this: &mut Object, _sel: Sel, sender: id) -> BOOL {
+        if let Some(this) = Self::get_this(this) {
+            let paths = unsafe {
+                let pasteboard: id = msg_send![sender, draggingPasteboard];
+                let filenames: id =
+                    msg_send![pasteboard, propertyListForType: appkit::NSFilenamesPboardType];
+                if filenames.is_null() {
+                    return NO;
+                }
+                (0..filenames.count())
+                    .map(|idx| PathBuf::from(nsstring_to_str(filenames.objectAtIndex(idx))))
+                    .collect::<Vec<_>>()
+            };
+            this.inner
+                .borrow_mut()
+                .events
+                .dispatch(WindowEvent::DroppedFile(paths));
+            YES
+        } else {
+            NO
+        }
+    }
+
     // Switch the coordinate system to have 0,0 in the top left
     extern "C" fn is_flipped(_this: &Object, _sel: Sel) -> BOOL {
         YES
@@ -1597,11 +1801,23 @@ impl WindowView {
         }
     }
 
+    extern "C" fn mouse_exited(this: &mut Object, _sel: Sel, _nsevent: id) {
+        if let Some(myself) = Self::get_this(this) {
+            let mut inner = myself.inner.borrow_mut();
+            inner.events.dispatch(WindowEvent::MouseLeave);
+        }
+    }
+
     extern "C" fn mouse_up(this: &mut Object, _sel: Sel, nsevent: id) {
         Self::mouse_common(this, nsevent, MouseEventKind::Release(MousePress::Left));
     }
 
     extern "C" fn mouse_down(this: &mut Object, _sel: Sel, nsevent: id) {
+        if !config::configuration().raise_window_on_click {
+            unsafe {
+                let () = msg_send![NSApp(), preventWindowOrdering];
+            }
+        }
         Self::mouse_common(this, nsevent, MouseEventKind::Press(MousePress::Left));
     }
     extern "C" fn right_mouse_up(this: &mut Object, _sel: Sel, nsevent: id) {
@@ -1612,10 +1828,8 @@ impl WindowView {
         // Safety: We know this is an button event
         unsafe {
             let button_number = NSEvent::buttonNumber(nsevent);
-            // Button 2 is the middle mouse button (scroll wheel)
-            // but is the dedicated middle mouse button on 4 button mouses
-            if button_number == 2 {
-                Self::mouse_common(this, nsevent, MouseEventKind::Release(MousePress::Middle));
+            if let Some(button) = Self::other_mouse_button(button_number) {
+                Self::mouse_common(this, nsevent, MouseEventKind::Release(button));
             }
         }
     }
@@ -1691,13 +1905,23 @@ impl WindowView {
         // Safety: See `other_mouse_up`
         unsafe {
             let button_number = NSEvent::buttonNumber(nsevent);
-            // See `other_mouse_up`
-            if button_number == 2 {
-                Self::mouse_common(this, nsevent, MouseEventKind::Press(MousePress::Middle));
+            if let Some(button) = Self::other_mouse_button(button_number) {
+                Self::mouse_common(this, nsevent, MouseEventKind::Press(button));
             }
         }
     }
 
+    fn other_mouse_button(button_number: NSInteger) -> Option<MousePress> {
+        match button_number {
+            // Button 2 is the middle mouse button (scroll wheel)
+            // but is the dedicated middle mouse button on 4 button mouses
+            2 => Some(MousePress::Middle),
+            3 => Some(MousePress::Back),
+            4 => Some(MousePress::Forward),
+            _ => None,
+        }
+    }
+
     extern "C" fn mouse_moved_or_dragged(this: &mut Object, _sel: Sel, nsevent: id) {
         Self::mouse_common(this, nsevent, MouseEventKind::Move);
     }
@@ -1881,6 +2105,7 @@ impl WindowView {
                 modifiers,
                 raw_modifiers,
                 raw_code: Some(virtual_key as u32),
+                phys_code: phys_code_from_virtual_key(virtual_key as u16),
                 repeat_count: 1,
                 key_is_down,
             }
@@ -1931,6 +2156,24 @@ impl WindowView {
         }
     }
 
+    extern "C" fn did_change_screen_profile(this: &mut Object, _sel: Sel, _notification: id) {
+        log::trace!("did_change_screen_profile");
+        let view = this as *mut Object;
+        if let Some(this) = Self::get_this(this) {
+            let inner = this.inner.borrow();
+            if let Some(window) = inner.window.as_ref() {
+                let window = window.load();
+                unsafe {
+                    apply_color_space(*window, view, &inner.config);
+                }
+            }
+        }
+        // Repaint so that the colors are matched against the new profile
+        unsafe {
+            let () = msg_send![view, setNeedsDisplay: YES];
+        }
+    }
+
     extern "C" fn did_resize(this: &mut Object, _sel: Sel, _notification: id) {
         if let Some(this) = Self::get_this(this) {
             let inner = this.inner.borrow_mut();
@@ -2054,6 +2297,15 @@ impl WindowView {
                 Self::window_should_close as extern "C" fn(&mut Object, Sel, id) -> BOOL,
             );
 
+            cls.add_method(
+                sel!(draggingEntered:),
+                Self::dragging_entered as extern "C" fn(&mut Object, Sel, id) -> NSUInteger,
+            );
+            cls.add_method(
+                sel!(performDragOperation:),
+                Self::perform_drag_operation as extern "C" fn(&mut Object, Sel, id) -> BOOL,
+            );
+
             cls.add_method(
                 sel!(drawRect:),
                 Self::draw_rect as extern "C" fn(&mut Object, Sel, NSRect),
@@ -2082,6 +2334,10 @@ impl WindowView {
                 sel!(windowDidChangeScreen:),
                 Self::did_change_screen as extern "C" fn(&mut Object, Sel, id),
             );
+            cls.add_method(
+                sel!(windowDidChangeScreenProfile:),
+                Self::did_change_screen_profile as extern "C" fn(&mut Object, Sel, id),
+            );
 
             cls.add_method(
                 sel!(windowDidBecomeKey:),
@@ -2104,6 +2360,10 @@ impl WindowView {
                 sel!(rightMouseDragged:),
                 Self::mouse_moved_or_dragged as extern "C" fn(&mut Object, Sel, id),
             );
+            cls.add_method(
+                sel!(mouseExited:),
+                Self::mouse_exited as extern "C" fn(&mut Object, Sel, id),
+            );
             cls.add_method(
                 sel!(mouseDown:),
                 Self::mouse_down as extern "C" fn(&mut Object, Sel, id),
@@ -2152,6 +2412,11 @@ impl WindowView {
                 Self::accepts_first_mouse as extern "C" fn(&mut Object, Sel, id) -> BOOL,
             );
 
+            cls.add_method(
+                sel!(shouldDelayWindowOrderingForEvent:),
+                Self::should_delay_window_ordering as extern "C" fn(&mut Object, Sel, id) -> BOOL,
+            );
+
             cls.add_method(
                 sel!(viewDidChangeEffectiveAppearance),
                 Self::view_did_change_effective_appearance as extern "C" fn(&mut Object, Sel),
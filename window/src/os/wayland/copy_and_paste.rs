@@ -23,6 +23,8 @@ impl std::fmt::Debug for CopyAndPaste {
 }
 
 pub const TEXT_MIME_TYPE: &str = "text/plain;charset=utf-8";
+/// The type in which the paths of dropped files are received
+pub const URI_LIST_MIME_TYPE: &str = "text/uri-list";
 
 impl CopyAndPaste {
     pub fn create() -> Arc<Mutex<Self>> {
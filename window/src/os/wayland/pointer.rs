@@ -1,7 +1,10 @@
 use super::copy_and_paste::*;
+use super::window::dispatch_dropped_files;
 use crate::os::wayland::connection::WaylandConnection;
+use filedescriptor::Pipe;
 use smithay_client_toolkit as toolkit;
 use std::collections::HashMap;
+use std::os::unix::io::AsRawFd;
 use std::sync::{Arc, Mutex};
 use toolkit::reexports::client::protocol::wl_data_device::{
     Event as DataDeviceEvent, WlDataDevice,
@@ -11,17 +14,27 @@ use toolkit::reexports::client::protocol::wl_pointer::{Axis, ButtonState, Event
 use toolkit::reexports::client::protocol::wl_surface::WlSurface;
 use toolkit::seat::pointer::{ThemeManager, ThemeSpec, ThemedPointer};
 use wayland_client::protocol::wl_compositor::WlCompositor;
-use wayland_client::protocol::wl_data_device_manager::WlDataDeviceManager;
+use wayland_client::protocol::wl_data_device_manager::{DndAction, WlDataDeviceManager};
 use wayland_client::protocol::wl_seat::WlSeat;
 use wayland_client::protocol::wl_shm::WlShm;
 use wayland_client::{Attached, Main};
 use wezterm_input_types::*;
 
+/// A drag from another application that is over one of our surfaces
+struct Drag {
+    offer: WlDataOffer,
+    surface_id: u32,
+}
+
 #[derive(Default)]
 struct Inner {
     active_surface_id: u32,
     surface_to_pending: HashMap<u32, Arc<Mutex<PendingMouse>>>,
     serial: u32,
+    /// The id of the most recent data offer, if it offers a list
+    /// of files
+    uri_list_offer: Option<u32>,
+    drag: Option<Drag>,
 }
 
 impl Inner {
@@ -60,21 +73,82 @@ impl Inner {
         }
     }
 
+    /// Asks the source of `drag` for the list of files that were
+    /// dropped, which is read and delivered to the window that they
+    /// were dropped onto by dispatch_dropped_files
+    fn receive_dropped_files(&mut self, drag: Drag) {
+        let window_id = match self.surface_to_pending.get(&drag.surface_id) {
+            Some(pending) => pending.lock().unwrap().window_id,
+            None => {
+                drag.offer.destroy();
+                return;
+            }
+        };
+        match Pipe::new() {
+            Ok(Pipe { read, write }) => {
+                drag.offer
+                    .receive(URI_LIST_MIME_TYPE.to_string(), write.as_raw_fd());
+                drop(write);
+                if drag.offer.as_ref().version() >= 3 {
+                    drag.offer.finish();
+                }
+                dispatch_dropped_files(window_id, read);
+            }
+            Err(err) => log::error!("failed to create pipe for dropped files: {:#}", err),
+        }
+        drag.offer.destroy();
+    }
+
     fn handle_data_event(&mut self, event: DataDeviceEvent, inner: &Arc<Mutex<Self>>) {
         match event {
             DataDeviceEvent::DataOffer { id } => {
+                self.uri_list_offer.take();
                 id.quick_assign({
                     let inner = Arc::clone(inner);
                     move |offer, event, _dispatch_data| {
                         let mut inner = inner.lock().unwrap();
+                        if let DataOfferEvent::Offer { mime_type } = &event {
+                            if mime_type == URI_LIST_MIME_TYPE {
+                                inner.uri_list_offer.replace(offer.as_ref().id());
+                            }
+                        }
                         inner.route_data_offer(event, offer.detach());
                     }
                 });
             }
-            DataDeviceEvent::Enter { .. }
-            | DataDeviceEvent::Leave { .. }
-            | DataDeviceEvent::Motion { .. }
-            | DataDeviceEvent::Drop => {}
+            DataDeviceEvent::Enter {
+                serial,
+                surface,
+                id,
+                ..
+            } => {
+                self.drag.take();
+                if let Some(offer) = id {
+                    if self.uri_list_offer == Some(offer.as_ref().id()) {
+                        offer.accept(serial, Some(URI_LIST_MIME_TYPE.to_string()));
+                        if offer.as_ref().version() >= 3 {
+                            offer.set_actions(DndAction::Copy, DndAction::Copy);
+                        }
+                        self.drag.replace(Drag {
+                            offer,
+                            surface_id: surface.as_ref().id(),
+                        });
+                    } else {
+                        offer.accept(serial, None);
+                    }
+                }
+            }
+            DataDeviceEvent::Leave { .. } => {
+                if let Some(drag) = self.drag.take() {
+                    drag.offer.destroy();
+                }
+            }
+            DataDeviceEvent::Motion { .. } => {}
+            DataDeviceEvent::Drop => {
+                if let Some(drag) = self.drag.take() {
+                    self.receive_dropped_files(drag);
+                }
+            }
 
             DataDeviceEvent::Selection { id } => {
                 if let Some(offer) = id {
@@ -104,6 +178,7 @@ pub struct PendingMouse {
     surface_coords: Option<(f64, f64)>,
     button: Vec<(MousePress, ButtonState)>,
     scroll: Option<(f64, f64)>,
+    left: bool,
 }
 
 impl PendingMouse {
@@ -114,6 +189,7 @@ impl PendingMouse {
             button: vec![],
             scroll: None,
             surface_coords: None,
+            left: false,
         }))
     }
 
@@ -126,8 +202,14 @@ impl PendingMouse {
                     .lock()
                     .unwrap()
                     .update_last_serial(serial);
+                self.left = false;
                 false
             }
+            PointerEvent::Leave { .. } => {
+                let changed = !self.left;
+                self.left = true;
+                changed
+            }
             PointerEvent::Motion {
                 surface_x,
                 surface_y,
@@ -153,6 +235,8 @@ impl PendingMouse {
                         0x110 => Some(MousePress::Left),
                         0x111 => Some(MousePress::Right),
                         0x112 => Some(MousePress::Middle),
+                        0x113 => Some(MousePress::Back),
+                        0x114 => Some(MousePress::Forward),
                         _ => None,
                     }
                 }
@@ -204,6 +288,10 @@ impl PendingMouse {
     pub fn scroll(pending: &Arc<Mutex<Self>>) -> Option<(f64, f64)> {
         pending.lock().unwrap().scroll.take()
     }
+
+    pub fn left(pending: &Arc<Mutex<Self>>) -> bool {
+        std::mem::take(&mut pending.lock().unwrap().left)
+    }
 }
 
 impl PointerDispatcher {
@@ -4,6 +4,7 @@ use super::pointer::*;
 use crate::connection::ConnectionOps;
 use crate::os::wayland::connection::WaylandConnection;
 use crate::os::x11::keyboard::Keyboard;
+use crate::os::x_and_wayland::paths_from_uri_list;
 use crate::{
     Clipboard, Connection, Dimensions, MouseCursor, Point, ScreenPoint, Window, WindowEvent,
     WindowEventSender, WindowOps, WindowState,
@@ -444,6 +445,8 @@ impl WaylandWindowInner {
                 MousePress::Left => MouseButtons::LEFT,
                 MousePress::Right => MouseButtons::RIGHT,
                 MousePress::Middle => MouseButtons::MIDDLE,
+                MousePress::Back => MouseButtons::X1,
+                MousePress::Forward => MouseButtons::X2,
             };
 
             if state == ButtonState::Pressed {
@@ -501,6 +504,10 @@ impl WaylandWindowInner {
                 self.events.dispatch(WindowEvent::MouseEvent(event));
             }
         }
+
+        if PendingMouse::left(&pending_mouse) {
+            self.events.dispatch(WindowEvent::MouseLeave);
+        }
     }
 
     fn get_dpi_factor(&self) -> i32 {
@@ -899,6 +906,23 @@ fn write_pipe_with_timeout(mut file: FileDescriptor, data: &[u8]) -> anyhow::Res
     Ok(())
 }
 
+/// Reads the `text/uri-list` of the files that were dropped onto
+/// `window_id` from `read` and delivers them to the window
+pub(crate) fn dispatch_dropped_files(window_id: usize, read: FileDescriptor) {
+    std::thread::spawn(move || match read_pipe_with_timeout(read) {
+        Ok(text) => {
+            let paths = paths_from_uri_list(&text);
+            if !paths.is_empty() {
+                WaylandConnection::with_window_inner(window_id, move |inner| {
+                    inner.events.dispatch(WindowEvent::DroppedFile(paths));
+                    Ok(())
+                });
+            }
+        }
+        Err(err) => log::error!("while reading dropped files: {:#}", err),
+    });
+}
+
 fn read_pipe_with_timeout(mut file: FileDescriptor) -> anyhow::Result<String> {
     let mut result = Vec::new();
 
@@ -3,8 +3,8 @@ use crate::connection::ConnectionOps;
 use crate::Appearance;
 use crate::{
     Clipboard, Dimensions, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseCursor, MouseEvent,
-    MouseEventKind, MousePress, Point, Rect, ScreenPoint, WindowDecorations, WindowEvent,
-    WindowEventSender, WindowOps, WindowState,
+    MouseEventKind, MousePress, PhysKeyCode, Point, Rect, ScreenPoint, WindowDecorations,
+    WindowEvent, WindowEventSender, WindowOps, WindowState,
 };
 use anyhow::{bail, Context};
 use async_trait::async_trait;
@@ -21,6 +21,7 @@ use std::convert::TryInto;
 use std::ffi::OsString;
 use std::io::{self, Error as IoError};
 use std::os::windows::ffi::OsStringExt;
+use std::path::PathBuf;
 use std::ptr::{null, null_mut};
 use std::rc::Rc;
 use wezterm_font::FontConfiguration;
@@ -29,6 +30,7 @@ use winapi::shared::ntdef::*;
 use winapi::shared::windef::*;
 use winapi::um::imm::*;
 use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::shellapi::{DragAcceptFiles, DragFinish, DragQueryFileW, HDROP};
 use winapi::um::winuser::*;
 use winreg::{enums::HKEY_CURRENT_USER, RegKey};
 
@@ -374,6 +376,10 @@ impl Window {
         // completely stick
         schedule_apply_decoration(hwnd, decorations);
 
+        unsafe {
+            DragAcceptFiles(hwnd, TRUE);
+        }
+
         Ok(hwnd)
     }
 
@@ -441,6 +447,20 @@ impl Window {
     }
 }
 
+fn schedule_focus_window(hwnd: HWindow) {
+    promise::spawn::spawn(async move {
+        unsafe {
+            if IsIconic(hwnd.0) != 0 {
+                ShowWindow(hwnd.0, SW_RESTORE);
+            } else {
+                ShowWindow(hwnd.0, SW_NORMAL);
+            }
+            SetForegroundWindow(hwnd.0);
+        }
+    })
+    .detach();
+}
+
 fn schedule_show_window(hwnd: HWindow, show: bool) {
     // ShowWindow can call to the window proc and may attempt
     // to lock inner, so we avoid locking it ourselves here
@@ -616,6 +636,10 @@ impl WindowOps for Window {
         schedule_show_window(self.0, false);
     }
 
+    fn focus(&self) {
+        schedule_focus_window(self.0);
+    }
+
     fn set_cursor(&self, cursor: Option<MouseCursor>) {
         Connection::with_window_inner(self.0, move |inner| {
             inner.set_cursor(cursor);
@@ -922,6 +946,27 @@ unsafe fn wm_kill_focus(
     None
 }
 
+unsafe fn wm_dropfiles(hwnd: HWND, _msg: UINT, wparam: WPARAM, _lparam: LPARAM) -> Option<LRESULT> {
+    let hdrop = wparam as HDROP;
+    let count = DragQueryFileW(hdrop, 0xFFFF_FFFF, null_mut(), 0);
+    let mut paths = vec![];
+    for idx in 0..count {
+        let len = DragQueryFileW(hdrop, idx, null_mut(), 0) as usize;
+        let mut buf = vec![0u16; len + 1];
+        DragQueryFileW(hdrop, idx, buf.as_mut_ptr(), buf.len() as u32);
+        paths.push(PathBuf::from(OsString::from_wide(&buf[..len])));
+    }
+    DragFinish(hdrop);
+
+    if let Some(inner) = rc_from_hwnd(hwnd) {
+        inner
+            .borrow_mut()
+            .events
+            .dispatch(WindowEvent::DroppedFile(paths));
+    }
+    Some(0)
+}
+
 unsafe fn wm_paint(hwnd: HWND, _msg: UINT, _wparam: WPARAM, _lparam: LPARAM) -> Option<LRESULT> {
     if let Some(inner) = rc_from_hwnd(hwnd) {
         let mut inner = inner.borrow_mut();
@@ -970,7 +1015,12 @@ fn mods_and_buttons(wparam: WPARAM) -> (Modifiers, MouseButtons) {
     if wparam & MK_RBUTTON != 0 {
         buttons |= MouseButtons::RIGHT;
     }
-    // TODO: XBUTTON1 and XBUTTON2?
+    if wparam & MK_XBUTTON1 != 0 {
+        buttons |= MouseButtons::X1;
+    }
+    if wparam & MK_XBUTTON2 != 0 {
+        buttons |= MouseButtons::X2;
+    }
     (modifiers, buttons)
 }
 
@@ -1041,6 +1091,18 @@ unsafe fn mouse_button(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
                 WM_RBUTTONUP => MouseEventKind::Release(MousePress::Right),
                 WM_MBUTTONDOWN => MouseEventKind::Press(MousePress::Middle),
                 WM_MBUTTONUP => MouseEventKind::Release(MousePress::Middle),
+                WM_XBUTTONDOWN | WM_XBUTTONUP => {
+                    let button = match GET_XBUTTON_WPARAM(wparam) {
+                        XBUTTON1 => MousePress::Back,
+                        XBUTTON2 => MousePress::Forward,
+                        _ => return None,
+                    };
+                    if msg == WM_XBUTTONDOWN {
+                        MouseEventKind::Press(button)
+                    } else {
+                        MouseEventKind::Release(button)
+                    }
+                }
                 _ => return None,
             },
             coords,
@@ -1052,7 +1114,12 @@ unsafe fn mouse_button(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
             .borrow_mut()
             .events
             .dispatch(WindowEvent::MouseEvent(event));
-        Some(0)
+        // The XBUTTON messages must return TRUE when processed
+        if msg == WM_XBUTTONDOWN || msg == WM_XBUTTONUP {
+            Some(1)
+        } else {
+            Some(0)
+        }
     } else {
         None
     }
@@ -1070,6 +1137,15 @@ unsafe fn mouse_move(hwnd: HWND, _msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
             modifiers,
         };
 
+        // Ask for WM_MOUSELEAVE when the mouse leaves the window
+        let mut track = TRACKMOUSEEVENT {
+            cbSize: std::mem::size_of::<TRACKMOUSEEVENT>() as u32,
+            dwFlags: TME_LEAVE,
+            hwndTrack: hwnd,
+            dwHoverTime: 0,
+        };
+        TrackMouseEvent(&mut track);
+
         inner
             .borrow_mut()
             .events
@@ -1080,6 +1156,15 @@ unsafe fn mouse_move(hwnd: HWND, _msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
     }
 }
 
+unsafe fn mouse_leave(hwnd: HWND, _msg: UINT, _wparam: WPARAM, _lparam: LPARAM) -> Option<LRESULT> {
+    if let Some(inner) = rc_from_hwnd(hwnd) {
+        inner.borrow_mut().events.dispatch(WindowEvent::MouseLeave);
+        Some(0)
+    } else {
+        None
+    }
+}
+
 lazy_static! {
     static ref WHEEL_SCROLL_LINES: i16 = read_scroll_speed("WheelScrollLines").unwrap_or(3);
     static ref WHEEL_SCROLL_CHARS: i16 = read_scroll_speed("WheelScrollChars").unwrap_or(3);
@@ -1121,7 +1206,8 @@ unsafe fn mouse_wheel(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
                     inner.hscroll_remainder,
                     position
                 );
-                MouseEventKind::HorzWheel(position)
+                // WM_MOUSEHWHEEL is positive when tilting to the right
+                MouseEventKind::HorzWheel(-position)
             } else {
                 let mut inner = inner.borrow_mut();
                 inner.vscroll_remainder += remainder;
@@ -1229,6 +1315,7 @@ unsafe fn ime_composition(
                         raw_key: None,
                         raw_modifiers: Modifiers::NONE,
                         raw_code: None,
+                        phys_code: None,
                         modifiers: Modifiers::NONE,
                         repeat_count: 1,
                         key_is_down: true,
@@ -1554,6 +1641,12 @@ unsafe fn key(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<L
         let alt_pressed = (lparam & (1 << 29)) != 0;
         let is_extended = (lparam & (1 << 24)) != 0;
         let was_down = (lparam & (1 << 30)) != 0;
+        // None of the layout dependent keys have extended scan codes
+        let phys_code = if is_extended {
+            None
+        } else {
+            PhysKeyCode::from_scan_code(scan_code as u32)
+        };
         let label = match msg {
             WM_CHAR => "WM_CHAR",
             WM_IME_CHAR => "WM_IME_CHAR",
@@ -1784,6 +1877,7 @@ unsafe fn key(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<L
                                 raw_key: None,
                                 raw_modifiers: Modifiers::NONE,
                                 raw_code: Some(wparam as u32),
+                                phys_code,
                                 modifiers,
                                 repeat_count: 1,
                                 key_is_down: !releasing,
@@ -1886,6 +1980,7 @@ unsafe fn key(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<L
                 raw_key: if is_composed { raw } else { None },
                 raw_modifiers,
                 raw_code: Some(wparam as u32),
+                phys_code,
                 modifiers,
                 repeat_count: repeat,
                 key_is_down: !releasing,
@@ -1917,6 +2012,7 @@ unsafe fn do_wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
         WM_WINDOWPOSCHANGED => wm_windowposchanged(hwnd, msg, wparam, lparam),
         WM_SETFOCUS => wm_set_focus(hwnd, msg, wparam, lparam),
         WM_KILLFOCUS => wm_kill_focus(hwnd, msg, wparam, lparam),
+        WM_DROPFILES => wm_dropfiles(hwnd, msg, wparam, lparam),
         WM_DEADCHAR | WM_KEYDOWN | WM_KEYUP | WM_SYSCHAR | WM_CHAR | WM_IME_CHAR | WM_SYSKEYUP
         | WM_SYSKEYDOWN => key(hwnd, msg, wparam, lparam),
         WM_SIZING => {
@@ -1927,11 +2023,11 @@ unsafe fn do_wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
         WM_SETTINGCHANGE => apply_theme(hwnd),
         WM_IME_COMPOSITION => ime_composition(hwnd, msg, wparam, lparam),
         WM_MOUSEMOVE => mouse_move(hwnd, msg, wparam, lparam),
+        WM_MOUSELEAVE => mouse_leave(hwnd, msg, wparam, lparam),
         WM_MOUSEHWHEEL | WM_MOUSEWHEEL => mouse_wheel(hwnd, msg, wparam, lparam),
         WM_LBUTTONDBLCLK | WM_RBUTTONDBLCLK | WM_MBUTTONDBLCLK | WM_LBUTTONDOWN | WM_LBUTTONUP
-        | WM_RBUTTONDOWN | WM_RBUTTONUP | WM_MBUTTONDOWN | WM_MBUTTONUP => {
-            mouse_button(hwnd, msg, wparam, lparam)
-        }
+        | WM_RBUTTONDOWN | WM_RBUTTONUP | WM_MBUTTONDOWN | WM_MBUTTONUP | WM_XBUTTONDOWN
+        | WM_XBUTTONUP => mouse_button(hwnd, msg, wparam, lparam),
         WM_ERASEBKGND => Some(1),
         WM_CLOSE => {
             if let Some(inner) = rc_from_hwnd(hwnd) {
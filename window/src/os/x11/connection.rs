@@ -39,6 +39,7 @@ pub struct XConnection {
     pub atom_state_hidden: xcb::Atom,
     pub atom_state_fullscreen: xcb::Atom,
     pub atom_net_wm_state: xcb::Atom,
+    pub atom_xdnd: XdndAtoms,
     keysyms: *mut xcb_key_symbols_t,
     pub(crate) xrm: RefCell<HashMap<String, String>>,
     pub(crate) windows: RefCell<HashMap<xcb::xproto::Window, Arc<Mutex<XWindowInner>>>>,
@@ -50,6 +51,43 @@ pub struct XConnection {
     pub(crate) ime_process_event_result: RefCell<anyhow::Result<()>>,
 }
 
+/// The atoms used by the XDND protocol, through which files
+/// are dragged from other applications and dropped onto our windows
+pub struct XdndAtoms {
+    pub aware: xcb::Atom,
+    pub enter: xcb::Atom,
+    pub position: xcb::Atom,
+    pub status: xcb::Atom,
+    pub leave: xcb::Atom,
+    pub drop: xcb::Atom,
+    pub finished: xcb::Atom,
+    pub selection: xcb::Atom,
+    pub type_list: xcb::Atom,
+    pub action_copy: xcb::Atom,
+    pub uri_list: xcb::Atom,
+}
+
+impl XdndAtoms {
+    fn intern(conn: &xcb::Connection) -> anyhow::Result<Self> {
+        let intern = |name: &str| -> anyhow::Result<xcb::Atom> {
+            Ok(xcb::intern_atom(conn, false, name).get_reply()?.atom())
+        };
+        Ok(Self {
+            aware: intern("XdndAware")?,
+            enter: intern("XdndEnter")?,
+            position: intern("XdndPosition")?,
+            status: intern("XdndStatus")?,
+            leave: intern("XdndLeave")?,
+            drop: intern("XdndDrop")?,
+            finished: intern("XdndFinished")?,
+            selection: intern("XdndSelection")?,
+            type_list: intern("XdndTypeList")?,
+            action_copy: intern("XdndActionCopy")?,
+            uri_list: intern("text/uri-list")?,
+        })
+    }
+}
+
 impl std::ops::Deref for XConnection {
     type Target = xcb::Connection;
 
@@ -102,6 +140,10 @@ fn window_id_from_event(event: &xcb::GenericEvent) -> Option<xcb::xproto::Window
             let motion: &xcb::MotionNotifyEvent = unsafe { xcb::cast_event(event) };
             Some(motion.event())
         }
+        xcb::LEAVE_NOTIFY => {
+            let leave: &xcb::LeaveNotifyEvent = unsafe { xcb::cast_event(event) };
+            Some(leave.event())
+        }
         xcb::BUTTON_PRESS | xcb::BUTTON_RELEASE => {
             let button_press: &xcb::ButtonPressEvent = unsafe { xcb::cast_event(event) };
             Some(button_press.event())
@@ -409,6 +451,7 @@ impl XConnection {
         let atom_net_wm_state = xcb::intern_atom(&conn, false, "_NET_WM_STATE")
             .get_reply()?
             .atom();
+        let atom_xdnd = XdndAtoms::intern(&conn)?;
 
         let keysyms = unsafe { xcb_key_symbols_alloc((*conn).get_raw_conn()) };
 
@@ -501,6 +544,7 @@ impl XConnection {
             atom_state_hidden,
             atom_state_fullscreen,
             atom_net_wm_state,
+            atom_xdnd,
             keysyms,
             keyboard,
             kbd_ev,
@@ -1,5 +1,5 @@
 use crate::os::xkeysyms::keysym_to_keycode;
-use crate::{KeyEvent, Modifiers};
+use crate::{KeyEvent, Modifiers, PhysKeyCode};
 use anyhow::{anyhow, ensure};
 use libc;
 use std::cell::RefCell;
@@ -137,17 +137,25 @@ impl Keyboard {
     }
 
     pub fn process_wayland_key(&self, code: u32, pressed: bool) -> Option<KeyEvent> {
-        self.process_key_event_impl(code + 8, pressed)
+        // Wayland reports evdev key codes, which xkb offsets by 8
+        self.process_key_event_impl(code + 8, PhysKeyCode::from_scan_code(code), pressed)
     }
 
     pub fn process_key_event(&self, xcb_ev: &xcb::KeyPressEvent) -> Option<KeyEvent> {
         let pressed = (xcb_ev.response_type() & !0x80) == xcb::KEY_PRESS;
 
         let xcode = xkb::Keycode::from(xcb_ev.detail());
-        self.process_key_event_impl(xcode, pressed)
+        // xkb keycodes are evdev key codes offset by 8
+        let phys_code = PhysKeyCode::from_scan_code(xcode.saturating_sub(8));
+        self.process_key_event_impl(xcode, phys_code, pressed)
     }
 
-    fn process_key_event_impl(&self, xcode: xkb::Keycode, pressed: bool) -> Option<KeyEvent> {
+    fn process_key_event_impl(
+        &self,
+        xcode: xkb::Keycode,
+        phys_code: Option<PhysKeyCode>,
+        pressed: bool,
+    ) -> Option<KeyEvent> {
         let xsym = self.state.borrow().key_get_one_sym(xcode);
 
         let ksym = if pressed {
@@ -197,6 +205,7 @@ impl Keyboard {
             raw_key: None,
             raw_modifiers,
             raw_code: Some(xcode),
+            phys_code,
             repeat_count: 1,
             key_is_down: pressed,
         })
@@ -1,6 +1,7 @@
 use super::*;
 use crate::bitmaps::*;
 use crate::connection::ConnectionOps;
+use crate::os::x_and_wayland::paths_from_uri_list;
 use crate::os::xkeysyms;
 use crate::os::{Connection, Window};
 use crate::{
@@ -54,6 +55,14 @@ impl CopyAndPaste {
     }
 }
 
+/// A drag from another application that is over the window
+struct XdndDrag {
+    source: xcb::xproto::Window,
+    /// Whether the source offers a list of files, which is the
+    /// only kind of data that we accept
+    accept: bool,
+}
+
 pub(crate) struct XWindowInner {
     window_id: xcb::xproto::Window,
     conn: Weak<XConnection>,
@@ -71,6 +80,7 @@ pub(crate) struct XWindowInner {
     invalidated: bool,
     paint_throttled: bool,
     pending: Vec<WindowEvent>,
+    drag: Option<XdndDrag>,
 }
 
 impl Drop for XWindowInner {
@@ -293,16 +303,26 @@ impl XWindowInner {
                 };
                 self.do_mouse_event(event)?;
             }
+            xcb::LEAVE_NOTIFY => {
+                let leave: &xcb::LeaveNotifyEvent = unsafe { xcb::cast_event(event) };
+                // Grabs, such as while a button is held, also
+                // generate leave events, without the pointer moving
+                if leave.mode() == xcb::NOTIFY_MODE_NORMAL as u8 {
+                    self.events.dispatch(WindowEvent::MouseLeave);
+                }
+            }
             xcb::BUTTON_PRESS | xcb::BUTTON_RELEASE => {
                 let button_press: &xcb::ButtonPressEvent = unsafe { xcb::cast_event(event) };
                 self.copy_and_paste.time = button_press.time();
 
                 let kind = match button_press.detail() {
-                    b @ 1..=3 => {
+                    b @ 1..=3 | b @ 8..=9 => {
                         let button = match b {
                             1 => MousePress::Left,
                             2 => MousePress::Middle,
                             3 => MousePress::Right,
+                            8 => MousePress::Back,
+                            9 => MousePress::Forward,
                             _ => unreachable!(),
                         };
                         if r == xcb::BUTTON_PRESS {
@@ -327,6 +347,13 @@ impl XWindowInner {
                             -LINES_PER_TICK
                         })
                     }
+                    b @ 6..=7 => {
+                        if r == xcb::BUTTON_RELEASE {
+                            return Ok(());
+                        }
+                        // Button 6 scrolls left, 7 scrolls right
+                        MouseEventKind::HorzWheel(if b == 6 { 1 } else { -1 })
+                    }
                     _ => {
                         eprintln!("button {} is not implemented", button_press.detail());
                         return Ok(());
@@ -353,6 +380,8 @@ impl XWindowInner {
 
                 if msg.data().data32()[0] == conn.atom_delete() {
                     self.events.dispatch(WindowEvent::CloseRequested);
+                } else {
+                    self.xdnd_client_message(msg);
                 }
             }
             xcb::DESTROY_NOTIFY => {
@@ -366,7 +395,12 @@ impl XWindowInner {
                 self.selection_request(unsafe { xcb::cast_event(event) })?;
             }
             xcb::SELECTION_NOTIFY => {
-                self.selection_notify(unsafe { xcb::cast_event(event) })?;
+                let selection: &xcb::SelectionNotifyEvent = unsafe { xcb::cast_event(event) };
+                if selection.selection() == conn.atom_xdnd.selection {
+                    self.xdnd_selection_notify(selection);
+                } else {
+                    self.selection_notify(selection)?;
+                }
             }
             xcb::PROPERTY_NOTIFY => {
                 let msg: &xcb::PropertyNotifyEvent = unsafe { xcb::cast_event(event) };
@@ -428,6 +462,7 @@ impl XWindowInner {
             raw_key: None,
             raw_modifiers: Modifiers::NONE,
             raw_code: None,
+            phys_code: None,
             modifiers: Modifiers::NONE,
             repeat_count: 1,
             key_is_down: true,
@@ -606,6 +641,140 @@ impl XWindowInner {
         Ok(())
     }
 
+    /// Handles the XDND messages that are sent to the window while
+    /// something is dragged over it by another application
+    fn xdnd_client_message(&mut self, msg: &xcb::ClientMessageEvent) {
+        let conn = self.conn();
+        let xdnd = &conn.atom_xdnd;
+        let data = msg.data().data32();
+        let source = data[0];
+
+        if msg.type_() == xdnd.enter {
+            let types = if data[1] & 1 != 0 {
+                // More than three types are offered, so they are
+                // listed in a property of the source window instead
+                match xcb::xproto::get_property(
+                    &conn,
+                    false,
+                    source,
+                    xdnd.type_list,
+                    xcb::xproto::ATOM_ATOM,
+                    0,
+                    1024,
+                )
+                .get_reply()
+                {
+                    Ok(reply) => reply.value::<u32>().to_vec(),
+                    Err(err) => {
+                        log::error!("XdndEnter: failed to read XdndTypeList: {:?}", err);
+                        vec![]
+                    }
+                }
+            } else {
+                data[2..5].to_vec()
+            };
+            self.drag.replace(XdndDrag {
+                source,
+                accept: types.contains(&xdnd.uri_list),
+            });
+        } else if msg.type_() == xdnd.position {
+            let accept = matches!(&self.drag, Some(drag) if drag.source == source && drag.accept);
+            let action = if accept { xdnd.action_copy } else { xcb::NONE };
+            // The empty rectangle asks the source to keep sending
+            // positions while the pointer moves over the window
+            self.send_xdnd_message(
+                source,
+                xdnd.status,
+                [self.window_id, accept as u32, 0, 0, action],
+            );
+        } else if msg.type_() == xdnd.leave {
+            self.drag.take();
+        } else if msg.type_() == xdnd.drop {
+            let accept = matches!(&self.drag, Some(drag) if drag.source == source && drag.accept);
+            if accept {
+                // The drop completes once the list of files
+                // arrives in xdnd_selection_notify
+                xcb::convert_selection(
+                    &conn,
+                    self.window_id,
+                    xdnd.selection,
+                    xdnd.uri_list,
+                    xdnd.selection,
+                    data[2],
+                );
+            } else {
+                self.drag.take();
+                self.send_xdnd_message(source, xdnd.finished, [self.window_id, 0, 0, 0, 0]);
+            }
+        }
+    }
+
+    /// Receives the list of files that were dropped onto the window
+    /// and tells the source that the drop is finished
+    fn xdnd_selection_notify(&mut self, selection: &xcb::SelectionNotifyEvent) {
+        let conn = self.conn();
+        let drag = match self.drag.take() {
+            Some(drag) => drag,
+            None => return,
+        };
+
+        let mut paths = vec![];
+        if selection.property() != xcb::NONE {
+            match xcb::xproto::get_property(
+                &conn,
+                true,
+                self.window_id,
+                selection.property(),
+                xcb::xproto::ATOM_ANY,
+                0,
+                u32::max_value() / 4,
+            )
+            .get_reply()
+            {
+                Ok(reply) => {
+                    paths = paths_from_uri_list(&String::from_utf8_lossy(reply.value::<u8>()));
+                }
+                Err(err) => log::error!("XdndDrop: failed to read the dropped files: {:?}", err),
+            }
+        }
+
+        let accepted = !paths.is_empty();
+        let action = if accepted {
+            conn.atom_xdnd.action_copy
+        } else {
+            xcb::NONE
+        };
+        self.send_xdnd_message(
+            drag.source,
+            conn.atom_xdnd.finished,
+            [self.window_id, accepted as u32, action, 0, 0],
+        );
+        if accepted {
+            self.events.dispatch(WindowEvent::DroppedFile(paths));
+        }
+    }
+
+    fn send_xdnd_message(
+        &self,
+        target: xcb::xproto::Window,
+        message_type: xcb::Atom,
+        data: [u32; 5],
+    ) {
+        let conn = self.conn();
+        xcb::xproto::send_event(
+            &conn,
+            false,
+            target,
+            xcb::xproto::EVENT_MASK_NO_EVENT,
+            &xcb::xproto::ClientMessageEvent::new(
+                32,
+                target,
+                message_type,
+                xcb::ClientMessageData::from_data32(data),
+            ),
+        );
+    }
+
     fn get_window_state(&self) -> anyhow::Result<WindowState> {
         let conn = self.conn();
 
@@ -818,6 +987,7 @@ impl XWindow {
                             | xcb::EVENT_MASK_BUTTON_RELEASE
                             | xcb::EVENT_MASK_POINTER_MOTION
                             | xcb::EVENT_MASK_BUTTON_MOTION
+                            | xcb::EVENT_MASK_LEAVE_WINDOW
                             | xcb::EVENT_MASK_KEY_RELEASE
                             | xcb::EVENT_MASK_PROPERTY_CHANGE
                             | xcb::EVENT_MASK_STRUCTURE_NOTIFY,
@@ -853,6 +1023,7 @@ impl XWindow {
                 paint_throttled: false,
                 invalidated: false,
                 pending: vec![],
+                drag: None,
             }))
         };
 
@@ -871,6 +1042,18 @@ impl XWindow {
             &[conn.atom_delete],
         );
 
+        // Accept files that are dropped onto the window, using
+        // version 5 of the XDND protocol
+        xcb::change_property(
+            &*conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            window_id,
+            conn.atom_xdnd.aware,
+            xcb::xproto::ATOM_ATOM,
+            32,
+            &[5u32],
+        );
+
         window
             .lock()
             .unwrap()
@@ -901,11 +1084,52 @@ impl XWindowInner {
         xcb::map_window(self.conn().conn(), self.window_id);
     }
 
+    fn focus(&mut self) -> anyhow::Result<()> {
+        self.show();
+        let conn = self.conn();
+
+        let net_active_window = xcb::intern_atom(conn.conn(), false, "_NET_ACTIVE_WINDOW")
+            .get_reply()?
+            .atom();
+
+        // Source indication 1 means that the request comes from a
+        // regular application, rather than a pager
+        let data: [u32; 5] = [1, xcb::CURRENT_TIME, 0, 0, 0];
+
+        // Ask window manager to raise and focus the window
+        xcb::xproto::send_event(
+            &conn,
+            true,
+            conn.root,
+            xcb::xproto::EVENT_MASK_SUBSTRUCTURE_REDIRECT
+                | xcb::xproto::EVENT_MASK_SUBSTRUCTURE_NOTIFY,
+            &xcb::xproto::ClientMessageEvent::new(
+                32,
+                self.window_id,
+                net_active_window,
+                xcb::ClientMessageData::from_data32(data),
+            ),
+        );
+
+        Ok(())
+    }
+
     fn invalidate(&mut self) {
         self.queue_pending(WindowEvent::NeedRepaint);
         self.dispatch_pending_events().ok();
     }
 
+    fn invalidate_immediately(&mut self) {
+        if !self.paint_throttled {
+            self.invalidate();
+            return;
+        }
+        // Paint now; the throttle timer is left running so that
+        // the frame rate still governs any subsequent invalidations
+        self.invalidated = false;
+        self.events.dispatch(WindowEvent::NeedRepaint);
+    }
+
     fn toggle_fullscreen(&mut self) {
         let fullscreen = match self.get_window_state() {
             Ok(f) => f.contains(WindowState::FULL_SCREEN),
@@ -1093,6 +1317,10 @@ impl WindowOps for XWindow {
         });
     }
 
+    fn focus(&self) {
+        XConnection::with_window_inner(self.0, |inner| inner.focus());
+    }
+
     fn set_cursor(&self, cursor: Option<MouseCursor>) {
         XConnection::with_window_inner(self.0, move |inner| {
             let _ = inner.set_cursor(cursor);
@@ -1107,6 +1335,13 @@ impl WindowOps for XWindow {
         });
     }
 
+    fn invalidate_immediately(&self) {
+        XConnection::with_window_inner(self.0, |inner| {
+            inner.invalidate_immediately();
+            Ok(())
+        });
+    }
+
     fn set_title(&self, title: &str) {
         let title = title.to_owned();
         XConnection::with_window_inner(self.0, move |inner| {
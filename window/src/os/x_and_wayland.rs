@@ -13,6 +13,9 @@ use config::ConfigHandle;
 use promise::*;
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 use std::any::Any;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+use std::path::PathBuf;
 use std::rc::Rc;
 use wezterm_font::FontConfiguration;
 
@@ -253,6 +256,14 @@ impl WindowOps for Window {
         }
     }
 
+    fn focus(&self) {
+        match self {
+            Self::X11(x) => x.focus(),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.focus(),
+        }
+    }
+
     fn set_cursor(&self, cursor: Option<MouseCursor>) {
         match self {
             Self::X11(x) => x.set_cursor(cursor),
@@ -269,6 +280,14 @@ impl WindowOps for Window {
         }
     }
 
+    fn invalidate_immediately(&self) {
+        match self {
+            Self::X11(x) => x.invalidate_immediately(),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.invalidate_immediately(),
+        }
+    }
+
     fn set_resize_increments(&self, x: u16, y: u16) {
         match self {
             Self::X11(x11) => x11.set_resize_increments(x, y),
@@ -338,3 +357,40 @@ impl WindowOps for Window {
         }
     }
 }
+
+/// Returns the local paths of the `file://` URIs in `text`, which is
+/// a `text/uri-list`: the form in which both X11 and Wayland deliver
+/// the files that are dropped onto a window
+pub(crate) fn paths_from_uri_list(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|uri| {
+            let path = uri.strip_prefix("file://")?;
+            // Skip the host, which is usually empty or localhost
+            let path = &path[path.find('/')?..];
+            Some(PathBuf::from(OsString::from_vec(percent_decode(path))))
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> Vec<u8> {
+    let mut decoded = vec![];
+    let mut idx = 0;
+    while idx < text.len() {
+        let byte = text.as_bytes()[idx];
+        if byte == b'%' {
+            if let Some(value) = text
+                .get(idx + 1..idx + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                decoded.push(value);
+                idx += 3;
+                continue;
+            }
+        }
+        decoded.push(byte);
+        idx += 1;
+    }
+    decoded
+}